@@ -1,4 +1,6 @@
-use meshql_core::{GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{
+    DeleteMode, GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig,
+};
 use meshql_ksql::{ConfluentClient, KsqlConfig, KsqlRepository, KsqlSearcher};
 use std::sync::Arc;
 
@@ -346,28 +348,33 @@ async fn main() -> Result<(), lambda_http::Error> {
                 path: "/farm/api".to_string(),
                 schema_json: serde_json::from_str(FARM_JSON).expect("invalid farm JSON schema"),
                 repository: farm_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".to_string(),
                 schema_json: serde_json::from_str(COOP_JSON).expect("invalid coop JSON schema"),
                 repository: coop_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".to_string(),
                 schema_json: serde_json::from_str(HEN_JSON).expect("invalid hen JSON schema"),
                 repository: hen_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container/api".to_string(),
                 schema_json: serde_json::from_str(CONTAINER_JSON)
                     .expect("invalid container JSON schema"),
                 repository: container_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumer/api".to_string(),
                 schema_json: serde_json::from_str(CONSUMER_JSON)
                     .expect("invalid consumer JSON schema"),
                 repository: consumer_repo,
+                delete_mode: DeleteMode::Soft,
             },
             // Events (5)
             RestletteConfig {
@@ -375,30 +382,35 @@ async fn main() -> Result<(), lambda_http::Error> {
                 schema_json: serde_json::from_str(LAY_REPORT_JSON)
                     .expect("invalid lay_report JSON schema"),
                 repository: lay_report_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_deposit/api".to_string(),
                 schema_json: serde_json::from_str(STORAGE_DEPOSIT_JSON)
                     .expect("invalid storage_deposit JSON schema"),
                 repository: storage_deposit_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_withdrawal/api".to_string(),
                 schema_json: serde_json::from_str(STORAGE_WITHDRAWAL_JSON)
                     .expect("invalid storage_withdrawal JSON schema"),
                 repository: storage_withdrawal_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_transfer/api".to_string(),
                 schema_json: serde_json::from_str(CONTAINER_TRANSFER_JSON)
                     .expect("invalid container_transfer JSON schema"),
                 repository: container_transfer_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumption_report/api".to_string(),
                 schema_json: serde_json::from_str(CONSUMPTION_REPORT_JSON)
                     .expect("invalid consumption_report JSON schema"),
                 repository: consumption_report_repo,
+                delete_mode: DeleteMode::Soft,
             },
             // Projections (3)
             RestletteConfig {
@@ -406,20 +418,25 @@ async fn main() -> Result<(), lambda_http::Error> {
                 schema_json: serde_json::from_str(CONTAINER_INVENTORY_JSON)
                     .expect("invalid container_inventory JSON schema"),
                 repository: container_inventory_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen_productivity/api".to_string(),
                 schema_json: serde_json::from_str(HEN_PRODUCTIVITY_JSON)
                     .expect("invalid hen_productivity JSON schema"),
                 repository: hen_productivity_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/farm_output/api".to_string(),
                 schema_json: serde_json::from_str(FARM_OUTPUT_JSON)
                     .expect("invalid farm_output JSON schema"),
                 repository: farm_output_repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     meshql_lambda::run_lambda(config).await
@@ -1,4 +1,6 @@
-use meshql_core::{GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{
+    DeleteMode, GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig,
+};
 use meshql_mongo::{MongoRepository, MongoSearcher};
 use meshql_server::run;
 use std::sync::Arc;
@@ -423,68 +425,83 @@ async fn main() -> anyhow::Result<()> {
                 path: "/farm/api".to_string(),
                 schema_json: serde_json::from_str(FARM_JSON_SCHEMA)?,
                 repository: farm_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".to_string(),
                 schema_json: serde_json::from_str(COOP_JSON_SCHEMA)?,
                 repository: coop_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".to_string(),
                 schema_json: serde_json::from_str(HEN_JSON_SCHEMA)?,
                 repository: hen_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container/api".to_string(),
                 schema_json: serde_json::from_str(CONTAINER_JSON_SCHEMA)?,
                 repository: container_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumer/api".to_string(),
                 schema_json: serde_json::from_str(CONSUMER_JSON_SCHEMA)?,
                 repository: consumer_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/lay_report/api".to_string(),
                 schema_json: serde_json::from_str(LAY_REPORT_JSON_SCHEMA)?,
                 repository: lay_report_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_deposit/api".to_string(),
                 schema_json: serde_json::from_str(STORAGE_DEPOSIT_JSON_SCHEMA)?,
                 repository: storage_deposit_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_withdrawal/api".to_string(),
                 schema_json: serde_json::from_str(STORAGE_WITHDRAWAL_JSON_SCHEMA)?,
                 repository: storage_withdrawal_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_transfer/api".to_string(),
                 schema_json: serde_json::from_str(CONTAINER_TRANSFER_JSON_SCHEMA)?,
                 repository: container_transfer_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumption_report/api".to_string(),
                 schema_json: serde_json::from_str(CONSUMPTION_REPORT_JSON_SCHEMA)?,
                 repository: consumption_report_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_inventory/api".to_string(),
                 schema_json: serde_json::from_str(CONTAINER_INVENTORY_JSON_SCHEMA)?,
                 repository: container_inventory_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen_productivity/api".to_string(),
                 schema_json: serde_json::from_str(HEN_PRODUCTIVITY_JSON_SCHEMA)?,
                 repository: hen_productivity_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/farm_output/api".to_string(),
                 schema_json: serde_json::from_str(FARM_OUTPUT_JSON_SCHEMA)?,
                 repository: farm_output_repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     run(config).await
@@ -1,5 +1,5 @@
 use merkql::broker::{Broker, BrokerConfig};
-use meshql_core::{GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{DeleteMode, GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
 use meshql_merkql::{MerkqlRepository, MerkqlSearcher};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -117,24 +117,30 @@ async fn main() -> anyhow::Result<()> {
                 path: "/farm/api".to_string(),
                 schema_json: serde_json::from_str(FARM_JSON).expect("invalid farm JSON schema"),
                 repository: farm_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".to_string(),
                 schema_json: serde_json::from_str(COOP_JSON).expect("invalid coop JSON schema"),
                 repository: coop_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".to_string(),
                 schema_json: serde_json::from_str(HEN_JSON).expect("invalid hen JSON schema"),
                 repository: hen_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/lay_report/api".to_string(),
                 schema_json: serde_json::from_str(LAY_REPORT_JSON)
                     .expect("invalid lay_report JSON schema"),
                 repository: lay_report_repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     meshql_server::run(config).await
@@ -0,0 +1,40 @@
+//! A [`meshql_core::Repository`] over Cassandra or ScyllaDB, for append-heavy
+//! entities such as event streams.
+//!
+//! Every version of an envelope is its own row in the versions table,
+//! partitioned by `id` and clustered by `created_at_ms DESC, version DESC`,
+//! where `version` is a ULID so versions written in the same millisecond each
+//! keep their own row and sort in write order. Reading an id as of a time is
+//! then one `WHERE id = ? AND created_at_ms <= ? LIMIT 1` that takes the first
+//! row in clustering order; its history is the same partition read in
+//! ascending order.
+//!
+//! The payload is stored as JSON text, `deleted` as a boolean, and the
+//! authorized tokens as a `set<text>`.
+//!
+//! `list` can't afford to read every version of every id, and CQL has no way
+//! to ask for the newest row of each partition. The usual answers both fall short:
+//!
+//! - A secondary index on `deleted` is low-cardinality and still returns every
+//!   version, so each `list` fans out to every node and filters most rows away.
+//! - A materialized view can re-key rows but not keep only the newest per id,
+//!   and views are experimental and off by default since Cassandra 4.
+//!
+//! Instead each write also upserts the version into a `<table>_latest` table
+//! keyed by `id` alone, in the same logged batch. That upsert carries
+//! `USING TIMESTAMP` of the version's `created_at`, so a write that is older
+//! than the stored row loses to it, and back-dated or out-of-order writes
+//! leave the newest version in place. `list` scans that table, one row per id
+//! rather than per version, and drops tombstones and rows the caller can't see.
+//! CQL can't `OR` together `CONTAINS` filters, so tokens are checked client-side.
+//!
+//! The price is a second write per version and a logged batch to keep the two
+//! tables in step. Reads of a single id never touch the latest table. Two
+//! versions stamped with the same microsecond tie on their write timestamp,
+//! which Cassandra breaks cell by cell by value rather than by write order,
+//! so the latest row may not be the later write. Both stay in the history.
+
+mod repository;
+mod row;
+
+pub use repository::CassandraRepository;
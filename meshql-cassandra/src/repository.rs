@@ -0,0 +1,521 @@
+use crate::row::{self, Row, COLUMNS};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use meshql_core::{
+    conflict, forbid_hidden, supersedes, Clock, Envelope, IdStrategy, ListOptions, MeshqlError,
+    Repository, Result, SystemClock,
+};
+use scylla::client::session::Session;
+use scylla::statement::batch::{Batch, BatchType};
+use scylla::statement::prepared::PreparedStatement;
+use scylla::value::CqlValue;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Most writes or reads a bulk call keeps in flight at once.
+const CONCURRENCY: usize = 64;
+
+fn storage(e: impl std::fmt::Display) -> MeshqlError {
+    MeshqlError::Storage(e.to_string())
+}
+
+pub struct CassandraRepository {
+    session: Arc<Session>,
+    /// Inserts a version and upserts it into the latest table, atomically.
+    write: Batch,
+    insert_version: PreparedStatement,
+    read_latest: PreparedStatement,
+    /// Makes a version the latest only if there is none yet, as a lightweight
+    /// transaction.
+    claim_new: PreparedStatement,
+    /// Makes a version the latest only if the latest is the given version, as
+    /// a lightweight transaction.
+    claim_latest: PreparedStatement,
+    read_at: PreparedStatement,
+    history: PreparedStatement,
+    /// Scans every partition; versions are clustered by id, not time.
+    created_between: PreparedStatement,
+    list: PreparedStatement,
+    delete_version: PreparedStatement,
+    /// Deletes the latest row unless it was written after the given time.
+    delete_latest: PreparedStatement,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+}
+
+impl CassandraRepository {
+    /// Create `table` and `<table>_latest` in `keyspace` unless they exist, and
+    /// prepare the statements used against them. The keyspace must exist.
+    pub async fn new(session: Arc<Session>, keyspace: &str, table: &str) -> Result<Self> {
+        let versions = format!("{keyspace}.{table}");
+        let latest = format!("{keyspace}.{table}_latest");
+        Self::init_schema(&session, &versions, &latest).await?;
+
+        let prepare = |cql: String| {
+            let session = Arc::clone(&session);
+            async move { session.prepare(cql).await.map_err(storage) }
+        };
+        let insert_version = prepare(format!(
+            "INSERT INTO {versions} ({COLUMNS}) VALUES (?, ?, ?, ?, ?, ?)"
+        ))
+        .await?;
+        let upsert_latest = prepare(format!(
+            "INSERT INTO {latest} ({COLUMNS}) VALUES (?, ?, ?, ?, ?, ?) USING TIMESTAMP ?"
+        ))
+        .await?;
+        let mut write = Batch::new(BatchType::Logged);
+        write.append_statement(insert_version.clone());
+        write.append_statement(upsert_latest);
+
+        Ok(Self {
+            read_latest: prepare(format!("SELECT {COLUMNS} FROM {latest} WHERE id = ?")).await?,
+            claim_new: prepare(format!(
+                "INSERT INTO {latest} ({COLUMNS}) VALUES (?, ?, ?, ?, ?, ?) IF NOT EXISTS"
+            ))
+            .await?,
+            claim_latest: prepare(format!(
+                "UPDATE {latest} SET created_at_ms = ?, version = ?, deleted = ?, \
+                 authorized_tokens = ?, payload = ? WHERE id = ? IF version = ?"
+            ))
+            .await?,
+            insert_version,
+            read_at: prepare(format!(
+                "SELECT {COLUMNS} FROM {versions} WHERE id = ? AND created_at_ms <= ? LIMIT 1"
+            ))
+            .await?,
+            history: prepare(format!(
+                "SELECT {COLUMNS} FROM {versions} WHERE id = ? \
+                 ORDER BY created_at_ms ASC, version ASC"
+            ))
+            .await?,
+            created_between: prepare(format!(
+                "SELECT {COLUMNS} FROM {versions} \
+                 WHERE created_at_ms >= ? AND created_at_ms < ? ALLOW FILTERING"
+            ))
+            .await?,
+            list: prepare(format!("SELECT {COLUMNS} FROM {latest}")).await?,
+            delete_version: prepare(format!(
+                "DELETE FROM {versions} WHERE id = ? AND created_at_ms = ? AND version = ?"
+            ))
+            .await?,
+            delete_latest: prepare(format!(
+                "DELETE FROM {latest} USING TIMESTAMP ? WHERE id = ?"
+            ))
+            .await?,
+            write,
+            session,
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    async fn init_schema(session: &Session, versions: &str, latest: &str) -> Result<()> {
+        session
+            .query_unpaged(
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {versions} (
+                        id text,
+                        created_at_ms bigint,
+                        version text,
+                        deleted boolean,
+                        authorized_tokens set<text>,
+                        payload text,
+                        PRIMARY KEY ((id), created_at_ms, version)
+                    ) WITH CLUSTERING ORDER BY (created_at_ms DESC, version DESC)"
+                ),
+                (),
+            )
+            .await
+            .map_err(storage)?;
+
+        session
+            .query_unpaged(
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {latest} (
+                        id text PRIMARY KEY,
+                        created_at_ms bigint,
+                        version text,
+                        deleted boolean,
+                        authorized_tokens set<text>,
+                        payload text
+                    )"
+                ),
+                (),
+            )
+            .await
+            .map_err(storage)?;
+
+        Ok(())
+    }
+
+    /// Store `env` as a new version and, unless a newer version is already
+    /// there, as its id's latest.
+    async fn put(&self, env: &Envelope) -> Result<()> {
+        let (id, created_at_ms, version, deleted, tokens, payload) = row::to_values(env)?;
+        let write_time = env.created_at.timestamp_micros();
+        self.session
+            .batch(
+                &self.write,
+                (
+                    (&id, created_at_ms, &version, deleted, &tokens, &payload),
+                    (
+                        &id,
+                        created_at_ms,
+                        &version,
+                        deleted,
+                        &tokens,
+                        &payload,
+                        write_time,
+                    ),
+                ),
+            )
+            .await
+            .map_err(storage)?;
+        Ok(())
+    }
+
+    /// Every row `statement` returns for `values`, fetched page by page.
+    async fn rows(
+        &self,
+        statement: &PreparedStatement,
+        values: impl scylla::serialize::row::SerializeRow,
+    ) -> Result<Vec<Envelope>> {
+        self.raw_rows(statement, values)
+            .await?
+            .into_iter()
+            .map(row::from_row)
+            .collect()
+    }
+
+    /// [`Self::rows`], undecoded.
+    async fn raw_rows(
+        &self,
+        statement: &PreparedStatement,
+        values: impl scylla::serialize::row::SerializeRow,
+    ) -> Result<Vec<Row>> {
+        self.session
+            .execute_iter(statement.clone(), values)
+            .await
+            .map_err(storage)?
+            .rows_stream::<Row>()
+            .map_err(storage)?
+            .map_err(storage)
+            .try_collect()
+            .await
+    }
+}
+
+#[async_trait]
+impl Repository for CassandraRepository {
+    async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+        self.put(&env).await?;
+        Ok(env)
+    }
+
+    async fn read(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        let cutoff_ms = match at {
+            Some(t) => t.timestamp_millis(),
+            None => self.clock.now().timestamp_millis() + 1,
+        };
+
+        let newest = self
+            .session
+            .execute_unpaged(&self.read_at, (id, cutoff_ms))
+            .await
+            .map_err(storage)?
+            .into_rows_result()
+            .map_err(storage)?
+            .maybe_first_row::<Row>()
+            .map_err(storage)?;
+        let Some(newest) = newest else {
+            return Ok(None);
+        };
+        let env = row::from_row(newest)?;
+        Ok(row::is_visible(&env, tokens).then_some(env))
+    }
+
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        let latest = self.rows(&self.list, ()).await?;
+        let listed = latest
+            .into_iter()
+            .filter(|env| options.lists(env.deleted) && row::is_visible(env, tokens))
+            .collect();
+        Ok(options.page(listed))
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        let versions = self.rows(&self.history, (id,)).await?;
+        Ok(versions
+            .into_iter()
+            .filter(|env| row::is_visible(env, tokens))
+            .collect())
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let window = (from.timestamp_millis(), to.timestamp_millis());
+        let mut versions: Vec<Envelope> = self
+            .rows(&self.created_between, window)
+            .await?
+            .into_iter()
+            .filter(|env| row::is_visible(env, tokens))
+            .collect();
+        versions.sort_by_key(|env| env.created_at);
+        Ok(versions)
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
+        let current = self.read(id, tokens, None).await?;
+        match current {
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
+            Some(env) => {
+                let deleted_env = Envelope {
+                    id: env.id,
+                    payload: env.payload,
+                    created_at: self.clock.now(),
+                    deleted: true,
+                    authorized_tokens: env.authorized_tokens,
+                };
+                self.create(deleted_env, tokens).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Deletes the versions `history` returns. The latest row goes too, unless
+    /// it holds a newer version `tokens` can't see.
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let mut keys: Vec<(String, i64, String)> = Vec::new();
+        let mut newest = None;
+        for found in self.raw_rows(&self.history, (id,)).await? {
+            let version = found.2.clone();
+            let env = row::from_row(found)?;
+            if row::is_visible(&env, tokens) {
+                keys.push((env.id.clone(), env.created_at.timestamp_millis(), version));
+                newest = Some(env);
+            }
+        }
+        let purged = keys.len() as u64;
+        futures::stream::iter(keys)
+            .map(|key| async move {
+                self.session
+                    .execute_unpaged(&self.delete_version, key)
+                    .await
+                    .map(|_| ())
+                    .map_err(storage)
+            })
+            .buffer_unordered(CONCURRENCY)
+            .try_collect::<()>()
+            .await?;
+        if let Some(newest) = newest {
+            // A delete wins over a write with the same timestamp, so this
+            // removes the latest row only if it was written by a purged version.
+            self.session
+                .execute_unpaged(
+                    &self.delete_latest,
+                    (newest.created_at.timestamp_micros(), id),
+                )
+                .await
+                .map_err(storage)?;
+        }
+        Ok(purged)
+    }
+
+    /// Claims the latest row with a lightweight transaction conditioned on the
+    /// version it held when read, so only one write based on that version
+    /// succeeds, whichever process it runs in, before storing the version.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+
+        let latest = self
+            .session
+            .execute_unpaged(&self.read_latest, (&env.id,))
+            .await
+            .map_err(storage)?
+            .into_rows_result()
+            .map_err(storage)?
+            .maybe_first_row::<Row>()
+            .map_err(storage)?;
+        let (id, created_at_ms, version, deleted, tokens, payload) = row::to_values(&env)?;
+        let claimed = match latest {
+            Some((_, latest_ms, latest_version, latest_deleted, _, _)) => {
+                if supersedes(latest_ms, latest_deleted, expected_created_at) {
+                    return Err(conflict(&env.id));
+                }
+                self.session
+                    .execute_unpaged(
+                        &self.claim_latest,
+                        (
+                            created_at_ms,
+                            &version,
+                            deleted,
+                            &tokens,
+                            &payload,
+                            &id,
+                            &latest_version,
+                        ),
+                    )
+                    .await
+            }
+            None => {
+                self.session
+                    .execute_unpaged(
+                        &self.claim_new,
+                        (&id, created_at_ms, &version, deleted, &tokens, &payload),
+                    )
+                    .await
+            }
+        }
+        .map_err(storage)?;
+
+        // The first column of a lightweight transaction's result is `[applied]`.
+        let applied = claimed
+            .into_rows_result()
+            .map_err(storage)?
+            .first_row::<scylla::value::Row>()
+            .map_err(storage)?
+            .columns
+            .first()
+            .is_some_and(|applied| matches!(applied, Some(CqlValue::Boolean(true))));
+        if !applied {
+            return Err(conflict(&env.id));
+        }
+        self.session
+            .execute_unpaged(
+                &self.insert_version,
+                (&id, created_at_ms, &version, deleted, &tokens, &payload),
+            )
+            .await
+            .map_err(storage)?;
+        Ok(env)
+    }
+
+    async fn create_many(
+        &self,
+        envelopes: Vec<Envelope>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let mut results = Vec::with_capacity(envelopes.len());
+        for mut env in envelopes {
+            if env.id.is_empty() {
+                env.id = self.ids.generate();
+            }
+            env.authorized_tokens = tokens.to_vec();
+            results.push(env);
+        }
+
+        // A batch spanning many partitions only loads its coordinator, so each
+        // envelope gets its own, with several in flight.
+        futures::stream::iter(results.clone())
+            .map(|env| async move { self.put(&env).await })
+            .buffer_unordered(CONCURRENCY)
+            .try_collect::<()>()
+            .await?;
+        Ok(results)
+    }
+
+    async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let found: Vec<Option<Envelope>> = futures::stream::iter(ids.iter().cloned())
+            .map(|id| async move { self.read(&id, tokens, None).await })
+            .buffered(CONCURRENCY)
+            .try_collect()
+            .await?;
+        Ok(found.into_iter().flatten().collect())
+    }
+
+    async fn remove_many(
+        &self,
+        ids: &[String],
+        tokens: &[String],
+    ) -> Result<HashMap<String, bool>> {
+        let current = self.read_many(ids, tokens).await?;
+        let now = self.clock.now();
+        let mut removed = HashSet::new();
+        let tombstones: Vec<Envelope> = current
+            .into_iter()
+            .filter(|env| removed.insert(env.id.clone()))
+            .map(|env| Envelope {
+                created_at: now,
+                deleted: true,
+                authorized_tokens: tokens.to_vec(),
+                ..env
+            })
+            .collect();
+
+        futures::stream::iter(tombstones)
+            .map(|env| async move { self.put(&env).await })
+            .buffer_unordered(CONCURRENCY)
+            .try_collect::<()>()
+            .await?;
+
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.session
+            .query_unpaged("SELECT release_version FROM system.local", ())
+            .await
+            .map(|_| ())
+            .map_err(storage)
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::DateTime;
+use meshql_core::{Envelope, IdStrategy, MeshqlError, Result};
+
+/// The columns every query selects, in [`Row`] order.
+pub(crate) const COLUMNS: &str = "id, created_at_ms, version, deleted, authorized_tokens, payload";
+
+/// `id`, `created_at_ms`, `version`, `deleted`, `authorized_tokens` and
+/// `payload`. An empty set reads back as null.
+pub(crate) type Row = (String, i64, String, bool, Option<Vec<String>>, String);
+
+/// Bind values for an insert of [`COLUMNS`], under a new `version`: a ULID,
+/// so versions written in the same millisecond get keys of their own that
+/// sort in write order.
+pub(crate) fn to_values(
+    env: &Envelope,
+) -> Result<(String, i64, String, bool, Vec<String>, String)> {
+    let payload =
+        serde_json::to_string(&env.payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+    Ok((
+        env.id.clone(),
+        env.created_at.timestamp_millis(),
+        IdStrategy::Ulid.generate(),
+        env.deleted,
+        env.authorized_tokens.clone(),
+        payload,
+    ))
+}
+
+pub(crate) fn from_row(row: Row) -> Result<Envelope> {
+    let (id, created_at_ms, _, deleted, tokens, payload) = row;
+    let payload = serde_json::from_str(&payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+    let created_at = DateTime::from_timestamp_millis(created_at_ms).ok_or_else(|| {
+        MeshqlError::Parse(format!("created_at_ms {created_at_ms} is out of range"))
+    })?;
+    Ok(Envelope {
+        id,
+        payload,
+        created_at,
+        deleted,
+        authorized_tokens: tokens.unwrap_or_default(),
+    })
+}
+
+/// Whether a caller holding `tokens` may see `env`. Envelopes stored with `*`
+/// are visible to everyone, and a caller holding `*` sees everything.
+pub(crate) fn is_visible(env: &Envelope, tokens: &[String]) -> bool {
+    tokens.iter().any(|t| t == "*")
+        || env
+            .authorized_tokens
+            .iter()
+            .any(|t| t == "*" || tokens.contains(t))
+}
@@ -53,37 +53,11 @@ async fn post_entity(
     let url = format!("{server_addr}/{entity_type}/api");
     let resp = client.post(&url).json(&data).send().await.unwrap();
     assert_eq!(resp.status().as_u16(), 201, "POST {entity_type} failed");
-    resp.text().await.unwrap(); // consume body
-
-    // Use GET list to find the entity
-    let list_resp: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
-
-    if let Some(items) = list_resp.as_array() {
-        // Try matching by name first (for named entities)
-        let name = data.get("name").and_then(|v| v.as_str()).unwrap_or("");
-        if !name.is_empty() {
-            for item in items {
-                if item.get("name").and_then(|v| v.as_str()) == Some(name) {
-                    if let Some(id) = item.get("id").and_then(|v| v.as_str()) {
-                        return id.to_string();
-                    }
-                }
-            }
-        }
-
-        // Fallback: match by all posted data fields (for events/projections)
-        if let Some(obj) = data.as_object() {
-            for item in items.iter().rev() {
-                let all_match = obj.iter().all(|(k, v)| item.get(k) == Some(v));
-                if all_match {
-                    if let Some(id) = item.get("id").and_then(|v| v.as_str()) {
-                        return id.to_string();
-                    }
-                }
-            }
-        }
-    }
-    panic!("Could not find {entity_type} after creation");
+    let created: Value = resp.json().await.unwrap();
+    created["id"]
+        .as_str()
+        .unwrap_or_else(|| panic!("POST {entity_type} returned no id"))
+        .to_string()
 }
 
 async fn graphql_query(
@@ -213,6 +187,21 @@ async fn query_graph_at_stamp(world: &mut CertWorld, entity_type: String, raw_qu
     world.farm_response = Some(response);
 }
 
+#[when(regex = r#"^I capture the response at "([^"]+)" as the "([^"]+)" id "([^"]+)"$"#)]
+async fn capture_response_id(
+    world: &mut CertWorld,
+    path: String,
+    entity_type: String,
+    name: String,
+) {
+    let resp = world.farm_response.as_ref().expect("no response");
+    let id = json_at_path(resp, &path)
+        .and_then(|v| v.as_str())
+        .unwrap_or_else(|| panic!("no id at '{path}' in response: {resp}"))
+        .to_string();
+    world.ids.entry(entity_type).or_default().insert(name, id);
+}
+
 // ---- Then assertions ----
 
 // Legacy assertion kept for backward compatibility with farm.feature
@@ -312,6 +312,25 @@ async fn assert_reading_returns_none(world: &mut CertWorld, name: String) {
     assert!(result.is_none(), "expected None after delete");
 }
 
+#[then(regex = r#"^reading "([^"]+)" raw should return a deleted version$"#)]
+async fn assert_reading_raw_returns_tombstone(world: &mut CertWorld, name: String) {
+    let env = world
+        .envelopes_by_name
+        .get(&name)
+        .expect("envelope not found");
+    let id = env.id.clone();
+    let result = world
+        .repo()
+        .read_raw(&id, &CertWorld::star(), None)
+        .await
+        .unwrap();
+    let tombstone = result.expect("expected the tombstone after delete");
+    assert!(
+        tombstone.deleted,
+        "expected the latest version to be deleted"
+    );
+}
+
 #[then(regex = r#"^I should have (\d+) created envelopes$"#)]
 async fn assert_create_count(world: &mut CertWorld, count: usize) {
     assert_eq!(world.last_envelopes.len(), count);
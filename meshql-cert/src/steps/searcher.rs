@@ -1,6 +1,6 @@
 use chrono::Utc;
 use cucumber::{given, then, when};
-use meshql_core::{Envelope, Stash};
+use meshql_core::{Envelope, MeshqlError, Stash};
 use serde_json::json;
 
 use crate::world::CertWorld;
@@ -214,6 +214,125 @@ async fn search_all_literal_limit(world: &mut CertWorld, template: String, limit
     world.search_results = results;
 }
 
+#[when(
+    regex = r#"^I search all using literal template '([^']+)' with limit (\d+) and offset (\d+)$"#
+)]
+async fn search_all_literal_page(world: &mut CertWorld, template: String, limit: i64, offset: i64) {
+    let mut args = Stash::new();
+    args.insert("limit".to_string(), json!(limit));
+    args.insert("offset".to_string(), json!(offset));
+    let results = world
+        .searcher()
+        .find_all(
+            &template,
+            &args,
+            &CertWorld::star(),
+            Utc::now().timestamp_millis(),
+        )
+        .await
+        .unwrap();
+    world.search_results = results;
+}
+
+#[when(regex = r#"^I search all using literal template '([^']+)' sorted by "([^"]+)"$"#)]
+async fn search_all_literal_sorted(world: &mut CertWorld, template: String, sort: String) {
+    let mut args = Stash::new();
+    args.insert("sort".to_string(), json!(sort));
+    let result = world
+        .searcher()
+        .find_all(
+            &template,
+            &args,
+            &CertWorld::star(),
+            Utc::now().timestamp_millis(),
+        )
+        .await;
+    match result {
+        Ok(results) => {
+            world.search_results = results;
+            world.last_search_error = None;
+        }
+        Err(e) => {
+            world.search_results = Vec::new();
+            world.last_search_error = Some(e);
+        }
+    }
+}
+
+#[when(regex = r#"^I count using template "([^"]+)" with arg "([^"]+)" = "([^"]+)"$"#)]
+async fn count_template(
+    world: &mut CertWorld,
+    template_name: String,
+    arg_key: String,
+    arg_value: String,
+) {
+    let template = world
+        .templates
+        .get(&template_name)
+        .cloned()
+        .expect("template not found");
+
+    let mut args = Stash::new();
+    args.insert(arg_key, json!(arg_value));
+
+    let count = world
+        .searcher()
+        .count(
+            &template,
+            &args,
+            &CertWorld::star(),
+            Utc::now().timestamp_millis(),
+        )
+        .await
+        .unwrap();
+    world.last_count = Some(count);
+}
+
+#[when(regex = r#"^I count using literal template '([^']+)'$"#)]
+async fn count_literal(world: &mut CertWorld, template: String) {
+    let args = Stash::new();
+    let count = world
+        .searcher()
+        .count(
+            &template,
+            &args,
+            &CertWorld::star(),
+            Utc::now().timestamp_millis(),
+        )
+        .await
+        .unwrap();
+    world.last_count = Some(count);
+}
+
+#[when(regex = r#"^I check existence using template "([^"]+)" with arg "([^"]+)" = "([^"]+)"$"#)]
+async fn exists_template(
+    world: &mut CertWorld,
+    template_name: String,
+    arg_key: String,
+    arg_value: String,
+) {
+    let template = world
+        .templates
+        .get(&template_name)
+        .cloned()
+        .expect("template not found");
+
+    let mut args = Stash::new();
+    args.insert(arg_key, json!(arg_value));
+
+    let exists = world
+        .searcher()
+        .exists(
+            &template,
+            &args,
+            &CertWorld::star(),
+            Utc::now().timestamp_millis(),
+        )
+        .await
+        .unwrap();
+    world.last_exists = Some(exists);
+}
+
 // ---- Then assertions ----
 
 #[then("the search result should be empty")]
@@ -282,3 +401,54 @@ async fn assert_all_field(world: &mut CertWorld, field: String, expected: String
         assert_eq!(actual, &json!(expected), "field '{field}' mismatch");
     }
 }
+
+#[then(regex = r#"^the search result ids should be "([^"]*)"$"#)]
+async fn assert_result_ids(world: &mut CertWorld, expected: String) {
+    let actual: Vec<&str> = world
+        .search_results
+        .iter()
+        .map(|r| {
+            r.get("id")
+                .and_then(|v| v.as_str())
+                .expect("result has no id")
+        })
+        .collect();
+    let expected: Vec<&str> = expected.split(',').map(str::trim).collect();
+    assert_eq!(actual, expected, "result ids mismatch");
+}
+
+#[then(regex = r#"^the search result "([^"]+)" values should be "([^"]*)"$"#)]
+async fn assert_result_values(world: &mut CertWorld, field: String, expected: String) {
+    let actual: Vec<&str> = world
+        .search_results
+        .iter()
+        .map(|r| {
+            r.get(&field)
+                .and_then(|v| v.as_str())
+                .expect("field not found")
+        })
+        .collect();
+    let expected: Vec<&str> = expected.split(',').map(str::trim).collect();
+    assert_eq!(actual, expected, "'{field}' values mismatch");
+}
+
+#[then("the search should fail with a parse error")]
+async fn assert_parse_error(world: &mut CertWorld) {
+    assert!(
+        matches!(world.last_search_error, Some(MeshqlError::Parse(_))),
+        "expected a parse error, got {:?}",
+        world.last_search_error
+    );
+}
+
+#[then(regex = r#"^the count should be (\d+)$"#)]
+async fn assert_count(world: &mut CertWorld, expected: u64) {
+    let actual = world.last_count.expect("no count recorded");
+    assert_eq!(actual, expected, "expected count {expected}, got {actual}");
+}
+
+#[then(regex = r#"^the existence check should be (true|false)$"#)]
+async fn assert_exists(world: &mut CertWorld, expected: bool) {
+    let actual = world.last_exists.expect("no existence check recorded");
+    assert_eq!(actual, expected, "expected exists = {expected}");
+}
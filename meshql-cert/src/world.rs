@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 use cucumber::World;
-use meshql_core::{Envelope, Repository, Searcher, Stash};
+use meshql_core::{Envelope, MeshqlError, Repository, Searcher, Stash};
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
@@ -31,6 +31,9 @@ pub struct CertWorld {
     pub timestamps: HashMap<String, DateTime<Utc>>,
     pub last_search_result: Option<Option<Stash>>,
     pub search_results: Vec<Stash>,
+    pub last_search_error: Option<MeshqlError>,
+    pub last_count: Option<u64>,
+    pub last_exists: Option<bool>,
     pub last_remove: bool,
     pub remove_results: HashMap<String, bool>,
     pub test_start: DateTime<Utc>,
@@ -62,6 +65,9 @@ impl CertWorld {
             timestamps: HashMap::new(),
             last_search_result: None,
             search_results: Vec::new(),
+            last_search_error: None,
+            last_count: None,
+            last_exists: None,
             last_remove: false,
             remove_results: HashMap::new(),
             test_start: Utc::now(),
@@ -27,3 +27,13 @@ Feature: Farm E2E Certification
     Then there should be no GraphQL errors
     And the response data.getFarm.name should be "Emerdale"
     And the response data.getFarm.coops should have 2 items
+
+  Scenario: Creating a farm through a GraphQL mutation makes it readable by id
+    When I query the "farm" graph with: mutation { createFarm(input: {name: "Westfield", address: "1 Mill Lane"}) { id name } }
+    Then there should be no GraphQL errors
+    And the response at "data.createFarm.name" should be "Westfield"
+    When I capture the response at "data.createFarm.id" as the "farm" id "Westfield"
+    And I query the "farm" graph with: { getFarm(id: "<ids.farm.Westfield>") { name address } }
+    Then there should be no GraphQL errors
+    And the response data.getFarm.name should be "Westfield"
+    And the response at "data.getFarm.address" should be "1 Mill Lane"
@@ -28,6 +28,12 @@ Feature: Repository Contract
     Then the remove should return true
     And reading "To Delete" should return None
 
+  Scenario: Reading raw returns the tombstone of a removed envelope
+    When I create envelopes named "Audited"
+    And I remove the envelope named "Audited"
+    Then reading "Audited" should return None
+    And reading "Audited" raw should return a deleted version
+
   Scenario: Creating many envelopes stores all of them
     When I create many envelopes with base name "Bulk Item" and count 3
     Then I should have 3 created envelopes
@@ -29,6 +29,14 @@ Feature: Searcher Contract
     Then the search results count should be 1
     And all search results should have "name" = "delta"
 
+  Scenario: Finding all with an $in list matches any listed value
+    When I search all using literal template '{"payload.name": {"$in": ["alpha", "gamma", "omega"]}}' sorted by "id"
+    Then the search result ids should be "s-id-1, s-id-3"
+
+  Scenario: Finding all with comparison operators matches a numeric range
+    When I search all using literal template '{"payload.count": {"$gt": 10, "$lte": 30}}' sorted by "id"
+    Then the search result ids should be "s-id-2, s-id-3"
+
   Scenario: Finding all for a nonexistent type returns empty
     When I search all using template "findAllByType" with arg "id" = "typeZ"
     Then the search results should be empty
@@ -37,6 +45,49 @@ Feature: Searcher Contract
     When I search all using literal template '{}' with limit 1
     Then the search results count should be 1
 
+  Scenario: Paging returns the first page ordered by id
+    When I search all using literal template '{}' with limit 2 and offset 0
+    Then the search results count should be 2
+    And the search result ids should be "s-id-1, s-id-2"
+
+  Scenario: Paging returns the second page ordered by id
+    When I search all using literal template '{}' with limit 2 and offset 2
+    Then the search results count should be 2
+    And the search result ids should be "s-id-3, s-id-4"
+
+  Scenario: Paging past the end returns empty
+    When I search all using literal template '{}' with limit 2 and offset 4
+    Then the search results should be empty
+
+  Scenario: Sorting by count descending orders the results
+    When I search all using literal template '{}' sorted by "payload.count:desc"
+    Then the search results count should be 4
+    And the search result "name" values should be "delta, gamma, beta, alpha"
+
+  Scenario: Sorting by an invalid field is a parse error
+    When I search all using literal template '{}' sorted by "count:desc"
+    Then the search should fail with a parse error
+
   Scenario: Searching with an empty query returns all items
     When I search all using literal template '{}'
     Then the search results should not be empty
+
+  Scenario: Counting by type returns the number of matches
+    When I count using template "findAllByType" with arg "id" = "typeA"
+    Then the count should be 2
+
+  Scenario: Counting with an empty query counts every item
+    When I count using literal template '{}'
+    Then the count should be 4
+
+  Scenario: Counting a nonexistent type returns zero
+    When I count using template "findAllByType" with arg "id" = "typeZ"
+    Then the count should be 0
+
+  Scenario: Existence check finds a matching item
+    When I check existence using template "findAllByType" with arg "id" = "typeB"
+    Then the existence check should be true
+
+  Scenario: Existence check for a nonexistent type is false
+    When I check existence using template "findAllByType" with arg "id" = "typeZ"
+    Then the existence check should be false
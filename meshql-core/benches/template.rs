@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use meshql_core::{render_template, CompiledTemplate, MissingKey, Stash};
+use serde_json::json;
+use std::hint::black_box;
+
+const TEMPLATE: &str = r#"{"payload.farm_id": "{{id}}", "payload.name": "{{name}}", "id": {"$in": "{{ids}}"}, "payload.eggs": {"$gte": {{min}}}}"#;
+
+fn args() -> Stash {
+    json!({"id": "farm-1", "name": "Emerdale", "ids": ["a", "b", "c"], "min": 3})
+        .as_object()
+        .unwrap()
+        .clone()
+}
+
+fn render(c: &mut Criterion) {
+    let args = args();
+    c.bench_function("render_template", |b| {
+        b.iter(|| render_template(black_box(TEMPLATE), black_box(&args), MissingKey::Error))
+    });
+    let compiled = CompiledTemplate::compile(TEMPLATE).unwrap();
+    c.bench_function("compiled_render", |b| {
+        b.iter(|| compiled.render(black_box(&args), MissingKey::Error))
+    });
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -1,12 +1,24 @@
-use crate::{Envelope, Stash};
+use crate::{Envelope, MeshqlError, Result, Stash};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use http::header::AUTHORIZATION;
+use http::HeaderMap;
+use ring::{hmac, signature};
+use serde::Deserialize;
+use serde_json::Value;
 
+#[async_trait::async_trait]
 pub trait Auth: Send + Sync {
     fn get_auth_token(&self, context: &Stash) -> Vec<String>;
     fn is_authorized(&self, credentials: &[String], envelope: &Envelope) -> bool;
+    /// Derive the caller's credentials from request headers.
+    /// Fails with [`MeshqlError::Unauthorized`] when the request can't be authenticated.
+    async fn authorize(&self, headers: &HeaderMap) -> Result<Vec<String>>;
 }
 
 pub struct NoAuth;
 
+#[async_trait::async_trait]
 impl Auth for NoAuth {
     fn get_auth_token(&self, _context: &Stash) -> Vec<String> {
         vec!["*".to_string()]
@@ -15,4 +27,245 @@ impl Auth for NoAuth {
     fn is_authorized(&self, _credentials: &[String], _envelope: &Envelope) -> bool {
         true
     }
+
+    async fn authorize(&self, _headers: &HeaderMap) -> Result<Vec<String>> {
+        Ok(vec!["*".to_string()])
+    }
+}
+
+/// How a repository matches a caller's tokens against the tokens an envelope
+/// was written with.
+///
+/// A caller holding `*` sees everything, and an envelope written with `*` is
+/// visible to every caller, under either policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthPolicy {
+    /// Visible to callers holding any one of the envelope's tokens.
+    #[default]
+    AnyOf,
+    /// Visible only to callers holding every one of the envelope's tokens.
+    AllOf,
+}
+
+impl AuthPolicy {
+    /// Whether a caller holding `tokens` may see an envelope written with
+    /// `authorized_tokens`.
+    pub fn allows(&self, authorized_tokens: &[String], tokens: &[String]) -> bool {
+        if tokens.iter().any(|t| t == "*") {
+            return true;
+        }
+        let held = |t: &String| t == "*" || tokens.contains(t);
+        match self {
+            AuthPolicy::AnyOf => authorized_tokens.iter().any(held),
+            AuthPolicy::AllOf => {
+                !authorized_tokens.is_empty() && authorized_tokens.iter().all(held)
+            }
+        }
+    }
+}
+
+/// Key that signs the bearer tokens accepted by [`JwtAuth`].
+pub enum JwtKey {
+    /// Shared secret for `HS256`.
+    Hs256(Vec<u8>),
+    /// RSA public key for `RS256`, as PKCS#1 `RSAPublicKey` DER.
+    Rs256(Vec<u8>),
+}
+
+/// Authenticates `Authorization: Bearer <jwt>` headers.
+///
+/// - The token's `alg` must match the configured key
+/// - `exp` is required and `nbf` is honoured when present
+/// - Credentials are read from one claim (`sub` by default), which may be a
+///   string or an array of strings such as `groups`
+pub struct JwtAuth {
+    key: JwtKey,
+    claim: String,
+}
+
+impl JwtAuth {
+    pub fn new(key: JwtKey) -> Self {
+        Self {
+            key,
+            claim: "sub".to_string(),
+        }
+    }
+
+    pub fn with_claim(mut self, claim: impl Into<String>) -> Self {
+        self.claim = claim.into();
+        self
+    }
+
+    /// Check the token's signature and validity window and return its claims.
+    pub fn verify(&self, token: &str) -> Result<Stash> {
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(sig), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(MeshqlError::Unauthorized);
+        };
+
+        let alg = decode_segment(header)?
+            .get("alg")
+            .and_then(Value::as_str)
+            .map(String::from);
+        let sig = URL_SAFE_NO_PAD
+            .decode(sig)
+            .map_err(|_| MeshqlError::Unauthorized)?;
+        let message = &token.as_bytes()[..header.len() + 1 + payload.len()];
+
+        let verified = match (&self.key, alg.as_deref()) {
+            (JwtKey::Hs256(secret), Some("HS256")) => {
+                hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, secret), message, &sig).is_ok()
+            }
+            (JwtKey::Rs256(public_key), Some("RS256")) => signature::UnparsedPublicKey::new(
+                &signature::RSA_PKCS1_2048_8192_SHA256,
+                public_key,
+            )
+            .verify(message, &sig)
+            .is_ok(),
+            _ => false,
+        };
+        if !verified {
+            return Err(MeshqlError::Unauthorized);
+        }
+
+        let claims = decode_segment(payload)?;
+        let now = chrono::Utc::now().timestamp();
+        match claims.get("exp").and_then(Value::as_i64) {
+            Some(exp) if exp > now => {}
+            _ => return Err(MeshqlError::Unauthorized),
+        }
+        if let Some(nbf) = claims.get("nbf").and_then(Value::as_i64) {
+            if nbf > now {
+                return Err(MeshqlError::Unauthorized);
+            }
+        }
+        Ok(claims)
+    }
+}
+
+#[async_trait::async_trait]
+impl Auth for JwtAuth {
+    /// Credentials from already verified claims.
+    fn get_auth_token(&self, context: &Stash) -> Vec<String> {
+        match context.get(&self.claim) {
+            Some(Value::String(s)) => vec![s.clone()],
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|v| v.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn is_authorized(&self, credentials: &[String], envelope: &Envelope) -> bool {
+        AuthPolicy::AnyOf.allows(&envelope.authorized_tokens, credentials)
+    }
+
+    async fn authorize(&self, headers: &HeaderMap) -> Result<Vec<String>> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(MeshqlError::Unauthorized)?;
+        let credentials = self.get_auth_token(&self.verify(token.trim())?);
+        if credentials.is_empty() {
+            return Err(MeshqlError::Unauthorized);
+        }
+        Ok(credentials)
+    }
+}
+
+fn decode_segment(segment: &str) -> Result<Stash> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(segment)
+        .map_err(|_| MeshqlError::Unauthorized)?;
+    serde_json::from_slice(&bytes).map_err(|_| MeshqlError::Unauthorized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SECRET: &[u8] = b"meshql-test-secret";
+
+    fn hs256_token(claims: Value, secret: &[u8]) -> String {
+        let header = URL_SAFE_NO_PAD.encode(json!({"alg": "HS256", "typ": "JWT"}).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let message = format!("{header}.{payload}");
+        let tag = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, secret),
+            message.as_bytes(),
+        );
+        format!("{message}.{}", URL_SAFE_NO_PAD.encode(tag.as_ref()))
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+        headers
+    }
+
+    fn in_an_hour() -> i64 {
+        chrono::Utc::now().timestamp() + 3600
+    }
+
+    #[tokio::test]
+    async fn valid_token_yields_the_subject() {
+        let auth = JwtAuth::new(JwtKey::Hs256(SECRET.to_vec()));
+        let token = hs256_token(json!({"sub": "alice", "exp": in_an_hour()}), SECRET);
+        assert_eq!(
+            auth.authorize(&bearer(&token)).await.unwrap(),
+            vec!["alice"]
+        );
+    }
+
+    #[tokio::test]
+    async fn array_claims_yield_every_entry() {
+        let auth = JwtAuth::new(JwtKey::Hs256(SECRET.to_vec())).with_claim("groups");
+        let token = hs256_token(
+            json!({"sub": "alice", "groups": ["farmers", "admins"], "exp": in_an_hour()}),
+            SECRET,
+        );
+        assert_eq!(
+            auth.authorize(&bearer(&token)).await.unwrap(),
+            vec!["farmers", "admins"]
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_rejected() {
+        let auth = JwtAuth::new(JwtKey::Hs256(SECRET.to_vec()));
+        let exp = chrono::Utc::now().timestamp() - 60;
+        let token = hs256_token(json!({"sub": "alice", "exp": exp}), SECRET);
+        assert!(matches!(
+            auth.authorize(&bearer(&token)).await,
+            Err(MeshqlError::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_token_is_rejected() {
+        let auth = JwtAuth::new(JwtKey::Hs256(SECRET.to_vec()));
+        assert!(matches!(
+            auth.authorize(&HeaderMap::new()).await,
+            Err(MeshqlError::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn bad_signatures_and_algorithms_are_rejected() {
+        let claims = json!({"sub": "alice", "exp": in_an_hour()});
+        let forged = hs256_token(claims.clone(), b"some-other-secret");
+        let hs256 = JwtAuth::new(JwtKey::Hs256(SECRET.to_vec()));
+        assert!(hs256.authorize(&bearer(&forged)).await.is_err());
+
+        // An HS256 token must not verify against an RS256 key.
+        let token = hs256_token(claims, SECRET);
+        let rs256 = JwtAuth::new(JwtKey::Rs256(SECRET.to_vec()));
+        assert!(rs256.authorize(&bearer(&token)).await.is_err());
+    }
 }
@@ -0,0 +1,93 @@
+//! How the SQL backends store envelope payloads.
+
+use crate::{MeshqlError, Result, Stash};
+use serde::Deserialize;
+
+/// Stored in the JSON `payload` column of rows written by a binary codec, so
+/// the column stays valid JSON for the searchers that query it.
+const PLACEHOLDER: &str = "{}";
+
+/// The encoding a SQL repository writes payloads in.
+///
+/// JSON rows keep the payload in the `payload` text column. Binary codecs
+/// write it to the nullable `payload_bin` column instead, which is smaller and
+/// quicker to parse for wide documents. Rows are read by whichever column is
+/// set, so switching codecs never strands rows written before the switch.
+///
+/// Searchers filter and project the JSON column in SQL, so only use a binary
+/// codec for entities read by id through their repository.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadCodec {
+    #[default]
+    Json,
+    #[serde(alias = "msgpack")]
+    MessagePack,
+}
+
+impl PayloadCodec {
+    /// `payload` as the `(payload, payload_bin)` column values to write.
+    pub fn to_columns(&self, payload: &Stash) -> Result<(String, Option<Vec<u8>>)> {
+        match self {
+            PayloadCodec::Json => {
+                let json = serde_json::to_string(payload)
+                    .map_err(|e| MeshqlError::Parse(e.to_string()))?;
+                Ok((json, None))
+            }
+            PayloadCodec::MessagePack => {
+                let bytes =
+                    rmp_serde::to_vec(payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+                Ok((PLACEHOLDER.to_string(), Some(bytes)))
+            }
+        }
+    }
+
+    /// The payload of a row written by any codec.
+    pub fn from_columns(json: &str, bin: Option<&[u8]>) -> Result<Stash> {
+        match bin {
+            Some(bytes) => {
+                rmp_serde::from_slice(bytes).map_err(|e| MeshqlError::Parse(e.to_string()))
+            }
+            None => serde_json::from_str(json).map_err(|e| MeshqlError::Parse(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn wide_payload() -> Stash {
+        let mut payload = Stash::new();
+        for i in 0..500 {
+            payload.insert(
+                format!("field_{i}"),
+                json!({"count": i, "ratio": i as f64 / 7.0, "active": i % 2 == 0, "tags": ["a", "b"]}),
+            );
+        }
+        payload
+    }
+
+    #[test]
+    fn message_pack_round_trips_smaller_than_json() {
+        let payload = wide_payload();
+        let (json, none) = PayloadCodec::Json.to_columns(&payload).unwrap();
+        let (placeholder, bin) = PayloadCodec::MessagePack.to_columns(&payload).unwrap();
+        assert!(none.is_none());
+        assert_eq!(placeholder, "{}");
+        let bin = bin.unwrap();
+        assert!(
+            bin.len() < json.len(),
+            "{} bytes of MessagePack against {} of JSON",
+            bin.len(),
+            json.len()
+        );
+
+        assert_eq!(
+            PayloadCodec::from_columns(&placeholder, Some(&bin)).unwrap(),
+            payload
+        );
+        assert_eq!(PayloadCodec::from_columns(&json, None).unwrap(), payload);
+    }
+}
@@ -0,0 +1,133 @@
+//! What makes [`Repository::create_if_unchanged`] and
+//! [`Repository::update_if_unchanged`] conflict, and the lock their default
+//! methods fall back to for backends that can't compare and set in the store.
+
+use crate::{MeshqlError, Repository, Result};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+/// A repository's address and an id.
+type LockKey = (usize, String);
+
+/// The id's lock for each `(repository, id)` a default conditional write holds
+/// or waits on.
+static CONDITIONAL_WRITES: Mutex<BTreeMap<LockKey, Arc<tokio::sync::Mutex<()>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// Whether an id's newest stored version, created at `latest_created_at_ms`
+/// and perhaps a tombstone, rules out a write based on `expected_created_at`:
+/// it is newer to the millisecond or, when that is `None`, live. Versions
+/// count whoever may see them.
+pub fn supersedes(
+    latest_created_at_ms: i64,
+    latest_deleted: bool,
+    expected_created_at: Option<DateTime<Utc>>,
+) -> bool {
+    match expected_created_at {
+        Some(expected) => latest_created_at_ms > expected.timestamp_millis(),
+        None => !latest_deleted,
+    }
+}
+
+/// The error a conditional write to `id` fails with when a newer version won.
+pub fn conflict(id: &str) -> MeshqlError {
+    MeshqlError::Conflict(format!(
+        "{id} has changed since the version it was based on"
+    ))
+}
+
+/// Fails with [`conflict`] if the newest version of `id`
+/// [`supersedes`] the one the write was based on.
+pub(crate) async fn check_unchanged<R: Repository + ?Sized>(
+    repo: &R,
+    id: &str,
+    expected_created_at: Option<DateTime<Utc>>,
+) -> Result<()> {
+    let latest = repo.read_raw(id, &["*".to_string()], None).await?;
+    match latest {
+        Some(latest)
+            if supersedes(
+                latest.created_at.timestamp_millis(),
+                latest.deleted,
+                expected_created_at,
+            ) =>
+        {
+            Err(conflict(id))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Held across the check and the write of a default conditional write, so two
+/// to the same id of the same repository in this process can't both pass
+/// their check. Writes to other ids or repositories don't wait on it.
+pub(crate) struct ConditionalWriteLock {
+    key: LockKey,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl ConditionalWriteLock {
+    pub(crate) async fn acquire<R: ?Sized>(repo: &R, id: &str) -> Self {
+        let key = (repo as *const R as *const () as usize, id.to_string());
+        let lock = CONDITIONAL_WRITES
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        Self {
+            key,
+            guard: Some(lock.lock_owned().await),
+        }
+    }
+}
+
+impl Drop for ConditionalWriteLock {
+    fn drop(&mut self) {
+        let mut locks = CONDITIONAL_WRITES.lock().unwrap();
+        self.guard = None;
+        // Forget the id's lock once no other write holds or waits on it.
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_newer_versions_supersede_an_expected_one() {
+        let expected = DateTime::from_timestamp_millis(1_000);
+        assert!(!supersedes(999, false, expected));
+        assert!(!supersedes(1_000, false, expected));
+        assert!(supersedes(1_001, false, expected));
+        assert!(supersedes(1_001, true, expected));
+    }
+
+    #[test]
+    fn only_live_versions_supersede_none() {
+        assert!(!supersedes(1_000, true, None));
+        assert!(supersedes(1_000, false, None));
+    }
+
+    #[tokio::test]
+    async fn locks_are_per_repository_and_id_and_forgotten_once_released() {
+        let (first, second) = (1u8, 2u8);
+        let held = ConditionalWriteLock::acquire(&first, "a").await;
+        // Neither of these waits on the lock already held.
+        let other_id = ConditionalWriteLock::acquire(&first, "b").await;
+        let other_repo = ConditionalWriteLock::acquire(&second, "a").await;
+        drop((other_id, other_repo));
+
+        let key = held.key.clone();
+        drop(held);
+        assert!(!CONDITIONAL_WRITES.lock().unwrap().contains_key(&key));
+    }
+}
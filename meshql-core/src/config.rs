@@ -1,14 +1,21 @@
-use crate::{Repository, Searcher};
+use crate::{
+    AuthPolicy, IdStrategy, MeshqlError, PayloadCodec, Repository, Result, Searcher, Stash,
+};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct QueryConfig {
     pub name: String,
     pub template: String,
     pub is_singleton: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct SingletonResolverConfig {
     pub field_name: String,
     pub foreign_key: Option<String>,
@@ -16,7 +23,7 @@ pub struct SingletonResolverConfig {
     pub url: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct VectorResolverConfig {
     pub field_name: String,
     pub foreign_key: Option<String>,
@@ -24,29 +31,222 @@ pub struct VectorResolverConfig {
     pub url: String,
 }
 
-#[derive(Debug, Clone)]
+/// Which parent fields a relation passes to its query template, keyed by the
+/// placeholder each one fills.
+///
+/// A single foreign key, or none for the parent's own `id`, fills `{{id}}`:
+///
+/// ```
+/// # use meshql_core::ForeignKeys;
+/// assert_eq!(ForeignKeys::from(Some("coop_id")), ForeignKeys::from([("id", "coop_id")]));
+/// assert_eq!(ForeignKeys::from(None), ForeignKeys::from([("id", "id")]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKeys(BTreeMap<String, String>);
+
+impl ForeignKeys {
+    /// The parent fields read, in placeholder order.
+    pub fn parent_fields(&self) -> impl Iterator<Item = &str> {
+        self.0.values().map(String::as_str)
+    }
+
+    /// The parent field filling `{{id}}`, when that is the only placeholder.
+    pub fn single(&self) -> Option<&str> {
+        match self.0.iter().next() {
+            Some((placeholder, field)) if self.0.len() == 1 && placeholder == "id" => Some(field),
+            _ => None,
+        }
+    }
+
+    /// The template args for `parent`, or `None` if it lacks any of the fields.
+    pub fn args(&self, parent: &Stash) -> Option<Stash> {
+        self.0
+            .iter()
+            .map(|(placeholder, field)| match parent.get(field) {
+                None | Some(Value::Null) => None,
+                Some(Value::String(s)) if s.is_empty() => None,
+                Some(v) => Some((placeholder.clone(), v.clone())),
+            })
+            .collect()
+    }
+}
+
+impl From<Option<&str>> for ForeignKeys {
+    fn from(foreign_key: Option<&str>) -> Self {
+        Self::from([("id", foreign_key.unwrap_or("id"))])
+    }
+}
+
+impl<const N: usize> From<[(&str, &str); N]> for ForeignKeys {
+    fn from(keys: [(&str, &str); N]) -> Self {
+        Self(
+            keys.into_iter()
+                .map(|(placeholder, field)| (placeholder.to_string(), field.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl From<BTreeMap<String, String>> for ForeignKeys {
+    fn from(keys: BTreeMap<String, String>) -> Self {
+        Self(keys)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct InternalSingletonResolverConfig {
     pub field_name: String,
-    pub foreign_key: Option<String>,
+    pub foreign_keys: ForeignKeys,
     pub query_name: String,
     pub graphlette_path: String,
+    /// Constant args for the query template's other placeholders, e.g.
+    /// `{"status": "active"}` for `{{status}}`. Foreign keys win a clash.
+    pub extra_args: Stash,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct InternalVectorResolverConfig {
     pub field_name: String,
     pub foreign_key: Option<String>,
     pub query_name: String,
     pub graphlette_path: String,
+    /// Constant args for the query template's other placeholders, e.g.
+    /// `{"status": "active"}` for `{{status}}`. The foreign key wins a clash.
+    pub extra_args: Stash,
+}
+
+/// A field only callers holding `token` may read, e.g. a farm's `owner`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestrictedField {
+    /// The SDL object type declaring the field, e.g. `Farm`.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub field: String,
+    pub token: String,
+}
+
+/// What callers without its token get for a [`RestrictedField`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldDenial {
+    /// Null, as if the record had no value there. A non-null field still
+    /// fails, as any null does.
+    #[default]
+    Null,
+    /// A `NOT_AUTHORIZED` error on the field.
+    Error,
+}
+
+/// A scalar field computed from two numeric payload fields rather than
+/// stored, e.g. a farm output's `eggsPerHen` from `eggs_week / active_hens`.
+/// It's null when either is missing or not a number, or when dividing by zero.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ComputedField {
+    /// The SDL object type declaring the field, e.g. `FarmOutput`.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub field: String,
+    pub left: String,
+    pub op: Operator,
+    pub right: String,
 }
 
-#[derive(Debug, Clone, Default)]
+/// The arithmetic a [`ComputedField`] applies, written `+`, `-`, `*` or `/`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Operator {
+    #[serde(rename = "+")]
+    Add,
+    #[serde(rename = "-")]
+    Subtract,
+    #[serde(rename = "*")]
+    Multiply,
+    #[serde(rename = "/")]
+    Divide,
+}
+
+impl Operator {
+    /// `left` and `right` combined, or `None` for a division by zero or a
+    /// result that isn't a finite number. Integers give an integer unless
+    /// divided, so declare quotients `Float`.
+    pub fn apply(
+        &self,
+        left: &serde_json::Number,
+        right: &serde_json::Number,
+    ) -> Option<serde_json::Value> {
+        if let (Some(l), Some(r), false) =
+            (left.as_i64(), right.as_i64(), *self == Operator::Divide)
+        {
+            let result = match self {
+                Operator::Add => l.checked_add(r),
+                Operator::Subtract => l.checked_sub(r),
+                _ => l.checked_mul(r),
+            };
+            if let Some(result) = result {
+                return Some(result.into());
+            }
+        }
+        let (l, r) = (left.as_f64()?, right.as_f64()?);
+        let result = match self {
+            Operator::Add => l + r,
+            Operator::Subtract => l - r,
+            Operator::Multiply => l * r,
+            Operator::Divide if r == 0.0 => return None,
+            Operator::Divide => l / r,
+        };
+        serde_json::Number::from_f64(result).map(serde_json::Value::Number)
+    }
+}
+
+/// The value a scalar field resolves to when the payload has none, e.g. `0`
+/// for a count that older records never stored.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FieldDefault {
+    /// The SDL object type declaring the field, e.g. `Hen`.
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub field: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct RootConfig {
     pub queries: Vec<QueryConfig>,
     pub singleton_resolvers: Vec<SingletonResolverConfig>,
     pub vector_resolvers: Vec<VectorResolverConfig>,
     pub internal_singleton_resolvers: Vec<InternalSingletonResolverConfig>,
     pub internal_vector_resolvers: Vec<InternalVectorResolverConfig>,
+    /// How often `Subscription` fields re-run their query; one second when unset.
+    pub subscription_interval: Option<Duration>,
+    /// How deeply a query may nest fields, bounding how far relations that
+    /// lead back to their own type (farm → coops → farm …) are followed.
+    pub max_depth: Option<usize>,
+    /// How costly a query may be, counting each scalar field as 1, each
+    /// relation as more and multiplying whatever a list field selects.
+    /// Unlimited when `None`.
+    pub max_complexity: Option<usize>,
+    /// How long an operation may run before it is abandoned, along with the
+    /// backend calls it was waiting on. Unlimited when `None`.
+    pub timeout: Option<Duration>,
+    /// Arguments whose values are left out of operation logs, e.g. `password`.
+    pub redacted_arguments: Vec<String>,
+    /// Reject `__schema` and `__type` queries as asking for unknown fields,
+    /// rather than answering them with the schema.
+    pub disable_introspection: bool,
+    /// The only operations this graphlette runs, when any are listed. Clients
+    /// send one's text or, as Apollo persisted queries, its SHA-256 hash.
+    pub persisted_queries: Vec<String>,
+    /// Fields hidden from callers lacking a token, on top of the records
+    /// their tokens already hide.
+    pub restricted_fields: Vec<RestrictedField>,
+    pub field_denial: FieldDenial,
+    pub computed_fields: Vec<ComputedField>,
+    pub field_defaults: Vec<FieldDefault>,
+    /// `Cache-Control` sent with successful responses to queries made with
+    /// `GET`, e.g. `public, max-age=60` to let a CDN cache them.
+    pub get_cache_control: Option<String>,
+    /// Queries whose fields fail when called without an argument their
+    /// template names, rather than rendering it empty as searchers do.
+    pub strict_queries: Vec<String>,
 }
 
 impl RootConfig {
@@ -120,30 +320,72 @@ impl RootConfigBuilder {
         self
     }
 
+    /// `foreign_keys` is a single optional foreign key, as for the other
+    /// resolvers, or `[(placeholder, parent_field), ...]` for a template
+    /// with several placeholders.
     pub fn internal_singleton_resolver(
+        self,
+        field_name: impl Into<String>,
+        foreign_keys: impl Into<ForeignKeys>,
+        query_name: impl Into<String>,
+        graphlette_path: impl Into<String>,
+    ) -> Self {
+        self.internal_singleton_resolver_filtered(
+            field_name,
+            foreign_keys,
+            query_name,
+            graphlette_path,
+            Stash::new(),
+        )
+    }
+
+    /// [`Self::internal_singleton_resolver`], also passing `extra_args` to the
+    /// query template, e.g. to only follow the relation to active records.
+    pub fn internal_singleton_resolver_filtered(
         mut self,
         field_name: impl Into<String>,
-        foreign_key: Option<&str>,
+        foreign_keys: impl Into<ForeignKeys>,
         query_name: impl Into<String>,
         graphlette_path: impl Into<String>,
+        extra_args: Stash,
     ) -> Self {
         self.config
             .internal_singleton_resolvers
             .push(InternalSingletonResolverConfig {
                 field_name: field_name.into(),
-                foreign_key: foreign_key.map(String::from),
+                foreign_keys: foreign_keys.into(),
                 query_name: query_name.into(),
                 graphlette_path: graphlette_path.into(),
+                extra_args,
             });
         self
     }
 
     pub fn internal_vector_resolver(
+        self,
+        field_name: impl Into<String>,
+        foreign_key: Option<&str>,
+        query_name: impl Into<String>,
+        graphlette_path: impl Into<String>,
+    ) -> Self {
+        self.internal_vector_resolver_filtered(
+            field_name,
+            foreign_key,
+            query_name,
+            graphlette_path,
+            Stash::new(),
+        )
+    }
+
+    /// [`Self::internal_vector_resolver`], also passing `extra_args` to the
+    /// query template, e.g. `{"status": "active"}` for a farm's active hens.
+    pub fn internal_vector_resolver_filtered(
         mut self,
         field_name: impl Into<String>,
         foreign_key: Option<&str>,
         query_name: impl Into<String>,
         graphlette_path: impl Into<String>,
+        extra_args: Stash,
     ) -> Self {
         self.config
             .internal_vector_resolvers
@@ -152,10 +394,114 @@ impl RootConfigBuilder {
                 foreign_key: foreign_key.map(String::from),
                 query_name: query_name.into(),
                 graphlette_path: graphlette_path.into(),
+                extra_args,
             });
         self
     }
 
+    pub fn subscription_interval(mut self, interval: Duration) -> Self {
+        self.config.subscription_interval = Some(interval);
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.config.max_depth = Some(depth);
+        self
+    }
+
+    pub fn max_complexity(mut self, complexity: usize) -> Self {
+        self.config.max_complexity = Some(complexity);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    pub fn redact_argument(mut self, name: impl Into<String>) -> Self {
+        self.config.redacted_arguments.push(name.into());
+        self
+    }
+
+    pub fn disable_introspection(mut self) -> Self {
+        self.config.disable_introspection = true;
+        self
+    }
+
+    /// Fail `query`'s fields when an argument its template names is missing.
+    pub fn strict(mut self, query: impl Into<String>) -> Self {
+        self.config.strict_queries.push(query.into());
+        self
+    }
+
+    /// Allow `document`, locking the graphlette down to the operations
+    /// allowed this way. Other graphlettes' resolvers query it too, so
+    /// allow theirs as well.
+    pub fn persisted_query(mut self, document: impl Into<String>) -> Self {
+        self.config.persisted_queries.push(document.into());
+        self
+    }
+
+    pub fn get_cache_control(mut self, value: impl Into<String>) -> Self {
+        self.config.get_cache_control = Some(value.into());
+        self
+    }
+
+    /// Only let callers holding `token` (or `*`) read `field` of `type_name`.
+    pub fn restrict_field(
+        mut self,
+        type_name: impl Into<String>,
+        field: impl Into<String>,
+        token: impl Into<String>,
+    ) -> Self {
+        self.config.restricted_fields.push(RestrictedField {
+            type_name: type_name.into(),
+            field: field.into(),
+            token: token.into(),
+        });
+        self
+    }
+
+    pub fn field_denial(mut self, denial: FieldDenial) -> Self {
+        self.config.field_denial = denial;
+        self
+    }
+
+    /// Resolve `field` of `type_name` as `left op right`, from the payload.
+    pub fn computed_field(
+        mut self,
+        type_name: impl Into<String>,
+        field: impl Into<String>,
+        left: impl Into<String>,
+        op: Operator,
+        right: impl Into<String>,
+    ) -> Self {
+        self.config.computed_fields.push(ComputedField {
+            type_name: type_name.into(),
+            field: field.into(),
+            left: left.into(),
+            op,
+            right: right.into(),
+        });
+        self
+    }
+
+    /// Resolve scalar `field` of `type_name` as `value` when the payload has none.
+    pub fn field_default(
+        mut self,
+        type_name: impl Into<String>,
+        field: impl Into<String>,
+        value: serde_json::Value,
+    ) -> Self {
+        self.config.field_defaults.push(FieldDefault {
+            type_name: type_name.into(),
+            field: field.into(),
+            value,
+        });
+        self
+    }
+
     pub fn build(self) -> RootConfig {
         self.config
     }
@@ -172,10 +518,790 @@ pub struct RestletteConfig {
     pub path: String,
     pub schema_json: serde_json::Value,
     pub repository: Arc<dyn Repository>,
+    pub delete_mode: DeleteMode,
+}
+
+/// What `DELETE` does to a restlette's items.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteMode {
+    /// Write a tombstone, keeping the item's history.
+    #[default]
+    Soft,
+    /// [`Repository::purge`] every version, so not even its history remains,
+    /// e.g. for drafts.
+    Hard,
+    /// Refuse with 405, for items that must never be removed, like events.
+    Forbidden,
 }
 
 pub struct ServerConfig {
     pub port: u16,
     pub graphlettes: Vec<GraphletteConfig>,
     pub restlettes: Vec<RestletteConfig>,
+    /// Cross-origin policy; `None` allows any origin, method and header.
+    pub cors: Option<CorsConfig>,
+    /// Largest request body accepted, in bytes; bigger ones get a 413.
+    /// [`DEFAULT_MAX_BODY_BYTES`] when `None`.
+    pub max_body_bytes: Option<usize>,
+}
+
+/// The request body cap when [`ServerConfig::max_body_bytes`] isn't set: 2 MiB.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Which cross-origin requests browsers may make. Origins must be listed;
+/// unset methods and headers mirror whatever the preflight asks for.
+///
+/// ```yaml
+/// cors:
+///   allow_origins: ["https://farm.example.com"]
+///   allow_methods: ["GET", "POST"]
+///   allow_credentials: true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct CorsConfig {
+    /// Exact origins, or `*` for any.
+    #[serde(default)]
+    pub allow_origins: Vec<String>,
+    #[serde(default)]
+    pub allow_methods: Vec<String>,
+    #[serde(default)]
+    pub allow_headers: Vec<String>,
+    #[serde(default)]
+    pub allow_credentials: bool,
+}
+
+impl CorsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.allow_origins.push(origin.into());
+        self
+    }
+
+    pub fn allow_method(mut self, method: impl Into<String>) -> Self {
+        self.allow_methods.push(method.into());
+        self
+    }
+
+    pub fn allow_header(mut self, header: impl Into<String>) -> Self {
+        self.allow_headers.push(header.into());
+        self
+    }
+
+    pub fn allow_credentials(mut self, allow: bool) -> Self {
+        self.allow_credentials = allow;
+        self
+    }
+
+    /// Reject policies browsers would refuse: no origins at all, or credentials
+    /// alongside a `*` wildcard.
+    pub fn validate(&self) -> Result<()> {
+        if self.allow_origins.is_empty() {
+            return Err(MeshqlError::Validation(
+                "CORS config must list at least one origin".to_string(),
+            ));
+        }
+        if self.allow_credentials {
+            let wildcard = [
+                ("origins", &self.allow_origins),
+                ("methods", &self.allow_methods),
+                ("headers", &self.allow_headers),
+            ]
+            .into_iter()
+            .find(|(_, values)| values.iter().any(|v| v == "*"));
+            if let Some((field, _)) = wildcard {
+                return Err(MeshqlError::Validation(format!(
+                    "CORS allow_credentials can't be combined with '*' {field}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ---- Declarative manifests ----
+
+/// A server described in a YAML or JSON file rather than in Rust.
+///
+/// Storage is only described here; a [`BackendFactory`] per backend turns each
+/// [`StorageManifest`] into a live `Repository` or `Searcher`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ServerConfigManifest {
+    pub port: u16,
+    #[serde(default)]
+    pub graphlettes: Vec<GraphletteManifest>,
+    #[serde(default)]
+    pub restlettes: Vec<RestletteManifest>,
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// Raise for bulk-ingest deployments; 2 MiB when unset.
+    #[serde(default)]
+    pub max_body_bytes: Option<usize>,
+    /// Applied to every storage `collection`, so deployments can share a database.
+    #[serde(default)]
+    pub namespace: Namespace,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GraphletteManifest {
+    pub path: String,
+    /// GraphQL SDL file, relative to the manifest.
+    pub schema: PathBuf,
+    /// Contents of `schema`, filled in by [`load_from_file`].
+    #[serde(skip)]
+    pub schema_text: String,
+    pub storage: StorageManifest,
+    #[serde(default)]
+    pub queries: Vec<QueryManifest>,
+    #[serde(default)]
+    pub resolvers: Vec<ResolverManifest>,
+    #[serde(
+        default,
+        rename = "subscription_interval_secs",
+        deserialize_with = "secs"
+    )]
+    pub subscription_interval: Option<Duration>,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub max_complexity: Option<usize>,
+    #[serde(default, rename = "timeout_ms", deserialize_with = "millis")]
+    pub timeout: Option<Duration>,
+    #[serde(default)]
+    pub redacted_arguments: Vec<String>,
+    #[serde(default = "enabled")]
+    pub enable_introspection: bool,
+    #[serde(default)]
+    pub persisted_queries: Vec<String>,
+    #[serde(default)]
+    pub get_cache_control: Option<String>,
+    #[serde(default)]
+    pub restricted_fields: Vec<RestrictedField>,
+    #[serde(default)]
+    pub field_denial: FieldDenial,
+    #[serde(default)]
+    pub computed_fields: Vec<ComputedField>,
+    #[serde(default)]
+    pub field_defaults: Vec<FieldDefault>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestletteManifest {
+    pub path: String,
+    /// JSON schema file, relative to the manifest.
+    #[serde(default)]
+    pub schema: Option<PathBuf>,
+    /// Contents of `schema` (or `{}` without one), filled in by [`load_from_file`].
+    #[serde(skip)]
+    pub schema_json: serde_json::Value,
+    pub storage: StorageManifest,
+    #[serde(default)]
+    pub delete_mode: DeleteMode,
+}
+
+/// Where an entity's envelopes live.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StorageManifest {
+    /// Name of the [`BackendFactory`] that opens this storage, e.g. `mongo`.
+    pub backend: String,
+    /// Connection string; `${VAR}` and `${VAR:-default}` are read from the environment.
+    pub uri: String,
+    #[serde(default)]
+    pub database: Option<String>,
+    /// Collection, table or topic holding this entity.
+    pub collection: String,
+    /// Connection pool sizing, for backends that pool connections.
+    #[serde(default)]
+    pub pool: PoolConfig,
+    /// How ids are generated for envelopes created without one.
+    #[serde(default)]
+    pub id_strategy: IdStrategy,
+    /// How the SQL backends' repositories write payloads.
+    #[serde(default)]
+    pub payload_codec: PayloadCodec,
+    /// How repositories match callers' tokens against envelopes'.
+    #[serde(default)]
+    pub auth_policy: AuthPolicy,
+    /// TLS for the mongo and postgres backends, applied over whatever `uri` sets.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// Connection pool sizing for the SQL backends. Unset fields keep the driver's defaults.
+///
+/// In a manifest the timeouts are whole seconds:
+///
+/// ```yaml
+/// pool: { max_connections: 16, acquire_timeout_secs: 5, idle_timeout_secs: 600 }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct PoolConfig {
+    #[serde(default)]
+    pub max_connections: Option<u32>,
+    /// Longest a query waits for a free connection before failing.
+    #[serde(default, rename = "acquire_timeout_secs", deserialize_with = "secs")]
+    pub acquire_timeout: Option<Duration>,
+    /// How long an unused connection is kept open.
+    #[serde(default, rename = "idle_timeout_secs", deserialize_with = "secs")]
+    pub idle_timeout: Option<Duration>,
+}
+
+impl PoolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = Some(timeout);
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+}
+
+/// TLS settings for a database connection, independent of the connection string,
+/// e.g. for mutual TLS to a managed database. Paths are PEM files.
+///
+/// ```yaml
+/// tls: { mode: verify_full, ca_file: /etc/ssl/db-ca.pem, client_cert: client.pem, client_key: client.key }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub mode: TlsMode,
+    /// Certificate authority the server's certificate must chain to.
+    #[serde(default)]
+    pub ca_file: Option<PathBuf>,
+    /// Certificate presented to the server. Mongo wants the private key in the same file.
+    #[serde(default)]
+    pub client_cert: Option<PathBuf>,
+    /// Private key for `client_cert`.
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    pub fn new(mode: TlsMode) -> Self {
+        Self {
+            mode,
+            ..Self::default()
+        }
+    }
+
+    pub fn ca_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_file = Some(path.into());
+        self
+    }
+
+    pub fn client_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.client_cert = Some(path.into());
+        self
+    }
+
+    pub fn client_key(mut self, path: impl Into<PathBuf>) -> Self {
+        self.client_key = Some(path.into());
+        self
+    }
+}
+
+/// How much of the server's identity a TLS connection checks, after Postgres' `sslmode`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    /// Plain connections.
+    Disable,
+    /// Encrypt, but accept any certificate.
+    Require,
+    /// Check the certificate chains to a trusted CA, but not its hostname.
+    VerifyCa,
+    /// Check the certificate chain and that it names the host.
+    #[default]
+    VerifyFull,
+}
+
+/// Names each entity's collection, table or topic `{prefix}_{entity}_{suffix}`,
+/// leaving out whichever part isn't set, so several deployments or
+/// environments can keep their entities apart in one database.
+///
+/// ```yaml
+/// namespace: { prefix: egg_economy, suffix: staging }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Namespace {
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub suffix: Option<String>,
+}
+
+impl Namespace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// The namespaced name of `entity`.
+    pub fn name(&self, entity: &str) -> String {
+        [self.prefix.as_deref(), Some(entity), self.suffix.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+}
+
+fn secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_secs))
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_millis))
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct QueryManifest {
+    pub name: String,
+    pub template: String,
+    #[serde(default)]
+    pub singleton: bool,
+    /// See [`RootConfigBuilder::strict`].
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// One relation field, mirroring the [`RootConfigBuilder`] resolver methods.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ResolverManifest {
+    Singleton {
+        field: String,
+        #[serde(default)]
+        foreign_key: Option<String>,
+        query: String,
+        url: String,
+    },
+    Vector {
+        field: String,
+        #[serde(default)]
+        foreign_key: Option<String>,
+        query: String,
+        url: String,
+    },
+    InternalSingleton {
+        field: String,
+        #[serde(default)]
+        foreign_key: Option<String>,
+        /// `{placeholder: parent_field}`, in place of `foreign_key`.
+        #[serde(default)]
+        foreign_keys: Option<BTreeMap<String, String>>,
+        query: String,
+        graphlette: String,
+        #[serde(default)]
+        extra_args: Stash,
+    },
+    InternalVector {
+        field: String,
+        #[serde(default)]
+        foreign_key: Option<String>,
+        query: String,
+        graphlette: String,
+        #[serde(default)]
+        extra_args: Stash,
+    },
+}
+
+impl GraphletteManifest {
+    pub fn root_config(&self) -> RootConfig {
+        let mut builder = RootConfig::builder();
+        for q in &self.queries {
+            builder = if q.singleton {
+                builder.singleton(&q.name, &q.template)
+            } else {
+                builder.vector(&q.name, &q.template)
+            };
+            if q.strict {
+                builder = builder.strict(&q.name);
+            }
+        }
+        for r in &self.resolvers {
+            builder = match r {
+                ResolverManifest::Singleton {
+                    field,
+                    foreign_key,
+                    query,
+                    url,
+                } => builder.singleton_resolver(field, foreign_key.as_deref(), query, url),
+                ResolverManifest::Vector {
+                    field,
+                    foreign_key,
+                    query,
+                    url,
+                } => builder.vector_resolver(field, foreign_key.as_deref(), query, url),
+                ResolverManifest::InternalSingleton {
+                    field,
+                    foreign_key,
+                    foreign_keys,
+                    query,
+                    graphlette,
+                    extra_args,
+                } => builder.internal_singleton_resolver_filtered(
+                    field,
+                    match foreign_keys {
+                        Some(keys) => ForeignKeys::from(keys.clone()),
+                        None => ForeignKeys::from(foreign_key.as_deref()),
+                    },
+                    query,
+                    graphlette,
+                    extra_args.clone(),
+                ),
+                ResolverManifest::InternalVector {
+                    field,
+                    foreign_key,
+                    query,
+                    graphlette,
+                    extra_args,
+                } => builder.internal_vector_resolver_filtered(
+                    field,
+                    foreign_key.as_deref(),
+                    query,
+                    graphlette,
+                    extra_args.clone(),
+                ),
+            };
+        }
+        if let Some(interval) = self.subscription_interval {
+            builder = builder.subscription_interval(interval);
+        }
+        if let Some(depth) = self.max_depth {
+            builder = builder.max_depth(depth);
+        }
+        if let Some(complexity) = self.max_complexity {
+            builder = builder.max_complexity(complexity);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        for name in &self.redacted_arguments {
+            builder = builder.redact_argument(name);
+        }
+        if !self.enable_introspection {
+            builder = builder.disable_introspection();
+        }
+        for document in &self.persisted_queries {
+            builder = builder.persisted_query(document);
+        }
+        if let Some(value) = &self.get_cache_control {
+            builder = builder.get_cache_control(value);
+        }
+        for restricted in &self.restricted_fields {
+            builder =
+                builder.restrict_field(&restricted.type_name, &restricted.field, &restricted.token);
+        }
+        for computed in &self.computed_fields {
+            builder = builder.computed_field(
+                &computed.type_name,
+                &computed.field,
+                &computed.left,
+                computed.op,
+                &computed.right,
+            );
+        }
+        for default in &self.field_defaults {
+            builder =
+                builder.field_default(&default.type_name, &default.field, default.value.clone());
+        }
+        builder.field_denial(self.field_denial).build()
+    }
+}
+
+/// Opens the storage of every manifest entry whose `backend` matches [`name`](Self::name).
+#[async_trait::async_trait]
+pub trait BackendFactory: Send + Sync {
+    fn name(&self) -> &str;
+    async fn repository(&self, storage: &StorageManifest) -> Result<Arc<dyn Repository>>;
+    async fn searcher(&self, storage: &StorageManifest) -> Result<Arc<dyn Searcher>>;
+}
+
+/// Load a server manifest, reading the schema files it references and
+/// interpolating environment variables into storage URIs.
+///
+/// Files ending in `.yaml`/`.yml` are parsed as YAML (with the `yaml` feature);
+/// anything else as JSON.
+pub fn load_from_file(path: impl AsRef<Path>) -> Result<ServerConfigManifest> {
+    let path = path.as_ref();
+    let text = read_file(path)?;
+    let mut manifest: ServerConfigManifest = match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => parse_yaml(&text)?,
+        _ => serde_json::from_str(&text).map_err(|e| MeshqlError::Parse(e.to_string()))?,
+    };
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    for g in &mut manifest.graphlettes {
+        g.schema_text = read_file(&base.join(&g.schema))?;
+        g.storage.uri = interpolate_env(&g.storage.uri)?;
+    }
+    for r in &mut manifest.restlettes {
+        r.schema_json = match &r.schema {
+            Some(schema) => serde_json::from_str(&read_file(&base.join(schema))?)
+                .map_err(|e| MeshqlError::Parse(format!("{}: {e}", schema.display())))?,
+            None => serde_json::json!({}),
+        };
+        r.storage.uri = interpolate_env(&r.storage.uri)?;
+    }
+    if let Some(cors) = &manifest.cors {
+        cors.validate()?;
+    }
+    Ok(manifest)
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| MeshqlError::Storage(format!("{}: {e}", path.display())))
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml(text: &str) -> Result<ServerConfigManifest> {
+    serde_yaml::from_str(text).map_err(|e| MeshqlError::Parse(e.to_string()))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn parse_yaml(_text: &str) -> Result<ServerConfigManifest> {
+    Err(MeshqlError::Parse(
+        "YAML manifests require the `yaml` feature of meshql-core".to_string(),
+    ))
+}
+
+/// Replace `${VAR}` with the environment variable `VAR`, or with `default` for
+/// `${VAR:-default}` when `VAR` is unset.
+pub fn interpolate_env(value: &str) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            MeshqlError::Parse(format!("Unterminated variable reference in '{value}'"))
+        })? + start;
+        let (name, default) = match rest[start + 2..end].split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (&rest[start + 2..end], None),
+        };
+        match (std::env::var(name), default) {
+            (Ok(v), _) => out.push_str(&v),
+            (Err(_), Some(d)) => out.push_str(d),
+            (Err(_), None) => {
+                return Err(MeshqlError::Validation(format!(
+                    "Environment variable {name} is not set"
+                )))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_env_vars_and_defaults() {
+        std::env::set_var("MESHQL_TEST_DB_HOST", "db.internal");
+        std::env::remove_var("MESHQL_TEST_DB_PORT");
+        assert_eq!(
+            interpolate_env("mongodb://${MESHQL_TEST_DB_HOST}:${MESHQL_TEST_DB_PORT:-27017}/x")
+                .unwrap(),
+            "mongodb://db.internal:27017/x"
+        );
+        assert_eq!(
+            interpolate_env("sqlite::memory:").unwrap(),
+            "sqlite::memory:"
+        );
+        assert!(interpolate_env("${MESHQL_TEST_DB_PORT}").is_err());
+        assert!(interpolate_env("${MESHQL_TEST_DB_HOST").is_err());
+    }
+
+    #[test]
+    fn parses_resolvers_into_a_root_config() {
+        let manifest: GraphletteManifest = serde_json::from_value(serde_json::json!({
+            "path": "/hen/graph",
+            "schema": "graph/hen.graphql",
+            "storage": {"backend": "sqlite", "uri": "sqlite::memory:", "collection": "hens"},
+            "queries": [
+                {"name": "getHen", "template": "{\"id\": \"{{id}}\"}", "singleton": true,
+                 "strict": true},
+                {"name": "getHensByCoop", "template": "{\"payload.coopId\": \"{{id}}\"}"}
+            ],
+            "resolvers": [
+                {"kind": "singleton", "field": "coop", "foreign_key": "coopId",
+                 "query": "getCoop", "url": "/coop/graph"},
+                {"kind": "internal_vector", "field": "layReports",
+                 "query": "getLayReportsByHen", "graphlette": "/lay_report/graph",
+                 "extra_args": {"quality": "grade_a"}},
+                {"kind": "internal_singleton", "field": "stock",
+                 "foreign_keys": {"coop": "coopId", "kind": "kind"},
+                 "query": "getStock", "graphlette": "/stock/graph"}
+            ],
+            "subscription_interval_secs": 5,
+            "max_depth": 12,
+            "max_complexity": 500,
+            "timeout_ms": 2500,
+            "redacted_arguments": ["token"],
+            "enable_introspection": false,
+            "persisted_queries": ["{ getHen(id: \"h-1\") { id } }"],
+            "get_cache_control": "public, max-age=60",
+            "restricted_fields": [{"type": "Hen", "field": "owner", "token": "admin"}],
+            "field_denial": "error",
+            "computed_fields": [{"type": "Hen", "field": "eggsPerDay",
+                                 "left": "eggs_week", "op": "/", "right": "days"}],
+            "field_defaults": [{"type": "Hen", "field": "eggs_week", "value": 0}]
+        }))
+        .unwrap();
+
+        let expected = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .strict("getHen")
+            .vector("getHensByCoop", r#"{"payload.coopId": "{{id}}"}"#)
+            .singleton_resolver("coop", Some("coopId"), "getCoop", "/coop/graph")
+            .internal_vector_resolver_filtered(
+                "layReports",
+                None,
+                "getLayReportsByHen",
+                "/lay_report/graph",
+                serde_json::json!({"quality": "grade_a"})
+                    .as_object()
+                    .cloned()
+                    .unwrap(),
+            )
+            .internal_singleton_resolver(
+                "stock",
+                [("coop", "coopId"), ("kind", "kind")],
+                "getStock",
+                "/stock/graph",
+            )
+            .subscription_interval(Duration::from_secs(5))
+            .max_depth(12)
+            .max_complexity(500)
+            .timeout(Duration::from_millis(2500))
+            .redact_argument("token")
+            .disable_introspection()
+            .persisted_query(r#"{ getHen(id: "h-1") { id } }"#)
+            .get_cache_control("public, max-age=60")
+            .restrict_field("Hen", "owner", "admin")
+            .field_denial(FieldDenial::Error)
+            .computed_field("Hen", "eggsPerDay", "eggs_week", Operator::Divide, "days")
+            .field_default("Hen", "eggs_week", serde_json::json!(0))
+            .build();
+        assert_eq!(manifest.root_config(), expected);
+    }
+
+    #[test]
+    fn parses_pool_config_from_storage() {
+        let storage: StorageManifest = serde_json::from_value(serde_json::json!({
+            "backend": "postgres",
+            "uri": "postgres://localhost/farm",
+            "collection": "hens",
+            "pool": {"max_connections": 16, "acquire_timeout_secs": 5}
+        }))
+        .unwrap();
+        assert_eq!(
+            storage.pool,
+            PoolConfig::new()
+                .max_connections(16)
+                .acquire_timeout(Duration::from_secs(5))
+        );
+
+        let storage: StorageManifest = serde_json::from_value(serde_json::json!({
+            "backend": "sqlite", "uri": "sqlite::memory:", "collection": "hens"
+        }))
+        .unwrap();
+        assert_eq!(storage.pool, PoolConfig::default());
+        assert_eq!(storage.id_strategy, IdStrategy::Uuid4);
+    }
+
+    #[test]
+    fn parses_tls_config_from_storage() {
+        let storage: StorageManifest = serde_json::from_value(serde_json::json!({
+            "backend": "postgres",
+            "uri": "postgres://localhost/farm",
+            "collection": "hens",
+            "tls": {"ca_file": "/etc/ssl/db-ca.pem", "client_cert": "client.pem", "client_key": "client.key"}
+        }))
+        .unwrap();
+        assert_eq!(
+            storage.tls,
+            Some(
+                TlsConfig::new(TlsMode::VerifyFull)
+                    .ca_file("/etc/ssl/db-ca.pem")
+                    .client_cert("client.pem")
+                    .client_key("client.key")
+            )
+        );
+
+        let storage: StorageManifest = serde_json::from_value(serde_json::json!({
+            "backend": "postgres", "uri": "postgres://localhost/farm", "collection": "hens",
+            "tls": {"mode": "require"}
+        }))
+        .unwrap();
+        assert_eq!(storage.tls, Some(TlsConfig::new(TlsMode::Require)));
+    }
+
+    #[test]
+    fn parses_id_strategy_from_storage() {
+        let storage: StorageManifest = serde_json::from_value(serde_json::json!({
+            "backend": "sqlite", "uri": "sqlite::memory:", "collection": "hens",
+            "id_strategy": "uuid7"
+        }))
+        .unwrap();
+        assert_eq!(storage.id_strategy, IdStrategy::Uuid7);
+    }
+
+    #[test]
+    fn parses_payload_codec_from_storage() {
+        let storage: StorageManifest = serde_json::from_value(serde_json::json!({
+            "backend": "sqlite", "uri": "sqlite::memory:", "collection": "hens",
+            "payload_codec": "msgpack"
+        }))
+        .unwrap();
+        assert_eq!(storage.payload_codec, PayloadCodec::MessagePack);
+    }
+
+    #[test]
+    fn namespaces_name_entities_with_whichever_parts_are_set() {
+        assert_eq!(Namespace::new().name("hens"), "hens");
+        assert_eq!(Namespace::new().prefix("farm").name("hens"), "farm_hens");
+        assert_eq!(Namespace::new().suffix("dev").name("hens"), "hens_dev");
+        let namespace: Namespace =
+            serde_json::from_value(serde_json::json!({"prefix": "farm", "suffix": "dev"})).unwrap();
+        assert_eq!(namespace.name("hens"), "farm_hens_dev");
+    }
 }
@@ -4,6 +4,9 @@ pub enum MeshqlError {
     NotFound(String),
     #[error("Unauthorized")]
     Unauthorized,
+    /// The caller is authenticated but none of its tokens may write `0`.
+    #[error("Not authorized: {0}")]
+    NotAuthorized(String),
     #[error("Storage error: {0}")]
     Storage(String),
     #[error("Validation error: {0}")]
@@ -12,6 +15,84 @@ pub enum MeshqlError {
     Template(String),
     #[error("Parse error: {0}")]
     Parse(String),
+    #[error("Timed out: {0}")]
+    Timeout(String),
+    /// A conditional write whose record has a newer version than the one the
+    /// caller based it on.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+}
+
+impl MeshqlError {
+    /// A stable, machine-readable name for the variant, for clients to branch on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MeshqlError::NotFound(_) => "NOT_FOUND",
+            MeshqlError::Unauthorized => "UNAUTHENTICATED",
+            MeshqlError::NotAuthorized(_) => "NOT_AUTHORIZED",
+            MeshqlError::Storage(_) => "STORAGE",
+            MeshqlError::Validation(_) => "VALIDATION",
+            MeshqlError::Template(_) => "TEMPLATE",
+            MeshqlError::Parse(_) => "PARSE",
+            MeshqlError::Timeout(_) => "TIMEOUT",
+            MeshqlError::Conflict(_) => "CONFLICT",
+        }
+    }
+
+    /// Whether the same call might succeed if retried. Only storage errors
+    /// qualify; the rest are deterministic given the same input.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, MeshqlError::Storage(_))
+    }
 }
 
 pub type Result<T> = std::result::Result<T, MeshqlError>;
+
+/// Why a [`ServerConfig`](crate::ServerConfig) couldn't be built into an app.
+/// Each variant names the graphlette or restlette path at fault.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Schema build error for {path}: {message}")]
+    SchemaParse { path: String, message: String },
+    /// An internal resolver's `graphlette_path` that no graphlette serves.
+    #[error("Resolver {field} on {path} targets {target}, which no graphlette serves")]
+    UnknownResolverTarget {
+        path: String,
+        field: String,
+        target: String,
+    },
+    /// A resolver's `query_name` that its target graphlette doesn't define.
+    #[error("Resolver {field} on {path} runs {query}, which {target} doesn't define")]
+    UnknownResolverQuery {
+        path: String,
+        field: String,
+        target: String,
+        query: String,
+    },
+    /// A query whose template doesn't compile, so every call to it would fail.
+    #[error("Query {query} on {path} has an invalid template: {message}")]
+    InvalidTemplate {
+        path: String,
+        query: String,
+        message: String,
+    },
+    /// A relation field, as `Type.field`, that no resolver fills in, so it
+    /// would always be null.
+    #[error("{field} on {path} has no resolver")]
+    DanglingRelation { path: String, field: String },
+    /// Two graphlettes or restlettes mounted at the same path, trailing
+    /// slashes aside, or one mounted over a route the other serves, such as
+    /// a graphlette's `/sdl` or a restlette's `/bulk`.
+    #[error("{path} is configured more than once")]
+    DuplicatePath { path: String },
+    /// A graphlette or restlette mounted at a route the app serves itself.
+    #[error("{path} is reserved for the app's own routes")]
+    ReservedPath { path: String },
+    #[error("Invalid restlette {path}: {message}")]
+    Restlette { path: String, message: String },
+    #[error("Invalid CORS config: {0}")]
+    Cors(String),
+    /// A `get_cache_control` that can't be sent as a header value.
+    #[error("Invalid get_cache_control for {path}: {value:?}")]
+    CacheControl { path: String, value: String },
+}
@@ -0,0 +1,90 @@
+//! How repositories name envelopes created without an id.
+
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Crockford's base32 alphabet, as used by ULIDs.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The kind of id a repository assigns when `create` is given an envelope
+/// whose id is empty. An id the caller supplies is always kept.
+///
+/// Time-ordered ids keep inserts near the end of `(id, created_at)` indexes
+/// and sort roughly by creation time. Within one process, ids generated in
+/// the same millisecond still sort in generation order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdStrategy {
+    /// Random UUIDs.
+    #[default]
+    Uuid4,
+    /// Time-ordered UUIDs.
+    Uuid7,
+    /// Time-ordered, 26-character ULIDs.
+    Ulid,
+}
+
+impl IdStrategy {
+    pub fn generate(&self) -> String {
+        match self {
+            IdStrategy::Uuid4 => Uuid::new_v4().to_string(),
+            IdStrategy::Uuid7 => Uuid::now_v7().to_string(),
+            // A v7 UUID leads with the same 48-bit millisecond timestamp as a
+            // ULID, so its bits read as base32 make a ULID in the same order.
+            IdStrategy::Ulid => {
+                let bits = Uuid::now_v7().as_u128();
+                (0..26)
+                    .rev()
+                    .map(|i| CROCKFORD[(bits >> (i * 5)) as usize & 31] as char)
+                    .collect()
+            }
+        }
+    }
+}
+
+/// `value` as the string an envelope's id would be: strings as they are and
+/// numbers, as ids ingested from systems with integer keys often are, in
+/// decimal. Anything else isn't an id.
+pub fn id_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuid7_ids_sort_in_creation_order() {
+        let ids: Vec<String> = (0..1000).map(|_| IdStrategy::Uuid7.generate()).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+        assert!(ids.iter().all(|id| Uuid::parse_str(id).is_ok()));
+    }
+
+    #[test]
+    fn ulids_sort_in_creation_order() {
+        let ids: Vec<String> = (0..1000).map(|_| IdStrategy::Ulid.generate()).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+        assert!(ids.iter().all(|id| id.len() == 26));
+    }
+
+    #[test]
+    fn numeric_ids_read_as_decimal_strings() {
+        assert_eq!(
+            id_string(&serde_json::json!("farm-1")),
+            Some("farm-1".to_string())
+        );
+        assert_eq!(id_string(&serde_json::json!(42)), Some("42".to_string()));
+        assert_eq!(id_string(&serde_json::json!(-7)), Some("-7".to_string()));
+        assert_eq!(id_string(&serde_json::json!(null)), None);
+        assert_eq!(id_string(&serde_json::json!({"id": 1})), None);
+    }
+}
@@ -1,22 +1,57 @@
 pub mod auth;
+pub mod clock;
+pub mod codec;
+pub mod conditional;
 pub mod config;
 pub mod error;
+pub mod id;
+pub mod list;
+pub mod merge;
+pub mod metadata;
+pub mod projection;
+pub mod retry;
+pub mod sort;
+pub mod template;
 pub mod testing;
+pub mod window;
 
-pub use auth::{Auth, NoAuth};
+pub use auth::{Auth, AuthPolicy, JwtAuth, JwtKey, NoAuth};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use codec::PayloadCodec;
+pub use conditional::{conflict, supersedes};
 pub use config::{
-    GraphletteConfig, InternalSingletonResolverConfig, InternalVectorResolverConfig, QueryConfig,
-    RestletteConfig, RootConfig, RootConfigBuilder, ServerConfig, SingletonResolverConfig,
-    VectorResolverConfig,
+    load_from_file, BackendFactory, ComputedField, CorsConfig, DeleteMode, FieldDefault,
+    FieldDenial, ForeignKeys, GraphletteConfig, GraphletteManifest,
+    InternalSingletonResolverConfig, InternalVectorResolverConfig, Namespace, Operator, PoolConfig,
+    QueryConfig, QueryManifest, ResolverManifest, RestletteConfig, RestletteManifest,
+    RestrictedField, RootConfig, RootConfigBuilder, ServerConfig, ServerConfigManifest,
+    SingletonResolverConfig, StorageManifest, TlsConfig, TlsMode, VectorResolverConfig,
+    DEFAULT_MAX_BODY_BYTES,
 };
-pub use error::{MeshqlError, Result};
+pub use error::{ConfigError, MeshqlError, Result};
+pub use id::{id_string, IdStrategy};
+pub use list::ListOptions;
+pub use merge::merge_patch;
+pub use metadata::{insert_metadata, CREATED_AT_KEY, DELETED_KEY, TYPE_KEY};
+pub use projection::is_projectable;
+pub use retry::{RetryPolicy, RetryRepository};
+pub use sort::{
+    distinct_from_args, distinct_stashes, parse_sort, sort_from_args, sort_stashes, SortField,
+    SortKey,
+};
+pub use template::{check_or_groups, render_template, CompiledTemplate, MissingKey, MAX_OR_DEPTH};
+pub use window::{CreatedWindow, CREATED_AFTER_ARG, CREATED_BEFORE_ARG};
 
 use chrono::{DateTime, Utc};
+use conditional::{check_unchanged, ConditionalWriteLock};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 pub type Stash = serde_json::Map<String, serde_json::Value>;
 
+/// Results yielded one at a time, from [`Searcher::find_stream`].
+pub type StashStream = futures::stream::BoxStream<'static, Result<Stash>>;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Envelope {
     pub id: String,
@@ -38,6 +73,44 @@ impl Envelope {
     }
 }
 
+/// What a repository reports at `/stats`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RepoStats {
+    /// Live documents, counting each id once at its latest version.
+    pub count: u64,
+    /// The connection pool, for backends that keep one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolStats>,
+}
+
+/// A snapshot of a repository's connection pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PoolStats {
+    /// Connections open, idle or in use.
+    pub size: u32,
+    /// Open connections waiting to be used.
+    pub idle: u32,
+    /// Connections checked out of the pool.
+    pub active: u32,
+}
+
+impl PoolStats {
+    /// The stats of a pool holding `size` connections, `idle` of them unused.
+    pub fn new(size: u32, idle: u32) -> Self {
+        Self {
+            size,
+            idle,
+            active: size.saturating_sub(idle),
+        }
+    }
+}
+
+/// Append-only storage of [`Envelope`] versions.
+///
+/// Reads of the latest versions (`read`, `read_raw`, `list_with`, `count` and
+/// `read_many`) reduce each id to its newest version before checking the
+/// caller's tokens against it. An id whose newest version the caller can't
+/// see is missing to them; an older version they could see never stands in.
 #[async_trait::async_trait]
 pub trait Repository: Send + Sync {
     async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope>;
@@ -47,16 +120,201 @@ pub trait Repository: Send + Sync {
         tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>>;
-    async fn list(&self, tokens: &[String]) -> Result<Vec<Envelope>>;
+    /// Like [`Repository::read`], but returns the latest version even when it is
+    /// the tombstone written by `remove`, with `deleted` set, e.g. for audit trails.
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>>;
+    /// The latest live version of each id visible to `tokens`.
+    async fn list(&self, tokens: &[String]) -> Result<Vec<Envelope>> {
+        self.list_with(tokens, ListOptions::default()).await
+    }
+    /// [`Repository::list`], keeping or only listing ids whose latest version
+    /// is a tombstone, and paged, as `options` asks.
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>>;
+    /// How many envelopes [`Repository::list`] would return, e.g. for paging
+    /// metadata. Backends that can't count in the store count the list.
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        Ok(self.list(tokens).await?.len() as u64)
+    }
+    /// How many live documents the store holds, across every caller, and the
+    /// state of its connection pool if it keeps one.
+    async fn stats(&self) -> Result<RepoStats> {
+        Ok(RepoStats {
+            count: self.count(&["*".to_string()]).await?,
+            pool: None,
+        })
+    }
+    /// Every stored version of `id` visible to `tokens`, oldest first, including
+    /// the tombstone written when it was removed.
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>>;
+    /// Every stored version of every id visible to `tokens` created from
+    /// `from` up to but not including `to`, tombstones included, oldest first.
+    /// Where [`Repository::history`] follows one id, this follows the clock,
+    /// e.g. for audits.
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>>;
+    /// The time this repository stamps the versions it writes itself on, such
+    /// as updates. Backends built with a [`Clock`] read it here.
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+    /// Deep-merge `patch` into the latest payload for `id` and write it as a new version.
+    /// Returns `None` if no live version exists, and fails with
+    /// [`MeshqlError::NotAuthorized`] if one exists that `tokens` can't see.
+    ///
+    /// Reads the latest version and creates the merged one after it, stamped
+    /// [`Repository::now`]. Backends that can patch in the store override it.
+    async fn update(&self, id: &str, patch: Stash, tokens: &[String]) -> Result<Option<Envelope>> {
+        match self.read(id, tokens, None).await? {
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(None)
+            }
+            Some(env) => {
+                let next = patched(env, patch, self.now());
+                self.create(next, tokens).await.map(Some)
+            }
+        }
+    }
+    /// Write a tombstone over the latest version of `id`. Returns `false` if no
+    /// live version exists, and fails with [`MeshqlError::NotAuthorized`] if one
+    /// exists that `tokens` can't see.
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool>;
+    /// Permanently delete every stored version of `id` visible to `tokens`,
+    /// tombstones included, e.g. to honour a right to erasure. Returns how many
+    /// versions were deleted. Log-backed stores can only ask for compaction to
+    /// drop them; see each backend.
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64>;
+    /// [`Repository::create`], unless the latest live version of the envelope's id
+    /// already holds an equal payload, in which case that version is returned and
+    /// nothing is written. Retrying an idempotent write then adds no history.
+    /// Fails with [`MeshqlError::NotAuthorized`] rather than write over a live
+    /// version `tokens` can't see.
+    async fn upsert(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        if !envelope.id.is_empty() {
+            match self.read(&envelope.id, tokens, None).await? {
+                Some(current) if current.payload == envelope.payload => return Ok(current),
+                Some(_) => {}
+                None => forbid_hidden(self, &envelope.id, tokens).await?,
+            }
+        }
+        self.create(envelope, tokens).await
+    }
+    /// [`Repository::create`], as long as the envelope's id has no version newer
+    /// than `expected_created_at`, the version the caller based it on, or no
+    /// live version at all when that is `None`. Otherwise fails with
+    /// [`MeshqlError::Conflict`] and writes nothing. Versions count whoever
+    /// may see them.
+    ///
+    /// Backends that can compare and set in the store override it, so writers
+    /// in other processes are held to it too. This default checks and writes
+    /// under a lock on the id held by this process alone.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        if envelope.id.is_empty() {
+            return self.create(envelope, tokens).await;
+        }
+        let _lock = ConditionalWriteLock::acquire(self, &envelope.id).await;
+        check_unchanged(self, &envelope.id, expected_created_at).await?;
+        self.create(envelope, tokens).await
+    }
+    /// [`Repository::update`], as long as `id` has no version newer than
+    /// `expected_created_at`, the version the caller based `patch` on.
+    /// Otherwise fails with [`MeshqlError::Conflict`] and writes nothing.
+    ///
+    /// Writes the merged version through [`Repository::create_if_unchanged`],
+    /// so only one of two updates racing on the same version wins.
+    async fn update_if_unchanged(
+        &self,
+        id: &str,
+        patch: Stash,
+        expected_created_at: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Option<Envelope>> {
+        check_unchanged(self, id, Some(expected_created_at)).await?;
+        match self.read(id, tokens, None).await? {
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(None)
+            }
+            Some(env) => {
+                let next = patched(env, patch, self.now());
+                self.create_if_unchanged(next, Some(expected_created_at), tokens)
+                    .await
+                    .map(Some)
+            }
+        }
+    }
+    /// Create every envelope, returning them in the order they were given, so
+    /// the `n`th result is the `n`th envelope with its id assigned.
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
         tokens: &[String],
     ) -> Result<Vec<Envelope>>;
     async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>>;
+    /// Remove every id, reporting whether each had a live version to remove.
+    /// See [`Repository::remove_many_in_order`] to match results to `ids` by position.
     async fn remove_many(&self, ids: &[String], tokens: &[String])
         -> Result<HashMap<String, bool>>;
+    /// [`Repository::remove_many`], as `(id, removed)` pairs in the order of `ids`.
+    async fn remove_many_in_order(
+        &self,
+        ids: &[String],
+        tokens: &[String],
+    ) -> Result<Vec<(String, bool)>> {
+        let removed = self.remove_many(ids, tokens).await?;
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.get(id).copied().unwrap_or(false)))
+            .collect())
+    }
+    /// Cheaply check the backing store is reachable, e.g. with `SELECT 1`.
+    async fn ping(&self) -> Result<()>;
+}
+
+/// Fails with [`MeshqlError::NotAuthorized`] if `id` has a live version that
+/// `tokens` can't see. Writes call it once their own read of `id` comes back
+/// empty, to tell a missing record from a forbidden one. Reads don't, so they
+/// never reveal that a hidden record exists.
+pub async fn forbid_hidden<R: Repository + ?Sized>(
+    repo: &R,
+    id: &str,
+    tokens: &[String],
+) -> Result<()> {
+    if tokens.iter().any(|t| t == "*") {
+        return Ok(());
+    }
+    match repo.read(id, &["*".to_string()], None).await? {
+        Some(_) => Err(MeshqlError::NotAuthorized(id.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// The version [`Repository::update`] writes over `current`: `patch` merged
+/// into its payload, stamped `now`.
+fn patched(current: Envelope, patch: Stash, now: DateTime<Utc>) -> Envelope {
+    let mut payload = current.payload;
+    merge_patch(&mut payload, patch);
+    Envelope {
+        id: current.id,
+        payload,
+        created_at: now,
+        deleted: false,
+        authorized_tokens: current.authorized_tokens,
+    }
 }
 
 #[async_trait::async_trait]
@@ -75,4 +333,49 @@ pub trait Searcher: Send + Sync {
         creds: &[String],
         at: i64,
     ) -> Result<Vec<Stash>>;
+    /// [`Searcher::find`], returning only the payload `fields` and `id`. Backends
+    /// that can't project fall back to the whole payload.
+    async fn find_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        let _ = fields;
+        self.find(template, args, creds, at).await
+    }
+    /// [`Searcher::find_all`], returning only the payload `fields` and `id`.
+    /// Backends that can't project fall back to the whole payload.
+    async fn find_all_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        let _ = fields;
+        self.find_all(template, args, creds, at).await
+    }
+    /// [`Searcher::find_all`] as a stream, so large result sets needn't be held
+    /// in memory at once. Backends that can't stream collect [`Searcher::find_all`].
+    async fn find_stream(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
+        let results = self.find_all(template, args, creds, at).await?;
+        Ok(Box::pin(futures::stream::iter(results.into_iter().map(Ok))))
+    }
+    /// Number of latest, non-deleted records matching the template. Ignores `limit`.
+    async fn count(&self, template: &str, args: &Stash, creds: &[String], at: i64) -> Result<u64>;
+    /// Whether at least one latest, non-deleted record matches the template.
+    async fn exists(&self, template: &str, args: &Stash, creds: &[String], at: i64)
+        -> Result<bool>;
+    /// Cheaply check the backing store is reachable, e.g. with `SELECT 1`.
+    async fn ping(&self) -> Result<()>;
 }
@@ -0,0 +1,50 @@
+//! Which envelopes [`Repository::list_with`](crate::Repository::list_with) returns.
+
+use crate::Envelope;
+
+/// Filters and pages [`Repository::list_with`](crate::Repository::list_with).
+/// The default lists every live envelope, like [`Repository::list`](crate::Repository::list).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    /// Also list ids whose latest version is the tombstone written by `remove`.
+    pub include_deleted: bool,
+    /// Only list ids whose latest version is a tombstone, e.g. for a trash view.
+    /// Wins over `include_deleted`.
+    pub deleted_only: bool,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl ListOptions {
+    /// The `deleted` flag of the latest versions listed, or `None` for either.
+    pub fn deleted(&self) -> Option<bool> {
+        match (self.deleted_only, self.include_deleted) {
+            (true, _) => Some(true),
+            (false, true) => None,
+            (false, false) => Some(false),
+        }
+    }
+
+    /// Whether an id whose latest version has `deleted` set is listed.
+    pub fn lists(&self, deleted: bool) -> bool {
+        self.deleted().is_none_or(|d| d == deleted)
+    }
+
+    /// Whether a page was asked for. Pages are taken in id order.
+    pub fn is_paged(&self) -> bool {
+        self.limit.is_some() || self.offset > 0
+    }
+
+    /// The page of `envelopes` asked for, for backends that page in memory.
+    pub fn page(&self, mut envelopes: Vec<Envelope>) -> Vec<Envelope> {
+        if !self.is_paged() {
+            return envelopes;
+        }
+        envelopes.sort_by(|a, b| a.id.cmp(&b.id));
+        envelopes
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
@@ -0,0 +1,75 @@
+use crate::Stash;
+use serde_json::Value;
+
+/// Deep-merge `patch` into `target`.
+///
+/// - Object values recurse into the matching key
+/// - Scalars and arrays replace the existing value
+/// - An explicit `null` removes the key
+pub fn merge_patch(target: &mut Stash, patch: Stash) {
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                target.remove(&key);
+            }
+            Value::Object(child_patch) => match target.get_mut(&key) {
+                Some(Value::Object(child)) => merge_patch(child, child_patch),
+                _ => {
+                    let mut child = Stash::new();
+                    merge_patch(&mut child, child_patch);
+                    target.insert(key, Value::Object(child));
+                }
+            },
+            other => {
+                target.insert(key, other);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stash(v: Value) -> Stash {
+        v.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn scalars_replace_and_new_keys_are_added() {
+        let mut target = stash(json!({"name": "alpha", "count": 1}));
+        merge_patch(&mut target, stash(json!({"count": 2, "type": "typeA"})));
+        assert_eq!(
+            Value::Object(target),
+            json!({"name": "alpha", "count": 2, "type": "typeA"})
+        );
+    }
+
+    #[test]
+    fn objects_recurse() {
+        let mut target = stash(json!({"address": {"city": "Leeds", "zip": "LS1"}}));
+        merge_patch(&mut target, stash(json!({"address": {"zip": "LS2"}})));
+        assert_eq!(
+            Value::Object(target),
+            json!({"address": {"city": "Leeds", "zip": "LS2"}})
+        );
+    }
+
+    #[test]
+    fn arrays_replace() {
+        let mut target = stash(json!({"tags": ["a", "b"]}));
+        merge_patch(&mut target, stash(json!({"tags": ["c"]})));
+        assert_eq!(Value::Object(target), json!({"tags": ["c"]}));
+    }
+
+    #[test]
+    fn null_removes_key() {
+        let mut target = stash(json!({"name": "alpha", "address": {"city": "Leeds"}}));
+        merge_patch(
+            &mut target,
+            stash(json!({"name": null, "address": {"city": null}})),
+        );
+        assert_eq!(Value::Object(target), json!({"address": {}}));
+    }
+}
@@ -0,0 +1,38 @@
+//! Envelope metadata searchers return alongside the payload, under reserved keys.
+
+use crate::Stash;
+use serde_json::json;
+
+/// When the returned version was written, in epoch milliseconds.
+pub const CREATED_AT_KEY: &str = "_created_at";
+/// Whether the returned version is a tombstone.
+pub const DELETED_KEY: &str = "_deleted";
+/// The GraphQL object type a result is, when it's returned through an
+/// interface or union. Written with the payload, so searchers return it as-is.
+pub const TYPE_KEY: &str = "_type";
+
+/// Add `created_at_ms` and `deleted` to a search result under the reserved
+/// keys, leaving a payload field of the same name in place.
+pub fn insert_metadata(stash: &mut Stash, created_at_ms: i64, deleted: bool) {
+    stash
+        .entry(CREATED_AT_KEY)
+        .or_insert_with(|| json!(created_at_ms));
+    stash.entry(DELETED_KEY).or_insert_with(|| json!(deleted));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_fields_win() {
+        let mut stash = Stash::new();
+        stash.insert(DELETED_KEY.to_string(), json!("kept"));
+        insert_metadata(&mut stash, 1_700_000_000_000, false);
+        assert_eq!(
+            stash.get(CREATED_AT_KEY),
+            Some(&json!(1_700_000_000_000i64))
+        );
+        assert_eq!(stash.get(DELETED_KEY), Some(&json!("kept")));
+    }
+}
@@ -0,0 +1,7 @@
+/// Whether every projected field is a plain name, ASCII alphanumerics and `_`,
+/// so it can be interpolated into a query safely.
+pub fn is_projectable(fields: &[String]) -> bool {
+    fields
+        .iter()
+        .all(|f| !f.is_empty() && f.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
@@ -0,0 +1,424 @@
+//! Retrying [`Repository`] calls that fail on a transient backend error, such
+//! as a dropped connection during a rolling database restart.
+
+use crate::{Envelope, IdStrategy, ListOptions, RepoStats, Repository, Result, Stash};
+use chrono::{DateTime, Utc};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How often and how patiently [`RetryRepository`] retries.
+///
+/// The wait before retry `n` is picked at random up to `base_delay * 2^n`,
+/// capped at `max_delay`, so clients failing together don't retry together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in all, counting the first.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// The jittered wait after the `retry`th failure, counting from 0.
+    fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let random = RandomState::new().build_hasher().finish();
+        ceiling.mul_f64(random as f64 / u64::MAX as f64)
+    }
+}
+
+/// A [`Repository`] that retries `inner` on
+/// [transient](crate::MeshqlError::is_transient) errors. Any other error is
+/// returned at once.
+///
+/// `create` and `create_many` assign missing ids before the first attempt, so
+/// a write that landed before its connection dropped is retried as a new
+/// version of the same id rather than as a second entity.
+pub struct RetryRepository<R> {
+    inner: R,
+    policy: RetryPolicy,
+    ids: IdStrategy,
+}
+
+impl<R: Repository> RetryRepository<R> {
+    pub fn new(inner: R, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            ids: IdStrategy::default(),
+        }
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`]. Set it to match `inner`'s.
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    async fn retry<T, F, Fut>(&self, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut + Send,
+        Fut: Future<Output = Result<T>> + Send,
+        T: Send,
+    {
+        let mut retry = 0;
+        loop {
+            match call().await {
+                Err(e) if e.is_transient() && retry + 1 < self.policy.max_attempts => {
+                    tokio::time::sleep(self.policy.delay(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn with_id(mut envelope: Envelope, ids: IdStrategy) -> Envelope {
+    if envelope.id.is_empty() {
+        envelope.id = ids.generate();
+    }
+    envelope
+}
+
+#[async_trait::async_trait]
+impl<R: Repository> Repository for RetryRepository<R> {
+    async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        let envelope = with_id(envelope, self.ids);
+        self.retry(|| self.inner.create(envelope.clone(), tokens))
+            .await
+    }
+
+    async fn read(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        self.retry(|| self.inner.read(id, tokens, at)).await
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        self.retry(|| self.inner.read_raw(id, tokens, at)).await
+    }
+
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        self.retry(|| self.inner.list_with(tokens, options)).await
+    }
+
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        self.retry(|| self.inner.count(tokens)).await
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.inner.now()
+    }
+
+    async fn stats(&self) -> Result<RepoStats> {
+        self.retry(|| self.inner.stats()).await
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        self.retry(|| self.inner.history(id, tokens)).await
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        self.retry(|| self.inner.created_between(from, to, tokens))
+            .await
+    }
+
+    async fn update(&self, id: &str, patch: Stash, tokens: &[String]) -> Result<Option<Envelope>> {
+        self.retry(|| self.inner.update(id, patch.clone(), tokens))
+            .await
+    }
+
+    async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
+        self.retry(|| self.inner.remove(id, tokens)).await
+    }
+
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        self.retry(|| self.inner.purge(id, tokens)).await
+    }
+
+    async fn upsert(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        let envelope = with_id(envelope, self.ids);
+        self.retry(|| self.inner.upsert(envelope.clone(), tokens))
+            .await
+    }
+
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let envelope = with_id(envelope, self.ids);
+        self.retry(|| {
+            self.inner
+                .create_if_unchanged(envelope.clone(), expected_created_at, tokens)
+        })
+        .await
+    }
+
+    async fn update_if_unchanged(
+        &self,
+        id: &str,
+        patch: Stash,
+        expected_created_at: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Option<Envelope>> {
+        self.retry(|| {
+            self.inner
+                .update_if_unchanged(id, patch.clone(), expected_created_at, tokens)
+        })
+        .await
+    }
+
+    async fn create_many(
+        &self,
+        envelopes: Vec<Envelope>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let envelopes: Vec<Envelope> = envelopes
+            .into_iter()
+            .map(|env| with_id(env, self.ids))
+            .collect();
+        self.retry(|| self.inner.create_many(envelopes.clone(), tokens))
+            .await
+    }
+
+    async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        self.retry(|| self.inner.read_many(ids, tokens)).await
+    }
+
+    async fn remove_many(
+        &self,
+        ids: &[String],
+        tokens: &[String],
+    ) -> Result<HashMap<String, bool>> {
+        self.retry(|| self.inner.remove_many(ids, tokens)).await
+    }
+
+    /// Not retried, so a health check reports an outage as soon as it starts.
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MeshqlError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    /// Fails each call with the queued errors, in order, then succeeds.
+    struct Flaky {
+        errors: Mutex<Vec<MeshqlError>>,
+        calls: AtomicU32,
+    }
+
+    impl Flaky {
+        fn failing_with(mut errors: Vec<MeshqlError>) -> Self {
+            errors.reverse();
+            Self {
+                errors: Mutex::new(errors),
+                calls: AtomicU32::new(0),
+            }
+        }
+
+        fn attempt(&self) -> Result<()> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.errors.lock().unwrap().pop() {
+                Some(e) => Err(e),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Repository for Flaky {
+        async fn create(&self, envelope: Envelope, _tokens: &[String]) -> Result<Envelope> {
+            self.attempt().map(|_| envelope)
+        }
+        async fn read(
+            &self,
+            id: &str,
+            _tokens: &[String],
+            _at: Option<DateTime<Utc>>,
+        ) -> Result<Option<Envelope>> {
+            self.attempt()
+                .map(|_| Some(Envelope::new(id, Stash::new(), vec![])))
+        }
+        async fn read_raw(
+            &self,
+            id: &str,
+            tokens: &[String],
+            at: Option<DateTime<Utc>>,
+        ) -> Result<Option<Envelope>> {
+            self.read(id, tokens, at).await
+        }
+        async fn list_with(
+            &self,
+            _tokens: &[String],
+            _options: ListOptions,
+        ) -> Result<Vec<Envelope>> {
+            self.attempt().map(|_| vec![])
+        }
+        async fn history(&self, _id: &str, _tokens: &[String]) -> Result<Vec<Envelope>> {
+            self.attempt().map(|_| vec![])
+        }
+        async fn created_between(
+            &self,
+            _from: DateTime<Utc>,
+            _to: DateTime<Utc>,
+            _tokens: &[String],
+        ) -> Result<Vec<Envelope>> {
+            self.attempt().map(|_| vec![])
+        }
+        async fn update(
+            &self,
+            _id: &str,
+            _patch: Stash,
+            _tokens: &[String],
+        ) -> Result<Option<Envelope>> {
+            self.attempt().map(|_| None)
+        }
+        async fn remove(&self, _id: &str, _tokens: &[String]) -> Result<bool> {
+            self.attempt().map(|_| true)
+        }
+        async fn purge(&self, _id: &str, _tokens: &[String]) -> Result<u64> {
+            self.attempt().map(|_| 0)
+        }
+
+        async fn create_many(
+            &self,
+            envelopes: Vec<Envelope>,
+            _tokens: &[String],
+        ) -> Result<Vec<Envelope>> {
+            self.attempt().map(|_| envelopes)
+        }
+        async fn read_many(&self, _ids: &[String], _tokens: &[String]) -> Result<Vec<Envelope>> {
+            self.attempt().map(|_| vec![])
+        }
+        async fn remove_many(
+            &self,
+            _ids: &[String],
+            _tokens: &[String],
+        ) -> Result<HashMap<String, bool>> {
+            self.attempt().map(|_| HashMap::new())
+        }
+        async fn ping(&self) -> Result<()> {
+            self.attempt()
+        }
+    }
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy::new()
+            .base_delay(Duration::from_millis(1))
+            .max_delay(Duration::from_millis(5))
+    }
+
+    #[tokio::test]
+    async fn succeeds_after_transient_failures() {
+        let flaky = Flaky::failing_with(vec![
+            MeshqlError::Storage("connection reset".to_string()),
+            MeshqlError::Storage("connection refused".to_string()),
+        ]);
+        let repo = RetryRepository::new(flaky, fast());
+
+        let found = repo.read("a", &[], None).await.unwrap();
+        assert_eq!(found.unwrap().id, "a");
+        assert_eq!(repo.inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let flaky = Flaky::failing_with(vec![
+            MeshqlError::Storage("down".to_string()),
+            MeshqlError::Storage("still down".to_string()),
+        ]);
+        let repo = RetryRepository::new(flaky, fast().max_attempts(2));
+
+        let err = repo.list(&[]).await.unwrap_err();
+        assert!(matches!(err, MeshqlError::Storage(m) if m == "still down"));
+        assert_eq!(repo.inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn deterministic_errors_are_not_retried() {
+        let flaky = Flaky::failing_with(vec![MeshqlError::Parse("bad json".to_string())]);
+        let repo = RetryRepository::new(flaky, fast());
+
+        let err = repo.update("a", Stash::new(), &[]).await.unwrap_err();
+        assert!(matches!(err, MeshqlError::Parse(_)));
+        assert_eq!(repo.inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_ids_are_assigned_before_the_first_attempt() {
+        let flaky = Flaky::failing_with(vec![MeshqlError::Storage("timeout".to_string())]);
+        let repo = RetryRepository::new(flaky, fast());
+
+        let created = repo
+            .create(Envelope::new("", Stash::new(), vec![]), &[])
+            .await
+            .unwrap();
+        assert!(!created.id.is_empty());
+    }
+
+    #[test]
+    fn delays_are_capped() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(300));
+        assert!(policy.delay(0) <= Duration::from_millis(100));
+        assert!((0..40).all(|retry| policy.delay(retry) <= Duration::from_millis(300)));
+    }
+}
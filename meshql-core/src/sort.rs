@@ -0,0 +1,232 @@
+use crate::{MeshqlError, Result, Stash};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+/// A field a search can be ordered, or made distinct, by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortField {
+    Id,
+    Payload(String),
+}
+
+/// One `field:dir` term of a `sort` search argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+/// Parse a sort spec like `"payload.count:desc,payload.name:asc"`.
+///
+/// - Direction is `asc` or `desc` and defaults to `asc`
+/// - Fields are `id` or `payload.<name>`, where `<name>` is ASCII alphanumerics
+///   and `_`, so it can be interpolated into a query safely
+pub fn parse_sort(spec: &str) -> Result<Vec<SortKey>> {
+    let mut keys = Vec::new();
+    for term in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        let (field, dir) = term.split_once(':').unwrap_or((term, "asc"));
+
+        let descending = match dir.trim().to_ascii_lowercase().as_str() {
+            "asc" => false,
+            "desc" => true,
+            other => {
+                return Err(MeshqlError::Parse(format!(
+                    "Invalid sort direction '{other}' in '{term}'"
+                )))
+            }
+        };
+
+        let field = parse_field(field.trim())
+            .ok_or_else(|| MeshqlError::Parse(format!("Invalid sort field '{}'", field.trim())))?;
+
+        keys.push(SortKey { field, descending });
+    }
+    Ok(keys)
+}
+
+/// `id` or `payload.<name>`, where `<name>` is ASCII alphanumerics and `_`.
+fn parse_field(field: &str) -> Option<SortField> {
+    match field {
+        "id" => Some(SortField::Id),
+        f => match f.strip_prefix("payload.") {
+            Some(name)
+                if !name.is_empty()
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+            {
+                Some(SortField::Payload(name.to_string()))
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Read the `sort` search argument, if present.
+pub fn sort_from_args(args: &Stash) -> Result<Vec<SortKey>> {
+    match args.get("sort") {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::String(spec)) => parse_sort(spec),
+        Some(other) => Err(MeshqlError::Parse(format!(
+            "sort must be a string, got {other}"
+        ))),
+    }
+}
+
+/// Read the `distinct` search argument, if present: `true` for one result per
+/// `id`, or a field like `"payload.hen_id"` for one per value of it.
+pub fn distinct_from_args(args: &Stash) -> Result<Option<SortField>> {
+    match args.get("distinct") {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => Ok(None),
+        Some(Value::Bool(true)) => Ok(Some(SortField::Id)),
+        Some(Value::String(field)) => parse_field(field)
+            .map(Some)
+            .ok_or_else(|| MeshqlError::Parse(format!("Invalid distinct field '{field}'"))),
+        Some(other) => Err(MeshqlError::Parse(format!(
+            "distinct must be a boolean or a field name, got {other}"
+        ))),
+    }
+}
+
+/// Keep one result per value of `field`, the one with the lowest `id`,
+/// leaving the survivors in their order. Missing values count as null.
+pub fn distinct_stashes(results: &mut Vec<Stash>, field: &SortField) {
+    let key = |stash: &Stash| {
+        let value = match field {
+            SortField::Id => stash.get("id"),
+            SortField::Payload(name) => stash.get(name),
+        };
+        value.unwrap_or(&Value::Null).to_string()
+    };
+    let mut first: HashMap<String, usize> = HashMap::new();
+    for (i, stash) in results.iter().enumerate() {
+        match first.entry(key(stash)) {
+            Entry::Vacant(e) => {
+                e.insert(i);
+            }
+            Entry::Occupied(mut e) => {
+                let kept = results[*e.get()].get("id");
+                if compare_values(stash.get("id"), kept) == Ordering::Less {
+                    e.insert(i);
+                }
+            }
+        }
+    }
+    let kept: HashSet<usize> = first.into_values().collect();
+    let mut i = 0;
+    results.retain(|_| {
+        i += 1;
+        kept.contains(&(i - 1))
+    });
+}
+
+/// Sort result stashes in memory by `keys`, breaking ties by `id`.
+pub fn sort_stashes(results: &mut [Stash], keys: &[SortKey]) {
+    results.sort_by(|a, b| {
+        for key in keys {
+            let ord = match &key.field {
+                SortField::Id => compare_values(a.get("id"), b.get("id")),
+                SortField::Payload(name) => compare_values(a.get(name), b.get(name)),
+            };
+            let ord = if key.descending { ord.reverse() } else { ord };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        compare_values(a.get("id"), b.get("id"))
+    });
+}
+
+/// Missing and null sort first, then numbers, then strings; other types compare as text.
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (None | Some(Value::Null), None | Some(Value::Null)) => Ordering::Equal,
+        (None | Some(Value::Null), _) => Ordering::Less,
+        (_, None | Some(Value::Null)) => Ordering::Greater,
+        (Some(Value::Number(x)), Some(Value::Number(y))) => {
+            let (x, y) = (x.as_f64().unwrap_or(0.0), y.as_f64().unwrap_or(0.0));
+            x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+        }
+        (Some(Value::Number(_)), _) => Ordering::Less,
+        (_, Some(Value::Number(_))) => Ordering::Greater,
+        (Some(Value::String(x)), Some(Value::String(y))) => x.cmp(y),
+        (Some(x), Some(y)) => x.to_string().cmp(&y.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_fields_and_directions() {
+        let keys = parse_sort("payload.count:desc, payload.name:asc,id").unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                SortKey {
+                    field: SortField::Payload("count".into()),
+                    descending: true
+                },
+                SortKey {
+                    field: SortField::Payload("name".into()),
+                    descending: false
+                },
+                SortKey {
+                    field: SortField::Id,
+                    descending: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_directions() {
+        assert!(parse_sort("name:asc").is_err());
+        assert!(parse_sort("payload.:asc").is_err());
+        assert!(parse_sort("payload.count') DESC; --:asc").is_err());
+        assert!(parse_sort("payload.count:sideways").is_err());
+    }
+
+    #[test]
+    fn distinct_keeps_the_lowest_id_per_value() {
+        let mut results: Vec<Stash> = [
+            json!({"id": "c", "hen": "h-1"}),
+            json!({"id": "a", "hen": "h-2"}),
+            json!({"id": "b", "hen": "h-1"}),
+            json!({"id": "d"}),
+        ]
+        .into_iter()
+        .map(|v| v.as_object().unwrap().clone())
+        .collect();
+
+        let mut args = Stash::new();
+        args.insert("distinct".to_string(), json!("payload.hen"));
+        let field = distinct_from_args(&args).unwrap().unwrap();
+        distinct_stashes(&mut results, &field);
+        let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["a", "b", "d"]);
+
+        args.insert("distinct".to_string(), json!(true));
+        assert_eq!(distinct_from_args(&args).unwrap(), Some(SortField::Id));
+        args.insert("distinct".to_string(), json!("hen"));
+        assert!(distinct_from_args(&args).is_err());
+    }
+
+    #[test]
+    fn sorts_stashes_numerically_with_id_tiebreak() {
+        let mut results: Vec<Stash> = [
+            json!({"id": "b", "count": 10}),
+            json!({"id": "a", "count": 9}),
+            json!({"id": "c", "count": 10}),
+        ]
+        .into_iter()
+        .map(|v| v.as_object().unwrap().clone())
+        .collect();
+
+        sort_stashes(&mut results, &parse_sort("payload.count:desc").unwrap());
+        let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["b", "c", "a"]);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{MeshqlError, Result, Stash};
+use serde_json::Value;
+
+/// What a `{{path}}` that isn't in the args renders as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingKey {
+    /// Substitute an empty string.
+    #[default]
+    Empty,
+    /// Fail with [`MeshqlError::Parse`].
+    Error,
+}
+
+/// Render a query template, replacing each `{{path}}` with the value it names in `args`.
+///
+/// - Paths are dotted, `{{user.id}}`, and index arrays with `{{ids.0}}`,
+///   `{{ids[0]}}` or `{{ids.[0]}}`
+/// - Strings are inserted JSON-escaped without quotes, so `"{{name}}"` stays a
+///   valid JSON string; other values are inserted as JSON and `null` as nothing
+/// - A list or object filling a whole JSON string replaces the string, so
+///   `{"id": {"$in": "{{ids}}"}}` matches any of `ids`
+///
+/// The template is compiled for this one call; compile it once with
+/// [`CompiledTemplate::compile`] to render it many times.
+pub fn render_template(template: &str, args: &Stash, missing: MissingKey) -> Result<String> {
+    CompiledTemplate::compile(template)?.render(args, missing)
+}
+
+/// A query template split once into its text and the paths of its
+/// placeholders, so rendering it is only substitution.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledTemplate {
+    len: usize,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    /// A `{{path}}`, with the path split into the keys and indexes it follows.
+    Key {
+        path: String,
+        segments: Vec<String>,
+    },
+}
+
+impl CompiledTemplate {
+    /// Split `template` into text and placeholders. Fails with
+    /// [`MeshqlError::Template`] on a `{{` that is never closed.
+    pub fn compile(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| {
+                MeshqlError::Template(format!("Unclosed '{{{{' in template '{template}'"))
+            })?;
+            let path = after[..end].trim();
+            let segments = path.split(['.', '[', ']']).filter(|s| !s.is_empty());
+            parts.push(Part::Key {
+                path: path.to_string(),
+                segments: segments.map(String::from).collect(),
+            });
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self {
+            len: template.len(),
+            parts,
+        })
+    }
+
+    /// Substitute `args` into the template, as [`render_template`] does.
+    pub fn render(&self, args: &Stash, missing: MissingKey) -> Result<String> {
+        let mut out = String::with_capacity(self.len);
+        // Set when a list or object took the place of a whole string, whose
+        // closing quote starts the next text.
+        let mut unquote = false;
+        for (i, part) in self.parts.iter().enumerate() {
+            let (path, segments) = match part {
+                Part::Text(text) if unquote => {
+                    out.push_str(&text[1..]);
+                    unquote = false;
+                    continue;
+                }
+                Part::Text(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Part::Key { path, segments } => (path, segments),
+            };
+            let closes_string =
+                matches!(self.parts.get(i + 1), Some(Part::Text(next)) if next.starts_with('"'));
+            match lookup(args, segments) {
+                Some(value @ (Value::Array(_) | Value::Object(_)))
+                    if out.ends_with('"') && closes_string =>
+                {
+                    out.pop();
+                    out.push_str(&value.to_string());
+                    unquote = true;
+                }
+                Some(value) => push_value(&mut out, value),
+                None if missing == MissingKey::Error => {
+                    return Err(MeshqlError::Parse(format!(
+                        "Template key '{path}' is missing from the arguments"
+                    )))
+                }
+                None => {}
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn lookup<'a>(args: &'a Stash, segments: &[String]) -> Option<&'a Value> {
+    let (first, rest) = segments.split_first()?;
+    let mut value = args.get(first)?;
+    for segment in rest {
+        value = match value {
+            Value::Object(map) => map.get(segment)?,
+            Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn push_value(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => {}
+        Value::String(s) => {
+            let quoted = Value::String(s.clone()).to_string();
+            out.push_str(&quoted[1..quoted.len() - 1]);
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// How deeply `$or` groups may nest in a rendered query, so no template
+/// compiles into a pathologically large filter.
+pub const MAX_OR_DEPTH: usize = 4;
+
+/// Check that every `$or` in a rendered query holds an array of objects, each
+/// a group of conditions ANDed together, and that they nest no deeper than
+/// [`MAX_OR_DEPTH`]. Fails with [`MeshqlError::Template`].
+pub fn check_or_groups(query: &Value) -> Result<()> {
+    check_or_depth(query, 0)
+}
+
+fn check_or_depth(query: &Value, depth: usize) -> Result<()> {
+    let Some(group) = query.as_object().and_then(|obj| obj.get("$or")) else {
+        return Ok(());
+    };
+    if depth == MAX_OR_DEPTH {
+        return Err(MeshqlError::Template(format!(
+            "$or groups nest deeper than {MAX_OR_DEPTH}"
+        )));
+    }
+    let branches = group
+        .as_array()
+        .filter(|branches| branches.iter().all(Value::is_object))
+        .ok_or_else(|| MeshqlError::Template("$or takes an array of objects".to_string()))?;
+    branches
+        .iter()
+        .try_for_each(|branch| check_or_depth(branch, depth + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(v: Value) -> Stash {
+        v.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn substitutes_nested_paths_and_array_indexes() {
+        let args = args(json!({
+            "user": {"id": "u1", "roles": ["admin", "ops"]},
+            "ids": [{"id": "a"}, {"id": "b"}],
+            "min": 3,
+        }));
+        let rendered = render_template(
+            r#"{"id": "{{user.id}}", "role": "{{ user.roles.1 }}", "other": "{{ids[1].id}}", "first": "{{ids.[0].id}}", "n": {{min}}}"#,
+            &args,
+            MissingKey::Error,
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            r#"{"id": "u1", "role": "ops", "other": "b", "first": "a", "n": 3}"#
+        );
+    }
+
+    #[test]
+    fn quoted_lists_and_objects_render_as_json() {
+        let args = args(json!({"ids": ["a", "b", "c"], "near": {"x": 1}, "n": 3}));
+        let rendered = render_template(
+            r#"{"id": {"$in": "{{ids}}"}, "at": "{{near}}", "n": "{{n}}"}"#,
+            &args,
+            MissingKey::Error,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&rendered).unwrap(),
+            json!({"id": {"$in": ["a", "b", "c"]}, "at": {"x": 1}, "n": "3"})
+        );
+    }
+
+    #[test]
+    fn strings_are_json_escaped() {
+        let rendered = render_template(
+            r#"{"name": "{{name}}"}"#,
+            &args(json!({"name": "say \"hi\" & <bye>"})),
+            MissingKey::Error,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&rendered).unwrap(),
+            json!({"name": "say \"hi\" & <bye>"})
+        );
+    }
+
+    #[test]
+    fn missing_key_is_empty_unless_strict() {
+        let template = r#"{"id": "{{user.id}}"}"#;
+        let args = args(json!({"user": {"name": "x"}}));
+
+        assert_eq!(
+            render_template(template, &args, MissingKey::Empty).unwrap(),
+            r#"{"id": ""}"#
+        );
+        assert!(matches!(
+            render_template(template, &args, MissingKey::Error),
+            Err(MeshqlError::Parse(msg)) if msg.contains("user.id")
+        ));
+    }
+
+    #[test]
+    fn unclosed_tag_is_a_template_error() {
+        assert!(matches!(
+            render_template(r#"{"id": "{{id"}"#, &Stash::new(), MissingKey::Empty),
+            Err(MeshqlError::Template(_))
+        ));
+    }
+
+    #[test]
+    fn compiled_templates_render_every_time_from_one_compile() {
+        let template = CompiledTemplate::compile(
+            r#"{"id": {"$in": "{{ids}}"}, "at": "{{user.id}}", "pair": "{{ids}}{{n}}", "n": {{n}}}"#,
+        )
+        .unwrap();
+        let cases = [
+            (
+                args(json!({"user": {"id": "u1"}, "ids": ["x", "y"], "n": 3})),
+                r#"{"id": {"$in": ["x","y"]}, "at": "u1", "pair": "["x","y"]3", "n": 3}"#,
+            ),
+            (
+                args(json!({"user": {"id": "say \"hi\""}, "ids": [], "n": null})),
+                r#"{"id": {"$in": []}, "at": "say \"hi\"", "pair": "[]", "n": }"#,
+            ),
+            (
+                args(json!({})),
+                r#"{"id": {"$in": ""}, "at": "", "pair": "", "n": }"#,
+            ),
+        ];
+        for (args, expected) in &cases {
+            assert_eq!(template.render(args, MissingKey::Empty).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn templates_without_placeholders_render_as_written() {
+        let template = CompiledTemplate::compile(r#"{"all": true}"#).unwrap();
+        assert_eq!(
+            template.render(&Stash::new(), MissingKey::Error).unwrap(),
+            r#"{"all": true}"#
+        );
+    }
+
+    #[test]
+    fn or_groups_nest_no_deeper_than_the_limit() {
+        let mut query = json!({"payload.type": "typeA"});
+        for _ in 0..MAX_OR_DEPTH {
+            query = json!({"$or": [query, {"id": "x"}]});
+        }
+        assert!(check_or_groups(&query).is_ok());
+
+        let deeper = json!({"$or": [query]});
+        assert!(matches!(
+            check_or_groups(&deeper),
+            Err(MeshqlError::Template(msg)) if msg.contains("deeper")
+        ));
+        assert!(matches!(
+            check_or_groups(&json!({"$or": {"id": "x"}})),
+            Err(MeshqlError::Template(_))
+        ));
+    }
+}
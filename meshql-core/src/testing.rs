@@ -1,5 +1,10 @@
-use crate::{Envelope, Repository, Searcher, Stash};
+use crate::{
+    Clock, Envelope, ListOptions, MeshqlError, MockClock, Repository, Searcher, Stash,
+    CREATED_AT_KEY, DELETED_KEY,
+};
+use futures::TryStreamExt;
 use serde_json::json;
+use std::collections::HashMap;
 
 const STAR: &str = "*";
 fn star() -> Vec<String> {
@@ -90,6 +95,50 @@ pub async fn test_read_many_should_retrieve_multiple_envelopes(repo: &dyn Reposi
     assert_eq!(results.len(), 3);
 }
 
+pub async fn test_read_many_returns_latest_live_versions(repo: &dyn Repository) {
+    let envelopes: Vec<Envelope> = (0..150)
+        .map(|i| {
+            let mut payload = Stash::new();
+            payload.insert("name".to_string(), json!(format!("many-{i}")));
+            // Older than the updates and tombstones below, so no backend has to break a tie.
+            Envelope {
+                created_at: chrono::Utc::now() - chrono::Duration::seconds(1),
+                ..Envelope::new(format!("many-id-{i}"), payload, star())
+            }
+        })
+        .collect();
+    repo.create_many(envelopes, &star()).await.unwrap();
+    for i in 0..10 {
+        let mut patch = Stash::new();
+        patch.insert("name".to_string(), json!(format!("updated-{i}")));
+        repo.update(&format!("many-id-{i}"), patch, &star())
+            .await
+            .unwrap();
+    }
+    let removed: Vec<String> = (100..130).map(|i| format!("many-id-{i}")).collect();
+    repo.remove_many(&removed, &star()).await.unwrap();
+
+    // 150..200 were never created.
+    let ids: Vec<String> = (0..200).map(|i| format!("many-id-{i}")).collect();
+    let found: HashMap<String, Envelope> = repo
+        .read_many(&ids, &star())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|env| (env.id.clone(), env))
+        .collect();
+    assert_eq!(found.len(), 120);
+    for i in (0..100).chain(130..150) {
+        let env = &found[&format!("many-id-{i}")];
+        let name = if i < 10 {
+            format!("updated-{i}")
+        } else {
+            format!("many-{i}")
+        };
+        assert_eq!(env.payload["name"], json!(name));
+    }
+}
+
 pub async fn test_remove_many_should_delete_multiple_envelopes(repo: &dyn Repository) {
     for i in 0..3 {
         let mut payload = Stash::new();
@@ -104,6 +153,43 @@ pub async fn test_remove_many_should_delete_multiple_envelopes(repo: &dyn Reposi
     assert!(results.values().all(|&v| v));
 }
 
+pub async fn test_bulk_results_follow_input_order(repo: &dyn Repository) {
+    // Named and unnamed envelopes interleaved, with ids out of sorted order.
+    let names = ["order-c", "", "order-a", "", "order-b"];
+    let envelopes: Vec<Envelope> = names
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let mut payload = Stash::new();
+            payload.insert("position".to_string(), json!(i));
+            Envelope::new(*id, payload, star())
+        })
+        .collect();
+    let created = repo.create_many(envelopes, &star()).await.unwrap();
+    assert_eq!(created.len(), names.len());
+    for (i, (env, id)) in created.iter().zip(names).enumerate() {
+        assert_eq!(env.payload["position"], json!(i));
+        if !id.is_empty() {
+            assert_eq!(env.id, id);
+        }
+    }
+
+    let ids = vec![
+        "order-b".to_string(),
+        "order-missing".to_string(),
+        created[3].id.clone(),
+        "order-c".to_string(),
+        created[1].id.clone(),
+        "order-a".to_string(),
+    ];
+    let removed = repo.remove_many_in_order(&ids, &star()).await.unwrap();
+    let expected: Vec<(String, bool)> = ids
+        .iter()
+        .map(|id| (id.clone(), id != "order-missing"))
+        .collect();
+    assert_eq!(removed, expected);
+}
+
 pub async fn test_temporal_versioning(repo: &dyn Repository) {
     let mut payload_v1 = Stash::new();
     payload_v1.insert("name".to_string(), json!("version-1"));
@@ -178,8 +264,747 @@ pub async fn test_list_shows_only_latest_version(repo: &dyn Repository) {
     assert_eq!(for_id[0].payload.get("version").unwrap(), &json!("new"));
 }
 
+/// Writes two versions of `id` through `create_many`: an older one held by
+/// `alice` and a newer one held by `bob`.
+async fn hand_over(repo: &dyn Repository, id: &str) {
+    let version = |name: &str, created_at| {
+        let mut payload = Stash::new();
+        payload.insert("version".to_string(), json!(name));
+        Envelope {
+            created_at,
+            ..Envelope::new(id, payload, star())
+        }
+    };
+    let now = chrono::Utc::now();
+    repo.create_many(
+        vec![version("old", now - chrono::Duration::seconds(10))],
+        &["alice".to_string()],
+    )
+    .await
+    .unwrap();
+    repo.create_many(vec![version("new", now)], &["bob".to_string()])
+        .await
+        .unwrap();
+}
+
+/// The newer of two versions written by `create_many` is held by other tokens
+/// than the older, so a list or count by the older's tokens must show neither.
+pub async fn test_list_hides_ids_whose_newest_version_is_hidden(repo: &dyn Repository) {
+    let alice = vec!["alice".to_string()];
+    let bob = vec!["bob".to_string()];
+    hand_over(repo, "handed-over-id").await;
+
+    let listed = repo.list(&alice).await.unwrap();
+    assert!(
+        listed.iter().all(|e| e.id != "handed-over-id"),
+        "{listed:?}"
+    );
+    assert_eq!(repo.count(&alice).await.unwrap(), listed.len() as u64);
+    let listed = repo.list(&bob).await.unwrap();
+    let for_id: Vec<_> = listed.iter().filter(|e| e.id == "handed-over-id").collect();
+    assert_eq!(for_id.len(), 1);
+    assert_eq!(for_id[0].payload["version"], json!("new"));
+    assert_eq!(repo.count(&bob).await.unwrap(), listed.len() as u64);
+}
+
+/// As above for `read_many`, and `remove_many` must leave alone what the
+/// older's tokens can no longer see.
+pub async fn test_read_many_hides_ids_whose_newest_version_is_hidden(repo: &dyn Repository) {
+    let alice = vec!["alice".to_string()];
+    let bob = vec!["bob".to_string()];
+    let ids = vec!["handed-over-many".to_string()];
+    hand_over(repo, "handed-over-many").await;
+
+    assert!(repo.read_many(&ids, &alice).await.unwrap().is_empty());
+    let removed = repo.remove_many(&ids, &alice).await.unwrap();
+    assert_eq!(removed.get("handed-over-many"), Some(&false));
+
+    let read = repo.read_many(&ids, &bob).await.unwrap();
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].payload["version"], json!("new"));
+}
+
+pub async fn test_history_returns_every_version(repo: &dyn Repository) {
+    let now = chrono::Utc::now();
+    for (i, secs_ago) in [(1, 30), (2, 20), (3, 10)] {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(format!("version-{i}")));
+        let env = Envelope {
+            id: "history-id".to_string(),
+            payload,
+            created_at: now - chrono::Duration::seconds(secs_ago),
+            deleted: false,
+            authorized_tokens: star(),
+        };
+        repo.create(env, &star()).await.unwrap();
+    }
+
+    let history = repo.history("history-id", &star()).await.unwrap();
+    let names: Vec<_> = history.iter().map(|e| e.payload["name"].clone()).collect();
+    assert_eq!(
+        names,
+        vec![json!("version-1"), json!("version-2"), json!("version-3")]
+    );
+    assert!(history.iter().all(|e| e.id == "history-id" && !e.deleted));
+
+    assert!(repo.remove("history-id", &star()).await.unwrap());
+    let history = repo.history("history-id", &star()).await.unwrap();
+    assert_eq!(history.len(), 4);
+    assert!(history[3].deleted);
+
+    assert!(repo
+        .history("no-such-id", &star())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+pub async fn test_created_between_returns_versions_in_the_window(repo: &dyn Repository) {
+    let start = chrono::DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+    // Two versions of `a` and one of `b` fall in the ten seconds from `start`;
+    // the window includes its start and excludes its end.
+    for (id, offset_ms) in [
+        ("a", -1),
+        ("a", 0),
+        ("b", 4_000),
+        ("a", 9_999),
+        ("b", 10_000),
+    ] {
+        let mut payload = Stash::new();
+        payload.insert("offset".to_string(), json!(offset_ms));
+        let env = Envelope {
+            created_at: start + chrono::Duration::milliseconds(offset_ms),
+            ..Envelope::new(format!("window-{id}"), payload, star())
+        };
+        repo.create(env, &star()).await.unwrap();
+    }
+
+    let versions = repo
+        .created_between(start, start + chrono::Duration::seconds(10), &star())
+        .await
+        .unwrap();
+    assert_eq!(versions.len(), 3);
+    let found: Vec<(&str, &serde_json::Value)> = versions
+        .iter()
+        .map(|env| (env.id.as_str(), &env.payload["offset"]))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("window-a", &json!(0)),
+            ("window-b", &json!(4_000)),
+            ("window-a", &json!(9_999)),
+        ]
+    );
+
+    assert!(repo
+        .created_between(start, start, &star())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+pub async fn test_update_should_merge_patch_into_new_version(repo: &dyn Repository) {
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("original"));
+    payload.insert("count".to_string(), json!(1));
+    payload.insert(
+        "address".to_string(),
+        json!({"city": "Leeds", "zip": "LS1"}),
+    );
+    let env = Envelope {
+        id: "update-id".to_string(),
+        payload,
+        created_at: chrono::Utc::now() - chrono::Duration::seconds(10),
+        deleted: false,
+        authorized_tokens: star(),
+    };
+    repo.create(env, &star()).await.unwrap();
+    let before = chrono::Utc::now() - chrono::Duration::seconds(5);
+
+    let mut patch = Stash::new();
+    patch.insert("count".to_string(), json!(2));
+    patch.insert("name".to_string(), json!(null));
+    patch.insert("address".to_string(), json!({"zip": "LS2"}));
+    let updated = repo
+        .update("update-id", patch, &star())
+        .await
+        .unwrap()
+        .expect("update should return the new version");
+    assert_eq!(updated.payload.get("count").unwrap(), &json!(2));
+    assert!(updated.payload.get("name").is_none());
+    assert_eq!(
+        updated.payload.get("address").unwrap(),
+        &json!({"city": "Leeds", "zip": "LS2"})
+    );
+
+    let current = repo
+        .read("update-id", &star(), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(current.payload, updated.payload);
+
+    // The previous version is still readable at an earlier point in time
+    let old = repo
+        .read("update-id", &star(), Some(before))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(old.payload.get("name").unwrap(), &json!("original"));
+
+    let missing = repo
+        .update("no-such-id", Stash::new(), &star())
+        .await
+        .unwrap();
+    assert!(missing.is_none());
+}
+
+pub async fn test_updates_racing_on_one_version_should_conflict_once(repo: &dyn Repository) {
+    test_updates_racing_across_repositories_should_conflict_once(repo, repo).await;
+}
+
+/// `first` and `second` must share one store, as two server instances would,
+/// so only a compare-and-set in the store keeps one of them from winning too.
+pub async fn test_updates_racing_across_repositories_should_conflict_once(
+    first: &dyn Repository,
+    second: &dyn Repository,
+) {
+    let mut payload = Stash::new();
+    payload.insert("count".to_string(), json!(1));
+    let env = Envelope {
+        id: "race-id".to_string(),
+        payload,
+        created_at: chrono::Utc::now() - chrono::Duration::seconds(10),
+        deleted: false,
+        authorized_tokens: star(),
+    };
+    let base = first.create(env, &star()).await.unwrap();
+
+    let patch = |count: i64| {
+        let mut patch = Stash::new();
+        patch.insert("count".to_string(), json!(count));
+        patch
+    };
+    let tokens = star();
+    let (left, right) = futures::join!(
+        first.update_if_unchanged("race-id", patch(2), base.created_at, &tokens),
+        second.update_if_unchanged("race-id", patch(3), base.created_at, &tokens),
+    );
+    let (won, lost) = match (left, right) {
+        (Ok(Some(won)), Err(lost)) | (Err(lost), Ok(Some(won))) => (won, lost),
+        other => panic!("expected exactly one conflict, got {other:?}"),
+    };
+    assert!(matches!(lost, MeshqlError::Conflict(_)), "{lost:?}");
+
+    let current = second
+        .read("race-id", &star(), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(current.payload, won.payload);
+    assert_eq!(first.history("race-id", &star()).await.unwrap().len(), 2);
+
+    let retried = second
+        .update_if_unchanged("race-id", patch(4), won.created_at, &star())
+        .await
+        .unwrap()
+        .expect("an update based on the latest version should succeed");
+    assert_eq!(retried.payload.get("count").unwrap(), &json!(4));
+}
+
+/// `repo` must stamp its versions by `clock`.
+pub async fn test_versions_are_stamped_by_the_clock(repo: &dyn Repository, clock: &MockClock) {
+    let start = chrono::DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+    clock.set(start);
+    let count = |count: i64| {
+        let mut payload = Stash::new();
+        payload.insert("count".to_string(), json!(count));
+        payload
+    };
+    let env = Envelope {
+        created_at: clock.now(),
+        ..Envelope::new("clock-id", count(1), star())
+    };
+    repo.create(env, &star()).await.unwrap();
+    clock.advance(chrono::Duration::milliseconds(1));
+    let updated = repo
+        .update("clock-id", count(2), &star())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(updated.created_at.timestamp_millis(), 1_700_000_000_001);
+
+    let history = repo.history("clock-id", &star()).await.unwrap();
+    let stamps: Vec<(i64, &serde_json::Value)> = history
+        .iter()
+        .map(|env| (env.created_at.timestamp_millis(), &env.payload["count"]))
+        .collect();
+    assert_eq!(
+        stamps,
+        vec![
+            (1_700_000_000_000, &json!(1)),
+            (1_700_000_000_001, &json!(2))
+        ]
+    );
+
+    let latest = repo.read("clock-id", &star(), None).await.unwrap().unwrap();
+    assert_eq!(latest.payload["count"], json!(2));
+    let first = repo
+        .read("clock-id", &star(), Some(start))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.payload["count"], json!(1));
+}
+
+/// `repo` must list, count and read many as of `clock`, as it reads one.
+pub async fn test_latest_versions_are_read_as_of_the_clock(
+    repo: &dyn Repository,
+    clock: &MockClock,
+) {
+    let start = chrono::DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+    let later = start + chrono::Duration::hours(1);
+    clock.set(start);
+    for (id, count, created_at) in [
+        ("as-of-id", 1, start),
+        ("as-of-id", 2, later),
+        ("as-of-later", 3, later),
+    ] {
+        let mut payload = Stash::new();
+        payload.insert("count".to_string(), json!(count));
+        let env = Envelope {
+            created_at,
+            ..Envelope::new(id, payload, star())
+        };
+        repo.create(env, &star()).await.unwrap();
+    }
+
+    let read = repo.read("as-of-id", &star(), None).await.unwrap().unwrap();
+    assert_eq!(read.payload["count"], json!(1));
+    let listed = repo.list(&star()).await.unwrap();
+    let counts: Vec<(&str, &serde_json::Value)> = listed
+        .iter()
+        .map(|env| (env.id.as_str(), &env.payload["count"]))
+        .collect();
+    assert_eq!(counts, vec![("as-of-id", &json!(1))]);
+    assert_eq!(repo.count(&star()).await.unwrap(), 1);
+    let ids = vec!["as-of-id".to_string(), "as-of-later".to_string()];
+    let many = repo.read_many(&ids, &star()).await.unwrap();
+    assert_eq!(many.len(), 1);
+    assert_eq!(many[0].payload["count"], json!(1));
+}
+
+pub async fn test_upsert_skips_unchanged_payloads(repo: &dyn Repository) {
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("steady"));
+    payload.insert("address".to_string(), json!({"city": "Leeds"}));
+    let env = Envelope {
+        id: "upsert-id".to_string(),
+        payload: payload.clone(),
+        created_at: chrono::Utc::now() - chrono::Duration::seconds(10),
+        deleted: false,
+        authorized_tokens: star(),
+    };
+    let first = repo.upsert(env, &star()).await.unwrap();
+    let again = repo
+        .upsert(Envelope::new("upsert-id", payload.clone(), star()), &star())
+        .await
+        .unwrap();
+    assert_eq!(
+        again.created_at.timestamp_millis(),
+        first.created_at.timestamp_millis(),
+        "An unchanged payload should return the existing version"
+    );
+    assert_eq!(repo.history("upsert-id", &star()).await.unwrap().len(), 1);
+
+    payload.insert("name".to_string(), json!("changed"));
+    let changed = repo
+        .upsert(Envelope::new("upsert-id", payload, star()), &star())
+        .await
+        .unwrap();
+    assert_eq!(changed.payload.get("name").unwrap(), &json!("changed"));
+    assert_eq!(repo.history("upsert-id", &star()).await.unwrap().len(), 2);
+}
+
+pub async fn test_purge_deletes_every_version(repo: &dyn Repository) {
+    let now = chrono::Utc::now();
+    for (name, secs_ago) in [("first", 20), ("second", 10)] {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(name));
+        let env = Envelope {
+            id: "purge-id".to_string(),
+            payload,
+            created_at: now - chrono::Duration::seconds(secs_ago),
+            deleted: false,
+            authorized_tokens: star(),
+        };
+        repo.create(env, &star()).await.unwrap();
+    }
+    assert!(repo.remove("purge-id", &star()).await.unwrap());
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("bystander"));
+    repo.create(Envelope::new("purge-other", payload, star()), &star())
+        .await
+        .unwrap();
+
+    assert_eq!(repo.purge("purge-id", &star()).await.unwrap(), 3);
+    assert!(repo.history("purge-id", &star()).await.unwrap().is_empty());
+    assert!(repo
+        .read_raw("purge-id", &star(), None)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(repo.purge("purge-id", &star()).await.unwrap(), 0);
+    assert_eq!(repo.history("purge-other", &star()).await.unwrap().len(), 1);
+}
+
+pub async fn test_non_matching_token_sees_no_rows(repo: &dyn Repository) {
+    let alice = vec!["alice".to_string()];
+    let bob = vec!["bob".to_string()];
+
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("secret"));
+    let env = Envelope::new("token-id", payload, alice.clone());
+    repo.create(env, &alice).await.unwrap();
+
+    assert!(repo.read("token-id", &bob, None).await.unwrap().is_none());
+    assert!(repo.list(&bob).await.unwrap().is_empty());
+    assert!(repo
+        .read_many(&["token-id".to_string()], &bob)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let found = repo.read("token-id", &alice, None).await.unwrap();
+    assert!(found.is_some());
+    let listed = repo.list(&alice).await.unwrap();
+    assert_eq!(listed.len(), 1);
+}
+
+/// Writes `shared`, held by alice and bob together, and `public`, written
+/// with `*`.
+async fn seed_policy_data(repo: &dyn Repository) {
+    let both = vec!["alice".to_string(), "bob".to_string()];
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("shared"));
+    repo.create(Envelope::new("shared", payload, both.clone()), &both)
+        .await
+        .unwrap();
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("public"));
+    repo.create(Envelope::new("public", payload, star()), &star())
+        .await
+        .unwrap();
+}
+
+/// The ids a caller holding `tokens` can list, sorted, checking `read` agrees.
+async fn visible_ids(repo: &dyn Repository, tokens: &[&str]) -> Vec<String> {
+    let tokens: Vec<String> = tokens.iter().map(|t| t.to_string()).collect();
+    let mut ids: Vec<String> = repo
+        .list(&tokens)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|env| env.id)
+        .collect();
+    ids.sort();
+    for id in ["public", "shared"] {
+        let read = repo.read(id, &tokens, None).await.unwrap();
+        assert_eq!(read.is_some(), ids.iter().any(|listed| listed == id));
+    }
+    ids
+}
+
+/// `repo` must match tokens by [`crate::AuthPolicy::AnyOf`].
+pub async fn test_any_of_policy_needs_one_shared_token(repo: &dyn Repository) {
+    seed_policy_data(repo).await;
+
+    assert_eq!(visible_ids(repo, &["alice"]).await, ["public", "shared"]);
+    assert_eq!(
+        visible_ids(repo, &["bob", "carol"]).await,
+        ["public", "shared"]
+    );
+    assert_eq!(visible_ids(repo, &["carol"]).await, ["public"]);
+    assert_eq!(visible_ids(repo, &["*"]).await, ["public", "shared"]);
+}
+
+/// `repo` must match tokens by [`crate::AuthPolicy::AllOf`].
+pub async fn test_all_of_policy_needs_every_token(repo: &dyn Repository) {
+    seed_policy_data(repo).await;
+
+    assert_eq!(visible_ids(repo, &["alice"]).await, ["public"]);
+    assert_eq!(visible_ids(repo, &["bob", "carol"]).await, ["public"]);
+    assert_eq!(
+        visible_ids(repo, &["alice", "bob"]).await,
+        ["public", "shared"]
+    );
+    assert_eq!(
+        visible_ids(repo, &["alice", "bob", "carol"]).await,
+        ["public", "shared"]
+    );
+    assert_eq!(visible_ids(repo, &["carol"]).await, ["public"]);
+    assert_eq!(visible_ids(repo, &["*"]).await, ["public", "shared"]);
+}
+
+pub async fn test_count_matches_list(repo: &dyn Repository) {
+    let alice = vec!["alice".to_string()];
+    let bob = vec!["bob".to_string()];
+    for id in ["count-a", "count-b", "count-c"] {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(id));
+        repo.create(Envelope::new(id, payload, star()), &star())
+            .await
+            .unwrap();
+    }
+    let mut patch = Stash::new();
+    patch.insert("name".to_string(), json!("renamed"));
+    repo.update("count-a", patch, &star()).await.unwrap();
+    repo.remove("count-b", &star()).await.unwrap();
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("private"));
+    repo.create(Envelope::new("count-d", payload, alice.clone()), &alice)
+        .await
+        .unwrap();
+
+    for tokens in [star(), alice, bob.clone()] {
+        let listed = repo.list(&tokens).await.unwrap();
+        assert_eq!(repo.count(&tokens).await.unwrap(), listed.len() as u64);
+    }
+    assert_eq!(repo.count(&star()).await.unwrap(), 3);
+    // Bob sees the two live records written for `*`, but not Alice's.
+    assert_eq!(repo.count(&bob).await.unwrap(), 2);
+}
+
+pub async fn test_versions_sharing_a_timestamp_resolve_to_the_later_write(repo: &dyn Repository) {
+    let created_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+    for name in ["first", "second"] {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(name));
+        let env = Envelope {
+            created_at,
+            ..Envelope::new("tie-id", payload, star())
+        };
+        repo.create(env, &star()).await.unwrap();
+    }
+
+    let listed = repo.list(&star()).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].payload.get("name"), Some(&json!("second")));
+    let read = repo.read("tie-id", &star(), None).await.unwrap().unwrap();
+    assert_eq!(read.payload.get("name"), Some(&json!("second")));
+    let many = repo
+        .read_many(&["tie-id".to_string()], &star())
+        .await
+        .unwrap();
+    assert_eq!(many.len(), 1);
+    assert_eq!(many[0].payload.get("name"), Some(&json!("second")));
+}
+
+/// Versions of one id written in the same millisecond must each be kept.
+pub async fn test_versions_sharing_a_timestamp_are_all_kept(repo: &dyn Repository) {
+    let created_at = chrono::Utc::now() - chrono::Duration::seconds(1);
+    for name in ["first", "second"] {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(name));
+        let env = Envelope {
+            created_at,
+            ..Envelope::new("kept-tie-id", payload, star())
+        };
+        repo.create(env, &star()).await.unwrap();
+    }
+
+    let history = repo.history("kept-tie-id", &star()).await.unwrap();
+    let names: Vec<&serde_json::Value> = history.iter().map(|env| &env.payload["name"]).collect();
+    assert_eq!(names, vec![&json!("first"), &json!("second")]);
+}
+
+pub async fn test_writes_to_hidden_ids_are_not_authorized(repo: &dyn Repository) {
+    let alice = vec!["alice".to_string()];
+    let bob = vec!["bob".to_string()];
+
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("secret"));
+    let env = Envelope::new("hidden-id", payload.clone(), alice.clone());
+    repo.create(env, &alice).await.unwrap();
+
+    let mut patch = Stash::new();
+    patch.insert("name".to_string(), json!("stolen"));
+    let updated = repo.update("hidden-id", patch.clone(), &bob).await;
+    assert!(matches!(updated, Err(MeshqlError::NotAuthorized(id)) if id == "hidden-id"));
+    let removed = repo.remove("hidden-id", &bob).await;
+    assert!(matches!(removed, Err(MeshqlError::NotAuthorized(_))));
+    let upserted = repo
+        .upsert(Envelope::new("hidden-id", patch.clone(), bob.clone()), &bob)
+        .await;
+    assert!(matches!(upserted, Err(MeshqlError::NotAuthorized(_))));
+
+    assert!(repo
+        .update("missing-id", patch, &bob)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!repo.remove("missing-id", &bob).await.unwrap());
+
+    let stored = repo.read("hidden-id", &alice, None).await.unwrap().unwrap();
+    assert_eq!(stored.payload, payload);
+    assert_eq!(repo.history("hidden-id", &alice).await.unwrap().len(), 1);
+}
+
+pub async fn test_create_many_should_store_5000_listable_rows(repo: &dyn Repository) {
+    let envelopes: Vec<Envelope> = (0..5000)
+        .map(|i| {
+            let mut payload = Stash::new();
+            payload.insert("name".to_string(), json!(format!("batch-{i}")));
+            Envelope::new(
+                if i % 2 == 0 {
+                    String::new()
+                } else {
+                    format!("batch-id-{i}")
+                },
+                payload,
+                star(),
+            )
+        })
+        .collect();
+
+    let results = repo.create_many(envelopes, &star()).await.unwrap();
+    assert_eq!(results.len(), 5000);
+    assert!(results.iter().all(|e| !e.id.is_empty()));
+    assert!(results.iter().all(|e| e.authorized_tokens == star()));
+    assert_eq!(results[1].id, "batch-id-1");
+    assert_eq!(
+        results[4999].payload.get("name"),
+        Some(&json!("batch-4999"))
+    );
+
+    let listed = repo.list(&star()).await.unwrap();
+    assert_eq!(listed.len(), 5000);
+}
+
+pub async fn test_remove_many_reports_missing_ids(repo: &dyn Repository) {
+    let envelopes: Vec<Envelope> = (0..450)
+        .map(|i| {
+            let mut payload = Stash::new();
+            payload.insert("name".to_string(), json!(format!("bulk-rm-{i}")));
+            // Older than the tombstones, so no backend has to break a same-millisecond tie.
+            Envelope {
+                created_at: chrono::Utc::now() - chrono::Duration::seconds(1),
+                ..Envelope::new(format!("bulk-rm-id-{i}"), payload, star())
+            }
+        })
+        .collect();
+    repo.create_many(envelopes, &star()).await.unwrap();
+
+    let ids: Vec<String> = (0..500).map(|i| format!("bulk-rm-id-{i}")).collect();
+    let results = repo.remove_many(&ids, &star()).await.unwrap();
+    assert_eq!(results.len(), 500);
+    assert_eq!(results.values().filter(|&&v| v).count(), 450);
+    assert!((450..500).all(|i| !results[&format!("bulk-rm-id-{i}")]));
+
+    assert!(repo.read_many(&ids, &star()).await.unwrap().is_empty());
+    assert!(repo.list(&star()).await.unwrap().is_empty());
+}
+
+/// `repo` must generate ids by [`crate::IdStrategy::Uuid7`].
+pub async fn test_generated_ids_sort_in_creation_order(repo: &dyn Repository) {
+    let mut ids = Vec::new();
+    for i in 0..50 {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(format!("ordered-{i}")));
+        let created = repo
+            .create(Envelope::new("", payload, star()), &star())
+            .await
+            .unwrap();
+        ids.push(created.id);
+    }
+
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+
+    let first = repo.read(&ids[0], &star(), None).await.unwrap().unwrap();
+    assert_eq!(first.payload.get("name"), Some(&json!("ordered-0")));
+}
+
+/// `deleted_only` lists exactly the removed ids, as their tombstones.
+pub async fn test_list_with_deleted_only_returns_removed(repo: &dyn Repository) {
+    for id in ["trash-1", "trash-2", "trash-3"] {
+        let mut payload = Stash::new();
+        payload.insert("name".to_string(), json!(id));
+        repo.create(Envelope::new(id, payload, star()), &star())
+            .await
+            .unwrap();
+    }
+    assert!(repo.remove("trash-1", &star()).await.unwrap());
+    assert!(repo.remove("trash-3", &star()).await.unwrap());
+
+    let ids = |envelopes: Vec<Envelope>| {
+        let mut ids: Vec<String> = envelopes.into_iter().map(|env| env.id).collect();
+        ids.sort();
+        ids
+    };
+    let deleted_only = ListOptions {
+        deleted_only: true,
+        ..ListOptions::default()
+    };
+    let trash = repo.list_with(&star(), deleted_only).await.unwrap();
+    assert!(trash.iter().all(|env| env.deleted));
+    assert_eq!(ids(trash), vec!["trash-1", "trash-3"]);
+
+    let live = repo
+        .list_with(&star(), ListOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(ids(live), vec!["trash-2"]);
+
+    let everything = ListOptions {
+        include_deleted: true,
+        ..ListOptions::default()
+    };
+    let all = repo.list_with(&star(), everything).await.unwrap();
+    assert_eq!(ids(all), vec!["trash-1", "trash-2", "trash-3"]);
+
+    let second = ListOptions {
+        limit: Some(1),
+        offset: 1,
+        ..everything
+    };
+    let page = repo.list_with(&star(), second).await.unwrap();
+    assert_eq!(ids(page), vec!["trash-2"]);
+}
+
 // ---- Searcher Certification Tests ----
 
+pub async fn test_read_raw_returns_tombstone(repo: &dyn Repository) {
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("doomed"));
+    let env = Envelope {
+        created_at: chrono::Utc::now() - chrono::Duration::seconds(1),
+        ..Envelope::new("raw-1", payload, star())
+    };
+    repo.create(env, &star()).await.unwrap();
+    assert!(repo.remove("raw-1", &star()).await.unwrap());
+
+    assert!(repo.read("raw-1", &star(), None).await.unwrap().is_none());
+    let raw = repo
+        .read_raw("raw-1", &star(), None)
+        .await
+        .unwrap()
+        .expect("the tombstone should be readable");
+    assert!(raw.deleted);
+    assert_eq!(raw.payload.get("name").unwrap(), &json!("doomed"));
+
+    // Before the removal, the live version is the latest.
+    let before = chrono::Utc::now() - chrono::Duration::milliseconds(500);
+    let raw = repo
+        .read_raw("raw-1", &star(), Some(before))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!raw.deleted);
+}
+
 pub async fn seed_searcher_data(repo: &dyn Repository) {
     let items = vec![
         ("s-id-1", "alpha", 10i64, "typeA"),
@@ -193,6 +1018,10 @@ pub async fn seed_searcher_data(repo: &dyn Repository) {
         payload.insert("name".to_string(), json!(name));
         payload.insert("count".to_string(), json!(count));
         payload.insert("type".to_string(), json!(item_type));
+        // Padding, so projections have a wide payload to cut down.
+        for i in 0..16 {
+            payload.insert(format!("extra_{i}"), json!(format!("{name}-{i}")));
+        }
         let env = Envelope::new(id, payload, star());
         repo.create(env, &star()).await.unwrap();
     }
@@ -281,6 +1110,166 @@ pub async fn test_searcher_find_all_by_type_and_name(searcher: &dyn Searcher) {
     assert_eq!(results[0].get("name").unwrap(), &json!("delta"));
 }
 
+pub async fn test_searcher_find_all_in_list(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut args = Stash::new();
+    args.insert("sort".to_string(), json!("id"));
+
+    let results = searcher
+        .find_all(
+            r#"{"payload.name": {"$in": ["alpha", "gamma", "omega"]}}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["s-id-1", "s-id-3"]);
+
+    let results = searcher
+        .find_all(
+            r#"{"id": {"$in": ["s-id-2", "s-id-4"]}, "payload.type": "typeB"}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["s-id-2", "s-id-4"]);
+
+    // A list argument fills a quoted placeholder
+    let mut by_ids = args.clone();
+    by_ids.insert("ids".to_string(), json!(["s-id-1", "s-id-2", "s-id-4"]));
+    let results = searcher
+        .find_all(r#"{"id": {"$in": "{{ids}}"}}"#, &by_ids, &star(), now)
+        .await
+        .unwrap();
+    let ids: Vec<&str> = results.iter().map(|r| r["id"].as_str().unwrap()).collect();
+    assert_eq!(ids, vec!["s-id-1", "s-id-2", "s-id-4"]);
+
+    let results = searcher
+        .find_all(r#"{"id": {"$in": []}}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+}
+
+pub async fn test_searcher_find_all_comparisons(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let ids_for = |results: Vec<Stash>| -> Vec<String> {
+        results
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let mut args = Stash::new();
+    args.insert("sort".to_string(), json!("id"));
+    args.insert("min".to_string(), json!(20));
+    let results = searcher
+        .find_all(
+            r#"{"payload.count": {"$gt": {{min}}}}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids_for(results), vec!["s-id-3", "s-id-4"]);
+
+    let results = searcher
+        .find_all(
+            r#"{"payload.count": {"$lte": {{min}}}}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids_for(results), vec!["s-id-1", "s-id-2"]);
+
+    let results = searcher
+        .find_all(
+            r#"{"payload.count": {"$gte": 20, "$lt": 40}, "payload.type": {"$ne": "typeA"}}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids_for(results), vec!["s-id-2"]);
+
+    // Strings compare lexically: "delta" and "gamma" sort after "beta".
+    let results = searcher
+        .find_all(r#"{"payload.name": {"$gt": "beta"}}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(ids_for(results), vec!["s-id-3", "s-id-4"]);
+}
+
+pub async fn test_searcher_find_all_or_groups(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut args = Stash::new();
+    args.insert("sort".to_string(), json!("id"));
+    let ids_for = |results: Vec<Stash>| -> Vec<String> {
+        results
+            .iter()
+            .map(|r| r["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let results = searcher
+        .find_all(
+            r#"{"$or": [{"payload.type": "typeA"}, {"payload.type": "typeB"}]}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        ids_for(results),
+        vec!["s-id-1", "s-id-2", "s-id-3", "s-id-4"]
+    );
+
+    // Sibling keys still have to match, and each group ANDs its own.
+    let results = searcher
+        .find_all(
+            r#"{"payload.count": {"$gt": 15}, "$or": [
+                {"payload.type": "typeA"},
+                {"payload.type": "typeB", "payload.name": "beta"}
+            ]}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids_for(results), vec!["s-id-2", "s-id-3"]);
+
+    let results = searcher
+        .find_all(
+            r#"{"$or": [{"$or": [{"id": "s-id-4"}, {"payload.name": "alpha"}]}]}"#,
+            &args,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids_for(results), vec!["s-id-1", "s-id-4"]);
+
+    let too_deep = (0..=crate::MAX_OR_DEPTH).fold(r#"{"id": "s-id-1"}"#.to_string(), |q, _| {
+        format!(r#"{{"$or": [{q}]}}"#)
+    });
+    let err = searcher
+        .find_all(&too_deep, &args, &star(), now)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MeshqlError::Template(_)), "{err}");
+}
+
 pub async fn test_searcher_empty_array_for_nonexistent_type(searcher: &dyn Searcher) {
     let mut args = Stash::new();
     args.insert("type".to_string(), json!("typeZ"));
@@ -324,3 +1313,308 @@ pub async fn test_searcher_empty_query(searcher: &dyn Searcher) {
         .unwrap();
     assert!(!results.is_empty());
 }
+
+pub async fn test_searcher_count(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let mut args = Stash::new();
+    args.insert("type".to_string(), json!("typeA"));
+    let n = searcher
+        .count(r#"{"payload.type": "{{type}}"}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(n, 2);
+
+    args.insert("type".to_string(), json!("typeZ"));
+    let n = searcher
+        .count(r#"{"payload.type": "{{type}}"}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(n, 0);
+
+    let mut args = Stash::new();
+    args.insert("limit".to_string(), json!(1));
+    let n = searcher.count(r#"{}"#, &args, &star(), now).await.unwrap();
+    assert_eq!(n, 4);
+}
+
+pub async fn test_searcher_exists(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let mut args = Stash::new();
+    args.insert("type".to_string(), json!("typeA"));
+    assert!(searcher
+        .exists(r#"{"payload.type": "{{type}}"}"#, &args, &star(), now)
+        .await
+        .unwrap());
+
+    args.insert("type".to_string(), json!("typeZ"));
+    assert!(!searcher
+        .exists(r#"{"payload.type": "{{type}}"}"#, &args, &star(), now)
+        .await
+        .unwrap());
+}
+
+pub async fn test_searcher_pages_with_limit_and_offset(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut pages = Vec::new();
+    for offset in [0, 2, 4] {
+        let mut args = Stash::new();
+        args.insert("limit".to_string(), json!(2));
+        args.insert("offset".to_string(), json!(offset));
+        let results = searcher
+            .find_all(r#"{}"#, &args, &star(), now)
+            .await
+            .unwrap();
+        let ids: Vec<String> = results
+            .iter()
+            .map(|r| r.get("id").unwrap().as_str().unwrap().to_string())
+            .collect();
+        pages.push(ids);
+    }
+
+    assert_eq!(pages[0], vec!["s-id-1", "s-id-2"]);
+    assert_eq!(pages[1], vec!["s-id-3", "s-id-4"]);
+    assert!(pages[2].is_empty());
+}
+
+pub async fn test_searcher_sorts_by_count_desc(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut args = Stash::new();
+    args.insert("sort".to_string(), json!("payload.count:desc"));
+    let results = searcher
+        .find_all(r#"{}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    let names: Vec<&str> = results
+        .iter()
+        .map(|r| r.get("name").unwrap().as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["delta", "gamma", "beta", "alpha"]);
+
+    args.insert("sort".to_string(), json!("count:desc"));
+    let err = searcher
+        .find_all(r#"{}"#, &args, &star(), now)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MeshqlError::Parse(_)));
+}
+
+pub async fn test_searcher_collapses_distinct_values(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let ids = |results: &[Stash]| -> Vec<String> {
+        results
+            .iter()
+            .map(|r| r.get("id").unwrap().as_str().unwrap().to_string())
+            .collect()
+    };
+
+    // Two records of each type: one survives per type, the lowest id.
+    let mut args = Stash::new();
+    args.insert("distinct".to_string(), json!("payload.type"));
+    args.insert("sort".to_string(), json!("id"));
+    let results = searcher
+        .find_all(r#"{}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(ids(&results), vec!["s-id-1", "s-id-2"]);
+    let n = searcher.count(r#"{}"#, &args, &star(), now).await.unwrap();
+    assert_eq!(n, 2);
+
+    args.insert("sort".to_string(), json!("payload.count:desc"));
+    let results = searcher
+        .find_all(r#"{}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(ids(&results), vec!["s-id-2", "s-id-1"]);
+
+    // Ids are already distinct among the latest versions.
+    args.insert("distinct".to_string(), json!(true));
+    args.insert("sort".to_string(), json!("id"));
+    let results = searcher
+        .find_all(r#"{}"#, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(ids(&results), vec!["s-id-1", "s-id-2", "s-id-3", "s-id-4"]);
+
+    args.insert("distinct".to_string(), json!("type"));
+    let err = searcher
+        .find_all(r#"{}"#, &args, &star(), now)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, MeshqlError::Parse(_)));
+}
+
+pub async fn test_searcher_projects_requested_fields(searcher: &dyn Searcher) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let fields = vec!["name".to_string(), "count".to_string()];
+
+    let mut args = Stash::new();
+    args.insert("type".to_string(), json!("typeA"));
+    let results = searcher
+        .find_all_projected(
+            r#"{"payload.type": "{{type}}"}"#,
+            &args,
+            &fields,
+            &star(),
+            now,
+        )
+        .await
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    for result in &results {
+        let mut keys: Vec<&str> = result.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![CREATED_AT_KEY, DELETED_KEY, "count", "id", "name"]
+        );
+    }
+
+    let mut args = Stash::new();
+    args.insert("id".to_string(), json!("s-id-1"));
+    let result = searcher
+        .find_projected(r#"{"id": "{{id}}"}"#, &args, &fields[..1], &star(), now)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.len(), 4);
+    assert_eq!(result.get("id").unwrap(), &json!("s-id-1"));
+    assert_eq!(result.get("name").unwrap(), &json!("alpha"));
+    assert_eq!(result.get(DELETED_KEY).unwrap(), &json!(false));
+}
+
+/// Drains 10,000 rows through [`Searcher::find_stream`] in batches of 100,
+/// never holding more than one batch.
+pub async fn test_searcher_streams_large_results(repo: &dyn Repository, searcher: &dyn Searcher) {
+    let envelopes: Vec<Envelope> = (0..10_000)
+        .map(|i| {
+            let mut payload = Stash::new();
+            payload.insert("name".to_string(), json!(format!("stream-{i}")));
+            payload.insert("type".to_string(), json!("stream"));
+            Envelope::new(String::new(), payload, star())
+        })
+        .collect();
+    repo.create_many(envelopes, &star()).await.unwrap();
+
+    let mut args = Stash::new();
+    args.insert("type".to_string(), json!("stream"));
+    let mut stream = searcher
+        .find_stream(
+            r#"{"payload.type": "{{type}}"}"#,
+            &args,
+            &star(),
+            chrono::Utc::now().timestamp_millis(),
+        )
+        .await
+        .unwrap();
+
+    let mut batch = Vec::with_capacity(100);
+    let mut drained = 0;
+    let mut peak = 0;
+    while let Some(row) = stream.try_next().await.unwrap() {
+        assert_eq!(row.get("type"), Some(&json!("stream")));
+        batch.push(row);
+        peak = peak.max(batch.len());
+        if batch.len() == 100 {
+            drained += batch.len();
+            batch.clear();
+        }
+    }
+    drained += batch.len();
+    assert_eq!(drained, 10_000);
+    assert!(peak <= 100, "held {peak} rows at once");
+}
+
+/// `_created_after`/`_created_before` keep results whose version was created
+/// in the window, after `at` has picked each id's latest version.
+pub async fn test_searcher_filters_by_creation_window(
+    repo: &dyn Repository,
+    searcher: &dyn Searcher,
+) {
+    let base = chrono::Utc::now().timestamp_millis() - 10_000;
+    let version = |id: &str, eggs: i64, offset_ms: i64| {
+        let mut payload = Stash::new();
+        payload.insert("type".to_string(), json!("lay_report"));
+        payload.insert("eggs".to_string(), json!(eggs));
+        Envelope {
+            created_at: chrono::DateTime::from_timestamp_millis(base + offset_ms).unwrap(),
+            ..Envelope::new(id, payload, star())
+        }
+    };
+    for env in [
+        version("lay-1", 1, 0),
+        version("lay-2", 2, 1_000),
+        version("lay-3", 3, 2_000),
+        version("lay-4", 4, 4_000),
+        // A later version moves lay-1 out of the window, unless `at` predates it.
+        version("lay-1", 5, 3_000),
+    ] {
+        repo.create(env, &star()).await.unwrap();
+    }
+
+    let template = r#"{"payload.type": "lay_report"}"#;
+    let mut args = Stash::new();
+    args.insert("_created_after".to_string(), json!(base));
+    args.insert("_created_before".to_string(), json!(base + 2_000));
+    args.insert("sort".to_string(), json!("id"));
+    let ids = |results: Vec<Stash>| -> Vec<String> {
+        results
+            .iter()
+            .map(|s| s.get("id").unwrap().as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let results = searcher
+        .find_all(template, &args, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(ids(results), vec!["lay-2"]);
+
+    let before_update = searcher
+        .find_all(template, &args, &star(), base + 2_500)
+        .await
+        .unwrap();
+    assert_eq!(ids(before_update), vec!["lay-1", "lay-2"]);
+
+    let mut open_ended = Stash::new();
+    open_ended.insert("_created_after".to_string(), json!(base + 2_000));
+    open_ended.insert("sort".to_string(), json!("id"));
+    let results = searcher
+        .find_all(template, &open_ended, &star(), now)
+        .await
+        .unwrap();
+    assert_eq!(ids(results), vec!["lay-1", "lay-3", "lay-4"]);
+    assert_eq!(
+        searcher
+            .count(template, &open_ended, &star(), now)
+            .await
+            .unwrap(),
+        3
+    );
+}
+
+/// A searcher sharing its repository's pool finds an envelope the moment it's created.
+pub async fn test_searcher_sees_created_envelope(repo: &dyn Repository, searcher: &dyn Searcher) {
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("shared"));
+    let created = repo
+        .create(Envelope::new("shared-1", payload, star()), &star())
+        .await
+        .unwrap();
+
+    let mut args = Stash::new();
+    args.insert("id".to_string(), json!("shared-1"));
+    let found = searcher
+        .find(
+            r#"{"id": "{{id}}"}"#,
+            &args,
+            &star(),
+            created.created_at.timestamp_millis(),
+        )
+        .await
+        .unwrap()
+        .expect("created envelope should be searchable");
+    assert_eq!(found.get("name"), Some(&json!("shared")));
+}
@@ -0,0 +1,105 @@
+//! The `_created_after`/`_created_before` search args, which keep only results
+//! whose version was created in a window of time.
+
+use crate::{MeshqlError, Result, Stash};
+use serde_json::Value;
+
+/// Search arg keeping results whose version was created at or after these epoch millis.
+pub const CREATED_AFTER_ARG: &str = "_created_after";
+/// Search arg keeping results whose version was created before these epoch millis.
+pub const CREATED_BEFORE_ARG: &str = "_created_before";
+
+/// A window on the creation time of the version a search returns.
+///
+/// The window doesn't change which version is returned: `at` still picks the
+/// latest version of each id as of then, and the window then keeps those
+/// created in it. `after` is inclusive and `before` exclusive, so consecutive
+/// windows neither overlap nor leave gaps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CreatedWindow {
+    pub after: Option<i64>,
+    pub before: Option<i64>,
+}
+
+impl CreatedWindow {
+    /// Read the window from the search args. Bounds are epoch millis, as a
+    /// number or a string of digits.
+    pub fn from_args(args: &Stash) -> Result<Self> {
+        Ok(Self {
+            after: millis(args, CREATED_AFTER_ARG)?,
+            before: millis(args, CREATED_BEFORE_ARG)?,
+        })
+    }
+
+    /// Take both args out of `args`, so they aren't rendered into the template.
+    pub fn remove_args(args: &mut Stash) {
+        args.remove(CREATED_AFTER_ARG);
+        args.remove(CREATED_BEFORE_ARG);
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.after.is_none() && self.before.is_none()
+    }
+
+    pub fn contains(&self, created_at_ms: i64) -> bool {
+        self.after.is_none_or(|after| created_at_ms >= after)
+            && self.before.is_none_or(|before| created_at_ms < before)
+    }
+}
+
+fn millis(args: &Stash, key: &str) -> Result<Option<i64>> {
+    let invalid = |v: &Value| MeshqlError::Parse(format!("{key} must be epoch millis, got {v}"));
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::Number(n)) => n
+            .as_i64()
+            .or_else(|| n.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+            .map(Some)
+            .ok_or_else(|| invalid(&Value::Number(n.clone()))),
+        Some(v @ Value::String(s)) => s.parse().map(Some).map_err(|_| invalid(v)),
+        Some(other) => Err(invalid(other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(value: Value) -> Stash {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn reads_bounds_as_numbers_or_strings() {
+        let window = CreatedWindow::from_args(&args(
+            json!({"_created_after": 100, "_created_before": "200"}),
+        ))
+        .unwrap();
+        assert_eq!(
+            window,
+            CreatedWindow {
+                after: Some(100),
+                before: Some(200)
+            }
+        );
+        assert!(CreatedWindow::from_args(&args(json!({})))
+            .unwrap()
+            .is_unbounded());
+        assert!(CreatedWindow::from_args(&args(json!({"_created_after": "soon"}))).is_err());
+        assert!(CreatedWindow::from_args(&args(json!({"_created_before": 1.5}))).is_err());
+    }
+
+    #[test]
+    fn includes_after_and_excludes_before() {
+        let window = CreatedWindow {
+            after: Some(100),
+            before: Some(200),
+        };
+        assert!(!window.contains(99));
+        assert!(window.contains(100));
+        assert!(window.contains(199));
+        assert!(!window.contains(200));
+        assert!(CreatedWindow::default().contains(i64::MIN));
+    }
+}
@@ -0,0 +1,124 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::DateTime;
+use meshql_core::{Envelope, IdStrategy, MeshqlError, Result};
+use std::collections::HashMap;
+
+pub(crate) type Item = HashMap<String, AttributeValue>;
+
+/// Partition key.
+pub(crate) const ID: &str = "id";
+/// Sort key: see [`version`].
+pub(crate) const VERSION: &str = "version";
+/// The version's `created_at` in epoch milliseconds.
+pub(crate) const CREATED_AT_MS: &str = "created_at_ms";
+pub(crate) const PAYLOAD: &str = "payload";
+pub(crate) const DELETED: &str = "deleted";
+/// String set; absent when the envelope has no tokens, as sets can't be empty.
+pub(crate) const TOKENS: &str = "authorized_tokens";
+/// Present only on the newest live version of each id, which puts exactly
+/// those items in the sparse [`LIVE_INDEX`].
+pub(crate) const LIVE: &str = "live";
+pub(crate) const LIVE_VALUE: &str = "1";
+pub(crate) const LIVE_INDEX: &str = "live-index";
+/// Set on a version once a conditional write has put another over it, to
+/// that version's [`VERSION`], so a second write based on it fails.
+pub(crate) const SUCCESSOR: &str = "successor";
+
+/// A new sort key for a version created at `created_at_ms`: the milliseconds
+/// zero-padded, so keys sort by time, then a ULID, so versions written in the
+/// same millisecond get keys of their own that sort in write order.
+pub(crate) fn new_version(created_at_ms: i64) -> String {
+    format!("{created_at_ms:020}#{}", IdStrategy::Ulid.generate())
+}
+
+/// The sort keys of versions created at or before `cutoff_ms` are below this.
+pub(crate) fn versions_before(cutoff_ms: i64) -> String {
+    format!("{:020}", cutoff_ms.saturating_add(1))
+}
+
+pub(crate) fn key(id: &str, version: &str) -> Item {
+    HashMap::from([
+        (ID.to_string(), AttributeValue::S(id.to_string())),
+        (VERSION.to_string(), AttributeValue::S(version.to_string())),
+    ])
+}
+
+/// The item storing `env` under a new [`VERSION`], flagged [`LIVE`] when it is
+/// the id's newest live version.
+pub(crate) fn to_item(env: &Envelope, live: bool) -> Result<Item> {
+    let payload =
+        serde_json::to_string(&env.payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+    let created_at_ms = env.created_at.timestamp_millis();
+    let mut item = key(&env.id, &new_version(created_at_ms));
+    item.insert(
+        CREATED_AT_MS.to_string(),
+        AttributeValue::N(created_at_ms.to_string()),
+    );
+    item.insert(PAYLOAD.to_string(), AttributeValue::S(payload));
+    item.insert(DELETED.to_string(), AttributeValue::Bool(env.deleted));
+    if !env.authorized_tokens.is_empty() {
+        item.insert(
+            TOKENS.to_string(),
+            AttributeValue::Ss(env.authorized_tokens.clone()),
+        );
+    }
+    if live && !env.deleted {
+        item.insert(LIVE.to_string(), AttributeValue::S(LIVE_VALUE.to_string()));
+    }
+    Ok(item)
+}
+
+pub(crate) fn from_item(item: &Item) -> Result<Envelope> {
+    let missing = |name: &str| MeshqlError::Parse(format!("DynamoDB item is missing {name}"));
+    let id = item
+        .get(ID)
+        .and_then(|v| v.as_s().ok())
+        .ok_or_else(|| missing(ID))?;
+    let payload = item
+        .get(PAYLOAD)
+        .and_then(|v| v.as_s().ok())
+        .ok_or_else(|| missing(PAYLOAD))?;
+    let payload = serde_json::from_str(payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+    let created_at = created_at_ms(item)
+        .and_then(DateTime::from_timestamp_millis)
+        .ok_or_else(|| missing(CREATED_AT_MS))?;
+    Ok(Envelope {
+        id: id.clone(),
+        payload,
+        created_at,
+        deleted: item
+            .get(DELETED)
+            .and_then(|v| v.as_bool().ok())
+            .copied()
+            .unwrap_or(false),
+        authorized_tokens: item
+            .get(TOKENS)
+            .and_then(|v| v.as_ss().ok())
+            .cloned()
+            .unwrap_or_default(),
+    })
+}
+
+pub(crate) fn created_at_ms(item: &Item) -> Option<i64> {
+    item.get(CREATED_AT_MS)?.as_n().ok()?.parse().ok()
+}
+
+pub(crate) fn version(item: &Item) -> Option<&str> {
+    item.get(VERSION)?.as_s().ok().map(String::as_str)
+}
+
+/// The key `item` is stored under.
+pub(crate) fn key_of(item: &Item) -> Option<Item> {
+    let id = item.get(ID)?.as_s().ok()?;
+    Some(key(id, version(item)?))
+}
+
+/// Whether a caller holding `tokens` may see `env`. Envelopes stored with `*`
+/// are visible to everyone, and a caller holding `*` sees everything.
+pub(crate) fn is_visible(env: &Envelope, tokens: &[String]) -> bool {
+    tokens.iter().any(|t| t == "*")
+        || env
+            .authorized_tokens
+            .iter()
+            .any(|t| t == "*" || tokens.contains(t))
+}
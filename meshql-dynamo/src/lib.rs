@@ -0,0 +1,29 @@
+//! A [`meshql_core::Repository`] over a single DynamoDB table.
+//!
+//! Every version of an envelope is its own item, keyed by partition key `id`
+//! and sort key `version`: the version's `created_at_ms`, zero-padded, then a
+//! ULID, so versions written in the same millisecond each keep their own item
+//! and sort in write order. Reading an id as of a time is then one `Query` on
+//! `id = :id AND version < :next_ms` with `ScanIndexForward=false` and
+//! `Limit=1`; its history is the same partition read forwards.
+//!
+//! The payload is stored as a JSON string, `deleted` as a boolean, and the
+//! authorized tokens as a string set that `list` filters on with `contains`.
+//!
+//! `list` can't afford to read every version of every id, so the newest
+//! non-deleted version of each id also carries `live = "1"`. That attribute is
+//! the partition key of the sparse `live-index` GSI (sort key `id`), which holds
+//! exactly those items. A write that supersedes the newest version moves the
+//! flag in one transaction; removing an id writes an unflagged tombstone.
+//! The GSI is eventually consistent, so a `list` straight after a write may
+//! briefly miss it.
+//!
+//! `create_many` writes through `BatchWriteItem`, 25 puts per call, which can't
+//! move flags transactionally: each put is flagged live as written. `list`
+//! keeps only the newest flagged version per id, and the next single write to
+//! that id clears the stale flag.
+
+mod item;
+mod repository;
+
+pub use repository::DynamoRepository;
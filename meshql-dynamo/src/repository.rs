@@ -0,0 +1,612 @@
+use crate::item::{
+    self, Item, CREATED_AT_MS, ID, LIVE, LIVE_INDEX, LIVE_VALUE, SUCCESSOR, VERSION,
+};
+use async_trait::async_trait;
+use aws_sdk_dynamodb::error::DisplayErrorContext;
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, AttributeValue, BillingMode, DeleteRequest, GlobalSecondaryIndex,
+    KeySchemaElement, KeyType, Projection, ProjectionType, Put, PutRequest, ScalarAttributeType,
+    TransactWriteItem, Update, WriteRequest,
+};
+use aws_sdk_dynamodb::Client;
+use chrono::{DateTime, Utc};
+use meshql_core::{
+    conflict, forbid_hidden, supersedes, Clock, Envelope, IdStrategy, ListOptions, MeshqlError,
+    Repository, Result, SystemClock,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Most puts a single `BatchWriteItem` call accepts.
+const BATCH_SIZE: usize = 25;
+
+fn storage(e: impl std::error::Error) -> MeshqlError {
+    MeshqlError::Storage(DisplayErrorContext(e).to_string())
+}
+
+pub struct DynamoRepository {
+    client: Client,
+    table: String,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+}
+
+impl DynamoRepository {
+    pub fn new(client: Client, table: impl Into<String>) -> Self {
+        Self {
+            client,
+            table: table.into(),
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Create the table and its live index, billed on demand, unless it exists.
+    pub async fn create_table(&self) -> Result<()> {
+        let attribute = |name: &str, ty: ScalarAttributeType| {
+            AttributeDefinition::builder()
+                .attribute_name(name)
+                .attribute_type(ty)
+                .build()
+                .map_err(storage)
+        };
+        let key = |name: &str, ty: KeyType| {
+            KeySchemaElement::builder()
+                .attribute_name(name)
+                .key_type(ty)
+                .build()
+                .map_err(storage)
+        };
+        let live_index = GlobalSecondaryIndex::builder()
+            .index_name(LIVE_INDEX)
+            .key_schema(key(LIVE, KeyType::Hash)?)
+            .key_schema(key(ID, KeyType::Range)?)
+            .projection(
+                Projection::builder()
+                    .projection_type(ProjectionType::All)
+                    .build(),
+            )
+            .build()
+            .map_err(storage)?;
+
+        let created = self
+            .client
+            .create_table()
+            .table_name(&self.table)
+            .billing_mode(BillingMode::PayPerRequest)
+            .attribute_definitions(attribute(ID, ScalarAttributeType::S)?)
+            .attribute_definitions(attribute(VERSION, ScalarAttributeType::S)?)
+            .attribute_definitions(attribute(LIVE, ScalarAttributeType::S)?)
+            .key_schema(key(ID, KeyType::Hash)?)
+            .key_schema(key(VERSION, KeyType::Range)?)
+            .global_secondary_indexes(live_index)
+            .send()
+            .await;
+        match created {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_resource_in_use_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(storage(e)),
+        }
+    }
+
+    /// The newest version of `id` created at or before `cutoff_ms`.
+    async fn newest(&self, id: &str, cutoff_ms: i64) -> Result<Option<Item>> {
+        let output = self
+            .client
+            .query()
+            .table_name(&self.table)
+            .key_condition_expression("#id = :id AND #version < :before")
+            .expression_attribute_names("#id", ID)
+            .expression_attribute_names("#version", VERSION)
+            .expression_attribute_values(":id", AttributeValue::S(id.to_string()))
+            .expression_attribute_values(
+                ":before",
+                AttributeValue::S(item::versions_before(cutoff_ms)),
+            )
+            .scan_index_forward(false)
+            .limit(1)
+            .consistent_read(true)
+            .send()
+            .await
+            .map_err(storage)?;
+        Ok(output.items().first().cloned())
+    }
+
+    /// Every stored version of `id`, oldest first.
+    async fn versions(&self, id: &str) -> Result<Vec<Item>> {
+        let mut items = self
+            .client
+            .query()
+            .table_name(&self.table)
+            .key_condition_expression("#id = :id")
+            .expression_attribute_names("#id", ID)
+            .expression_attribute_values(":id", AttributeValue::S(id.to_string()))
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+        let mut versions = Vec::new();
+        while let Some(found) = items.next().await {
+            versions.push(found.map_err(storage)?);
+        }
+        Ok(versions)
+    }
+
+    /// The newest version of every id, tombstones included. Tombstones are
+    /// left out of [`LIVE_INDEX`], so this scans the whole table.
+    async fn scan_latest(&self) -> Result<BTreeMap<String, (String, Envelope)>> {
+        let mut latest = BTreeMap::new();
+        let mut items = self
+            .client
+            .scan()
+            .table_name(&self.table)
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+        while let Some(found) = items.next().await {
+            keep_newest(&mut latest, &found.map_err(storage)?)?;
+        }
+        Ok(latest)
+    }
+
+    /// Store `env`, moving the live flag onto it when it is now its id's newest version.
+    async fn put(&self, env: &Envelope) -> Result<()> {
+        let newest = self.newest(&env.id, i64::MAX).await?;
+        let is_newest = newest
+            .as_ref()
+            .and_then(item::created_at_ms)
+            .is_none_or(|ms| env.created_at.timestamp_millis() >= ms);
+        let item = item::to_item(env, is_newest)?;
+
+        let demoted = newest
+            .as_ref()
+            .filter(|previous| is_newest && previous.contains_key(LIVE))
+            .and_then(item::key_of);
+        let Some(demoted) = demoted else {
+            self.client
+                .put_item()
+                .table_name(&self.table)
+                .set_item(Some(item))
+                .send()
+                .await
+                .map_err(storage)?;
+            return Ok(());
+        };
+
+        let put = Put::builder()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .build()
+            .map_err(storage)?;
+        let demote = Update::builder()
+            .table_name(&self.table)
+            .set_key(Some(demoted))
+            .update_expression("REMOVE #live")
+            .expression_attribute_names("#live", LIVE)
+            .build()
+            .map_err(storage)?;
+        self.client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().put(put).build())
+            .transact_items(TransactWriteItem::builder().update(demote).build())
+            .send()
+            .await
+            .map_err(storage)?;
+        Ok(())
+    }
+    /// Write `items` with `BatchWriteItem`, [`BATCH_SIZE`] at a time. A batch
+    /// can't hold two items with the same key.
+    async fn batch_put(&self, items: Vec<Item>) -> Result<()> {
+        let requests = items
+            .into_iter()
+            .map(|item| {
+                let put = PutRequest::builder()
+                    .set_item(Some(item))
+                    .build()
+                    .map_err(storage)?;
+                Ok(WriteRequest::builder().put_request(put).build())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.batch_write(requests).await
+    }
+
+    /// Delete the items under `keys`, [`BATCH_SIZE`] at a time.
+    async fn batch_delete(&self, keys: Vec<Item>) -> Result<()> {
+        let requests = keys
+            .into_iter()
+            .map(|key| {
+                let delete = DeleteRequest::builder()
+                    .set_key(Some(key))
+                    .build()
+                    .map_err(storage)?;
+                Ok(WriteRequest::builder().delete_request(delete).build())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.batch_write(requests).await
+    }
+
+    async fn batch_write(&self, requests: Vec<WriteRequest>) -> Result<()> {
+        for chunk in requests.chunks(BATCH_SIZE) {
+            let mut requests = chunk.to_vec();
+            // Throttled writes come back unprocessed; retry them until none are left.
+            let mut backoff = Duration::from_millis(50);
+            loop {
+                let output = self
+                    .client
+                    .batch_write_item()
+                    .request_items(&self.table, requests)
+                    .send()
+                    .await
+                    .map_err(storage)?;
+                requests = output
+                    .unprocessed_items()
+                    .and_then(|unprocessed| unprocessed.get(&self.table))
+                    .cloned()
+                    .unwrap_or_default();
+                if requests.is_empty() {
+                    break;
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_secs(2));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Repository for DynamoRepository {
+    async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+        self.put(&env).await?;
+        Ok(env)
+    }
+
+    async fn read(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        let cutoff_ms = match at {
+            Some(t) => t.timestamp_millis(),
+            None => self.clock.now().timestamp_millis() + 1,
+        };
+
+        let Some(newest) = self.newest(id, cutoff_ms).await? else {
+            return Ok(None);
+        };
+        let env = item::from_item(&newest)?;
+        Ok(item::is_visible(&env, tokens).then_some(env))
+    }
+
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        if options.deleted() != Some(false) {
+            let latest = self.scan_latest().await?;
+            let listed = latest
+                .into_values()
+                .map(|(_, env)| env)
+                .filter(|env| options.lists(env.deleted) && item::is_visible(env, tokens))
+                .collect();
+            return Ok(options.page(listed));
+        }
+
+        let query = self
+            .client
+            .query()
+            .table_name(&self.table)
+            .index_name(LIVE_INDEX)
+            .key_condition_expression("#live = :live")
+            .expression_attribute_names("#live", LIVE)
+            .expression_attribute_values(":live", AttributeValue::S(LIVE_VALUE.to_string()));
+
+        // Batch writes can leave an older version flagged live next to the
+        // newest, so keep one version per id before checking visibility.
+        let mut latest = BTreeMap::new();
+        let mut items = query.into_paginator().items().send();
+        while let Some(found) = items.next().await {
+            keep_newest(&mut latest, &found.map_err(storage)?)?;
+        }
+        let listed = latest
+            .into_values()
+            .map(|(_, env)| env)
+            .filter(|env| item::is_visible(env, tokens))
+            .collect();
+        Ok(options.page(listed))
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        let mut versions = Vec::new();
+        for found in self.versions(id).await? {
+            let env = item::from_item(&found)?;
+            if item::is_visible(&env, tokens) {
+                versions.push(env);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Versions are keyed by id, so this scans the whole table.
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let mut items = self
+            .client
+            .scan()
+            .table_name(&self.table)
+            .filter_expression("#at >= :from AND #at < :to")
+            .expression_attribute_names("#at", CREATED_AT_MS)
+            .expression_attribute_values(
+                ":from",
+                AttributeValue::N(from.timestamp_millis().to_string()),
+            )
+            .expression_attribute_values(
+                ":to",
+                AttributeValue::N(to.timestamp_millis().to_string()),
+            )
+            .consistent_read(true)
+            .into_paginator()
+            .items()
+            .send();
+        let mut versions = Vec::new();
+        while let Some(found) = items.next().await {
+            let env = item::from_item(&found.map_err(storage)?)?;
+            if item::is_visible(&env, tokens) {
+                versions.push(env);
+            }
+        }
+        versions.sort_by_key(|env| env.created_at);
+        Ok(versions)
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
+        let current = self.read(id, tokens, None).await?;
+        match current {
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
+            Some(env) => {
+                let deleted_env = Envelope {
+                    id: env.id,
+                    payload: env.payload,
+                    created_at: self.clock.now(),
+                    deleted: true,
+                    authorized_tokens: env.authorized_tokens,
+                };
+                self.create(deleted_env, tokens).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Deletes the versions `history` returns.
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let mut keys = Vec::new();
+        for found in self.versions(id).await? {
+            if item::is_visible(&item::from_item(&found)?, tokens) {
+                keys.extend(item::key_of(&found));
+            }
+        }
+        let purged = keys.len() as u64;
+        self.batch_delete(keys).await?;
+        Ok(purged)
+    }
+
+    /// Puts the new version in one transaction with a conditional update
+    /// marking the newest version as succeeded, which fails for every write
+    /// but the first based on it, whichever process it runs in. The first
+    /// versions of a new id have no newest to mark, so they don't conflict.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+        let created_at_ms = env.created_at.timestamp_millis();
+
+        let newest = self.newest(&env.id, i64::MAX).await?;
+        let newest_ms = newest.as_ref().and_then(item::created_at_ms);
+        if let (Some(newest), Some(newest_ms)) = (&newest, newest_ms) {
+            let deleted = item::from_item(newest)?.deleted;
+            if supersedes(newest_ms, deleted, expected_created_at) {
+                return Err(conflict(&env.id));
+            }
+        }
+        let is_newest = newest_ms.is_none_or(|ms| created_at_ms >= ms);
+        let item = item::to_item(&env, is_newest)?;
+        let successor = item[VERSION].clone();
+
+        let put = Put::builder()
+            .table_name(&self.table)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(#id)")
+            .expression_attribute_names("#id", ID)
+            .build()
+            .map_err(storage)?;
+        let mut write = self
+            .client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().put(put).build());
+
+        if let Some(previous) = newest.as_ref().and_then(item::key_of) {
+            let update_expression = match is_newest {
+                true => "SET #successor = :successor REMOVE #live",
+                false => "SET #successor = :successor",
+            };
+            let succeed = Update::builder()
+                .table_name(&self.table)
+                .set_key(Some(previous))
+                .update_expression(update_expression)
+                .condition_expression("attribute_not_exists(#successor)")
+                .expression_attribute_names("#successor", SUCCESSOR)
+                .expression_attribute_values(":successor", successor);
+            let succeed = match is_newest {
+                true => succeed.expression_attribute_names("#live", LIVE),
+                false => succeed,
+            };
+            write = write.transact_items(
+                TransactWriteItem::builder()
+                    .update(succeed.build().map_err(storage)?)
+                    .build(),
+            );
+        }
+
+        match write.send().await {
+            Ok(_) => Ok(env),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_transaction_canceled_exception()) =>
+            {
+                Err(conflict(&env.id))
+            }
+            Err(e) => Err(storage(e)),
+        }
+    }
+
+    async fn create_many(
+        &self,
+        envelopes: Vec<Envelope>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let mut results = Vec::with_capacity(envelopes.len());
+        for mut env in envelopes {
+            if env.id.is_empty() {
+                env.id = self.ids.generate();
+            }
+            env.authorized_tokens = tokens.to_vec();
+            results.push(env);
+        }
+
+        let items = results
+            .iter()
+            .map(|env| item::to_item(env, true))
+            .collect::<Result<Vec<_>>>()?;
+        self.batch_put(items).await?;
+        Ok(results)
+    }
+
+    async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let mut results = Vec::new();
+        for id in ids {
+            if let Some(env) = self.read(id, tokens, None).await? {
+                results.push(env);
+            }
+        }
+        Ok(results)
+    }
+
+    async fn remove_many(
+        &self,
+        ids: &[String],
+        tokens: &[String],
+    ) -> Result<HashMap<String, bool>> {
+        let mut removed = HashSet::new();
+        let now = self.clock.now();
+
+        // Each tombstone goes in alongside a copy of the version it replaces with
+        // the live flag dropped, so the batch takes the ids out of `list` too.
+        let mut items = Vec::with_capacity(ids.len() * 2);
+        for id in ids {
+            if removed.contains(id) {
+                continue;
+            }
+            let Some(mut newest) = self.newest(id, now.timestamp_millis() + 1).await? else {
+                continue;
+            };
+            let env = item::from_item(&newest)?;
+            if env.deleted || !item::is_visible(&env, tokens) {
+                continue;
+            }
+            removed.insert(id.clone());
+            let tombstone = Envelope {
+                created_at: now,
+                deleted: true,
+                authorized_tokens: tokens.to_vec(),
+                ..env
+            };
+            newest.remove(LIVE);
+            items.push(newest);
+            items.push(item::to_item(&tombstone, false)?);
+        }
+        self.batch_put(items).await?;
+
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.client
+            .describe_table()
+            .table_name(&self.table)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(storage)
+    }
+}
+
+/// Keep `found` in `latest`, by id with its [`VERSION`], if it is the newest
+/// version of its id seen so far.
+fn keep_newest(latest: &mut BTreeMap<String, (String, Envelope)>, found: &Item) -> Result<()> {
+    let version = item::version(found).unwrap_or_default().to_string();
+    let env = item::from_item(found)?;
+    match latest.get(&env.id) {
+        Some((kept, _)) if *kept >= version => {}
+        _ => {
+            latest.insert(env.id.clone(), (version, env));
+        }
+    }
+    Ok(())
+}
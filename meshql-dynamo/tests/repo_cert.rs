@@ -0,0 +1,218 @@
+use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+use meshql_core::testing as cert;
+use meshql_core::MockClock;
+use meshql_dynamo::DynamoRepository;
+use std::sync::Arc;
+use testcontainers::runners::AsyncRunner;
+use testcontainers_modules::dynamodb_local::DynamoDb;
+
+async fn create_repo() -> (DynamoRepository, impl std::any::Any) {
+    let (client, container) = create_client().await;
+    let table = format!("env_{}", uuid::Uuid::new_v4().simple());
+    let repo = DynamoRepository::new(client, table);
+    repo.create_table().await.unwrap();
+    (repo, container)
+}
+
+/// Two repositories over one table, as two server instances would have.
+async fn create_repos() -> (DynamoRepository, DynamoRepository, impl std::any::Any) {
+    let (client, container) = create_client().await;
+    let table = format!("env_{}", uuid::Uuid::new_v4().simple());
+    let first = DynamoRepository::new(client.clone(), table.clone());
+    first.create_table().await.unwrap();
+    (first, DynamoRepository::new(client, table), container)
+}
+
+async fn create_client() -> (aws_sdk_dynamodb::Client, impl std::any::Any) {
+    let container = DynamoDb::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(8000).await.unwrap();
+    let config = aws_sdk_dynamodb::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .endpoint_url(format!("http://127.0.0.1:{port}"))
+        .credentials_provider(Credentials::new("local", "local", None, None, "test"))
+        .build();
+    (aws_sdk_dynamodb::Client::from_conf(config), container)
+}
+
+#[tokio::test]
+async fn create_should_store_and_return_envelope() {
+    let (repo, _c) = create_repo().await;
+    cert::test_create_should_store_and_return_envelope(&repo).await;
+}
+
+#[tokio::test]
+async fn read_should_retrieve_existing_envelope() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_should_retrieve_existing_envelope(&repo).await;
+}
+
+#[tokio::test]
+async fn list_should_retrieve_all_created_envelopes() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_should_retrieve_all_created_envelopes(&repo).await;
+}
+
+#[tokio::test]
+async fn remove_should_delete_envelope() {
+    let (repo, _c) = create_repo().await;
+    cert::test_remove_should_delete_envelope(&repo).await;
+}
+
+#[tokio::test]
+async fn create_many_should_store_multiple_envelopes() {
+    let (repo, _c) = create_repo().await;
+    cert::test_create_many_should_store_multiple_envelopes(&repo).await;
+}
+
+#[tokio::test]
+async fn read_many_should_retrieve_multiple_envelopes() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_should_retrieve_multiple_envelopes(&repo).await;
+}
+
+#[tokio::test]
+async fn read_many_should_return_latest_live_versions() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_returns_latest_live_versions(&repo).await;
+}
+
+#[tokio::test]
+async fn remove_many_should_delete_multiple_envelopes() {
+    let (repo, _c) = create_repo().await;
+    cert::test_remove_many_should_delete_multiple_envelopes(&repo).await;
+}
+
+#[tokio::test]
+async fn bulk_results_should_follow_input_order() {
+    let (repo, _c) = create_repo().await;
+    cert::test_bulk_results_follow_input_order(&repo).await;
+}
+
+#[tokio::test]
+async fn temporal_versioning() {
+    let (repo, _c) = create_repo().await;
+    cert::test_temporal_versioning(&repo).await;
+}
+
+#[tokio::test]
+async fn list_shows_only_latest_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_shows_only_latest_version(&repo).await;
+}
+
+#[tokio::test]
+async fn list_hides_ids_whose_newest_version_is_hidden() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_hides_ids_whose_newest_version_is_hidden(&repo).await;
+}
+
+#[tokio::test]
+async fn read_many_hides_ids_whose_newest_version_is_hidden() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_hides_ids_whose_newest_version_is_hidden(&repo).await;
+}
+
+#[tokio::test]
+async fn update_should_merge_patch_into_new_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_update_should_merge_patch_into_new_version(&repo).await;
+}
+
+#[tokio::test]
+async fn updates_racing_on_one_version_should_conflict_once() {
+    let (repo, _c) = create_repo().await;
+    cert::test_updates_racing_on_one_version_should_conflict_once(&repo).await;
+}
+
+#[tokio::test]
+async fn updates_racing_across_repositories_should_conflict_once() {
+    let (first, second, _c) = create_repos().await;
+    cert::test_updates_racing_across_repositories_should_conflict_once(&first, &second).await;
+}
+
+#[tokio::test]
+async fn versions_should_be_stamped_by_the_clock() {
+    let clock = Arc::new(MockClock::default());
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_clock(clock.clone());
+    cert::test_versions_are_stamped_by_the_clock(&repo, &clock).await;
+}
+
+#[tokio::test]
+async fn history_returns_every_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_history_returns_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn versions_sharing_a_timestamp_resolve_to_the_later_write() {
+    let (repo, _c) = create_repo().await;
+    cert::test_versions_sharing_a_timestamp_resolve_to_the_later_write(&repo).await;
+}
+
+#[tokio::test]
+async fn versions_sharing_a_timestamp_are_all_kept() {
+    let (repo, _c) = create_repo().await;
+    cert::test_versions_sharing_a_timestamp_are_all_kept(&repo).await;
+}
+
+#[tokio::test]
+async fn created_between_returns_versions_in_the_window() {
+    let (repo, _c) = create_repo().await;
+    cert::test_created_between_returns_versions_in_the_window(&repo).await;
+}
+
+#[tokio::test]
+async fn upsert_should_skip_unchanged_payloads() {
+    let (repo, _c) = create_repo().await;
+    cert::test_upsert_skips_unchanged_payloads(&repo).await;
+}
+
+#[tokio::test]
+async fn purge_should_delete_every_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_purge_deletes_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn non_matching_token_sees_no_rows() {
+    let (repo, _c) = create_repo().await;
+    cert::test_non_matching_token_sees_no_rows(&repo).await;
+}
+
+#[tokio::test]
+async fn writes_to_hidden_ids_are_not_authorized() {
+    let (repo, _c) = create_repo().await;
+    cert::test_writes_to_hidden_ids_are_not_authorized(&repo).await;
+}
+
+#[tokio::test]
+async fn count_matches_list() {
+    let (repo, _c) = create_repo().await;
+    cert::test_count_matches_list(&repo).await;
+}
+
+#[tokio::test]
+async fn create_many_should_store_5000_listable_rows() {
+    let (repo, _c) = create_repo().await;
+    cert::test_create_many_should_store_5000_listable_rows(&repo).await;
+}
+
+#[tokio::test]
+async fn remove_many_reports_missing_ids() {
+    let (repo, _c) = create_repo().await;
+    cert::test_remove_many_reports_missing_ids(&repo).await;
+}
+
+#[tokio::test]
+async fn read_raw_returns_tombstone() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_raw_returns_tombstone(&repo).await;
+}
+
+#[tokio::test]
+async fn list_with_deleted_only_returns_removed() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_with_deleted_only_returns_removed(&repo).await;
+}
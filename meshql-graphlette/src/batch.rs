@@ -0,0 +1,298 @@
+use crate::errors::graphql_error;
+use chrono::Utc;
+use meshql_core::{Searcher, Stash};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Upper bound on ids per `$in` query, to stay within backend bind-parameter limits.
+const MAX_IDS_PER_QUERY: usize = 1000;
+
+type BatchResult = async_graphql::Result<Arc<HashMap<String, Vec<Stash>>>>;
+
+/// Request-scoped loader that coalesces in-process relation lookups.
+///
+/// Every resolver that asks for the same target searcher and template while
+/// sibling fields are still being polled joins one batch, which issues a
+/// single `find_all` with an `{"$in": [...]}` filter and hands each parent its
+/// share of the results. Attach a fresh loader to each request with
+/// `async_graphql::Request::data`.
+pub struct BatchLoader {
+    at: i64,
+    creds: Vec<String>,
+    pending: Mutex<HashMap<(usize, String), Arc<Batch>>>,
+}
+
+#[derive(Default)]
+struct Batch {
+    ids: Mutex<Vec<String>>,
+    result: OnceCell<BatchResult>,
+}
+
+impl BatchLoader {
+    pub fn new() -> Self {
+        Self::with_credentials(vec!["*".to_string()])
+    }
+
+    /// A loader whose batched queries run with the request's credentials.
+    pub fn with_credentials(creds: Vec<String>) -> Self {
+        Self {
+            at: Utc::now().timestamp_millis(),
+            creds,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Results of `template` rendered with `id`, where `key` is the template's
+    /// [`batch_key`].
+    ///
+    /// Whichever caller polls the batch first runs its query, and any other
+    /// caller waiting on it takes over if that one is dropped, e.g. by a
+    /// timeout, so no lookup waits on a query nobody is running.
+    pub(crate) async fn load(
+        &self,
+        searcher: &Arc<dyn Searcher>,
+        template: &str,
+        key: &str,
+        id: &str,
+    ) -> async_graphql::Result<Vec<Stash>> {
+        let target = (
+            Arc::as_ptr(searcher) as *const () as usize,
+            template.to_string(),
+        );
+        let batch = {
+            let mut pending = self.pending.lock().unwrap();
+            let batch = Arc::clone(pending.entry(target.clone()).or_default());
+            let mut ids = batch.ids.lock().unwrap();
+            if !ids.iter().any(|i| i == id) {
+                ids.push(id.to_string());
+            }
+            drop(ids);
+            batch
+        };
+
+        let result = batch
+            .result
+            .get_or_init(|| async {
+                // Let sibling resolvers queue their ids until a yield adds no more.
+                let mut seen = 0;
+                loop {
+                    tokio::task::yield_now().await;
+                    let queued = batch.ids.lock().unwrap().len();
+                    if queued == seen {
+                        break;
+                    }
+                    seen = queued;
+                }
+                let ids = {
+                    let mut pending = self.pending.lock().unwrap();
+                    if pending
+                        .get(&target)
+                        .is_some_and(|queued| Arc::ptr_eq(queued, &batch))
+                    {
+                        pending.remove(&target);
+                    }
+                    batch.ids.lock().unwrap().clone()
+                };
+                self.run(searcher, template, key, &ids).await
+            })
+            .await
+            .clone();
+        Ok(result?.get(id).cloned().unwrap_or_default())
+    }
+
+    async fn run(
+        &self,
+        searcher: &Arc<dyn Searcher>,
+        template: &str,
+        key: &str,
+        ids: &[String],
+    ) -> BatchResult {
+        let field = key.strip_prefix("payload.").unwrap_or(key);
+        let mut grouped: HashMap<String, Vec<Stash>> = HashMap::new();
+        for chunk in ids.chunks(MAX_IDS_PER_QUERY) {
+            let query = in_template(template, key, chunk)?;
+            let results = searcher
+                .find_all(&query, &Stash::new(), &self.creds, self.at)
+                .await
+                .map_err(graphql_error)?;
+            for stash in results {
+                if let Some(owner) = stash.get(field).and_then(Value::as_str) {
+                    grouped.entry(owner.to_string()).or_default().push(stash);
+                }
+            }
+        }
+        Ok(Arc::new(grouped))
+    }
+}
+
+impl Default for BatchLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The key a relation template binds the parent id to, e.g. `payload.coop_id`
+/// for `{"payload.coop_id": "{{id}}"}`.
+///
+/// `None` when the template can't be rewritten into an `$in` query: it isn't a
+/// JSON object, binds `{{id}}` to anything but exactly one `id` or
+/// `payload.<field>` key, or uses any other placeholder.
+pub(crate) fn batch_key(template: &str) -> Option<String> {
+    let query: serde_json::Map<String, Value> = serde_json::from_str(template).ok()?;
+    let mut key = None;
+    for (k, v) in &query {
+        if v.as_str() == Some("{{id}}") {
+            let field = k.strip_prefix("payload.").unwrap_or(k);
+            if key.is_some() || (k != "id" && (field == k || field.contains('.'))) {
+                return None;
+            }
+            key = Some(k.clone());
+        } else if v.to_string().contains("{{") {
+            return None;
+        }
+    }
+    key
+}
+
+/// Rewrite `template` so `key` matches any of `ids`. The result is plain JSON,
+/// so rendering it as a template leaves it untouched.
+fn in_template(template: &str, key: &str, ids: &[String]) -> async_graphql::Result<String> {
+    if ids.iter().any(|id| id.contains("{{")) {
+        return Err(async_graphql::Error::new(
+            "Related ids may not contain template placeholders",
+        ));
+    }
+    let mut query: serde_json::Map<String, Value> = serde_json::from_str(template)?;
+    query.insert(key.to_string(), serde_json::json!({ "$in": ids }));
+    Ok(Value::Object(query).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Takes a while to find one hen in each of two coops.
+    struct Slow;
+
+    #[async_trait::async_trait]
+    impl Searcher for Slow {
+        async fn find(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Option<Stash>> {
+            Ok(None)
+        }
+
+        async fn find_all(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Vec<Stash>> {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let hens = [json!({"coop_id": "c1"}), json!({"coop_id": "c2"})];
+            Ok(hens
+                .into_iter()
+                .filter_map(|hen| hen.as_object().cloned())
+                .collect())
+        }
+
+        async fn count(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<u64> {
+            Ok(0)
+        }
+
+        async fn exists(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<bool> {
+            Ok(false)
+        }
+
+        async fn ping(&self) -> meshql_core::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn waiters_finish_the_query_when_its_first_caller_is_dropped() {
+        let loader = BatchLoader::new();
+        let searcher: Arc<dyn Searcher> = Arc::new(Slow);
+        let template = r#"{"payload.coop_id": "{{id}}"}"#;
+        let key = "payload.coop_id";
+
+        let (first, second) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(
+                tokio::time::timeout(
+                    Duration::from_millis(10),
+                    loader.load(&searcher, template, key, "c1"),
+                ),
+                loader.load(&searcher, template, key, "c2"),
+            )
+        })
+        .await
+        .expect("the second lookup ran the query itself");
+
+        assert!(first.is_err(), "the first lookup timed out");
+        assert_eq!(
+            second.unwrap(),
+            vec![json!({"coop_id": "c2"}).as_object().cloned().unwrap()]
+        );
+    }
+
+    #[test]
+    fn finds_the_key_bound_to_id() {
+        assert_eq!(batch_key(r#"{"id": "{{id}}"}"#).as_deref(), Some("id"));
+        assert_eq!(
+            batch_key(r#"{"payload.coop_id": "{{id}}", "payload.kind": "hen"}"#).as_deref(),
+            Some("payload.coop_id")
+        );
+    }
+
+    #[test]
+    fn rejects_templates_that_cannot_be_batched() {
+        assert_eq!(batch_key(r#"{"payload.zone": "{{zone}}"}"#), None);
+        assert_eq!(
+            batch_key(r#"{"payload.a": "{{id}}", "payload.b": "{{id}}"}"#),
+            None
+        );
+        assert_eq!(
+            batch_key(r#"{"payload.a": "{{id}}", "payload.b": "{{other}}"}"#),
+            None
+        );
+        assert_eq!(batch_key(r#"{"payload.a.b": "{{id}}"}"#), None);
+        assert_eq!(batch_key(r#"{"coop_id": "{{id}}"}"#), None);
+        assert_eq!(batch_key("not json {{id}}"), None);
+    }
+
+    #[test]
+    fn rewrites_the_key_to_an_in_list() {
+        let query = in_template(
+            r#"{"payload.coop_id": "{{id}}", "payload.kind": "hen"}"#,
+            "payload.coop_id",
+            &["c1".to_string(), "c2".to_string()],
+        )
+        .unwrap();
+        let query: Value = serde_json::from_str(&query).unwrap();
+        assert_eq!(
+            query,
+            serde_json::json!({"payload.coop_id": {"$in": ["c1", "c2"]}, "payload.kind": "hen"})
+        );
+    }
+}
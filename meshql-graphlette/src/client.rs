@@ -0,0 +1,150 @@
+//! Run operations against a graphlette in-process, for tests and for code
+//! embedding one without its HTTP layer.
+
+use crate::schema_builder::execute;
+use async_graphql::dynamic::Schema;
+use async_graphql::{ServerError, Variables};
+use meshql_core::{MeshqlError, Result};
+
+/// A built graphlette schema, queried directly rather than over HTTP.
+///
+/// Operations run as [`GraphletteRouter`](crate::GraphletteRouter) would run
+/// them, with the same batch loader and logging, as a caller holding `*`
+/// unless [`GraphletteClient::with_credentials`] says otherwise.
+pub struct GraphletteClient {
+    path: String,
+    schema: Schema,
+    creds: Vec<String>,
+}
+
+impl GraphletteClient {
+    /// A client for `schema`, logged as the graphlette at `path`.
+    pub fn new(path: impl Into<String>, schema: Schema) -> Self {
+        Self {
+            path: path.into(),
+            schema,
+            creds: vec!["*".to_string()],
+        }
+    }
+
+    /// Run operations as a caller holding `creds`.
+    pub fn with_credentials(mut self, creds: Vec<String>) -> Self {
+        self.creds = creds;
+        self
+    }
+
+    /// Run `query` with `variables`, a JSON object or null, returning the
+    /// response's `data`.
+    ///
+    /// Fails with the first error the response carries, as the
+    /// [`MeshqlError`] its `extensions.code` names; errors without a code,
+    /// such as a query that doesn't parse, are [`MeshqlError::Validation`].
+    pub async fn query(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        let request = async_graphql::Request::new(query).variables(Variables::from_json(variables));
+        let response = execute(&self.schema, request, self.creds.clone(), &self.path).await;
+        if let Some(error) = response.errors.first() {
+            return Err(meshql_error(error));
+        }
+        response
+            .data
+            .into_json()
+            .map_err(|e| MeshqlError::Parse(e.to_string()))
+    }
+}
+
+/// The [`MeshqlError`] a response error stands for, by its `extensions.code`.
+fn meshql_error(error: &ServerError) -> MeshqlError {
+    let code = error
+        .extensions
+        .as_ref()
+        .and_then(|extensions| extensions.get("code"));
+    let message = error.message.clone();
+    match code {
+        Some(async_graphql::Value::String(code)) => match code.as_str() {
+            "NOT_FOUND" => MeshqlError::NotFound(message),
+            "UNAUTHENTICATED" => MeshqlError::Unauthorized,
+            "NOT_AUTHORIZED" => MeshqlError::NotAuthorized(message),
+            "STORAGE" => MeshqlError::Storage(message),
+            "TEMPLATE" => MeshqlError::Template(message),
+            "PARSE" => MeshqlError::Parse(message),
+            "TIMEOUT" => MeshqlError::Timeout(message),
+            "CONFLICT" => MeshqlError::Conflict(message),
+            _ => MeshqlError::Validation(message),
+        },
+        _ => MeshqlError::Validation(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_schema, ResolverRegistry};
+    use meshql_core::{Envelope, Repository, RootConfig, Stash};
+    use meshql_memory::{MemoryRepository, MemorySearcher};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    const FARM_GRAPHQL: &str = r#"
+        type Farm {
+            id: ID
+            name: String
+        }
+        type Query {
+            getById(id: ID, at: Int): Farm
+        }
+    "#;
+
+    async fn farm_client() -> GraphletteClient {
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getById", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        GraphletteClient::new("/farm/graph", schema)
+    }
+
+    #[tokio::test]
+    async fn queries_run_in_process() {
+        let client = farm_client().await;
+        let data = client
+            .query(
+                "query Farm($id: ID) { getById(id: $id) { id name } }",
+                json!({"id": "farm-1"}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            data,
+            json!({"getById": {"id": "farm-1", "name": "Emerdale"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_queries_fail_validation() {
+        let client = farm_client().await;
+        let result = client
+            .query("{ getById(id: \"farm-1\") { acres } }", json!(null))
+            .await;
+        assert!(
+            matches!(result, Err(MeshqlError::Validation(_))),
+            "{result:?}"
+        );
+    }
+}
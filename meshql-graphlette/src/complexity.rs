@@ -0,0 +1,269 @@
+//! Rejects operations whose cost exceeds [`meshql_core::RootConfig::max_complexity`]
+//! before anything is resolved.
+//!
+//! async-graphql's own complexity limit counts every field of a dynamic schema
+//! as 1, which can't tell a scalar from a relation fanning out to a backend, so
+//! the cost is worked out here from the SDL instead.
+
+use crate::logging::operation;
+use crate::schema_builder::{base_type_name, RootTypes};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery,
+};
+use async_graphql::{Response, ServerError, ServerResult, Variables};
+use async_graphql_parser::types::{self as pt, ExecutableDocument, Field, Selection, SelectionSet};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// What an object field costs before counting the fields it selects.
+const RELATION_COST: usize = 2;
+
+/// How many times a list field costs an object field selecting the same.
+const LIST_WEIGHT: usize = 10;
+
+/// For each object type, its fields' type names and whether they're lists.
+type Fields = HashMap<String, HashMap<String, (String, bool)>>;
+
+/// Rejects an operation costing more than `limit`. A scalar field costs 1, an
+/// object field [`RELATION_COST`] plus what it selects, and a list field
+/// [`LIST_WEIGHT`] times that. Introspection is free.
+pub(crate) struct Complexity {
+    limit: usize,
+    fields: Arc<Fields>,
+    roots: Arc<RootTypes>,
+}
+
+impl Complexity {
+    pub(crate) fn new(
+        limit: usize,
+        object_types: &HashMap<String, Vec<pt::FieldDefinition>>,
+        roots: &RootTypes,
+    ) -> Self {
+        let fields = object_types
+            .iter()
+            .map(|(type_name, fields)| {
+                let fields = fields
+                    .iter()
+                    .map(|f| {
+                        let ty = &f.ty.node;
+                        let is_list = matches!(ty.base, pt::BaseType::List(_));
+                        let shape = (base_type_name(ty).to_string(), is_list);
+                        (f.name.node.to_string(), shape)
+                    })
+                    .collect();
+                (type_name.clone(), fields)
+            })
+            .collect();
+        Self {
+            limit,
+            fields: Arc::new(fields),
+            roots: Arc::new(roots.clone()),
+        }
+    }
+}
+
+impl ExtensionFactory for Complexity {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(CostedOperation {
+            limit: self.limit,
+            fields: Arc::clone(&self.fields),
+            roots: Arc::clone(&self.roots),
+            document: Mutex::default(),
+        })
+    }
+}
+
+struct CostedOperation {
+    limit: usize,
+    fields: Arc<Fields>,
+    roots: Arc<RootTypes>,
+    document: Mutex<Option<ExecutableDocument>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for CostedOperation {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        *self.document.lock().unwrap_or_else(|e| e.into_inner()) = Some(document.clone());
+        Ok(document)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let cost = {
+            let document = self.document.lock().unwrap_or_else(|e| e.into_inner());
+            document.as_ref().and_then(|doc| {
+                let (_, op) = operation(doc, operation_name)?;
+                let root = self.roots.of(op.ty);
+                Some(self.selection_cost(doc, root, &op.selection_set.node))
+            })
+        };
+        match cost {
+            Some(cost) if cost > self.limit => Response::from_errors(vec![ServerError::new(
+                format!(
+                    "Query is too complex: it costs {cost}, over the limit of {}.",
+                    self.limit
+                ),
+                None,
+            )]),
+            _ => next.run(ctx, operation_name).await,
+        }
+    }
+}
+
+impl CostedOperation {
+    fn selection_cost(&self, doc: &ExecutableDocument, parent: &str, set: &SelectionSet) -> usize {
+        set.items
+            .iter()
+            .map(|selection| match &selection.node {
+                Selection::Field(field) => self.field_cost(doc, parent, &field.node),
+                Selection::FragmentSpread(spread) => doc
+                    .fragments
+                    .get(&spread.node.fragment_name.node)
+                    .map(|fragment| {
+                        let on = fragment.node.type_condition.node.on.node.as_str();
+                        self.selection_cost(doc, on, &fragment.node.selection_set.node)
+                    })
+                    .unwrap_or(0),
+                Selection::InlineFragment(inline) => {
+                    let on = inline
+                        .node
+                        .type_condition
+                        .as_ref()
+                        .map_or(parent, |t| t.node.on.node.as_str());
+                    self.selection_cost(doc, on, &inline.node.selection_set.node)
+                }
+            })
+            .fold(0, usize::saturating_add)
+    }
+
+    fn field_cost(&self, doc: &ExecutableDocument, parent: &str, field: &Field) -> usize {
+        let name = field.name.node.as_str();
+        if name.starts_with("__") {
+            return 0;
+        }
+        if field.selection_set.node.items.is_empty() {
+            return 1;
+        }
+        let (type_name, is_list) = self
+            .fields
+            .get(parent)
+            .and_then(|fields| fields.get(name))
+            .map_or(("", false), |(type_name, is_list)| {
+                (type_name.as_str(), *is_list)
+            });
+        let cost = RELATION_COST.saturating_add(self.selection_cost(
+            doc,
+            type_name,
+            &field.selection_set.node,
+        ));
+        if is_list {
+            cost.saturating_mul(LIST_WEIGHT)
+        } else {
+            cost
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{build_schema, ResolverRegistry};
+    use meshql_core::{Envelope, Repository, RootConfig, Stash};
+    use meshql_memory::{MemoryRepository, MemorySearcher};
+    use std::sync::Arc;
+
+    const FARM_SDL: &str = r#"
+        type Farm {
+            id: ID
+            name: String
+            owner: String
+        }
+        type Query {
+            getFarm(id: ID, at: Int): Farm
+            getFarms(at: Int): [Farm]
+        }
+    "#;
+
+    async fn schema(max_complexity: usize) -> async_graphql::dynamic::Schema {
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .vector("getFarms", "{}")
+            .max_complexity(max_complexity)
+            .build();
+        build_schema(
+            FARM_SDL,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn operations_over_the_limit_are_rejected() {
+        // getFarm costs 2, plus 1 for each scalar it selects.
+        let schema = schema(4).await;
+
+        let at_limit = schema
+            .execute(r#"{ getFarm(id: "farm-1") { id name } }"#)
+            .await;
+        assert!(at_limit.errors.is_empty(), "{:?}", at_limit.errors);
+        assert_eq!(
+            at_limit.data.into_json().unwrap(),
+            serde_json::json!({"getFarm": {"id": "farm-1", "name": "Emerdale"}})
+        );
+
+        let over = schema
+            .execute(r#"{ getFarm(id: "farm-1") { id name owner } }"#)
+            .await;
+        assert_eq!(over.errors.len(), 1);
+        assert_eq!(
+            over.errors[0].message,
+            "Query is too complex: it costs 5, over the limit of 4."
+        );
+        assert_eq!(over.data, async_graphql::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn list_fields_cost_more_than_single_ones() {
+        let schema = schema(10).await;
+
+        let single = schema
+            .execute(r#"{ getFarm(id: "farm-1") { name } }"#)
+            .await;
+        assert!(single.errors.is_empty(), "{:?}", single.errors);
+
+        let list = schema.execute("{ getFarms { name } }").await;
+        assert_eq!(
+            list.errors[0].message,
+            "Query is too complex: it costs 30, over the limit of 10."
+        );
+    }
+
+    #[tokio::test]
+    async fn introspection_is_free() {
+        let schema = schema(1).await;
+        let response = schema
+            .execute("{ __schema { types { name fields { name } } } }")
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+    }
+}
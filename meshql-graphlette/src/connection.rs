@@ -0,0 +1,59 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use meshql_core::Stash;
+use serde_json::{json, Value};
+
+/// Opaque Relay cursor for the edge at `position` in a connection read as of `at`.
+///
+/// Later pages are read at the same `at`, so versions written while a client is
+/// paging neither shift nor duplicate edges.
+pub(crate) fn encode_cursor(at: i64, position: usize) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{at}:{position}"))
+}
+
+/// The `(at, position)` an [`encode_cursor`] cursor was made from.
+pub(crate) fn decode_cursor(cursor: &str) -> Option<(i64, usize)> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    let text = String::from_utf8(bytes).ok()?;
+    let (at, position) = text.split_once(':')?;
+    Some((at.parse().ok()?, position.parse().ok()?))
+}
+
+/// One page of a connection, shaped as `{edges: [{cursor, node}], pageInfo, totalCount}`,
+/// from the `items` found from `offset` on in id order, out of `total`.
+pub(crate) fn page(items: Vec<Stash>, at: i64, offset: usize, total: usize) -> Stash {
+    let end = offset + items.len();
+    let edges: Vec<Value> = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, node)| json!({"cursor": encode_cursor(at, offset + i), "node": node}))
+        .collect();
+    let cursor_of = |edge: Option<&Value>| edge.map(|e| e["cursor"].clone()).unwrap_or(Value::Null);
+
+    let mut connection = Stash::new();
+    connection.insert(
+        "pageInfo".to_string(),
+        json!({
+            "hasNextPage": end < total,
+            "hasPreviousPage": offset > 0,
+            "startCursor": cursor_of(edges.first()),
+            "endCursor": cursor_of(edges.last()),
+        }),
+    );
+    connection.insert("edges".to_string(), Value::Array(edges));
+    connection.insert("totalCount".to_string(), json!(total));
+    connection
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_round_trip() {
+        let cursor = encode_cursor(1_700_000_000_000, 7);
+        assert_eq!(cursor, encode_cursor(1_700_000_000_000, 7));
+        assert_eq!(decode_cursor(&cursor), Some((1_700_000_000_000, 7)));
+        assert_eq!(decode_cursor("not a cursor"), None);
+    }
+}
@@ -0,0 +1,78 @@
+use async_graphql::dynamic::Scalar;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use meshql_core::Stash;
+use serde_json::Value;
+
+/// The `Date` scalar: accepts an RFC 3339 timestamp, a `YYYY-MM-DD` date or
+/// epoch milliseconds, and always reads back as an RFC 3339 UTC timestamp.
+pub(crate) fn date_scalar() -> Scalar {
+    Scalar::new("Date")
+        .description("RFC 3339, YYYY-MM-DD or epoch milliseconds in; RFC 3339 UTC out.")
+        .validator(|value| value.clone().into_json().is_ok_and(|v| parse(&v).is_some()))
+}
+
+/// The instant a stored or submitted date value denotes, if it is one.
+pub(crate) fn parse(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                let day = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+                Some(day.and_hms_opt(0, 0, 0)?.and_utc())
+            }),
+        Value::Number(n) => DateTime::from_timestamp_millis(n.as_i64()?),
+        _ => None,
+    }
+}
+
+/// `value` in the canonical form dates are stored and served in.
+pub(crate) fn normalize(value: &Value) -> Result<Value, async_graphql::Error> {
+    match value {
+        Value::Null => Ok(Value::Null),
+        Value::Array(items) => items
+            .iter()
+            .map(normalize)
+            .collect::<Result<_, _>>()
+            .map(Value::Array),
+        v => parse(v)
+            .map(|dt| Value::String(dt.to_rfc3339_opts(SecondsFormat::Millis, true)))
+            .ok_or_else(|| async_graphql::Error::new(format!("{v} is not a valid Date"))),
+    }
+}
+
+/// Rewrite the `date_fields` present in `payload` into canonical form.
+pub(crate) fn normalize_fields(
+    payload: &mut Stash,
+    date_fields: &[String],
+) -> Result<(), async_graphql::Error> {
+    for field in date_fields {
+        if let Some(value) = payload.get_mut(field) {
+            *value = normalize(value)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalizes_strings_and_epoch_millis_to_rfc3339() {
+        let expected = json!("2024-03-01T12:30:00.000Z");
+        assert_eq!(normalize(&json!("2024-03-01T12:30:00Z")).unwrap(), expected);
+        assert_eq!(
+            normalize(&json!("2024-03-01T14:30:00+02:00")).unwrap(),
+            expected
+        );
+        assert_eq!(normalize(&json!(1_709_296_200_000_i64)).unwrap(), expected);
+        assert_eq!(
+            normalize(&json!("2024-03-01")).unwrap(),
+            json!("2024-03-01T00:00:00.000Z")
+        );
+        assert!(normalize(&json!("last tuesday")).is_err());
+        assert!(normalize(&json!(true)).is_err());
+    }
+}
@@ -0,0 +1,55 @@
+//! Resolver errors clients can act on: each carries the failing
+//! [`MeshqlError::code`] in `extensions.code` and the `path` of its field.
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::{ErrorExtensions, PathSegment, QueryPathNode, QueryPathSegment, ServerResult};
+use meshql_core::MeshqlError;
+use std::sync::Arc;
+
+/// A resolver error carrying the [`MeshqlError::code`] as `extensions.code`,
+/// so clients can branch on the kind of failure.
+pub(crate) fn graphql_error(e: MeshqlError) -> async_graphql::Error {
+    async_graphql::Error::new(e.to_string()).extend_with(|_, ext| ext.set("code", e.code()))
+}
+
+/// Fills in the `path` of resolver errors, which dynamic schemas leave empty.
+pub(crate) struct ErrorPath;
+
+impl ExtensionFactory for ErrorPath {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ErrorPath)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for ErrorPath {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<async_graphql::Value>> {
+        let node = info.path_node;
+        next.run(ctx, info).await.map_err(|mut e| {
+            // Errors from nested fields arrive with their own, longer path.
+            if e.path.is_empty() {
+                e.path = path(node);
+            }
+            e
+        })
+    }
+}
+
+fn path(node: &QueryPathNode<'_>) -> Vec<PathSegment> {
+    let mut segments: Vec<PathSegment> = std::iter::once(node)
+        .chain(node.parents())
+        .map(|n| match n.segment {
+            QueryPathSegment::Name(name) => PathSegment::Field(name.to_string()),
+            QueryPathSegment::Index(i) => PathSegment::Index(i),
+        })
+        .collect();
+    segments.reverse();
+    segments
+}
@@ -0,0 +1,78 @@
+//! Hides [`meshql_core::RootConfig::restricted_fields`] from callers whose
+//! [`Credentials`] lack the field's token.
+
+use crate::errors::graphql_error;
+use crate::schema_builder::Credentials;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::ServerResult;
+use meshql_core::{FieldDenial, MeshqlError, RestrictedField};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Tokens granting each restricted field, by type then field.
+type Grants = HashMap<String, HashMap<String, Vec<String>>>;
+
+/// Resolves a restricted field only for callers holding one of its tokens,
+/// or `*`. Requests executed without credentials act with `*`.
+pub(crate) struct FieldAuth {
+    grants: Arc<Grants>,
+    denial: FieldDenial,
+}
+
+impl FieldAuth {
+    pub(crate) fn new(restricted: &[RestrictedField], denial: FieldDenial) -> Self {
+        let mut grants = Grants::new();
+        for r in restricted {
+            grants
+                .entry(r.type_name.clone())
+                .or_default()
+                .entry(r.field.clone())
+                .or_default()
+                .push(r.token.clone());
+        }
+        Self {
+            grants: Arc::new(grants),
+            denial,
+        }
+    }
+}
+
+impl ExtensionFactory for FieldAuth {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(FieldAuth {
+            grants: Arc::clone(&self.grants),
+            denial: self.denial,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for FieldAuth {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<async_graphql::Value>> {
+        let granted = self.grants.get(info.parent_type);
+        let Some(tokens) = granted.and_then(|fields| fields.get(info.name)) else {
+            return next.run(ctx, info).await;
+        };
+        let allowed = match ctx.data_opt::<Credentials>() {
+            Some(Credentials(creds)) => creds
+                .iter()
+                .any(|c| c == "*" || tokens.iter().any(|t| t == c)),
+            None => true,
+        };
+        match (allowed, self.denial) {
+            (true, _) => next.run(ctx, info).await,
+            (false, FieldDenial::Null) => Ok(None),
+            (false, FieldDenial::Error) => {
+                let e = MeshqlError::NotAuthorized(format!("{}.{}", info.parent_type, info.name));
+                Err(graphql_error(e).into_server_error(Default::default()))
+            }
+        }
+    }
+}
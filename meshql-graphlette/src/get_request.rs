@@ -0,0 +1,98 @@
+//! GraphQL queries sent with `GET`, for plain links and CDNs that cache them.
+//! Mutations change data, so they must still be `POST`ed.
+
+use crate::logging::operation;
+use crate::schema_builder::{execute, operation_choice_error, response_body, with_request_id};
+use async_graphql::dynamic::Schema;
+use async_graphql_parser::types::OperationType;
+use axum::http::header::{ALLOW, CACHE_CONTROL};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use meshql_core::Auth;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// A GraphQL request in the query string, for clients that can only `GET`.
+/// `variables` is JSON.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GetParams {
+    query: String,
+    #[serde(default)]
+    variables: Option<String>,
+    #[serde(default)]
+    operation_name: Option<String>,
+}
+
+impl GetParams {
+    /// The request these params describe; `variables` must be JSON.
+    pub(crate) fn into_request(self) -> serde_json::Result<async_graphql::Request> {
+        let mut request = async_graphql::Request::new(self.query);
+        if let Some(variables) = self.variables {
+            let variables = serde_json::from_str(&variables)?;
+            request = request.variables(async_graphql::Variables::from_json(variables));
+        }
+        if let Some(name) = self.operation_name {
+            request = request.operation_name(name);
+        }
+        Ok(request)
+    }
+
+    /// The kind of operation asked for, when the query parses and names one.
+    fn operation_type(&self) -> Option<OperationType> {
+        let document = async_graphql_parser::parse_query(&self.query).ok()?;
+        operation(&document, self.operation_name.as_deref()).map(|(_, op)| op.ty)
+    }
+}
+
+/// Run a query sent with `GET`, answering anything but a query with a 405.
+/// Successful responses carry `cache_control`.
+pub(crate) async fn query(
+    schema: Arc<Schema>,
+    auth: Arc<dyn Auth>,
+    path: String,
+    headers: HeaderMap,
+    params: GetParams,
+    cache_control: Option<HeaderValue>,
+) -> Response {
+    let creds = match auth.authorize(&headers).await {
+        Ok(creds) => creds,
+        Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+    };
+    if let Some(message) = operation_choice_error(&params.query, params.operation_name.as_deref()) {
+        return error(StatusCode::BAD_REQUEST, message).into_response();
+    }
+    if let Some(ty @ (OperationType::Mutation | OperationType::Subscription)) =
+        params.operation_type()
+    {
+        let message = format!("Only queries can be sent with GET; POST this {ty} instead");
+        let mut response = error(StatusCode::METHOD_NOT_ALLOWED, message).into_response();
+        response
+            .headers_mut()
+            .insert(ALLOW, HeaderValue::from_static("POST"));
+        return response;
+    }
+    let request = match params.into_request() {
+        Ok(request) => with_request_id(request, &headers),
+        Err(e) => return invalid_variables(e),
+    };
+    let response = execute(&schema, request, creds, &path).await;
+    let cacheable = response.errors.is_empty();
+    let mut response = axum::Json(response_body(response)).into_response();
+    if let Some(value) = cache_control.filter(|_| cacheable) {
+        response.headers_mut().insert(CACHE_CONTROL, value);
+    }
+    response
+}
+
+/// The 400 for `variables` that aren't JSON.
+pub(crate) fn invalid_variables(e: serde_json::Error) -> Response {
+    error(StatusCode::BAD_REQUEST, format!("variables: {e}")).into_response()
+}
+
+fn error(status: StatusCode, message: String) -> impl IntoResponse {
+    (
+        status,
+        axum::Json(serde_json::json!({"errors": [{"message": message}]})),
+    )
+}
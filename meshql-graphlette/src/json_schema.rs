@@ -0,0 +1,139 @@
+//! A JSON Schema (draft 2020-12) for a graphlette's entities, derived from its
+//! SDL so generated client types can't drift from the graph.
+
+use crate::schema_builder::RootTypes;
+use async_graphql_parser::types as pt;
+use async_graphql_parser::{parse_schema, Positioned};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The JSON Schema describing every object, interface and union type `sdl`
+/// declares, under `$defs` by type name. Root operation types are left out.
+///
+/// Scalars map to their JSON types, `Date` to a `date-time` string and enums
+/// to a string of their values. Relations are `$ref`s to the related type's
+/// definition, lists arrays of them. Non-null fields are `required`.
+pub fn json_schema(sdl: &str) -> async_graphql::Result<Value> {
+    let doc = parse_schema(sdl)
+        .map_err(|e| async_graphql::Error::new(format!("Schema parse error: {e}")))?;
+    let roots = RootTypes::declared(&doc);
+    let types: Vec<&pt::TypeDefinition> = doc
+        .definitions
+        .iter()
+        .filter_map(|def| match def {
+            pt::TypeSystemDefinition::Type(td) => Some(&td.node),
+            _ => None,
+        })
+        .collect();
+    let enums: HashMap<&str, Vec<String>> = types
+        .iter()
+        .filter_map(|td| match &td.kind {
+            pt::TypeKind::Enum(e) => {
+                let values = e.values.iter().map(|v| v.node.value.node.to_string());
+                Some((td.name.node.as_str(), values.collect()))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut defs = Map::new();
+    for td in types {
+        let name = td.name.node.as_str();
+        let def = match &td.kind {
+            pt::TypeKind::Object(obj) if !roots.contains(name) => object(&obj.fields, &enums),
+            pt::TypeKind::Interface(iface) => object(&iface.fields, &enums),
+            pt::TypeKind::Union(union) => {
+                let members = union.members.iter().map(|m| reference(m.node.as_str()));
+                json!({ "anyOf": members.collect::<Vec<_>>() })
+            }
+            _ => continue,
+        };
+        defs.insert(name.to_string(), def);
+    }
+    Ok(json!({ "$schema": DRAFT, "$defs": defs }))
+}
+
+fn object(fields: &[Positioned<pt::FieldDefinition>], enums: &HashMap<&str, Vec<String>>) -> Value {
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|f| {
+            (
+                f.node.name.node.to_string(),
+                field_type(&f.node.ty.node, enums),
+            )
+        })
+        .collect();
+    let required: Vec<&str> = fields
+        .iter()
+        .filter(|f| !f.node.ty.node.nullable)
+        .map(|f| f.node.name.node.as_str())
+        .collect();
+    let mut def = json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        def["required"] = json!(required);
+    }
+    def
+}
+
+fn field_type(ty: &pt::Type, enums: &HashMap<&str, Vec<String>>) -> Value {
+    match &ty.base {
+        pt::BaseType::List(inner) => json!({ "type": "array", "items": field_type(inner, enums) }),
+        pt::BaseType::Named(name) => match name.as_str() {
+            "ID" | "String" => json!({ "type": "string" }),
+            "Int" => json!({ "type": "integer" }),
+            "Float" => json!({ "type": "number" }),
+            "Boolean" => json!({ "type": "boolean" }),
+            "Date" => json!({ "type": "string", "format": "date-time" }),
+            name => match enums.get(name) {
+                Some(values) => json!({ "type": "string", "enum": values }),
+                None => reference(name),
+            },
+        },
+    }
+}
+
+fn reference(type_name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{type_name}") })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_enums_and_non_null_fields() {
+        let schema = json_schema(
+            r#"
+                schema { query: Root }
+                enum Quality { GOOD, BAD }
+                type Egg {
+                    id: ID!
+                    quality: Quality
+                    weights: [Float!]!
+                }
+                type Root {
+                    getEgg(id: ID): Egg
+                }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(schema["$schema"], DRAFT);
+        assert_eq!(
+            schema["$defs"],
+            json!({
+                "Egg": {
+                    "type": "object",
+                    "properties": {
+                        "id": {"type": "string"},
+                        "quality": {"type": "string", "enum": ["GOOD", "BAD"]},
+                        "weights": {"type": "array", "items": {"type": "number"}},
+                    },
+                    "required": ["id", "weights"],
+                }
+            })
+        );
+    }
+}
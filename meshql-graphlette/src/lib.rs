@@ -1,3 +1,26 @@
+pub mod batch;
+mod client;
+mod complexity;
+mod connection;
+mod date;
+mod errors;
+mod field_auth;
+mod get_request;
+mod json_schema;
+mod logging;
+mod persisted;
+mod post_request;
 pub mod schema_builder;
+mod spans;
+mod subscription;
+mod timeout;
+mod upload;
 
-pub use schema_builder::{build_schema, GraphletteRouter, ResolverRegistry};
+pub use batch::BatchLoader;
+pub use client::GraphletteClient;
+pub use json_schema::json_schema;
+pub use schema_builder::{
+    build_schema, build_schema_at, build_schema_at_with_report, build_schema_with_report,
+    BuildReport, Credentials, GraphletteRouter, RequestId, ResolverRegistry, DEFAULT_MAX_DEPTH,
+    REQUEST_ID_HEADER,
+};
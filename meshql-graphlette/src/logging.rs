@@ -0,0 +1,285 @@
+//! A log line for every GraphQL operation: its name, the root fields it
+//! selected, how long it took and how many errors it returned.
+
+use crate::schema_builder::RequestId;
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextRequest,
+};
+use async_graphql::parser::types::{
+    DocumentOperations, ExecutableDocument, OperationDefinition, Selection,
+};
+use async_graphql::{Response, ServerResult, Variables};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Shown instead of a redacted argument's value.
+const REDACTED: &str = "[redacted]";
+
+/// Logs each operation to the `meshql::graphql` target, at `info`, or at
+/// `warn` when the response carries errors, with the request's [`RequestId`]
+/// if it has one. Literal values of the arguments named in `redacted` are
+/// logged as `[redacted]`; variables are never logged.
+pub(crate) struct OperationLog {
+    redacted: Arc<HashSet<String>>,
+}
+
+impl OperationLog {
+    pub(crate) fn new(redacted: &[String]) -> Self {
+        Self {
+            redacted: Arc::new(redacted.iter().cloned().collect()),
+        }
+    }
+}
+
+impl ExtensionFactory for OperationLog {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(LoggedOperation {
+            redacted: Arc::clone(&self.redacted),
+            document: Mutex::default(),
+            operation_name: Mutex::default(),
+            request_id: Mutex::default(),
+        })
+    }
+}
+
+struct LoggedOperation {
+    redacted: Arc<HashSet<String>>,
+    document: Mutex<Option<ExecutableDocument>>,
+    operation_name: Mutex<Option<String>>,
+    /// Read once the request's data is attached, which it isn't yet when the
+    /// request starts.
+    request_id: Mutex<Option<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for LoggedOperation {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let started = Instant::now();
+        let response = next.run(ctx).await;
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+        let operation_name = self
+            .operation_name
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let document = self.document.lock().unwrap_or_else(|e| e.into_inner());
+        let operation = document
+            .as_ref()
+            .and_then(|doc| operation(doc, operation_name.as_deref()));
+        let name = operation_name
+            .or_else(|| operation.and_then(|(name, _)| name.map(str::to_string)))
+            .unwrap_or_default();
+        let selection = operation
+            .map(|(_, op)| summarize(op, &self.redacted))
+            .unwrap_or_default();
+        let errors = response.errors.len();
+        let request_id = self
+            .request_id
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+
+        if errors == 0 {
+            tracing::info!(
+                target: "meshql::graphql",
+                operation = %name,
+                selection = %selection,
+                request_id = request_id.as_deref().unwrap_or_default(),
+                duration_ms,
+                errors,
+                "graphql operation"
+            );
+        } else {
+            tracing::warn!(
+                target: "meshql::graphql",
+                operation = %name,
+                selection = %selection,
+                request_id = request_id.as_deref().unwrap_or_default(),
+                duration_ms,
+                errors,
+                "graphql operation"
+            );
+        }
+        response
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        *self.document.lock().unwrap_or_else(|e| e.into_inner()) = Some(document.clone());
+        Ok(document)
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        *self
+            .operation_name
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = operation_name.map(str::to_string);
+        *self.request_id.lock().unwrap_or_else(|e| e.into_inner()) =
+            ctx.data_opt::<RequestId>().map(|id| id.0.clone());
+        next.run(ctx, operation_name).await
+    }
+}
+
+/// The operation `name` picks out of `doc`, or its only one.
+pub(crate) fn operation<'a>(
+    doc: &'a ExecutableDocument,
+    name: Option<&str>,
+) -> Option<(Option<&'a str>, &'a OperationDefinition)> {
+    match (&doc.operations, name) {
+        (DocumentOperations::Single(op), _) => Some((None, &op.node)),
+        (DocumentOperations::Multiple(ops), Some(name)) => ops
+            .get_key_value(name)
+            .map(|(name, op)| (Some(name.as_str()), &op.node)),
+        (DocumentOperations::Multiple(ops), None) if ops.len() == 1 => ops
+            .iter()
+            .next()
+            .map(|(name, op)| (Some(name.as_str()), &op.node)),
+        _ => None,
+    }
+}
+
+/// The root fields `op` selects with their arguments, e.g.
+/// `getFarm(id: "farm-1"), getCoops(token: [redacted])`.
+fn summarize(op: &OperationDefinition, redacted: &HashSet<String>) -> String {
+    op.selection_set
+        .node
+        .items
+        .iter()
+        .filter_map(|selection| match &selection.node {
+            Selection::Field(field) => Some(&field.node),
+            _ => None,
+        })
+        .map(|field| {
+            if field.arguments.is_empty() {
+                return field.name.node.to_string();
+            }
+            let arguments: Vec<String> = field
+                .arguments
+                .iter()
+                .map(|(name, value)| {
+                    if redacted.contains(name.node.as_str()) {
+                        format!("{}: {REDACTED}", name.node)
+                    } else {
+                        format!("{}: {}", name.node, value.node)
+                    }
+                })
+                .collect();
+            format!("{}({})", field.name.node, arguments.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    /// An event's fields, as text.
+    type Event = Vec<(String, String)>;
+
+    /// Every `meshql::graphql` event.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<Event>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Captured {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            if event.metadata().target() != "meshql::graphql" {
+                return;
+            }
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+
+    #[derive(Default)]
+    struct Fields(Event);
+
+    impl Visit for Fields {
+        fn record_f64(&mut self, field: &Field, value: f64) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_each_operation_with_its_duration() {
+        use crate::{build_schema, ResolverRegistry};
+        use meshql_core::{Envelope, Repository, RootConfig, Stash};
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .redact_argument("secret")
+            .build();
+        let schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getFarm(id: ID, secret: String, at: Int): Farm
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::registry().with(captured.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let response = schema
+            .execute(r#"query FarmName { getFarm(id: "farm-1", secret: "hunter2") { name } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let events = captured.0.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        let field = |name: &str| {
+            events[0]
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+                .unwrap_or_else(|| panic!("no {name} in {:?}", events[0]))
+        };
+        assert_eq!(field("operation"), "FarmName");
+        assert_eq!(
+            field("selection"),
+            r#"getFarm(id: "farm-1", secret: [redacted])"#
+        );
+        assert_eq!(field("errors"), "0");
+        assert!(field("duration_ms").parse::<f64>().unwrap() > 0.0);
+    }
+}
@@ -0,0 +1,172 @@
+//! Runs only the operations listed in [`meshql_core::RootConfig::persisted_queries`].
+//!
+//! Clients send an operation's full text, or just its hash as an Apollo
+//! persisted query:
+//!
+//! ```json
+//! {"extensions": {"persistedQuery": {"version": 1, "sha256Hash": "…"}}}
+//! ```
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest,
+};
+use async_graphql::{ErrorExtensions, Pos, Request, ServerError, ServerResult};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Rejects operations missing from the allow-list with
+/// `PERSISTED_QUERY_NOT_FOUND` (an unknown hash) or
+/// `PERSISTED_QUERY_NOT_IN_LIST` (any other text).
+pub(crate) struct PersistedOnly {
+    /// Allowed documents by the hex SHA-256 of their text.
+    documents: Arc<HashMap<String, String>>,
+}
+
+impl PersistedOnly {
+    pub(crate) fn new(documents: &[String]) -> Self {
+        let documents = documents
+            .iter()
+            .map(|document| (sha256(document), document.clone()))
+            .collect();
+        Self {
+            documents: Arc::new(documents),
+        }
+    }
+}
+
+impl ExtensionFactory for PersistedOnly {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedOnly {
+            documents: Arc::clone(&self.documents),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for PersistedOnly {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        let hash = match request.extensions.remove("persistedQuery") {
+            Some(value) => value
+                .into_json()
+                .ok()
+                .and_then(|v| v.get("sha256Hash")?.as_str().map(String::from))
+                .ok_or_else(|| {
+                    rejection("INVALID", "the persistedQuery extension needs a sha256Hash")
+                })?,
+            None => sha256(&request.query),
+        };
+        match self.documents.get(&hash) {
+            Some(document) => request.query = document.clone(),
+            None if request.query.is_empty() => {
+                return Err(rejection(
+                    "PERSISTED_QUERY_NOT_FOUND",
+                    "PersistedQueryNotFound",
+                ))
+            }
+            None => {
+                return Err(rejection(
+                    "PERSISTED_QUERY_NOT_IN_LIST",
+                    "the operation is not on this graphlette's allow-list",
+                ))
+            }
+        }
+        next.run(ctx, request).await
+    }
+}
+
+fn sha256(document: &str) -> String {
+    format!("{:x}", Sha256::digest(document.as_bytes()))
+}
+
+fn rejection(code: &'static str, message: &str) -> ServerError {
+    async_graphql::Error::new(message)
+        .extend_with(|_, ext| ext.set("code", code))
+        .into_server_error(Pos::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sha256;
+    use crate::{build_schema, ResolverRegistry};
+    use async_graphql::{Request, Response, Value};
+    use meshql_core::{Envelope, Repository, RootConfig};
+    use meshql_memory::{MemoryRepository, MemorySearcher};
+    use std::sync::Arc;
+
+    const ALLOWED: &str = r#"{ getFarm(id: "farm-1") { name } }"#;
+
+    fn code(response: &Response) -> Option<&Value> {
+        response.errors[0]
+            .extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"))
+    }
+
+    fn by_hash(hash: &str) -> Request {
+        let mut request = Request::new("");
+        request.extensions.insert(
+            "persistedQuery".to_string(),
+            Value::from_json(serde_json::json!({"version": 1, "sha256Hash": hash})).unwrap(),
+        );
+        request
+    }
+
+    #[tokio::test]
+    async fn only_listed_operations_run() {
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = meshql_core::Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .persisted_query(ALLOWED)
+            .build();
+        let schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getFarm(id: ID, at: Int): Farm
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let expected = serde_json::json!({"getFarm": {"name": "Emerdale"}});
+
+        for request in [Request::new(ALLOWED), by_hash(&sha256(ALLOWED))] {
+            let response = schema.execute(request).await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(response.data.into_json().unwrap(), expected);
+        }
+
+        let unlisted = schema
+            .execute(r#"{ getFarm(id: "farm-1") { id name } }"#)
+            .await;
+        assert_eq!(
+            code(&unlisted),
+            Some(&Value::from("PERSISTED_QUERY_NOT_IN_LIST"))
+        );
+        assert_eq!(unlisted.data, Value::Null);
+
+        let unknown = schema.execute(by_hash(&sha256("{ __typename }"))).await;
+        assert_eq!(
+            code(&unknown),
+            Some(&Value::from("PERSISTED_QUERY_NOT_FOUND"))
+        );
+    }
+}
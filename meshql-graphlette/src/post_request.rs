@@ -0,0 +1,39 @@
+//! GraphQL requests sent with `POST`, as JSON, as a raw `application/graphql`
+//! query, or as a `multipart/form-data` request carrying files per the
+//! GraphQL multipart request spec.
+
+use async_graphql::http::{receive_body, MultipartOptions};
+use axum::body::Bytes;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::HeaderMap;
+
+/// The request `body` holds, read according to its `Content-Type`. Bodies
+/// without one are read as JSON.
+pub(crate) async fn receive(
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Result<async_graphql::Request, String> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/json");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match mime.as_str() {
+        "application/graphql" => String::from_utf8(body.to_vec())
+            .map(async_graphql::Request::new)
+            .map_err(|e| e.to_string()),
+        "multipart/form-data" => receive_body(
+            Some(content_type),
+            body.as_ref(),
+            MultipartOptions::default(),
+        )
+        .await
+        .map_err(|e| e.to_string()),
+        _ => serde_json::from_slice(&body).map_err(|e| e.to_string()),
+    }
+}
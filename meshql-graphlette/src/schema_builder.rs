@@ -1,18 +1,38 @@
+use crate::batch::{batch_key, BatchLoader};
+use crate::complexity;
+use crate::connection;
+use crate::date;
+use crate::errors::{self, graphql_error};
+use crate::field_auth;
+use crate::get_request::{self, GetParams};
+use crate::json_schema::json_schema;
+use crate::logging;
+use crate::persisted;
+use crate::post_request;
+use crate::spans::{self, ResolverSpan};
+use crate::subscription;
+use crate::timeout;
+use crate::upload;
 use async_graphql::dynamic::{
-    Field, FieldFuture, FieldValue, InputValue, Object, Scalar, Schema, TypeRef,
+    Enum, Field, FieldFuture, FieldValue, InputObject, InputValue, Interface, InterfaceField,
+    Object, Schema, Subscription, TypeRef, Union,
 };
 use async_graphql_parser::parse_schema;
 use async_graphql_parser::types as pt;
-use axum::http::StatusCode;
+use axum::extract::Query;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::response::IntoResponse;
-use axum::routing::post;
+use axum::routing::get;
 use axum::Router;
 use chrono::Utc;
 use meshql_core::{
-    InternalSingletonResolverConfig, InternalVectorResolverConfig, RootConfig, Searcher,
-    SingletonResolverConfig, Stash, VectorResolverConfig,
+    id_string, insert_metadata, render_template, Auth, ComputedField, Envelope,
+    InternalSingletonResolverConfig, InternalVectorResolverConfig, MeshqlError, MissingKey, NoAuth,
+    QueryConfig, Repository, RootConfig, Searcher, SingletonResolverConfig, Stash,
+    VectorResolverConfig, CREATED_AT_KEY, DELETED_KEY, TYPE_KEY,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 fn is_http_url(url: &str) -> bool {
@@ -41,6 +61,7 @@ fn build_selection_set(fields: &[String]) -> String {
 async fn http_graphql_find(
     client: &reqwest::Client,
     url: &str,
+    request_id: Option<&str>,
     query_name: &str,
     id_val: &str,
     at: i64,
@@ -49,9 +70,11 @@ async fn http_graphql_find(
     let selection = build_selection_set(fields);
     let query = format!("{{ {query_name}(id: \"{id_val}\", at: {at}) {selection} }}");
     let body = serde_json::json!({ "query": query });
-    let resp = client
-        .post(url)
-        .json(&body)
+    let mut request = client.post(url).json(&body);
+    if let Some(id) = request_id {
+        request = request.header(REQUEST_ID_HEADER, id);
+    }
+    let resp = request
         .send()
         .await
         .map_err(|e| async_graphql::Error::new(format!("HTTP resolver error: {e}")))?;
@@ -94,6 +117,7 @@ async fn http_graphql_find(
 async fn http_graphql_find_all(
     client: &reqwest::Client,
     url: &str,
+    request_id: Option<&str>,
     query_name: &str,
     id_val: &str,
     at: i64,
@@ -102,9 +126,11 @@ async fn http_graphql_find_all(
     let selection = build_selection_set(fields);
     let query = format!("{{ {query_name}(id: \"{id_val}\", at: {at}) {selection} }}");
     let body = serde_json::json!({ "query": query });
-    let resp = client
-        .post(url)
-        .json(&body)
+    let mut request = client.post(url).json(&body);
+    if let Some(id) = request_id {
+        request = request.header(REQUEST_ID_HEADER, id);
+    }
+    let resp = request
         .send()
         .await
         .map_err(|e| async_graphql::Error::new(format!("HTTP resolver error: {e}")))?;
@@ -161,6 +187,8 @@ pub struct ResolverRegistry {
 pub struct RegistryEntry {
     pub searcher: Arc<dyn Searcher>,
     pub root_config: RootConfig,
+    /// Backs the graphlette's `Mutation` type, when one is registered.
+    pub repository: Option<Arc<dyn Repository>>,
 }
 
 impl ResolverRegistry {
@@ -179,10 +207,39 @@ impl ResolverRegistry {
             RegistryEntry {
                 searcher,
                 root_config,
+                repository: None,
+            },
+        );
+    }
+
+    /// [`ResolverRegistry::register`], along with the repository that writes
+    /// the graphlette's entity, for its mutations and for resolvers writing
+    /// through to it.
+    pub fn register_with_repository(
+        &mut self,
+        path: impl Into<String>,
+        searcher: Arc<dyn Searcher>,
+        root_config: RootConfig,
+        repository: Arc<dyn Repository>,
+    ) {
+        self.entries.insert(
+            path.into(),
+            RegistryEntry {
+                searcher,
+                root_config,
+                repository: Some(repository),
             },
         );
     }
 
+    /// Attach a repository to an already registered graphlette path so its
+    /// schema can expose mutations.
+    pub fn register_repository(&mut self, path: &str, repository: Arc<dyn Repository>) {
+        if let Some(entry) = self.entries.get_mut(path) {
+            entry.repository = Some(repository);
+        }
+    }
+
     /// Given a URL like "http://localhost:3033/coop/graph" or just "/coop/graph", extract path.
     pub fn get_for_url(&self, url: &str) -> Option<&RegistryEntry> {
         let path = if let Ok(parsed) = url::Url::parse(url) {
@@ -229,7 +286,7 @@ fn is_scalar(type_name: &str) -> bool {
 }
 
 /// Get the base type name (unwrapping List wrappers).
-fn base_type_name(ty: &pt::Type) -> &str {
+pub(crate) fn base_type_name(ty: &pt::Type) -> &str {
     match &ty.base {
         pt::BaseType::Named(n) => n.as_ref(),
         pt::BaseType::List(inner) => base_type_name(inner),
@@ -256,7 +313,7 @@ fn gql_value_to_json(v: &async_graphql::Value) -> serde_json::Value {
     }
 }
 
-fn base64_encode(data: &[u8]) -> String {
+pub(crate) fn base64_encode(data: &[u8]) -> String {
     let alphabet = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
     let mut result = String::new();
     for chunk in data.chunks(3) {
@@ -287,13 +344,34 @@ fn base64_encode(data: &[u8]) -> String {
     result
 }
 
-/// Scalar field: extract value from parent Stash and convert to GraphQL value.
-fn scalar_field(field_name: String, type_ref: TypeRef) -> Field {
+/// The stored value a scalar or date field reads: the payload field of its
+/// name, or for `createdAt` and `deleted` the envelope's metadata when the
+/// payload has no such field.
+fn field_value<'a>(stash: &'a Stash, field_name: &str) -> Option<&'a serde_json::Value> {
+    match stash.get(field_name) {
+        Some(v) if !v.is_null() => Some(v),
+        found => match field_name {
+            "createdAt" => stash.get(CREATED_AT_KEY),
+            "deleted" => stash.get(DELETED_KEY),
+            _ => None,
+        }
+        .or(found),
+    }
+}
+
+/// Scalar field: the stored value, or `default` when there is none.
+fn scalar_field(
+    field_name: String,
+    type_ref: TypeRef,
+    default: Option<serde_json::Value>,
+) -> Field {
     Field::new(field_name.clone(), type_ref, move |ctx| {
         let fname = field_name.clone();
+        let default = default.clone();
         FieldFuture::new(async move {
             let stash = ctx.parent_value.try_downcast_ref::<Stash>()?;
-            Ok(stash.get(&fname).cloned().map(|v| {
+            let stored = field_value(stash, &fname).filter(|v| !v.is_null());
+            Ok(stored.cloned().or(default).map(|v| {
                 let gql_val = async_graphql::to_value(v).unwrap_or(async_graphql::Value::Null);
                 FieldValue::value(gql_val)
             }))
@@ -301,6 +379,169 @@ fn scalar_field(field_name: String, type_ref: TypeRef) -> Field {
     })
 }
 
+/// Computed field: two numeric payload fields combined, or null when either
+/// is missing or the division is by zero.
+fn computed_field(field_name: String, type_ref: TypeRef, computed: ComputedField) -> Field {
+    Field::new(field_name, type_ref, move |ctx| {
+        let computed = computed.clone();
+        FieldFuture::new(async move {
+            let stash = ctx.parent_value.try_downcast_ref::<Stash>()?;
+            let operand = |key: &str| match stash.get(key) {
+                Some(serde_json::Value::Number(n)) => Some(n.clone()),
+                _ => None,
+            };
+            let value = operand(&computed.left)
+                .zip(operand(&computed.right))
+                .and_then(|(left, right)| computed.op.apply(&left, &right));
+            Ok(value.map(|v| FieldValue::value(async_graphql::to_value(v).unwrap_or_default())))
+        })
+    })
+}
+
+/// Date field: the stored date served as RFC 3339, or an error when it isn't one.
+fn date_field(field_name: String, type_ref: TypeRef) -> Field {
+    Field::new(field_name.clone(), type_ref, move |ctx| {
+        let fname = field_name.clone();
+        FieldFuture::new(async move {
+            let stash = ctx.parent_value.try_downcast_ref::<Stash>()?;
+            match field_value(stash, &fname) {
+                Some(v) => Ok(Some(FieldValue::value(async_graphql::Value::from_json(
+                    date::normalize(v)?,
+                )?))),
+                None => Ok(FieldValue::NONE),
+            }
+        })
+    })
+}
+
+/// Enum field: the payload string, rejected with an error when it isn't one of `values`.
+fn enum_field(
+    field_name: String,
+    type_ref: TypeRef,
+    type_name: String,
+    values: Vec<String>,
+) -> Field {
+    Field::new(field_name.clone(), type_ref, move |ctx| {
+        let fname = field_name.clone();
+        let type_name = type_name.clone();
+        let values = values.clone();
+        FieldFuture::new(async move {
+            let stash = ctx.parent_value.try_downcast_ref::<Stash>()?;
+            let to_enum = |v: &serde_json::Value| match v.as_str() {
+                Some(s) if values.iter().any(|value| value == s) => Ok(FieldValue::value(
+                    async_graphql::Value::Enum(async_graphql::Name::new(s)),
+                )),
+                _ => Err(async_graphql::Error::new(format!(
+                    "{v} is not a valid {type_name} value"
+                ))),
+            };
+            match stash.get(&fname) {
+                None | Some(serde_json::Value::Null) => Ok(FieldValue::NONE),
+                Some(serde_json::Value::Array(items)) => Ok(Some(FieldValue::list(
+                    items.iter().map(to_enum).collect::<Result<Vec<_>, _>>()?,
+                ))),
+                Some(v) => to_enum(v).map(Some),
+            }
+        })
+    })
+}
+
+/// Credentials the request was authorized with, attached by [`GraphletteRouter`].
+/// Requests executed without them act with `*`.
+pub struct Credentials(pub Vec<String>);
+
+/// Header a request's correlation id arrives in, which HTTP resolvers send on.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The correlation id a request arrived with in its [`REQUEST_ID_HEADER`],
+/// attached by [`GraphletteRouter`] so each operation is logged with it and
+/// relations resolved over HTTP carry it on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The id in `headers`, if they carry a readable one.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|id| Self(id.to_string()))
+    }
+}
+
+/// `request`, carrying the [`RequestId`] in `headers`, if any.
+pub(crate) fn with_request_id(
+    request: async_graphql::Request,
+    headers: &HeaderMap,
+) -> async_graphql::Request {
+    match RequestId::from_headers(headers) {
+        Some(id) => request.data(id),
+        None => request,
+    }
+}
+
+/// Why `operation_name` can't pick the operation to run out of `query`: the
+/// document defines several and none is named, or none is called that. The
+/// message lists the operations there are. `None` when it can, or when
+/// `query` doesn't parse, which execution reports itself.
+pub(crate) fn operation_choice_error(query: &str, operation_name: Option<&str>) -> Option<String> {
+    let doc = async_graphql_parser::parse_query(query).ok()?;
+    let mut names: Vec<&str> = match &doc.operations {
+        pt::DocumentOperations::Single(_) => Vec::new(),
+        pt::DocumentOperations::Multiple(ops) => ops.keys().map(|name| name.as_str()).collect(),
+    };
+    names.sort_unstable();
+    match operation_name {
+        None if names.len() > 1 => Some(format!(
+            "The document defines several operations ({}); name the one to run with operationName",
+            names.join(", ")
+        )),
+        Some(name) if !names.contains(&name) => Some(match names.as_slice() {
+            [] => format!(
+                "No operation is named \"{name}\"; the document's only operation is anonymous"
+            ),
+            names => format!(
+                "No operation is named \"{name}\"; the document defines {}",
+                names.join(", ")
+            ),
+        }),
+        _ => None,
+    }
+}
+
+/// `request`, unless [`operation_choice_error`] says why its operation can't be chosen.
+fn operation_chosen(request: async_graphql::Request) -> Result<async_graphql::Request, String> {
+    match operation_choice_error(&request.query, request.operation_name.as_deref()) {
+        Some(message) => Err(message),
+        None => Ok(request),
+    }
+}
+
+fn request_id(ctx: &async_graphql::dynamic::ResolverContext) -> Option<String> {
+    ctx.data_opt::<RequestId>().map(|id| id.0.clone())
+}
+
+pub(crate) fn credentials(ctx: &async_graphql::dynamic::ResolverContext) -> Vec<String> {
+    ctx.data_opt::<Credentials>()
+        .map(|c| c.0.clone())
+        .unwrap_or_else(|| vec!["*".to_string()])
+}
+
+/// The request's [`BatchLoader`] and the template's batch key, when the
+/// relation can be resolved together with its siblings.
+fn batching<'a>(
+    ctx: &'a async_graphql::dynamic::ResolverContext,
+    batch_key: &'a Option<String>,
+) -> Option<(&'a BatchLoader, &'a str)> {
+    Some((ctx.data_opt::<BatchLoader>()?, batch_key.as_deref()?))
+}
+
+/// The id `parent` holds under `key`, a number read as its decimal string, or
+/// empty if it holds none.
+fn parent_id(parent: &Stash, key: &str) -> String {
+    parent.get(key).and_then(id_string).unwrap_or_default()
+}
+
 /// Singleton relation field: look up foreign key in parent, call target searcher.
 /// If the URL starts with http(s), makes a real HTTP GraphQL call.
 /// Otherwise, uses the in-process registry lookup.
@@ -318,25 +559,37 @@ fn singleton_resolver_field(
             .clone()
             .unwrap_or_else(|| "id".to_string());
 
+        let span = ResolverSpan::new(&field_name, &query_name, &url);
+
         Some(Field::new(field_name, type_ref, move |ctx| {
             let url = url.clone();
             let query_name = query_name.clone();
             let fk = fk.clone();
             let fields = collect_selected_fields(&ctx);
-            FieldFuture::new(async move {
+            FieldFuture::new(span.wrap(async move {
                 let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
-                let id_val = parent.get(&fk).and_then(|v| v.as_str()).unwrap_or("");
+                let id_val = parent_id(parent, &fk);
                 if id_val.is_empty() {
                     return Ok(FieldValue::NONE);
                 }
                 let at = Utc::now().timestamp_millis();
                 let client = reqwest::Client::new();
-                match http_graphql_find(&client, &url, &query_name, id_val, at, &fields).await {
+                let request_id = request_id(&ctx);
+                let found = http_graphql_find(
+                    &client,
+                    &url,
+                    request_id.as_deref(),
+                    &query_name,
+                    &id_val,
+                    at,
+                    &fields,
+                );
+                match found.await {
                     Ok(Some(stash)) => Ok(Some(FieldValue::owned_any(stash))),
                     Ok(None) => Ok(FieldValue::NONE),
                     Err(e) => Err(e),
                 }
-            })
+            }))
         }))
     } else {
         let entry = registry.get_for_url(&resolver.url)?;
@@ -350,28 +603,33 @@ fn singleton_resolver_field(
             .clone()
             .unwrap_or_else(|| "id".to_string());
 
+        let batch_key = batch_key(&template);
+        let span = ResolverSpan::new(&field_name, &resolver.query_name, &resolver.url);
+
         Some(Field::new(field_name, type_ref, move |ctx| {
             let s = Arc::clone(&searcher);
             let tmpl = template.clone();
             let fk = fk.clone();
-            FieldFuture::new(async move {
+            let batch_key = batch_key.clone();
+            FieldFuture::new(span.wrap(async move {
                 let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
-                let id_val = parent.get(&fk).and_then(|v| v.as_str()).unwrap_or("");
+                let id_val = parent_id(parent, &fk);
                 if id_val.is_empty() {
                     return Ok(FieldValue::NONE);
                 }
+                if let Some((loader, key)) = batching(&ctx, &batch_key) {
+                    let related = loader.load(&s, &tmpl, key, &id_val).await?;
+                    return Ok(related.into_iter().next().map(FieldValue::owned_any));
+                }
                 let mut args = Stash::new();
-                args.insert(
-                    "id".to_string(),
-                    serde_json::Value::String(id_val.to_string()),
-                );
+                args.insert("id".to_string(), serde_json::Value::String(id_val));
                 let at = Utc::now().timestamp_millis();
-                match s.find(&tmpl, &args, &["*".to_string()], at).await {
+                match s.find(&tmpl, &args, &credentials(&ctx), at).await {
                     Ok(Some(stash)) => Ok(Some(FieldValue::owned_any(stash))),
                     Ok(None) => Ok(FieldValue::NONE),
-                    Err(e) => Err(async_graphql::Error::new(e.to_string())),
+                    Err(e) => Err(graphql_error(e)),
                 }
-            })
+            }))
         }))
     }
 }
@@ -390,20 +648,32 @@ fn vector_resolver_field(
         let query_name = resolver.query_name.clone();
         let fk = resolver.foreign_key.clone();
 
+        let span = ResolverSpan::new(&field_name, &query_name, &url);
+
         Some(Field::new(field_name, type_ref, move |ctx| {
             let url = url.clone();
             let query_name = query_name.clone();
             let fk = fk.clone();
             let fields = collect_selected_fields(&ctx);
-            FieldFuture::new(async move {
+            FieldFuture::new(span.wrap(async move {
                 let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
                 let id_val = match &fk {
-                    Some(key) => parent.get(key).and_then(|v| v.as_str()).unwrap_or(""),
-                    None => parent.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                    Some(key) => parent_id(parent, key),
+                    None => parent_id(parent, "id"),
                 };
                 let at = Utc::now().timestamp_millis();
                 let client = reqwest::Client::new();
-                match http_graphql_find_all(&client, &url, &query_name, id_val, at, &fields).await {
+                let request_id = request_id(&ctx);
+                let found = http_graphql_find_all(
+                    &client,
+                    &url,
+                    request_id.as_deref(),
+                    &query_name,
+                    &id_val,
+                    at,
+                    &fields,
+                );
+                match found.await {
                     Ok(stashes) => {
                         let items: Vec<FieldValue> =
                             stashes.into_iter().map(FieldValue::owned_any).collect();
@@ -411,7 +681,7 @@ fn vector_resolver_field(
                     }
                     Err(e) => Err(e),
                 }
-            })
+            }))
         }))
     } else {
         let entry = registry.get_for_url(&resolver.url)?;
@@ -422,36 +692,43 @@ fn vector_resolver_field(
             .to_string();
         let fk = resolver.foreign_key.clone();
 
+        let batch_key = batch_key(&template);
+        let span = ResolverSpan::new(&field_name, &resolver.query_name, &resolver.url);
+
         Some(Field::new(field_name, type_ref, move |ctx| {
             let s = Arc::clone(&searcher);
             let tmpl = template.clone();
             let fk = fk.clone();
-            FieldFuture::new(async move {
+            let batch_key = batch_key.clone();
+            FieldFuture::new(span.wrap(async move {
                 let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
                 let id_val = match &fk {
-                    Some(key) => parent.get(key).and_then(|v| v.as_str()).unwrap_or(""),
-                    None => parent.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                    Some(key) => parent_id(parent, key),
+                    None => parent_id(parent, "id"),
                 };
+                if let Some((loader, key)) = batching(&ctx, &batch_key) {
+                    let related = loader.load(&s, &tmpl, key, &id_val).await?;
+                    let items: Vec<FieldValue> =
+                        related.into_iter().map(FieldValue::owned_any).collect();
+                    return Ok(Some(FieldValue::list(items)));
+                }
                 let mut args = Stash::new();
-                args.insert(
-                    "id".to_string(),
-                    serde_json::Value::String(id_val.to_string()),
-                );
+                args.insert("id".to_string(), serde_json::Value::String(id_val));
                 let at = Utc::now().timestamp_millis();
-                match s.find_all(&tmpl, &args, &["*".to_string()], at).await {
+                match s.find_all(&tmpl, &args, &credentials(&ctx), at).await {
                     Ok(stashes) => {
                         let items: Vec<FieldValue> =
                             stashes.into_iter().map(FieldValue::owned_any).collect();
                         Ok(Some(FieldValue::list(items)))
                     }
-                    Err(e) => Err(async_graphql::Error::new(e.to_string())),
+                    Err(e) => Err(graphql_error(e)),
                 }
-            })
+            }))
         }))
     }
 }
 
-/// Internal singleton relation field: look up foreign key in parent, call target searcher via registry.
+/// Internal singleton relation field: look up the foreign keys in parent, call target searcher via registry.
 fn internal_singleton_resolver_field(
     field_name: String,
     type_ref: TypeRef,
@@ -464,33 +741,41 @@ fn internal_singleton_resolver_field(
         .root_config
         .get_template(&resolver.query_name)?
         .to_string();
-    let fk = resolver
-        .foreign_key
-        .clone()
-        .unwrap_or_else(|| "id".to_string());
+    let keys = resolver.foreign_keys.clone();
+    let extra_args = resolver.extra_args.clone();
+
+    // Only a lone `{{id}}` can be gathered into one query across parents.
+    let batch_key = keys.single().and_then(|_| batch_key(&template));
+    let span = ResolverSpan::new(&field_name, &resolver.query_name, &resolver.graphlette_path);
 
     Some(Field::new(field_name, type_ref, move |ctx| {
         let s = Arc::clone(&searcher);
         let tmpl = template.clone();
-        let fk = fk.clone();
-        FieldFuture::new(async move {
+        let keys = keys.clone();
+        let extra_args = extra_args.clone();
+        let batch_key = batch_key.clone();
+        FieldFuture::new(span.wrap(async move {
             let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
-            let id_val = parent.get(&fk).and_then(|v| v.as_str()).unwrap_or("");
-            if id_val.is_empty() {
+            let Some(mut args) = keys.args(parent) else {
                 return Ok(FieldValue::NONE);
+            };
+            if let Some(id_val) = args.get("id").and_then(id_string) {
+                args.insert("id".to_string(), serde_json::Value::String(id_val));
+            }
+            let args = with_extra_args(args, &extra_args);
+            if let Some((loader, key)) = batching(&ctx, &batch_key) {
+                if let Some(id_val) = args.get("id").and_then(|v| v.as_str()) {
+                    let related = loader.load(&s, &tmpl, key, id_val).await?;
+                    return Ok(related.into_iter().next().map(FieldValue::owned_any));
+                }
             }
-            let mut args = Stash::new();
-            args.insert(
-                "id".to_string(),
-                serde_json::Value::String(id_val.to_string()),
-            );
             let at = Utc::now().timestamp_millis();
-            match s.find(&tmpl, &args, &["*".to_string()], at).await {
+            match s.find(&tmpl, &args, &credentials(&ctx), at).await {
                 Ok(Some(stash)) => Ok(Some(FieldValue::owned_any(stash))),
                 Ok(None) => Ok(FieldValue::NONE),
-                Err(e) => Err(async_graphql::Error::new(e.to_string())),
+                Err(e) => Err(graphql_error(e)),
             }
-        })
+        }))
     }))
 }
 
@@ -508,35 +793,214 @@ fn internal_vector_resolver_field(
         .get_template(&resolver.query_name)?
         .to_string();
     let fk = resolver.foreign_key.clone();
+    let extra_args = resolver.extra_args.clone();
+
+    let batch_key = batch_key(&template);
+    let span = ResolverSpan::new(&field_name, &resolver.query_name, &resolver.graphlette_path);
 
     Some(Field::new(field_name, type_ref, move |ctx| {
         let s = Arc::clone(&searcher);
         let tmpl = template.clone();
         let fk = fk.clone();
-        FieldFuture::new(async move {
+        let extra_args = extra_args.clone();
+        let batch_key = batch_key.clone();
+        FieldFuture::new(span.wrap(async move {
             let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
             let id_val = match &fk {
-                Some(key) => parent.get(key).and_then(|v| v.as_str()).unwrap_or(""),
-                None => parent.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                Some(key) => parent_id(parent, key),
+                None => parent_id(parent, "id"),
             };
+            if let Some((loader, key)) = batching(&ctx, &batch_key) {
+                let related = loader.load(&s, &tmpl, key, &id_val).await?;
+                let items: Vec<FieldValue> =
+                    related.into_iter().map(FieldValue::owned_any).collect();
+                return Ok(Some(FieldValue::list(items)));
+            }
             let mut args = Stash::new();
-            args.insert(
-                "id".to_string(),
-                serde_json::Value::String(id_val.to_string()),
-            );
+            args.insert("id".to_string(), serde_json::Value::String(id_val));
+            let args = with_extra_args(args, &extra_args);
             let at = Utc::now().timestamp_millis();
-            match s.find_all(&tmpl, &args, &["*".to_string()], at).await {
+            match s.find_all(&tmpl, &args, &credentials(&ctx), at).await {
                 Ok(stashes) => {
                     let items: Vec<FieldValue> =
                         stashes.into_iter().map(FieldValue::owned_any).collect();
                     Ok(Some(FieldValue::list(items)))
                 }
-                Err(e) => Err(async_graphql::Error::new(e.to_string())),
+                Err(e) => Err(graphql_error(e)),
             }
-        })
+        }))
     }))
 }
 
+/// `args` with each of a resolver's `extra_args` it doesn't already hold.
+fn with_extra_args(mut args: Stash, extra_args: &Stash) -> Stash {
+    for (key, value) in extra_args {
+        args.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+    args
+}
+
+/// Whether a resolver configured for `resolver_field` serves `field_name`, either
+/// exactly or as the last segment of a nested path like "hens.layReports".
+fn names_field(resolver_field: &str, field_name: &str) -> bool {
+    resolver_field == field_name
+        || resolver_field
+            .rsplit_once('.')
+            .map(|(_, suffix)| suffix == field_name)
+            .unwrap_or(false)
+}
+
+/// Where an in-process vector relation's children are searched for.
+struct RelationSource {
+    searcher: Arc<dyn Searcher>,
+    template: String,
+    foreign_key: Option<String>,
+    extra_args: Stash,
+    query_name: String,
+    target: String,
+}
+
+/// The in-process vector resolver for `relation`, configured on this graphlette
+/// or on any other registered one.
+fn relation_source(
+    relation: &str,
+    root_config: &RootConfig,
+    registry: &ResolverRegistry,
+) -> Option<RelationSource> {
+    let configs = std::iter::once(root_config).chain(registry.iter().map(|(_, e)| &e.root_config));
+    for config in configs {
+        let target = config
+            .vector_resolvers
+            .iter()
+            .filter(|r| !is_http_url(&r.url))
+            .find(|r| names_field(&r.field_name, relation))
+            .map(|r| (&r.url, &r.query_name, &r.foreign_key, Stash::new()))
+            .or_else(|| {
+                config
+                    .internal_vector_resolvers
+                    .iter()
+                    .find(|r| names_field(&r.field_name, relation))
+                    .map(|r| {
+                        (
+                            &r.graphlette_path,
+                            &r.query_name,
+                            &r.foreign_key,
+                            r.extra_args.clone(),
+                        )
+                    })
+            });
+        let Some((url, query_name, foreign_key, extra_args)) = target else {
+            continue;
+        };
+        let Some(entry) = registry.get_for_url(url) else {
+            continue;
+        };
+        if let Some(template) = entry.root_config.get_template(query_name) {
+            return Some(RelationSource {
+                searcher: Arc::clone(&entry.searcher),
+                template: template.to_string(),
+                foreign_key: foreign_key.clone(),
+                extra_args,
+                query_name: query_name.clone(),
+                target: url.clone(),
+            });
+        }
+    }
+    None
+}
+
+/// `*Connection` types with an `edges` field, and the edge and page info types
+/// they reference. Their fields are read from the page a connection field builds.
+fn connection_types(object_types: &HashMap<String, Vec<pt::FieldDefinition>>) -> HashSet<String> {
+    let mut types = HashSet::new();
+    for (type_name, fields) in object_types {
+        if !type_name.ends_with("Connection") || !fields.iter().any(|f| f.name.node == "edges") {
+            continue;
+        }
+        types.insert(type_name.clone());
+        for field_def in fields {
+            if matches!(field_def.name.node.as_str(), "edges" | "pageInfo") {
+                types.insert(base_type_name(&field_def.ty.node).to_string());
+            }
+        }
+    }
+    types
+}
+
+/// Relay connection field such as `layReportsConnection(first: Int, after: String)`
+/// over the vector relation `layReports`, paged by an opaque cursor. The
+/// searcher finds just the page asked for, in id order.
+fn connection_field(field_name: String, type_ref: TypeRef, source: RelationSource) -> Field {
+    let span = ResolverSpan::new(&field_name, &source.query_name, &source.target);
+    let source = Arc::new(source);
+    Field::new(field_name, type_ref, move |ctx| {
+        let source = Arc::clone(&source);
+        FieldFuture::new(span.wrap(async move {
+            let parent = ctx.parent_value.try_downcast_ref::<Stash>()?;
+            let fk = source.foreign_key.as_deref().unwrap_or("id");
+            let id_val = parent_id(parent, fk);
+
+            let first = match ctx.args.get("first").filter(|v| !v.is_null()) {
+                Some(v) => Some(
+                    usize::try_from(v.i64()?)
+                        .map_err(|_| async_graphql::Error::new("first must not be negative"))?,
+                ),
+                None => None,
+            };
+            let (at, offset) = match ctx.args.get("after").filter(|v| !v.is_null()) {
+                Some(v) => {
+                    let (at, position) = connection::decode_cursor(v.string()?)
+                        .ok_or_else(|| async_graphql::Error::new("Invalid cursor"))?;
+                    (at, position + 1)
+                }
+                None => (Utc::now().timestamp_millis(), 0),
+            };
+
+            let mut args = Stash::new();
+            args.insert("id".to_string(), serde_json::Value::String(id_val));
+            let mut args = with_extra_args(args, &source.extra_args);
+            let creds = credentials(&ctx);
+            let total = source
+                .searcher
+                .count(&source.template, &args, &creds, at)
+                .await
+                .map_err(graphql_error)?;
+            args.insert("sort".to_string(), "id".into());
+            args.insert("offset".to_string(), offset.into());
+            if let Some(first) = first {
+                args.insert("limit".to_string(), first.into());
+            }
+            let items = source
+                .searcher
+                .find_all(&source.template, &args, &creds, at)
+                .await
+                .map_err(graphql_error)?;
+            let page = connection::page(items, at, offset, total as usize);
+            Ok(Some(FieldValue::owned_any(page)))
+        }))
+    })
+}
+
+/// Object or list field already present in the parent Stash, e.g. a connection's `edges`.
+fn nested_field(field_name: String, type_ref: TypeRef) -> Field {
+    Field::new(field_name.clone(), type_ref, move |ctx| {
+        let fname = field_name.clone();
+        FieldFuture::new(async move {
+            let stash = ctx.parent_value.try_downcast_ref::<Stash>()?;
+            Ok(match stash.get(&fname) {
+                Some(serde_json::Value::Object(map)) => Some(FieldValue::owned_any(map.clone())),
+                Some(serde_json::Value::Array(items)) => Some(FieldValue::list(
+                    items
+                        .iter()
+                        .filter_map(|item| item.as_object().cloned())
+                        .map(FieldValue::owned_any),
+                )),
+                _ => None,
+            })
+        })
+    })
+}
+
 /// Null field: returns None (for relation fields with no registered resolver).
 fn null_field(field_name: String, type_ref: TypeRef) -> Field {
     Field::new(field_name, type_ref, |_ctx| {
@@ -574,13 +1038,7 @@ fn find_resolver_in_registry(
             .root_config
             .internal_vector_resolvers
             .iter()
-            .find(|r| {
-                r.field_name == field_name
-                    || r.field_name
-                        .rsplit_once('.')
-                        .map(|(_, suffix)| suffix == field_name)
-                        .unwrap_or(false)
-            })
+            .find(|r| names_field(&r.field_name, field_name))
         {
             if let Some(f) = internal_vector_resolver_field(
                 field_name.to_string(),
@@ -605,13 +1063,12 @@ fn find_resolver_in_registry(
             }
         }
         // Check vector resolvers (HTTP-based)
-        if let Some(r) = entry.root_config.vector_resolvers.iter().find(|r| {
-            r.field_name == field_name
-                || r.field_name
-                    .rsplit_once('.')
-                    .map(|(_, suffix)| suffix == field_name)
-                    .unwrap_or(false)
-        }) {
+        if let Some(r) = entry
+            .root_config
+            .vector_resolvers
+            .iter()
+            .find(|r| names_field(&r.field_name, field_name))
+        {
             if let Some(f) =
                 vector_resolver_field(field_name.to_string(), type_ref.clone(), r, registry)
             {
@@ -622,119 +1079,1005 @@ fn find_resolver_in_registry(
     None
 }
 
-/// Build a complete dynamic Schema from a GraphQL SDL + RootConfig + Searcher.
-pub fn build_schema(
-    schema_text: &str,
+/// `stash` as the value of a field typed `base`, tagged with its object type
+/// from [`TYPE_KEY`] when `base` is an interface or union.
+fn entity_value(
+    stash: Stash,
+    base: &str,
+    abstract_types: &HashSet<String>,
+) -> async_graphql::Result<FieldValue<'static>> {
+    if !abstract_types.contains(base) {
+        return Ok(FieldValue::owned_any(stash));
+    }
+    match stash.get(TYPE_KEY).and_then(|v| v.as_str()) {
+        Some(type_name) => {
+            let type_name = type_name.to_string();
+            Ok(FieldValue::owned_any(stash).with_type(type_name))
+        }
+        None => Err(graphql_error(MeshqlError::Validation(format!(
+            "a {base} result has no {TYPE_KEY} naming its type"
+        )))),
+    }
+}
+
+/// The payload keys each field of an entity reads: scalars and enums read their
+/// own name, relations configured on this graphlette read their foreign keys.
+/// Fields resolved any other way are left out.
+fn payload_keys(
+    type_name: &str,
+    fields: &[pt::FieldDefinition],
     root_config: &RootConfig,
-    searcher: Arc<dyn Searcher>,
-    registry: &ResolverRegistry,
-) -> async_graphql::Result<Schema> {
-    let service_doc = parse_schema(schema_text)
-        .map_err(|e| async_graphql::Error::new(format!("Schema parse error: {e}")))?;
+    enum_types: &HashMap<String, Vec<String>>,
+) -> HashMap<String, Vec<String>> {
+    let mut keys = HashMap::new();
+    for field_def in fields {
+        let field_name = field_def.name.node.to_string();
+        let base_name = base_type_name(&field_def.ty.node);
+        let computed = root_config
+            .computed_fields
+            .iter()
+            .find(|c| c.type_name == type_name && c.field == field_name);
+        let read = if let Some(c) = computed {
+            Some(vec![c.left.clone(), c.right.clone()])
+        } else if is_scalar(base_name) || enum_types.contains_key(base_name) {
+            Some(vec![field_name.clone()])
+        } else {
+            let foreign_key =
+                |fk: &Option<String>| vec![fk.clone().unwrap_or_else(|| "id".to_string())];
+            root_config
+                .singleton_resolvers
+                .iter()
+                .find(|r| r.field_name == field_name)
+                .map(|r| foreign_key(&r.foreign_key))
+                .or_else(|| {
+                    root_config
+                        .internal_singleton_resolvers
+                        .iter()
+                        .find(|r| r.field_name == field_name)
+                        .map(|r| r.foreign_keys.parent_fields().map(String::from).collect())
+                })
+                .or_else(|| {
+                    root_config
+                        .vector_resolvers
+                        .iter()
+                        .find(|r| names_field(&r.field_name, &field_name))
+                        .map(|r| foreign_key(&r.foreign_key))
+                })
+                .or_else(|| {
+                    root_config
+                        .internal_vector_resolvers
+                        .iter()
+                        .find(|r| names_field(&r.field_name, &field_name))
+                        .map(|r| foreign_key(&r.foreign_key))
+                })
+        };
+        if let Some(read) = read {
+            keys.insert(field_name, read);
+        }
+    }
+    keys
+}
 
-    // Collect object type definitions keyed by name
-    let mut object_types: HashMap<String, Vec<pt::FieldDefinition>> = HashMap::new();
-    for def in &service_doc.definitions {
-        if let pt::TypeSystemDefinition::Type(td) = def {
-            let type_def = &td.node;
-            if let pt::TypeKind::Object(obj) = &type_def.kind {
-                let name = type_def.name.node.to_string();
-                let fields: Vec<pt::FieldDefinition> =
-                    obj.fields.iter().map(|f| f.node.clone()).collect();
-                object_types.insert(name, fields);
+/// The payload keys a root query's selection reads, or `None` if it selects a
+/// field missing from `keys` and so needs the whole payload. `id` always comes back.
+fn projected_fields(
+    ctx: &async_graphql::dynamic::ResolverContext,
+    keys: &HashMap<String, Vec<String>>,
+) -> Option<Vec<String>> {
+    let mut fields: Vec<String> = Vec::new();
+    for selected in ctx.look_ahead().selection_fields() {
+        for field in selected.selection_set() {
+            if field.name() == "__typename" {
+                continue;
+            }
+            for key in keys.get(field.name())? {
+                if key != "id" && !fields.contains(key) {
+                    fields.push(key.clone());
+                }
             }
         }
     }
+    Some(fields)
+}
 
-    let mut schema_builder = Schema::build("Query", None, None);
-    schema_builder = schema_builder.register(Scalar::new("Date"));
-
-    // Build Query type
-    if let Some(query_fields) = object_types.get("Query") {
-        let mut query_obj = Object::new("Query");
-
-        for field_def in query_fields {
-            let field_name = field_def.name.node.to_string();
-            let field_type = convert_type(&field_def.ty.node);
+/// The latest `at` accepted, the last millisecond of year 9999. Larger values
+/// are most likely microseconds or nanoseconds.
+const MAX_AT_MILLIS: i64 = 253_402_300_799_999;
 
-            if let Some(qc) = root_config.queries.iter().find(|q| q.name == field_name) {
-                let template = qc.template.clone();
-                let is_singleton = qc.is_singleton;
-                let s = Arc::clone(&searcher);
+/// The `at` argument as epoch milliseconds, declared `Int` or `Float` in the
+/// SDL. Anything else is rejected rather than read as now.
+fn at_millis(field: &str, value: &async_graphql::Value) -> async_graphql::Result<i64> {
+    let invalid = |why: &str| {
+        graphql_error(MeshqlError::Validation(format!(
+            "`at` on {field} {why}, got {value}"
+        )))
+    };
+    let millis = match value {
+        async_graphql::Value::Number(n) => n.as_i64().or_else(|| {
+            n.as_f64()
+                .filter(|f| f.fract() == 0.0 && f.abs() <= MAX_AT_MILLIS as f64)
+                .map(|f| f as i64)
+        }),
+        _ => None,
+    }
+    .ok_or_else(|| invalid("must be a whole number of epoch milliseconds"))?;
+    if millis < 0 {
+        return Err(invalid("must not be negative"));
+    }
+    if millis > MAX_AT_MILLIS {
+        return Err(invalid("is past year 9999; pass epoch milliseconds"));
+    }
+    Ok(millis)
+}
 
-                let mut gql_field = Field::new(field_name.clone(), field_type, move |ctx| {
-                    let s = Arc::clone(&s);
-                    let tmpl = template.clone();
-                    FieldFuture::new(async move {
-                        let at = ctx
-                            .args
-                            .get("at")
-                            .and_then(|v| {
-                                if let async_graphql::Value::Number(n) = v.as_value() {
-                                    n.as_i64()
-                                } else {
-                                    None
-                                }
-                            })
-                            .unwrap_or_else(|| Utc::now().timestamp_millis());
-
-                        let mut args = Stash::new();
-                        for (k, v) in ctx.args.iter() {
-                            if k.as_str() != "at" {
-                                let json_val = gql_value_to_json(v.as_value());
-                                args.insert(k.to_string(), json_val);
-                            }
-                        }
+/// The names of `field_def`'s `ID` and `[ID]` arguments.
+fn id_arguments(field_def: &pt::FieldDefinition) -> Arc<HashSet<String>> {
+    Arc::new(
+        field_def
+            .arguments
+            .iter()
+            .filter(|arg| base_type_name(&arg.node.ty.node) == "ID")
+            .map(|arg| arg.node.name.node.to_string())
+            .collect(),
+    )
+}
 
-                        let creds = &["*".to_string()];
-                        if is_singleton {
-                            match s.find(&tmpl, &args, creds, at).await {
-                                Ok(Some(stash)) => Ok(Some(FieldValue::owned_any(stash))),
-                                Ok(None) => Ok(FieldValue::NONE),
-                                Err(e) => Err(async_graphql::Error::new(e.to_string())),
-                            }
-                        } else {
-                            match s.find_all(&tmpl, &args, creds, at).await {
-                                Ok(stashes) => {
-                                    let items: Vec<FieldValue> =
-                                        stashes.into_iter().map(FieldValue::owned_any).collect();
-                                    Ok(Some(FieldValue::list(items)))
-                                }
-                                Err(e) => Err(async_graphql::Error::new(e.to_string())),
-                            }
-                        }
-                    })
-                });
+/// An `ID` argument's value with any numbers read as their decimal strings,
+/// as GraphQL serializes IDs.
+fn id_value(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Array(items) => items.into_iter().map(id_value).collect(),
+        value => id_string(&value).map_or(value, serde_json::Value::String),
+    }
+}
 
-                // Add arguments from the field definition
-                for arg_def in &field_def.arguments {
-                    let arg_name = arg_def.node.name.node.to_string();
-                    let arg_type = convert_type(&arg_def.node.ty.node);
-                    gql_field = gql_field.argument(InputValue::new(arg_name, arg_type));
-                }
+/// Extract the `at` timestamp (defaulting to now) and the remaining query args,
+/// reading the `id_args` as [`id_value`]s.
+pub(crate) fn query_args(
+    ctx: &async_graphql::dynamic::ResolverContext,
+    id_args: &HashSet<String>,
+) -> async_graphql::Result<(Stash, i64)> {
+    let at = match ctx.args.get("at").filter(|v| !v.is_null()) {
+        Some(v) => at_millis(ctx.field().name(), v.as_value())?,
+        None => Utc::now().timestamp_millis(),
+    };
 
-                query_obj = query_obj.field(gql_field);
-            }
+    let mut args = Stash::new();
+    for (k, v) in ctx.args.iter() {
+        if k.as_str() != "at" {
+            let json_val = gql_value_to_json(v.as_value());
+            let json_val = match id_args.contains(k.as_str()) {
+                true => id_value(json_val),
+                false => json_val,
+            };
+            args.insert(k.to_string(), json_val);
         }
-
-        schema_builder = schema_builder.register(query_obj);
     }
+    Ok((args, at))
+}
 
-    // Build entity types
-    for (type_name, fields) in &object_types {
-        if type_name == "Query" {
-            continue;
-        }
+/// Fail when `args` lack a key `template` names, for the fields of
+/// [`RootConfig::strict_queries`].
+pub(crate) fn require_args(template: &str, args: &Stash) -> async_graphql::Result<()> {
+    render_template(template, args, MissingKey::Error)
+        .map(drop)
+        .map_err(graphql_error)
+}
 
-        let mut entity_obj = Object::new(type_name.as_str());
+#[derive(Clone, Copy)]
+enum Aggregate {
+    Count,
+    Exists,
+}
 
-        for field_def in fields {
-            let field_name = field_def.name.node.to_string();
-            let field_type = convert_type(&field_def.ty.node);
+/// Match `<query>Count: Int` / `<query>Exists: Boolean` to a configured query.
+fn aggregate_query<'a>(
+    field_name: &str,
+    ty: &pt::Type,
+    root_config: &'a RootConfig,
+) -> Option<(&'a QueryConfig, Aggregate)> {
+    let (query_name, aggregate) = match base_type_name(ty) {
+        "Int" => (field_name.strip_suffix("Count")?, Aggregate::Count),
+        "Boolean" => (field_name.strip_suffix("Exists")?, Aggregate::Exists),
+        _ => return None,
+    };
+    root_config
+        .queries
+        .iter()
+        .find(|q| q.name == query_name)
+        .map(|q| (q, aggregate))
+}
+
+/// Aggregate query field: count or existence check over a configured query template.
+fn aggregate_query_field(
+    field_name: String,
+    type_ref: TypeRef,
+    template: String,
+    aggregate: Aggregate,
+    strict: bool,
+    searcher: Arc<dyn Searcher>,
+    id_args: Arc<HashSet<String>>,
+) -> Field {
+    Field::new(field_name, type_ref, move |ctx| {
+        let s = Arc::clone(&searcher);
+        let tmpl = template.clone();
+        let id_args = Arc::clone(&id_args);
+        FieldFuture::new(async move {
+            let (args, at) = query_args(&ctx, &id_args)?;
+            if strict {
+                require_args(&tmpl, &args)?;
+            }
+            let creds = &credentials(&ctx);
+            let value = match aggregate {
+                Aggregate::Count => s
+                    .count(&tmpl, &args, creds, at)
+                    .await
+                    .map(|n| async_graphql::Value::Number(n.into())),
+                Aggregate::Exists => s
+                    .exists(&tmpl, &args, creds, at)
+                    .await
+                    .map(async_graphql::Value::Boolean),
+            }
+            .map_err(graphql_error)?;
+            Ok(Some(FieldValue::value(value)))
+        })
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MutationOp {
+    Create,
+    Update,
+    Delete,
+}
+
+/// Split `createFarm` / `updateFarm` / `deleteFarm` into the operation and entity type name.
+fn mutation_op(field_name: &str) -> Option<(MutationOp, &str)> {
+    let (op, type_name) = if let Some(t) = field_name.strip_prefix("create") {
+        (MutationOp::Create, t)
+    } else if let Some(t) = field_name.strip_prefix("update") {
+        (MutationOp::Update, t)
+    } else if let Some(t) = field_name.strip_prefix("delete") {
+        (MutationOp::Delete, t)
+    } else {
+        return None;
+    };
+    type_name
+        .starts_with(|c: char| c.is_ascii_uppercase())
+        .then_some((op, type_name))
+}
+
+/// Input object mirroring an entity's scalar and enum fields (all optional, `id` excluded).
+fn entity_input_object(
+    input_name: &str,
+    fields: &[pt::FieldDefinition],
+    enum_types: &HashMap<String, Vec<String>>,
+) -> InputObject {
+    let mut input = InputObject::new(input_name);
+    for field_def in fields {
+        let field_name = field_def.name.node.to_string();
+        let base_name = base_type_name(&field_def.ty.node);
+        if field_name == "id" || !(is_scalar(base_name) || enum_types.contains_key(base_name)) {
+            continue;
+        }
+        let type_ref = match &field_def.ty.node.base {
+            pt::BaseType::Named(_) => TypeRef::named(base_name),
+            pt::BaseType::List(_) => TypeRef::named_list(base_name),
+        };
+        input = input.field(InputValue::new(field_name, type_ref));
+    }
+    input
+}
+
+/// An SDL interface, resolved to whichever object type its value is tagged with.
+fn declared_interface(name: &str, iface: &pt::InterfaceType) -> Interface {
+    let mut interface = Interface::new(name);
+    for parent in &iface.implements {
+        interface = interface.implement(parent.node.as_str());
+    }
+    for field_def in &iface.fields {
+        let field_def = &field_def.node;
+        let mut field = InterfaceField::new(
+            field_def.name.node.to_string(),
+            convert_type(&field_def.ty.node),
+        );
+        for arg_def in &field_def.arguments {
+            let arg_name = arg_def.node.name.node.to_string();
+            field = field.argument(InputValue::new(
+                arg_name,
+                convert_type(&arg_def.node.ty.node),
+            ));
+        }
+        interface = interface.field(field);
+    }
+    interface
+}
+
+/// An `input` type declared in the schema, for query arguments. Its values
+/// reach templates as nested JSON, e.g. `{{filter.zone}}`.
+fn declared_input_object(input_name: &str, input: &pt::InputObjectType) -> InputObject {
+    let mut object = InputObject::new(input_name);
+    for field in &input.fields {
+        let field = &field.node;
+        object = object.field(InputValue::new(
+            field.name.node.to_string(),
+            convert_type(&field.ty.node),
+        ));
+    }
+    object
+}
+
+/// Read the generated `input` argument as a payload Stash.
+fn input_arg(ctx: &async_graphql::dynamic::ResolverContext) -> async_graphql::Result<Stash> {
+    match gql_value_to_json(ctx.args.try_get("input")?.as_value()) {
+        serde_json::Value::Object(obj) => Ok(obj),
+        _ => Err(async_graphql::Error::new("input must be an object")),
+    }
+}
+
+/// A mutation's `id` argument, with a number read as its decimal string, as
+/// the query path reads one.
+fn id_arg(ctx: &async_graphql::dynamic::ResolverContext) -> async_graphql::Result<String> {
+    id_string(&gql_value_to_json(ctx.args.try_get("id")?.as_value()))
+        .ok_or_else(|| async_graphql::Error::new("id must be a string or a number"))
+}
+
+/// An update's `expectedCreatedAt`: the `createdAt` of the version it was
+/// based on, which makes it fail with `CONFLICT` if a newer one was written.
+fn expected_created_at_arg(
+    ctx: &async_graphql::dynamic::ResolverContext,
+) -> async_graphql::Result<Option<chrono::DateTime<chrono::Utc>>> {
+    match ctx.args.get("expectedCreatedAt") {
+        None => Ok(None),
+        Some(v) if v.is_null() => Ok(None),
+        Some(v) => date::parse(&gql_value_to_json(v.as_value()))
+            .map(Some)
+            .ok_or_else(|| async_graphql::Error::new("expectedCreatedAt must be a date")),
+    }
+}
+
+fn envelope_to_stash(env: Envelope) -> Stash {
+    let mut stash = env.payload;
+    stash.insert("id".to_string(), serde_json::Value::String(env.id));
+    insert_metadata(&mut stash, env.created_at.timestamp_millis(), env.deleted);
+    stash
+}
+
+/// The type a `delete<Type>` mutation may return instead of `Boolean`, saying
+/// which id it was asked to remove and whether it was there to remove.
+const DELETE_RESULT: &str = "DeleteResult";
+
+/// `type DeleteResult { id: ID! deleted: Boolean! }`, for schemas whose delete
+/// mutations return it without declaring it.
+fn delete_result_object() -> Object {
+    Object::new(DELETE_RESULT)
+        .field(scalar_field(
+            "id".to_string(),
+            TypeRef::named_nn(TypeRef::ID),
+            None,
+        ))
+        .field(scalar_field(
+            "deleted".to_string(),
+            TypeRef::named_nn(TypeRef::BOOLEAN),
+            None,
+        ))
+}
+
+/// Mutation field: create, update or delete an entity through the repository.
+/// Inputs to `date_fields` are stored as RFC 3339, and files sent to
+/// `upload_fields` as their base64-encoded contents. Deletes return whether
+/// the entity was removed, or a [`DELETE_RESULT`] if the field's type is one.
+fn mutation_field(
+    field_name: String,
+    type_ref: TypeRef,
+    op: MutationOp,
+    input_name: &str,
+    date_fields: Vec<String>,
+    upload_fields: Vec<String>,
+    repository: Arc<dyn Repository>,
+) -> Field {
+    let returns_result = type_ref.type_name() == DELETE_RESULT;
+    let field = Field::new(field_name, type_ref, move |ctx| {
+        let repo = Arc::clone(&repository);
+        let date_fields = date_fields.clone();
+        let upload_fields = upload_fields.clone();
+        FieldFuture::new(async move {
+            let creds = credentials(&ctx);
+            match op {
+                MutationOp::Create => {
+                    let mut payload = input_arg(&ctx)?;
+                    payload.retain(|_, v| !v.is_null());
+                    date::normalize_fields(&mut payload, &date_fields)?;
+                    upload::read_fields(&ctx, &mut payload, &upload_fields)?;
+                    let env = Envelope::new("", payload, creds.clone());
+                    let created = repo.create(env, &creds).await.map_err(graphql_error)?;
+                    Ok(Some(FieldValue::owned_any(envelope_to_stash(created))))
+                }
+                MutationOp::Update => {
+                    let id = id_arg(&ctx)?;
+                    let mut patch = input_arg(&ctx)?;
+                    date::normalize_fields(&mut patch, &date_fields)?;
+                    upload::read_fields(&ctx, &mut patch, &upload_fields)?;
+                    let updated = match expected_created_at_arg(&ctx)? {
+                        Some(expected) => {
+                            repo.update_if_unchanged(&id, patch, expected, &creds).await
+                        }
+                        None => repo.update(&id, patch, &creds).await,
+                    }
+                    .map_err(graphql_error)?;
+                    Ok(updated.map(|env| FieldValue::owned_any(envelope_to_stash(env))))
+                }
+                MutationOp::Delete => {
+                    let id = id_arg(&ctx)?;
+                    let removed = repo.remove(&id, &creds).await.map_err(graphql_error)?;
+                    if returns_result {
+                        let mut result = Stash::new();
+                        result.insert("id".to_string(), serde_json::Value::String(id));
+                        result.insert("deleted".to_string(), serde_json::Value::Bool(removed));
+                        return Ok(Some(FieldValue::owned_any(result)));
+                    }
+                    Ok(Some(FieldValue::value(async_graphql::Value::Boolean(
+                        removed,
+                    ))))
+                }
+            }
+        })
+    });
+
+    match op {
+        MutationOp::Create => {
+            field.argument(InputValue::new("input", TypeRef::named_nn(input_name)))
+        }
+        MutationOp::Update => field
+            .argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID)))
+            .argument(InputValue::new("input", TypeRef::named_nn(input_name)))
+            .argument(InputValue::new(
+                "expectedCreatedAt",
+                TypeRef::named(TypeRef::STRING),
+            )),
+        MutationOp::Delete => field.argument(InputValue::new("id", TypeRef::named_nn(TypeRef::ID))),
+    }
+}
+
+/// How deeply a query may nest fields when its [`RootConfig`] doesn't say.
+/// Deep enough for GraphiQL's introspection query.
+pub const DEFAULT_MAX_DEPTH: usize = 24;
+
+/// Build a complete dynamic Schema from a GraphQL SDL + RootConfig + Searcher.
+/// `Schema::sdl()` renders the result, including which relation fields were left unresolved.
+///
+/// Fields of a `Subscription` type named after a configured query re-run it
+/// every [`RootConfig::subscription_interval`], pushing what changed.
+///
+/// Every operation is logged to the `meshql::graphql` `tracing` target with
+/// its duration, leaving out [`RootConfig::redacted_arguments`]. Relation
+/// fields no resolver fills in, which always resolve to null, are logged there
+/// as warnings at build time; [`build_schema_with_report`] returns them too.
+///
+/// Relations may lead back to their own type, so queries nested deeper than
+/// [`RootConfig::max_depth`] (or [`DEFAULT_MAX_DEPTH`]) are rejected before
+/// anything is resolved, as are queries costing more than
+/// [`RootConfig::max_complexity`], with list fields weighing the most.
+/// Operations still running after [`RootConfig::timeout`] are abandoned with
+/// a `TIMEOUT` error.
+///
+/// Fields in [`RootConfig::restricted_fields`] resolve per
+/// [`RootConfig::field_denial`] for callers lacking their token.
+///
+/// Searchers render a placeholder with no argument to fill it as empty; the
+/// fields of [`RootConfig::strict_queries`] fail instead.
+///
+/// Declaring `scalar Upload` lets mutation inputs take files sent in
+/// multipart requests, stored as their base64-encoded contents.
+///
+/// For locked-down deployments, [`RootConfig::disable_introspection`] hides
+/// the schema and [`RootConfig::persisted_queries`] restricts the graphlette
+/// to an allow-list of operations.
+///
+/// The root types are those a `schema { query: … }` definition names, or
+/// `Query`, `Mutation` and `Subscription` for the operations it leaves out.
+pub fn build_schema(
+    schema_text: &str,
+    root_config: &RootConfig,
+    searcher: Arc<dyn Searcher>,
+    registry: &ResolverRegistry,
+) -> async_graphql::Result<Schema> {
+    build_schema_with_repository(schema_text, root_config, searcher, None, registry)
+        .map(|(schema, _)| schema)
+}
+
+/// [`build_schema`], also returning a [`BuildReport`] of what it couldn't wire up.
+pub fn build_schema_with_report(
+    schema_text: &str,
+    root_config: &RootConfig,
+    searcher: Arc<dyn Searcher>,
+    registry: &ResolverRegistry,
+) -> async_graphql::Result<(Schema, BuildReport)> {
+    build_schema_with_repository(schema_text, root_config, searcher, None, registry)
+}
+
+/// Misconfiguration found while building a schema, which still builds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BuildReport {
+    /// Object and list fields, as `Type.field`, with no resolver to fill them
+    /// in, so they always resolve to null. Scalar and enum fields are read
+    /// from the payload and never appear here.
+    pub dangling_relations: Vec<String>,
+}
+
+/// Build the schema for the graphlette mounted at `path`. If a repository is
+/// registered for that path and the SDL declares a `Mutation` type, its
+/// `create<Type>(input)`, `update<Type>(id, input, expectedCreatedAt)` and
+/// `delete<Type>(id)` fields are wired to the repository, with `<Type>Input`
+/// generated from the entity's scalar fields. An update given the
+/// `createdAt` it was based on fails with `CONFLICT` if that is no longer the
+/// latest version. A `delete<Type>` field returns `Boolean`, or
+/// `DeleteResult { id: ID! deleted: Boolean! }`, generated unless the SDL
+/// declares it; either way, `false` means there was no such entity to remove.
+pub fn build_schema_at(
+    path: &str,
+    schema_text: &str,
+    root_config: &RootConfig,
+    searcher: Arc<dyn Searcher>,
+    registry: &ResolverRegistry,
+) -> async_graphql::Result<Schema> {
+    build_schema_at_with_report(path, schema_text, root_config, searcher, registry)
+        .map(|(schema, _)| schema)
+}
+
+/// [`build_schema_at`], also returning a [`BuildReport`] of what it couldn't wire up.
+pub fn build_schema_at_with_report(
+    path: &str,
+    schema_text: &str,
+    root_config: &RootConfig,
+    searcher: Arc<dyn Searcher>,
+    registry: &ResolverRegistry,
+) -> async_graphql::Result<(Schema, BuildReport)> {
+    let repository = registry
+        .get_for_url(path)
+        .and_then(|entry| entry.repository.clone());
+    build_schema_with_repository(schema_text, root_config, searcher, repository, registry)
+}
+
+/// The names of the schema's operation root types.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RootTypes {
+    pub(crate) query: String,
+    pub(crate) mutation: String,
+    pub(crate) subscription: String,
+}
+
+impl Default for RootTypes {
+    fn default() -> Self {
+        Self {
+            query: "Query".to_string(),
+            mutation: "Mutation".to_string(),
+            subscription: "Subscription".to_string(),
+        }
+    }
+}
+
+impl RootTypes {
+    /// The roots `doc`'s `schema` definitions and extensions name, falling
+    /// back to the conventional names.
+    pub(crate) fn declared(doc: &pt::ServiceDocument) -> Self {
+        let mut roots = Self::default();
+        for def in &doc.definitions {
+            if let pt::TypeSystemDefinition::Schema(sd) = def {
+                let sd = &sd.node;
+                let declared = [
+                    (&mut roots.query, &sd.query),
+                    (&mut roots.mutation, &sd.mutation),
+                    (&mut roots.subscription, &sd.subscription),
+                ];
+                for (root, name) in declared {
+                    if let Some(name) = name {
+                        *root = name.node.to_string();
+                    }
+                }
+            }
+        }
+        roots
+    }
+
+    pub(crate) fn contains(&self, type_name: &str) -> bool {
+        [&self.query, &self.mutation, &self.subscription]
+            .iter()
+            .any(|root| root.as_str() == type_name)
+    }
+
+    /// The root type operations of type `ty` start from.
+    pub(crate) fn of(&self, ty: pt::OperationType) -> &str {
+        match ty {
+            pt::OperationType::Query => &self.query,
+            pt::OperationType::Mutation => &self.mutation,
+            pt::OperationType::Subscription => &self.subscription,
+        }
+    }
+}
+
+fn build_schema_with_repository(
+    schema_text: &str,
+    root_config: &RootConfig,
+    searcher: Arc<dyn Searcher>,
+    repository: Option<Arc<dyn Repository>>,
+    registry: &ResolverRegistry,
+) -> async_graphql::Result<(Schema, BuildReport)> {
+    let service_doc = parse_schema(schema_text)
+        .map_err(|e| async_graphql::Error::new(format!("Schema parse error: {e}")))?;
+    let roots = RootTypes::declared(&service_doc);
+
+    // Collect object, enum, interface and union type definitions keyed by name
+    let mut object_types: HashMap<String, Vec<pt::FieldDefinition>> = HashMap::new();
+    let mut implements: HashMap<String, Vec<String>> = HashMap::new();
+    let mut enum_types: HashMap<String, Vec<String>> = HashMap::new();
+    let mut interfaces = Vec::new();
+    let mut unions = Vec::new();
+    let mut abstract_types = HashSet::new();
+    let mut input_objects = Vec::new();
+    let mut inputs = HashSet::new();
+    let mut upload_fields: HashMap<String, Vec<String>> = HashMap::new();
+    let mut uploads_enabled = false;
+    for def in &service_doc.definitions {
+        if let pt::TypeSystemDefinition::Type(td) = def {
+            let type_def = &td.node;
+            let name = type_def.name.node.to_string();
+            match &type_def.kind {
+                pt::TypeKind::Object(obj) => {
+                    let fields: Vec<pt::FieldDefinition> =
+                        obj.fields.iter().map(|f| f.node.clone()).collect();
+                    let names = obj.implements.iter().map(|i| i.node.to_string());
+                    implements.insert(name.clone(), names.collect());
+                    object_types.insert(name, fields);
+                }
+                pt::TypeKind::Interface(iface) => {
+                    interfaces.push(declared_interface(&name, iface));
+                    abstract_types.insert(name);
+                }
+                pt::TypeKind::Union(union) => {
+                    let members = union.members.iter();
+                    unions.push(members.fold(Union::new(name.as_str()), |u, member| {
+                        u.possible_type(member.node.as_str())
+                    }));
+                    abstract_types.insert(name);
+                }
+                pt::TypeKind::Enum(e) => {
+                    let values = e
+                        .values
+                        .iter()
+                        .map(|v| v.node.value.node.to_string())
+                        .collect();
+                    enum_types.insert(name, values);
+                }
+                pt::TypeKind::InputObject(input) => {
+                    input_objects.push(declared_input_object(&name, input));
+                    let uploads = input
+                        .fields
+                        .iter()
+                        .filter(|f| base_type_name(&f.node.ty.node) == TypeRef::UPLOAD)
+                        .map(|f| f.node.name.node.to_string());
+                    upload_fields.insert(name.clone(), uploads.collect());
+                    inputs.insert(name);
+                }
+                pt::TypeKind::Scalar => uploads_enabled |= name == TypeRef::UPLOAD,
+            }
+        }
+    }
+
+    // Build Mutation type and the input objects its fields take, unless the
+    // schema declares them itself
+    let mut mutation_obj = None;
+    if let (Some(mutation_fields), Some(repo)) = (object_types.get(&roots.mutation), &repository) {
+        let mut obj = Object::new(roots.mutation.as_str());
+        let mut has_fields = false;
+
+        for field_def in mutation_fields {
+            let field_name = field_def.name.node.to_string();
+            let Some((op, type_name)) = mutation_op(&field_name) else {
+                continue;
+            };
+            let Some(entity_fields) = object_types.get(type_name) else {
+                continue;
+            };
+
+            let input_name = format!("{type_name}Input");
+            if op != MutationOp::Delete && inputs.insert(input_name.clone()) {
+                input_objects.push(entity_input_object(&input_name, entity_fields, &enum_types));
+            }
+
+            let field_type = convert_type(&field_def.ty.node);
+            let date_fields = entity_fields
+                .iter()
+                .filter(|f| base_type_name(&f.ty.node) == "Date")
+                .map(|f| f.name.node.to_string())
+                .collect();
+            obj = obj.field(mutation_field(
+                field_name.clone(),
+                field_type,
+                op,
+                &input_name,
+                date_fields,
+                upload_fields.get(&input_name).cloned().unwrap_or_default(),
+                Arc::clone(repo),
+            ));
+            has_fields = true;
+        }
+
+        if has_fields {
+            mutation_obj = Some(obj);
+        }
+    }
+    let delete_result = mutation_obj.is_some()
+        && !object_types.contains_key(DELETE_RESULT)
+        && object_types[&roots.mutation]
+            .iter()
+            .any(|f| base_type_name(&f.ty.node) == DELETE_RESULT);
+
+    // Build Subscription type from fields named after configured queries
+    let mut subscription_obj = None;
+    if let Some(subscription_fields) = object_types.get(&roots.subscription) {
+        let interval = root_config
+            .subscription_interval
+            .unwrap_or(subscription::DEFAULT_INTERVAL);
+        let mut obj = Subscription::new(roots.subscription.as_str());
+        let mut has_fields = false;
+
+        for field_def in subscription_fields {
+            let field_name = field_def.name.node.to_string();
+            let Some(qc) = root_config.queries.iter().find(|q| q.name == field_name) else {
+                continue;
+            };
+            let mut field = subscription::subscription_field(
+                field_name,
+                convert_type(&field_def.ty.node),
+                qc,
+                root_config.strict_queries.contains(&qc.name),
+                Arc::clone(&searcher),
+                interval,
+                id_arguments(field_def),
+            );
+            for arg_def in &field_def.arguments {
+                let arg_name = arg_def.node.name.node.to_string();
+                let arg_type = convert_type(&arg_def.node.ty.node);
+                field = field.argument(InputValue::new(arg_name, arg_type));
+            }
+            obj = obj.field(field);
+            has_fields = true;
+        }
+
+        if has_fields {
+            subscription_obj = Some(obj);
+        }
+    }
+
+    let mut schema_builder = Schema::build(
+        roots.query.as_str(),
+        mutation_obj.as_ref().map(|_| roots.mutation.as_str()),
+        subscription_obj
+            .as_ref()
+            .map(|_| roots.subscription.as_str()),
+    );
+    schema_builder = schema_builder
+        .limit_depth(root_config.max_depth.unwrap_or(DEFAULT_MAX_DEPTH))
+        .extension(errors::ErrorPath)
+        .extension(logging::OperationLog::new(&root_config.redacted_arguments))
+        .register(date::date_scalar());
+    if let Some(limit) = root_config.max_complexity {
+        schema_builder =
+            schema_builder.extension(complexity::Complexity::new(limit, &object_types, &roots));
+    }
+    if let Some(limit) = root_config.timeout {
+        schema_builder = schema_builder.extension(timeout::Deadline::new(limit));
+    }
+    if !root_config.restricted_fields.is_empty() {
+        schema_builder = schema_builder.extension(field_auth::FieldAuth::new(
+            &root_config.restricted_fields,
+            root_config.field_denial,
+        ));
+    }
+    if root_config.disable_introspection {
+        schema_builder = schema_builder.disable_introspection();
+    }
+    if uploads_enabled {
+        schema_builder = schema_builder.enable_uploading();
+    }
+    if !root_config.persisted_queries.is_empty() {
+        schema_builder = schema_builder.extension(persisted::PersistedOnly::new(
+            &root_config.persisted_queries,
+        ));
+    }
+    for (name, values) in &enum_types {
+        schema_builder = schema_builder.register(Enum::new(name).items(values));
+    }
+    if let Some(obj) = mutation_obj {
+        schema_builder = schema_builder.register(obj);
+    }
+    if delete_result {
+        schema_builder = schema_builder.register(delete_result_object());
+    }
+    if let Some(obj) = subscription_obj {
+        schema_builder = schema_builder.register(obj);
+    }
+    for input in input_objects {
+        schema_builder = schema_builder.register(input);
+    }
+    for interface in interfaces {
+        schema_builder = schema_builder.register(interface);
+    }
+    for union in unions {
+        schema_builder = schema_builder.register(union);
+    }
+
+    // Build Query type
+    if let Some(query_fields) = object_types.get(&roots.query) {
+        let mut query_obj = Object::new(roots.query.as_str());
+
+        for field_def in query_fields {
+            let field_name = field_def.name.node.to_string();
+            let field_type = convert_type(&field_def.ty.node);
+
+            if let Some(qc) = root_config.queries.iter().find(|q| q.name == field_name) {
+                let template = qc.template.clone();
+                let is_singleton = qc.is_singleton;
+                let strict = root_config.strict_queries.contains(&qc.name);
+                let s = Arc::clone(&searcher);
+                let base = base_type_name(&field_def.ty.node).to_string();
+                // Projecting would drop the TYPE_KEY an interface or union resolves by
+                let keys = match abstract_types.contains(&base) {
+                    true => None,
+                    false => Some(Arc::new(
+                        object_types
+                            .get(&base)
+                            .map(|fields| payload_keys(&base, fields, root_config, &enum_types))
+                            .unwrap_or_default(),
+                    )),
+                };
+                let abstract_types = Arc::new(abstract_types.clone());
+                let id_args = id_arguments(field_def);
+
+                let mut gql_field = Field::new(field_name.clone(), field_type, move |ctx| {
+                    let s = Arc::clone(&s);
+                    let tmpl = template.clone();
+                    let keys = keys.clone();
+                    let base = base.clone();
+                    let abstract_types = Arc::clone(&abstract_types);
+                    let id_args = Arc::clone(&id_args);
+                    FieldFuture::new(async move {
+                        let (args, at) = query_args(&ctx, &id_args)?;
+                        if strict {
+                            require_args(&tmpl, &args)?;
+                        }
+
+                        let creds = &credentials(&ctx);
+                        let fields = keys.and_then(|keys| projected_fields(&ctx, &keys));
+                        if is_singleton {
+                            let found = match &fields {
+                                Some(fields) => s.find_projected(&tmpl, &args, fields, creds, at),
+                                None => s.find(&tmpl, &args, creds, at),
+                            };
+                            match found.await {
+                                Ok(Some(stash)) => {
+                                    entity_value(stash, &base, &abstract_types).map(Some)
+                                }
+                                Ok(None) => Ok(FieldValue::NONE),
+                                Err(e) => Err(graphql_error(e)),
+                            }
+                        } else {
+                            let found = match &fields {
+                                Some(fields) => {
+                                    s.find_all_projected(&tmpl, &args, fields, creds, at)
+                                }
+                                None => s.find_all(&tmpl, &args, creds, at),
+                            };
+                            match found.await {
+                                Ok(stashes) => {
+                                    let items = stashes
+                                        .into_iter()
+                                        .map(|stash| entity_value(stash, &base, &abstract_types))
+                                        .collect::<async_graphql::Result<Vec<_>>>()?;
+                                    Ok(Some(FieldValue::list(items)))
+                                }
+                                Err(e) => Err(graphql_error(e)),
+                            }
+                        }
+                    })
+                });
+
+                // Add arguments from the field definition
+                for arg_def in &field_def.arguments {
+                    let arg_name = arg_def.node.name.node.to_string();
+                    let arg_type = convert_type(&arg_def.node.ty.node);
+                    gql_field = gql_field.argument(InputValue::new(arg_name, arg_type));
+                }
+
+                query_obj = query_obj.field(gql_field);
+            } else if let Some((qc, aggregate)) =
+                aggregate_query(&field_name, &field_def.ty.node, root_config)
+            {
+                let mut gql_field = aggregate_query_field(
+                    field_name.clone(),
+                    field_type,
+                    qc.template.clone(),
+                    aggregate,
+                    root_config.strict_queries.contains(&qc.name),
+                    Arc::clone(&searcher),
+                    id_arguments(field_def),
+                );
+
+                for arg_def in &field_def.arguments {
+                    let arg_name = arg_def.node.name.node.to_string();
+                    let arg_type = convert_type(&arg_def.node.ty.node);
+                    gql_field = gql_field.argument(InputValue::new(arg_name, arg_type));
+                }
+
+                query_obj = query_obj.field(gql_field);
+            }
+        }
+
+        schema_builder = schema_builder.register(query_obj);
+    }
+
+    // Build entity types
+    let connections = connection_types(&object_types);
+    let mut report = BuildReport::default();
+    for (type_name, fields) in &object_types {
+        if roots.contains(type_name) {
+            continue;
+        }
+
+        let mut entity_obj = Object::new(type_name.as_str());
+        for interface in implements.get(type_name).into_iter().flatten() {
+            entity_obj = entity_obj.implement(interface);
+        }
+
+        for field_def in fields {
+            let field_name = field_def.name.node.to_string();
+            let field_type = convert_type(&field_def.ty.node);
             let base_name = base_type_name(&field_def.ty.node).to_string();
 
-            if is_scalar(&base_name) {
-                entity_obj = entity_obj.field(scalar_field(field_name, field_type));
+            let computed = root_config
+                .computed_fields
+                .iter()
+                .find(|c| &c.type_name == type_name && c.field == field_name);
+            if let Some(c) = computed {
+                entity_obj = entity_obj.field(computed_field(field_name, field_type, c.clone()));
+            } else if base_name == "Date" {
+                entity_obj = entity_obj.field(date_field(field_name, field_type));
+            } else if is_scalar(&base_name) {
+                let default = root_config
+                    .field_defaults
+                    .iter()
+                    .find(|d| &d.type_name == type_name && d.field == field_name)
+                    .map(|d| d.value.clone());
+                entity_obj = entity_obj.field(scalar_field(field_name, field_type, default));
+            } else if let Some(values) = enum_types.get(&base_name) {
+                entity_obj = entity_obj.field(enum_field(
+                    field_name,
+                    field_type,
+                    base_name,
+                    values.clone(),
+                ));
+            } else if connections.contains(type_name) {
+                entity_obj = entity_obj.field(nested_field(field_name, field_type));
+            } else if connections.contains(&base_name) {
+                let source = field_name
+                    .strip_suffix("Connection")
+                    .and_then(|relation| relation_source(relation, root_config, registry));
+                let mut gql_field = match source {
+                    Some(source) => connection_field(field_name, field_type, source),
+                    None => {
+                        report
+                            .dangling_relations
+                            .push(format!("{type_name}.{field_name}"));
+                        null_field(field_name, field_type)
+                    }
+                };
+                for arg_def in &field_def.arguments {
+                    let arg_name = arg_def.node.name.node.to_string();
+                    let arg_type = convert_type(&arg_def.node.ty.node);
+                    gql_field = gql_field.argument(InputValue::new(arg_name, arg_type));
+                }
+                entity_obj = entity_obj.field(gql_field);
             } else {
                 // Check singleton resolvers (exact field name match)
                 let singleton = root_config
@@ -749,26 +2092,19 @@ pub fn build_schema(
                     .find(|r| r.field_name == field_name);
 
                 // Check vector resolvers (exact match or nested path, e.g. "hens.layReports")
-                let vector = root_config.vector_resolvers.iter().find(|r| {
-                    r.field_name == field_name
-                        || r.field_name
-                            .rsplit_once('.')
-                            .map(|(_, suffix)| suffix == field_name)
-                            .unwrap_or(false)
-                });
+                let vector = root_config
+                    .vector_resolvers
+                    .iter()
+                    .find(|r| names_field(&r.field_name, &field_name));
 
                 // Check internal vector resolvers
-                let internal_vector = root_config.internal_vector_resolvers.iter().find(|r| {
-                    r.field_name == field_name
-                        || r.field_name
-                            .rsplit_once('.')
-                            .map(|(_, suffix)| suffix == field_name)
-                            .unwrap_or(false)
-                });
+                let internal_vector = root_config
+                    .internal_vector_resolvers
+                    .iter()
+                    .find(|r| names_field(&r.field_name, &field_name));
 
-                let field = if let Some(r) = singleton {
+                let resolved = if let Some(r) = singleton {
                     singleton_resolver_field(field_name.clone(), field_type.clone(), r, registry)
-                        .unwrap_or_else(|| null_field(field_name, field_type))
                 } else if let Some(r) = internal_singleton {
                     internal_singleton_resolver_field(
                         field_name.clone(),
@@ -776,10 +2112,8 @@ pub fn build_schema(
                         r,
                         registry,
                     )
-                    .unwrap_or_else(|| null_field(field_name, field_type))
                 } else if let Some(r) = vector {
                     vector_resolver_field(field_name.clone(), field_type.clone(), r, registry)
-                        .unwrap_or_else(|| null_field(field_name, field_type))
                 } else if let Some(r) = internal_vector {
                     internal_vector_resolver_field(
                         field_name.clone(),
@@ -787,13 +2121,17 @@ pub fn build_schema(
                         r,
                         registry,
                     )
-                    .unwrap_or_else(|| null_field(field_name, field_type))
                 } else {
                     // Fall through to registry: check other graphlettes' root_configs
                     // for resolvers that match this field (enables deep federation).
                     find_resolver_in_registry(&field_name, field_type.clone(), registry)
-                        .unwrap_or_else(|| null_field(field_name, field_type))
                 };
+                let field = resolved.unwrap_or_else(|| {
+                    report
+                        .dangling_relations
+                        .push(format!("{type_name}.{field_name}"));
+                    null_field(field_name, field_type)
+                });
 
                 entity_obj = entity_obj.field(field);
             }
@@ -802,46 +2140,2276 @@ pub fn build_schema(
         schema_builder = schema_builder.register(entity_obj);
     }
 
-    schema_builder
-        .finish()
-        .map_err(|e| async_graphql::Error::new(e.to_string()))
+    report.dangling_relations.sort();
+    for field in &report.dangling_relations {
+        tracing::warn!(
+            target: "meshql::graphql",
+            field = %field,
+            "relation field has no resolver and will always be null"
+        );
+    }
+
+    let schema = schema_builder
+        .finish()
+        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+    Ok((schema, report))
 }
 
-/// Axum Router serving a GraphQL schema at the given path.
+/// The `{data, errors}` body a GraphQL response is sent as.
+pub(crate) fn response_body(response: async_graphql::Response) -> serde_json::Value {
+    serde_json::json!({
+        "data": response.data,
+        "errors": if response.errors.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::to_value(&response.errors).unwrap_or(serde_json::Value::Null)
+        },
+    })
+}
+
+/// Run `request` with `creds`, through the per-request batch loader.
+pub(crate) async fn execute(
+    schema: &Schema,
+    request: async_graphql::Request,
+    creds: Vec<String>,
+    path: &str,
+) -> async_graphql::Response {
+    let request = request
+        .data(BatchLoader::with_credentials(creds.clone()))
+        .data(Credentials(creds));
+    spans::execute(schema, request, path).await
+}
+
+/// Axum Router serving a GraphQL schema at the given path, its SDL as
+/// `text/plain` at `<path>/sdl`, a JSON Schema of its entities for client
+/// codegen at `<path>/schema.json` (see [`json_schema`]), and subscriptions as Server-Sent Events at
+/// `GET <path>/stream?query=...&variables=...`. Queries may also be sent as
+/// `GET <path>?query=...&variables=...&operationName=...`. `POST`ed bodies may
+/// be JSON, a raw `application/graphql` query, or a GraphQL multipart request
+/// uploading files.
 pub struct GraphletteRouter;
 
 impl GraphletteRouter {
     pub fn build(path: &str, schema: Schema) -> Router {
+        Self::build_with_auth(path, schema, Arc::new(NoAuth))
+    }
+
+    /// Like [`GraphletteRouter::build`], but authorizes every request with
+    /// `auth` and runs its resolvers with the resulting credentials.
+    /// Unauthenticated requests get a 401.
+    pub fn build_with_auth(path: &str, schema: Schema, auth: Arc<dyn Auth>) -> Router {
+        Self::build_with_cache_control(path, schema, auth, None)
+    }
+
+    /// Like [`GraphletteRouter::build_with_auth`], sending `cache_control` as
+    /// the `Cache-Control` of successful responses to queries made with `GET`.
+    pub fn build_with_cache_control(
+        path: &str,
+        schema: Schema,
+        auth: Arc<dyn Auth>,
+        cache_control: Option<HeaderValue>,
+    ) -> Router {
+        let sdl = schema.sdl();
+        let json_schema = json_schema(&sdl)
+            .unwrap_or_else(|e| serde_json::json!({"errors": [{"message": e.message}]}));
+        let sdl_path = format!("{}/sdl", path.trim_end_matches('/'));
+        let json_schema_path = format!("{}/schema.json", path.trim_end_matches('/'));
+        let stream_path = format!("{}/stream", path.trim_end_matches('/'));
         let schema = Arc::new(schema);
-        Router::new().route(
-            path,
-            post(move |body: axum::body::Bytes| {
-                let schema = Arc::clone(&schema);
-                async move {
-                    let request: async_graphql::Request = match serde_json::from_slice(&body) {
-                        Ok(r) => r,
-                        Err(e) => {
-                            return (
-                                StatusCode::BAD_REQUEST,
-                                axum::Json(serde_json::json!({
-                                    "errors": [{"message": e.to_string()}]
-                                })),
-                            )
-                                .into_response();
-                        }
-                    };
-                    let response = schema.execute(request).await;
-                    let body = serde_json::json!({
-                        "data": response.data,
-                        "errors": if response.errors.is_empty() {
-                            serde_json::Value::Null
-                        } else {
-                            serde_json::to_value(&response.errors).unwrap_or(serde_json::Value::Null)
-                        },
-                    });
-                    axum::Json(body).into_response()
+        let graphlette = path.to_string();
+        let sdl_route =
+            get(move || async move { ([(CONTENT_TYPE, "text/plain; charset=utf-8")], sdl) });
+        let stream_route = {
+            let schema = Arc::clone(&schema);
+            let auth = Arc::clone(&auth);
+            get(move |headers: HeaderMap, Query(params): Query<GetParams>| {
+                subscription::stream(Arc::clone(&schema), Arc::clone(&auth), headers, params)
+            })
+        };
+        let get_route = {
+            let schema = Arc::clone(&schema);
+            let auth = Arc::clone(&auth);
+            let path = graphlette.clone();
+            get(move |headers: HeaderMap, Query(params): Query<GetParams>| {
+                get_request::query(
+                    Arc::clone(&schema),
+                    Arc::clone(&auth),
+                    path.clone(),
+                    headers,
+                    params,
+                    cache_control.clone(),
+                )
+            })
+        };
+        Router::new()
+            .route(&sdl_path, sdl_route)
+            .route(
+                &json_schema_path,
+                get(move || async move { axum::Json(json_schema) }),
+            )
+            .route(&stream_path, stream_route)
+            .route(
+                path,
+                get_route.post(move |headers: HeaderMap, body: axum::body::Bytes| {
+                    let schema = Arc::clone(&schema);
+                    let auth = Arc::clone(&auth);
+                    let path = graphlette.clone();
+                    async move {
+                        let creds = match auth.authorize(&headers).await {
+                            Ok(creds) => creds,
+                            Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+                        };
+                        let received = post_request::receive(&headers, body).await;
+                        let request = match received.and_then(operation_chosen) {
+                            Ok(r) => with_request_id(r, &headers),
+                            Err(message) => {
+                                return (
+                                    StatusCode::BAD_REQUEST,
+                                    axum::Json(serde_json::json!({
+                                        "errors": [{"message": message}]
+                                    })),
+                                )
+                                    .into_response();
+                            }
+                        };
+                        let response = execute(&schema, request, creds, &path).await;
+                        axum::Json(response_body(response)).into_response()
+                    }
+                }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FARM_GRAPHQL: &str = r#"
+        type Farm {
+            id: ID
+            name: String
+            founded: Date
+            coops: [Coop]
+        }
+        type Coop {
+            id: ID
+            name: String
+        }
+        type Query {
+            getFarm(id: ID, at: Int): Farm
+        }
+    "#;
+
+    struct EmptySearcher;
+
+    #[async_trait::async_trait]
+    impl Searcher for EmptySearcher {
+        async fn find(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Option<Stash>> {
+            Ok(None)
+        }
+
+        async fn find_all(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Vec<Stash>> {
+            Ok(Vec::new())
+        }
+
+        async fn count(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<u64> {
+            Ok(0)
+        }
+
+        async fn exists(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<bool> {
+            Ok(false)
+        }
+
+        async fn ping(&self) -> meshql_core::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_the_schema_sdl() {
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(EmptySearcher),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/farm/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = reqwest::get(format!("http://{addr}/farm/graph/sdl"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain"));
+        let sdl = response.text().await.unwrap();
+        assert!(sdl.contains("type Farm"), "{sdl}");
+        assert!(sdl.contains("scalar Date"), "{sdl}");
+    }
+
+    #[tokio::test]
+    async fn serves_a_json_schema_of_the_entities() {
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(EmptySearcher),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/farm/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = reqwest::get(format!("http://{addr}/farm/graph/schema.json"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(
+            body["$schema"],
+            "https://json-schema.org/draft/2020-12/schema"
+        );
+        let farm = &body["$defs"]["Farm"]["properties"];
+        assert_eq!(farm["name"], serde_json::json!({"type": "string"}));
+        assert_eq!(
+            farm["coops"],
+            serde_json::json!({"type": "array", "items": {"$ref": "#/$defs/Coop"}})
+        );
+        assert_eq!(
+            farm["founded"],
+            serde_json::json!({"type": "string", "format": "date-time"})
+        );
+        assert!(body["$defs"]["Coop"].is_object(), "{body}");
+        assert!(body["$defs"].get("Query").is_none(), "{body}");
+    }
+
+    #[tokio::test]
+    async fn several_operations_need_a_valid_operation_name() {
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(EmptySearcher),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/farm/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let url = format!("http://{addr}/farm/graph");
+        let query =
+            r#"query Named { getFarm(id: "1") { name } } query Ided { getFarm(id: "1") { id } }"#;
+        let post = |body: serde_json::Value| {
+            let url = url.clone();
+            async move {
+                let response = reqwest::Client::new()
+                    .post(url)
+                    .json(&body)
+                    .send()
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body: serde_json::Value = response.json().await.unwrap();
+                (status, body)
+            }
+        };
+
+        let (status, body) = post(serde_json::json!({"query": query})).await;
+        assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+        let message = body["errors"][0]["message"].as_str().unwrap();
+        assert!(message.contains("(Ided, Named)"), "{message}");
+        assert!(message.contains("operationName"), "{message}");
+
+        let (status, body) =
+            post(serde_json::json!({"query": query, "operationName": "Hens"})).await;
+        assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+        let message = body["errors"][0]["message"].as_str().unwrap();
+        assert!(
+            message.contains(r#"No operation is named "Hens""#),
+            "{message}"
+        );
+        assert!(message.contains("Ided, Named"), "{message}");
+
+        let (status, body) =
+            post(serde_json::json!({"query": query, "operationName": "Ided"})).await;
+        assert_eq!(status, reqwest::StatusCode::OK);
+        assert_eq!(body["data"], serde_json::json!({"getFarm": null}));
+        assert!(body["errors"].is_null(), "{body}");
+
+        let response = reqwest::Client::new()
+            .get(&url)
+            .query(&[("query", query)])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn registered_repositories_are_found_by_url() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let coops = Arc::new(MemoryRepository::new());
+        let mut registry = ResolverRegistry::new();
+        registry.register_with_repository(
+            "/coop/graph",
+            Arc::new(MemorySearcher::new(coops.store())),
+            RootConfig::default(),
+            coops.clone(),
+        );
+        registry.register(
+            "/farm/graph",
+            Arc::new(EmptySearcher),
+            RootConfig::default(),
+        );
+
+        let entry = registry
+            .get_for_url("http://localhost:3033/coop/graph")
+            .unwrap();
+        let repository = entry.repository.as_ref().unwrap();
+        let mut coop = Stash::new();
+        coop.insert("name".to_string(), serde_json::json!("red"));
+        repository
+            .create(Envelope::new("coop-1", coop, star.clone()), &star)
+            .await
+            .unwrap();
+        assert!(coops.read("coop-1", &star, None).await.unwrap().is_some());
+
+        let farm = registry.get_for_url("/farm/graph").unwrap();
+        assert!(farm.repository.is_none());
+    }
+
+    #[test]
+    fn reports_relations_left_without_a_resolver() {
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let (_, report) = build_schema_with_report(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(EmptySearcher),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        assert_eq!(report.dangling_relations, vec!["Farm.coops".to_string()]);
+    }
+
+    struct FailingSearcher;
+
+    #[async_trait::async_trait]
+    impl Searcher for FailingSearcher {
+        async fn find(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Option<Stash>> {
+            Err(MeshqlError::Storage("connection refused".to_string()))
+        }
+
+        async fn find_all(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Vec<Stash>> {
+            Err(MeshqlError::Storage("connection refused".to_string()))
+        }
+
+        async fn count(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<u64> {
+            Err(MeshqlError::Storage("connection refused".to_string()))
+        }
+
+        async fn exists(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<bool> {
+            Err(MeshqlError::Storage("connection refused".to_string()))
+        }
+
+        async fn ping(&self) -> meshql_core::Result<()> {
+            Err(MeshqlError::Storage("connection refused".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn resolver_errors_carry_a_code_and_path() {
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(FailingSearcher),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/farm/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let body: serde_json::Value = reqwest::Client::new()
+            .post(format!("http://{addr}/farm/graph"))
+            .json(&serde_json::json!({"query": r#"{ getFarm(id: "farm-1") { name } }"#}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let error = &body["errors"][0];
+        assert_eq!(error["extensions"]["code"], "STORAGE", "{body}");
+        assert_eq!(error["path"], serde_json::json!(["getFarm"]), "{body}");
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .contains("connection refused"));
+    }
+
+    const HEN_GRAPHQL: &str = r#"
+        type Hen {
+            id: ID
+            name: String
+            layReportsConnection(first: Int, after: String): LayReportConnection
+        }
+        type LayReport {
+            id: ID
+            eggs: Int
+        }
+        type LayReportEdge {
+            cursor: String
+            node: LayReport
+        }
+        type PageInfo {
+            hasNextPage: Boolean
+            endCursor: String
+        }
+        type LayReportConnection {
+            edges: [LayReportEdge]
+            pageInfo: PageInfo
+            totalCount: Int
+        }
+        type Query {
+            getHen(id: ID, at: Int): Hen
+        }
+    "#;
+
+    #[tokio::test]
+    async fn input_object_arguments_reach_the_template_nested() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        for (id, zone, owner) in [
+            ("farm-1", "north", "ann"),
+            ("farm-2", "north", "bob"),
+            ("farm-3", "south", "ann"),
+        ] {
+            let mut farm = Stash::new();
+            farm.insert("zone".to_string(), serde_json::json!(zone));
+            farm.insert("owner".to_string(), serde_json::json!(owner));
+            farms
+                .create(Envelope::new(id, farm, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+
+        let root_config = RootConfig::builder()
+            .vector(
+                "getFarms",
+                r#"{"payload.zone": "{{filter.zone}}", "payload.owner": "{{filter.owner}}"}"#,
+            )
+            .build();
+        let schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    zone: String
+                    owner: String
+                }
+                input FarmFilter {
+                    zone: String
+                    owner: String
+                }
+                type Query {
+                    getFarms(filter: FarmFilter!): [Farm]
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let response = schema
+            .execute(r#"{ getFarms(filter: { zone: "north", owner: "bob" }) { id } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["getFarms"], serde_json::json!([{"id": "farm-2"}]));
+    }
+
+    #[tokio::test]
+    async fn list_arguments_fill_in_membership_templates() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        for id in ["f-1", "f-2", "f-3", "f-4"] {
+            let mut farm = Stash::new();
+            farm.insert("name".to_string(), serde_json::json!(format!("farm {id}")));
+            farms
+                .create(Envelope::new(id, farm, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        let root_config = RootConfig::builder()
+            .vector("getByIds", r#"{"id": {"$in": "{{ids}}"}}"#)
+            .build();
+        let schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getByIds(ids: [ID], at: Int): [Farm]
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let response = schema
+            .execute(r#"{ getByIds(ids: ["f-1", "f-3", "f-4"]) { id } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let mut ids: Vec<&str> = data["getByIds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|farm| farm["id"].as_str().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["f-1", "f-3", "f-4"]);
+    }
+
+    #[tokio::test]
+    async fn pages_a_relation_through_its_connection_field() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        let mut hen = Stash::new();
+        hen.insert("name".to_string(), serde_json::json!("Henny"));
+        hens.create(Envelope::new("hen-1", hen, star.clone()), &star)
+            .await
+            .unwrap();
+        let reports = MemoryRepository::new();
+        for i in 0..50 {
+            let mut report = Stash::new();
+            report.insert("henId".to_string(), serde_json::json!("hen-1"));
+            report.insert("eggs".to_string(), serde_json::json!(i));
+            let id = format!("report-{i:02}");
+            reports
+                .create(Envelope::new(id, report, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+
+        let mut registry = ResolverRegistry::new();
+        registry.register(
+            "/lay_report/graph",
+            Arc::new(MemorySearcher::new(reports.store())),
+            RootConfig::builder()
+                .vector("getByHen", r#"{"payload.henId": "{{id}}"}"#)
+                .build(),
+        );
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .internal_vector_resolver("layReports", None, "getByHen", "/lay_report/graph")
+            .build();
+        let schema = build_schema(
+            HEN_GRAPHQL,
+            &root_config,
+            Arc::new(MemorySearcher::new(hens.store())),
+            &registry,
+        )
+        .unwrap();
+
+        let mut after = serde_json::Value::Null;
+        let mut seen = Vec::new();
+        for page in 0..5 {
+            let request = async_graphql::Request::new(
+                r#"query($after: String) { getHen(id: "hen-1") {
+                    layReportsConnection(first: 10, after: $after) {
+                        edges { cursor node { id eggs } }
+                        pageInfo { hasNextPage endCursor }
+                        totalCount
+                    }
+                } }"#,
+            )
+            .variables(async_graphql::Variables::from_json(
+                serde_json::json!({ "after": after }),
+            ));
+            let response = schema.execute(request).await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            let data = response.data.into_json().unwrap();
+            let connection = &data["getHen"]["layReportsConnection"];
+
+            let edges = connection["edges"].as_array().unwrap();
+            assert_eq!(edges.len(), 10);
+            assert_eq!(connection["totalCount"], 50);
+            assert_eq!(connection["pageInfo"]["hasNextPage"], page < 4);
+            assert_eq!(connection["pageInfo"]["endCursor"], edges[9]["cursor"]);
+            seen.extend(
+                edges
+                    .iter()
+                    .map(|e| e["node"]["id"].as_str().unwrap().to_string()),
+            );
+            after = connection["pageInfo"]["endCursor"].clone();
+        }
+
+        let expected: Vec<String> = (0..50).map(|i| format!("report-{i:02}")).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[tokio::test]
+    async fn resolves_a_relation_keyed_by_two_parent_fields() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let stock = MemoryRepository::new();
+        for (id, consumer, container, eggs) in [
+            ("s-1", "c-1", "k-1", 5),
+            ("s-2", "c-1", "k-2", 7),
+            ("s-3", "c-2", "k-2", 9),
+        ] {
+            let mut level = Stash::new();
+            level.insert("consumer_id".to_string(), serde_json::json!(consumer));
+            level.insert("container_id".to_string(), serde_json::json!(container));
+            level.insert("eggs".to_string(), serde_json::json!(eggs));
+            stock
+                .create(Envelope::new(id, level, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        let reports = MemoryRepository::new();
+        let mut report = Stash::new();
+        report.insert("consumer_id".to_string(), serde_json::json!("c-1"));
+        report.insert("container_id".to_string(), serde_json::json!("k-2"));
+        reports
+            .create(Envelope::new("r-1", report, star.clone()), &star)
+            .await
+            .unwrap();
+
+        let mut registry = ResolverRegistry::new();
+        registry.register(
+            "/stock/graph",
+            Arc::new(MemorySearcher::new(stock.store())),
+            RootConfig::builder()
+                .singleton(
+                    "getByConsumerAndContainer",
+                    r#"{"payload.consumer_id": "{{consumer}}", "payload.container_id": "{{container}}"}"#,
+                )
+                .build(),
+        );
+        let root_config = RootConfig::builder()
+            .singleton("getReport", r#"{"id": "{{id}}"}"#)
+            .internal_singleton_resolver(
+                "stock",
+                [("consumer", "consumer_id"), ("container", "container_id")],
+                "getByConsumerAndContainer",
+                "/stock/graph",
+            )
+            .build();
+        let schema = build_schema(
+            r#"
+                type Stock {
+                    id: ID
+                    eggs: Int
+                }
+                type ConsumptionReport {
+                    id: ID
+                    consumer_id: String
+                    container_id: String
+                    stock: Stock
+                }
+                type Query {
+                    getReport(id: ID, at: Int): ConsumptionReport
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(reports.store())),
+            &registry,
+        )
+        .unwrap();
+
+        let response = schema
+            .execute(r#"{ getReport(id: "r-1") { stock { id eggs } } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"getReport": {"stock": {"id": "s-2", "eggs": 7}}})
+        );
+    }
+
+    #[tokio::test]
+    async fn filtered_relations_pass_their_extra_args_to_the_template() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        farms
+            .create(Envelope::new("farm-1", Stash::new(), star.clone()), &star)
+            .await
+            .unwrap();
+        let hens = MemoryRepository::new();
+        for (id, farm, status) in [
+            ("hen-1", "farm-1", "active"),
+            ("hen-2", "farm-1", "retired"),
+            ("hen-3", "farm-1", "active"),
+            ("hen-4", "farm-2", "active"),
+        ] {
+            let mut hen = Stash::new();
+            hen.insert("farm_id".to_string(), serde_json::json!(farm));
+            hen.insert("status".to_string(), serde_json::json!(status));
+            hens.create(Envelope::new(id, hen, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+
+        let mut registry = ResolverRegistry::new();
+        registry.register(
+            "/hen/graph",
+            Arc::new(MemorySearcher::new(hens.store())),
+            RootConfig::builder()
+                .vector(
+                    "getByFarmAndStatus",
+                    r#"{"payload.farm_id": "{{id}}", "payload.status": "{{status}}"}"#,
+                )
+                .build(),
+        );
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .internal_vector_resolver_filtered(
+                "activeHens",
+                None,
+                "getByFarmAndStatus",
+                "/hen/graph",
+                serde_json::json!({"status": "active"})
+                    .as_object()
+                    .cloned()
+                    .unwrap(),
+            )
+            .build();
+        let schema = build_schema(
+            r#"
+                type Hen {
+                    id: ID
+                    status: String
+                }
+                type Farm {
+                    id: ID
+                    activeHens: [Hen]
+                }
+                type Query {
+                    getFarm(id: ID, at: Int): Farm
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &registry,
+        )
+        .unwrap();
+
+        let response = schema
+            .execute(r#"{ getFarm(id: "farm-1") { activeHens { id status } } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let mut ids: Vec<&str> = data["getFarm"]["activeHens"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hen| hen["id"].as_str().unwrap())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["hen-1", "hen-3"]);
+    }
+
+    #[tokio::test]
+    async fn resolves_enum_fields_from_stored_strings() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let reports = MemoryRepository::new();
+        for (id, quality) in [("good", "GOOD"), ("odd", "CRACKED")] {
+            let mut report = Stash::new();
+            report.insert("quality".to_string(), serde_json::json!(quality));
+            reports
+                .create(Envelope::new(id, report, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        let schema = build_schema(
+            r#"
+                enum Quality { GOOD BAD }
+                type LayReport {
+                    id: ID
+                    quality: Quality
+                }
+                type Query {
+                    getLayReport(id: ID, at: Int): LayReport
+                }
+            "#,
+            &RootConfig::builder()
+                .singleton("getLayReport", r#"{"id": "{{id}}"}"#)
+                .build(),
+            Arc::new(MemorySearcher::new(reports.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        assert!(schema.sdl().contains("enum Quality"));
+
+        let response = schema
+            .execute(r#"{ getLayReport(id: "good") { quality } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"getLayReport": {"quality": "GOOD"}})
+        );
+
+        let response = schema
+            .execute(r#"{ getLayReport(id: "odd") { quality } }"#)
+            .await;
+        assert_eq!(response.errors.len(), 1);
+        assert!(
+            response.errors[0].message.contains("not a valid Quality"),
+            "{:?}",
+            response.errors
+        );
+    }
+
+    #[tokio::test]
+    async fn resolves_union_and_interface_results_by_their_type_key() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let things = MemoryRepository::new();
+        for (id, type_name, name) in [("farm-1", "Farm", "Emerdale"), ("coop-1", "Coop", "Red")] {
+            let mut thing = Stash::new();
+            thing.insert(TYPE_KEY.to_string(), serde_json::json!(type_name));
+            thing.insert("name".to_string(), serde_json::json!(name));
+            things
+                .create(Envelope::new(id, thing, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        let schema = build_schema(
+            r#"
+                interface Node {
+                    id: ID
+                }
+                type Farm implements Node {
+                    id: ID
+                    name: String
+                }
+                type Coop implements Node {
+                    id: ID
+                    name: String
+                }
+                union SearchResult = Farm | Coop
+                type Query {
+                    search(at: Int): [SearchResult]
+                    getNode(id: ID, at: Int): Node
+                }
+            "#,
+            &RootConfig::builder()
+                .vector("search", "{}")
+                .singleton("getNode", r#"{"id": "{{id}}"}"#)
+                .build(),
+            Arc::new(MemorySearcher::new(things.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let response = schema
+            .execute(
+                r#"{ search {
+                    __typename
+                    ... on Farm { id name }
+                    ... on Coop { id }
+                } }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let mut results = data["search"].as_array().unwrap().clone();
+        results.sort_by_key(|r| r["id"].as_str().unwrap().to_string());
+        assert_eq!(
+            results,
+            vec![
+                serde_json::json!({"__typename": "Coop", "id": "coop-1"}),
+                serde_json::json!({"__typename": "Farm", "id": "farm-1", "name": "Emerdale"}),
+            ]
+        );
+
+        let response = schema
+            .execute(r#"{ getNode(id: "coop-1") { __typename id } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"getNode": {"__typename": "Coop", "id": "coop-1"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn computes_ratio_fields_and_fills_defaults() {
+        use meshql_core::Operator;
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let outputs = MemoryRepository::new();
+        for (id, eggs, hens) in [("busy", 140, 20), ("empty", 0, 0)] {
+            let mut output = Stash::new();
+            output.insert("eggs_week".to_string(), serde_json::json!(eggs));
+            output.insert("active_hens".to_string(), serde_json::json!(hens));
+            outputs
+                .create(Envelope::new(id, output, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        let schema = build_schema(
+            r#"
+                type FarmOutput {
+                    id: ID
+                    eggs_week: Int
+                    eggsPerHen: Float
+                    farm_type: String
+                }
+                type Query {
+                    getOutput(id: ID, at: Int): FarmOutput
+                }
+            "#,
+            &RootConfig::builder()
+                .singleton("getOutput", r#"{"id": "{{id}}"}"#)
+                .computed_field(
+                    "FarmOutput",
+                    "eggsPerHen",
+                    "eggs_week",
+                    Operator::Divide,
+                    "active_hens",
+                )
+                .field_default("FarmOutput", "farm_type", serde_json::json!("homestead"))
+                .build(),
+            Arc::new(MemorySearcher::new(outputs.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let query = |id: &str| {
+            format!(r#"{{ getOutput(id: "{id}") {{ eggs_week eggsPerHen farm_type }} }}"#)
+        };
+
+        let response = schema.execute(query("busy")).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"getOutput": {
+                "eggs_week": 140, "eggsPerHen": 7.0, "farm_type": "homestead"
+            }})
+        );
+
+        let response = schema.execute(query("empty")).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"getOutput": {
+                "eggs_week": 0, "eggsPerHen": null, "farm_type": "homestead"
+            }})
+        );
+    }
+
+    #[tokio::test]
+    async fn created_at_and_deleted_resolve_from_envelope_metadata() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let coops = MemoryRepository::new();
+        coops
+            .create(Envelope::new("x", Stash::new(), star.clone()), &star)
+            .await
+            .unwrap();
+        let mut own = Stash::new();
+        own.insert("createdAt".to_string(), serde_json::json!("2020-01-01"));
+        coops
+            .create(Envelope::new("own", own, star.clone()), &star)
+            .await
+            .unwrap();
+        let schema = build_schema(
+            r#"
+                type Coop {
+                    id: ID
+                    createdAt: Date
+                    deleted: Boolean
+                }
+                type Query {
+                    getById(id: ID, at: Int): Coop
+                }
+            "#,
+            &RootConfig::builder()
+                .singleton("getById", r#"{"id": "{{id}}"}"#)
+                .build(),
+            Arc::new(MemorySearcher::new(coops.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let response = schema
+            .execute(r#"{ getById(id: "x") { createdAt deleted } }"#)
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let coop = response.data.into_json().unwrap()["getById"].clone();
+        let created_at = chrono::DateTime::parse_from_rfc3339(coop["createdAt"].as_str().unwrap())
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let age = chrono::Utc::now() - created_at;
+        assert!(age >= chrono::Duration::zero() && age < chrono::Duration::minutes(1));
+        assert_eq!(coop["deleted"], serde_json::json!(false));
+
+        let response = schema
+            .execute(r#"{ getById(id: "own") { createdAt } }"#)
+            .await;
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            serde_json::json!({"getById": {"createdAt": "2020-01-01T00:00:00.000Z"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn deletes_report_whether_there_was_anything_to_delete() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        hens.create(Envelope::new("hen-1", Stash::new(), star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .build();
+        let searcher: Arc<dyn Searcher> = Arc::new(MemorySearcher::new(hens.store()));
+        let mut registry = ResolverRegistry::new();
+        registry.register("/hen/graph", Arc::clone(&searcher), root_config.clone());
+        registry.register_repository(
+            "/hen/graph",
+            Arc::new(MemoryRepository::new_with_store(hens.store())),
+        );
+        let schema = build_schema_at(
+            "/hen/graph",
+            r#"
+                type Hen {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getHen(id: ID, at: Int): Hen
+                }
+                type Mutation {
+                    deleteHen(id: ID!): DeleteResult
+                }
+            "#,
+            &root_config,
+            searcher,
+            &registry,
+        )
+        .unwrap();
+
+        for (id, deleted) in [("hen-1", true), ("hen-2", false)] {
+            let response = schema
+                .execute(format!(
+                    r#"mutation {{ deleteHen(id: "{id}") {{ id deleted }} }}"#
+                ))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(
+                response.data.into_json().unwrap(),
+                serde_json::json!({"deleteHen": {"id": id, "deleted": deleted}})
+            );
+        }
+        let gone = schema.execute(r#"{ getHen(id: "hen-1") { id } }"#).await;
+        assert_eq!(
+            gone.data.into_json().unwrap(),
+            serde_json::json!({"getHen": null})
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_based_on_a_stale_version_conflict() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        let mut earlier = Envelope::new("hen-1", Stash::new(), star.clone());
+        earlier.created_at = chrono::Utc::now() - chrono::Duration::hours(1);
+        hens.create(earlier.clone(), &star).await.unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .build();
+        let searcher: Arc<dyn Searcher> = Arc::new(MemorySearcher::new(hens.store()));
+        let mut registry = ResolverRegistry::new();
+        registry.register("/hen/graph", Arc::clone(&searcher), root_config.clone());
+        registry.register_repository(
+            "/hen/graph",
+            Arc::new(MemoryRepository::new_with_store(hens.store())),
+        );
+        let schema = build_schema_at(
+            "/hen/graph",
+            r#"
+                type Hen {
+                    id: ID
+                    name: String
+                    createdAt: Date
+                }
+                type Query {
+                    getHen(id: ID, at: Int): Hen
+                }
+                type Mutation {
+                    updateHen(id: ID!, input: HenInput, expectedCreatedAt: String): Hen
+                }
+            "#,
+            &root_config,
+            searcher,
+            &registry,
+        )
+        .unwrap();
+        let update = |expected: &str| {
+            format!(
+                r#"mutation {{ updateHen(id: "hen-1", input: {{name: "chuck"}}, expectedCreatedAt: "{expected}") {{ createdAt }} }}"#
+            )
+        };
+        let stale = earlier.created_at.to_rfc3339();
+
+        let updated = schema.execute(update(&stale)).await;
+        assert!(updated.errors.is_empty(), "{:?}", updated.errors);
+        let current = updated.data.into_json().unwrap()["updateHen"]["createdAt"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let conflicting = schema.execute(update(&stale)).await;
+        assert_eq!(
+            conflicting.errors[0]
+                .extensions
+                .as_ref()
+                .unwrap()
+                .get("code"),
+            Some(&async_graphql::Value::from("CONFLICT"))
+        );
+        let updated = schema.execute(update(&current)).await;
+        assert!(updated.errors.is_empty(), "{:?}", updated.errors);
+        assert_eq!(hens.history("hen-1", &star).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn strict_queries_need_every_argument_their_template_names() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        hens.create(Envelope::new("hen-1", Stash::new(), star.clone()), &star)
+            .await
+            .unwrap();
+        let schema = build_schema(
+            r#"
+                type Hen {
+                    id: ID
+                }
+                type Query {
+                    getByCoop(coop: String): [Hen]
+                    getStrictlyByCoop(coop: String): [Hen]
+                }
+            "#,
+            &RootConfig::builder()
+                .vector("getByCoop", r#"{"payload.coop": "{{coop}}"}"#)
+                .vector("getStrictlyByCoop", r#"{"payload.coop": "{{coop}}"}"#)
+                .strict("getStrictlyByCoop")
+                .build(),
+            Arc::new(MemorySearcher::new(hens.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let lenient = schema.execute("{ getByCoop { id } }").await;
+        assert!(lenient.errors.is_empty(), "{:?}", lenient.errors);
+        let strict = schema.execute("{ getStrictlyByCoop { id } }").await;
+        assert_eq!(strict.errors.len(), 1, "{:?}", strict.errors);
+        assert!(
+            strict.errors[0].message.contains("coop"),
+            "{:?}",
+            strict.errors
+        );
+        let given = schema
+            .execute(r#"{ getStrictlyByCoop(coop: "red") { id } }"#)
+            .await;
+        assert!(given.errors.is_empty(), "{:?}", given.errors);
+    }
+
+    #[tokio::test]
+    async fn mutations_take_integer_ids() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        hens.create(Envelope::new("42", Stash::new(), star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .build();
+        let searcher: Arc<dyn Searcher> = Arc::new(MemorySearcher::new(hens.store()));
+        let mut registry = ResolverRegistry::new();
+        registry.register("/hen/graph", Arc::clone(&searcher), root_config.clone());
+        registry.register_repository(
+            "/hen/graph",
+            Arc::new(MemoryRepository::new_with_store(hens.store())),
+        );
+        let schema = build_schema_at(
+            "/hen/graph",
+            r#"
+                type Hen {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getHen(id: ID, at: Int): Hen
+                }
+                type Mutation {
+                    updateHen(id: ID!, input: HenInput): Hen
+                    deleteHen(id: ID!): DeleteResult
+                }
+            "#,
+            &root_config,
+            searcher,
+            &registry,
+        )
+        .unwrap();
+
+        let updated = schema
+            .execute(r#"mutation { updateHen(id: 42, input: {name: "chuck"}) { id name } }"#)
+            .await;
+        assert!(updated.errors.is_empty(), "{:?}", updated.errors);
+        assert_eq!(
+            updated.data.into_json().unwrap(),
+            serde_json::json!({"updateHen": {"id": "42", "name": "chuck"}})
+        );
+
+        let deleted = schema
+            .execute("mutation { deleteHen(id: 42) { id deleted } }")
+            .await;
+        assert!(deleted.errors.is_empty(), "{:?}", deleted.errors);
+        assert_eq!(
+            deleted.data.into_json().unwrap(),
+            serde_json::json!({"deleteHen": {"id": "42", "deleted": true}})
+        );
+    }
+
+    #[tokio::test]
+    async fn coerces_date_fields_to_rfc3339() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        for (id, dob) in [
+            (
+                "from-string",
+                serde_json::json!("2024-03-01T14:30:00+02:00"),
+            ),
+            ("from-millis", serde_json::json!(1_709_296_200_000_i64)),
+            ("garbage", serde_json::json!("last tuesday")),
+        ] {
+            let mut hen = Stash::new();
+            hen.insert("dob".to_string(), dob);
+            hens.create(Envelope::new(id, hen, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .build();
+        let searcher: Arc<dyn Searcher> = Arc::new(MemorySearcher::new(hens.store()));
+        let mut registry = ResolverRegistry::new();
+        registry.register("/hen/graph", Arc::clone(&searcher), root_config.clone());
+        registry.register_repository(
+            "/hen/graph",
+            Arc::new(MemoryRepository::new_with_store(hens.store())),
+        );
+        let schema = build_schema_at(
+            "/hen/graph",
+            r#"
+                type Hen {
+                    id: ID
+                    dob: Date
+                }
+                type Query {
+                    getHen(id: ID, at: Int): Hen
+                }
+                type Mutation {
+                    createHen(input: HenInput): Hen
                 }
-            }),
+            "#,
+            &root_config,
+            searcher,
+            &registry,
         )
+        .unwrap();
+
+        for id in ["from-string", "from-millis"] {
+            let response = schema
+                .execute(format!(r#"{{ getHen(id: "{id}") {{ dob }} }}"#))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(
+                response.data.into_json().unwrap(),
+                serde_json::json!({"getHen": {"dob": "2024-03-01T12:30:00.000Z"}})
+            );
+        }
+        let response = schema.execute(r#"{ getHen(id: "garbage") { dob } }"#).await;
+        assert!(
+            response.errors[0].message.contains("not a valid Date"),
+            "{:?}",
+            response.errors
+        );
+
+        for dob in [r#""2024-03-01T12:30:00Z""#, "1709296200000"] {
+            let response = schema
+                .execute(format!(
+                    "mutation {{ createHen(input: {{dob: {dob}}}) {{ id }} }}"
+                ))
+                .await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            let data = response.data.into_json().unwrap();
+            let id = data["createHen"]["id"].as_str().unwrap();
+            let stored = hens.read(id, &star, None).await.unwrap().unwrap();
+            assert_eq!(stored.payload["dob"], "2024-03-01T12:30:00.000Z");
+        }
+        let response = schema
+            .execute(r#"mutation { createHen(input: {dob: "soon"}) { id } }"#)
+            .await;
+        assert!(!response.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn forbidden_updates_report_not_authorized() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let alice = vec!["alice".to_string()];
+        let hens = MemoryRepository::new();
+        let mut hen = Stash::new();
+        hen.insert("name".to_string(), serde_json::json!("chuck"));
+        hens.create(Envelope::new("hen-1", hen, alice.clone()), &alice)
+            .await
+            .unwrap();
+
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .build();
+        let searcher: Arc<dyn Searcher> = Arc::new(MemorySearcher::new(hens.store()));
+        let mut registry = ResolverRegistry::new();
+        registry.register("/hen/graph", Arc::clone(&searcher), root_config.clone());
+        registry.register_repository(
+            "/hen/graph",
+            Arc::new(MemoryRepository::new_with_store(hens.store())),
+        );
+        let schema = build_schema_at(
+            "/hen/graph",
+            r#"
+                type Hen {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getHen(id: ID, at: Int): Hen
+                }
+                type Mutation {
+                    updateHen(id: ID!, input: HenInput): Hen
+                }
+            "#,
+            &root_config,
+            searcher,
+            &registry,
+        )
+        .unwrap();
+        let update = |id: &str, creds: &[String]| {
+            async_graphql::Request::new(format!(
+                r#"mutation {{ updateHen(id: "{id}", input: {{name: "foghorn"}}) {{ name }} }}"#
+            ))
+            .data(Credentials(creds.to_vec()))
+        };
+        let bob = vec!["bob".to_string()];
+
+        let forbidden = schema.execute(update("hen-1", &bob)).await;
+        assert_eq!(forbidden.errors.len(), 1);
+        let code = forbidden.errors[0]
+            .extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"));
+        assert_eq!(
+            code,
+            Some(&async_graphql::Value::from("NOT_AUTHORIZED")),
+            "{:?}",
+            forbidden.errors
+        );
+
+        let missing = schema.execute(update("hen-2", &bob)).await;
+        assert!(missing.errors.is_empty(), "{:?}", missing.errors);
+        assert_eq!(
+            missing.data.into_json().unwrap(),
+            serde_json::json!({"updateHen": null})
+        );
+
+        let allowed = schema.execute(update("hen-1", &alice)).await;
+        assert!(allowed.errors.is_empty(), "{:?}", allowed.errors);
+    }
+
+    #[tokio::test]
+    async fn restricted_fields_need_their_token() {
+        use meshql_core::FieldDenial;
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farm.insert("owner".to_string(), serde_json::json!("alice"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let sdl = r#"
+            type Farm {
+                id: ID
+                name: String
+                owner: String
+            }
+            type Query {
+                getFarm(id: ID, at: Int): Farm
+            }
+        "#;
+        let build = |denial: FieldDenial| {
+            let root_config = RootConfig::builder()
+                .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+                .restrict_field("Farm", "owner", "admin")
+                .field_denial(denial)
+                .build();
+            build_schema(
+                sdl,
+                &root_config,
+                Arc::new(MemorySearcher::new(farms.store())),
+                &ResolverRegistry::new(),
+            )
+            .unwrap()
+        };
+        let get_farm = |creds: &[&str]| {
+            async_graphql::Request::new(r#"{ getFarm(id: "farm-1") { name owner } }"#)
+                .data(Credentials(creds.iter().map(|c| c.to_string()).collect()))
+        };
+
+        let nulled = build(FieldDenial::Null);
+        let viewer = nulled.execute(get_farm(&["viewer"])).await;
+        assert!(viewer.errors.is_empty(), "{:?}", viewer.errors);
+        assert_eq!(
+            viewer.data.into_json().unwrap(),
+            serde_json::json!({"getFarm": {"name": "Emerdale", "owner": null}})
+        );
+        for creds in [&["viewer", "admin"][..], &["*"]] {
+            let admin = nulled.execute(get_farm(creds)).await;
+            assert_eq!(
+                admin.data.into_json().unwrap(),
+                serde_json::json!({"getFarm": {"name": "Emerdale", "owner": "alice"}})
+            );
+        }
+
+        let refused = build(FieldDenial::Error)
+            .execute(get_farm(&["viewer"]))
+            .await;
+        assert_eq!(refused.errors.len(), 1);
+        let code = refused.errors[0]
+            .extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"));
+        assert_eq!(code, Some(&async_graphql::Value::from("NOT_AUTHORIZED")));
+        assert_eq!(
+            refused.data.into_json().unwrap(),
+            serde_json::json!({"getFarm": {"name": "Emerdale"}})
+        );
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_new_matching_envelopes_over_sse() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        let mut hen = Stash::new();
+        hen.insert("name".to_string(), serde_json::json!("chuck"));
+        hen.insert("coop".to_string(), serde_json::json!("north"));
+        hens.create(Envelope::new("hen-1", hen, star.clone()), &star)
+            .await
+            .unwrap();
+
+        let root_config = RootConfig::builder()
+            .vector("getByCoop", r#"{"payload.coop": "{{coop}}"}"#)
+            .subscription_interval(std::time::Duration::from_millis(20))
+            .build();
+        let schema = build_schema(
+            r#"
+                type Hen {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getByCoop(coop: String, at: Int): [Hen]
+                }
+                type Subscription {
+                    getByCoop(coop: String): [Hen]
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(hens.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/hen/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let mut response = reqwest::Client::new()
+            .get(format!("http://{addr}/hen/graph/stream"))
+            .query(&[(
+                "query",
+                r#"subscription { getByCoop(coop: "north") { id name } }"#,
+            )])
+            .send()
+            .await
+            .unwrap();
+        assert!(response.headers()[CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/event-stream"));
+
+        // Events arrive as `data: {...}` lines, possibly split across chunks.
+        async fn next_event(
+            response: &mut reqwest::Response,
+            buffered: &mut String,
+        ) -> serde_json::Value {
+            loop {
+                if let Some(end) = buffered.find("\n\n") {
+                    let event: String = buffered.drain(..end + 2).collect();
+                    if let Some(data) = event.trim().strip_prefix("data:") {
+                        return serde_json::from_str(data.trim()).unwrap();
+                    }
+                    continue;
+                }
+                let chunk = response.chunk().await.unwrap().unwrap();
+                buffered.push_str(std::str::from_utf8(&chunk).unwrap());
+            }
+        }
+        let mut buffered = String::new();
+
+        let first = next_event(&mut response, &mut buffered).await;
+        assert_eq!(
+            first["data"]["getByCoop"],
+            serde_json::json!([{"id": "hen-1", "name": "chuck"}])
+        );
+
+        for (id, coop) in [("hen-2", "south"), ("hen-3", "north")] {
+            let mut hen = Stash::new();
+            hen.insert("name".to_string(), serde_json::json!(id));
+            hen.insert("coop".to_string(), serde_json::json!(coop));
+            hens.create(Envelope::new(id, hen, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+
+        let update = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            next_event(&mut response, &mut buffered),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            update["data"]["getByCoop"],
+            serde_json::json!([{"id": "hen-3", "name": "hen-3"}])
+        );
+    }
+
+    #[tokio::test]
+    async fn relations_cycling_between_graphlettes_stop_at_max_depth() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let coops = MemoryRepository::new();
+        let mut coop = Stash::new();
+        coop.insert("farm_id".to_string(), serde_json::json!("farm-1"));
+        coops
+            .create(Envelope::new("coop-1", coop, star.clone()), &star)
+            .await
+            .unwrap();
+
+        let farm_sdl = r#"
+            type Coop {
+                id: ID
+                farm: Farm
+            }
+            type Farm {
+                id: ID
+                name: String
+                coops: [Coop]
+            }
+            type Query {
+                getFarm(id: ID, at: Int): Farm
+            }
+        "#;
+        let farm_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .internal_vector_resolver("coops", None, "getByFarm", "/coop/graph")
+            .max_depth(6)
+            .build();
+        let coop_config = RootConfig::builder()
+            .singleton("getCoop", r#"{"id": "{{id}}"}"#)
+            .vector("getByFarm", r#"{"payload.farm_id": "{{id}}"}"#)
+            .internal_singleton_resolver("farm", [("id", "farm_id")], "getFarm", "/farm/graph")
+            .build();
+        let mut registry = ResolverRegistry::new();
+        registry.register(
+            "/farm/graph",
+            Arc::new(MemorySearcher::new(farms.store())),
+            farm_config.clone(),
+        );
+        registry.register(
+            "/coop/graph",
+            Arc::new(MemorySearcher::new(coops.store())),
+            coop_config,
+        );
+        let schema = build_schema(
+            farm_sdl,
+            &farm_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &registry,
+        )
+        .unwrap();
+
+        let at_limit = schema
+            .execute(r#"{ getFarm(id: "farm-1") { coops { farm { coops { farm { name } } } } } }"#)
+            .await;
+        assert!(at_limit.errors.is_empty(), "{:?}", at_limit.errors);
+        assert_eq!(
+            at_limit.data.into_json().unwrap(),
+            serde_json::json!({"getFarm": {"coops": [{"farm": {"coops": [{"farm": {"name": "Emerdale"}}]}}]}})
+        );
+
+        let deep = schema
+            .execute(
+                r#"{ getFarm(id: "farm-1") { coops { farm { coops { farm { coops { id } } } } } } }"#,
+            )
+            .await;
+        assert_eq!(deep.errors.len(), 1);
+        assert!(
+            deep.errors[0].message.contains("nested too deep"),
+            "{}",
+            deep.errors[0].message
+        );
+        assert_eq!(deep.data, async_graphql::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn numeric_ids_resolve_as_their_decimal_strings() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("42", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let coops = MemoryRepository::new();
+        let mut coop = Stash::new();
+        coop.insert("farm_id".to_string(), serde_json::json!(42));
+        coops
+            .create(Envelope::new("coop-1", coop, star.clone()), &star)
+            .await
+            .unwrap();
+
+        let farm_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .vector("getFarms", r#"{"id": {"$in": "{{ids}}"}}"#)
+            .build();
+        let coop_config = RootConfig::builder()
+            .singleton("getCoop", r#"{"id": "{{id}}"}"#)
+            .singleton_resolver("farm", Some("farm_id"), "getFarm", "/farm/graph")
+            .build();
+        let mut registry = ResolverRegistry::new();
+        registry.register(
+            "/farm/graph",
+            Arc::new(MemorySearcher::new(farms.store())),
+            farm_config.clone(),
+        );
+        let farm_schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getFarm(id: ID, at: Int): Farm
+                    getFarms(ids: [ID], at: Int): [Farm]
+                }
+            "#,
+            &farm_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &registry,
+        )
+        .unwrap();
+        let coop_schema = build_schema(
+            r#"
+                type Coop {
+                    id: ID
+                    farm: Farm
+                }
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getCoop(id: ID, at: Int): Coop
+                }
+            "#,
+            &coop_config,
+            Arc::new(MemorySearcher::new(coops.store())),
+            &registry,
+        )
+        .unwrap();
+
+        let coop = coop_schema
+            .execute(r#"{ getCoop(id: "coop-1") { farm { id name } } }"#)
+            .await;
+        assert!(coop.errors.is_empty(), "{:?}", coop.errors);
+        assert_eq!(
+            coop.data.into_json().unwrap(),
+            serde_json::json!({"getCoop": {"farm": {"id": "42", "name": "Emerdale"}}})
+        );
+
+        let listed = farm_schema
+            .execute("{ getFarms(ids: [42, 7]) { name } }")
+            .await;
+        assert!(listed.errors.is_empty(), "{:?}", listed.errors);
+        assert_eq!(
+            listed.data.into_json().unwrap(),
+            serde_json::json!({"getFarms": [{"name": "Emerdale"}]})
+        );
+    }
+
+    #[tokio::test]
+    async fn schema_definitions_name_the_root_types() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let hens = MemoryRepository::new();
+        let mut hen = Stash::new();
+        hen.insert("name".to_string(), serde_json::json!("Ruby"));
+        hens.create(Envelope::new("hen-1", hen, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getHen", r#"{"id": "{{id}}"}"#)
+            .build();
+        let searcher: Arc<dyn Searcher> = Arc::new(MemorySearcher::new(hens.store()));
+        let mut registry = ResolverRegistry::new();
+        registry.register("/hen/graph", Arc::clone(&searcher), root_config.clone());
+        registry.register_repository(
+            "/hen/graph",
+            Arc::new(MemoryRepository::new_with_store(hens.store())),
+        );
+        let schema = build_schema_at(
+            "/hen/graph",
+            r#"
+                schema {
+                    query: RootQuery
+                    mutation: RootMutation
+                }
+                type Hen {
+                    id: ID
+                    name: String
+                }
+                type RootQuery {
+                    getHen(id: ID, at: Int): Hen
+                }
+                type RootMutation {
+                    createHen(input: HenInput): Hen
+                }
+            "#,
+            &root_config,
+            searcher,
+            &registry,
+        )
+        .unwrap();
+
+        let found = schema.execute(r#"{ getHen(id: "hen-1") { name } }"#).await;
+        assert!(found.errors.is_empty(), "{:?}", found.errors);
+        assert_eq!(
+            found.data.into_json().unwrap(),
+            serde_json::json!({"getHen": {"name": "Ruby"}})
+        );
+        let created = schema
+            .execute(r#"mutation { createHen(input: {name: "Pearl"}) { name } }"#)
+            .await;
+        assert!(created.errors.is_empty(), "{:?}", created.errors);
+        assert_eq!(
+            created.data.into_json().unwrap(),
+            serde_json::json!({"createHen": {"name": "Pearl"}})
+        );
+        let sdl = schema.sdl();
+        assert!(sdl.contains("type RootQuery"), "{sdl}");
+        assert!(!sdl.contains("type Query"), "{sdl}");
+    }
+
+    #[tokio::test]
+    async fn at_must_be_whole_epoch_millis() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        for (name, created_at) in [
+            (
+                "old",
+                chrono::DateTime::from_timestamp_millis(1_577_836_800_000).unwrap(),
+            ),
+            ("new", Utc::now()),
+        ] {
+            let mut farm = Stash::new();
+            farm.insert("name".to_string(), serde_json::json!(name));
+            let env = Envelope {
+                created_at,
+                ..Envelope::new("farm-1", farm, star.clone())
+            };
+            farms.create(env, &star).await.unwrap();
+        }
+        let template = r#"{"id": "{{id}}"}"#;
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", template)
+            .singleton("getFarmByFloat", template)
+            .singleton("getFarmByString", template)
+            .build();
+        let schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getFarm(id: ID, at: Int): Farm
+                    getFarmByFloat(id: ID, at: Float): Farm
+                    getFarmByString(id: ID, at: String): Farm
+                }
+            "#,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        for query in [
+            r#"{ farm: getFarm(id: "farm-1", at: 1600000000000) { name } }"#,
+            r#"{ farm: getFarmByFloat(id: "farm-1", at: 1600000000000.0) { name } }"#,
+        ] {
+            let response = schema.execute(query).await;
+            assert!(response.errors.is_empty(), "{:?}", response.errors);
+            assert_eq!(
+                response.data.into_json().unwrap(),
+                serde_json::json!({"farm": {"name": "old"}})
+            );
+        }
+
+        for (query, expected) in [
+            (
+                r#"{ getFarmByString(id: "farm-1", at: "1600000000000") { name } }"#,
+                "`at` on getFarmByString must be a whole number of epoch milliseconds",
+            ),
+            (
+                r#"{ getFarmByFloat(id: "farm-1", at: 1600000000000.5) { name } }"#,
+                "must be a whole number",
+            ),
+            (
+                r#"{ getFarm(id: "farm-1", at: -1) { name } }"#,
+                "must not be negative",
+            ),
+            (
+                r#"{ getFarm(id: "farm-1", at: 1600000000000000) { name } }"#,
+                "is past year 9999",
+            ),
+        ] {
+            let response = schema.execute(query).await;
+            let error = &response.errors[0];
+            assert!(error.message.contains(expected), "{}", error.message);
+            let code = error.extensions.as_ref().and_then(|e| e.get("code"));
+            assert_eq!(code, Some(&async_graphql::Value::from("VALIDATION")));
+        }
+    }
+
+    #[tokio::test]
+    async fn introspection_can_be_disabled() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let sdl = r#"
+            type Farm {
+                id: ID
+                name: String
+            }
+            type Query {
+                getFarm(id: ID, at: Int): Farm
+            }
+        "#;
+        let build = |root_config: RootConfig| {
+            build_schema(
+                sdl,
+                &root_config,
+                Arc::new(MemorySearcher::new(MemoryRepository::new().store())),
+                &ResolverRegistry::new(),
+            )
+            .unwrap()
+        };
+        let open = build(RootConfig::builder().singleton("getFarm", "{}").build());
+        let locked = build(
+            RootConfig::builder()
+                .singleton("getFarm", "{}")
+                .disable_introspection()
+                .build(),
+        );
+
+        for query in [
+            "{ __schema { queryType { name } } }",
+            r#"{ __type(name: "Farm") { name } }"#,
+        ] {
+            let allowed = open.execute(query).await;
+            assert!(allowed.errors.is_empty(), "{:?}", allowed.errors);
+
+            let rejected = locked.execute(query).await;
+            assert_eq!(rejected.errors.len(), 1, "{query}");
+            // async-graphql's own answer: the introspection fields don't exist.
+            assert!(
+                rejected.errors[0].message.starts_with("Unknown field \"__"),
+                "{}",
+                rejected.errors[0].message
+            );
+        }
+
+        let typename = locked.execute("{ __typename }").await;
+        assert!(typename.errors.is_empty(), "{:?}", typename.errors);
+    }
+
+    #[tokio::test]
+    async fn queries_can_be_sent_with_get() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build_with_cache_control(
+            "/farm/graph",
+            schema,
+            Arc::new(NoAuth),
+            Some(HeaderValue::from_static("public, max-age=60")),
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        let url = format!("http://{addr}/farm/graph");
+        let client = reqwest::Client::new();
+        let query = "query Farm($id: ID) { getFarm(id: $id) { id name } }";
+        let variables = serde_json::json!({"id": "farm-1"});
+
+        let posted: serde_json::Value = client
+            .post(&url)
+            .json(&serde_json::json!({"query": query, "variables": variables}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let response = client
+            .get(&url)
+            .query(&[
+                ("query", query),
+                ("variables", &variables.to_string()),
+                ("operationName", "Farm"),
+            ])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[axum::http::header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+        let fetched: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(fetched, posted);
+        assert_eq!(fetched["data"]["getFarm"]["name"], "Emerdale");
+
+        let mutation = client
+            .get(&url)
+            .query(&[("query", "mutation { create(name: \"x\") { id } }")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(mutation.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(mutation.headers()[axum::http::header::ALLOW], "POST");
+
+        // Errors aren't worth caching.
+        let failed = client
+            .get(&url)
+            .query(&[("query", "{ getFarm(id: \"farm-1\") { acres } }")])
+            .send()
+            .await
+            .unwrap();
+        assert!(failed
+            .headers()
+            .get(axum::http::header::CACHE_CONTROL)
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn raw_graphql_bodies_are_run_as_queries() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = MemoryRepository::new();
+        let mut farm = Stash::new();
+        farm.insert("name".to_string(), serde_json::json!("Emerdale"));
+        farms
+            .create(Envelope::new("farm-1", farm, star.clone()), &star)
+            .await
+            .unwrap();
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let schema = build_schema(
+            FARM_GRAPHQL,
+            &root_config,
+            Arc::new(MemorySearcher::new(farms.store())),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/farm/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let body: serde_json::Value = reqwest::Client::new()
+            .post(format!("http://{addr}/farm/graph"))
+            .header(CONTENT_TYPE, "application/graphql")
+            .body(r#"{ getFarm(id: "farm-1") { name } }"#)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["errors"], serde_json::Value::Null, "{body}");
+        assert_eq!(body["data"]["getFarm"]["name"], "Emerdale");
+    }
+
+    #[tokio::test]
+    async fn multipart_uploads_fill_upload_fields() {
+        use meshql_memory::{MemoryRepository, MemorySearcher};
+
+        let star = vec!["*".to_string()];
+        let farms = Arc::new(MemoryRepository::new());
+        let searcher = Arc::new(MemorySearcher::new(farms.store()));
+        let root_config = RootConfig::builder()
+            .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+            .build();
+        let mut registry = ResolverRegistry::new();
+        registry.register_with_repository(
+            "/farm/graph",
+            searcher.clone(),
+            root_config.clone(),
+            farms.clone(),
+        );
+        let schema = build_schema_at(
+            "/farm/graph",
+            r#"
+                scalar Upload
+                type Farm {
+                    id: ID
+                    name: String
+                    deed: String
+                }
+                input FarmInput {
+                    name: String
+                    deed: Upload
+                }
+                type Query {
+                    getFarm(id: ID, at: Int): Farm
+                }
+                type Mutation {
+                    createFarm(input: FarmInput): Farm
+                }
+            "#,
+            &root_config,
+            searcher,
+            &registry,
+        )
+        .unwrap();
+        let app = GraphletteRouter::build("/farm/graph", schema);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let operations = serde_json::json!({
+            "query": r#"mutation ($deed: Upload!) {
+                createFarm(input: {name: "Emerdale", deed: $deed}) { id deed }
+            }"#,
+            "variables": {"deed": null},
+        });
+        let body = format!(
+            "--boundary\r\n\
+             Content-Disposition: form-data; name=\"operations\"\r\n\r\n\
+             {operations}\r\n\
+             --boundary\r\n\
+             Content-Disposition: form-data; name=\"map\"\r\n\r\n\
+             {{\"0\": [\"variables.deed\"]}}\r\n\
+             --boundary\r\n\
+             Content-Disposition: form-data; name=\"0\"; filename=\"deed.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             signed\r\n\
+             --boundary--\r\n"
+        );
+        let body: serde_json::Value = reqwest::Client::new()
+            .post(format!("http://{addr}/farm/graph"))
+            .header(CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+            .body(body)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["errors"], serde_json::Value::Null, "{body}");
+        let created = &body["data"]["createFarm"];
+        assert_eq!(created["deed"], "c2lnbmVk");
+        let id = created["id"].as_str().unwrap();
+        let stored = farms.read(id, &star, None).await.unwrap().unwrap();
+        assert_eq!(stored.payload["deed"], "c2lnbmVk");
+        assert_eq!(stored.payload["name"], "Emerdale");
     }
 }
@@ -0,0 +1,65 @@
+//! `tracing` spans around requests and resolvers, recorded with the `otel` feature.
+//! Without it these are pass-throughs.
+
+use async_graphql::dynamic::Schema;
+use std::future::Future;
+
+/// Execute `request` against the graphlette at `path`, inside a `graphql.request` span.
+#[cfg(feature = "otel")]
+pub(crate) async fn execute(
+    schema: &Schema,
+    request: async_graphql::Request,
+    path: &str,
+) -> async_graphql::Response {
+    use tracing::Instrument;
+    let span = tracing::info_span!(
+        "graphql.request",
+        graphlette = %path,
+        operation = request.operation_name.as_deref().unwrap_or(""),
+    );
+    schema.execute(request).instrument(span).await
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) async fn execute(
+    schema: &Schema,
+    request: async_graphql::Request,
+    _path: &str,
+) -> async_graphql::Response {
+    schema.execute(request).await
+}
+
+/// Names the `graphql.resolver` span a relation field resolves in: the field,
+/// the query it runs and the graphlette (or URL) that query targets.
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+pub(crate) struct ResolverSpan {
+    field: String,
+    query: String,
+    target: String,
+}
+
+impl ResolverSpan {
+    pub(crate) fn new(field: &str, query: &str, target: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            query: query.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    #[cfg(feature = "otel")]
+    pub(crate) fn wrap<F: Future>(&self, future: F) -> tracing::instrument::Instrumented<F> {
+        use tracing::Instrument;
+        future.instrument(tracing::info_span!(
+            "graphql.resolver",
+            field = %self.field,
+            query = %self.query,
+            target_path = %self.target,
+        ))
+    }
+
+    #[cfg(not(feature = "otel"))]
+    pub(crate) fn wrap<F: Future>(&self, future: F) -> F {
+        future
+    }
+}
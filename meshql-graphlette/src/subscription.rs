@@ -0,0 +1,178 @@
+//! `Subscription` fields that re-run a configured query on an interval, and the
+//! Server-Sent Events route that streams their results.
+
+use crate::errors::graphql_error;
+use crate::get_request::{invalid_variables, GetParams};
+use crate::schema_builder::{
+    credentials, query_args, require_args, response_body, with_request_id,
+};
+use async_graphql::dynamic::{
+    FieldValue, Schema, SubscriptionField, SubscriptionFieldFuture, TypeRef,
+};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use chrono::Utc;
+use futures::StreamExt;
+use meshql_core::{Auth, QueryConfig, Searcher, Stash};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// How often a subscription polls when its [`meshql_core::RootConfig`] doesn't say.
+pub(crate) const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Subscription field over `query`, polled every `interval`.
+///
+/// The first event carries the current results. After that a vector query
+/// sends only the entities that are new or changed since the previous poll,
+/// and a singleton query sends its entity (or null) whenever it changes.
+/// Entities leaving a vector query's results aren't reported. A failed search
+/// ends the subscription with its error.
+pub(crate) fn subscription_field(
+    field_name: String,
+    type_ref: TypeRef,
+    query: &QueryConfig,
+    strict: bool,
+    searcher: Arc<dyn Searcher>,
+    interval: Duration,
+    id_args: Arc<HashSet<String>>,
+) -> SubscriptionField {
+    let template = query.template.clone();
+    let is_singleton = query.is_singleton;
+    SubscriptionField::new(field_name, type_ref, move |ctx| {
+        let searcher = Arc::clone(&searcher);
+        let template = template.clone();
+        let id_args = Arc::clone(&id_args);
+        SubscriptionFieldFuture::new(async move {
+            let (args, _) = query_args(&ctx, &id_args)?;
+            if strict {
+                require_args(&template, &args)?;
+            }
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let poll = Poll {
+                searcher,
+                template,
+                args,
+                creds: credentials(&ctx),
+                is_singleton,
+                ticks,
+                seen: None,
+                failed: false,
+            };
+            Ok(futures::stream::unfold(poll, Poll::next))
+        })
+    })
+}
+
+struct Poll {
+    searcher: Arc<dyn Searcher>,
+    template: String,
+    args: Stash,
+    creds: Vec<String>,
+    is_singleton: bool,
+    ticks: Interval,
+    /// Results of the previous poll by id, `None` before the first.
+    seen: Option<HashMap<String, Stash>>,
+    failed: bool,
+}
+
+impl Poll {
+    async fn next<'a>(mut self) -> Option<(async_graphql::Result<FieldValue<'a>>, Self)> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            self.ticks.tick().await;
+            let rows = match self.search().await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    self.failed = true;
+                    return Some((Err(e), self));
+                }
+            };
+
+            let first = self.seen.is_none();
+            let previous = self.seen.take().unwrap_or_default();
+            let current: HashMap<String, Stash> =
+                rows.iter().map(|row| (key(row), row.clone())).collect();
+            let changed: Vec<Stash> = rows
+                .into_iter()
+                .filter(|row| previous.get(&key(row)) != Some(row))
+                .collect();
+            let removed = previous.keys().any(|k| !current.contains_key(k));
+            self.seen = Some(current);
+
+            let value = if self.is_singleton {
+                if !first && changed.is_empty() && !removed {
+                    continue;
+                }
+                match changed.into_iter().next() {
+                    Some(row) => FieldValue::owned_any(row),
+                    None => FieldValue::NULL,
+                }
+            } else {
+                if !first && changed.is_empty() {
+                    continue;
+                }
+                FieldValue::list(changed.into_iter().map(FieldValue::owned_any))
+            };
+            return Some((Ok(value), self));
+        }
+    }
+
+    async fn search(&self) -> async_graphql::Result<Vec<Stash>> {
+        let at = Utc::now().timestamp_millis();
+        let found = if self.is_singleton {
+            self.searcher
+                .find(&self.template, &self.args, &self.creds, at)
+                .await
+                .map(|found| found.into_iter().collect())
+        } else {
+            self.searcher
+                .find_all(&self.template, &self.args, &self.creds, at)
+                .await
+        };
+        found.map_err(graphql_error)
+    }
+}
+
+/// Identifies a result row across polls.
+fn key(row: &Stash) -> String {
+    match row.get("id") {
+        Some(serde_json::Value::String(id)) => id.clone(),
+        _ => serde_json::Value::Object(row.clone()).to_string(),
+    }
+}
+
+/// Execute a subscription, sent in the query string as `EventSource` can only
+/// `GET`, and send each response as an SSE `data` event in the same
+/// `{data, errors}` shape as a `POST`.
+pub(crate) async fn stream(
+    schema: Arc<Schema>,
+    auth: Arc<dyn Auth>,
+    headers: HeaderMap,
+    params: GetParams,
+) -> Response {
+    let creds = match auth.authorize(&headers).await {
+        Ok(creds) => creds,
+        Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
+    };
+    let request = match params.into_request() {
+        Ok(request) => with_request_id(request, &headers),
+        Err(e) => return invalid_variables(e),
+    };
+    // No BatchLoader: it pins one `at` for the request's lifetime, which
+    // would freeze relations on every later event.
+    let request = request.data(crate::Credentials(creds));
+    let events = schema
+        .execute_stream(request)
+        .map(|response| Event::default().json_data(response_body(response)))
+        .map(|event| Ok::<_, Infallible>(event.unwrap_or_default()));
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
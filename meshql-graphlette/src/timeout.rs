@@ -0,0 +1,139 @@
+//! Abandons operations still running after [`meshql_core::RootConfig::timeout`].
+//!
+//! The whole execution is dropped when the deadline passes, so a searcher call
+//! stuck on a slow backend is cancelled with it and gives its connection back.
+
+use crate::errors::graphql_error;
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextRequest};
+use async_graphql::Response;
+use meshql_core::MeshqlError;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Answers an operation running longer than `limit` with a single error
+/// whose `extensions.code` is `TIMEOUT`.
+pub(crate) struct Deadline {
+    limit: Duration,
+}
+
+impl Deadline {
+    pub(crate) fn new(limit: Duration) -> Self {
+        Self { limit }
+    }
+}
+
+impl ExtensionFactory for Deadline {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(Deadline { limit: self.limit })
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for Deadline {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        match tokio::time::timeout(self.limit, next.run(ctx)).await {
+            Ok(response) => response,
+            Err(_) => {
+                let e = MeshqlError::Timeout(format!(
+                    "the operation ran longer than {}ms",
+                    self.limit.as_millis()
+                ));
+                Response::from_errors(vec![graphql_error(e).into_server_error(Default::default())])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{build_schema, ResolverRegistry};
+    use meshql_core::{RootConfig, Searcher, Stash};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Takes a minute to find anything.
+    struct Stuck;
+
+    #[async_trait::async_trait]
+    impl Searcher for Stuck {
+        async fn find(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Option<Stash>> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(None)
+        }
+
+        async fn find_all(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<Vec<Stash>> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(Vec::new())
+        }
+
+        async fn count(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<u64> {
+            Ok(0)
+        }
+
+        async fn exists(
+            &self,
+            _template: &str,
+            _args: &Stash,
+            _creds: &[String],
+            _at: i64,
+        ) -> meshql_core::Result<bool> {
+            Ok(false)
+        }
+
+        async fn ping(&self) -> meshql_core::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn operations_past_the_deadline_fail_with_timeout() {
+        let root_config = RootConfig::builder()
+            .vector("getFarms", "{}")
+            .timeout(Duration::from_millis(50))
+            .build();
+        let schema = build_schema(
+            r#"
+                type Farm {
+                    id: ID
+                    name: String
+                }
+                type Query {
+                    getFarms(at: Int): [Farm]
+                }
+            "#,
+            &root_config,
+            Arc::new(Stuck),
+            &ResolverRegistry::new(),
+        )
+        .unwrap();
+
+        let started = Instant::now();
+        let response = schema.execute("{ getFarms { name } }").await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(response.errors.len(), 1, "{:?}", response.errors);
+        let code = response.errors[0]
+            .extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"));
+        assert_eq!(code, Some(&async_graphql::Value::from("TIMEOUT")));
+        assert_eq!(response.data, async_graphql::Value::Null);
+    }
+}
@@ -0,0 +1,34 @@
+//! Files uploaded with a multipart request, into mutation inputs declaring
+//! `Upload` fields. The SDL opts in with `scalar Upload`.
+
+use crate::schema_builder::base64_encode;
+use async_graphql::dynamic::ResolverContext;
+use meshql_core::Stash;
+use serde_json::Value;
+use std::io::Read;
+
+/// Replace the `upload_fields` present in `payload` with their files' contents,
+/// base64-encoded.
+pub(crate) fn read_fields(
+    ctx: &ResolverContext,
+    payload: &mut Stash,
+    upload_fields: &[String],
+) -> async_graphql::Result<()> {
+    if upload_fields.is_empty() {
+        return Ok(());
+    }
+    let input = ctx.args.try_get("input")?.object()?;
+    for field in upload_fields {
+        let Some(value) = input.get(field).filter(|v| !v.is_null()) else {
+            continue;
+        };
+        let mut content = Vec::new();
+        value
+            .upload()?
+            .value(ctx.ctx)?
+            .into_read()
+            .read_to_end(&mut content)?;
+        payload.insert(field.clone(), Value::String(base64_encode(&content)));
+    }
+    Ok(())
+}
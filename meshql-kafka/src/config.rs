@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::time::Duration;
+
+/// Connection settings for [`crate::KafkaRepository`].
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    pub bootstrap_servers: String,
+    /// Extra librdkafka properties for every client, e.g. `security.protocol`
+    /// or `sasl.username`.
+    pub properties: BTreeMap<String, String>,
+    /// Partitions of the topics a repository creates. Existing topics are
+    /// used as they are.
+    pub partitions: i32,
+    pub replication_factor: i32,
+    /// Longest a produce or metadata request may take.
+    pub timeout: Duration,
+}
+
+impl KafkaConfig {
+    pub fn new(bootstrap_servers: impl Into<String>) -> Self {
+        Self {
+            bootstrap_servers: bootstrap_servers.into(),
+            properties: BTreeMap::new(),
+            partitions: 1,
+            replication_factor: 1,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, env::VarError> {
+        Ok(Self::new(env::var("KAFKA_BOOTSTRAP_SERVERS")?))
+    }
+
+    pub fn property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+
+    /// Derive the Kafka topic name from an entity name.
+    pub fn topic_name(entity: &str) -> String {
+        entity.to_string()
+    }
+}
@@ -0,0 +1,25 @@
+//! A [`meshql_core::Repository`] that talks to Kafka directly, rather than
+//! through the REST Proxy and ksqlDB as `meshql-ksql` does.
+//!
+//! Each entity is a compacted topic. Every version of an envelope is a record
+//! keyed by its id, in the same JSON value format `meshql-ksql` produces, so
+//! the two crates can share a topic.
+//!
+//! Reads never go to the broker. Each repository reads its topic from the
+//! beginning into memory, keeping the versions of each id in `created_at`
+//! order, and carries on reading in the background. [`KafkaRepository::new`]
+//! returns once it has read everything already in the topic. A write is added
+//! to memory as soon as the broker acknowledges it, so a repository reads its
+//! own writes at once; writes by other processes show up once consumed.
+//!
+//! Compaction keeps only the newest record of each id, so `history` and reads
+//! `at` an earlier time see only the versions compaction hasn't yet removed.
+//! Removal writes a version marked deleted rather than a null record, so a
+//! removed id stays removed after compaction.
+
+mod config;
+mod repository;
+mod view;
+
+pub use config::KafkaConfig;
+pub use repository::KafkaRepository;
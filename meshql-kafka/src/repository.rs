@@ -0,0 +1,385 @@
+use crate::config::KafkaConfig;
+use crate::view::{self, View};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use meshql_core::{
+    forbid_hidden, Clock, Envelope, IdStrategy, ListOptions, MeshqlError, Repository, Result,
+    SystemClock,
+};
+use meshql_ksql::converters::envelope_to_kafka_value;
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::error::RDKafkaErrorCode;
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+fn storage(e: impl std::fmt::Display) -> MeshqlError {
+    MeshqlError::Storage(e.to_string())
+}
+
+pub struct KafkaRepository {
+    producer: FutureProducer,
+    topic: String,
+    timeout: Duration,
+    view: Arc<View>,
+    consumer: JoinHandle<()>,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+}
+
+impl KafkaRepository {
+    /// Create the compacted topic for `entity` unless it exists, then read it
+    /// from the beginning into memory. Returns once everything already in the
+    /// topic has been read, and keeps reading in the background.
+    pub async fn new(config: &KafkaConfig, entity: &str) -> Result<Self> {
+        let topic = KafkaConfig::topic_name(entity);
+        let mut client = ClientConfig::new();
+        client.set("bootstrap.servers", &config.bootstrap_servers);
+        for (key, value) in &config.properties {
+            client.set(key, value);
+        }
+
+        Self::create_topic(&client, &topic, config).await?;
+
+        let producer: FutureProducer = client
+            .clone()
+            .set("enable.idempotence", "true")
+            .create()
+            .map_err(storage)?;
+        let consumer: StreamConsumer = client
+            .clone()
+            .set(
+                "group.id",
+                format!("meshql-{topic}-{}", uuid::Uuid::new_v4()),
+            )
+            .set("enable.auto.commit", "false")
+            .set("enable.partition.eof", "false")
+            .create()
+            .map_err(storage)?;
+        let consumer = Arc::new(consumer);
+
+        let ends = Self::assign_from_beginning(&consumer, &topic, config.timeout).await?;
+        let view = Arc::new(View::default());
+        let consume = tokio::spawn(Self::consume(Arc::clone(&consumer), Arc::clone(&view)));
+        view.caught_up(&ends).await;
+
+        Ok(Self {
+            producer,
+            topic,
+            timeout: config.timeout,
+            view,
+            consumer: consume,
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    async fn create_topic(client: &ClientConfig, topic: &str, config: &KafkaConfig) -> Result<()> {
+        let admin: AdminClient<DefaultClientContext> = client.create().map_err(storage)?;
+        let new_topic = NewTopic::new(
+            topic,
+            config.partitions,
+            TopicReplication::Fixed(config.replication_factor),
+        )
+        .set("cleanup.policy", "compact");
+        let options = AdminOptions::new().operation_timeout(Some(config.timeout));
+        for result in admin
+            .create_topics([&new_topic], &options)
+            .await
+            .map_err(storage)?
+        {
+            match result {
+                Ok(_) | Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => {}
+                Err((name, code)) => return Err(storage(format!("creating {name}: {code}"))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Assign every partition of `topic` from its first offset, and return the
+    /// offset each partition currently ends at.
+    async fn assign_from_beginning(
+        consumer: &Arc<StreamConsumer>,
+        topic: &str,
+        timeout: Duration,
+    ) -> Result<HashMap<i32, i64>> {
+        let consumer = Arc::clone(consumer);
+        let topic = topic.to_string();
+        // Metadata and watermark lookups block, so keep them off the runtime.
+        tokio::task::spawn_blocking(move || {
+            let metadata = consumer
+                .fetch_metadata(Some(&topic), timeout)
+                .map_err(storage)?;
+            let partitions: Vec<i32> = metadata
+                .topics()
+                .iter()
+                .flat_map(|t| t.partitions())
+                .map(|p| p.id())
+                .collect();
+
+            let mut assignment = TopicPartitionList::new();
+            let mut ends = HashMap::new();
+            for partition in partitions {
+                assignment
+                    .add_partition_offset(&topic, partition, Offset::Beginning)
+                    .map_err(storage)?;
+                let (_, high) = consumer
+                    .fetch_watermarks(&topic, partition, timeout)
+                    .map_err(storage)?;
+                ends.insert(partition, high);
+            }
+            consumer.assign(&assignment).map_err(storage)?;
+            Ok(ends)
+        })
+        .await
+        .map_err(storage)?
+    }
+
+    async fn consume(consumer: Arc<StreamConsumer>, view: Arc<View>) {
+        loop {
+            let message = match consumer.recv().await {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Kafka consume error: {}", e);
+                    continue;
+                }
+            };
+            let key = message.key().map(String::from_utf8_lossy);
+            match (key, message.payload()) {
+                (Some(id), Some(value)) => match view::decode(&id, value) {
+                    Ok(env) => view.apply(env),
+                    Err(e) => warn!("Skipping unreadable record for {}: {}", id, e),
+                },
+                (Some(id), None) => view.forget(&id),
+                (None, _) => {}
+            }
+            view.consumed(message.partition(), message.offset());
+        }
+    }
+
+    /// Produce `env` keyed by its id, and once Kafka has it, add it to the view
+    /// so this repository reads its own writes straight away.
+    async fn put(&self, env: &Envelope) -> Result<()> {
+        let value = envelope_to_kafka_value(env).to_string();
+        let record = FutureRecord::to(&self.topic).key(&env.id).payload(&value);
+        self.producer
+            .send(record, self.timeout)
+            .await
+            .map_err(|(e, _)| storage(e))?;
+        self.view.apply(env.clone());
+        Ok(())
+    }
+}
+
+impl Drop for KafkaRepository {
+    fn drop(&mut self) {
+        self.consumer.abort();
+    }
+}
+
+#[async_trait]
+impl Repository for KafkaRepository {
+    async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+        self.put(&env).await?;
+        Ok(env)
+    }
+
+    async fn read(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .view
+            .read(
+                id,
+                at.unwrap_or_else(|| self.clock.now() + chrono::Duration::milliseconds(1)),
+            )
+            .filter(|env| view::is_visible(env, tokens)))
+    }
+
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        let listed = self
+            .view
+            .latest()
+            .into_iter()
+            .filter(|env| options.lists(env.deleted) && view::is_visible(env, tokens))
+            .collect();
+        Ok(options.page(listed))
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        Ok(self
+            .view
+            .history(id)
+            .into_iter()
+            .filter(|env| view::is_visible(env, tokens))
+            .collect())
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        Ok(self
+            .view
+            .created_between(from, to)
+            .into_iter()
+            .filter(|env| view::is_visible(env, tokens))
+            .collect())
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
+        let current = self.read(id, tokens, None).await?;
+        match current {
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
+            Some(env) => {
+                let deleted_env = Envelope {
+                    id: env.id,
+                    payload: env.payload,
+                    created_at: self.clock.now(),
+                    deleted: true,
+                    authorized_tokens: env.authorized_tokens,
+                };
+                self.create(deleted_env, tokens).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Writes a null-valued record for `id`, which log compaction takes as the
+    /// cue to drop every earlier record with that key, whoever could see them.
+    /// Until compaction runs they stay on the broker, though no repository
+    /// reads them back.
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let purged = self.history(id, tokens).await?.len() as u64;
+        if purged == 0 {
+            return Ok(0);
+        }
+        let record = FutureRecord::<str, [u8]>::to(&self.topic).key(id);
+        self.producer
+            .send(record, self.timeout)
+            .await
+            .map_err(|(e, _)| storage(e))?;
+        self.view.forget(id);
+        Ok(purged)
+    }
+
+    async fn create_many(
+        &self,
+        envelopes: Vec<Envelope>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let mut results = Vec::with_capacity(envelopes.len());
+        for mut env in envelopes {
+            if env.id.is_empty() {
+                env.id = self.ids.generate();
+            }
+            env.authorized_tokens = tokens.to_vec();
+            results.push(env);
+        }
+
+        // The producer batches records sent together, so send them all at once.
+        futures::future::try_join_all(results.iter().map(|env| self.put(env))).await?;
+        Ok(results)
+    }
+
+    async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let mut results = Vec::new();
+        for id in ids {
+            if let Some(env) = self.read(id, tokens, None).await? {
+                results.push(env);
+            }
+        }
+        Ok(results)
+    }
+
+    async fn remove_many(
+        &self,
+        ids: &[String],
+        tokens: &[String],
+    ) -> Result<HashMap<String, bool>> {
+        let current = self.read_many(ids, tokens).await?;
+        let now = self.clock.now();
+        let mut removed = HashSet::new();
+        let tombstones: Vec<Envelope> = current
+            .into_iter()
+            .filter(|env| removed.insert(env.id.clone()))
+            .map(|env| Envelope {
+                created_at: now,
+                deleted: true,
+                authorized_tokens: tokens.to_vec(),
+                ..env
+            })
+            .collect();
+
+        futures::future::try_join_all(tombstones.iter().map(|env| self.put(env))).await?;
+
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        let producer = self.producer.clone();
+        let topic = self.topic.clone();
+        let timeout = self.timeout;
+        tokio::task::spawn_blocking(move || {
+            producer
+                .client()
+                .fetch_metadata(Some(&topic), timeout)
+                .map(|_| ())
+                .map_err(storage)
+        })
+        .await
+        .map_err(storage)?
+    }
+}
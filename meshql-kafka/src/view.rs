@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use meshql_core::{Envelope, MeshqlError, Result};
+use meshql_ksql::converters::row_to_envelope;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use tokio::sync::Notify;
+
+/// The versions of each id read from a topic, kept in `created_at` order.
+#[derive(Default)]
+pub(crate) struct View {
+    versions: RwLock<HashMap<String, Vec<Envelope>>>,
+    /// The next offset to read from each partition.
+    positions: Mutex<HashMap<i32, i64>>,
+    advanced: Notify,
+}
+
+impl View {
+    /// Add `env` as a version of its id. A version with the same `created_at`
+    /// is replaced, so a record applied both on produce and on consume is kept once.
+    pub(crate) fn apply(&self, env: Envelope) {
+        let mut versions = self.versions.write().unwrap_or_else(|e| e.into_inner());
+        let versions = versions.entry(env.id.clone()).or_default();
+        let created_at = env.created_at.timestamp_millis();
+        match versions.binary_search_by_key(&created_at, |v| v.created_at.timestamp_millis()) {
+            Ok(i) => versions[i] = env,
+            Err(i) => versions.insert(i, env),
+        }
+    }
+
+    /// Forget `id`, for a null-valued record compaction leaves behind.
+    pub(crate) fn forget(&self, id: &str) {
+        let mut versions = self.versions.write().unwrap_or_else(|e| e.into_inner());
+        versions.remove(id);
+    }
+
+    /// Record that `partition` has been read up to and including `offset`.
+    pub(crate) fn consumed(&self, partition: i32, offset: i64) {
+        let mut positions = self.positions.lock().unwrap_or_else(|e| e.into_inner());
+        positions.insert(partition, offset + 1);
+        self.advanced.notify_waiters();
+    }
+
+    /// Wait until each partition has been read up to its offset in `ends`.
+    pub(crate) async fn caught_up(&self, ends: &HashMap<i32, i64>) {
+        loop {
+            let advanced = self.advanced.notified();
+            {
+                let positions = self.positions.lock().unwrap_or_else(|e| e.into_inner());
+                let behind = ends
+                    .iter()
+                    .any(|(p, end)| positions.get(p).copied().unwrap_or(0) < *end);
+                if !behind {
+                    return;
+                }
+            }
+            advanced.await;
+        }
+    }
+
+    /// The newest version of `id` created at or before `at`.
+    pub(crate) fn read(&self, id: &str, at: DateTime<Utc>) -> Option<Envelope> {
+        let cutoff_ms = at.timestamp_millis();
+        let versions = self.versions.read().unwrap_or_else(|e| e.into_inner());
+        versions
+            .get(id)?
+            .iter()
+            .rev()
+            .find(|v| v.created_at.timestamp_millis() <= cutoff_ms)
+            .cloned()
+    }
+
+    /// The newest version of every id, deleted or not.
+    pub(crate) fn latest(&self) -> Vec<Envelope> {
+        let versions = self.versions.read().unwrap_or_else(|e| e.into_inner());
+        versions
+            .values()
+            .filter_map(|v| v.last())
+            .cloned()
+            .collect()
+    }
+
+    /// Every version of `id` still in the topic, oldest first.
+    pub(crate) fn history(&self, id: &str) -> Vec<Envelope> {
+        let versions = self.versions.read().unwrap_or_else(|e| e.into_inner());
+        versions.get(id).cloned().unwrap_or_default()
+    }
+
+    /// Every version of every id still in the topic created from `from` up to
+    /// but not including `to`, oldest first.
+    pub(crate) fn created_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Envelope> {
+        let window = from.timestamp_millis()..to.timestamp_millis();
+        let versions = self.versions.read().unwrap_or_else(|e| e.into_inner());
+        let mut found: Vec<Envelope> = versions
+            .values()
+            .flatten()
+            .filter(|v| window.contains(&v.created_at.timestamp_millis()))
+            .cloned()
+            .collect();
+        found.sort_by_key(|v| v.created_at.timestamp_millis());
+        found
+    }
+}
+
+/// The envelope a record keyed by `id` holds, in the
+/// [`envelope_to_kafka_value`](meshql_ksql::converters::envelope_to_kafka_value) format.
+pub(crate) fn decode(id: &str, value: &[u8]) -> Result<Envelope> {
+    let mut row: HashMap<String, Value> =
+        serde_json::from_slice(value).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+    row.insert("id".to_string(), Value::String(id.to_string()));
+    row_to_envelope(&row).map_err(|e| MeshqlError::Parse(e.to_string()))
+}
+
+/// Whether a caller holding `tokens` may see `env`. Envelopes stored with `*`
+/// are visible to everyone, and a caller holding `*` sees everything.
+pub(crate) fn is_visible(env: &Envelope, tokens: &[String]) -> bool {
+    tokens.iter().any(|t| t == "*")
+        || env
+            .authorized_tokens
+            .iter()
+            .any(|t| t == "*" || tokens.contains(t))
+}
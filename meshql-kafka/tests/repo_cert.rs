@@ -0,0 +1,86 @@
+//! Runs against the broker in `KAFKA_BOOTSTRAP_SERVERS`, and is skipped
+//! without one.
+
+use meshql_core::testing as cert;
+use meshql_core::{Envelope, Repository, Stash};
+use meshql_kafka::{KafkaConfig, KafkaRepository};
+
+fn config() -> Option<KafkaConfig> {
+    match KafkaConfig::from_env() {
+        Ok(config) => Some(config),
+        Err(_) => {
+            eprintln!("Skipping kafka repo cert tests: KAFKA_BOOTSTRAP_SERVERS not set");
+            None
+        }
+    }
+}
+
+fn topic() -> String {
+    format!("cert_{}", uuid::Uuid::new_v4().simple())
+}
+
+#[tokio::test]
+async fn create_should_store_and_return_envelope() {
+    let Some(config) = config() else { return };
+    let repo = KafkaRepository::new(&config, &topic()).await.unwrap();
+    cert::test_create_should_store_and_return_envelope(&repo).await;
+}
+
+#[tokio::test]
+async fn read_should_retrieve_existing_envelope() {
+    let Some(config) = config() else { return };
+    let repo = KafkaRepository::new(&config, &topic()).await.unwrap();
+    cert::test_read_should_retrieve_existing_envelope(&repo).await;
+}
+
+#[tokio::test]
+async fn list_should_retrieve_all_created_envelopes() {
+    let Some(config) = config() else { return };
+    let repo = KafkaRepository::new(&config, &topic()).await.unwrap();
+    cert::test_list_should_retrieve_all_created_envelopes(&repo).await;
+}
+
+#[tokio::test]
+async fn remove_should_delete_envelope() {
+    let Some(config) = config() else { return };
+    let repo = KafkaRepository::new(&config, &topic()).await.unwrap();
+    cert::test_remove_should_delete_envelope(&repo).await;
+}
+
+#[tokio::test]
+async fn upsert_should_skip_unchanged_payloads() {
+    let Some(config) = config() else { return };
+    let repo = KafkaRepository::new(&config, &topic()).await.unwrap();
+    cert::test_upsert_skips_unchanged_payloads(&repo).await;
+}
+
+#[tokio::test]
+async fn purge_should_delete_every_version() {
+    let Some(config) = config() else { return };
+    let repo = KafkaRepository::new(&config, &topic()).await.unwrap();
+    cert::test_purge_deletes_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn a_new_repository_reads_the_topic_back() {
+    let Some(config) = config() else { return };
+    let topic = topic();
+    let star = vec!["*".to_string()];
+    {
+        let repo = KafkaRepository::new(&config, &topic).await.unwrap();
+        for id in ["kept", "removed"] {
+            let mut payload = Stash::new();
+            payload.insert("name".to_string(), serde_json::json!(id));
+            repo.create(Envelope::new(id, payload, star.clone()), &star)
+                .await
+                .unwrap();
+        }
+        assert!(repo.remove("removed", &star).await.unwrap());
+    }
+
+    let repo = KafkaRepository::new(&config, &topic).await.unwrap();
+    let listed = repo.list(&star).await.unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, "kept");
+    assert!(repo.read("removed", &star, None).await.unwrap().is_none());
+}
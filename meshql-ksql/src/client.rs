@@ -1,12 +1,59 @@
+use async_trait::async_trait;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder, Response};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use tracing::{debug, error, warn};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{debug, error, info_span, warn, Instrument, Span};
 
 use crate::config::KsqlConfig;
 
+/// What a pull query came back with.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pulled {
+    Rows(Vec<HashMap<String, Value>>),
+    /// ksqlDB can't serve the table yet, e.g. just after `CREATE TABLE`, as
+    /// opposed to the table holding no matching rows.
+    NotReady,
+}
+
+/// Header carrying the id generated for each request to Confluent, which
+/// its span records too, so a slow call can be found in the server's logs.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Spans around each call, at `info` under this target, record the
+/// statement or topic, the request id, the HTTP status and the duration.
+pub const SPAN_TARGET: &str = "meshql::ksql";
+
+/// Records [`ConfluentClient::produce_records`] sends per request unless
+/// [`ConfluentClient::with_max_batch_records`] says otherwise.
+pub const MAX_BATCH_RECORDS: usize = 100;
+
+/// Runs pull queries, so the polling around them can be tested without ksqlDB.
+#[async_trait]
+pub trait PullQuery: Send + Sync {
+    async fn pull(&self, ksql: &str) -> anyhow::Result<Pulled>;
+}
+
+/// The first entry a batch produce response reports as failed, if any. The
+/// endpoint answers `207 Multi-Status`, with each entry's outcome under
+/// `successes` or `failures`.
+fn batch_failure(body: &str) -> Option<String> {
+    let response: Value = serde_json::from_str(body).ok()?;
+    response["failures"]
+        .as_array()
+        .and_then(|failures| failures.first())
+        .map(Value::to_string)
+}
+
+/// Whether the body of a failed pull query says the table is still warming
+/// up, rather than that the query itself is at fault.
+fn is_not_ready(body: &str) -> bool {
+    body.contains("not available yet") || body.contains("Cannot determine which host")
+}
+
 /// HTTP client for Confluent Cloud Kafka REST API v3 and ksqlDB REST API.
 #[derive(Clone)]
 pub struct ConfluentClient {
@@ -16,6 +63,7 @@ pub struct ConfluentClient {
     kafka_auth: String,
     ksqldb_url: String,
     ksqldb_auth: String,
+    max_batch_records: usize,
 }
 
 impl ConfluentClient {
@@ -39,9 +87,17 @@ impl ConfluentClient {
             kafka_auth,
             ksqldb_url,
             ksqldb_auth,
+            max_batch_records: MAX_BATCH_RECORDS,
         }
     }
 
+    /// Send at most `max` records per batched produce request, rather than
+    /// [`MAX_BATCH_RECORDS`].
+    pub fn with_max_batch_records(mut self, max: usize) -> Self {
+        self.max_batch_records = max.max(1);
+        self
+    }
+
     /// Produce a record to a Kafka topic via REST API v3.
     pub async fn produce_record(
         &self,
@@ -49,35 +105,135 @@ impl ConfluentClient {
         key: &str,
         value: &Value,
     ) -> anyhow::Result<()> {
+        self.produce(topic, key, Some(value)).await
+    }
+
+    /// Produce `records`, as `(key, value)` pairs, via the REST API v3 batch
+    /// endpoint: one request per [`ConfluentClient::with_max_batch_records`]
+    /// records, in order. Fails if any record in a batch is rejected, leaving
+    /// the batch's other records, and those of earlier batches, produced.
+    pub async fn produce_records(
+        &self,
+        topic: &str,
+        records: Vec<(String, Value)>,
+    ) -> anyhow::Result<()> {
+        let url = format!(
+            "{}/kafka/v3/clusters/{}/topics/{}/records:batch",
+            self.kafka_rest_url, self.kafka_cluster_id, topic
+        );
+
+        for batch in records.chunks(self.max_batch_records) {
+            let entries: Vec<Value> = batch
+                .iter()
+                .enumerate()
+                .map(|(i, (key, value))| {
+                    json!({
+                        "id": i.to_string(),
+                        "key": { "type": "STRING", "data": key },
+                        "value": { "type": "JSON", "data": value },
+                    })
+                })
+                .collect();
+            let body = json!({ "entries": entries });
+
+            let span = info_span!(
+                target: SPAN_TARGET,
+                "produce_records",
+                topic,
+                records = batch.len(),
+                request_id = Empty,
+                status = Empty,
+                duration_ms = Empty,
+            );
+            async {
+                debug!("Producing {} records to {}", batch.len(), topic);
+
+                let resp = send(
+                    self.http
+                        .post(&url)
+                        .header("Content-Type", "application/json")
+                        .header("Authorization", format!("Basic {}", self.kafka_auth))
+                        .json(&body),
+                )
+                .await?;
+
+                let status = resp.status();
+                let body_text = resp.text().await.unwrap_or_default();
+                if !status.is_success() {
+                    error!(
+                        "Kafka REST batch produce failed ({}): {}",
+                        status, body_text
+                    );
+                    anyhow::bail!(
+                        "Kafka REST batch produce failed ({}): {}",
+                        status,
+                        body_text
+                    );
+                }
+                if let Some(failure) = batch_failure(&body_text) {
+                    error!("Kafka REST batch produce rejected a record: {}", failure);
+                    anyhow::bail!("Kafka REST batch produce rejected a record: {}", failure);
+                }
+
+                Ok(())
+            }
+            .instrument(span)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Produce a null-valued record for `key`, which compaction takes as the
+    /// cue to drop every earlier record with that key.
+    pub async fn produce_tombstone(&self, topic: &str, key: &str) -> anyhow::Result<()> {
+        self.produce(topic, key, None).await
+    }
+
+    async fn produce(&self, topic: &str, key: &str, value: Option<&Value>) -> anyhow::Result<()> {
         let url = format!(
             "{}/kafka/v3/clusters/{}/topics/{}/records",
             self.kafka_rest_url, self.kafka_cluster_id, topic
         );
 
-        let body = json!({
+        let mut body = json!({
             "key": { "type": "STRING", "data": key },
-            "value": { "type": "JSON", "data": value },
         });
+        if let Some(value) = value {
+            body["value"] = json!({ "type": "JSON", "data": value });
+        }
 
-        debug!("Producing to {}: key={}", topic, key);
-
-        let resp = self
-            .http
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Basic {}", self.kafka_auth))
-            .json(&body)
-            .send()
+        let span = info_span!(
+            target: SPAN_TARGET,
+            "produce_record",
+            topic,
+            key,
+            request_id = Empty,
+            status = Empty,
+            duration_ms = Empty,
+        );
+        async {
+            debug!("Producing to {}: key={}", topic, key);
+
+            let resp = send(
+                self.http
+                    .post(&url)
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", format!("Basic {}", self.kafka_auth))
+                    .json(&body),
+            )
             .await?;
 
-        let status = resp.status();
-        if !status.is_success() {
-            let body_text = resp.text().await.unwrap_or_default();
-            error!("Kafka REST produce failed ({}): {}", status, body_text);
-            anyhow::bail!("Kafka REST produce failed ({}): {}", status, body_text);
-        }
+            let status = resp.status();
+            if !status.is_success() {
+                let body_text = resp.text().await.unwrap_or_default();
+                error!("Kafka REST produce failed ({}): {}", status, body_text);
+                anyhow::bail!("Kafka REST produce failed ({}): {}", status, body_text);
+            }
 
-        Ok(())
+            Ok(())
+        }
+        .instrument(span)
+        .await
     }
 
     /// Execute a ksqlDB DDL statement (CREATE STREAM, CREATE TABLE, etc.).
@@ -89,73 +245,76 @@ impl ConfluentClient {
             "streamsProperties": {}
         });
 
-        debug!("Executing ksqlDB statement: {}", ksql);
-
-        let resp = self
-            .http
-            .post(&url)
-            .header("Content-Type", "application/vnd.ksql.v1+json")
-            .header("Accept", "application/vnd.ksql.v1+json")
-            .header("Authorization", format!("Basic {}", self.ksqldb_auth))
-            .json(&body)
-            .send()
+        let span = info_span!(
+            target: SPAN_TARGET,
+            "execute_statement",
+            statement = ksql,
+            table = relation(ksql),
+            request_id = Empty,
+            status = Empty,
+            duration_ms = Empty,
+        );
+        async {
+            debug!("Executing ksqlDB statement: {}", ksql);
+
+            let resp = send(
+                self.http
+                    .post(&url)
+                    .header("Content-Type", "application/vnd.ksql.v1+json")
+                    .header("Accept", "application/vnd.ksql.v1+json")
+                    .header("Authorization", format!("Basic {}", self.ksqldb_auth))
+                    .json(&body),
+            )
             .await?;
 
-        let status = resp.status();
-        if status.as_u16() >= 400 {
-            let body_text = resp.text().await.unwrap_or_default();
-            error!("ksqlDB statement failed ({}): {}", status, body_text);
-            anyhow::bail!("ksqlDB statement failed ({}): {}", status, body_text);
-        }
+            let status = resp.status();
+            if status.as_u16() >= 400 {
+                let body_text = resp.text().await.unwrap_or_default();
+                error!("ksqlDB statement failed ({}): {}", status, body_text);
+                anyhow::bail!("ksqlDB statement failed ({}): {}", status, body_text);
+            }
 
-        debug!("ksqlDB statement succeeded: {}", status);
-        Ok(())
+            debug!("ksqlDB statement succeeded: {}", status);
+            Ok(())
+        }
+        .instrument(span)
+        .await
     }
 
-    /// Execute a ksqlDB pull query, returning parsed rows.
+    /// Execute a ksqlDB pull query, returning parsed rows, or none while the
+    /// table isn't ready. See [`PullQuery::pull`] to tell the two apart.
     pub async fn pull_query(&self, ksql: &str) -> anyhow::Result<Vec<HashMap<String, Value>>> {
-        let url = format!("{}/query", self.ksqldb_url);
-
-        let body = json!({
-            "ksql": ksql,
-            "streamsProperties": {}
-        });
+        match self.pull(ksql).await? {
+            Pulled::Rows(rows) => Ok(rows),
+            Pulled::NotReady => Ok(Vec::new()),
+        }
+    }
 
-        debug!("Executing ksqlDB query: {}", ksql);
+    /// Check the ksqlDB server is reachable and accepts our credentials.
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        let url = format!("{}/info", self.ksqldb_url);
 
         let resp = self
             .http
-            .post(&url)
-            .header("Content-Type", "application/vnd.ksql.v1+json")
+            .get(&url)
             .header("Accept", "application/vnd.ksql.v1+json")
             .header("Authorization", format!("Basic {}", self.ksqldb_auth))
-            .json(&body)
             .send()
             .await?;
 
         let status = resp.status();
-        if status.as_u16() >= 400 {
-            let body_text = resp.text().await.unwrap_or_default();
-            // Return empty for "not ready yet" errors — caller can retry
-            if body_text.contains("not available yet")
-                || body_text.contains("Cannot determine which host")
-            {
-                debug!("ksqlDB query not ready yet: {}", body_text);
-                return Ok(Vec::new());
-            }
-            error!("ksqlDB query failed ({}): {}", status, body_text);
-            anyhow::bail!("ksqlDB query failed ({}): {}", status, body_text);
+        if !status.is_success() {
+            anyhow::bail!("ksqlDB info request failed ({})", status);
         }
-
-        let body_text = resp.text().await?;
-        parse_query_response(&body_text)
+        Ok(())
     }
 
     /// Check if a ksqlDB table is ready for pull queries.
     pub async fn is_table_ready(&self, table_name: &str) -> bool {
         let ksql = format!("SELECT * FROM {} LIMIT 1;", table_name);
-        match self.pull_query(&ksql).await {
-            Ok(_) => true,
+        match self.pull(&ksql).await {
+            Ok(Pulled::Rows(_)) => true,
+            Ok(Pulled::NotReady) => false,
             Err(e) => {
                 warn!("Table {} not ready: {}", table_name, e);
                 false
@@ -164,6 +323,85 @@ impl ConfluentClient {
     }
 }
 
+#[async_trait]
+impl PullQuery for ConfluentClient {
+    async fn pull(&self, ksql: &str) -> anyhow::Result<Pulled> {
+        let url = format!("{}/query", self.ksqldb_url);
+
+        let body = json!({
+            "ksql": ksql,
+            "streamsProperties": {}
+        });
+
+        let span = info_span!(
+            target: SPAN_TARGET,
+            "pull_query",
+            statement = ksql,
+            table = relation(ksql),
+            request_id = Empty,
+            status = Empty,
+            duration_ms = Empty,
+        );
+        async {
+            debug!("Executing ksqlDB query: {}", ksql);
+
+            let resp = send(
+                self.http
+                    .post(&url)
+                    .header("Content-Type", "application/vnd.ksql.v1+json")
+                    .header("Accept", "application/vnd.ksql.v1+json")
+                    .header("Authorization", format!("Basic {}", self.ksqldb_auth))
+                    .json(&body),
+            )
+            .await?;
+
+            let status = resp.status();
+            if status.as_u16() >= 400 {
+                let body_text = resp.text().await.unwrap_or_default();
+                if is_not_ready(&body_text) {
+                    debug!("ksqlDB query not ready yet: {}", body_text);
+                    return Ok(Pulled::NotReady);
+                }
+                error!("ksqlDB query failed ({}): {}", status, body_text);
+                anyhow::bail!("ksqlDB query failed ({}): {}", status, body_text);
+            }
+
+            let body_text = resp.text().await?;
+            parse_query_response(&body_text).map(Pulled::Rows)
+        }
+        .instrument(span)
+        .await
+    }
+}
+
+/// Send `request` with a fresh [`REQUEST_ID_HEADER`], recording the id, the
+/// response's status and how long it took on the current span.
+async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let span = Span::current();
+    let request_id = uuid::Uuid::new_v4().to_string();
+    span.record("request_id", request_id.as_str());
+    let started = Instant::now();
+    let sent = request
+        .header(REQUEST_ID_HEADER, request_id.as_str())
+        .send()
+        .await;
+    span.record("duration_ms", started.elapsed().as_secs_f64() * 1000.0);
+    if let Ok(resp) = &sent {
+        span.record("status", resp.status().as_u16());
+    }
+    sent
+}
+
+/// The table or stream a statement reads from or creates, e.g. `hens_table`
+/// for `SELECT * FROM hens_table WHERE ...;`.
+fn relation(ksql: &str) -> Option<&str> {
+    let mut words = ksql.split_whitespace();
+    words.find(|w| ["FROM", "TABLE", "STREAM"].contains(&w.to_ascii_uppercase().as_str()))?;
+    words
+        .find(|w| !["IF", "NOT", "EXISTS"].contains(&w.to_ascii_uppercase().as_str()))
+        .map(|w| w.trim_end_matches([';', '(']))
+}
+
 /// Parse ksqlDB query response format:
 /// `[{header: {schema: ...}}, {row: {columns: [...]}}, ..., {finalMessage: ...}]`
 fn parse_query_response(body: &str) -> anyhow::Result<Vec<HashMap<String, Value>>> {
@@ -239,6 +477,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_batch_failure() {
+        let ok = r#"{"successes": [{"id": "0", "partition_id": 0, "offset": 7}], "failures": []}"#;
+        assert_eq!(batch_failure(ok), None);
+        let rejected =
+            r#"{"successes": [], "failures": [{"id": "0", "error_code": 400, "message": "bad"}]}"#;
+        assert!(batch_failure(rejected).unwrap().contains("bad"));
+    }
+
     #[test]
     fn test_parse_schema_columns_empty() {
         assert!(parse_schema_columns("").is_empty());
@@ -270,4 +517,32 @@ mod tests {
         let rows = parse_query_response(body).unwrap();
         assert!(rows.is_empty());
     }
+
+    #[test]
+    fn statements_name_the_relation_they_touch() {
+        assert_eq!(
+            relation("SELECT * FROM hens_table WHERE id = 'a';"),
+            Some("hens_table")
+        );
+        assert_eq!(
+            relation("CREATE TABLE IF NOT EXISTS hens_table AS SELECT * FROM hens_stream;"),
+            Some("hens_table")
+        );
+        assert_eq!(relation("DROP STREAM hens_stream;"), Some("hens_stream"));
+        assert_eq!(relation("SHOW QUERIES;"), None);
+    }
+
+    #[test]
+    fn warming_tables_are_told_apart_from_failed_queries() {
+        assert!(is_not_ready(
+            r#"{"@type":"statement_error","message":"Table 'HENS_TABLE' is not available yet"}"#
+        ));
+        assert!(is_not_ready(
+            r#"{"message":"Cannot determine which host contains the required partitions"}"#
+        ));
+        assert!(!is_not_ready(
+            r#"{"@type":"statement_error","message":"line 1:15: mismatched input 'WHER'"}"#
+        ));
+        assert!(!is_not_ready(""));
+    }
 }
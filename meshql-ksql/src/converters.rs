@@ -1,5 +1,5 @@
 use chrono::{DateTime, TimeZone, Utc};
-use meshql_core::{Envelope, Stash};
+use meshql_core::{insert_metadata, Envelope, Stash};
 use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 
@@ -63,10 +63,15 @@ pub fn row_to_envelope(row: &HashMap<String, Value>) -> anyhow::Result<Envelope>
     })
 }
 
-/// Convert an Envelope to a result Stash (payload fields + id merged in).
+/// Convert an Envelope to a result Stash (payload fields + id and metadata merged in).
 pub fn envelope_to_stash(envelope: &Envelope) -> Stash {
     let mut stash = envelope.payload.clone();
     stash.insert("id".to_string(), json!(envelope.id));
+    insert_metadata(
+        &mut stash,
+        envelope.created_at.timestamp_millis(),
+        envelope.deleted,
+    );
     stash
 }
 
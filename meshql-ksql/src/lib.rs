@@ -5,7 +5,7 @@ pub mod query;
 pub mod repository;
 pub mod searcher;
 
-pub use client::ConfluentClient;
+pub use client::{ConfluentClient, PullQuery, Pulled};
 pub use config::KsqlConfig;
-pub use repository::KsqlRepository;
+pub use repository::{KsqlRepository, Polling};
 pub use searcher::KsqlSearcher;
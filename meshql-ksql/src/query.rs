@@ -11,38 +11,123 @@ pub struct QueryPart {
 /// Follows the same template convention as meshql-sqlite/query.rs:
 /// - `"id"` → `id = 'escaped_value'`
 /// - `"payload.field"` → `EXTRACTJSONFIELD(payload, '$.field') = 'escaped_value'`
+/// - `{"$in": [...]}` values → `IN ('a', 'b')`
+/// - `{"$gt": 20, "$lte": 40}` values → one comparison per operator; numeric
+///   operands compare the field as a `DOUBLE`
+/// - `{"$or": [{...}, {...}]}` → `((...) OR (...))`, each group ANDed
 /// - `{}` → empty (match all)
 pub fn build_where(query_obj: &serde_json::Map<String, serde_json::Value>) -> QueryPart {
-    if query_obj.is_empty() {
-        return QueryPart {
-            clause: String::new(),
-        };
+    QueryPart {
+        clause: conditions(query_obj).join(" AND "),
     }
+}
 
+/// One condition per key of `query_obj`.
+fn conditions(query_obj: &serde_json::Map<String, serde_json::Value>) -> Vec<String> {
     let mut clauses = Vec::new();
 
     for (key, val) in query_obj {
-        let str_val = match val {
-            serde_json::Value::String(s) => s.clone(),
-            other => other.to_string(),
-        };
-        let escaped = escape_sql_string(&str_val);
-
-        if key == "id" {
-            clauses.push(format!("id = '{}'", escaped));
+        if key == "$or" {
+            clauses.push(or_group(val));
+            continue;
+        }
+        let column = if key == "id" {
+            "id".to_string()
         } else if let Some(field) = key.strip_prefix("payload.") {
-            clauses.push(format!(
-                "EXTRACTJSONFIELD(payload, '$.{}') = '{}'",
-                field, escaped
-            ));
+            format!("EXTRACTJSONFIELD(payload, '$.{}')", field)
         } else {
             // Unknown key — skip
             continue;
+        };
+
+        if let Some(ops) = comparisons(val) {
+            for (op, operand) in ops {
+                clauses.push(match operand {
+                    serde_json::Value::Number(n) if key != "id" => {
+                        format!("CAST({} AS DOUBLE) {} {}", column, op, n)
+                    }
+                    _ => format!(
+                        "{} {} '{}'",
+                        column,
+                        op,
+                        escape_sql_string(&literal_value(operand))
+                    ),
+                });
+            }
+            continue;
+        }
+
+        match in_list(val) {
+            Some([]) => clauses.push("1 = 0".to_string()),
+            Some(list) => {
+                let literals: Vec<String> = list
+                    .iter()
+                    .map(|v| format!("'{}'", escape_sql_string(&literal_value(v))))
+                    .collect();
+                clauses.push(format!("{} IN ({})", column, literals.join(", ")));
+            }
+            None => {
+                clauses.push(format!(
+                    "{} = '{}'",
+                    column,
+                    escape_sql_string(&literal_value(val))
+                ));
+            }
         }
     }
 
-    QueryPart {
-        clause: clauses.join(" AND "),
+    clauses
+}
+
+/// `((a AND b) OR (c))` for the groups of an `{"$or": [...]}`. No groups
+/// match nothing.
+fn or_group(val: &serde_json::Value) -> String {
+    let groups: Vec<String> = val
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_object)
+        .map(|group| match conditions(group).as_slice() {
+            [] => "1 = 1".to_string(),
+            clauses => format!("({})", clauses.join(" AND ")),
+        })
+        .collect();
+    if groups.is_empty() {
+        "1 = 0".to_string()
+    } else {
+        format!("({})", groups.join(" OR "))
+    }
+}
+
+/// The candidates of an `{"$in": [...]}` value, which matches any one of them.
+fn in_list(val: &serde_json::Value) -> Option<&[serde_json::Value]> {
+    val.as_object()?.get("$in")?.as_array().map(Vec::as_slice)
+}
+
+/// The operators of a comparison object like `{"$gt": 20, "$lte": 40}` as SQL
+/// operators paired with their operands. `None` unless every key is one of
+/// `$gt`, `$gte`, `$lt`, `$lte` or `$ne`.
+fn comparisons(val: &serde_json::Value) -> Option<Vec<(&'static str, &serde_json::Value)>> {
+    let obj = val.as_object().filter(|obj| !obj.is_empty())?;
+    obj.iter()
+        .map(|(op, operand)| {
+            let op = match op.as_str() {
+                "$gt" => ">",
+                "$gte" => ">=",
+                "$lt" => "<",
+                "$lte" => "<=",
+                "$ne" => "!=",
+                _ => return None,
+            };
+            Some((op, operand))
+        })
+        .collect()
+}
+
+fn literal_value(val: &serde_json::Value) -> String {
+    match val {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
     }
 }
 
@@ -105,6 +190,49 @@ mod tests {
         assert_eq!(result.clause, "id = '''; DROP TABLE foo; --'");
     }
 
+    #[test]
+    fn test_in_list_query() {
+        let mut obj = serde_json::Map::new();
+        obj.insert("payload.coop_id".to_string(), json!({"$in": ["c1", "o'c"]}));
+        let result = build_where(&obj);
+        assert_eq!(
+            result.clause,
+            "EXTRACTJSONFIELD(payload, '$.coop_id') IN ('c1', 'o''c')"
+        );
+    }
+
+    #[test]
+    fn test_comparison_query() {
+        let mut obj = serde_json::Map::new();
+        obj.insert("payload.count".to_string(), json!({"$gt": 20}));
+        assert_eq!(
+            build_where(&obj).clause,
+            "CAST(EXTRACTJSONFIELD(payload, '$.count') AS DOUBLE) > 20"
+        );
+
+        obj.insert("payload.name".to_string(), json!({"$lte": "o'c"}));
+        assert!(build_where(&obj)
+            .clause
+            .contains("EXTRACTJSONFIELD(payload, '$.name') <= 'o''c'"));
+    }
+
+    #[test]
+    fn test_or_query() {
+        let mut obj = serde_json::Map::new();
+        obj.insert(
+            "$or".to_string(),
+            json!([{"payload.type": "typeA"}, {"payload.type": "typeB"}]),
+        );
+        assert_eq!(
+            build_where(&obj).clause,
+            "((EXTRACTJSONFIELD(payload, '$.type') = 'typeA') OR \
+             (EXTRACTJSONFIELD(payload, '$.type') = 'typeB'))"
+        );
+
+        obj.insert("$or".to_string(), json!([]));
+        assert_eq!(build_where(&obj).clause, "1 = 0");
+    }
+
     #[test]
     fn test_numeric_value() {
         let mut obj = serde_json::Map::new();
@@ -1,21 +1,73 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use meshql_core::{Envelope, MeshqlError, Repository, Result};
+use meshql_core::{
+    forbid_hidden, Clock, Envelope, IdStrategy, ListOptions, MeshqlError, Repository, Result,
+    SystemClock,
+};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
-use crate::client::ConfluentClient;
+use crate::client::{ConfluentClient, PullQuery, Pulled};
 use crate::config::KsqlConfig;
 use crate::converters::{envelope_to_kafka_value, row_to_envelope};
 
+/// How reads wait on a table ksqlDB reports isn't ready yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Polling {
+    /// Attempts in all, the first included.
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+}
+
+impl Polling {
+    pub fn from_config(config: &KsqlConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            retry_delay: Duration::from_millis(config.retry_delay_ms),
+        }
+    }
+}
+
+/// Run `query`, asking again only while ksqlDB reports the table isn't ready.
+/// No rows is a final answer: they are genuinely absent. Still not ready after
+/// the last attempt is a [`MeshqlError::Storage`] error, so it may be retried.
+async fn pull_when_ready(
+    client: &dyn PullQuery,
+    query: &str,
+    polling: Polling,
+) -> Result<Vec<HashMap<String, Value>>> {
+    let attempts = polling.max_retries.max(1);
+    for attempt in 1..=attempts {
+        let pulled = client
+            .pull(query)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        match pulled {
+            Pulled::Rows(rows) => return Ok(rows),
+            Pulled::NotReady => {
+                debug!("Query not ready (attempt {attempt}/{attempts}): {query}");
+                if attempt < attempts {
+                    tokio::time::sleep(polling.retry_delay).await;
+                }
+            }
+        }
+    }
+    Err(MeshqlError::Storage(format!(
+        "ksqlDB table not ready after {attempts} attempts: {query}"
+    )))
+}
+
 pub struct KsqlRepository {
     client: Arc<ConfluentClient>,
     topic: String,
     stream_name: String,
     table_name: String,
-    max_retries: u32,
-    retry_delay_ms: u64,
+    polling: Polling,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
 }
 
 impl KsqlRepository {
@@ -25,11 +77,26 @@ impl KsqlRepository {
             topic: KsqlConfig::topic_name(entity),
             stream_name: KsqlConfig::stream_name(entity),
             table_name: KsqlConfig::table_name(entity),
-            max_retries: config.max_retries,
-            retry_delay_ms: config.retry_delay_ms,
+            polling: Polling::from_config(config),
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Stamp updates and tombstones with `clock`'s time, rather than
+    /// [`SystemClock`]'s.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Run DDL to create the ksqlDB stream and materialized table.
     /// Idempotent — uses IF NOT EXISTS.
     pub async fn initialize(&self) -> anyhow::Result<()> {
@@ -66,7 +133,11 @@ impl KsqlRepository {
     }
 
     async fn wait_for_table_ready(&self) {
-        for i in 0..self.max_retries {
+        let Polling {
+            max_retries,
+            retry_delay,
+        } = self.polling;
+        for i in 0..max_retries {
             if self.client.is_table_ready(&self.table_name).await {
                 info!("ksqlDB table {} is ready", self.table_name);
                 return;
@@ -75,9 +146,9 @@ impl KsqlRepository {
                 "Waiting for table {} (attempt {}/{})",
                 self.table_name,
                 i + 1,
-                self.max_retries
+                max_retries
             );
-            tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms)).await;
+            tokio::time::sleep(retry_delay).await;
         }
         warn!(
             "ksqlDB table {} may not be ready after waiting",
@@ -88,11 +159,68 @@ impl KsqlRepository {
     fn escape_id(id: &str) -> String {
         id.replace('\'', "''")
     }
+
+    /// [`Repository::read`], waiting on a table that isn't ready yet per
+    /// `polling` rather than the configured [`KsqlConfig::max_retries`].
+    pub async fn read_polling(&self, id: &str, polling: Polling) -> Result<Option<Envelope>> {
+        Ok(self.latest(id, polling).await?.filter(|env| !env.deleted))
+    }
+
+    /// [`Repository::list`], waiting on a table that isn't ready yet per
+    /// `polling` rather than the configured [`KsqlConfig::max_retries`].
+    pub async fn list_polling(&self, polling: Polling) -> Result<Vec<Envelope>> {
+        self.list_with_polling(ListOptions::default(), polling)
+            .await
+    }
+
+    /// [`Repository::list_with`], waiting on a table that isn't ready yet per
+    /// `polling` rather than the configured [`KsqlConfig::max_retries`].
+    pub async fn list_with_polling(
+        &self,
+        options: ListOptions,
+        polling: Polling,
+    ) -> Result<Vec<Envelope>> {
+        let filter = options
+            .deleted()
+            .map(|deleted| format!(" WHERE deleted = {deleted}"))
+            .unwrap_or_default();
+        let query = format!("SELECT * FROM {}{filter};", self.table_name);
+        let rows = pull_when_ready(self.client.as_ref(), &query, polling).await?;
+
+        let mut envelopes = Vec::new();
+        for row in &rows {
+            match row_to_envelope(row) {
+                Ok(env) if options.lists(env.deleted) => envelopes.push(env),
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Failed to parse row: {}", e);
+                }
+            }
+        }
+        Ok(options.page(envelopes))
+    }
+
+    /// The table's latest version of `id`, tombstone or not.
+    async fn latest(&self, id: &str, polling: Polling) -> Result<Option<Envelope>> {
+        let query = format!(
+            "SELECT * FROM {} WHERE id = '{}';",
+            self.table_name,
+            Self::escape_id(id)
+        );
+        let rows = pull_when_ready(self.client.as_ref(), &query, polling).await?;
+        rows.first()
+            .map(|row| row_to_envelope(row).map_err(|e| MeshqlError::Parse(e.to_string())))
+            .transpose()
+    }
 }
 
 #[async_trait]
 impl Repository for KsqlRepository {
     async fn create(&self, envelope: Envelope, _tokens: &[String]) -> Result<Envelope> {
+        let mut envelope = envelope;
+        if envelope.id.is_empty() {
+            envelope.id = self.ids.generate();
+        }
         let kafka_value = envelope_to_kafka_value(&envelope);
 
         self.client
@@ -106,116 +234,94 @@ impl Repository for KsqlRepository {
     async fn read(
         &self,
         id: &str,
-        _tokens: &[String],
+        tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>> {
-        let escaped_id = Self::escape_id(id);
-
-        if at.is_some() {
-            // Temporal read: query the STREAM for all versions, filter client-side.
-            // ksqlDB stream pull queries may not support key-based lookup on
-            // Confluent Cloud, so we fall back to TABLE read (latest only).
-            let query = format!(
-                "SELECT * FROM {} WHERE id = '{}';",
-                self.table_name, escaped_id
-            );
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
 
-            for _ in 0..self.max_retries {
-                match self.client.pull_query(&query).await {
-                    Ok(rows) if !rows.is_empty() => {
-                        let env = row_to_envelope(&rows[0])
-                            .map_err(|e| MeshqlError::Parse(e.to_string()))?;
-                        if env.deleted {
-                            return Ok(None);
-                        }
-                        return Ok(Some(env));
-                    }
-                    Ok(_) => {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms))
-                            .await;
-                    }
-                    Err(e) => {
-                        debug!("Read query not ready: {}", e);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms))
-                            .await;
-                    }
-                }
-            }
-            Ok(None)
-        } else {
-            // Current read: query the TABLE for latest state.
-            let query = format!(
-                "SELECT * FROM {} WHERE id = '{}';",
-                self.table_name, escaped_id
-            );
+    /// Always the latest version: `at` isn't honoured, since ksqlDB stream pull
+    /// queries may not support key-based lookup on Confluent Cloud.
+    async fn read_raw(
+        &self,
+        id: &str,
+        _tokens: &[String],
+        _at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        self.latest(id, self.polling).await
+    }
 
-            for _ in 0..self.max_retries {
-                match self.client.pull_query(&query).await {
-                    Ok(rows) if !rows.is_empty() => {
-                        let env = row_to_envelope(&rows[0])
-                            .map_err(|e| MeshqlError::Parse(e.to_string()))?;
-                        if env.deleted {
-                            return Ok(None);
-                        }
-                        return Ok(Some(env));
-                    }
-                    Ok(_) => {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms))
-                            .await;
-                    }
-                    Err(e) => {
-                        debug!("Read query not ready: {}", e);
-                        tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms))
-                            .await;
-                    }
-                }
-            }
-            Ok(None)
-        }
+    async fn list_with(&self, _tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        self.list_with_polling(options, self.polling).await
     }
 
-    async fn list(&self, _tokens: &[String]) -> Result<Vec<Envelope>> {
-        let query = format!("SELECT * FROM {} WHERE deleted = false;", self.table_name);
-
-        for _ in 0..self.max_retries {
-            match self.client.pull_query(&query).await {
-                Ok(rows) if !rows.is_empty() => {
-                    let mut envelopes = Vec::new();
-                    for row in &rows {
-                        match row_to_envelope(row) {
-                            Ok(env) if !env.deleted => envelopes.push(env),
-                            Ok(_) => {} // skip deleted
-                            Err(e) => {
-                                warn!("Failed to parse row: {}", e);
-                            }
-                        }
-                    }
-                    return Ok(envelopes);
-                }
-                Ok(_) => {
-                    tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms))
-                        .await;
-                }
-                Err(e) => {
-                    debug!("List query not ready: {}", e);
-                    tokio::time::sleep(tokio::time::Duration::from_millis(self.retry_delay_ms))
-                        .await;
-                }
-            }
-        }
+    async fn history(&self, id: &str, _tokens: &[String]) -> Result<Vec<Envelope>> {
+        // The table only keeps the latest version, so scan the stream instead.
+        let query = format!(
+            "SELECT * FROM {} WHERE id = '{}';",
+            self.stream_name,
+            Self::escape_id(id)
+        );
+        let rows = self
+            .client
+            .pull_query(&query)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        let mut versions = rows
+            .iter()
+            .map(|row| row_to_envelope(row).map_err(|e| MeshqlError::Parse(e.to_string())))
+            .collect::<Result<Vec<_>>>()?;
+        versions.sort_by_key(|env| env.created_at.timestamp_millis());
+        Ok(versions)
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        _tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        // As with history, only the stream holds every version.
+        let query = format!(
+            "SELECT * FROM {} WHERE created_at >= {} AND created_at < {};",
+            self.stream_name,
+            from.timestamp_millis(),
+            to.timestamp_millis()
+        );
+        let rows = self
+            .client
+            .pull_query(&query)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        let mut versions = rows
+            .iter()
+            .map(|row| row_to_envelope(row).map_err(|e| MeshqlError::Parse(e.to_string())))
+            .collect::<Result<Vec<_>>>()?;
+        versions.sort_by_key(|env| env.created_at.timestamp_millis());
+        Ok(versions)
+    }
 
-        Ok(Vec::new())
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
     }
 
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
         let current = self.read(id, tokens, None).await?;
         match current {
-            None => Ok(false),
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
             Some(env) => {
                 let deleted_env = Envelope {
                     id: env.id,
                     payload: env.payload,
-                    created_at: Utc::now(),
+                    created_at: self.clock.now(),
                     deleted: true,
                     authorized_tokens: env.authorized_tokens,
                 };
@@ -229,16 +335,46 @@ impl Repository for KsqlRepository {
         }
     }
 
+    /// Produces a null-valued record for `id`, which removes it from the table
+    /// and lets compaction drop its earlier records. The stream `history` reads
+    /// keeps them until the topic's compaction or retention catches up.
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let purged = self.history(id, tokens).await?.len() as u64;
+        if purged == 0 {
+            return Ok(0);
+        }
+        self.client
+            .produce_tombstone(&self.topic, id)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(purged)
+    }
+
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
-        tokens: &[String],
+        _tokens: &[String],
     ) -> Result<Vec<Envelope>> {
-        let mut results = Vec::new();
-        for env in envelopes {
-            results.push(self.create(env, tokens).await?);
-        }
-        Ok(results)
+        let envelopes: Vec<Envelope> = envelopes
+            .into_iter()
+            .map(|mut envelope| {
+                if envelope.id.is_empty() {
+                    envelope.id = self.ids.generate();
+                }
+                envelope
+            })
+            .collect();
+        let records = envelopes
+            .iter()
+            .map(|env| (env.id.clone(), envelope_to_kafka_value(env)))
+            .collect();
+
+        self.client
+            .produce_records(&self.topic, records)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        Ok(envelopes)
     }
 
     async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
@@ -263,4 +399,102 @@ impl Repository for KsqlRepository {
         }
         Ok(results)
     }
+
+    async fn ping(&self) -> Result<()> {
+        self.client
+            .ping()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    /// Answers each pull with the next scripted outcome, then with no rows.
+    struct Scripted {
+        answers: Mutex<Vec<anyhow::Result<Pulled>>>,
+        calls: AtomicU32,
+    }
+
+    impl Scripted {
+        fn new(mut answers: Vec<anyhow::Result<Pulled>>) -> Self {
+            answers.reverse();
+            Self {
+                answers: Mutex::new(answers),
+                calls: AtomicU32::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl PullQuery for Scripted {
+        async fn pull(&self, _ksql: &str) -> anyhow::Result<Pulled> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.answers
+                .lock()
+                .unwrap()
+                .pop()
+                .unwrap_or(Ok(Pulled::Rows(Vec::new())))
+        }
+    }
+
+    fn fast() -> Polling {
+        Polling {
+            max_retries: 3,
+            retry_delay: Duration::from_millis(1),
+        }
+    }
+
+    fn row(id: &str) -> HashMap<String, Value> {
+        HashMap::from([("ID".to_string(), Value::from(id))])
+    }
+
+    #[tokio::test]
+    async fn absent_rows_are_answered_after_one_attempt() {
+        let client = Scripted::new(vec![Ok(Pulled::Rows(Vec::new()))]);
+        let rows = pull_when_ready(&client, "SELECT 1;", fast()).await.unwrap();
+        assert!(rows.is_empty());
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn warming_tables_are_polled_until_ready() {
+        let client = Scripted::new(vec![
+            Ok(Pulled::NotReady),
+            Ok(Pulled::NotReady),
+            Ok(Pulled::Rows(vec![row("hen-1")])),
+        ]);
+        let rows = pull_when_ready(&client, "SELECT 1;", fast()).await.unwrap();
+        assert_eq!(rows, vec![row("hen-1")]);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn tables_never_ready_fail_transiently() {
+        let client = Scripted::new(vec![
+            Ok(Pulled::NotReady),
+            Ok(Pulled::NotReady),
+            Ok(Pulled::NotReady),
+            Ok(Pulled::Rows(vec![row("too-late")])),
+        ]);
+        let err = pull_when_ready(&client, "SELECT 1;", fast())
+            .await
+            .unwrap_err();
+        assert!(err.is_transient(), "{err}");
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_queries_are_not_retried() {
+        let client = Scripted::new(vec![Err(anyhow::anyhow!("mismatched input 'WHER'"))]);
+        let err = pull_when_ready(&client, "SELECT 1;", fast())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, MeshqlError::Storage(msg) if msg.contains("WHER")));
+        assert_eq!(client.calls.load(Ordering::SeqCst), 1);
+    }
 }
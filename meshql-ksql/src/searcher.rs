@@ -1,6 +1,8 @@
 use async_trait::async_trait;
-use handlebars::Handlebars;
-use meshql_core::{MeshqlError, Result, Searcher, Stash};
+use meshql_core::{
+    check_or_groups, distinct_from_args, distinct_stashes, render_template, sort_from_args,
+    sort_stashes, MeshqlError, MissingKey, Result, Searcher, Stash,
+};
 use std::sync::Arc;
 use tracing::{debug, warn};
 
@@ -22,20 +24,17 @@ impl KsqlSearcher {
         }
     }
 
-    /// Render a Handlebars template with the given args, then parse as JSON query object.
+    /// Render a template with the given args, then parse as JSON query object.
     fn render_template(
         &self,
         template: &str,
         args: &Stash,
     ) -> Result<serde_json::Map<String, serde_json::Value>> {
-        let mut hbs = Handlebars::new();
-        hbs.set_strict_mode(false);
-        let rendered = hbs
-            .render_template(template, args)
-            .map_err(|e| MeshqlError::Template(e.to_string()))?;
-        let query_obj: serde_json::Map<String, serde_json::Value> =
+        let rendered = render_template(template, args, MissingKey::Empty)?;
+        let query: serde_json::Value =
             serde_json::from_str(&rendered).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-        Ok(query_obj)
+        check_or_groups(&query)?;
+        serde_json::from_value(query).map_err(|e| MeshqlError::Parse(e.to_string()))
     }
 }
 
@@ -98,6 +97,11 @@ impl Searcher for KsqlSearcher {
             .get("limit")
             .and_then(|v| v.as_i64())
             .map(|v| v as usize);
+        let offset = args
+            .get("offset")
+            .and_then(|v| v.as_i64())
+            .map(|v| v as usize);
+        let sort = sort_from_args(args)?;
 
         let query = if where_part.clause.is_empty() {
             format!("SELECT * FROM {} WHERE deleted = false;", self.table_name)
@@ -124,6 +128,17 @@ impl Searcher for KsqlSearcher {
                     })
                     .collect();
 
+                if let Some(field) = distinct_from_args(args)? {
+                    distinct_stashes(&mut results, &field);
+                }
+
+                // Sorted and paged results end with id so consecutive pages neither overlap nor skip
+                if !sort.is_empty() || limit.is_some() || offset.is_some() {
+                    sort_stashes(&mut results, &sort);
+                }
+                if let Some(off) = offset {
+                    results.drain(..off.min(results.len()));
+                }
                 if let Some(lim) = limit {
                     results.truncate(lim);
                 }
@@ -136,4 +151,31 @@ impl Searcher for KsqlSearcher {
             }
         }
     }
+
+    async fn count(&self, template: &str, args: &Stash, creds: &[String], at: i64) -> Result<u64> {
+        // Pull queries can't aggregate, so count the matching rows client-side.
+        let mut args = args.clone();
+        args.remove("limit");
+        args.remove("offset");
+        args.remove("sort");
+        let results = self.find_all(template, &args, creds, at).await?;
+        Ok(results.len() as u64)
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        Ok(self.find(template, args, creds, at).await?.is_some())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.client
+            .ping()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
 }
@@ -0,0 +1,127 @@
+use meshql_core::{Envelope, Repository, Stash};
+use meshql_ksql::{ConfluentClient, KsqlConfig, KsqlRepository};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const BATCH_PATH: &str = "/kafka/v3/clusters/cluster-1/topics/hen/records:batch";
+
+fn config(server: &MockServer) -> KsqlConfig {
+    KsqlConfig {
+        kafka_rest_url: server.uri(),
+        kafka_cluster_id: "cluster-1".to_string(),
+        kafka_api_key: "key".to_string(),
+        kafka_api_secret: "secret".to_string(),
+        ksqldb_url: server.uri(),
+        ksqldb_api_key: "key".to_string(),
+        ksqldb_api_secret: "secret".to_string(),
+        auto_create_ddl: false,
+        max_retries: 1,
+        retry_delay_ms: 0,
+    }
+}
+
+fn hens(count: usize) -> Vec<Envelope> {
+    let star = vec!["*".to_string()];
+    (0..count)
+        .map(|i| {
+            let mut payload = Stash::new();
+            payload.insert("name".to_string(), json!(format!("hen-{i}")));
+            Envelope::new(format!("hen-{i}"), payload, star.clone())
+        })
+        .collect()
+}
+
+async fn mock_batches(server: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path(BATCH_PATH))
+        .respond_with(
+            ResponseTemplate::new(207).set_body_json(json!({"successes": [], "failures": []})),
+        )
+        .mount(server)
+        .await;
+}
+
+/// The `entries` of every batch posted, in order.
+async fn posted_batches(server: &MockServer) -> Vec<Vec<Value>> {
+    server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| {
+            assert_eq!(request.url.path(), BATCH_PATH);
+            let body: Value = serde_json::from_slice(&request.body).unwrap();
+            body["entries"].as_array().unwrap().clone()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn create_many_posts_one_batch() {
+    let server = MockServer::start().await;
+    mock_batches(&server).await;
+    let config = config(&server);
+    let repo = KsqlRepository::new(Arc::new(ConfluentClient::new(&config)), "hen", &config);
+
+    let created = repo
+        .create_many(hens(50), &["*".to_string()])
+        .await
+        .unwrap();
+
+    assert_eq!(created.len(), 50);
+    let batches = posted_batches(&server).await;
+    assert_eq!(batches.len(), 1);
+    let entries = &batches[0];
+    assert_eq!(entries.len(), 50);
+    for (i, entry) in entries.iter().enumerate() {
+        assert_eq!(
+            entry["key"],
+            json!({"type": "STRING", "data": format!("hen-{i}")})
+        );
+        assert_eq!(entry["value"]["type"], "JSON");
+        assert_eq!(
+            entry["value"]["data"]["payload"],
+            json!(format!(r#"{{"name":"hen-{i}"}}"#))
+        );
+    }
+}
+
+#[tokio::test]
+async fn larger_inputs_split_into_batches() {
+    let server = MockServer::start().await;
+    mock_batches(&server).await;
+    let config = config(&server);
+    let client = ConfluentClient::new(&config).with_max_batch_records(20);
+    let repo = KsqlRepository::new(Arc::new(client), "hen", &config);
+
+    repo.create_many(hens(50), &["*".to_string()])
+        .await
+        .unwrap();
+
+    let sizes: Vec<usize> = posted_batches(&server).await.iter().map(Vec::len).collect();
+    assert_eq!(sizes, vec![20, 20, 10]);
+}
+
+#[tokio::test]
+async fn rejected_records_fail_create_many() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(BATCH_PATH))
+        .respond_with(ResponseTemplate::new(207).set_body_json(json!({
+            "successes": [],
+            "failures": [{"id": "0", "error_code": 400, "message": "record too large"}],
+        })))
+        .mount(&server)
+        .await;
+    let config = config(&server);
+    let repo = KsqlRepository::new(Arc::new(ConfluentClient::new(&config)), "hen", &config);
+
+    let result = repo.create_many(hens(2), &["*".to_string()]).await;
+
+    assert!(
+        matches!(&result, Err(meshql_core::MeshqlError::Storage(m)) if m.contains("record too large")),
+        "{result:?}"
+    );
+}
@@ -0,0 +1,171 @@
+use meshql_ksql::client::{REQUEST_ID_HEADER, SPAN_TARGET};
+use meshql_ksql::{ConfluentClient, KsqlConfig};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+use wiremock::matchers::{header_exists, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A span's name and fields, as text.
+type Span = (String, Vec<(String, String)>);
+
+/// Every span under [`SPAN_TARGET`], with the fields recorded on it later.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<HashMap<u64, Span>>>);
+
+impl Captured {
+    fn named(&self, name: &str) -> Vec<(String, String)> {
+        let spans = self.0.lock().unwrap();
+        let mut found = spans.values().filter(|(n, _)| n == name);
+        let (_, fields) = found.next().unwrap_or_else(|| panic!("no {name} span"));
+        assert!(found.next().is_none(), "more than one {name} span");
+        fields.clone()
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for Captured {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+        if attrs.metadata().target() != SPAN_TARGET {
+            return;
+        }
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let name = attrs.metadata().name().to_string();
+        self.0
+            .lock()
+            .unwrap()
+            .insert(id.into_u64(), (name, fields.0));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        if let Some((_, fields)) = self.0.lock().unwrap().get_mut(&id.into_u64()) {
+            let mut recorded = Fields::default();
+            values.record(&mut recorded);
+            fields.extend(recorded.0);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push((field.name().to_string(), value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{value:?}")));
+    }
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> &'a str {
+    fields
+        .iter()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.as_str())
+        .unwrap_or_else(|| panic!("no {name} in {fields:?}"))
+}
+
+fn client(server: &MockServer) -> ConfluentClient {
+    ConfluentClient::new(&KsqlConfig {
+        kafka_rest_url: server.uri(),
+        kafka_cluster_id: "cluster-1".to_string(),
+        kafka_api_key: "key".to_string(),
+        kafka_api_secret: "secret".to_string(),
+        ksqldb_url: server.uri(),
+        ksqldb_api_key: "key".to_string(),
+        ksqldb_api_secret: "secret".to_string(),
+        auto_create_ddl: false,
+        max_retries: 1,
+        retry_delay_ms: 0,
+    })
+}
+
+#[tokio::test]
+async fn spans_record_each_calls_status_and_request_id() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/query"))
+        .and(header_exists(REQUEST_ID_HEADER))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {"header": {"queryId": "q1", "schema": "`ID` STRING KEY"}},
+            {"row": {"columns": ["hen-1"]}},
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/ksql"))
+        .and(header_exists(REQUEST_ID_HEADER))
+        .respond_with(ResponseTemplate::new(400).set_body_string("line 1:1: mismatched input"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/kafka/v3/clusters/cluster-1/topics/hens/records"))
+        .and(header_exists(REQUEST_ID_HEADER))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::registry().with(captured.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let client = client(&server);
+
+    let rows = client
+        .pull_query("SELECT * FROM hens_table WHERE ID = 'hen-1';")
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!(client
+        .execute_statement("CREATE TABLE hens_table AS SELEC;")
+        .await
+        .is_err());
+    client
+        .produce_record("hens", "hen-1", &json!({"name": "Henrietta"}))
+        .await
+        .unwrap();
+
+    let pull = captured.named("pull_query");
+    assert_eq!(field(&pull, "status"), "200");
+    assert_eq!(field(&pull, "table"), "hens_table");
+    assert_eq!(
+        field(&pull, "statement"),
+        "SELECT * FROM hens_table WHERE ID = 'hen-1';"
+    );
+    assert!(field(&pull, "duration_ms").parse::<f64>().unwrap() >= 0.0);
+
+    let statement = captured.named("execute_statement");
+    assert_eq!(field(&statement, "status"), "400");
+
+    let produce = captured.named("produce_record");
+    assert_eq!(field(&produce, "status"), "200");
+    assert_eq!(field(&produce, "topic"), "hens");
+
+    let sent: Vec<String> = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| {
+            request.headers[REQUEST_ID_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    let recorded: Vec<String> = [&pull, &statement, &produce]
+        .iter()
+        .map(|fields| field(fields, "request_id").to_string())
+        .collect();
+    assert_eq!(sent, recorded);
+}
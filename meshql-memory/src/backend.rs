@@ -0,0 +1,47 @@
+use crate::{MemoryRepository, MemorySearcher, MemoryStore};
+use async_trait::async_trait;
+use meshql_core::{BackendFactory, Repository, Result, Searcher, StorageManifest};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Opens `backend: memory` manifest storage.
+///
+/// Nothing is persisted and the `uri` is ignored. Each `collection` gets its own
+/// [`MemoryStore`], shared by that entity's repository and searcher.
+#[derive(Default)]
+pub struct MemoryBackend {
+    stores: Mutex<HashMap<String, MemoryStore>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn store(&self, storage: &StorageManifest) -> MemoryStore {
+        let mut stores = self.stores.lock().unwrap_or_else(|e| e.into_inner());
+        stores
+            .entry(storage.collection.clone())
+            .or_default()
+            .clone()
+    }
+}
+
+#[async_trait]
+impl BackendFactory for MemoryBackend {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    async fn repository(&self, storage: &StorageManifest) -> Result<Arc<dyn Repository>> {
+        Ok(Arc::new(
+            MemoryRepository::new_with_store(self.store(storage))
+                .with_id_strategy(storage.id_strategy)
+                .with_auth_policy(storage.auth_policy),
+        ))
+    }
+
+    async fn searcher(&self, storage: &StorageManifest) -> Result<Arc<dyn Searcher>> {
+        Ok(Arc::new(MemorySearcher::new(self.store(storage))))
+    }
+}
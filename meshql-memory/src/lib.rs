@@ -0,0 +1,10 @@
+mod backend;
+mod matcher;
+mod repository;
+mod searcher;
+mod store;
+
+pub use backend::MemoryBackend;
+pub use repository::MemoryRepository;
+pub use searcher::MemorySearcher;
+pub use store::MemoryStore;
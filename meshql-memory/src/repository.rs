@@ -0,0 +1,292 @@
+use crate::store::{latest_per_id, MemoryStore};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use meshql_core::{
+    conflict, forbid_hidden, supersedes, AuthPolicy, Clock, Envelope, IdStrategy, ListOptions,
+    Repository, Result, SystemClock,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub struct MemoryRepository {
+    store: MemoryStore,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+    policy: AuthPolicy,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::new_with_store(MemoryStore::new())
+    }
+
+    pub fn new_with_store(store: MemoryStore) -> Self {
+        Self {
+            store,
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+            policy: AuthPolicy::default(),
+        }
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Match callers' tokens by `policy`, rather than [`AuthPolicy::AnyOf`].
+    pub fn with_auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether a caller holding `tokens` may see `env`.
+    fn is_visible(&self, env: &Envelope, tokens: &[String]) -> bool {
+        self.policy.allows(&env.authorized_tokens, tokens)
+    }
+
+    /// The store this repository writes to, for building a [`crate::MemorySearcher`] over it.
+    pub fn store(&self) -> MemoryStore {
+        self.store.clone()
+    }
+}
+
+impl Default for MemoryRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Repository for MemoryRepository {
+    async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+        self.store.write()?.push(env.clone());
+        Ok(env)
+    }
+
+    async fn read(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        let cutoff_ms = match at {
+            Some(t) => t.timestamp_millis(),
+            None => self.clock.now().timestamp_millis() + 1,
+        };
+
+        let envelopes = self.store.read()?;
+        let latest = envelopes
+            .iter()
+            .filter(|env| env.id == id && env.created_at.timestamp_millis() <= cutoff_ms)
+            .max_by_key(|env| env.created_at.timestamp_millis());
+        Ok(latest.filter(|env| self.is_visible(env, tokens)).cloned())
+    }
+
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        let cutoff_ms = self.clock.now().timestamp_millis() + 1;
+        let envelopes = self.store.read()?;
+        let listed = latest_per_id(&envelopes, cutoff_ms)
+            .into_iter()
+            .filter(|env| options.lists(env.deleted) && self.is_visible(env, tokens))
+            .cloned()
+            .collect();
+        Ok(options.page(listed))
+    }
+
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        let cutoff_ms = self.clock.now().timestamp_millis() + 1;
+        let envelopes = self.store.read()?;
+        Ok(latest_per_id(&envelopes, cutoff_ms)
+            .into_iter()
+            .filter(|env| !env.deleted && self.is_visible(env, tokens))
+            .count() as u64)
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        let mut versions: Vec<Envelope> = self
+            .store
+            .read()?
+            .iter()
+            .filter(|env| env.id == id && self.is_visible(env, tokens))
+            .cloned()
+            .collect();
+        // Stable, so versions written in the same millisecond keep write order.
+        versions.sort_by_key(|env| env.created_at.timestamp_millis());
+        Ok(versions)
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let (from, to) = (from.timestamp_millis(), to.timestamp_millis());
+        let mut versions: Vec<Envelope> = self
+            .store
+            .read()?
+            .iter()
+            .filter(|env| (from..to).contains(&env.created_at.timestamp_millis()))
+            .filter(|env| self.is_visible(env, tokens))
+            .cloned()
+            .collect();
+        versions.sort_by_key(|env| env.created_at.timestamp_millis());
+        Ok(versions)
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
+        let current = self.read(id, tokens, None).await?;
+        match current {
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
+            Some(env) => {
+                let deleted_env = Envelope {
+                    id: env.id,
+                    payload: env.payload,
+                    created_at: self.clock.now(),
+                    deleted: true,
+                    authorized_tokens: env.authorized_tokens,
+                };
+                self.create(deleted_env, tokens).await?;
+                Ok(true)
+            }
+        }
+    }
+
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let mut store = self.store.write()?;
+        let before = store.len();
+        store.retain(|env| env.id != id || !self.is_visible(env, tokens));
+        Ok((before - store.len()) as u64)
+    }
+
+    /// Checks and appends under one lock on the store, so it holds for every
+    /// repository sharing it.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+
+        let mut envelopes = self.store.write()?;
+        let latest = envelopes
+            .iter()
+            .filter(|version| version.id == env.id)
+            .max_by_key(|version| version.created_at.timestamp_millis());
+        if latest.is_some_and(|latest| {
+            supersedes(
+                latest.created_at.timestamp_millis(),
+                latest.deleted,
+                expected_created_at,
+            )
+        }) {
+            return Err(conflict(&env.id));
+        }
+        envelopes.push(env.clone());
+        Ok(env)
+    }
+
+    async fn create_many(
+        &self,
+        envelopes: Vec<Envelope>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let mut results = Vec::with_capacity(envelopes.len());
+        for mut env in envelopes {
+            if env.id.is_empty() {
+                env.id = self.ids.generate();
+            }
+            env.authorized_tokens = tokens.to_vec();
+            results.push(env);
+        }
+        // One lock, so readers never see half a batch.
+        self.store.write()?.extend(results.iter().cloned());
+        Ok(results)
+    }
+
+    async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let mut results = Vec::new();
+        for id in ids {
+            if let Some(env) = self.read(id, tokens, None).await? {
+                results.push(env);
+            }
+        }
+        Ok(results)
+    }
+
+    async fn remove_many(
+        &self,
+        ids: &[String],
+        tokens: &[String],
+    ) -> Result<HashMap<String, bool>> {
+        let wanted: HashSet<&str> = ids.iter().map(String::as_str).collect();
+        let now = self.clock.now();
+
+        // One lock, so no write lands between reading the latest versions and
+        // appending their tombstones.
+        let mut envelopes = self.store.write()?;
+        let tombstones: Vec<Envelope> = latest_per_id(&envelopes, now.timestamp_millis() + 1)
+            .into_iter()
+            .filter(|env| {
+                wanted.contains(env.id.as_str()) && !env.deleted && self.is_visible(env, tokens)
+            })
+            .map(|env| Envelope {
+                id: env.id.clone(),
+                payload: env.payload.clone(),
+                created_at: now,
+                deleted: true,
+                authorized_tokens: tokens.to_vec(),
+            })
+            .collect();
+        let removed: HashSet<String> = tombstones.iter().map(|env| env.id.clone()).collect();
+        envelopes.extend(tombstones);
+
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.store.read().map(|_| ())
+    }
+}
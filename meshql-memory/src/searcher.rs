@@ -0,0 +1,130 @@
+use crate::matcher;
+use crate::store::{latest_per_id, MemoryStore};
+use async_trait::async_trait;
+use meshql_core::{
+    check_or_groups, distinct_from_args, distinct_stashes, insert_metadata, render_template,
+    sort_from_args, sort_stashes, CreatedWindow, Envelope, MeshqlError, MissingKey, Result,
+    Searcher, Stash,
+};
+use serde_json::json;
+
+pub struct MemorySearcher {
+    store: MemoryStore,
+}
+
+impl MemorySearcher {
+    pub fn new(store: MemoryStore) -> Self {
+        Self { store }
+    }
+
+    /// Render the template, leaving out the `limit`, `offset`, `sort`,
+    /// `distinct` and creation window args.
+    fn render_template(&self, template: &str, args: &Stash) -> Result<serde_json::Value> {
+        let mut filter_args = args.clone();
+        for key in ["limit", "offset", "sort", "distinct"] {
+            filter_args.remove(key);
+        }
+        CreatedWindow::remove_args(&mut filter_args);
+        let rendered = render_template(template, &filter_args, MissingKey::Empty)?;
+        let query =
+            serde_json::from_str(&rendered).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        check_or_groups(&query)?;
+        Ok(query)
+    }
+
+    /// Latest, non-deleted versions as of `at` that match the rendered template
+    /// and were created in the window, in the order their ids were first
+    /// written, one per `distinct` value.
+    fn matching(&self, template: &str, args: &Stash, at: i64) -> Result<Vec<Stash>> {
+        let query = self.render_template(template, args)?;
+        let distinct = distinct_from_args(args)?;
+        let window = CreatedWindow::from_args(args)?;
+        let envelopes = self.store.read()?;
+        let mut results: Vec<Stash> = latest_per_id(&envelopes, at + 1)
+            .into_iter()
+            .filter(|env| !env.deleted)
+            .filter(|env| window.contains(env.created_at.timestamp_millis()))
+            .filter(|env| {
+                let record_json = json!({"id": env.id, "payload": env.payload});
+                matcher::matches(&record_json, &query)
+            })
+            .map(Self::envelope_to_stash)
+            .collect();
+        if let Some(field) = distinct {
+            distinct_stashes(&mut results, &field);
+        }
+        Ok(results)
+    }
+
+    /// Convert an Envelope to a result Stash (payload fields + id and metadata merged in).
+    fn envelope_to_stash(env: &Envelope) -> Stash {
+        let mut stash = env.payload.clone();
+        stash.insert("id".to_string(), json!(env.id));
+        insert_metadata(&mut stash, env.created_at.timestamp_millis(), env.deleted);
+        stash
+    }
+}
+
+#[async_trait]
+impl Searcher for MemorySearcher {
+    async fn find(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        Ok(self.matching(template, args, at)?.into_iter().next())
+    }
+
+    async fn find_all(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        let limit = args
+            .get("limit")
+            .and_then(|v| v.as_i64())
+            .map(|v| v as usize);
+        let offset = args
+            .get("offset")
+            .and_then(|v| v.as_i64())
+            .map(|v| v as usize);
+        let sort = sort_from_args(args)?;
+
+        let mut results = self.matching(template, args, at)?;
+
+        // Sorted and paged results end with id so consecutive pages neither overlap nor skip
+        if !sort.is_empty() || limit.is_some() || offset.is_some() {
+            sort_stashes(&mut results, &sort);
+        }
+        if let Some(off) = offset {
+            results.drain(..off.min(results.len()));
+        }
+        if let Some(lim) = limit {
+            results.truncate(lim);
+        }
+
+        Ok(results)
+    }
+
+    async fn count(&self, template: &str, args: &Stash, _creds: &[String], at: i64) -> Result<u64> {
+        Ok(self.matching(template, args, at)?.len() as u64)
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        Ok(!self.matching(template, args, at)?.is_empty())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.store.read().map(|_| ())
+    }
+}
@@ -0,0 +1,53 @@
+use meshql_core::{Envelope, MeshqlError, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Every envelope version ever written, in write order.
+///
+/// Clones share the same data, so a repository and a searcher built from one
+/// store see each other's writes.
+#[derive(Clone, Default)]
+pub struct MemoryStore(Arc<RwLock<Vec<Envelope>>>);
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn read(&self) -> Result<RwLockReadGuard<'_, Vec<Envelope>>> {
+        self.0
+            .read()
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+
+    pub(crate) fn write(&self) -> Result<RwLockWriteGuard<'_, Vec<Envelope>>> {
+        self.0
+            .write()
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+}
+
+/// The latest version of each id created at or before `cutoff_ms`, deleted or
+/// not, in the order the ids were first written. Of two versions written in the
+/// same millisecond the later write wins, as with `rowid` in the SQL backends.
+pub(crate) fn latest_per_id(envelopes: &[Envelope], cutoff_ms: i64) -> Vec<&Envelope> {
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    let mut latest: Vec<&Envelope> = Vec::new();
+    for env in envelopes {
+        if env.created_at.timestamp_millis() > cutoff_ms {
+            continue;
+        }
+        match positions.get(env.id.as_str()) {
+            Some(&i) => {
+                if env.created_at.timestamp_millis() >= latest[i].created_at.timestamp_millis() {
+                    latest[i] = env;
+                }
+            }
+            None => {
+                positions.insert(&env.id, latest.len());
+                latest.push(env);
+            }
+        }
+    }
+    latest
+}
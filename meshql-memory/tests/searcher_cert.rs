@@ -0,0 +1,117 @@
+use meshql_core::testing as cert;
+use meshql_memory::{MemoryRepository, MemorySearcher};
+
+async fn create_searcher() -> (MemoryRepository, MemorySearcher) {
+    let repo = MemoryRepository::new();
+    let searcher = MemorySearcher::new(repo.store());
+    cert::seed_searcher_data(&repo).await;
+    (repo, searcher)
+}
+
+#[tokio::test]
+async fn should_return_empty_for_nonexistent_id() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_empty_result_for_nonexistent(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_by_id() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_by_id(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_by_name() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_by_name(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_type() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_by_type(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_type_and_name() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_by_type_and_name(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_list() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_in_list(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_comparison() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_comparisons(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_or_groups() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_or_groups(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_return_empty_for_nonexistent_type() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_empty_array_for_nonexistent_type(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_respect_limit() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_respects_limit(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_handle_empty_query() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_empty_query(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_count_matches() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_count(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_check_existence() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_exists(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_page_with_limit_and_offset() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_pages_with_limit_and_offset(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_sort_by_count_desc() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_sorts_by_count_desc(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_collapse_distinct_values() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_collapses_distinct_values(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_stream_large_results() {
+    let (repo, searcher) = create_searcher().await;
+    cert::test_searcher_streams_large_results(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_creation_window() {
+    let (repo, searcher) = create_searcher().await;
+    cert::test_searcher_filters_by_creation_window(&repo, &searcher).await;
+}
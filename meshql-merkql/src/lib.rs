@@ -1,6 +1,7 @@
 mod matcher;
 pub mod repository;
 pub mod searcher;
+mod view;
 
 pub use repository::MerkqlRepository;
 pub use searcher::MerkqlSearcher;
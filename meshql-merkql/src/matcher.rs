@@ -1,10 +1,14 @@
 use serde_json::Value;
+use std::cmp::Ordering;
 
 /// Match a record's JSON representation against a dot-notation query.
 ///
 /// The query is a JSON object where:
 /// - Keys may use dot notation for nested fields (e.g., "payload.name")
-/// - Values must equal the record's value at that path
+/// - Values must equal the record's value at that path, or be one of the
+///   candidates of an `{"$in": [...]}` value, or satisfy every operator of a
+///   `{"$gt": .., "$gte": .., "$lt": .., "$lte": .., "$ne": ..}` value
+/// - An `"$or"` key holds an array of queries, at least one of which must match
 /// - An empty query `{}` matches everything
 /// - All query keys must match (AND semantics)
 pub fn matches(record_json: &Value, query: &Value) -> bool {
@@ -14,14 +18,16 @@ pub fn matches(record_json: &Value, query: &Value) -> bool {
     };
 
     for (key, expected) in query_obj {
-        let path: Vec<&str> = key.split('.').collect();
-        match get_path(record_json, &path) {
-            Some(actual) => {
-                if actual != expected {
-                    return false;
-                }
+        if key == "$or" {
+            let groups = expected.as_array().map(Vec::as_slice).unwrap_or_default();
+            if !groups.iter().any(|group| matches(record_json, group)) {
+                return false;
             }
-            None => return false,
+            continue;
+        }
+        let path: Vec<&str> = key.split('.').collect();
+        if !value_matches(get_path(record_json, &path), expected) {
+            return false;
         }
     }
     true
@@ -41,6 +47,47 @@ fn get_path<'a>(val: &'a Value, path: &[&str]) -> Option<&'a Value> {
     }
 }
 
+/// A missing field only matches `$ne`.
+fn value_matches(actual: Option<&Value>, expected: &Value) -> bool {
+    if let Some(candidates) = expected.get("$in").and_then(Value::as_array) {
+        return actual.is_some_and(|a| candidates.contains(a));
+    }
+    match comparisons(expected) {
+        Some(ops) => ops.into_iter().all(|(op, operand)| match op {
+            "$ne" => actual != Some(operand),
+            _ => actual
+                .and_then(|a| compare(a, operand))
+                .is_some_and(|ord| match op {
+                    "$gt" => ord.is_gt(),
+                    "$gte" => ord.is_ge(),
+                    "$lt" => ord.is_lt(),
+                    _ => ord.is_le(),
+                }),
+        }),
+        None => actual == Some(expected),
+    }
+}
+
+/// The operators of a `{"$gt": 20, "$lte": 40}` value, if every key is one.
+fn comparisons(expected: &Value) -> Option<Vec<(&str, &Value)>> {
+    let obj = expected.as_object().filter(|obj| !obj.is_empty())?;
+    obj.iter()
+        .map(|(op, operand)| {
+            matches!(op.as_str(), "$gt" | "$gte" | "$lt" | "$lte" | "$ne")
+                .then_some((op.as_str(), operand))
+        })
+        .collect()
+}
+
+/// Numbers compare numerically and strings lexically; anything else is unordered.
+fn compare(actual: &Value, operand: &Value) -> Option<Ordering> {
+    match (actual, operand) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,4 +142,56 @@ mod tests {
         let query2 = json!({"payload.type": "typeA"});
         assert!(matches(&record_json2, &query2), "findAllByType should work");
     }
+
+    #[test]
+    fn comparison_match() {
+        let record = json!({"id": "x", "payload": {"count": 20, "name": "beta"}});
+        assert!(matches(
+            &record,
+            &json!({"payload.count": {"$gt": 10, "$lte": 20}})
+        ));
+        assert!(!matches(&record, &json!({"payload.count": {"$gt": 20}})));
+        assert!(matches(&record, &json!({"payload.name": {"$lt": "gamma"}})));
+        assert!(!matches(&record, &json!({"payload.name": {"$gt": 5}})));
+        assert!(matches(&record, &json!({"payload.name": {"$ne": "alpha"}})));
+        assert!(matches(
+            &record,
+            &json!({"payload.missing": {"$ne": "alpha"}})
+        ));
+        assert!(!matches(&record, &json!({"payload.missing": {"$gte": 0}})));
+    }
+
+    #[test]
+    fn in_list_match() {
+        let record = json!({"id": "x", "payload": {"coop_id": "c2"}});
+        assert!(matches(
+            &record,
+            &json!({"payload.coop_id": {"$in": ["c1", "c2"]}})
+        ));
+        assert!(!matches(
+            &record,
+            &json!({"payload.coop_id": {"$in": ["c1"]}})
+        ));
+        assert!(!matches(&record, &json!({"payload.coop_id": {"$in": []}})));
+    }
+
+    #[test]
+    fn or_match() {
+        let record = json!({"id": "x", "payload": {"name": "beta", "type": "typeB"}});
+        let either = json!({"$or": [{"payload.type": "typeA"}, {"payload.type": "typeB"}]});
+        assert!(matches(&record, &either));
+        assert!(!matches(
+            &record,
+            &json!({"$or": [{"payload.type": "typeA"}, {"payload.name": "gamma"}]})
+        ));
+        assert!(!matches(
+            &record,
+            &json!({"payload.name": "gamma", "$or": either["$or"]})
+        ));
+        assert!(matches(
+            &record,
+            &json!({"$or": [{"$or": [{"id": "y"}, {"id": "x"}]}]})
+        ));
+        assert!(!matches(&record, &json!({"$or": []})));
+    }
 }
@@ -1,79 +1,57 @@
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use merkql::broker::BrokerRef;
-use merkql::consumer::{ConsumerConfig, OffsetReset};
 use merkql::record::ProducerRecord;
-use meshql_core::{Envelope, MeshqlError, Repository, Result};
-use serde_json::Value;
+use meshql_core::{
+    forbid_hidden, Clock, Envelope, IdStrategy, ListOptions, MeshqlError, Repository, Result,
+    SystemClock,
+};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::sync::{Arc, Mutex, MutexGuard};
 
+use crate::view::TopicView;
+
+/// Reads are answered from an in-memory view of the topic, which each call
+/// first catches up with the records written since the last.
 pub struct MerkqlRepository {
     broker: BrokerRef,
     topic: String,
+    view: Mutex<TopicView>,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
 }
 
 impl MerkqlRepository {
     pub fn new(broker: BrokerRef, topic: impl Into<String>) -> Self {
+        let topic = topic.into();
         Self {
+            view: Mutex::new(TopicView::new(broker.clone(), &topic)),
             broker,
-            topic: topic.into(),
+            topic,
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
         }
     }
 
-    /// Read all envelopes from the topic.
-    fn read_all_envelopes(&self) -> Result<Vec<Envelope>> {
-        let mut consumer = merkql::broker::Broker::consumer(
-            &self.broker,
-            ConsumerConfig {
-                group_id: uuid::Uuid::new_v4().to_string(),
-                auto_commit: false,
-                offset_reset: OffsetReset::Earliest,
-            },
-        );
-        consumer
-            .subscribe(&[&self.topic])
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-
-        let mut envelopes = Vec::new();
-        loop {
-            let batch = consumer
-                .poll(Duration::from_millis(50))
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            if batch.is_empty() {
-                break;
-            }
-            for rec in batch {
-                let json: Value = serde_json::from_str(&rec.value)
-                    .map_err(|e| MeshqlError::Parse(e.to_string()))?;
-                let env: Envelope =
-                    serde_json::from_value(json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-                envelopes.push(env);
-            }
-        }
-        Ok(envelopes)
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
     }
 
-    /// Find the latest version of an envelope by ID, filtered by created_at milliseconds <= cutoff_ms.
-    /// Uses millisecond precision to avoid sub-millisecond precision issues.
-    fn latest_for_id(envelopes: &[Envelope], id: &str, cutoff_ms: i64) -> Option<Envelope> {
-        envelopes
-            .iter()
-            .filter(|env| env.id == id && env.created_at.timestamp_millis() <= cutoff_ms)
-            .max_by_key(|env| env.created_at.timestamp_millis())
-            .cloned()
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    /// Get the latest non-deleted version of each unique ID (no temporal filter — uses max created_at).
-    fn latest_per_id_not_deleted(envelopes: &[Envelope]) -> Vec<Envelope> {
-        let mut latest: HashMap<String, Envelope> = HashMap::new();
-        for env in envelopes {
-            let entry = latest.entry(env.id.clone()).or_insert_with(|| env.clone());
-            if env.created_at >= entry.created_at {
-                *entry = env.clone();
-            }
-        }
-        latest.into_values().filter(|env| !env.deleted).collect()
+    /// The topic's view, caught up with everything written so far.
+    fn caught_up(&self) -> Result<MutexGuard<'_, TopicView>> {
+        let mut view = self.view.lock().unwrap();
+        view.catch_up()?;
+        Ok(view)
     }
 
     fn write_envelope(&self, envelope: &Envelope) -> Result<()> {
@@ -91,18 +69,34 @@ impl MerkqlRepository {
 #[async_trait]
 impl Repository for MerkqlRepository {
     async fn create(&self, envelope: Envelope, _tokens: &[String]) -> Result<Envelope> {
+        let mut envelope = envelope;
+        if envelope.id.is_empty() {
+            envelope.id = self.ids.generate();
+        }
         self.write_envelope(&envelope)?;
         Ok(envelope)
     }
 
     async fn read(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
         &self,
         id: &str,
         _tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>> {
         // Convert optional cutoff to milliseconds; use current time if None
-        let cutoff_ms = at.unwrap_or_else(Utc::now).timestamp_millis();
+        let cutoff_ms = at.unwrap_or_else(|| self.clock.now()).timestamp_millis();
         // Add 1ms to handle sub-millisecond precision when reading "now"
         // (records created at the same millisecond should be included)
         let cutoff_ms = if at.is_none() {
@@ -110,26 +104,44 @@ impl Repository for MerkqlRepository {
         } else {
             cutoff_ms
         };
-        let envelopes = self.read_all_envelopes()?;
-        let result = Self::latest_for_id(&envelopes, id, cutoff_ms);
-        // Return None if deleted
-        Ok(result.filter(|env| !env.deleted))
+        Ok(self.caught_up()?.latest(id, cutoff_ms))
+    }
+
+    async fn list_with(&self, _tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        Ok(options.page(self.caught_up()?.latest_each(options)))
+    }
+
+    async fn history(&self, id: &str, _tokens: &[String]) -> Result<Vec<Envelope>> {
+        Ok(self.caught_up()?.history(id))
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        _tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        Ok(self
+            .caught_up()?
+            .created_between(from.timestamp_millis(), to.timestamp_millis()))
     }
 
-    async fn list(&self, _tokens: &[String]) -> Result<Vec<Envelope>> {
-        let envelopes = self.read_all_envelopes()?;
-        Ok(Self::latest_per_id_not_deleted(&envelopes))
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
     }
 
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
         let current = self.read(id, tokens, None).await?;
         match current {
-            None => Ok(false),
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
             Some(env) => {
                 let deleted_env = Envelope {
                     id: env.id,
                     payload: env.payload,
-                    created_at: Utc::now(),
+                    created_at: self.clock.now(),
                     deleted: true,
                     authorized_tokens: env.authorized_tokens,
                 };
@@ -139,6 +151,14 @@ impl Repository for MerkqlRepository {
         }
     }
 
+    /// Always fails: a merkql topic is an append-only Merkle log, with no
+    /// compaction to hand a purge to.
+    async fn purge(&self, _id: &str, _tokens: &[String]) -> Result<u64> {
+        Err(MeshqlError::Storage(
+            "merkql topics are append-only; their records can't be purged".to_string(),
+        ))
+    }
+
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
@@ -173,4 +193,40 @@ impl Repository for MerkqlRepository {
         }
         Ok(results)
     }
+
+    async fn ping(&self) -> Result<()> {
+        // The broker is embedded in this process, so there's no connection to lose.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use merkql::broker::{Broker, BrokerConfig};
+    use meshql_core::Stash;
+
+    #[tokio::test]
+    async fn reads_only_consume_records_written_since_the_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let broker = Broker::open(BrokerConfig::new(dir.path())).unwrap();
+        let repo = MerkqlRepository::new(broker, "hens");
+        let star = vec!["*".to_string()];
+
+        for i in 0..1000 {
+            let env = Envelope::new(format!("hen-{i}"), Stash::new(), star.clone());
+            repo.create(env, &star).await.unwrap();
+        }
+        assert_eq!(repo.list(&star).await.unwrap().len(), 1000);
+        assert_eq!(repo.view.lock().unwrap().consumed(), 1000);
+
+        assert!(repo.read("hen-7", &star, None).await.unwrap().is_some());
+        assert_eq!(repo.view.lock().unwrap().consumed(), 1000);
+
+        assert!(repo.remove("hen-7", &star).await.unwrap());
+        assert!(repo.read("hen-7", &star, None).await.unwrap().is_none());
+        assert_eq!(repo.view.lock().unwrap().consumed(), 1001);
+        assert_eq!(repo.list(&star).await.unwrap().len(), 999);
+        assert_eq!(repo.history("hen-7", &star).await.unwrap().len(), 2);
+    }
 }
@@ -3,7 +3,7 @@ use merkql::broker::{Broker, BrokerConfig};
 #[allow(unused_imports)]
 use meshql_cert::steps::farm;
 use meshql_cert::CertWorld;
-use meshql_core::{GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{DeleteMode, GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
 use meshql_merkql::{MerkqlRepository, MerkqlSearcher};
 use meshql_server::build_app;
 use std::sync::Arc;
@@ -20,6 +20,11 @@ type Query {
     getFarm(id: ID, at: Int): Farm
     getFarms(name: String, at: Int): [Farm]
 }
+type Mutation {
+    createFarm(input: FarmInput): Farm
+    updateFarm(id: ID, input: FarmInput): Farm
+    deleteFarm(id: ID): Boolean
+}
 ";
 
 const COOP_GRAPHQL: &str = "
@@ -126,18 +131,23 @@ async fn build_farm_server() -> String {
                 path: "/farm/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: coop_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen_repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     let app = build_app(server_config).await.unwrap();
@@ -0,0 +1,122 @@
+use merkql::broker::{Broker, BrokerConfig};
+use meshql_core::testing as cert;
+use meshql_merkql::{MerkqlRepository, MerkqlSearcher};
+use tempfile::TempDir;
+
+async fn create_searcher() -> (TempDir, MerkqlRepository, MerkqlSearcher) {
+    let dir = tempfile::tempdir().unwrap();
+    let broker = Broker::open(BrokerConfig::new(dir.path())).unwrap();
+    let topic = format!("searcher_{}", uuid::Uuid::new_v4().simple());
+    let repo = MerkqlRepository::new(broker.clone(), &topic);
+    let searcher = MerkqlSearcher::new(broker, &topic);
+    cert::seed_searcher_data(&repo).await;
+    (dir, repo, searcher)
+}
+
+#[tokio::test]
+async fn should_return_empty_for_nonexistent_id() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_empty_result_for_nonexistent(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_by_id() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_by_id(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_by_name() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_by_name(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_type() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_by_type(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_type_and_name() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_by_type_and_name(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_list() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_in_list(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_comparison() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_comparisons(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_or_groups() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_or_groups(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_return_empty_for_nonexistent_type() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_empty_array_for_nonexistent_type(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_respect_limit() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_respects_limit(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_handle_empty_query() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_empty_query(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_count_matches() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_count(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_check_existence() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_exists(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_page_with_limit_and_offset() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_pages_with_limit_and_offset(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_sort_by_count_desc() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_sorts_by_count_desc(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_collapse_distinct_values() {
+    let (_dir, _repo, searcher) = create_searcher().await;
+    cert::test_searcher_collapses_distinct_values(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_stream_large_results() {
+    let (_dir, repo, searcher) = create_searcher().await;
+    cert::test_searcher_streams_large_results(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_creation_window() {
+    let (_dir, repo, searcher) = create_searcher().await;
+    cert::test_searcher_filters_by_creation_window(&repo, &searcher).await;
+}
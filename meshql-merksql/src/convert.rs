@@ -1,5 +1,5 @@
 use chrono::{DateTime, TimeZone, Utc};
-use meshql_core::{Envelope, Stash};
+use meshql_core::{insert_metadata, Envelope, Stash};
 use serde_json::{json, Value};
 
 /// Flatten an Envelope into a flat JSON object for storage in merkql topic.
@@ -53,10 +53,11 @@ pub fn flat_json_to_envelope(json: &Value) -> Option<Envelope> {
     })
 }
 
-/// Convert an Envelope to a result Stash (payload fields + id merged in).
+/// Convert an Envelope to a result Stash (payload fields + id and metadata merged in).
 pub fn envelope_to_stash(env: &Envelope) -> Stash {
     let mut stash = env.payload.clone();
     stash.insert("id".to_string(), json!(env.id));
+    insert_metadata(&mut stash, env.created_at.timestamp_millis(), env.deleted);
     stash
 }
 
@@ -2,6 +2,7 @@ mod convert;
 mod matcher;
 pub mod repository;
 pub mod searcher;
+mod view;
 
 pub use repository::MerksqlRepository;
 pub use searcher::MerksqlSearcher;
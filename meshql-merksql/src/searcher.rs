@@ -1,8 +1,10 @@
 use async_trait::async_trait;
-use handlebars::Handlebars;
 use merkql::broker::BrokerRef;
 use merkql::consumer::{ConsumerConfig, OffsetReset};
-use meshql_core::{Envelope, MeshqlError, Result, Searcher, Stash};
+use meshql_core::{
+    check_or_groups, distinct_from_args, distinct_stashes, render_template, sort_from_args,
+    sort_stashes, CreatedWindow, Envelope, MeshqlError, MissingKey, Result, Searcher, Stash,
+};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
@@ -31,18 +33,24 @@ impl MerksqlSearcher {
         }
     }
 
-    /// Render a Handlebars template with the given args Stash.
+    /// Render the template, leaving out the `limit`, `offset`, `sort`,
+    /// `distinct` and creation window args.
     fn render_template(&self, template: &str, args: &Stash) -> Result<Value> {
-        let mut hbs = Handlebars::new();
-        hbs.set_strict_mode(false);
-        let rendered = hbs
-            .render_template(template, args)
-            .map_err(|e| MeshqlError::Template(e.to_string()))?;
-        serde_json::from_str(&rendered).map_err(|e| MeshqlError::Parse(e.to_string()))
+        let mut filter_args = args.clone();
+        for key in ["limit", "offset", "sort", "distinct"] {
+            filter_args.remove(key);
+        }
+        CreatedWindow::remove_args(&mut filter_args);
+        let rendered = render_template(template, &filter_args, MissingKey::Empty)?;
+        let query =
+            serde_json::from_str(&rendered).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        check_or_groups(&query)?;
+        Ok(query)
     }
 
     /// Read all envelopes from the topic, returning the latest non-deleted per ID
-    /// filtered by envelope.created_at milliseconds <= cutoff_ms.
+    /// filtered by envelope.created_at milliseconds <= cutoff_ms, in the order
+    /// their ids were first written.
     fn scan_latest(&self, cutoff_ms: i64) -> Result<Vec<(Envelope, Value)>> {
         let mut consumer = merkql::broker::Broker::consumer(
             &self.broker,
@@ -56,7 +64,8 @@ impl MerksqlSearcher {
             .subscribe(&[&self.topic])
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        let mut by_id: HashMap<String, (i64, Envelope, Value)> = HashMap::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut latest: Vec<(Envelope, Value)> = Vec::new();
 
         loop {
             let batch = consumer
@@ -79,21 +88,42 @@ impl MerksqlSearcher {
                     continue;
                 }
 
-                let entry = by_id
-                    .entry(env.id.clone())
-                    .or_insert_with(|| (env_ms, env.clone(), raw_json.clone()));
-                if env_ms >= entry.0 {
-                    *entry = (env_ms, env, raw_json);
+                match positions.get(&env.id) {
+                    Some(&i) => {
+                        if env_ms >= latest[i].0.created_at.timestamp_millis() {
+                            latest[i] = (env, raw_json);
+                        }
+                    }
+                    None => {
+                        positions.insert(env.id.clone(), latest.len());
+                        latest.push((env, raw_json));
+                    }
                 }
             }
         }
 
-        let results: Vec<(Envelope, Value)> = by_id
-            .into_values()
-            .filter(|(_, env, _)| !env.deleted)
-            .map(|(_, env, raw)| (env, raw))
-            .collect();
+        let results: Vec<(Envelope, Value)> =
+            latest.into_iter().filter(|(env, _)| !env.deleted).collect();
+
+        Ok(results)
+    }
 
+    /// Latest, non-deleted versions as of `at` that match the rendered template
+    /// and were created in the window, one per `distinct` value.
+    fn matching(&self, template: &str, args: &Stash, at: i64) -> Result<Vec<Stash>> {
+        let query = self.render_template(template, args)?;
+        let distinct = distinct_from_args(args)?;
+        let window = CreatedWindow::from_args(args)?;
+        let mut results: Vec<Stash> = self
+            .scan_latest(at)?
+            .into_iter()
+            .filter(|(env, _)| window.contains(env.created_at.timestamp_millis()))
+            .filter(|(_, raw_json)| matcher::matches(raw_json, &query))
+            .map(|(env, _)| convert::envelope_to_stash(&env))
+            .collect();
+        if let Some(field) = distinct {
+            distinct_stashes(&mut results, &field);
+        }
         Ok(results)
     }
 }
@@ -107,15 +137,7 @@ impl Searcher for MerksqlSearcher {
         _creds: &[String],
         at: i64,
     ) -> Result<Option<Stash>> {
-        let query = self.render_template(template, args)?;
-        let records = self.scan_latest(at)?;
-
-        let result = records
-            .into_iter()
-            .find(|(_, raw_json)| matcher::matches(raw_json, &query))
-            .map(|(env, _)| convert::envelope_to_stash(&env));
-
-        Ok(result)
+        Ok(self.matching(template, args, at)?.into_iter().next())
     }
 
     async fn find_all(
@@ -125,24 +147,48 @@ impl Searcher for MerksqlSearcher {
         _creds: &[String],
         at: i64,
     ) -> Result<Vec<Stash>> {
-        let query = self.render_template(template, args)?;
         let limit = args
             .get("limit")
             .and_then(|v| v.as_i64())
             .map(|v| v as usize);
+        let offset = args
+            .get("offset")
+            .and_then(|v| v.as_i64())
+            .map(|v| v as usize);
+        let sort = sort_from_args(args)?;
 
-        let records = self.scan_latest(at)?;
-
-        let mut results: Vec<Stash> = records
-            .into_iter()
-            .filter(|(_, raw_json)| matcher::matches(raw_json, &query))
-            .map(|(env, _)| convert::envelope_to_stash(&env))
-            .collect();
+        let mut results = self.matching(template, args, at)?;
 
+        // Sorted and paged results end with id so consecutive pages neither overlap nor skip
+        if !sort.is_empty() || limit.is_some() || offset.is_some() {
+            sort_stashes(&mut results, &sort);
+        }
+        if let Some(off) = offset {
+            results.drain(..off.min(results.len()));
+        }
         if let Some(lim) = limit {
             results.truncate(lim);
         }
 
         Ok(results)
     }
+
+    async fn count(&self, template: &str, args: &Stash, _creds: &[String], at: i64) -> Result<u64> {
+        Ok(self.matching(template, args, at)?.len() as u64)
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        Ok(!self.matching(template, args, at)?.is_empty())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        // The broker is embedded in this process, so there's no connection to lose.
+        Ok(())
+    }
 }
@@ -0,0 +1,120 @@
+//! A topic's envelopes held in memory, caught up from the log as it grows.
+
+use merkql::broker::BrokerRef;
+use merkql::consumer::{Consumer, ConsumerConfig, OffsetReset};
+use meshql_core::{Envelope, ListOptions, MeshqlError, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::convert;
+
+/// Every version of each id written to a topic. One consumer stays subscribed
+/// for the view's lifetime, so [`catch_up`](Self::catch_up) only reads the
+/// records written since it last ran rather than the whole topic.
+pub(crate) struct TopicView {
+    broker: BrokerRef,
+    topic: String,
+    /// Subscribed on the first catch-up.
+    consumer: Option<Consumer>,
+    /// Each id's versions in log order; a tombstone is just the latest one.
+    versions: HashMap<String, Vec<Envelope>>,
+    /// Records applied so far, to tell a catch-up from a rescan.
+    consumed: u64,
+}
+
+impl TopicView {
+    pub(crate) fn new(broker: BrokerRef, topic: impl Into<String>) -> Self {
+        Self {
+            broker,
+            topic: topic.into(),
+            consumer: None,
+            versions: HashMap::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Apply the records written since the last call.
+    pub(crate) fn catch_up(&mut self) -> Result<()> {
+        if self.consumer.is_none() {
+            let mut consumer = merkql::broker::Broker::consumer(
+                &self.broker,
+                ConsumerConfig {
+                    group_id: uuid::Uuid::new_v4().to_string(),
+                    auto_commit: false,
+                    offset_reset: OffsetReset::Earliest,
+                },
+            );
+            consumer
+                .subscribe(&[&self.topic])
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            self.consumer = Some(consumer);
+        }
+        let consumer = self.consumer.as_mut().expect("subscribed above");
+
+        loop {
+            let batch = consumer
+                .poll(Duration::from_millis(50))
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            if batch.is_empty() {
+                break;
+            }
+            for rec in batch {
+                let json: Value = serde_json::from_str(&rec.value)
+                    .map_err(|e| MeshqlError::Parse(e.to_string()))?;
+                if let Some(env) = convert::flat_json_to_envelope(&json) {
+                    self.versions.entry(env.id.clone()).or_default().push(env);
+                }
+                self.consumed += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// The latest version of `id` created at or before `cutoff_ms`, deleted or not.
+    /// Uses millisecond precision to avoid sub-millisecond precision issues.
+    pub(crate) fn latest(&self, id: &str, cutoff_ms: i64) -> Option<Envelope> {
+        self.versions
+            .get(id)?
+            .iter()
+            .filter(|env| env.created_at.timestamp_millis() <= cutoff_ms)
+            .max_by_key(|env| env.created_at.timestamp_millis())
+            .cloned()
+    }
+
+    /// The latest version of each id that `options` lists, unpaged.
+    pub(crate) fn latest_each(&self, options: ListOptions) -> Vec<Envelope> {
+        self.versions
+            .values()
+            .filter_map(|versions| versions.iter().max_by_key(|env| env.created_at))
+            .filter(|env| options.lists(env.deleted))
+            .cloned()
+            .collect()
+    }
+
+    /// Every version of `id`, oldest first.
+    pub(crate) fn history(&self, id: &str) -> Vec<Envelope> {
+        let mut versions = self.versions.get(id).cloned().unwrap_or_default();
+        // Stable, so versions written in the same millisecond keep log order.
+        versions.sort_by_key(|env| env.created_at.timestamp_millis());
+        versions
+    }
+
+    /// Every version of every id created from `from_ms` up to but not
+    /// including `to_ms`, oldest first.
+    pub(crate) fn created_between(&self, from_ms: i64, to_ms: i64) -> Vec<Envelope> {
+        let mut versions: Vec<Envelope> = self
+            .versions
+            .values()
+            .flatten()
+            .filter(|env| (from_ms..to_ms).contains(&env.created_at.timestamp_millis()))
+            .cloned()
+            .collect();
+        versions.sort_by_key(|env| env.created_at.timestamp_millis());
+        versions
+    }
+
+    pub(crate) fn consumed(&self) -> u64 {
+        self.consumed
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{client_options, MongoRepository, MongoSearcher};
+use meshql_core::{
+    Auth, BackendFactory, MeshqlError, Repository, Result, Searcher, StorageManifest,
+};
+use std::sync::Arc;
+
+/// Opens `backend: mongo` manifest storage. `database` is required.
+pub struct MongoBackend {
+    auth: Arc<dyn Auth>,
+}
+
+impl MongoBackend {
+    pub fn new(auth: Arc<dyn Auth>) -> Self {
+        Self { auth }
+    }
+
+    fn database(storage: &StorageManifest) -> Result<&str> {
+        storage.database.as_deref().ok_or_else(|| {
+            MeshqlError::Validation(format!(
+                "mongo storage for '{}' needs a database",
+                storage.collection
+            ))
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl BackendFactory for MongoBackend {
+    fn name(&self) -> &str {
+        "mongo"
+    }
+
+    async fn repository(&self, storage: &StorageManifest) -> Result<Arc<dyn Repository>> {
+        let options = client_options(&storage.uri, storage.tls.as_ref()).await?;
+        let repo = MongoRepository::new_with_options(
+            options,
+            Self::database(storage)?,
+            &storage.collection,
+            Arc::clone(&self.auth),
+            true,
+        )
+        .await?
+        .with_id_strategy(storage.id_strategy)
+        .with_auth_policy(storage.auth_policy);
+        Ok(Arc::new(repo))
+    }
+
+    async fn searcher(&self, storage: &StorageManifest) -> Result<Arc<dyn Searcher>> {
+        let options = client_options(&storage.uri, storage.tls.as_ref()).await?;
+        let searcher = MongoSearcher::new_with_options(
+            options,
+            Self::database(storage)?,
+            &storage.collection,
+            Arc::clone(&self.auth),
+        )?
+        .with_auth_policy(storage.auth_policy);
+        Ok(Arc::new(searcher))
+    }
+}
@@ -8,7 +8,9 @@
 //!   MONGO_URI - MongoDB connection string (default mongodb://127.0.0.1:27017)
 //!   DB_NAME   - database name (default meshql_perf)
 
-use meshql_core::{GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{
+    DeleteMode, GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig,
+};
 use meshql_mongo::{MongoRepository, MongoSearcher};
 use std::sync::Arc;
 
@@ -422,68 +424,83 @@ async fn main() -> anyhow::Result<()> {
                 path: "/farm/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: coop.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumer/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: consumer.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/lay_report/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: lay_report.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_deposit/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: storage_deposit.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_withdrawal/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: storage_withdrawal.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_transfer/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container_transfer.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumption_report/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: consumption_report.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_inventory/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container_inventory.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen_productivity/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen_productivity.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/farm_output/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm_output.repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     meshql_server::run(config).await
@@ -0,0 +1,42 @@
+use meshql_core::{MeshqlError, Result, TlsConfig, TlsMode};
+use mongodb::options::{ClientOptions, Tls, TlsOptions};
+
+/// Options for `uri`, with `tls` applied over whatever TLS settings it carries.
+///
+/// Mongo reads the client certificate and its private key from one PEM file,
+/// so `client_cert` must hold both and `client_key` isn't accepted. Without
+/// OpenSSL the driver can't skip just the hostname check, so
+/// [`TlsMode::VerifyCa`] isn't accepted either.
+pub async fn client_options(uri: &str, tls: Option<&TlsConfig>) -> Result<ClientOptions> {
+    let mut options = ClientOptions::parse(uri)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+    if let Some(tls) = tls {
+        options.tls = Some(to_tls(tls)?);
+    }
+    Ok(options)
+}
+
+fn to_tls(tls: &TlsConfig) -> Result<Tls> {
+    if tls.client_key.is_some() {
+        return Err(MeshqlError::Validation(
+            "mongo reads the client key from client_cert; leave client_key unset".into(),
+        ));
+    }
+    let allow_invalid_certificates = match tls.mode {
+        TlsMode::Disable => return Ok(Tls::Disabled),
+        TlsMode::Require => Some(true),
+        TlsMode::VerifyCa => {
+            return Err(MeshqlError::Validation(
+                "mongo can't check the CA without the hostname; use verify_full or require".into(),
+            ))
+        }
+        TlsMode::VerifyFull => None,
+    };
+    let options = TlsOptions::builder()
+        .allow_invalid_certificates(allow_invalid_certificates)
+        .ca_file_path(tls.ca_file.clone())
+        .cert_key_file_path(tls.client_cert.clone())
+        .build();
+    Ok(Tls::Enabled(options))
+}
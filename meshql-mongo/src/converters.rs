@@ -1,6 +1,6 @@
 use bson::{doc, Bson, Document};
 use chrono::DateTime;
-use meshql_core::{Envelope, Stash};
+use meshql_core::{insert_metadata, AuthPolicy, Envelope, Stash};
 use serde_json::{Map, Value};
 
 pub fn stash_to_doc(stash: &Stash) -> Document {
@@ -17,6 +17,27 @@ pub fn doc_to_stash(doc: &Document) -> Stash {
         .collect()
 }
 
+/// The condition on `authorizedTokens` letting a caller holding `tokens` see a
+/// document under `policy`. Envelopes written with `*` are public and callers
+/// holding `*` see everything.
+pub fn token_match(tokens: &[String], policy: AuthPolicy) -> Document {
+    if tokens.iter().any(|t| t == "*") {
+        return doc! { "$exists": true };
+    }
+    let held: Vec<Bson> = tokens
+        .iter()
+        .map(|s| Bson::String(s.clone()))
+        .chain([Bson::String("*".to_string())])
+        .collect();
+    match policy {
+        AuthPolicy::AnyOf => doc! { "$in": held },
+        AuthPolicy::AllOf => doc! {
+            "$not": { "$elemMatch": { "$nin": held } },
+            "$ne": [],
+        },
+    }
+}
+
 pub fn envelope_to_document(env: &Envelope) -> Document {
     let tokens: Vec<Bson> = env
         .authorized_tokens
@@ -55,13 +76,20 @@ pub fn document_to_envelope(doc: &Document) -> Option<Envelope> {
     })
 }
 
-/// Returns a Stash with payload fields + the top-level "id" merged in.
+/// Returns a Stash with payload fields + the top-level "id" and metadata merged in.
 /// This is what the Searcher returns — a flat map ready for GraphQL resolvers.
 pub fn document_to_result_stash(doc: &Document) -> Option<Stash> {
     let id = doc.get_str("id").ok()?.to_string();
     let payload_doc = doc.get_document("payload").ok()?;
     let mut stash = doc_to_stash(payload_doc);
     stash.insert("id".to_string(), Value::String(id));
+    if let Ok(created_at) = doc.get_datetime("createdAt") {
+        insert_metadata(
+            &mut stash,
+            created_at.timestamp_millis(),
+            doc.get_bool("deleted").unwrap_or(false),
+        );
+    }
     Some(stash)
 }
 
@@ -1,6 +1,10 @@
+pub mod backend;
+pub mod client;
 pub mod converters;
 pub mod repository;
 pub mod searcher;
 
+pub use backend::MongoBackend;
+pub use client::client_options;
 pub use repository::MongoRepository;
 pub use searcher::MongoSearcher;
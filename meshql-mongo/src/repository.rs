@@ -1,30 +1,128 @@
-use crate::converters::{document_to_envelope, envelope_to_document};
+use crate::client::client_options;
+use crate::converters::{document_to_envelope, envelope_to_document, token_match};
+use bson::oid::ObjectId;
 use bson::{doc, Bson, Document};
 use chrono::{DateTime, Utc};
-use meshql_core::{Auth, Envelope, MeshqlError, Repository, Result};
-use mongodb::Collection;
-use std::collections::HashMap;
+use meshql_core::{
+    conflict, forbid_hidden, supersedes, Auth, AuthPolicy, Clock, Envelope, IdStrategy,
+    ListOptions, MeshqlError, Repository, Result, SystemClock, TlsConfig,
+};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::ClientOptions;
+use mongodb::{Collection, Database, IndexModel};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 pub struct MongoRepository {
+    db: Database,
     collection: Collection<Document>,
     #[allow(dead_code)]
     auth: Arc<dyn Auth>,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+    policy: AuthPolicy,
 }
 
 impl MongoRepository {
+    /// Connect, ensuring the indexes `read` and `list` rely on exist.
     pub async fn new(
         uri: &str,
         db_name: &str,
         collection_name: &str,
         auth: Arc<dyn Auth>,
     ) -> Result<Self> {
-        let client = mongodb::Client::with_uri_str(uri)
-            .await
+        Self::new_with_indexes(uri, db_name, collection_name, auth, true).await
+    }
+
+    /// Like [`MongoRepository::new`], but leaves the collection's indexes alone
+    /// unless `ensure_indexes` is set, for deployments that manage them externally.
+    pub async fn new_with_indexes(
+        uri: &str,
+        db_name: &str,
+        collection_name: &str,
+        auth: Arc<dyn Auth>,
+        ensure_indexes: bool,
+    ) -> Result<Self> {
+        let options = client_options(uri, None).await?;
+        Self::new_with_options(options, db_name, collection_name, auth, ensure_indexes).await
+    }
+
+    /// Like [`MongoRepository::new`], with `tls` applied over the URI's TLS settings.
+    pub async fn new_with_tls(
+        uri: &str,
+        db_name: &str,
+        collection_name: &str,
+        auth: Arc<dyn Auth>,
+        tls: &TlsConfig,
+    ) -> Result<Self> {
+        let options = client_options(uri, Some(tls)).await?;
+        Self::new_with_options(options, db_name, collection_name, auth, true).await
+    }
+
+    /// Connect with driver `options`, e.g. from [`crate::client_options`].
+    pub async fn new_with_options(
+        options: ClientOptions,
+        db_name: &str,
+        collection_name: &str,
+        auth: Arc<dyn Auth>,
+        ensure_indexes: bool,
+    ) -> Result<Self> {
+        let client = mongodb::Client::with_options(options)
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
         let db = client.database(db_name);
         let collection = db.collection::<Document>(collection_name);
-        Ok(Self { collection, auth })
+        if ensure_indexes {
+            Self::ensure_indexes(&collection).await?;
+        }
+        Ok(Self {
+            db,
+            collection,
+            auth,
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+            policy: AuthPolicy::default(),
+        })
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Match callers' tokens by `policy`, rather than [`AuthPolicy::AnyOf`].
+    pub fn with_auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// `{id: 1, createdAt: -1, _id: -1}` for latest-version lookups,
+    /// `{createdAt: 1}` for scans of a window of time and a multikey index on
+    /// `authorizedTokens` for the token match. Creating an index that already
+    /// exists is a no-op, so this is safe on every startup.
+    async fn ensure_indexes(collection: &Collection<Document>) -> Result<()> {
+        let indexes = [
+            IndexModel::builder()
+                .keys(doc! { "id": 1, "createdAt": -1, "_id": -1 })
+                .build(),
+            IndexModel::builder().keys(doc! { "createdAt": 1 }).build(),
+            IndexModel::builder()
+                .keys(doc! { "authorizedTokens": 1 })
+                .build(),
+        ];
+        collection
+            .create_indexes(indexes)
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
     }
 }
 
@@ -32,7 +130,7 @@ impl MongoRepository {
 impl Repository for MongoRepository {
     async fn create(&self, mut envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
         if envelope.id.is_empty() {
-            envelope.id = uuid::Uuid::new_v4().to_string();
+            envelope.id = self.ids.generate();
         }
         envelope.authorized_tokens = tokens.to_vec();
 
@@ -51,19 +149,31 @@ impl Repository for MongoRepository {
         tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>> {
-        let at_bson = bson::DateTime::from_chrono(at.unwrap_or_else(Utc::now));
-        let bson_tokens: Vec<Bson> = tokens.iter().map(|s| Bson::String(s.clone())).collect();
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
 
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        let at_bson = bson::DateTime::from_chrono(at.unwrap_or_else(|| self.clock.now()));
         let pipeline = vec![
             doc! {
                 "$match": {
                     "id": id,
                     "createdAt": { "$lte": at_bson },
-                    "authorizedTokens": { "$in": bson_tokens },
                 }
             },
-            doc! { "$sort": { "createdAt": -1 } },
+            // Versions stamped in the same millisecond fall back to `_id`, which
+            // the driver generates in insertion order.
+            doc! { "$sort": { "createdAt": -1, "_id": -1 } },
             doc! { "$limit": 1 },
+            doc! { "$match": { "authorizedTokens": token_match(tokens, self.policy) } },
         ];
 
         let mut cursor = self
@@ -81,32 +191,164 @@ impl Repository for MongoRepository {
                 .deserialize_current()
                 .map_err(|e| MeshqlError::Storage(e.to_string()))?;
             let env = document_to_envelope(&doc);
-            Ok(env.filter(|e| !e.deleted))
+            Ok(env)
         } else {
             Ok(None)
         }
     }
 
-    async fn list(&self, tokens: &[String]) -> Result<Vec<Envelope>> {
-        let now = bson::DateTime::now();
-        let bson_tokens: Vec<Bson> = tokens.iter().map(|s| Bson::String(s.clone())).collect();
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        // `$limit` must be positive
+        if options.limit == Some(0) {
+            return Ok(Vec::new());
+        }
+        let now = bson::DateTime::from_chrono(self.clock.now());
+        let mut pipeline = vec![
+            doc! { "$match": { "createdAt": { "$lte": now } } },
+            doc! { "$sort": { "id": 1, "createdAt": -1, "_id": -1 } },
+            doc! {
+                "$group": {
+                    "_id": "$id",
+                    "doc": { "$first": "$$ROOT" }
+                }
+            },
+            doc! { "$replaceRoot": { "newRoot": "$doc" } },
+            doc! { "$match": { "authorizedTokens": token_match(tokens, self.policy) } },
+        ];
+        match options.deleted() {
+            Some(true) => pipeline.push(doc! { "$match": { "deleted": true } }),
+            Some(false) => pipeline.push(doc! { "$match": { "deleted": { "$ne": true } } }),
+            None => {}
+        }
+        if options.is_paged() {
+            pipeline.push(doc! { "$sort": { "id": 1 } });
+            pipeline.push(doc! { "$skip": options.offset as i64 });
+            if let Some(limit) = options.limit {
+                pipeline.push(doc! { "$limit": limit as i64 });
+            }
+        }
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
+        let mut results = Vec::new();
+        while cursor
+            .advance()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?
+        {
+            let doc = cursor
+                .deserialize_current()
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            if let Some(env) = document_to_envelope(&doc) {
+                results.push(env);
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        let now = bson::DateTime::from_chrono(self.clock.now());
         let pipeline = vec![
+            doc! { "$match": { "createdAt": { "$lte": now } } },
+            doc! { "$sort": { "id": 1, "createdAt": -1, "_id": -1 } },
+            doc! {
+                "$group": {
+                    "_id": "$id",
+                    "deleted": { "$first": "$deleted" },
+                    "authorizedTokens": { "$first": "$authorizedTokens" }
+                }
+            },
             doc! {
                 "$match": {
-                    "createdAt": { "$lte": now },
-                    "authorizedTokens": { "$in": bson_tokens },
+                    "deleted": { "$ne": true },
+                    "authorizedTokens": token_match(tokens, self.policy),
+                }
+            },
+            doc! { "$count": "count" },
+        ];
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        // `$count` yields no document at all when nothing matched.
+        if !cursor
+            .advance()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?
+        {
+            return Ok(0);
+        }
+        let doc = cursor
+            .deserialize_current()
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        match doc.get("count") {
+            Some(Bson::Int32(n)) => Ok(*n as u64),
+            Some(Bson::Int64(n)) => Ok(*n as u64),
+            other => Err(MeshqlError::Storage(format!(
+                "unexpected $count result: {other:?}"
+            ))),
+        }
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        let pipeline = vec![
+            doc! {
+                "$match": {
+                    "id": id,
+                    "authorizedTokens": token_match(tokens, self.policy),
                 }
             },
-            doc! { "$sort": { "id": 1, "createdAt": -1 } },
+            doc! { "$sort": { "createdAt": 1, "_id": 1 } },
+        ];
+
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        let mut results = Vec::new();
+        while cursor
+            .advance()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?
+        {
+            let doc = cursor
+                .deserialize_current()
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            if let Some(env) = document_to_envelope(&doc) {
+                results.push(env);
+            }
+        }
+
+        Ok(results)
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let pipeline = vec![
             doc! {
-                "$group": {
-                    "_id": "$id",
-                    "doc": { "$first": "$$ROOT" }
+                "$match": {
+                    "createdAt": {
+                        "$gte": bson::DateTime::from_chrono(from),
+                        "$lt": bson::DateTime::from_chrono(to),
+                    },
+                    "authorizedTokens": token_match(tokens, self.policy),
                 }
             },
-            doc! { "$replaceRoot": { "newRoot": "$doc" } },
-            doc! { "$match": { "deleted": { "$ne": true } } },
+            doc! { "$sort": { "createdAt": 1, "_id": 1 } },
         ];
 
         let mut cursor = self
@@ -132,13 +374,20 @@ impl Repository for MongoRepository {
         Ok(results)
     }
 
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
         let current = self.read(id, tokens, None).await?;
         match current {
-            None => Ok(false),
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
             Some(mut env) => {
                 env.deleted = true;
-                env.created_at = Utc::now();
+                env.created_at = self.clock.now();
                 let doc = envelope_to_document(&env);
                 self.collection
                     .insert_one(doc)
@@ -149,6 +398,72 @@ impl Repository for MongoRepository {
         }
     }
 
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let result = self
+            .collection
+            .delete_many(doc! {
+                "id": id,
+                "authorizedTokens": token_match(tokens, self.policy),
+            })
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(result.deleted_count)
+    }
+
+    /// Upserts the new version under an `_id` derived from the newest
+    /// version's, guarded by a filter on that `_id`. Every write based on the
+    /// same version asks for the same `_id`, so only the first inserts and
+    /// the rest match its document, whichever process they run in.
+    async fn create_if_unchanged(
+        &self,
+        mut envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        if envelope.id.is_empty() {
+            envelope.id = self.ids.generate();
+        }
+        envelope.authorized_tokens = tokens.to_vec();
+
+        let latest = self
+            .collection
+            .find_one(doc! { "id": &envelope.id })
+            .sort(doc! { "createdAt": -1, "_id": -1 })
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let base = match &latest {
+            Some(latest) => {
+                let created_at_ms = latest
+                    .get_datetime("createdAt")
+                    .map(|at| at.timestamp_millis())
+                    .unwrap_or_default();
+                let deleted = latest.get_bool("deleted").unwrap_or(false);
+                if supersedes(created_at_ms, deleted, expected_created_at) {
+                    return Err(conflict(&envelope.id));
+                }
+                latest.get_object_id("_id").ok()
+            }
+            None => None,
+        };
+
+        let successor = successor_id(&envelope.id, base);
+        let upserted = self
+            .collection
+            .update_one(
+                doc! { "_id": successor },
+                doc! { "$setOnInsert": envelope_to_document(&envelope) },
+            )
+            .upsert(true)
+            .await;
+        match upserted {
+            Ok(result) if result.upserted_id.is_some() => Ok(envelope),
+            Ok(_) => Err(conflict(&envelope.id)),
+            // Concurrent upserts of one `_id` can also lose on the unique index.
+            Err(e) if is_duplicate_key(&e) => Err(conflict(&envelope.id)),
+            Err(e) => Err(MeshqlError::Storage(e.to_string())),
+        }
+    }
+
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
@@ -162,19 +477,17 @@ impl Repository for MongoRepository {
     }
 
     async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
-        let bson_tokens: Vec<Bson> = tokens.iter().map(|s| Bson::String(s.clone())).collect();
         let bson_ids: Vec<Bson> = ids.iter().map(|s| Bson::String(s.clone())).collect();
-        let now = bson::DateTime::now();
+        let now = bson::DateTime::from_chrono(self.clock.now());
 
         let pipeline = vec![
             doc! {
                 "$match": {
                     "id": { "$in": bson_ids },
                     "createdAt": { "$lte": now },
-                    "authorizedTokens": { "$in": bson_tokens },
                 }
             },
-            doc! { "$sort": { "id": 1, "createdAt": -1 } },
+            doc! { "$sort": { "id": 1, "createdAt": -1, "_id": -1 } },
             doc! {
                 "$group": {
                     "_id": "$id",
@@ -182,7 +495,12 @@ impl Repository for MongoRepository {
                 }
             },
             doc! { "$replaceRoot": { "newRoot": "$doc" } },
-            doc! { "$match": { "deleted": { "$ne": true } } },
+            doc! {
+                "$match": {
+                    "deleted": { "$ne": true },
+                    "authorizedTokens": token_match(tokens, self.policy),
+                }
+            },
         ];
 
         let mut cursor = self
@@ -213,11 +531,84 @@ impl Repository for MongoRepository {
         ids: &[String],
         tokens: &[String],
     ) -> Result<HashMap<String, bool>> {
-        let mut results = HashMap::new();
-        for id in ids {
-            let deleted = self.remove(id, tokens).await?;
-            results.insert(id.clone(), deleted);
+        let current = self.read_many(ids, tokens).await?;
+        let removed: HashSet<String> = current.iter().map(|env| env.id.clone()).collect();
+        let now = self.clock.now();
+        let tombstones: Vec<Document> = current
+            .into_iter()
+            .map(|env| {
+                envelope_to_document(&Envelope {
+                    created_at: now,
+                    deleted: true,
+                    ..env
+                })
+            })
+            .collect();
+        // insert_many rejects an empty batch.
+        if !tombstones.is_empty() {
+            self.collection
+                .insert_many(tombstones)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
         }
-        Ok(results)
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.db
+            .run_command(doc! { "ping": 1 })
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+}
+
+/// The `_id` of the version a conditional write puts over `base`, the `_id`
+/// of the newest version of `id`, if it has one. It sorts after `base`, so a
+/// tie in `createdAt` still falls to the newer version.
+fn successor_id(id: &str, base: Option<ObjectId>) -> ObjectId {
+    let mut bytes = [0u8; 12];
+    let seed = match base {
+        Some(base) => {
+            let base = base.bytes();
+            let seconds = u32::from_be_bytes([base[0], base[1], base[2], base[3]]);
+            bytes[..4].copy_from_slice(&seconds.wrapping_add(1).to_be_bytes());
+            fnv1a(&base)
+        }
+        None => fnv1a(id.as_bytes()),
+    };
+    bytes[4..].copy_from_slice(&seed.to_be_bytes());
+    ObjectId::from_bytes(bytes)
+}
+
+/// 64-bit FNV-1a, which every process computes the same.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn is_duplicate_key(e: &mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(failure)) if failure.code == 11000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_based_on_one_version_share_a_successor_that_sorts_after_it() {
+        let base = ObjectId::new();
+        assert_eq!(successor_id("a", Some(base)), successor_id("b", Some(base)));
+        assert!(successor_id("a", Some(base)) > base);
+        assert_ne!(successor_id("a", Some(base)), successor_id("a", None));
+        assert_eq!(successor_id("a", None), successor_id("a", None));
+        assert_ne!(successor_id("a", None), successor_id("b", None));
     }
 }
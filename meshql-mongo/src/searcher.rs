@@ -1,15 +1,22 @@
-use crate::converters::{document_to_result_stash, stash_to_doc};
+use crate::client::client_options;
+use crate::converters::{document_to_result_stash, stash_to_doc, token_match};
 use bson::{doc, Bson, Document};
-use handlebars::Handlebars;
-use meshql_core::{Auth, MeshqlError, Result, Searcher, Stash};
-use mongodb::Collection;
+use futures::TryStreamExt;
+use meshql_core::{
+    check_or_groups, distinct_from_args, is_projectable, render_template, sort_from_args, Auth,
+    AuthPolicy, CreatedWindow, MeshqlError, MissingKey, Result, Searcher, SortField, SortKey,
+    Stash, StashStream, TlsConfig,
+};
+use mongodb::options::ClientOptions;
+use mongodb::{Collection, Database};
 use std::sync::Arc;
 
 pub struct MongoSearcher {
+    db: Database,
     collection: Collection<Document>,
     #[allow(dead_code)]
     auth: Arc<dyn Auth>,
-    handlebars: Handlebars<'static>,
+    policy: AuthPolicy,
 }
 
 impl MongoSearcher {
@@ -19,49 +26,85 @@ impl MongoSearcher {
         collection_name: &str,
         auth: Arc<dyn Auth>,
     ) -> Result<Self> {
-        let client = mongodb::Client::with_uri_str(uri)
-            .await
+        let options = client_options(uri, None).await?;
+        Self::new_with_options(options, db_name, collection_name, auth)
+    }
+
+    /// Like [`MongoSearcher::new`], with `tls` applied over the URI's TLS settings.
+    pub async fn new_with_tls(
+        uri: &str,
+        db_name: &str,
+        collection_name: &str,
+        auth: Arc<dyn Auth>,
+        tls: &TlsConfig,
+    ) -> Result<Self> {
+        let options = client_options(uri, Some(tls)).await?;
+        Self::new_with_options(options, db_name, collection_name, auth)
+    }
+
+    /// Connect with driver `options`, e.g. from [`crate::client_options`].
+    pub fn new_with_options(
+        options: ClientOptions,
+        db_name: &str,
+        collection_name: &str,
+        auth: Arc<dyn Auth>,
+    ) -> Result<Self> {
+        let client = mongodb::Client::with_options(options)
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
         let db = client.database(db_name);
         let collection = db.collection::<Document>(collection_name);
-        let mut handlebars = Handlebars::new();
-        handlebars.set_strict_mode(false);
         Ok(Self {
+            db,
             collection,
             auth,
-            handlebars,
+            policy: AuthPolicy::default(),
         })
     }
 
+    /// Match callers' tokens by `policy`, rather than [`AuthPolicy::AnyOf`].
+    pub fn with_auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
     fn render_template(&self, template: &str, args: &Stash) -> Result<String> {
-        self.handlebars
-            .render_template(template, &serde_json::Value::Object(args.clone()))
-            .map_err(|e| MeshqlError::Template(e.to_string()))
+        // Paging args are applied as pipeline stages, never as payload filters
+        let mut filter_args = args.clone();
+        filter_args.remove("limit");
+        filter_args.remove("offset");
+        filter_args.remove("sort");
+        filter_args.remove("distinct");
+        CreatedWindow::remove_args(&mut filter_args);
+        render_template(template, &filter_args, MissingKey::Empty)
     }
 
+    /// The latest, live version of each matching document created in the
+    /// window, keeping the one with the lowest id of each value of a
+    /// `distinct` payload field.
     fn build_pipeline(
         &self,
         query_json: &str,
         creds: &[String],
         at: i64,
-        limit: Option<i64>,
+        distinct: Option<&SortField>,
+        created: &CreatedWindow,
     ) -> Result<Vec<Document>> {
         let at_bson = bson::DateTime::from_millis(at);
-        let bson_tokens: Vec<Bson> = creds.iter().map(|s| Bson::String(s.clone())).collect();
 
         let json_val: serde_json::Value =
             serde_json::from_str(query_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        check_or_groups(&json_val)?;
         let obj = json_val
             .as_object()
             .ok_or_else(|| MeshqlError::Parse("Query must be a JSON object".to_string()))?;
         let mut query_doc = stash_to_doc(obj);
 
         query_doc.insert("createdAt", doc! { "$lte": at_bson });
-        query_doc.insert("authorizedTokens", doc! { "$in": bson_tokens });
+        query_doc.insert("authorizedTokens", token_match(creds, self.policy));
 
         let mut pipeline = vec![
             doc! { "$match": &query_doc },
-            doc! { "$sort": { "id": 1, "createdAt": -1 } },
+            doc! { "$sort": { "id": 1, "createdAt": -1, "_id": -1 } },
             doc! {
                 "$group": {
                     "_id": "$id",
@@ -71,26 +114,94 @@ impl MongoSearcher {
             doc! { "$replaceRoot": { "newRoot": "$doc" } },
             doc! { "$match": { "deleted": { "$ne": true } } },
         ];
+        if !created.is_unbounded() {
+            let mut window = Document::new();
+            if let Some(after) = created.after {
+                window.insert("$gte", bson::DateTime::from_millis(after));
+            }
+            if let Some(before) = created.before {
+                window.insert("$lt", bson::DateTime::from_millis(before));
+            }
+            pipeline.push(doc! { "$match": { "createdAt": window } });
+        }
+        if let Some(SortField::Payload(name)) = distinct {
+            pipeline.extend([
+                doc! { "$sort": { "id": 1 } },
+                doc! {
+                    "$group": {
+                        "_id": format!("$payload.{name}"),
+                        "doc": { "$first": "$$ROOT" }
+                    }
+                },
+                doc! { "$replaceRoot": { "newRoot": "$doc" } },
+            ]);
+        }
 
+        Ok(pipeline)
+    }
+
+    /// `$sort`, `$skip` and `$limit` stages for the paging args.
+    fn page_stages(limit: Option<i64>, offset: Option<i64>, sort: &[SortKey]) -> Vec<Document> {
+        let mut pipeline = Vec::new();
+        // Sorted and paged results end with id so consecutive pages neither overlap nor skip
+        if !sort.is_empty() || limit.is_some() || offset.is_some() {
+            let mut order = Document::new();
+            for key in sort {
+                let field = match &key.field {
+                    SortField::Id => "id".to_string(),
+                    SortField::Payload(name) => format!("payload.{name}"),
+                };
+                order.insert(field, if key.descending { -1 } else { 1 });
+            }
+            if !order.contains_key("id") {
+                order.insert("id", 1);
+            }
+            pipeline.push(doc! { "$sort": order });
+        }
+        if let Some(o) = offset {
+            pipeline.push(doc! { "$skip": o });
+        }
         if let Some(l) = limit {
             pipeline.push(doc! { "$limit": l });
         }
+        pipeline
+    }
 
-        Ok(pipeline)
+    /// A `$project` stage keeping `id` and only `fields` of the payload, when
+    /// they are given and are plain field names.
+    fn projection(fields: Option<&[String]>) -> Option<Document> {
+        let fields = fields.filter(|f| is_projectable(f))?;
+        let payload = if fields.is_empty() {
+            doc! { "$literal": {} }
+        } else {
+            fields
+                .iter()
+                .map(|f| (f.clone(), Bson::String(format!("$payload.{f}"))))
+                .collect()
+        };
+        Some(doc! {
+            "$project": { "_id": 0, "id": 1, "createdAt": 1, "deleted": 1, "payload": payload }
+        })
     }
-}
 
-#[async_trait::async_trait]
-impl Searcher for MongoSearcher {
-    async fn find(
+    async fn find_one(
         &self,
         template: &str,
         args: &Stash,
+        fields: Option<&[String]>,
         creds: &[String],
         at: i64,
     ) -> Result<Option<Stash>> {
         let query_json = self.render_template(template, args)?;
-        let pipeline = self.build_pipeline(&query_json, creds, at, Some(1))?;
+        let mut pipeline = self.build_pipeline(
+            &query_json,
+            creds,
+            at,
+            None,
+            &CreatedWindow::from_args(args)?,
+        )?;
+        pipeline.extend(Self::page_stages(Some(1), None, &[]));
+        pipeline.extend(Self::projection(fields));
 
         let mut cursor = self
             .collection
@@ -112,16 +223,124 @@ impl Searcher for MongoSearcher {
         }
     }
 
-    async fn find_all(
+    async fn find_many(
         &self,
         template: &str,
         args: &Stash,
+        fields: Option<&[String]>,
         creds: &[String],
         at: i64,
     ) -> Result<Vec<Stash>> {
+        self.stream_many(template, args, fields, creds, at)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Matching documents, pulled from the cursor one batch at a time.
+    async fn stream_many(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: Option<&[String]>,
+        creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
         let query_json = self.render_template(template, args)?;
         let limit = args.get("limit").and_then(|v| v.as_i64());
-        let pipeline = self.build_pipeline(&query_json, creds, at, limit)?;
+        let offset = args.get("offset").and_then(|v| v.as_i64());
+        let sort = sort_from_args(args)?;
+        let distinct = distinct_from_args(args)?;
+        let mut pipeline = self.build_pipeline(
+            &query_json,
+            creds,
+            at,
+            distinct.as_ref(),
+            &CreatedWindow::from_args(args)?,
+        )?;
+        pipeline.extend(Self::page_stages(limit, offset, &sort));
+        pipeline.extend(Self::projection(fields));
+
+        let cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        Ok(Box::pin(
+            cursor
+                .map_err(|e| MeshqlError::Storage(e.to_string()))
+                .try_filter_map(|doc| async move { Ok(document_to_result_stash(&doc)) }),
+        ))
+    }
+}
+
+#[async_trait::async_trait]
+impl Searcher for MongoSearcher {
+    async fn find(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        self.find_one(template, args, None, creds, at).await
+    }
+
+    async fn find_all(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        self.find_many(template, args, None, creds, at).await
+    }
+
+    async fn find_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        self.find_one(template, args, Some(fields), creds, at).await
+    }
+
+    async fn find_all_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        self.find_many(template, args, Some(fields), creds, at)
+            .await
+    }
+
+    async fn find_stream(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
+        self.stream_many(template, args, None, creds, at).await
+    }
+
+    async fn count(&self, template: &str, args: &Stash, creds: &[String], at: i64) -> Result<u64> {
+        let query_json = self.render_template(template, args)?;
+        let distinct = distinct_from_args(args)?;
+        let mut pipeline = self.build_pipeline(
+            &query_json,
+            creds,
+            at,
+            distinct.as_ref(),
+            &CreatedWindow::from_args(args)?,
+        )?;
+        pipeline.push(doc! { "$count": "count" });
 
         let mut cursor = self
             .collection
@@ -129,20 +348,60 @@ impl Searcher for MongoSearcher {
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        let mut results = Vec::new();
-        while cursor
+        // $count emits no document at all when nothing matched
+        if !cursor
             .advance()
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?
         {
-            let doc = cursor
-                .deserialize_current()
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            if let Some(stash) = document_to_result_stash(&doc) {
-                results.push(stash);
-            }
+            return Ok(0);
+        }
+        let doc = cursor
+            .deserialize_current()
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        match doc.get("count") {
+            Some(Bson::Int32(n)) => Ok(*n as u64),
+            Some(Bson::Int64(n)) => Ok(*n as u64),
+            other => Err(MeshqlError::Parse(format!(
+                "unexpected $count result: {other:?}"
+            ))),
         }
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        let query_json = self.render_template(template, args)?;
+        let mut pipeline = self.build_pipeline(
+            &query_json,
+            creds,
+            at,
+            None,
+            &CreatedWindow::from_args(args)?,
+        )?;
+        pipeline.extend(Self::page_stages(Some(1), None, &[]));
 
-        Ok(results)
+        let mut cursor = self
+            .collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        cursor
+            .advance()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.db
+            .run_command(doc! { "ping": 1 })
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
     }
 }
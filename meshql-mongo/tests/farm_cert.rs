@@ -2,7 +2,9 @@ use cucumber::World as _;
 #[allow(unused_imports)]
 use meshql_cert::steps::farm;
 use meshql_cert::CertWorld;
-use meshql_core::{GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{
+    DeleteMode, GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig,
+};
 use meshql_mongo::{MongoRepository, MongoSearcher};
 use meshql_server::build_app;
 use std::sync::Arc;
@@ -24,6 +26,11 @@ type Query {
     getFarm(id: ID, at: Int): Farm
     getFarms(name: String, at: Int): [Farm]
 }
+type Mutation {
+    createFarm(input: FarmInput): Farm
+    updateFarm(id: ID, input: FarmInput): Farm
+    deleteFarm(id: ID): Boolean
+}
 "#;
 
 const COOP_GRAPHQL: &str = r#"
@@ -143,18 +150,23 @@ async fn build_farm_server(mongo_uri: &str) -> String {
                 path: "/farm/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: coop_repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen_repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     let app = build_app(server_config).await.unwrap();
@@ -0,0 +1,67 @@
+use bson::Document;
+use meshql_core::NoAuth;
+use meshql_mongo::MongoRepository;
+use std::sync::Arc;
+use testcontainers::runners::AsyncRunner;
+use testcontainers_modules::mongo::Mongo;
+
+async fn index_names(uri: &str, collection_name: &str) -> Vec<String> {
+    let client = mongodb::Client::with_uri_str(uri).await.unwrap();
+    client
+        .database("test_db")
+        .collection::<Document>(collection_name)
+        .list_index_names()
+        .await
+        // A collection nothing was written to has no indexes to list.
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn new_ensures_the_read_and_token_indexes() {
+    let container = Mongo::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(27017).await.unwrap();
+    let uri = format!("mongodb://127.0.0.1:{port}");
+    let collection_name = format!("test_{}", uuid::Uuid::new_v4().simple());
+
+    MongoRepository::new(&uri, "test_db", &collection_name, Arc::new(NoAuth))
+        .await
+        .unwrap();
+    // Constructing again over the same collection must not fail.
+    MongoRepository::new(&uri, "test_db", &collection_name, Arc::new(NoAuth))
+        .await
+        .unwrap();
+
+    let names = index_names(&uri, &collection_name).await;
+    assert!(
+        names.contains(&"id_1_createdAt_-1__id_-1".to_string()),
+        "{names:?}"
+    );
+    assert!(names.contains(&"createdAt_1".to_string()), "{names:?}");
+    assert!(
+        names.contains(&"authorizedTokens_1".to_string()),
+        "{names:?}"
+    );
+}
+
+#[tokio::test]
+async fn new_with_indexes_can_leave_indexes_to_the_operator() {
+    let container = Mongo::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(27017).await.unwrap();
+    let uri = format!("mongodb://127.0.0.1:{port}");
+    let collection_name = format!("test_{}", uuid::Uuid::new_v4().simple());
+
+    MongoRepository::new_with_indexes(&uri, "test_db", &collection_name, Arc::new(NoAuth), false)
+        .await
+        .unwrap();
+
+    let names = index_names(&uri, &collection_name).await;
+    assert!(
+        !names.contains(&"id_1_createdAt_-1__id_-1".to_string()),
+        "{names:?}"
+    );
+    assert!(!names.contains(&"createdAt_1".to_string()), "{names:?}");
+    assert!(
+        !names.contains(&"authorizedTokens_1".to_string()),
+        "{names:?}"
+    );
+}
@@ -1,5 +1,5 @@
 use meshql_core::testing as cert;
-use meshql_core::NoAuth;
+use meshql_core::{AuthPolicy, MockClock, NoAuth};
 use meshql_mongo::MongoRepository;
 use std::sync::Arc;
 use testcontainers::runners::AsyncRunner;
@@ -16,6 +16,22 @@ async fn create_repo() -> (MongoRepository, impl std::any::Any) {
     (repo, container)
 }
 
+/// Two repositories over one collection, each with its own client, as two
+/// server instances would have.
+async fn create_repos() -> (MongoRepository, MongoRepository, impl std::any::Any) {
+    let container = Mongo::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(27017).await.unwrap();
+    let uri = format!("mongodb://127.0.0.1:{port}");
+    let collection_name = format!("test_{}", uuid::Uuid::new_v4().simple());
+    let first = MongoRepository::new(&uri, "test_db", &collection_name, Arc::new(NoAuth))
+        .await
+        .unwrap();
+    let second = MongoRepository::new(&uri, "test_db", &collection_name, Arc::new(NoAuth))
+        .await
+        .unwrap();
+    (first, second, container)
+}
+
 #[tokio::test]
 async fn create_should_store_and_return_envelope() {
     let (repo, _c) = create_repo().await;
@@ -52,12 +68,24 @@ async fn read_many_should_retrieve_multiple_envelopes() {
     cert::test_read_many_should_retrieve_multiple_envelopes(&repo).await;
 }
 
+#[tokio::test]
+async fn read_many_should_return_latest_live_versions() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_returns_latest_live_versions(&repo).await;
+}
+
 #[tokio::test]
 async fn remove_many_should_delete_multiple_envelopes() {
     let (repo, _c) = create_repo().await;
     cert::test_remove_many_should_delete_multiple_envelopes(&repo).await;
 }
 
+#[tokio::test]
+async fn bulk_results_should_follow_input_order() {
+    let (repo, _c) = create_repo().await;
+    cert::test_bulk_results_follow_input_order(&repo).await;
+}
+
 #[tokio::test]
 async fn should_allow_multiple_versions_and_temporal_reads() {
     let (repo, _c) = create_repo().await;
@@ -69,3 +97,129 @@ async fn should_only_list_latest_version() {
     let (repo, _c) = create_repo().await;
     cert::test_list_shows_only_latest_version(&repo).await;
 }
+
+#[tokio::test]
+async fn should_hide_ids_whose_newest_version_is_hidden() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_hides_ids_whose_newest_version_is_hidden(&repo).await;
+}
+
+#[tokio::test]
+async fn should_hide_read_many_ids_whose_newest_version_is_hidden() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_hides_ids_whose_newest_version_is_hidden(&repo).await;
+}
+
+#[tokio::test]
+async fn update_should_merge_patch_into_new_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_update_should_merge_patch_into_new_version(&repo).await;
+}
+
+#[tokio::test]
+async fn updates_racing_on_one_version_should_conflict_once() {
+    let (repo, _c) = create_repo().await;
+    cert::test_updates_racing_on_one_version_should_conflict_once(&repo).await;
+}
+
+#[tokio::test]
+async fn updates_racing_across_repositories_should_conflict_once() {
+    let (first, second, _c) = create_repos().await;
+    cert::test_updates_racing_across_repositories_should_conflict_once(&first, &second).await;
+}
+
+#[tokio::test]
+async fn versions_should_be_stamped_by_the_clock() {
+    let clock = Arc::new(MockClock::default());
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_clock(clock.clone());
+    cert::test_versions_are_stamped_by_the_clock(&repo, &clock).await;
+}
+
+#[tokio::test]
+async fn latest_versions_should_be_read_as_of_the_clock() {
+    let clock = Arc::new(MockClock::default());
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_clock(clock.clone());
+    cert::test_latest_versions_are_read_as_of_the_clock(&repo, &clock).await;
+}
+
+#[tokio::test]
+async fn history_should_return_every_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_history_returns_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn created_between_should_return_versions_in_the_window() {
+    let (repo, _c) = create_repo().await;
+    cert::test_created_between_returns_versions_in_the_window(&repo).await;
+}
+
+#[tokio::test]
+async fn upsert_should_skip_unchanged_payloads() {
+    let (repo, _c) = create_repo().await;
+    cert::test_upsert_skips_unchanged_payloads(&repo).await;
+}
+
+#[tokio::test]
+async fn purge_should_delete_every_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_purge_deletes_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn non_matching_token_should_see_no_rows() {
+    let (repo, _c) = create_repo().await;
+    cert::test_non_matching_token_sees_no_rows(&repo).await;
+}
+
+#[tokio::test]
+async fn any_of_policy_should_need_one_shared_token() {
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_auth_policy(AuthPolicy::AnyOf);
+    cert::test_any_of_policy_needs_one_shared_token(&repo).await;
+}
+
+#[tokio::test]
+async fn all_of_policy_should_need_every_token() {
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_auth_policy(AuthPolicy::AllOf);
+    cert::test_all_of_policy_needs_every_token(&repo).await;
+}
+
+#[tokio::test]
+async fn writes_to_hidden_ids_should_not_be_authorized() {
+    let (repo, _c) = create_repo().await;
+    cert::test_writes_to_hidden_ids_are_not_authorized(&repo).await;
+}
+
+#[tokio::test]
+async fn count_should_match_list() {
+    let (repo, _c) = create_repo().await;
+    cert::test_count_matches_list(&repo).await;
+}
+
+#[tokio::test]
+async fn create_many_should_store_5000_listable_rows() {
+    let (repo, _c) = create_repo().await;
+    cert::test_create_many_should_store_5000_listable_rows(&repo).await;
+}
+
+#[tokio::test]
+async fn remove_many_reports_missing_ids() {
+    let (repo, _c) = create_repo().await;
+    cert::test_remove_many_reports_missing_ids(&repo).await;
+}
+
+#[tokio::test]
+async fn read_raw_returns_tombstone() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_raw_returns_tombstone(&repo).await;
+}
+
+#[tokio::test]
+async fn list_with_deleted_only_returns_removed() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_with_deleted_only_returns_removed(&repo).await;
+}
@@ -0,0 +1,51 @@
+use meshql_core::{Clock, Envelope, MockClock, NoAuth, Repository, Stash};
+use meshql_mongo::MongoRepository;
+use serde_json::json;
+use std::sync::Arc;
+use testcontainers::runners::AsyncRunner;
+use testcontainers_modules::mongo::Mongo;
+
+fn star() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn count(count: i64) -> Stash {
+    let mut payload = Stash::new();
+    payload.insert("count".to_string(), json!(count));
+    payload
+}
+
+#[tokio::test]
+async fn versions_in_the_same_millisecond_read_as_the_later_inserted() {
+    let container = Mongo::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(27017).await.unwrap();
+    let uri = format!("mongodb://127.0.0.1:{port}");
+    let collection_name = format!("test_{}", uuid::Uuid::new_v4().simple());
+    let clock = Arc::new(MockClock::new(
+        chrono::DateTime::from_timestamp_millis(1_700_000_000_000).unwrap(),
+    ));
+    let repo = MongoRepository::new(&uri, "test_db", &collection_name, Arc::new(NoAuth))
+        .await
+        .unwrap()
+        .with_clock(clock.clone());
+
+    let at = clock.now();
+    let env = Envelope {
+        created_at: at,
+        ..Envelope::new("tie-id", count(0), star())
+    };
+    repo.create(env, &star()).await.unwrap();
+
+    // The clock never moves, so every version shares `at` to the millisecond.
+    for n in 1..=5 {
+        repo.update("tie-id", count(n), &star()).await.unwrap();
+
+        let at_boundary = repo.read("tie-id", &star(), Some(at)).await.unwrap();
+        assert_eq!(at_boundary.unwrap().payload["count"], json!(n));
+        let latest = repo.read("tie-id", &star(), None).await.unwrap();
+        assert_eq!(latest.unwrap().payload["count"], json!(n));
+        let listed = repo.list(&star()).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].payload["count"], json!(n));
+    }
+}
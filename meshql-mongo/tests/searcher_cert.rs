@@ -6,6 +6,11 @@ use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::mongo::Mongo;
 
 async fn create_searcher() -> (MongoSearcher, impl std::any::Any) {
+    let (_repo, searcher, container) = create_repo_and_searcher().await;
+    (searcher, container)
+}
+
+async fn create_repo_and_searcher() -> (MongoRepository, MongoSearcher, impl std::any::Any) {
     let container = Mongo::default().start().await.unwrap();
     let port = container.get_host_port_ipv4(27017).await.unwrap();
     let uri = format!("mongodb://127.0.0.1:{port}");
@@ -20,7 +25,7 @@ async fn create_searcher() -> (MongoSearcher, impl std::any::Any) {
     let searcher = MongoSearcher::new(&uri, "test_db", &collection_name, Arc::new(NoAuth))
         .await
         .unwrap();
-    (searcher, container)
+    (repo, searcher, container)
 }
 
 #[tokio::test]
@@ -53,6 +58,24 @@ async fn should_find_all_by_type_and_name() {
     cert::test_searcher_find_all_by_type_and_name(&searcher).await;
 }
 
+#[tokio::test]
+async fn should_find_all_in_list() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_find_all_in_list(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_comparison() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_find_all_comparisons(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_or_groups() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_find_all_or_groups(&searcher).await;
+}
+
 #[tokio::test]
 async fn should_return_empty_for_nonexistent_type() {
     let (searcher, _c) = create_searcher().await;
@@ -70,3 +93,51 @@ async fn should_handle_empty_query() {
     let (searcher, _c) = create_searcher().await;
     cert::test_searcher_empty_query(&searcher).await;
 }
+
+#[tokio::test]
+async fn should_count_matches() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_count(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_check_existence() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_exists(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_page_with_limit_and_offset() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_pages_with_limit_and_offset(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_sort_by_count_desc() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_sorts_by_count_desc(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_collapse_distinct_values() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_collapses_distinct_values(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_project_requested_fields() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_projects_requested_fields(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_stream_large_results() {
+    let (repo, searcher, _c) = create_repo_and_searcher().await;
+    cert::test_searcher_streams_large_results(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_creation_window() {
+    let (repo, searcher, _c) = create_repo_and_searcher().await;
+    cert::test_searcher_filters_by_creation_window(&repo, &searcher).await;
+}
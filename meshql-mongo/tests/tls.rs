@@ -0,0 +1,67 @@
+use meshql_core::{MeshqlError, TlsConfig, TlsMode};
+use meshql_mongo::client_options;
+use mongodb::options::{Tls, TlsOptions};
+use std::path::PathBuf;
+
+const URI: &str = "mongodb://localhost:27017";
+
+#[tokio::test]
+async fn client_options_should_carry_tls_settings() {
+    let tls = TlsConfig::new(TlsMode::VerifyFull)
+        .ca_file("/etc/ssl/db-ca.pem")
+        .client_cert("/etc/ssl/client.pem");
+    let options = client_options(URI, Some(&tls)).await.unwrap();
+
+    let Some(Tls::Enabled(tls)) = options.tls else {
+        panic!("expected TLS to be enabled, got {:?}", options.tls);
+    };
+    assert_eq!(tls.ca_file_path, Some(PathBuf::from("/etc/ssl/db-ca.pem")));
+    assert_eq!(
+        tls.cert_key_file_path,
+        Some(PathBuf::from("/etc/ssl/client.pem"))
+    );
+    assert_eq!(tls.allow_invalid_certificates, None);
+}
+
+#[tokio::test]
+async fn client_options_should_map_tls_modes() {
+    let require = client_options(URI, Some(&TlsConfig::new(TlsMode::Require)))
+        .await
+        .unwrap();
+    assert_eq!(
+        require.tls,
+        Some(Tls::Enabled(
+            TlsOptions::builder()
+                .allow_invalid_certificates(true)
+                .build()
+        ))
+    );
+
+    let disable = client_options(
+        "mongodb://localhost:27017/?tls=true",
+        Some(&TlsConfig::new(TlsMode::Disable)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(disable.tls, Some(Tls::Disabled));
+
+    let untouched = client_options("mongodb://localhost:27017/?tls=true", None)
+        .await
+        .unwrap();
+    assert!(matches!(untouched.tls, Some(Tls::Enabled(_))));
+}
+
+#[tokio::test]
+async fn client_options_should_reject_settings_mongo_cannot_honour() {
+    let separate_key = TlsConfig::new(TlsMode::VerifyFull)
+        .client_cert("client.pem")
+        .client_key("client.key");
+    assert!(matches!(
+        client_options(URI, Some(&separate_key)).await,
+        Err(MeshqlError::Validation(_))
+    ));
+    assert!(matches!(
+        client_options(URI, Some(&TlsConfig::new(TlsMode::VerifyCa))).await,
+        Err(MeshqlError::Validation(_))
+    ));
+}
@@ -0,0 +1,37 @@
+use crate::{MysqlRepository, MysqlSearcher};
+use async_trait::async_trait;
+use meshql_core::{BackendFactory, Repository, Result, Searcher, StorageManifest};
+use std::sync::Arc;
+
+/// Opens `backend: mysql` manifest storage, one table per `collection`.
+pub struct MysqlBackend;
+
+#[async_trait]
+impl BackendFactory for MysqlBackend {
+    fn name(&self) -> &str {
+        "mysql"
+    }
+
+    async fn repository(&self, storage: &StorageManifest) -> Result<Arc<dyn Repository>> {
+        let repo = MysqlRepository::new_with_table_and_config(
+            &storage.uri,
+            &storage.collection,
+            storage.pool.clone(),
+        )
+        .await?
+        .with_id_strategy(storage.id_strategy)
+        .with_payload_codec(storage.payload_codec)
+        .with_auth_policy(storage.auth_policy);
+        Ok(Arc::new(repo))
+    }
+
+    async fn searcher(&self, storage: &StorageManifest) -> Result<Arc<dyn Searcher>> {
+        let searcher = MysqlSearcher::new_with_table_and_config(
+            &storage.uri,
+            &storage.collection,
+            storage.pool.clone(),
+        )
+        .await?;
+        Ok(Arc::new(searcher))
+    }
+}
@@ -1,6 +1,11 @@
+mod backend;
+mod pool;
 mod query;
 mod repository;
 mod searcher;
 
+pub use backend::MysqlBackend;
+pub use meshql_core::PoolConfig;
+pub use pool::pool_options;
 pub use repository::MysqlRepository;
 pub use searcher::MysqlSearcher;
@@ -0,0 +1,24 @@
+use meshql_core::{MeshqlError, PoolConfig, Result};
+use sqlx::mysql::{MySqlPool, MySqlPoolOptions};
+
+/// sqlx pool options with `config` applied over the driver's defaults.
+pub fn pool_options(config: &PoolConfig) -> MySqlPoolOptions {
+    let mut options = MySqlPoolOptions::new();
+    if let Some(max) = config.max_connections {
+        options = options.max_connections(max);
+    }
+    if let Some(timeout) = config.acquire_timeout {
+        options = options.acquire_timeout(timeout);
+    }
+    if let Some(timeout) = config.idle_timeout {
+        options = options.idle_timeout(timeout);
+    }
+    options
+}
+
+pub(crate) async fn connect(database_url: &str, config: &PoolConfig) -> Result<MySqlPool> {
+    pool_options(config)
+        .connect(database_url)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))
+}
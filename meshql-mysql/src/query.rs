@@ -1,3 +1,7 @@
+use meshql_core::{
+    distinct_from_args, sort_from_args, AuthPolicy, CreatedWindow, Result, SortField, SortKey,
+};
+
 pub struct QueryPart {
     pub clause: String,
     pub values: Vec<String>,
@@ -8,40 +12,255 @@ pub struct QueryPart {
 /// Supported key patterns:
 /// - `"id"` -> `` `id` = ? ``
 /// - `"payload.field"` -> `JSON_UNQUOTE(JSON_EXTRACT(payload, '$.field')) = ?`
+/// - `{"$in": [...]}` values -> `IN (?, ...)`
+/// - `{"$gt": 20, "$lte": 40}` values -> one comparison per operator; numeric
+///   operands compare against the JSON value rather than its text
+/// - `{"$or": [{...}, {...}]}` -> `((...) OR (...))`, each group ANDed
 /// - Empty object `{}` -> empty clause (no filter)
 pub fn build_where(query_obj: &serde_json::Map<String, serde_json::Value>) -> QueryPart {
-    if query_obj.is_empty() {
-        return QueryPart {
-            clause: String::new(),
-            values: Vec::new(),
-        };
+    let mut values: Vec<String> = Vec::new();
+    let conditions = conditions(query_obj, &mut values);
+    QueryPart {
+        clause: conditions.join(" AND "),
+        values,
     }
+}
 
+/// One condition per key of `query_obj`, pushing their bind values to `values`.
+fn conditions(
+    query_obj: &serde_json::Map<String, serde_json::Value>,
+    values: &mut Vec<String>,
+) -> Vec<String> {
     let mut conditions: Vec<String> = Vec::new();
-    let mut values: Vec<String> = Vec::new();
 
     for (key, val) in query_obj {
-        let str_val = match val {
-            serde_json::Value::String(s) => s.clone(),
-            other => other.to_string(),
-        };
-
-        let condition = if key == "id" {
-            "`id` = ?".to_string()
-        } else if let Some(field) = key.strip_prefix("payload.") {
-            format!("JSON_UNQUOTE(JSON_EXTRACT(payload, '$.{field}')) = ?")
+        if key == "$or" {
+            conditions.push(or_group(val, values));
+            continue;
+        }
+        let field = key.strip_prefix("payload.");
+        let column = if key == "id" {
+            "`id`".to_string()
+        } else if let Some(field) = field {
+            format!("JSON_UNQUOTE(JSON_EXTRACT(payload, '$.{field}'))")
         } else {
             // Unknown key -- try it as a top-level column
-            format!("`{key}` = ?")
+            format!("`{key}`")
         };
 
-        conditions.push(condition);
-        values.push(str_val);
+        if let Some(ops) = comparisons(val) {
+            for (op, operand) in ops {
+                let (lhs, rhs) = match field {
+                    Some(field) if operand.is_number() => (
+                        format!("JSON_EXTRACT(payload, '$.{field}')"),
+                        "CAST(? AS DECIMAL(65, 30))",
+                    ),
+                    _ => (column.clone(), "?"),
+                };
+                conditions.push(if op == "!=" {
+                    format!("NOT ({lhs} <=> {rhs})")
+                } else {
+                    format!("{lhs} {op} {rhs}")
+                });
+                values.push(bind_value(operand));
+            }
+            continue;
+        }
+
+        match in_list(val) {
+            Some([]) => conditions.push("FALSE".to_string()),
+            Some(list) => {
+                let placeholders = vec!["?"; list.len()].join(", ");
+                conditions.push(format!("{column} IN ({placeholders})"));
+                values.extend(list.iter().map(bind_value));
+            }
+            None => {
+                conditions.push(format!("{column} = ?"));
+                values.push(bind_value(val));
+            }
+        }
     }
 
-    QueryPart {
-        clause: conditions.join(" AND "),
-        values,
+    conditions
+}
+
+/// `((a AND b) OR (c))` for the groups of an `{"$or": [...]}`. No groups
+/// match nothing.
+fn or_group(val: &serde_json::Value, values: &mut Vec<String>) -> String {
+    let groups: Vec<String> = val
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_object)
+        .map(|group| match conditions(group, values).as_slice() {
+            [] => "TRUE".to_string(),
+            conditions => format!("({})", conditions.join(" AND ")),
+        })
+        .collect();
+    if groups.is_empty() {
+        "FALSE".to_string()
+    } else {
+        format!("({})", groups.join(" OR "))
+    }
+}
+
+/// The candidates of an `{"$in": [...]}` value, which matches any one of them.
+fn in_list(val: &serde_json::Value) -> Option<&[serde_json::Value]> {
+    val.as_object()?.get("$in")?.as_array().map(Vec::as_slice)
+}
+
+/// The operators of a comparison object like `{"$gt": 20, "$lte": 40}` as SQL
+/// operators paired with their operands. `None` unless every key is one of
+/// `$gt`, `$gte`, `$lt`, `$lte` or `$ne`.
+fn comparisons(val: &serde_json::Value) -> Option<Vec<(&'static str, &serde_json::Value)>> {
+    let obj = val.as_object().filter(|obj| !obj.is_empty())?;
+    obj.iter()
+        .map(|(op, operand)| {
+            let op = match op.as_str() {
+                "$gt" => ">",
+                "$gte" => ">=",
+                "$lt" => "<",
+                "$lte" => "<=",
+                "$ne" => "!=",
+                _ => return None,
+            };
+            Some((op, operand))
+        })
+        .collect()
+}
+
+fn bind_value(val: &serde_json::Value) -> String {
+    match val {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Build a clause restricting rows to those whose stored `authorized_tokens`
+/// intersect the caller's `tokens`, or under [`AuthPolicy::AllOf`] are all
+/// held by the caller. Rows stored with `*` are visible to everyone.
+///
+/// Returns `None` when the caller holds `*` and may see every row.
+pub fn build_token_filter(tokens: &[String], policy: AuthPolicy) -> Option<QueryPart> {
+    if tokens.iter().any(|t| t == "*") {
+        return None;
+    }
+
+    let mut values: Vec<String> = tokens.to_vec();
+    values.push("*".to_string());
+    let placeholders = vec!["?"; values.len()].join(", ");
+
+    let clause = match policy {
+        AuthPolicy::AnyOf => {
+            format!("JSON_OVERLAPS(authorized_tokens, JSON_ARRAY({placeholders}))")
+        }
+        AuthPolicy::AllOf => format!(
+            "JSON_LENGTH(authorized_tokens) > 0 AND JSON_CONTAINS(JSON_ARRAY({placeholders}), authorized_tokens)"
+        ),
+    };
+    Some(QueryPart { clause, values })
+}
+
+/// The SQL expression reading `field` from a row.
+pub fn column(field: &SortField) -> String {
+    match field {
+        SortField::Id => "`id`".to_string(),
+        SortField::Payload(name) => format!("JSON_EXTRACT(payload, '$.{}')", name),
+    }
+}
+
+/// ` AND created_at_ms >= <after> AND created_at_ms < <before>` for whichever
+/// bounds of `window` are set. They're integers, so safe to interpolate.
+pub fn created_filter(window: &CreatedWindow) -> String {
+    let mut sql = String::new();
+    if let Some(after) = window.after {
+        sql.push_str(&format!(" AND created_at_ms >= {after}"));
+    }
+    if let Some(before) = window.before {
+        sql.push_str(&format!(" AND created_at_ms < {before}"));
+    }
+    sql
+}
+
+/// `limit`/`offset` paging, `sort` ordering, `distinct` and the creation
+/// window taken from the search args.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Page {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub sort: Vec<SortKey>,
+    pub distinct: Option<SortField>,
+    pub created: CreatedWindow,
+}
+
+impl Page {
+    /// Just the first match, for singleton searches.
+    pub fn first() -> Self {
+        Page {
+            limit: Some(1),
+            ..Default::default()
+        }
+    }
+
+    /// Split `limit`, `offset`, `sort`, `distinct` and the creation window out
+    /// of `args` so they are never rendered into the template as payload filters.
+    pub fn split(
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(serde_json::Map<String, serde_json::Value>, Page)> {
+        let sort = sort_from_args(args)?;
+        let distinct = distinct_from_args(args)?;
+        let created = CreatedWindow::from_args(args)?;
+        let mut rest = args.clone();
+        let limit = rest.remove("limit").and_then(|v| v.as_i64());
+        let offset = rest.remove("offset").and_then(|v| v.as_i64());
+        rest.remove("sort");
+        rest.remove("distinct");
+        CreatedWindow::remove_args(&mut rest);
+        Ok((
+            rest,
+            Page {
+                limit,
+                offset,
+                sort,
+                distinct,
+                created,
+            },
+        ))
+    }
+
+    pub fn is_unpaged(&self) -> bool {
+        self.limit.is_none() && self.offset.is_none()
+    }
+
+    /// `ORDER BY <sort>, id LIMIT ? OFFSET ?`; each part is omitted when not
+    /// requested. Paged results always end with `id` so consecutive pages neither
+    /// overlap nor skip rows.
+    pub fn clause(&self) -> String {
+        let mut order: Vec<String> = self
+            .sort
+            .iter()
+            .map(|key| {
+                let dir = if key.descending { "DESC" } else { "ASC" };
+                format!("{} {dir}", column(&key.field))
+            })
+            .collect();
+        if !order.is_empty() || !self.is_unpaged() {
+            order.push("`id`".to_string());
+        }
+
+        let mut sql = String::new();
+        if !order.is_empty() {
+            sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+        }
+        if !self.is_unpaged() {
+            sql.push_str(" LIMIT ? OFFSET ?");
+        }
+        sql
+    }
+
+    /// Bind values for the limit clause: a missing limit means "no limit".
+    pub fn values(&self) -> (i64, i64) {
+        (self.limit.unwrap_or(i64::MAX), self.offset.unwrap_or(0))
     }
 }
 
@@ -78,4 +297,148 @@ mod tests {
         );
         assert_eq!(part.values, vec!["Alice"]);
     }
+
+    #[test]
+    fn in_list_query_produces_in_condition() {
+        let mut obj = serde_json::Map::new();
+        obj.insert("payload.coop_id".to_string(), json!({"$in": ["c1", "c2"]}));
+        let part = build_where(&obj);
+        assert_eq!(
+            part.clause,
+            "JSON_UNQUOTE(JSON_EXTRACT(payload, '$.coop_id')) IN (?, ?)"
+        );
+        assert_eq!(part.values, vec!["c1", "c2"]);
+
+        obj.insert("payload.coop_id".to_string(), json!({"$in": []}));
+        assert_eq!(build_where(&obj).clause, "FALSE");
+    }
+
+    #[test]
+    fn or_query_produces_parenthesized_groups() {
+        let mut obj = serde_json::Map::new();
+        obj.insert(
+            "$or".to_string(),
+            json!([{"payload.type": "typeA"}, {"id": "x", "payload.type": "typeB"}]),
+        );
+        let part = build_where(&obj);
+        assert_eq!(
+            part.clause,
+            "((JSON_UNQUOTE(JSON_EXTRACT(payload, '$.type')) = ?) OR \
+             (`id` = ? AND JSON_UNQUOTE(JSON_EXTRACT(payload, '$.type')) = ?))"
+        );
+        assert_eq!(part.values, vec!["typeA", "x", "typeB"]);
+
+        obj.insert("$or".to_string(), json!([]));
+        assert_eq!(build_where(&obj).clause, "FALSE");
+    }
+
+    #[test]
+    fn comparison_query_produces_one_condition_per_operator() {
+        let mut obj = serde_json::Map::new();
+        obj.insert("payload.count".to_string(), json!({"$gt": 20}));
+        let part = build_where(&obj);
+        assert_eq!(
+            part.clause,
+            "JSON_EXTRACT(payload, '$.count') > CAST(? AS DECIMAL(65, 30))"
+        );
+        assert_eq!(part.values, vec!["20"]);
+
+        obj.insert(
+            "payload.count".to_string(),
+            json!({"$gte": 20, "$ne": "n/a"}),
+        );
+        assert_eq!(
+            build_where(&obj).clause,
+            "JSON_EXTRACT(payload, '$.count') >= CAST(? AS DECIMAL(65, 30)) AND \
+             NOT (JSON_UNQUOTE(JSON_EXTRACT(payload, '$.count')) <=> ?)"
+        );
+    }
+
+    #[test]
+    fn unknown_operator_is_an_equality_match() {
+        let mut obj = serde_json::Map::new();
+        obj.insert("payload.range".to_string(), json!({"$between": [1, 2]}));
+        let part = build_where(&obj);
+        assert_eq!(
+            part.clause,
+            "JSON_UNQUOTE(JSON_EXTRACT(payload, '$.range')) = ?"
+        );
+    }
+
+    #[test]
+    fn star_token_produces_no_filter() {
+        assert!(build_token_filter(&["*".to_string()], AuthPolicy::AnyOf).is_none());
+        assert!(build_token_filter(&["*".to_string()], AuthPolicy::AllOf).is_none());
+    }
+
+    #[test]
+    fn token_filter_includes_caller_tokens_and_star() {
+        let part = build_token_filter(&["alice".to_string()], AuthPolicy::AnyOf).unwrap();
+        assert_eq!(
+            part.clause,
+            "JSON_OVERLAPS(authorized_tokens, JSON_ARRAY(?, ?))"
+        );
+        assert_eq!(part.values, vec!["alice", "*"]);
+    }
+
+    #[test]
+    fn all_of_token_filter_needs_every_stored_token_held() {
+        let part = build_token_filter(&["alice".to_string()], AuthPolicy::AllOf).unwrap();
+        assert_eq!(
+            part.clause,
+            "JSON_LENGTH(authorized_tokens) > 0 AND JSON_CONTAINS(JSON_ARRAY(?, ?), authorized_tokens)"
+        );
+        assert_eq!(part.values, vec!["alice", "*"]);
+    }
+
+    #[test]
+    fn page_is_split_out_of_args() {
+        let mut args = serde_json::Map::new();
+        args.insert("type".to_string(), json!("typeA"));
+        args.insert("limit".to_string(), json!(2));
+        args.insert("offset".to_string(), json!(4));
+        let (rest, page) = Page::split(&args).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert!(rest.contains_key("type"));
+        assert_eq!(page.limit, Some(2));
+        assert_eq!(page.offset, Some(4));
+        assert_eq!(page.values(), (2, 4));
+    }
+
+    #[test]
+    fn unpaged_args_produce_no_clause() {
+        let (_, page) = Page::split(&serde_json::Map::new()).unwrap();
+        assert!(page.is_unpaged());
+        assert!(page.clause().is_empty());
+    }
+
+    #[test]
+    fn offset_without_limit_is_unbounded() {
+        let page = Page {
+            limit: None,
+            offset: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(page.clause(), " ORDER BY `id` LIMIT ? OFFSET ?");
+        assert_eq!(page.values(), (i64::MAX, 2));
+    }
+
+    #[test]
+    fn sort_orders_by_json_fields_then_id() {
+        let mut args = serde_json::Map::new();
+        args.insert("sort".to_string(), json!("payload.count:desc,payload.name"));
+        let (rest, page) = Page::split(&args).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(
+            page.clause(),
+            " ORDER BY JSON_EXTRACT(payload, '$.count') DESC, JSON_EXTRACT(payload, '$.name') ASC, `id`"
+        );
+    }
+
+    #[test]
+    fn invalid_sort_field_is_a_parse_error() {
+        let mut args = serde_json::Map::new();
+        args.insert("sort".to_string(), json!("count:desc"));
+        assert!(Page::split(&args).is_err());
+    }
 }
@@ -1,13 +1,31 @@
+use crate::query::build_token_filter;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use meshql_core::{Envelope, MeshqlError, Repository, Result, Stash};
-use sqlx::MySqlPool;
+use meshql_core::{
+    conflict, forbid_hidden, supersedes, AuthPolicy, Clock, Envelope, IdStrategy, ListOptions,
+    MeshqlError, PayloadCodec, PoolConfig, PoolStats, RepoStats, Repository, Result, Stash,
+    SystemClock,
+};
 use sqlx::Row;
-use std::collections::HashMap;
+use sqlx::{MySql, MySqlPool, QueryBuilder};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+/// MySQL caps a prepared statement at 65535 placeholders; each row binds 6 values.
+const MAX_ROWS_PER_INSERT: usize = 65535 / 6;
+/// Ids bound per bulk read, keeping the `IN` list a manageable size.
+const MAX_IDS_PER_SELECT: usize = 1000;
 
 pub struct MysqlRepository {
     pool: MySqlPool,
     table: String,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+    codec: PayloadCodec,
+    /// Fail reads of several rows on one that doesn't parse, rather than skip it.
+    strict: bool,
+    policy: AuthPolicy,
 }
 
 impl MysqlRepository {
@@ -16,10 +34,29 @@ impl MysqlRepository {
     }
 
     pub async fn new_with_table(database_url: &str, table: &str) -> Result<Self> {
-        let pool = MySqlPool::connect(database_url)
-            .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Self::new_with_table_and_config(database_url, table, PoolConfig::default()).await
+    }
+
+    /// Connect to the default table with a pool sized by `config`.
+    pub async fn new_with_config(database_url: &str, config: PoolConfig) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, "envelopes", config).await
+    }
 
+    pub async fn new_with_table_and_config(
+        database_url: &str,
+        table: &str,
+        config: PoolConfig,
+    ) -> Result<Self> {
+        let pool = crate::pool::connect(database_url, &config).await?;
+        Self::new_with_pool_and_table(pool, table).await
+    }
+
+    /// Use an existing pool, e.g. one shared with a [`crate::MysqlSearcher`].
+    pub async fn new_with_pool(pool: MySqlPool) -> Result<Self> {
+        Self::new_with_pool_and_table(pool, "envelopes").await
+    }
+
+    pub async fn new_with_pool_and_table(pool: MySqlPool, table: &str) -> Result<Self> {
         let create_sql = format!(
             r#"CREATE TABLE IF NOT EXISTS `{table}` (
                 id VARCHAR(255) NOT NULL,
@@ -27,8 +64,12 @@ impl MysqlRepository {
                 deleted TINYINT(1) NOT NULL DEFAULT 0,
                 authorized_tokens TEXT NOT NULL,
                 payload TEXT NOT NULL,
+                payload_bin LONGBLOB,
+                seq BIGINT NOT NULL AUTO_INCREMENT,
+                UNIQUE KEY uq_seq (seq),
                 INDEX idx_id (id),
-                INDEX idx_id_ts (id, created_at_ms)
+                INDEX idx_id_ts (id, created_at_ms),
+                INDEX idx_ts (created_at_ms)
             ) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4"#
         );
 
@@ -36,19 +77,191 @@ impl MysqlRepository {
             .execute(&pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Self::add_seq_column(&pool, table).await?;
+        Self::add_payload_bin_column(&pool, table).await?;
 
         Ok(Self {
             pool,
             table: table.to_string(),
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+            codec: PayloadCodec::default(),
+            strict: false,
+            policy: AuthPolicy::default(),
         })
     }
 
+    /// Number the rows of a table created before versions carried `seq`, which
+    /// breaks ties between versions written in the same millisecond.
+    async fn add_seq_column(pool: &MySqlPool, table: &str) -> Result<()> {
+        let (columns,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM information_schema.columns \
+             WHERE table_schema = DATABASE() AND table_name = ? AND column_name = 'seq'",
+        )
+        .bind(table)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        if columns == 0 {
+            let alter = format!(
+                "ALTER TABLE `{table}` ADD COLUMN seq BIGINT NOT NULL AUTO_INCREMENT, \
+                 ADD UNIQUE KEY uq_seq (seq)"
+            );
+            sqlx::query(&alter)
+                .execute(pool)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Give a table created before payloads could be binary its `payload_bin`
+    /// column. Its rows keep reading from `payload`.
+    async fn add_payload_bin_column(pool: &MySqlPool, table: &str) -> Result<()> {
+        let (columns,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM information_schema.columns \
+             WHERE table_schema = DATABASE() AND table_name = ? AND column_name = 'payload_bin'",
+        )
+        .bind(table)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        if columns == 0 {
+            sqlx::query(&format!(
+                "ALTER TABLE `{table}` ADD COLUMN payload_bin LONGBLOB"
+            ))
+            .execute(pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Write payloads with `codec`, rather than [`PayloadCodec::Json`].
+    pub fn with_payload_codec(mut self, codec: PayloadCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Fail `list` and `read_many` on a row whose payload or tokens don't
+    /// parse, rather than skip it with a warning.
+    pub fn with_strict_parsing(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Match callers' tokens by `policy`, rather than [`AuthPolicy::AnyOf`].
+    pub fn with_auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Decode `rows`, skipping any that don't parse unless strict.
+    fn decode_rows(&self, rows: &[sqlx::mysql::MySqlRow]) -> Result<Vec<Envelope>> {
+        let mut envelopes = Vec::with_capacity(rows.len());
+        for row in rows {
+            match Self::decode_row(row) {
+                Ok(env) => envelopes.push(env),
+                Err(MeshqlError::Parse(e)) if !self.strict => {
+                    warn!(table = %self.table, error = %e, "skipping a row that doesn't parse");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(envelopes)
+    }
+
+    /// The latest version of each of `ids`, skipping ids that are deleted or
+    /// whose latest version `tokens` can't see.
+    async fn read_latest(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let cutoff_ms = self.clock.now().timestamp_millis() + 1;
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("AND {}", f.clause))
+            .unwrap_or_default();
+        let table = &self.table;
+
+        let mut results = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_IDS_PER_SELECT) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let sql = format!(
+                r#"SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                       SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin,
+                              ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, seq DESC) AS rn
+                       FROM `{table}`
+                       WHERE id IN ({placeholders}) AND created_at_ms <= ?
+                   ) latest
+                   WHERE rn = 1 AND deleted = 0
+                   {token_where}"#
+            );
+
+            let mut q = sqlx::query(&sql);
+            for id in chunk {
+                q = q.bind(id.as_str());
+            }
+            q = q.bind(cutoff_ms);
+            for val in token_filter.iter().flat_map(|f| &f.values) {
+                q = q.bind(val.as_str());
+            }
+            let rows = q
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            results.extend(self.decode_rows(&rows)?);
+        }
+        Ok(results)
+    }
+
+    fn decode_row(r: &sqlx::mysql::MySqlRow) -> Result<Envelope> {
+        let env_id: String = r
+            .try_get("id")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let created_at_ms: i64 = r
+            .try_get("created_at_ms")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let deleted_flag: i8 = r
+            .try_get("deleted")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let tokens_json: String = r
+            .try_get("authorized_tokens")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let payload_json: String = r
+            .try_get("payload")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let payload_bin: Option<Vec<u8>> = r
+            .try_get("payload_bin")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        Self::row_to_envelope(
+            env_id,
+            created_at_ms,
+            deleted_flag,
+            tokens_json,
+            PayloadCodec::from_columns(&payload_json, payload_bin.as_deref())?,
+        )
+    }
+
     fn row_to_envelope(
         env_id: String,
         created_at_ms: i64,
         deleted_flag: i8,
         tokens_json: String,
-        payload_json: String,
+        payload: Stash,
     ) -> Result<Envelope> {
         let created_at = DateTime::from_timestamp_millis(created_at_ms)
             .ok_or_else(|| MeshqlError::Parse(format!("Invalid timestamp: {created_at_ms}")))?;
@@ -56,9 +269,6 @@ impl MysqlRepository {
         let authorized_tokens: Vec<String> =
             serde_json::from_str(&tokens_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
 
-        let payload: Stash =
-            serde_json::from_str(&payload_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-
         Ok(Envelope {
             id: env_id,
             payload,
@@ -73,21 +283,20 @@ impl MysqlRepository {
 impl Repository for MysqlRepository {
     async fn create(&self, mut envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
         if envelope.id.is_empty() {
-            envelope.id = uuid::Uuid::new_v4().to_string();
+            envelope.id = self.ids.generate();
         }
         envelope.authorized_tokens = tokens.to_vec();
 
         let table = &self.table;
         let sql = format!(
-            "INSERT INTO `{table}` (id, created_at_ms, deleted, authorized_tokens, payload) VALUES (?, ?, ?, ?, ?)"
+            "INSERT INTO `{table}` (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) VALUES (?, ?, ?, ?, ?, ?)"
         );
 
         let created_at_ms = envelope.created_at.timestamp_millis();
         let deleted_flag: i8 = if envelope.deleted { 1 } else { 0 };
         let tokens_json = serde_json::to_string(&envelope.authorized_tokens)
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        let payload_json = serde_json::to_string(&envelope.payload)
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let (payload_json, payload_bin) = self.codec.to_columns(&envelope.payload)?;
 
         sqlx::query(&sql)
             .bind(&envelope.id)
@@ -95,6 +304,7 @@ impl Repository for MysqlRepository {
             .bind(deleted_flag)
             .bind(&tokens_json)
             .bind(&payload_json)
+            .bind(&payload_bin)
             .execute(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
@@ -105,127 +315,200 @@ impl Repository for MysqlRepository {
     async fn read(
         &self,
         id: &str,
-        _tokens: &[String],
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>> {
-        let cutoff_ms = at.unwrap_or_else(Utc::now).timestamp_millis() + 1;
+        let cutoff_ms = at.unwrap_or_else(|| self.clock.now()).timestamp_millis() + 1;
 
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("WHERE {}", f.clause))
+            .unwrap_or_default();
         let table = &self.table;
         let sql = format!(
-            r#"SELECT id, created_at_ms, deleted, authorized_tokens, payload
-               FROM `{table}`
-               WHERE id = ? AND created_at_ms <= ?
-               ORDER BY created_at_ms DESC
-               LIMIT 1"#
+            r#"SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                   SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+                   FROM `{table}`
+                   WHERE id = ? AND created_at_ms <= ?
+                   ORDER BY created_at_ms DESC, seq DESC
+                   LIMIT 1
+               ) AS latest
+               {token_where}"#
         );
 
-        let row = sqlx::query(&sql)
-            .bind(id)
-            .bind(cutoff_ms)
+        let mut q = sqlx::query(&sql).bind(id).bind(cutoff_ms);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val.as_str());
+        }
+        let row = q
             .fetch_optional(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        match row {
-            None => Ok(None),
-            Some(r) => {
-                let env_id: String = r
-                    .try_get("id")
-                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-                let created_at_ms: i64 = r
-                    .try_get("created_at_ms")
-                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-                let deleted_flag: i8 = r
-                    .try_get("deleted")
-                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-                let tokens_json: String = r
-                    .try_get("authorized_tokens")
-                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-                let payload_json: String = r
-                    .try_get("payload")
-                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        row.as_ref().map(Self::decode_row).transpose()
+    }
 
-                let env = Self::row_to_envelope(
-                    env_id,
-                    created_at_ms,
-                    deleted_flag,
-                    tokens_json,
-                    payload_json,
-                )?;
-
-                if env.deleted {
-                    Ok(None)
-                } else {
-                    Ok(Some(env))
-                }
-            }
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("AND {}", f.clause))
+            .unwrap_or_default();
+        let deleted_where = options
+            .deleted()
+            .map(|deleted| format!("AND deleted = {}", deleted as i32))
+            .unwrap_or_default();
+        let page = page_clause(&options);
+        let table = &self.table;
+        let sql = format!(
+            r#"SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                   SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin,
+                          ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, seq DESC) AS rn
+                   FROM `{table}`
+               ) latest
+               WHERE rn = 1
+               {deleted_where}
+               {token_where}
+               {page}"#
+        );
+
+        let mut q = sqlx::query(&sql);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val.as_str());
+        }
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        self.decode_rows(&rows)
+    }
+
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("AND {}", f.clause))
+            .unwrap_or_default();
+        let table = &self.table;
+        let sql = format!(
+            r#"SELECT COUNT(*) FROM (
+                   SELECT deleted, authorized_tokens,
+                          ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, seq DESC) AS rn
+                   FROM `{table}`
+               ) latest
+               WHERE rn = 1 AND deleted = 0
+               {token_where}"#
+        );
+
+        let mut q = sqlx::query_scalar::<_, i64>(&sql);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val.as_str());
         }
+        let count = q
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(count as u64)
     }
 
-    async fn list(&self, _tokens: &[String]) -> Result<Vec<Envelope>> {
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("AND {}", f.clause))
+            .unwrap_or_default();
         let table = &self.table;
         let sql = format!(
-            r#"SELECT e.id, e.created_at_ms, e.deleted, e.authorized_tokens, e.payload
-               FROM `{table}` e
-               INNER JOIN (
-                   SELECT id, MAX(created_at_ms) AS max_ts FROM `{table}` GROUP BY id
-               ) m ON e.id = m.id AND e.created_at_ms = m.max_ts
-               WHERE e.deleted = 0"#
+            r#"SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+               FROM `{table}`
+               WHERE id = ?
+               {token_where}
+               ORDER BY created_at_ms ASC, seq ASC"#
         );
 
-        let rows = sqlx::query(&sql)
+        let mut q = sqlx::query(&sql).bind(id);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val.as_str());
+        }
+        let rows = q
             .fetch_all(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        let mut results = Vec::new();
-        for r in rows {
-            let env_id: String = r
-                .try_get("id")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            let created_at_ms: i64 = r
-                .try_get("created_at_ms")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            let deleted_flag: i8 = r
-                .try_get("deleted")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            let tokens_json: String = r
-                .try_get("authorized_tokens")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            let payload_json: String = r
-                .try_get("payload")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        rows.iter().map(Self::decode_row).collect()
+    }
 
-            let env = Self::row_to_envelope(
-                env_id,
-                created_at_ms,
-                deleted_flag,
-                tokens_json,
-                payload_json,
-            )?;
-            results.push(env);
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("AND {}", f.clause))
+            .unwrap_or_default();
+        let table = &self.table;
+        let sql = format!(
+            r#"SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+               FROM `{table}`
+               WHERE created_at_ms >= ? AND created_at_ms < ?
+               {token_where}
+               ORDER BY created_at_ms ASC, seq ASC"#
+        );
+
+        let mut q = sqlx::query(&sql)
+            .bind(from.timestamp_millis())
+            .bind(to.timestamp_millis());
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val.as_str());
         }
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        Ok(results)
+        rows.iter().map(Self::decode_row).collect()
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
     }
 
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
         let current = self.read(id, tokens, None).await?;
         match current {
-            None => Ok(false),
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
             Some(mut env) => {
                 env.deleted = true;
-                env.created_at = Utc::now();
+                env.created_at = self.clock.now();
                 let table = &self.table;
                 let sql = format!(
-                    "INSERT INTO `{table}` (id, created_at_ms, deleted, authorized_tokens, payload) VALUES (?, ?, ?, ?, ?)"
+                    "INSERT INTO `{table}` (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) VALUES (?, ?, ?, ?, ?, ?)"
                 );
 
                 let created_at_ms = env.created_at.timestamp_millis();
                 let tokens_json = serde_json::to_string(&env.authorized_tokens)
                     .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-                let payload_json = serde_json::to_string(&env.payload)
-                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
 
                 sqlx::query(&sql)
                     .bind(&env.id)
@@ -233,6 +516,7 @@ impl Repository for MysqlRepository {
                     .bind(1i8)
                     .bind(&tokens_json)
                     .bind(&payload_json)
+                    .bind(&payload_bin)
                     .execute(&self.pool)
                     .await
                     .map_err(|e| MeshqlError::Storage(e.to_string()))?;
@@ -242,26 +526,154 @@ impl Repository for MysqlRepository {
         }
     }
 
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let token_where = token_filter
+            .as_ref()
+            .map(|f| format!("AND {}", f.clause))
+            .unwrap_or_default();
+        let table = &self.table;
+        let sql = format!("DELETE FROM `{table}` WHERE id = ? {token_where}");
+
+        let mut q = sqlx::query(&sql).bind(id);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val.as_str());
+        }
+        let result = q
+            .execute(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    /// Reads the id's versions with `FOR UPDATE` in the transaction that
+    /// inserts the new one, so InnoDB's next-key locks hold every connection
+    /// to the database to it. Of two writes racing on an id with no versions
+    /// yet, InnoDB rolls one back as a deadlock, which is reported as the
+    /// conflict it is.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let mut envelope = envelope;
+        if envelope.id.is_empty() {
+            envelope.id = self.ids.generate();
+        }
+        envelope.authorized_tokens = tokens.to_vec();
+
+        let deleted_flag: i8 = if envelope.deleted { 1 } else { 0 };
+        let tokens_json = serde_json::to_string(&envelope.authorized_tokens)
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let (payload_json, payload_bin) = self.codec.to_columns(&envelope.payload)?;
+
+        let table = &self.table;
+        let storage_or_conflict = |e: sqlx::Error| {
+            let deadlocked = e
+                .as_database_error()
+                .and_then(|e| e.code())
+                .is_some_and(|code| code == "40001");
+            match deadlocked {
+                true => conflict(&envelope.id),
+                false => MeshqlError::Storage(e.to_string()),
+            }
+        };
+
+        let mut tx = self.pool.begin().await.map_err(storage_or_conflict)?;
+        let latest: Option<(i64, i8)> = sqlx::query_as(&format!(
+            "SELECT created_at_ms, deleted FROM `{table}` WHERE id = ?
+             ORDER BY created_at_ms DESC, seq DESC FOR UPDATE"
+        ))
+        .bind(&envelope.id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(storage_or_conflict)?;
+        if latest.is_some_and(|(created_at_ms, deleted)| {
+            supersedes(created_at_ms, deleted != 0, expected_created_at)
+        }) {
+            return Err(conflict(&envelope.id));
+        }
+
+        sqlx::query(&format!(
+            "INSERT INTO `{table}` (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) VALUES (?, ?, ?, ?, ?, ?)"
+        ))
+        .bind(&envelope.id)
+        .bind(envelope.created_at.timestamp_millis())
+        .bind(deleted_flag)
+        .bind(&tokens_json)
+        .bind(&payload_json)
+        .bind(&payload_bin)
+        .execute(&mut *tx)
+        .await
+        .map_err(storage_or_conflict)?;
+        tx.commit().await.map_err(storage_or_conflict)?;
+
+        Ok(envelope)
+    }
+
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
         tokens: &[String],
     ) -> Result<Vec<Envelope>> {
+        let tokens_json =
+            serde_json::to_string(tokens).map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
         let mut results = Vec::with_capacity(envelopes.len());
-        for env in envelopes {
-            results.push(self.create(env, tokens).await?);
+        let mut rows = Vec::with_capacity(envelopes.len());
+        for mut envelope in envelopes {
+            if envelope.id.is_empty() {
+                envelope.id = self.ids.generate();
+            }
+            envelope.authorized_tokens = tokens.to_vec();
+            let (payload_json, payload_bin) = self.codec.to_columns(&envelope.payload)?;
+            let deleted_flag: i8 = if envelope.deleted { 1 } else { 0 };
+            rows.push((
+                envelope.id.clone(),
+                envelope.created_at.timestamp_millis(),
+                deleted_flag,
+                payload_json,
+                payload_bin,
+            ));
+            results.push(envelope);
+        }
+
+        let table = &self.table;
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        for chunk in rows.chunks(MAX_ROWS_PER_INSERT) {
+            let mut qb = QueryBuilder::<MySql>::new(format!(
+                "INSERT INTO `{table}` (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) "
+            ));
+            qb.push_values(
+                chunk,
+                |mut b, (id, created_at_ms, deleted_flag, payload, payload_bin)| {
+                    b.push_bind(id)
+                        .push_bind(created_at_ms)
+                        .push_bind(deleted_flag)
+                        .push_bind(&tokens_json)
+                        .push_bind(payload)
+                        .push_bind(payload_bin);
+                },
+            );
+            qb.build()
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
         }
+        tx.commit()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
         Ok(results)
     }
 
     async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
-        let mut results = Vec::new();
-        for id in ids {
-            if let Some(env) = self.read(id, tokens, None).await? {
-                results.push(env);
-            }
-        }
-        Ok(results)
+        self.read_latest(ids, tokens).await
     }
 
     async fn remove_many(
@@ -269,11 +681,49 @@ impl Repository for MysqlRepository {
         ids: &[String],
         tokens: &[String],
     ) -> Result<HashMap<String, bool>> {
-        let mut results = HashMap::new();
-        for id in ids {
-            let deleted = self.remove(id, tokens).await?;
-            results.insert(id.clone(), deleted);
-        }
-        Ok(results)
+        let current = self.read_latest(ids, tokens).await?;
+        let removed: HashSet<String> = current.iter().map(|env| env.id.clone()).collect();
+        let now = self.clock.now();
+        let tombstones = current
+            .into_iter()
+            .map(|env| Envelope {
+                created_at: now,
+                deleted: true,
+                ..env
+            })
+            .collect();
+        self.create_many(tombstones, tokens).await?;
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn stats(&self) -> Result<RepoStats> {
+        Ok(RepoStats {
+            count: self.count(&["*".to_string()]).await?,
+            pool: Some(PoolStats::new(
+                self.pool.size(),
+                self.pool.num_idle() as u32,
+            )),
+        })
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+}
+
+/// `ORDER BY id` and the `LIMIT`/`OFFSET` of the page `options` asks for, if any.
+fn page_clause(options: &ListOptions) -> String {
+    if !options.is_paged() {
+        return String::new();
     }
+    // MySQL only takes an OFFSET after a LIMIT; its docs use u64::MAX for no limit
+    let limit = options.limit.map_or(u64::MAX, |limit| limit as u64);
+    format!("ORDER BY id LIMIT {limit} OFFSET {}", options.offset)
 }
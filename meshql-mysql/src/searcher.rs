@@ -1,14 +1,18 @@
-use crate::query::build_where;
+use crate::query::{build_where, column, created_filter, Page, QueryPart};
+use async_stream::try_stream;
 use async_trait::async_trait;
-use handlebars::Handlebars;
-use meshql_core::{MeshqlError, Result, Searcher, Stash};
+use futures::TryStreamExt;
+use meshql_core::{
+    check_or_groups, distinct_from_args, insert_metadata, is_projectable, render_template,
+    CreatedWindow, MeshqlError, MissingKey, PoolConfig, Result, Searcher, SortField, Stash,
+    StashStream,
+};
 use sqlx::MySqlPool;
 use sqlx::Row;
 
 pub struct MysqlSearcher {
     pool: MySqlPool,
     table: String,
-    handlebars: Handlebars<'static>,
 }
 
 impl MysqlSearcher {
@@ -17,34 +21,54 @@ impl MysqlSearcher {
     }
 
     pub async fn new_with_table(database_url: &str, table: &str) -> Result<Self> {
-        let pool = MySqlPool::connect(database_url)
-            .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Self::new_with_table_and_config(database_url, table, PoolConfig::default()).await
+    }
 
-        let mut handlebars = Handlebars::new();
-        handlebars.set_strict_mode(false);
+    /// Connect to the default table with a pool sized by `config`.
+    pub async fn new_with_config(database_url: &str, config: PoolConfig) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, "envelopes", config).await
+    }
 
+    pub async fn new_with_table_and_config(
+        database_url: &str,
+        table: &str,
+        config: PoolConfig,
+    ) -> Result<Self> {
+        let pool = crate::pool::connect(database_url, &config).await?;
+        Self::new_with_pool_and_table(pool, table).await
+    }
+
+    /// Use an existing pool, e.g. one shared with a [`crate::MysqlRepository`].
+    pub async fn new_with_pool(pool: MySqlPool) -> Result<Self> {
+        Self::new_with_pool_and_table(pool, "envelopes").await
+    }
+
+    pub async fn new_with_pool_and_table(pool: MySqlPool, table: &str) -> Result<Self> {
         Ok(Self {
             pool,
             table: table.to_string(),
-            handlebars,
         })
     }
 
     fn render_template(&self, template: &str, args: &Stash) -> Result<String> {
-        self.handlebars
-            .render_template(template, &serde_json::Value::Object(args.clone()))
-            .map_err(|e| MeshqlError::Template(e.to_string()))
+        let (filter_args, _) = Page::split(args)?;
+        render_template(template, &filter_args, MissingKey::Empty)
     }
 
-    async fn execute_query(
+    /// Build the latest-version query for `query_json`, selecting `projection`
+    /// from the versions created in `created`. A `distinct` payload field keeps
+    /// the lowest id of each of its values; the latest versions already hold
+    /// one row per id.
+    fn build_query(
         &self,
         query_json: &str,
-        at: i64,
-        limit: Option<i64>,
-    ) -> Result<Vec<Stash>> {
+        distinct: Option<&SortField>,
+        created: &CreatedWindow,
+        projection: &str,
+    ) -> Result<(String, QueryPart)> {
         let json_val: serde_json::Value =
             serde_json::from_str(query_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        check_or_groups(&json_val)?;
 
         let obj = json_val
             .as_object()
@@ -53,62 +77,129 @@ impl MysqlSearcher {
         let where_part = build_where(obj);
         let table = &self.table;
 
-        let dynamic_where = if where_part.clause.is_empty() {
-            String::new()
-        } else {
-            format!("AND {}", where_part.clause)
-        };
-
-        let limit_clause = if limit.is_some() {
-            "LIMIT ?".to_string()
-        } else {
-            String::new()
-        };
+        let mut dynamic_where = created_filter(created);
+        if !where_part.clause.is_empty() {
+            dynamic_where.push_str(&format!(" AND {}", where_part.clause));
+        }
 
-        let sql = format!(
+        let latest = format!(
             r#"WITH latest AS (
                 SELECT id, created_at_ms, deleted, authorized_tokens, payload,
-                       ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC) AS rn
+                       ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, seq DESC) AS rn
                 FROM `{table}` WHERE created_at_ms <= ?
+            )"#
+        );
+        let sql = match distinct {
+            Some(field @ SortField::Payload(_)) => format!(
+                r#"{latest},
+            matched AS (
+                SELECT latest.*, ROW_NUMBER() OVER (PARTITION BY {} ORDER BY id) AS dn
+                FROM latest WHERE rn = 1 AND deleted = 0
+                {dynamic_where}
             )
-            SELECT id, created_at_ms, deleted, authorized_tokens, payload
+            SELECT {projection}
+            FROM matched WHERE dn = 1"#,
+                column(field)
+            ),
+            _ => format!(
+                r#"{latest}
+            SELECT {projection}
             FROM latest WHERE rn = 1 AND deleted = 0
-            {dynamic_where}
-            {limit_clause}"#
-        );
+            {dynamic_where}"#
+            ),
+        };
 
-        let mut q = sqlx::query(&sql).bind(at);
-        for val in &where_part.values {
-            q = q.bind(val.as_str());
-        }
-        if let Some(lim) = limit {
-            q = q.bind(lim);
+        Ok((sql, where_part))
+    }
+
+    /// `id`, the version's metadata and the payload as `body`, cut down to
+    /// `fields` when they are given and safe to interpolate. The alias leaves
+    /// `payload` free for sorting on.
+    fn projection(fields: Option<&[String]>) -> String {
+        match fields {
+            Some(fields) if is_projectable(fields) => {
+                let pairs: Vec<String> = fields
+                    .iter()
+                    .map(|f| format!("'{f}', JSON_EXTRACT(payload, '$.\"{f}\"')"))
+                    .collect();
+                format!(
+                    "id, created_at_ms, deleted, CAST(JSON_OBJECT({}) AS CHAR) AS body",
+                    pairs.join(", ")
+                )
+            }
+            _ => "id, created_at_ms, deleted, payload AS body".to_string(),
         }
+    }
 
-        let rows = q
-            .fetch_all(&self.pool)
+    async fn execute_query(
+        &self,
+        query_json: &str,
+        fields: Option<&[String]>,
+        at: i64,
+        page: Page,
+    ) -> Result<Vec<Stash>> {
+        self.stream_query(query_json, fields, at, page)?
+            .try_collect()
             .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+    }
 
-        let mut results = Vec::new();
-        for r in rows {
-            let env_id: String = r
-                .try_get("id")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-            let payload_json: String = r
-                .try_get("payload")
-                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+    /// Matching rows, decoded one at a time as the database returns them.
+    fn stream_query(
+        &self,
+        query_json: &str,
+        fields: Option<&[String]>,
+        at: i64,
+        page: Page,
+    ) -> Result<StashStream> {
+        let (base_sql, where_part) = self.build_query(
+            query_json,
+            page.distinct.as_ref(),
+            &page.created,
+            &Self::projection(fields),
+        )?;
 
-            let mut stash: Stash = serde_json::from_str(&payload_json)
-                .map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let sql = format!("{base_sql}{}", page.clause());
+        let pool = self.pool.clone();
 
-            // Merge id into the stash so callers can find by id field
-            stash.insert("id".to_string(), serde_json::Value::String(env_id));
+        Ok(Box::pin(try_stream! {
+            let mut q = sqlx::query(&sql).bind(at);
+            for val in &where_part.values {
+                q = q.bind(val.as_str());
+            }
+            if !page.is_unpaged() {
+                let (limit, offset) = page.values();
+                q = q.bind(limit).bind(offset);
+            }
 
-            results.push(stash);
-        }
+            let mut rows = q.fetch(&pool);
+            while let Some(r) = rows
+                .try_next()
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?
+            {
+                let env_id: String = r
+                    .try_get("id")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let body: String = r
+                    .try_get("body")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let created_at_ms: i64 = r
+                    .try_get("created_at_ms")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let deleted: i8 = r
+                    .try_get("deleted")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+                let mut stash: Stash =
+                    serde_json::from_str(&body).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+
+                // Merge id into the stash so callers can find by id field
+                stash.insert("id".to_string(), serde_json::Value::String(env_id));
 
-        Ok(results)
+                insert_metadata(&mut stash, created_at_ms, deleted != 0);
+                yield stash;
+            }
+        }))
     }
 }
 
@@ -122,7 +213,11 @@ impl Searcher for MysqlSearcher {
         at: i64,
     ) -> Result<Option<Stash>> {
         let query_json = self.render_template(template, args)?;
-        let results = self.execute_query(&query_json, at, Some(1)).await?;
+        let page = Page {
+            created: CreatedWindow::from_args(args)?,
+            ..Page::first()
+        };
+        let results = self.execute_query(&query_json, None, at, page).await?;
         Ok(results.into_iter().next())
     }
 
@@ -134,7 +229,107 @@ impl Searcher for MysqlSearcher {
         at: i64,
     ) -> Result<Vec<Stash>> {
         let query_json = self.render_template(template, args)?;
-        let limit = args.get("limit").and_then(|v| v.as_i64());
-        self.execute_query(&query_json, at, limit).await
+        let (_, page) = Page::split(args)?;
+        self.execute_query(&query_json, None, at, page).await
+    }
+
+    async fn find_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        _creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        let query_json = self.render_template(template, args)?;
+        let page = Page {
+            created: CreatedWindow::from_args(args)?,
+            ..Page::first()
+        };
+        let results = self
+            .execute_query(&query_json, Some(fields), at, page)
+            .await?;
+        Ok(results.into_iter().next())
+    }
+
+    async fn find_all_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        _creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        let query_json = self.render_template(template, args)?;
+        let (_, page) = Page::split(args)?;
+        self.execute_query(&query_json, Some(fields), at, page)
+            .await
+    }
+
+    async fn find_stream(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
+        let query_json = self.render_template(template, args)?;
+        let (_, page) = Page::split(args)?;
+        self.stream_query(&query_json, None, at, page)
+    }
+
+    async fn count(&self, template: &str, args: &Stash, _creds: &[String], at: i64) -> Result<u64> {
+        let query_json = self.render_template(template, args)?;
+        let distinct = distinct_from_args(args)?;
+        let created = CreatedWindow::from_args(args)?;
+        let (sql, where_part) =
+            self.build_query(&query_json, distinct.as_ref(), &created, "COUNT(*) AS n")?;
+
+        let mut q = sqlx::query(&sql).bind(at);
+        for val in &where_part.values {
+            q = q.bind(val.as_str());
+        }
+
+        let row = q
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let n: i64 = row
+            .try_get("n")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(n as u64)
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        let query_json = self.render_template(template, args)?;
+        let distinct = distinct_from_args(args)?;
+        let created = CreatedWindow::from_args(args)?;
+        let (sql, where_part) = self.build_query(&query_json, distinct.as_ref(), &created, "1")?;
+        let sql = format!("{sql} LIMIT 1");
+
+        let mut q = sqlx::query(&sql).bind(at);
+        for val in &where_part.values {
+            q = q.bind(val.as_str());
+        }
+
+        let row = q
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(row.is_some())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
     }
 }
@@ -1,9 +1,14 @@
 use meshql_core::testing as cert;
-use meshql_mysql::{MysqlRepository, MysqlSearcher};
+use meshql_mysql::{pool_options, MysqlRepository, MysqlSearcher, PoolConfig};
 use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::mysql::Mysql;
 
 async fn create_searcher() -> (MysqlSearcher, impl std::any::Any) {
+    let (_repo, searcher, container) = create_repo_and_searcher().await;
+    (searcher, container)
+}
+
+async fn create_repo_and_searcher() -> (MysqlRepository, MysqlSearcher, impl std::any::Any) {
     let container = Mysql::default().start().await.unwrap();
     let port = container.get_host_port_ipv4(3306).await.unwrap();
     // testcontainers-modules mysql defaults: root with empty password, db = "test"
@@ -14,7 +19,7 @@ async fn create_searcher() -> (MysqlSearcher, impl std::any::Any) {
     cert::seed_searcher_data(&repo).await;
 
     let searcher = MysqlSearcher::new_with_table(&url, &table).await.unwrap();
-    (searcher, container)
+    (repo, searcher, container)
 }
 
 #[tokio::test]
@@ -47,6 +52,24 @@ async fn should_find_all_by_type_and_name() {
     cert::test_searcher_find_all_by_type_and_name(&searcher).await;
 }
 
+#[tokio::test]
+async fn should_find_all_in_list() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_find_all_in_list(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_comparison() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_find_all_comparisons(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_or_groups() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_find_all_or_groups(&searcher).await;
+}
+
 #[tokio::test]
 async fn should_return_empty_for_nonexistent_type() {
     let (searcher, _c) = create_searcher().await;
@@ -64,3 +87,70 @@ async fn should_handle_empty_query() {
     let (searcher, _c) = create_searcher().await;
     cert::test_searcher_empty_query(&searcher).await;
 }
+
+#[tokio::test]
+async fn should_count_matches() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_count(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_check_existence() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_exists(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_page_with_limit_and_offset() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_pages_with_limit_and_offset(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_sort_by_count_desc() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_sorts_by_count_desc(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_collapse_distinct_values() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_collapses_distinct_values(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_project_requested_fields() {
+    let (searcher, _c) = create_searcher().await;
+    cert::test_searcher_projects_requested_fields(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_stream_large_results() {
+    let (repo, searcher, _c) = create_repo_and_searcher().await;
+    cert::test_searcher_streams_large_results(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_share_a_pool_with_the_repository() {
+    let container = Mysql::default().start().await.unwrap();
+    let port = container.get_host_port_ipv4(3306).await.unwrap();
+    let url = format!("mysql://root:@127.0.0.1:{port}/test");
+    let pool = pool_options(&PoolConfig::default())
+        .connect(&url)
+        .await
+        .unwrap();
+    let table = format!("env_{}", uuid::Uuid::new_v4().simple());
+    let repo = MysqlRepository::new_with_pool_and_table(pool.clone(), &table)
+        .await
+        .unwrap();
+    let searcher = MysqlSearcher::new_with_pool_and_table(pool, &table)
+        .await
+        .unwrap();
+    cert::test_searcher_sees_created_envelope(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_creation_window() {
+    let (repo, searcher, _c) = create_repo_and_searcher().await;
+    cert::test_searcher_filters_by_creation_window(&repo, &searcher).await;
+}
@@ -0,0 +1,36 @@
+use crate::pool::connect;
+use crate::{PostgresRepository, PostgresSearcher};
+use async_trait::async_trait;
+use meshql_core::{BackendFactory, Repository, Result, Searcher, StorageManifest};
+use std::sync::Arc;
+
+/// Opens `backend: postgres` manifest storage, one table per `collection`.
+pub struct PostgresBackend;
+
+#[async_trait]
+impl BackendFactory for PostgresBackend {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    async fn repository(&self, storage: &StorageManifest) -> Result<Arc<dyn Repository>> {
+        let repo = PostgresRepository::new_with_pool_and_table(
+            connect(&storage.uri, &storage.pool, storage.tls.as_ref()).await?,
+            &storage.collection,
+        )
+        .await?
+        .with_id_strategy(storage.id_strategy)
+        .with_payload_codec(storage.payload_codec)
+        .with_auth_policy(storage.auth_policy);
+        Ok(Arc::new(repo))
+    }
+
+    async fn searcher(&self, storage: &StorageManifest) -> Result<Arc<dyn Searcher>> {
+        let searcher = PostgresSearcher::new_with_pool_and_table(
+            connect(&storage.uri, &storage.pool, storage.tls.as_ref()).await?,
+            &storage.collection,
+        )
+        .await?;
+        Ok(Arc::new(searcher))
+    }
+}
@@ -1,6 +1,11 @@
+mod backend;
+mod pool;
 mod query;
 mod repository;
 mod searcher;
 
+pub use backend::PostgresBackend;
+pub use meshql_core::PoolConfig;
+pub use pool::{connect_options, pool_options};
 pub use repository::PostgresRepository;
 pub use searcher::PostgresSearcher;
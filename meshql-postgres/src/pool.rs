@@ -0,0 +1,54 @@
+use meshql_core::{MeshqlError, PoolConfig, Result, TlsConfig, TlsMode};
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions, PgSslMode};
+use std::str::FromStr;
+
+/// sqlx pool options with `config` applied over the driver's defaults.
+pub fn pool_options(config: &PoolConfig) -> PgPoolOptions {
+    let mut options = PgPoolOptions::new();
+    if let Some(max) = config.max_connections {
+        options = options.max_connections(max);
+    }
+    if let Some(timeout) = config.acquire_timeout {
+        options = options.acquire_timeout(timeout);
+    }
+    if let Some(timeout) = config.idle_timeout {
+        options = options.idle_timeout(timeout);
+    }
+    options
+}
+
+/// Connect options for `database_url`, with `tls` applied over its `sslmode`
+/// and certificate parameters.
+pub fn connect_options(database_url: &str, tls: Option<&TlsConfig>) -> Result<PgConnectOptions> {
+    let mut options = PgConnectOptions::from_str(database_url)
+        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+    if let Some(tls) = tls {
+        options = options.ssl_mode(match tls.mode {
+            TlsMode::Disable => PgSslMode::Disable,
+            TlsMode::Require => PgSslMode::Require,
+            TlsMode::VerifyCa => PgSslMode::VerifyCa,
+            TlsMode::VerifyFull => PgSslMode::VerifyFull,
+        });
+        if let Some(path) = &tls.ca_file {
+            options = options.ssl_root_cert(path);
+        }
+        if let Some(path) = &tls.client_cert {
+            options = options.ssl_client_cert(path);
+        }
+        if let Some(path) = &tls.client_key {
+            options = options.ssl_client_key(path);
+        }
+    }
+    Ok(options)
+}
+
+pub(crate) async fn connect(
+    database_url: &str,
+    config: &PoolConfig,
+    tls: Option<&TlsConfig>,
+) -> Result<PgPool> {
+    pool_options(config)
+        .connect_with(connect_options(database_url, tls)?)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))
+}
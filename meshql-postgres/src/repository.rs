@@ -1,12 +1,27 @@
+use crate::query::build_token_filter;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use meshql_core::{Envelope, MeshqlError, Repository, Result};
-use sqlx::{PgPool, Row};
-use std::collections::HashMap;
+use meshql_core::{
+    conflict, forbid_hidden, AuthPolicy, Clock, Envelope, IdStrategy, ListOptions, MeshqlError,
+    PayloadCodec, PoolConfig, PoolStats, RepoStats, Repository, Result, SystemClock, TlsConfig,
+};
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Postgres caps a statement at 65535 bind parameters; each row binds 6 values.
+const MAX_ROWS_PER_INSERT: usize = 65535 / 6;
 
 pub struct PostgresRepository {
     pub pool: PgPool,
     pub table: String,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+    codec: PayloadCodec,
+    /// Fail reads of several rows on one that doesn't parse, rather than skip it.
+    strict: bool,
+    policy: AuthPolicy,
 }
 
 impl PostgresRepository {
@@ -17,17 +32,102 @@ impl PostgresRepository {
 
     /// Create a new repository with a custom table name (useful for test isolation).
     pub async fn new_with_table(database_url: &str, table: &str) -> Result<Self> {
-        let pool = PgPool::connect(database_url)
-            .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Self::new_with_table_and_config(database_url, table, PoolConfig::default()).await
+    }
+
+    /// Connect to the default table with a pool sized by `config`.
+    pub async fn new_with_config(database_url: &str, config: PoolConfig) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, "envelopes", config).await
+    }
+
+    pub async fn new_with_table_and_config(
+        database_url: &str,
+        table: &str,
+        config: PoolConfig,
+    ) -> Result<Self> {
+        let pool = crate::pool::connect(database_url, &config, None).await?;
+        Self::new_with_pool_and_table(pool, table).await
+    }
+
+    /// Like [`Self::new_with_table_and_config`], with `tls` applied over the
+    /// URL's `sslmode` and certificate parameters.
+    pub async fn new_with_tls(
+        database_url: &str,
+        table: &str,
+        config: PoolConfig,
+        tls: &TlsConfig,
+    ) -> Result<Self> {
+        let pool = crate::pool::connect(database_url, &config, Some(tls)).await?;
+        Self::new_with_pool_and_table(pool, table).await
+    }
+
+    /// Use an existing pool, e.g. one shared with a [`crate::PostgresSearcher`].
+    pub async fn new_with_pool(pool: PgPool) -> Result<Self> {
+        Self::new_with_pool_and_table(pool, "envelopes").await
+    }
+
+    pub async fn new_with_pool_and_table(pool: PgPool, table: &str) -> Result<Self> {
         let repo = Self {
             pool,
             table: table.to_string(),
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+            codec: PayloadCodec::default(),
+            strict: false,
+            policy: AuthPolicy::default(),
         };
         repo.init_schema().await?;
         Ok(repo)
     }
 
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Write payloads with `codec`, rather than [`PayloadCodec::Json`].
+    pub fn with_payload_codec(mut self, codec: PayloadCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Fail `list` and `read_many` on a row whose payload or tokens don't
+    /// parse, rather than skip it with a warning.
+    pub fn with_strict_parsing(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Match callers' tokens by `policy`, rather than [`AuthPolicy::AnyOf`].
+    pub fn with_auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Decode `rows`, skipping any that don't parse unless strict.
+    fn decode_rows(&self, rows: &[sqlx::postgres::PgRow]) -> Result<Vec<Envelope>> {
+        let mut envelopes = Vec::with_capacity(rows.len());
+        for row in rows {
+            match Self::row_to_envelope(row) {
+                Ok(env) => envelopes.push(env),
+                Err(MeshqlError::Parse(e)) if !self.strict => {
+                    warn!(table = %self.table, error = %e, "skipping a row that doesn't parse");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(envelopes)
+    }
+
     async fn init_schema(&self) -> Result<()> {
         let create_table = format!(
             "CREATE TABLE IF NOT EXISTS {} (
@@ -35,7 +135,8 @@ impl PostgresRepository {
                 created_at_ms BIGINT NOT NULL,
                 deleted BOOLEAN NOT NULL DEFAULT FALSE,
                 authorized_tokens TEXT NOT NULL,
-                payload TEXT NOT NULL
+                payload TEXT NOT NULL,
+                payload_bin BYTEA
             )",
             self.table
         );
@@ -44,18 +145,60 @@ impl PostgresRepository {
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        let create_index = format!(
-            "CREATE INDEX IF NOT EXISTS idx_{}_id ON {}(id)",
-            self.table, self.table
+        // Tables created before payloads could be binary keep reading from `payload`.
+        let add_payload_bin = format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS payload_bin BYTEA",
+            self.table
         );
-        sqlx::query(&create_index)
+        sqlx::query(&add_payload_bin)
             .execute(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
+        for (name, column) in [("id", "id"), ("created", "created_at_ms")] {
+            let create_index = format!(
+                "CREATE INDEX IF NOT EXISTS idx_{table}_{name} ON {table}({column})",
+                table = self.table
+            );
+            sqlx::query(&create_index)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
+
         Ok(())
     }
 
+    /// The latest version of each of `ids`, skipping ids that are deleted or
+    /// whose latest version `tokens` can't see.
+    async fn read_latest(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let cutoff_ms = self.clock.now().timestamp_millis() + 1;
+        // $1 = ids, $2 = cutoff_ms, token params start at $3
+        let token_filter = build_token_filter(tokens, 3, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                SELECT DISTINCT ON (id) id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+                FROM {} WHERE id = ANY($1) AND created_at_ms <= $2
+                ORDER BY id, created_at_ms DESC
+             ) latest WHERE deleted = FALSE{}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+        let mut q = sqlx::query(&sql).bind(ids).bind(cutoff_ms);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        self.decode_rows(&rows)
+    }
+
     fn row_to_envelope(row: &sqlx::postgres::PgRow) -> Result<Envelope> {
         let id: String = row
             .try_get("id")
@@ -72,12 +215,14 @@ impl PostgresRepository {
         let payload_json: String = row
             .try_get("payload")
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let payload_bin: Option<Vec<u8>> = row
+            .try_get("payload_bin")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
         let created_at = DateTime::from_timestamp_millis(created_at_ms).unwrap_or_default();
         let authorized_tokens: Vec<String> =
             serde_json::from_str(&tokens_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-        let payload: meshql_core::Stash =
-            serde_json::from_str(&payload_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let payload = PayloadCodec::from_columns(&payload_json, payload_bin.as_deref())?;
 
         Ok(Envelope {
             id,
@@ -94,18 +239,17 @@ impl Repository for PostgresRepository {
     async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
         let mut env = envelope;
         if env.id.is_empty() {
-            env.id = uuid::Uuid::new_v4().to_string();
+            env.id = self.ids.generate();
         }
         env.authorized_tokens = tokens.to_vec();
 
         let created_at_ms = env.created_at.timestamp_millis();
         let tokens_json = serde_json::to_string(&env.authorized_tokens)
             .map_err(|e| MeshqlError::Parse(e.to_string()))?;
-        let payload_json =
-            serde_json::to_string(&env.payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
 
         let sql = format!(
-            "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload) VALUES ($1, $2, $3, $4, $5)",
+            "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) VALUES ($1, $2, $3, $4, $5, $6)",
             self.table
         );
         sqlx::query(&sql)
@@ -114,6 +258,7 @@ impl Repository for PostgresRepository {
             .bind(env.deleted)
             .bind(&tokens_json)
             .bind(&payload_json)
+            .bind(&payload_bin)
             .execute(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
@@ -124,68 +269,181 @@ impl Repository for PostgresRepository {
     async fn read(
         &self,
         id: &str,
-        _tokens: &[String],
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>> {
         let cutoff_ms = match at {
             Some(t) => t.timestamp_millis(),
-            None => Utc::now().timestamp_millis() + 1,
+            None => self.clock.now().timestamp_millis() + 1,
         };
 
+        // $1 = id, $2 = cutoff_ms, token params start at $3
+        let token_filter = build_token_filter(tokens, 3, self.policy);
         let sql = format!(
-            "SELECT id, created_at_ms, deleted, authorized_tokens, payload
-             FROM {} WHERE id = $1 AND created_at_ms <= $2
-             ORDER BY created_at_ms DESC LIMIT 1",
-            self.table
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+                FROM {} WHERE id = $1 AND created_at_ms <= $2
+                ORDER BY created_at_ms DESC LIMIT 1
+             ) latest{}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" WHERE {}", f.clause))
+                .unwrap_or_default()
         );
-        let row = sqlx::query(&sql)
-            .bind(id)
-            .bind(cutoff_ms)
+        let mut q = sqlx::query(&sql).bind(id).bind(cutoff_ms);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let row = q
             .fetch_optional(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        match row {
-            None => Ok(None),
-            Some(r) => {
-                let env = Self::row_to_envelope(&r)?;
-                if env.deleted {
-                    Ok(None)
-                } else {
-                    Ok(Some(env))
-                }
-            }
+        row.map(|r| Self::row_to_envelope(&r)).transpose()
+    }
+
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, 1, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                SELECT DISTINCT ON (id) id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+                FROM {}
+                ORDER BY id, created_at_ms DESC
+             ) latest WHERE TRUE{}{}{}",
+            self.table,
+            options
+                .deleted()
+                .map(|deleted| format!(" AND deleted = {deleted}"))
+                .unwrap_or_default(),
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default(),
+            page_clause(&options)
+        );
+        let mut q = sqlx::query(&sql);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
         }
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        self.decode_rows(&rows)
     }
 
-    async fn list(&self, _tokens: &[String]) -> Result<Vec<Envelope>> {
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        let token_filter = build_token_filter(tokens, 1, self.policy);
         let sql = format!(
-            "SELECT DISTINCT ON (id) id, created_at_ms, deleted, authorized_tokens, payload
-             FROM {} WHERE deleted = FALSE
-             ORDER BY id, created_at_ms DESC",
-            self.table
+            "SELECT COUNT(*) FROM (
+                SELECT DISTINCT ON (id) deleted, authorized_tokens
+                FROM {}
+                ORDER BY id, created_at_ms DESC
+             ) latest WHERE deleted = FALSE{}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
         );
-        let rows = sqlx::query(&sql)
+        let mut q = sqlx::query_scalar::<_, i64>(&sql);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let count = q
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(count as u64)
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        // $1 = id, token params start at $2
+        let token_filter = build_token_filter(tokens, 2, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+             FROM {} WHERE id = $1{}
+             ORDER BY created_at_ms ASC",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+        let mut q = sqlx::query(&sql).bind(id);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let rows = q
             .fetch_all(&self.pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(Self::row_to_envelope(&row)?);
+        rows.iter().map(Self::row_to_envelope).collect()
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        // $1 = from, $2 = to, token params start at $3
+        let token_filter = build_token_filter(tokens, 3, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+             FROM {} WHERE created_at_ms >= $1 AND created_at_ms < $2{}
+             ORDER BY created_at_ms ASC",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+        let mut q = sqlx::query(&sql)
+            .bind(from.timestamp_millis())
+            .bind(to.timestamp_millis());
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
         }
-        Ok(results)
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        rows.iter().map(Self::row_to_envelope).collect()
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
     }
 
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
         let current = self.read(id, tokens, None).await?;
         match current {
-            None => Ok(false),
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
             Some(env) => {
                 let deleted_env = Envelope {
                     id: env.id,
                     payload: env.payload,
-                    created_at: Utc::now(),
+                    created_at: self.clock.now(),
                     deleted: true,
                     authorized_tokens: env.authorized_tokens,
                 };
@@ -195,26 +453,151 @@ impl Repository for PostgresRepository {
         }
     }
 
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        // $1 = id, token params start at $2
+        let token_filter = build_token_filter(tokens, 2, self.policy);
+        let sql = format!(
+            "DELETE FROM {} WHERE id = $1{}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+        let mut q = sqlx::query(&sql).bind(id);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let result = q
+            .execute(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    /// Takes a transaction-scoped advisory lock on the table and id before an
+    /// `INSERT ... SELECT ... WHERE NOT EXISTS`, so it holds for every
+    /// connection to the database. Under READ COMMITTED the insert then sees
+    /// whatever the lock's previous holder committed.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+
+        let tokens_json = serde_json::to_string(&env.authorized_tokens)
+            .map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1 || ':' || $2, 0))")
+            .bind(&self.table)
+            .bind(&env.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        // $1..$6 = the new row, $7 = the expected created_at_ms
+        let sql = format!(
+            "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin)
+            SELECT $1, $2, $3, $4, $5, $6 WHERE NOT EXISTS ({})",
+            self.table,
+            superseding_clause(&self.table, expected_created_at.is_some())
+        );
+        let mut q = sqlx::query(&sql)
+            .bind(&env.id)
+            .bind(env.created_at.timestamp_millis())
+            .bind(env.deleted)
+            .bind(&tokens_json)
+            .bind(&payload_json)
+            .bind(&payload_bin);
+        if let Some(expected) = expected_created_at {
+            q = q.bind(expected.timestamp_millis());
+        }
+        let result = q
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(conflict(&env.id));
+        }
+        Ok(env)
+    }
+
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
         tokens: &[String],
     ) -> Result<Vec<Envelope>> {
-        let mut results = Vec::new();
-        for env in envelopes {
-            results.push(self.create(env, tokens).await?);
+        let tokens_json =
+            serde_json::to_string(tokens).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+
+        let mut results = Vec::with_capacity(envelopes.len());
+        let mut rows = Vec::with_capacity(envelopes.len());
+        for mut env in envelopes {
+            if env.id.is_empty() {
+                env.id = self.ids.generate();
+            }
+            env.authorized_tokens = tokens.to_vec();
+            let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
+            rows.push((
+                env.id.clone(),
+                env.created_at.timestamp_millis(),
+                env.deleted,
+                payload_json,
+                payload_bin,
+            ));
+            results.push(env);
         }
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        for chunk in rows.chunks(MAX_ROWS_PER_INSERT) {
+            let mut qb = QueryBuilder::<Postgres>::new(format!(
+                "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) ",
+                self.table
+            ));
+            qb.push_values(
+                chunk,
+                |mut b, (id, created_at_ms, deleted, payload, payload_bin)| {
+                    b.push_bind(id)
+                        .push_bind(created_at_ms)
+                        .push_bind(deleted)
+                        .push_bind(&tokens_json)
+                        .push_bind(payload)
+                        .push_bind(payload_bin);
+                },
+            );
+            qb.build()
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
         Ok(results)
     }
 
     async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
-        let mut results = Vec::new();
-        for id in ids {
-            if let Some(env) = self.read(id, tokens, None).await? {
-                results.push(env);
-            }
-        }
-        Ok(results)
+        self.read_latest(ids, tokens).await
     }
 
     async fn remove_many(
@@ -222,11 +605,65 @@ impl Repository for PostgresRepository {
         ids: &[String],
         tokens: &[String],
     ) -> Result<HashMap<String, bool>> {
-        let mut results = HashMap::new();
-        for id in ids {
-            let deleted = self.remove(id, tokens).await?;
-            results.insert(id.clone(), deleted);
-        }
-        Ok(results)
+        let current = self.read_latest(ids, tokens).await?;
+        let removed: HashSet<String> = current.iter().map(|env| env.id.clone()).collect();
+        let now = self.clock.now();
+        let tombstones = current
+            .into_iter()
+            .map(|env| Envelope {
+                created_at: now,
+                deleted: true,
+                ..env
+            })
+            .collect();
+        self.create_many(tombstones, tokens).await?;
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn stats(&self) -> Result<RepoStats> {
+        Ok(RepoStats {
+            count: self.count(&["*".to_string()]).await?,
+            pool: Some(PoolStats::new(
+                self.pool.size(),
+                self.pool.num_idle() as u32,
+            )),
+        })
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+}
+
+/// `ORDER BY id` and the `LIMIT`/`OFFSET` of the page `options` asks for, if any.
+fn page_clause(options: &ListOptions) -> String {
+    if !options.is_paged() {
+        return String::new();
+    }
+    let limit = options
+        .limit
+        .map_or("ALL".to_string(), |limit| limit.to_string());
+    format!(" ORDER BY id LIMIT {limit} OFFSET {}", options.offset)
+}
+
+/// A query finding the version of the id bound as `$1` that rules out a
+/// conditional write: one newer than the millis bound as `$7` when the write
+/// expects a version, or else a live latest version.
+fn superseding_clause(table: &str, expects_version: bool) -> String {
+    match expects_version {
+        true => format!("SELECT 1 FROM {table} WHERE id = $1 AND created_at_ms > $7"),
+        false => format!(
+            "SELECT 1 FROM (
+                SELECT deleted FROM {table} WHERE id = $1
+                ORDER BY created_at_ms DESC LIMIT 1
+            ) latest WHERE NOT deleted"
+        ),
     }
 }
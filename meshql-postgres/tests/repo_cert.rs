@@ -1,5 +1,7 @@
 use meshql_core::testing as cert;
+use meshql_core::{AuthPolicy, MockClock};
 use meshql_postgres::PostgresRepository;
+use std::sync::Arc;
 use testcontainers::runners::AsyncRunner;
 use testcontainers_modules::postgres::Postgres;
 
@@ -50,12 +52,24 @@ async fn read_many_should_retrieve_multiple_envelopes() {
     cert::test_read_many_should_retrieve_multiple_envelopes(&repo).await;
 }
 
+#[tokio::test]
+async fn read_many_should_return_latest_live_versions() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_returns_latest_live_versions(&repo).await;
+}
+
 #[tokio::test]
 async fn remove_many_should_delete_multiple_envelopes() {
     let (repo, _c) = create_repo().await;
     cert::test_remove_many_should_delete_multiple_envelopes(&repo).await;
 }
 
+#[tokio::test]
+async fn bulk_results_should_follow_input_order() {
+    let (repo, _c) = create_repo().await;
+    cert::test_bulk_results_follow_input_order(&repo).await;
+}
+
 #[tokio::test]
 async fn should_allow_multiple_versions_and_temporal_reads() {
     let (repo, _c) = create_repo().await;
@@ -67,3 +81,124 @@ async fn should_only_list_latest_version() {
     let (repo, _c) = create_repo().await;
     cert::test_list_shows_only_latest_version(&repo).await;
 }
+
+#[tokio::test]
+async fn should_hide_ids_whose_newest_version_is_hidden() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_hides_ids_whose_newest_version_is_hidden(&repo).await;
+}
+
+#[tokio::test]
+async fn should_hide_read_many_ids_whose_newest_version_is_hidden() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_many_hides_ids_whose_newest_version_is_hidden(&repo).await;
+}
+
+#[tokio::test]
+async fn update_should_merge_patch_into_new_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_update_should_merge_patch_into_new_version(&repo).await;
+}
+
+#[tokio::test]
+async fn updates_racing_on_one_version_should_conflict_once() {
+    let (repo, _c) = create_repo().await;
+    cert::test_updates_racing_on_one_version_should_conflict_once(&repo).await;
+}
+
+#[tokio::test]
+async fn updates_racing_across_repositories_should_conflict_once() {
+    let (first, _c) = create_repo().await;
+    let second = PostgresRepository::new_with_pool_and_table(first.pool.clone(), &first.table)
+        .await
+        .unwrap();
+    cert::test_updates_racing_across_repositories_should_conflict_once(&first, &second).await;
+}
+
+#[tokio::test]
+async fn versions_should_be_stamped_by_the_clock() {
+    let clock = Arc::new(MockClock::default());
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_clock(clock.clone());
+    cert::test_versions_are_stamped_by_the_clock(&repo, &clock).await;
+}
+
+#[tokio::test]
+async fn history_should_return_every_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_history_returns_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn created_between_should_return_versions_in_the_window() {
+    let (repo, _c) = create_repo().await;
+    cert::test_created_between_returns_versions_in_the_window(&repo).await;
+}
+
+#[tokio::test]
+async fn upsert_should_skip_unchanged_payloads() {
+    let (repo, _c) = create_repo().await;
+    cert::test_upsert_skips_unchanged_payloads(&repo).await;
+}
+
+#[tokio::test]
+async fn purge_should_delete_every_version() {
+    let (repo, _c) = create_repo().await;
+    cert::test_purge_deletes_every_version(&repo).await;
+}
+
+#[tokio::test]
+async fn non_matching_token_should_see_no_rows() {
+    let (repo, _c) = create_repo().await;
+    cert::test_non_matching_token_sees_no_rows(&repo).await;
+}
+
+#[tokio::test]
+async fn any_of_policy_should_need_one_shared_token() {
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_auth_policy(AuthPolicy::AnyOf);
+    cert::test_any_of_policy_needs_one_shared_token(&repo).await;
+}
+
+#[tokio::test]
+async fn all_of_policy_should_need_every_token() {
+    let (repo, _c) = create_repo().await;
+    let repo = repo.with_auth_policy(AuthPolicy::AllOf);
+    cert::test_all_of_policy_needs_every_token(&repo).await;
+}
+
+#[tokio::test]
+async fn writes_to_hidden_ids_should_not_be_authorized() {
+    let (repo, _c) = create_repo().await;
+    cert::test_writes_to_hidden_ids_are_not_authorized(&repo).await;
+}
+
+#[tokio::test]
+async fn count_should_match_list() {
+    let (repo, _c) = create_repo().await;
+    cert::test_count_matches_list(&repo).await;
+}
+
+#[tokio::test]
+async fn create_many_should_store_5000_listable_rows() {
+    let (repo, _c) = create_repo().await;
+    cert::test_create_many_should_store_5000_listable_rows(&repo).await;
+}
+
+#[tokio::test]
+async fn remove_many_reports_missing_ids() {
+    let (repo, _c) = create_repo().await;
+    cert::test_remove_many_reports_missing_ids(&repo).await;
+}
+
+#[tokio::test]
+async fn read_raw_returns_tombstone() {
+    let (repo, _c) = create_repo().await;
+    cert::test_read_raw_returns_tombstone(&repo).await;
+}
+
+#[tokio::test]
+async fn list_with_deleted_only_returns_removed() {
+    let (repo, _c) = create_repo().await;
+    cert::test_list_with_deleted_only_returns_removed(&repo).await;
+}
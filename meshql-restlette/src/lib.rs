@@ -1,6 +1,8 @@
+pub mod openapi;
 pub mod routes;
 
+pub use openapi::{openapi_document, openapi_router};
 pub use routes::{
-    build_restlette_router, build_restlette_router_ext, PostCreateFn, SideEffectContext,
-    ValidatorContext, ValidatorFn,
+    build_restlette_router, build_restlette_router_ext, build_validated_restlette_router,
+    PostCreateFn, SideEffectContext, ValidatorContext, ValidatorFn,
 };
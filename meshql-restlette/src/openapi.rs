@@ -0,0 +1,298 @@
+use axum::{routing::get, Json, Router};
+use serde_json::{json, Map, Value};
+
+/// OpenAPI 3.0 document describing the routes of every restlette, given as
+/// `(path, schema_json)` pairs.
+///
+/// Each restlette's JSON Schema becomes a component named after the first
+/// segment of its path, so `/farm/api` is described by `#/components/schemas/Farm`.
+pub fn openapi_document(restlettes: &[(&str, &Value)]) -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    schemas.insert(
+        "ValidationErrors".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "errors": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": {"type": "string"},
+                            "message": {"type": "string"}
+                        }
+                    }
+                }
+            }
+        }),
+    );
+
+    for (path, schema_json) in restlettes {
+        let name = component_name(path);
+        let schema_ref = json!({"$ref": format!("#/components/schemas/{name}")});
+        let body = |description: &str| {
+            json!({
+                "description": description,
+                "content": {"application/json": {"schema": schema_ref}}
+            })
+        };
+        let invalid = json!({
+            "description": "The body does not match the schema",
+            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/ValidationErrors"}}}
+        });
+        let unauthorized = json!({"description": "The request is not authenticated"});
+        let not_found = json!({"description": format!("No {name} has this id")});
+        let preconditions = json!([
+            {
+                "name": "If-Match",
+                "in": "header",
+                "description": format!("Write only if the {name} is still at this ETag"),
+                "schema": {"type": "string"}
+            },
+            {
+                "name": "If-Unmodified-Since",
+                "in": "header",
+                "description": format!("Write only if the {name} has not changed since"),
+                "schema": {"type": "string"}
+            }
+        ]);
+        let stale = json!({"description": format!("The {name} changed since, or isn't there")});
+
+        let base = path.trim_end_matches('/');
+        paths.insert(
+            path.to_string(),
+            json!({
+                "get": {
+                    "summary": format!("List every {name}"),
+                    "description": "Any other query parameter naming a schema property keeps only the items whose field equals it.",
+                    "parameters": [
+                        {"name": "limit", "in": "query", "schema": {"type": "integer", "minimum": 0}},
+                        {"name": "offset", "in": "query", "schema": {"type": "integer", "minimum": 0}},
+                        {
+                            "name": "at",
+                            "in": "query",
+                            "description": "List the items as they were at this time, in epoch milliseconds",
+                            "schema": {"type": "integer", "minimum": 0}
+                        }
+                    ],
+                    "responses": {
+                        "200": {
+                            "description": format!("Every {name}"),
+                            "content": {"application/json": {"schema": {"type": "array", "items": schema_ref}}}
+                        },
+                        "400": {"description": "A paging or `at` parameter is not a non-negative integer"},
+                        "401": unauthorized
+                    }
+                },
+                "post": {
+                    "summary": format!("Create a {name}"),
+                    "requestBody": {
+                        "required": true,
+                        "content": {"application/json": {"schema": schema_ref}}
+                    },
+                    "responses": {
+                        "201": {
+                            "description": format!("The created {name}"),
+                            "headers": {
+                                "Location": {
+                                    "description": format!("Where the created {name} is read"),
+                                    "schema": {"type": "string"}
+                                }
+                            },
+                            "content": {"application/json": {"schema": schema_ref}}
+                        },
+                        "400": {"description": "The body was rejected by a validator"},
+                        "401": unauthorized,
+                        "422": invalid
+                    }
+                }
+            }),
+        );
+        paths.insert(
+            format!("{base}/{{id}}"),
+            json!({
+                "parameters": [{
+                    "name": "id",
+                    "in": "path",
+                    "required": true,
+                    "schema": {"type": "string"}
+                }],
+                "get": {
+                    "summary": format!("Read a {name}"),
+                    "responses": {
+                        "200": body(&format!("The {name}")),
+                        "401": unauthorized,
+                        "404": not_found
+                    }
+                },
+                "put": {
+                    "summary": format!("Replace fields of a {name}"),
+                    "parameters": preconditions,
+                    "requestBody": {
+                        "required": true,
+                        "content": {"application/json": {"schema": schema_ref}}
+                    },
+                    "responses": {
+                        "200": body(&format!("The updated {name}")),
+                        "401": unauthorized,
+                        "412": stale,
+                        "422": invalid
+                    }
+                },
+                "patch": {
+                    "summary": format!("Merge a JSON Merge Patch into a {name}"),
+                    "parameters": preconditions,
+                    "requestBody": {
+                        "required": true,
+                        "content": {"application/merge-patch+json": {"schema": {"type": "object"}}}
+                    },
+                    "responses": {
+                        "200": body(&format!("The updated {name}")),
+                        "401": unauthorized,
+                        "404": not_found,
+                        "412": stale,
+                        "422": invalid
+                    }
+                },
+                "delete": {
+                    "summary": format!("Delete a {name}"),
+                    "responses": {
+                        "200": {"description": format!("The {name} was deleted")},
+                        "401": unauthorized,
+                        "404": not_found
+                    }
+                }
+            }),
+        );
+        let ids = json!({
+            "required": true,
+            "content": {"application/json": {"schema": {"type": "array", "items": {"type": "string"}}}}
+        });
+        let many = |description: String| {
+            json!({
+                "description": description,
+                "content": {"application/json": {"schema": {"type": "array", "items": schema_ref}}}
+            })
+        };
+        paths.insert(
+            format!("{base}/bulk"),
+            json!({
+                "post": {
+                    "summary": format!("Create many {name}s"),
+                    "requestBody": {
+                        "required": true,
+                        "content": {"application/json": {"schema": {"type": "array", "items": schema_ref}}}
+                    },
+                    "responses": {
+                        "201": many(format!("The created {name}s, in request order")),
+                        "400": {"description": "An element was rejected by a validator"},
+                        "401": unauthorized,
+                        "422": invalid
+                    }
+                },
+                "delete": {
+                    "summary": format!("Delete many {name}s by id"),
+                    "requestBody": ids,
+                    "responses": {
+                        "200": {
+                            "description": "Whether each id was deleted",
+                            "content": {"application/json": {"schema": {
+                                "type": "object",
+                                "additionalProperties": {"type": "boolean"}
+                            }}}
+                        },
+                        "401": unauthorized
+                    }
+                }
+            }),
+        );
+        paths.insert(
+            format!("{base}/bulk-read"),
+            json!({
+                "post": {
+                    "summary": format!("Read many {name}s by id"),
+                    "requestBody": ids,
+                    "responses": {
+                        "200": many(format!("Every {name} found")),
+                        "401": unauthorized
+                    }
+                }
+            }),
+        );
+
+        let mut schema = schema_json.as_object().cloned().unwrap_or_default();
+        // A JSON Schema dialect marker isn't valid inside an OpenAPI 3.0 schema.
+        schema.remove("$schema");
+        schemas.insert(name, Value::Object(schema));
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {"title": "meshql", "version": env!("CARGO_PKG_VERSION")},
+        "paths": paths,
+        "components": {"schemas": schemas}
+    })
+}
+
+/// `GET /openapi.json` serving `document`.
+pub fn openapi_router(document: Value) -> Router {
+    Router::new().route("/openapi.json", get(move || async move { Json(document) }))
+}
+
+/// `/farm/api` → `Farm`.
+fn component_name(path: &str) -> String {
+    let segment = path.split('/').find(|s| !s.is_empty()).unwrap_or("root");
+    let mut chars = segment.chars();
+    chars
+        .next()
+        .map(|c| c.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn serves_a_parseable_document() {
+        let farm_schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "required": ["name"],
+            "properties": {"name": {"type": "string"}}
+        });
+        let app = openapi_router(openapi_document(&[("/farm", &farm_schema)]));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let body = reqwest::get(format!("http://{addr}/openapi.json"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        let spec: openapiv3::OpenAPI = serde_json::from_str(&body).unwrap();
+
+        let openapiv3::ReferenceOr::Item(item) = &spec.paths.paths["/farm/{id}"] else {
+            panic!("/farm/{{id}} should be inlined");
+        };
+        assert_eq!(item.parameters.len(), 1);
+        let get = item.get.as_ref().unwrap();
+        let openapiv3::ReferenceOr::Item(ok) =
+            &get.responses.responses[&openapiv3::StatusCode::Code(200)]
+        else {
+            panic!("200 should be inlined");
+        };
+        let schema = ok.content["application/json"].schema.as_ref().unwrap();
+        assert!(matches!(
+            schema,
+            openapiv3::ReferenceOr::Reference { reference } if reference == "#/components/schemas/Farm"
+        ));
+        assert!(spec.components.unwrap().schemas.contains_key("Farm"));
+    }
+}
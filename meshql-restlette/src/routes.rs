@@ -1,13 +1,17 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use meshql_core::{Auth, Envelope, Repository, Stash};
+use chrono::{DateTime, Utc};
+use meshql_core::{
+    merge_patch, Auth, DeleteMode, Envelope, ListOptions, MeshqlError, Repository, Searcher, Stash,
+    CREATED_AT_KEY, DELETED_KEY,
+};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use uuid::Uuid;
 
 /// Validation result: Ok(()) to proceed, Err(message) to reject with 400.
 pub type ValidatorFn =
@@ -41,12 +45,20 @@ pub struct SideEffectContext {
 
 #[derive(Clone)]
 struct RestletteState {
+    /// The path items are read at, below which `POST` locates what it created.
+    base: Arc<str>,
     repo: Arc<dyn Repository>,
     auth: Arc<dyn Auth>,
     defaults: Option<Stash>,
     validator: Option<ValidatorFn>,
+    schema: Option<Arc<jsonschema::Validator>>,
+    /// Properties the schema declares, which list filters always apply to.
+    fields: Arc<HashSet<String>>,
+    /// Serves `GET {path}?at=`, which the repository can't.
+    searcher: Option<Arc<dyn Searcher>>,
     post_create: Option<PostCreateFn>,
     side_effect_ctx: Option<SideEffectContext>,
+    delete_mode: DeleteMode,
 }
 
 pub fn build_restlette_router(
@@ -57,6 +69,47 @@ pub fn build_restlette_router(
     build_restlette_router_ext(path, repo, auth, None, None, None, None)
 }
 
+/// Like [`build_restlette_router`], but POST and PUT bodies, and payloads as
+/// PATCHed, must satisfy the JSON Schema `schema_json` or are rejected with
+/// 422. An empty schema (`{}`) accepts everything.
+///
+/// With a `searcher` over the same entity, `GET {path}?at=` lists the entity
+/// as it was at that time. `delete_mode` decides what `DELETE` does.
+pub fn build_validated_restlette_router(
+    path: &str,
+    repo: Arc<dyn Repository>,
+    auth: Arc<dyn Auth>,
+    schema_json: &serde_json::Value,
+    searcher: Option<Arc<dyn Searcher>>,
+    delete_mode: DeleteMode,
+) -> meshql_core::Result<Router> {
+    let schema = match schema_json.as_object() {
+        Some(obj) if obj.is_empty() => None,
+        _ => Some(Arc::new(jsonschema::validator_for(schema_json).map_err(
+            |e| MeshqlError::Validation(format!("Invalid JSON schema for {path}: {e}")),
+        )?)),
+    };
+    let fields = schema_json
+        .get("properties")
+        .and_then(|p| p.as_object())
+        .map(|p| p.keys().cloned().collect())
+        .unwrap_or_default();
+    let state = RestletteState {
+        base: path.trim_end_matches('/').into(),
+        repo,
+        auth,
+        defaults: None,
+        validator: None,
+        schema,
+        fields: Arc::new(fields),
+        searcher,
+        post_create: None,
+        side_effect_ctx: None,
+        delete_mode,
+    };
+    Ok(restlette_router(path, state))
+}
+
 pub fn build_restlette_router_ext(
     path: &str,
     repo: Arc<dyn Repository>,
@@ -67,29 +120,75 @@ pub fn build_restlette_router_ext(
     side_effect_ctx: Option<SideEffectContext>,
 ) -> Router {
     let state = RestletteState {
+        base: path.trim_end_matches('/').into(),
         repo,
         auth,
         defaults,
         validator,
+        schema: None,
+        fields: Arc::default(),
+        searcher: None,
         post_create,
         side_effect_ctx,
+        delete_mode: DeleteMode::Soft,
     };
-    let item_path = format!("{}/:id", path.trim_end_matches('/'));
+    restlette_router(path, state)
+}
+
+fn restlette_router(path: &str, state: RestletteState) -> Router {
+    let base = path.trim_end_matches('/');
+    let item_path = format!("{base}/:id");
 
     Router::new()
         .route(path, post(create_handler).get(list_handler))
+        .route(
+            &format!("{base}/bulk"),
+            post(bulk_create_handler).delete(bulk_delete_handler),
+        )
+        .route(&format!("{base}/bulk-read"), post(bulk_read_handler))
         .route(
             &item_path,
-            get(read_handler).put(update_handler).delete(delete_handler),
+            get(read_handler)
+                .put(update_handler)
+                .patch(patch_handler)
+                .delete(delete_handler),
         )
         .with_state(state)
 }
 
-async fn create_handler(
-    State(state): State<RestletteState>,
-    Json(mut payload): Json<Stash>,
-) -> impl IntoResponse {
-    // Apply defaults for missing fields
+/// Every way `payload` breaks the configured JSON Schema, with each error's
+/// path prefixed by `prefix`.
+fn schema_violations(
+    state: &RestletteState,
+    payload: &Stash,
+    prefix: &str,
+) -> Vec<serde_json::Value> {
+    let Some(schema) = state.schema.as_ref() else {
+        return Vec::new();
+    };
+    let instance = serde_json::Value::Object(payload.clone());
+    schema
+        .iter_errors(&instance)
+        .map(|e| serde_json::json!({"path": format!("{prefix}{}", e.instance_path), "message": e.to_string()}))
+        .collect()
+}
+
+fn unprocessable(errors: Vec<serde_json::Value>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({ "errors": errors })),
+    )
+        .into_response()
+}
+
+/// A 422 listing every way `payload` breaks the configured JSON Schema, if it does.
+fn schema_errors(state: &RestletteState, payload: &Stash) -> Option<Response> {
+    let errors = schema_violations(state, payload, "");
+    (!errors.is_empty()).then(|| unprocessable(errors))
+}
+
+/// Fill in the configured defaults for fields `payload` doesn't set.
+fn apply_defaults(state: &RestletteState, payload: &mut Stash) {
     if let Some(defaults) = &state.defaults {
         for (k, v) in defaults {
             if !payload.contains_key(k) {
@@ -97,84 +196,454 @@ async fn create_handler(
             }
         }
     }
+}
 
-    // Run validator
-    if let Some(validator) = &state.validator {
-        let ctx = ValidatorContext::default();
-        if let Err(msg) = validator(&payload, &ctx) {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": msg})),
-            )
-                .into_response();
+/// Run the configured validator, turning a rejection into a 400.
+fn validation_error(state: &RestletteState, payload: &Stash) -> Option<Response> {
+    let validator = state.validator.as_ref()?;
+    let ctx = ValidatorContext::default();
+    let msg = validator(payload, &ctx).err()?;
+    Some(
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": msg})),
+        )
+            .into_response(),
+    )
+}
+
+/// Hand `created` to the post-create side effect, if one is configured.
+fn fire_post_create(state: &RestletteState, created: &serde_json::Value) {
+    if let (Some(post_create), Some(ctx)) = (&state.post_create, &state.side_effect_ctx) {
+        let created = created.clone();
+        let ctx = ctx.clone();
+        let post_create = Arc::clone(post_create);
+        tokio::spawn(async move {
+            post_create(created, ctx);
+        });
+    }
+}
+
+/// The payload with `id` folded in, as every route returns it.
+fn to_json(env: Envelope) -> serde_json::Value {
+    let mut payload = env.payload;
+    payload.insert("id".to_string(), serde_json::Value::String(env.id));
+    serde_json::Value::Object(payload)
+}
+
+/// The strong `ETag` of a stored version: its creation time in epoch millis.
+fn etag(env: &Envelope) -> String {
+    format!("\"{}\"", env.created_at.timestamp_millis())
+}
+
+/// `env` as JSON, with its [`etag`] for a later `If-Match`.
+fn versioned(env: Envelope) -> Response {
+    ([(header::ETAG, etag(&env))], Json(to_json(env))).into_response()
+}
+
+/// The version a conditional `PUT` or `PATCH` was based on: the newest
+/// [`etag`] `If-Match` lists or, without one, the end of the second
+/// `If-Unmodified-Since` names. `Ok(None)` when the write is unconditional,
+/// as for `If-Match: *`, and a 412 when `If-Match` names no version this
+/// restlette hands out. A malformed `If-Unmodified-Since` is ignored.
+fn precondition(headers: &HeaderMap) -> Result<Option<DateTime<Utc>>, StatusCode> {
+    if let Some(if_match) = headers.get(header::IF_MATCH) {
+        let tags: Vec<&str> = if_match
+            .to_str()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .collect();
+        if tags.contains(&"*") {
+            return Ok(None);
         }
+        return tags
+            .iter()
+            .filter_map(|tag| tag.strip_prefix('"')?.strip_suffix('"')?.parse().ok())
+            .max()
+            .and_then(DateTime::from_timestamp_millis)
+            .map(Some)
+            .ok_or(StatusCode::PRECONDITION_FAILED);
     }
+    Ok(headers
+        .get(header::IF_UNMODIFIED_SINCE)
+        .and_then(|since| since.to_str().ok())
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+        .map(|since| since.with_timezone(&Utc) + chrono::Duration::milliseconds(999)))
+}
 
-    let id = Uuid::new_v4().to_string();
-    let tokens = state.auth.get_auth_token(&Stash::new());
-    let envelope = Envelope::new(id, payload, tokens.clone());
+/// [`error_response`], but 412 when a conditional write lost to a newer version.
+fn precondition_failed(e: MeshqlError) -> Response {
+    match e {
+        MeshqlError::Conflict(message) => {
+            (StatusCode::PRECONDITION_FAILED, message).into_response()
+        }
+        e => error_response(e),
+    }
+}
+
+/// The caller's credentials, or a 401 response when the request isn't authenticated.
+async fn credentials(state: &RestletteState, headers: &HeaderMap) -> Result<Vec<String>, Response> {
+    state
+        .auth
+        .authorize(headers)
+        .await
+        .map_err(|_| StatusCode::UNAUTHORIZED.into_response())
+}
+
+/// The response for a failed repository call: 403 when the caller may not
+/// touch the record, 401 when it isn't authenticated, 409 when a conditional
+/// write found a newer version, otherwise 500.
+fn error_response(e: MeshqlError) -> Response {
+    let status = match e {
+        MeshqlError::NotAuthorized(_) => StatusCode::FORBIDDEN,
+        MeshqlError::Unauthorized => StatusCode::UNAUTHORIZED,
+        MeshqlError::Conflict(_) => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (status, e.to_string()).into_response()
+}
+
+async fn create_handler(
+    State(state): State<RestletteState>,
+    headers: HeaderMap,
+    Json(mut payload): Json<Stash>,
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+
+    apply_defaults(&state, &mut payload);
+    if let Some(response) = validation_error(&state, &payload) {
+        return response;
+    }
+    if let Some(response) = schema_errors(&state, &payload) {
+        return response;
+    }
+
+    // The repository names it, by its id strategy.
+    let envelope = Envelope::new(String::new(), payload, tokens.clone());
     match state.repo.create(envelope, &tokens).await {
         Ok(env) => {
-            let mut payload = env.payload;
-            payload.insert("id".to_string(), serde_json::Value::String(env.id));
-            let result = serde_json::Value::Object(payload);
-
-            // Fire post-create side effect
-            if let (Some(post_create), Some(ctx)) = (&state.post_create, &state.side_effect_ctx) {
-                let result_clone = result.clone();
-                let ctx_clone = ctx.clone();
-                let post_create = Arc::clone(post_create);
-                tokio::spawn(async move {
-                    post_create(result_clone, ctx_clone);
-                });
-            }
+            let location = format!("{}/{}", state.base, env.id);
+            let result = to_json(env);
+            fire_post_create(&state, &result);
+            (
+                StatusCode::CREATED,
+                [(header::LOCATION, location)],
+                Json(result),
+            )
+                .into_response()
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+/// `POST {path}/bulk`: create every payload in the array with one
+/// `create_many`. Nothing is stored unless every element passes validation.
+async fn bulk_create_handler(
+    State(state): State<RestletteState>,
+    headers: HeaderMap,
+    Json(payloads): Json<Vec<Stash>>,
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
 
-            (StatusCode::CREATED, Json(result)).into_response()
+    let mut envelopes = Vec::with_capacity(payloads.len());
+    let mut errors = Vec::new();
+    for (i, mut payload) in payloads.into_iter().enumerate() {
+        apply_defaults(&state, &mut payload);
+        if let Some(response) = validation_error(&state, &payload) {
+            return response;
         }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        errors.extend(schema_violations(&state, &payload, &format!("/{i}")));
+        envelopes.push(Envelope::new(String::new(), payload, tokens.clone()));
+    }
+    if !errors.is_empty() {
+        return unprocessable(errors);
+    }
+
+    match state.repo.create_many(envelopes, &tokens).await {
+        Ok(created) => {
+            let results: Vec<serde_json::Value> = created.into_iter().map(to_json).collect();
+            for result in &results {
+                fire_post_create(&state, result);
+            }
+            (StatusCode::CREATED, Json(results)).into_response()
+        }
+        Err(e) => error_response(e),
     }
 }
 
-async fn list_handler(State(state): State<RestletteState>) -> impl IntoResponse {
-    let tokens = state.auth.get_auth_token(&Stash::new());
-    match state.repo.list(&tokens).await {
+/// `POST {path}/bulk-read`: every live item among the array of ids.
+async fn bulk_read_handler(
+    State(state): State<RestletteState>,
+    headers: HeaderMap,
+    Json(ids): Json<Vec<String>>,
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+    match state.repo.read_many(&ids, &tokens).await {
         Ok(envelopes) => {
-            let items: Vec<serde_json::Value> = envelopes
+            let items: Vec<serde_json::Value> = envelopes.into_iter().map(to_json).collect();
+            Json(items).into_response()
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+/// `DELETE {path}/bulk`: remove the array of ids, answering with whether each was removed.
+async fn bulk_delete_handler(
+    State(state): State<RestletteState>,
+    headers: HeaderMap,
+    Json(ids): Json<Vec<String>>,
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+    let removed = match state.delete_mode {
+        DeleteMode::Soft => state.repo.remove_many(&ids, &tokens).await,
+        DeleteMode::Hard => purge_many(&state, &ids, &tokens).await,
+        DeleteMode::Forbidden => return delete_forbidden("POST"),
+    };
+    match removed {
+        Ok(removed) => Json(removed).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Purge every id, reporting whether each had any versions to purge.
+async fn purge_many(
+    state: &RestletteState,
+    ids: &[String],
+    tokens: &[String],
+) -> meshql_core::Result<HashMap<String, bool>> {
+    let mut purged = HashMap::with_capacity(ids.len());
+    for id in ids {
+        purged.insert(id.clone(), state.repo.purge(id, tokens).await? > 0);
+    }
+    Ok(purged)
+}
+
+/// The 405 a [`DeleteMode::Forbidden`] restlette answers `DELETE` with, on a
+/// route that still takes the `allow`ed methods.
+fn delete_forbidden(allow: &'static str) -> Response {
+    (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, allow)]).into_response()
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+/// The query parameter `name` parsed as a `T`, or why it isn't one.
+fn param<T: std::str::FromStr>(
+    params: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    params
+        .get(name)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format!("{name} must be a non-negative integer, got {v}"))
+        })
+        .transpose()
+}
+
+/// Whether `value` reads as `wanted`. Strings compare as written, numbers and
+/// booleans by value.
+fn field_equals(value: Option<&serde_json::Value>, wanted: &str) -> bool {
+    match value {
+        Some(serde_json::Value::String(s)) => s == wanted,
+        Some(v) => serde_json::from_str::<serde_json::Value>(wanted).is_ok_and(|w| w == *v),
+        None => false,
+    }
+}
+
+/// The values a field read as `wanted` may hold, for a search: the string
+/// itself, and the number or boolean it spells.
+fn equal_values(wanted: &str) -> serde_json::Value {
+    match serde_json::from_str::<serde_json::Value>(wanted) {
+        Ok(value @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+            serde_json::json!([wanted, value])
+        }
+        _ => serde_json::json!([wanted]),
+    }
+}
+
+/// `GET {path}`: every live item, narrowed by query parameters. `limit` and
+/// `offset` page through the items in id order, `at` (epoch millis) lists
+/// them as they were then, and any other parameter naming a schema property
+/// keeps the items whose field equals it. Filters and `at` are searched for
+/// with the searcher; without one `at` is refused and filters apply in memory,
+/// to any field the items have. Parameters naming no field are ignored.
+/// `X-Total-Count` is how many items matched before `limit` and `offset`.
+async fn list_handler(
+    State(state): State<RestletteState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+    let (limit, offset, at) = match (
+        param::<usize>(&params, "limit"),
+        param::<usize>(&params, "offset"),
+        param::<u64>(&params, "at"),
+    ) {
+        (Ok(limit), Ok(offset), Ok(at)) => (limit, offset, at.map(|at| at as i64)),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => return bad_request(e),
+    };
+    let page = ListOptions {
+        limit,
+        offset: offset.unwrap_or(0),
+        ..Default::default()
+    };
+
+    let filters: Vec<(&String, &String)> = params
+        .iter()
+        .filter(|(name, _)| !matches!(name.as_str(), "limit" | "offset" | "at"))
+        .collect();
+    let found = match &state.searcher {
+        Some(searcher) => {
+            let filters: Vec<_> = filters
                 .into_iter()
-                .map(|env| {
-                    let mut payload = env.payload;
-                    payload.insert("id".to_string(), serde_json::Value::String(env.id));
-                    serde_json::Value::Object(payload)
-                })
+                .filter(|(name, _)| state.fields.contains(*name))
                 .collect();
-            Json(serde_json::Value::Array(items)).into_response()
+            match (at, filters.is_empty()) {
+                (None, true) => list_page(&state, page, &tokens).await,
+                _ => search_page(searcher.as_ref(), &filters, page, at, &tokens).await,
+            }
+        }
+        None if at.is_some() => {
+            return bad_request("at is not supported by this endpoint".to_string())
+        }
+        None if filters.is_empty() => list_page(&state, page, &tokens).await,
+        None => filter_page(&state, &filters, page, &tokens).await,
+    };
+    match found {
+        Ok((total, items)) => ([("x-total-count", total.to_string())], Json(items)).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// The `page` of live items, and how many there are.
+async fn list_page(
+    state: &RestletteState,
+    page: ListOptions,
+    tokens: &[String],
+) -> meshql_core::Result<(u64, Vec<serde_json::Value>)> {
+    let items = state.repo.list_with(tokens, page).await?;
+    let total = state.repo.count(tokens).await?;
+    Ok((total, items.into_iter().map(to_json).collect()))
+}
+
+/// The `page` of items `searcher` finds with each filtered field equal to its
+/// parameter, as they were `at`, and how many it finds in all.
+async fn search_page(
+    searcher: &dyn Searcher,
+    filters: &[(&String, &String)],
+    page: ListOptions,
+    at: Option<i64>,
+    tokens: &[String],
+) -> meshql_core::Result<(u64, Vec<serde_json::Value>)> {
+    let at = at.unwrap_or_else(|| Utc::now().timestamp_millis());
+    // Values go in as args so nothing a client sends is read as template
+    let mut query = serde_json::Map::new();
+    let mut args = Stash::new();
+    for (i, (name, wanted)) in filters.iter().enumerate() {
+        let key = format!("filter{i}");
+        query.insert(
+            format!("payload.{name}"),
+            serde_json::json!({ "$in": format!("{{{{{key}}}}}") }),
+        );
+        args.insert(key, equal_values(wanted));
+    }
+    let template = serde_json::Value::Object(query).to_string();
+
+    let total = searcher.count(&template, &args, tokens, at).await?;
+    if page.is_paged() {
+        args.insert("sort".to_string(), "id".into());
+        args.insert("offset".to_string(), page.offset.into());
+        if let Some(limit) = page.limit {
+            args.insert("limit".to_string(), limit.into());
         }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
+    let items = searcher.find_all(&template, &args, tokens, at).await?;
+    let items = items
+        .into_iter()
+        .map(|mut stash| {
+            stash.remove(CREATED_AT_KEY);
+            stash.remove(DELETED_KEY);
+            serde_json::Value::Object(stash)
+        })
+        .collect();
+    Ok((total, items))
+}
+
+/// The `page` of live items whose filtered fields equal their parameters,
+/// filtered in memory, and how many match. Filters naming a field no item has
+/// are ignored.
+async fn filter_page(
+    state: &RestletteState,
+    filters: &[(&String, &String)],
+    page: ListOptions,
+    tokens: &[String],
+) -> meshql_core::Result<(u64, Vec<serde_json::Value>)> {
+    let mut items = state.repo.list(tokens).await?;
+    let filters: Vec<_> = filters
+        .iter()
+        .filter(|(name, _)| {
+            state.fields.contains(*name) || items.iter().any(|env| env.payload.contains_key(*name))
+        })
+        .collect();
+    items.retain(|env| {
+        filters
+            .iter()
+            .all(|(name, v)| field_equals(env.payload.get(*name), v))
+    });
+    let total = items.len() as u64;
+    Ok((total, page.page(items).into_iter().map(to_json).collect()))
 }
 
 async fn read_handler(
     State(state): State<RestletteState>,
+    headers: HeaderMap,
     Path(id): Path<String>,
-) -> impl IntoResponse {
-    let tokens = state.auth.get_auth_token(&Stash::new());
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
     match state.repo.read(&id, &tokens, None).await {
-        Ok(Some(env)) => {
-            let mut payload = env.payload;
-            payload.insert("id".to_string(), serde_json::Value::String(env.id));
-            Json(serde_json::Value::Object(payload)).into_response()
-        }
+        Ok(Some(env)) => versioned(env),
         Ok(None) => StatusCode::NOT_FOUND.into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => error_response(e),
     }
 }
 
+/// `If-Match` or `If-Unmodified-Since` make it conditional on [`precondition`]:
+/// it fails with 412 if the item changed since, or isn't there.
 async fn update_handler(
     State(state): State<RestletteState>,
+    headers: HeaderMap,
     Path(id): Path<String>,
     Json(payload): Json<Stash>,
-) -> impl IntoResponse {
-    let tokens = state.auth.get_auth_token(&Stash::new());
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+    let expected = match precondition(&headers) {
+        Ok(expected) => expected,
+        Err(status) => return status.into_response(),
+    };
 
     // Merge: read existing, overlay new fields
     let merged = match state.repo.read(&id, &tokens, None).await {
@@ -185,31 +654,672 @@ async fn update_handler(
             }
             merged
         }
+        _ if expected.is_some() => return StatusCode::PRECONDITION_FAILED.into_response(),
         _ => payload,
     };
+    if let Some(response) = schema_errors(&state, &merged) {
+        return response;
+    }
 
     let envelope = Envelope::new(id, merged, tokens.clone());
-    match state.repo.create(envelope, &tokens).await {
-        Ok(env) => {
-            let mut payload = env.payload;
-            payload.insert("id".to_string(), serde_json::Value::String(env.id));
-            Json(serde_json::Value::Object(payload)).into_response()
-        }
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    if expected.is_some() {
+        return match state
+            .repo
+            .create_if_unchanged(envelope, expected, &tokens)
+            .await
+        {
+            Ok(env) => versioned(env),
+            Err(e) => precondition_failed(e),
+        };
+    }
+    // A PUT repeating the current payload leaves the history alone
+    match state.repo.upsert(envelope, &tokens).await {
+        Ok(env) => versioned(env),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Apply a JSON Merge Patch (RFC 7386) to the latest version: nested objects
+/// merge, `null` removes a field and anything else replaces it. The merged
+/// payload must still match the JSON Schema. Conditional like a `PUT`.
+async fn patch_handler(
+    State(state): State<RestletteState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(patch): Json<Stash>,
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+    let expected = match precondition(&headers) {
+        Ok(expected) => expected,
+        Err(status) => return status.into_response(),
+    };
+
+    let mut merged = match state.repo.read(&id, &tokens, None).await {
+        Ok(Some(existing)) => existing.payload,
+        Ok(None) if expected.is_some() => return StatusCode::PRECONDITION_FAILED.into_response(),
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => return error_response(e),
+    };
+    merge_patch(&mut merged, patch.clone());
+    if let Some(response) = schema_errors(&state, &merged) {
+        return response;
+    }
+
+    let updated = match expected {
+        Some(expected) => state
+            .repo
+            .update_if_unchanged(&id, patch, expected, &tokens)
+            .await
+            .map_err(precondition_failed),
+        None => state
+            .repo
+            .update(&id, patch, &tokens)
+            .await
+            .map_err(error_response),
+    };
+    match updated {
+        Ok(Some(env)) => versioned(env),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(response) => response,
     }
 }
 
 async fn delete_handler(
     State(state): State<RestletteState>,
+    headers: HeaderMap,
     Path(id): Path<String>,
-) -> impl IntoResponse {
-    let tokens = state.auth.get_auth_token(&Stash::new());
-    match state.repo.remove(&id, &tokens).await {
+) -> Response {
+    let tokens = match credentials(&state, &headers).await {
+        Ok(tokens) => tokens,
+        Err(response) => return response,
+    };
+    let removed = match state.delete_mode {
+        DeleteMode::Soft => state.repo.remove(&id, &tokens).await,
+        DeleteMode::Hard => state.repo.purge(&id, &tokens).await.map(|n| n > 0),
+        DeleteMode::Forbidden => return delete_forbidden("GET, PUT, PATCH"),
+    };
+    match removed {
         Ok(true) => {
             let body = serde_json::json!({"id": id, "status": "deleted"});
             (StatusCode::OK, Json(body)).into_response()
         }
         Ok(false) => StatusCode::NOT_FOUND.into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meshql_core::NoAuth;
+    use meshql_memory::MemoryRepository;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    async fn serve(app: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{addr}/hen/api")
+    }
+
+    #[tokio::test]
+    async fn create_locates_the_created_item() {
+        let app = build_restlette_router(
+            "/hen/api",
+            Arc::new(MemoryRepository::new()),
+            Arc::new(NoAuth),
+        );
+        let url = serve(app).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(&url)
+            .json(&json!({"name": "chuck"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+        let location = response.headers()["location"].to_str().unwrap().to_string();
+        let created: Value = response.json().await.unwrap();
+        let id = created["id"].as_str().unwrap();
+        assert!(!id.is_empty());
+        assert_eq!(created["name"], "chuck");
+        assert_eq!(location, format!("/hen/api/{id}"));
+
+        let origin = url.trim_end_matches("/hen/api");
+        let read: Value = client
+            .get(format!("{origin}{location}"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(read, created);
+    }
+
+    #[tokio::test]
+    async fn bulk_creates_listable_items() {
+        let app = build_restlette_router(
+            "/hen/api",
+            Arc::new(MemoryRepository::new()),
+            Arc::new(NoAuth),
+        );
+        let url = serve(app).await;
+        let client = reqwest::Client::new();
+
+        let hens: Vec<Value> = (0..100)
+            .map(|i| json!({"name": format!("hen-{i}")}))
+            .collect();
+        let response = client
+            .post(format!("{url}/bulk"))
+            .json(&hens)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+        let created: Vec<Value> = response.json().await.unwrap();
+        assert_eq!(created.len(), 100);
+        assert!(created.iter().all(|hen| hen["id"].is_string()));
+
+        let listed: Vec<Value> = client.get(&url).send().await.unwrap().json().await.unwrap();
+        assert_eq!(listed.len(), 100);
+
+        let ids: Vec<&Value> = created.iter().take(3).map(|hen| &hen["id"]).collect();
+        let read: Vec<Value> = client
+            .post(format!("{url}/bulk-read"))
+            .json(&ids)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(read.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn bulk_create_validates_every_element() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {"name": {"type": "string"}}
+        });
+        let repo = Arc::new(MemoryRepository::new());
+        let app = build_validated_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(NoAuth),
+            &schema,
+            None,
+            DeleteMode::Soft,
+        )
+        .unwrap();
+        let url = serve(app).await;
+
+        let response = reqwest::Client::new()
+            .post(format!("{url}/bulk"))
+            .json(&json!([{"name": "henny"}, {"name": 7}]))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["errors"][0]["path"], "/1/name");
+        assert!(repo.list(&["*".to_string()]).await.unwrap().is_empty());
+    }
+
+    async fn serve_deleting(delete_mode: DeleteMode) -> (Arc<MemoryRepository>, String) {
+        let repo = Arc::new(MemoryRepository::new());
+        let app = build_validated_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(NoAuth),
+            &json!({}),
+            None,
+            delete_mode,
+        )
+        .unwrap();
+        (repo, serve(app).await)
+    }
+
+    async fn create_hen(client: &reqwest::Client, url: &str) -> String {
+        let created: Value = client
+            .post(url)
+            .json(&json!({"name": "henny"}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        created["id"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn forbidden_deletes_are_refused() {
+        let (repo, url) = serve_deleting(DeleteMode::Forbidden).await;
+        let client = reqwest::Client::new();
+        let id = create_hen(&client, &url).await;
+
+        let response = client.delete(format!("{url}/{id}")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "GET, PUT, PATCH");
+        let response = client
+            .delete(format!("{url}/bulk"))
+            .json(&json!([id]))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()["allow"], "POST");
+
+        let star = ["*".to_string()];
+        assert!(repo.read(&id, &star, None).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn hard_deletes_leave_no_history() {
+        let (repo, url) = serve_deleting(DeleteMode::Hard).await;
+        let client = reqwest::Client::new();
+        let id = create_hen(&client, &url).await;
+
+        let response = client.delete(format!("{url}/{id}")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let star = ["*".to_string()];
+        assert!(repo.history(&id, &star).await.unwrap().is_empty());
+        let response = client.get(format!("{url}/{id}")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        let response = client.delete(format!("{url}/{id}")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn bulk_delete_reports_each_id() {
+        let app = build_restlette_router(
+            "/hen/api",
+            Arc::new(MemoryRepository::new()),
+            Arc::new(NoAuth),
+        );
+        let url = serve(app).await;
+        let client = reqwest::Client::new();
+
+        let created: Vec<Value> = client
+            .post(format!("{url}/bulk"))
+            .json(&json!([{"name": "a"}, {"name": "b"}]))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let mut ids: Vec<String> = created
+            .iter()
+            .map(|hen| hen["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.push("missing".to_string());
+
+        let response = client
+            .delete(format!("{url}/bulk"))
+            .json(&ids)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let removed: HashMap<String, bool> = response.json().await.unwrap();
+        assert_eq!(removed.len(), 3);
+        assert!(removed[&ids[0]]);
+        assert!(removed[&ids[1]]);
+        assert!(!removed["missing"]);
+
+        let listed: Vec<Value> = client.get(&url).send().await.unwrap().json().await.unwrap();
+        assert!(listed.is_empty());
+    }
+
+    /// Authenticates every request as `bob`.
+    struct Bob;
+
+    #[async_trait::async_trait]
+    impl Auth for Bob {
+        fn get_auth_token(&self, _context: &Stash) -> Vec<String> {
+            vec!["bob".to_string()]
+        }
+
+        fn is_authorized(&self, credentials: &[String], envelope: &Envelope) -> bool {
+            envelope
+                .authorized_tokens
+                .iter()
+                .any(|t| credentials.contains(t))
+        }
+
+        async fn authorize(&self, _headers: &HeaderMap) -> meshql_core::Result<Vec<String>> {
+            Ok(vec!["bob".to_string()])
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_to_records_the_caller_cant_see_are_forbidden() {
+        let alice = vec!["alice".to_string()];
+        let repo = Arc::new(MemoryRepository::new());
+        let mut hen = Stash::new();
+        hen.insert("name".to_string(), json!("chuck"));
+        repo.create(Envelope::new("hen-1", hen, alice.clone()), &alice)
+            .await
+            .unwrap();
+        let url = serve(build_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(Bob),
+        ))
+        .await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .put(format!("{url}/hen-1"))
+            .json(&json!({"name": "foghorn"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        let response = client.delete(format!("{url}/hen-1")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+        // Reads don't give away that the record exists.
+        let response = client.get(format!("{url}/hen-1")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        let response = client.delete(format!("{url}/hen-2")).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let stored = repo.read("hen-1", &alice, None).await.unwrap().unwrap();
+        assert_eq!(stored.payload["name"], "chuck");
+        assert_eq!(repo.history("hen-1", &alice).await.unwrap().len(), 1);
+    }
+
+    async fn seeded_hens() -> (String, Arc<MemoryRepository>) {
+        let schema = json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}, "type": {"type": "string"}}
+        });
+        let repo = Arc::new(MemoryRepository::new());
+        let searcher = Arc::new(meshql_memory::MemorySearcher::new(repo.store()));
+        let app = build_validated_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(NoAuth),
+            &schema,
+            Some(searcher),
+            DeleteMode::Soft,
+        )
+        .unwrap();
+        let url = serve(app).await;
+        reqwest::Client::new()
+            .post(format!("{url}/bulk"))
+            .json(&json!([
+                {"name": "alpha", "type": "typeA"},
+                {"name": "beta", "type": "typeA"},
+                {"name": "beta", "type": "typeB"},
+                {"name": "gamma", "type": "typeB"}
+            ]))
+            .send()
+            .await
+            .unwrap();
+        (url, repo)
+    }
+
+    async fn get_list(url: &str) -> Vec<Value> {
+        let response = reqwest::get(url).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        response.json().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_payload_fields() {
+        let (url, _) = seeded_hens().await;
+
+        let typed = get_list(&format!("{url}?type=typeA")).await;
+        assert_eq!(typed.len(), 2);
+
+        let both = get_list(&format!("{url}?name=beta&type=typeA")).await;
+        assert_eq!(both.len(), 1);
+        assert_eq!(both[0]["name"], "beta");
+        assert_eq!(both[0]["type"], "typeA");
+
+        let unknown = get_list(&format!("{url}?name=beta&colour=red")).await;
+        assert_eq!(unknown.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn list_filters_numbers_by_value() {
+        let schema = json!({"type": "object", "properties": {"eggs": {"type": "integer"}}});
+        let repo = Arc::new(MemoryRepository::new());
+        let searcher = Arc::new(meshql_memory::MemorySearcher::new(repo.store()));
+        let searched = build_validated_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(NoAuth),
+            &schema,
+            Some(searcher),
+            DeleteMode::Soft,
+        )
+        .unwrap();
+        let listed = build_restlette_router("/hen/api", repo.clone(), Arc::new(NoAuth));
+        let tokens = vec!["*".to_string()];
+        for (id, eggs) in [("hen-1", 3), ("hen-2", 5)] {
+            let payload = json!({"eggs": eggs}).as_object().unwrap().clone();
+            repo.create(Envelope::new(id, payload, tokens.clone()), &tokens)
+                .await
+                .unwrap();
+        }
+
+        for url in [serve(searched).await, serve(listed).await] {
+            let laying = get_list(&format!("{url}?eggs=5")).await;
+            assert_eq!(laying.len(), 1);
+            assert_eq!(laying[0]["id"], "hen-2");
+        }
+    }
+
+    #[tokio::test]
+    async fn list_pages_in_id_order() {
+        let (url, _) = seeded_hens().await;
+        let mut ids: Vec<String> = get_list(&url)
+            .await
+            .iter()
+            .map(|hen| hen["id"].as_str().unwrap().to_string())
+            .collect();
+        ids.sort();
+
+        let response = reqwest::get(format!("{url}?limit=2&offset=1"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-total-count"], "4");
+        let page: Vec<Value> = response.json().await.unwrap();
+        let paged: Vec<&str> = page.iter().map(|hen| hen["id"].as_str().unwrap()).collect();
+        assert_eq!(paged, [ids[1].as_str(), ids[2].as_str()]);
+
+        let response = reqwest::get(format!("{url}?type=typeB&limit=1"))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-total-count"], "2");
+
+        let response = reqwest::get(format!("{url}?limit=-1")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn list_at_a_time_shows_earlier_versions() {
+        let (url, repo) = seeded_hens().await;
+        let tokens = vec!["*".to_string()];
+        let mut earlier = Envelope::new(
+            "delta",
+            json!({"name": "delta", "type": "typeA"})
+                .as_object()
+                .unwrap()
+                .clone(),
+            tokens.clone(),
+        );
+        earlier.created_at = chrono::Utc::now() - chrono::Duration::hours(1);
+        repo.create(earlier, &tokens).await.unwrap();
+        let mut patch = Stash::new();
+        patch.insert("type".to_string(), json!("typeB"));
+        repo.update("delta", patch, &tokens).await.unwrap();
+
+        let past = (chrono::Utc::now() - chrono::Duration::minutes(30)).timestamp_millis();
+        let then = get_list(&format!("{url}?at={past}")).await;
+        assert_eq!(then.len(), 1);
+        assert_eq!(then[0]["id"], "delta");
+        assert_eq!(then[0]["type"], "typeA");
+
+        let now = get_list(&format!("{url}?name=delta")).await;
+        assert_eq!(now[0]["type"], "typeB");
+    }
+
+    #[tokio::test]
+    async fn list_at_needs_a_searcher() {
+        let app = build_restlette_router(
+            "/hen/api",
+            Arc::new(MemoryRepository::new()),
+            Arc::new(NoAuth),
+        );
+        let url = serve(app).await;
+
+        let response = reqwest::get(format!("{url}?at=0")).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn patch_merges_into_the_latest_version() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "type": {"type": "string"},
+                "perch": {"type": "object"}
+            }
+        });
+        let repo = Arc::new(MemoryRepository::new());
+        let app = build_validated_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(NoAuth),
+            &schema,
+            None,
+            DeleteMode::Soft,
+        )
+        .unwrap();
+        let url = serve(app).await;
+        let client = reqwest::Client::new();
+        let patch = |id: &str, body: Value| {
+            client
+                .patch(format!("{url}/{id}"))
+                .header("content-type", "application/merge-patch+json")
+                .body(body.to_string())
+                .send()
+        };
+
+        let created: Value = client
+            .post(&url)
+            .json(&json!({"name": "chuck", "type": "typeA", "perch": {"x": 1, "y": 2}}))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let id = created["id"].as_str().unwrap();
+
+        let response = patch(id, json!({"name": "foghorn", "perch": {"y": 3}}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let patched: Value = response.json().await.unwrap();
+        assert_eq!(patched["name"], "foghorn");
+        assert_eq!(patched["type"], "typeA");
+        assert_eq!(patched["perch"], json!({"x": 1, "y": 3}));
+
+        let response = patch(id, json!({"type": null})).await.unwrap();
+        let patched: Value = response.json().await.unwrap();
+        assert!(patched.get("type").is_none(), "{patched}");
+        assert_eq!(patched["name"], "foghorn");
+
+        let response = patch(id, json!({"name": 5})).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+        let tokens = vec!["*".to_string()];
+        assert_eq!(repo.history(id, &tokens).await.unwrap().len(), 3);
+
+        let response = patch("missing", json!({"name": "x"})).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn writes_based_on_a_stale_version_fail_their_precondition() {
+        let repo = Arc::new(MemoryRepository::new());
+        let url = serve(build_restlette_router(
+            "/hen/api",
+            repo.clone(),
+            Arc::new(NoAuth),
+        ))
+        .await;
+        let tokens = vec!["*".to_string()];
+        let mut earlier = Envelope::new("hen-1", Stash::new(), tokens.clone());
+        earlier.created_at = chrono::Utc::now() - chrono::Duration::hours(1);
+        repo.create(earlier.clone(), &tokens).await.unwrap();
+        let client = reqwest::Client::new();
+        let write = |method: reqwest::Method, id: &str, header: (&str, String)| {
+            client
+                .request(method, format!("{url}/{id}"))
+                .header(header.0, header.1)
+                .json(&json!({"name": "chuck"}))
+                .send()
+        };
+
+        let read = reqwest::get(format!("{url}/hen-1")).await.unwrap();
+        let stale = read.headers()["etag"].to_str().unwrap().to_string();
+        assert_eq!(
+            stale,
+            format!("\"{}\"", earlier.created_at.timestamp_millis())
+        );
+
+        let response = write(reqwest::Method::PUT, "hen-1", ("if-match", stale.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let current = response.headers()["etag"].to_str().unwrap().to_string();
+
+        for header in [
+            ("if-match", stale),
+            ("if-match", "\"not-a-version\"".to_string()),
+            ("if-unmodified-since", earlier.created_at.to_rfc2822()),
+        ] {
+            let response = write(reqwest::Method::PATCH, "hen-1", header)
+                .await
+                .unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+        }
+        let response = write(reqwest::Method::PUT, "hen-2", ("if-match", "*".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let response = write(
+            reqwest::Method::PATCH,
+            "hen-3",
+            ("if-match", current.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+        assert_eq!(repo.history("hen-1", &tokens).await.unwrap().len(), 2);
+
+        let response = write(reqwest::Method::PATCH, "hen-1", ("if-match", current))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(repo.history("hen-1", &tokens).await.unwrap().len(), 3);
     }
 }
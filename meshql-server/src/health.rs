@@ -0,0 +1,45 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use meshql_core::{Repository, Searcher};
+use serde_json::json;
+use std::sync::Arc;
+
+/// A store `/ready` probes, named by the graphlette or restlette path it backs.
+pub(crate) enum Backend {
+    Repository(Arc<dyn Repository>),
+    Searcher(Arc<dyn Searcher>),
+}
+
+/// `GET /health`, 200 whenever the app is serving, and `GET /ready`, 200 once
+/// every backend answers a ping and 503 naming the ones that don't.
+pub(crate) fn health_router(backends: Vec<(String, Backend)>) -> Router {
+    let backends = Arc::new(backends);
+    Router::new()
+        .route("/health", get(|| async { Json(json!({"status": "ok"})) }))
+        .route("/ready", get(move || ready(Arc::clone(&backends))))
+}
+
+async fn ready(backends: Arc<Vec<(String, Backend)>>) -> Response {
+    let mut failing = Vec::new();
+    for (name, backend) in backends.iter() {
+        let ping = match backend {
+            Backend::Repository(repo) => repo.ping().await,
+            Backend::Searcher(searcher) => searcher.ping().await,
+        };
+        if ping.is_err() {
+            failing.push(name.clone());
+        }
+    }
+
+    if failing.is_empty() {
+        Json(json!({"status": "ready"})).into_response()
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({"status": "unavailable", "failing": failing})),
+        )
+            .into_response()
+    }
+}
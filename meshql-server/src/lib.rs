@@ -1,57 +1,323 @@
+mod health;
+mod manifest;
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "otel")]
+mod otel;
+mod preflight;
+mod stats;
+
+use axum::extract::DefaultBodyLimit;
+use axum::http::HeaderValue;
 use axum::Router;
-use meshql_core::{Auth, NoAuth, ServerConfig};
-use meshql_graphlette::{build_schema, GraphletteRouter, ResolverRegistry};
-use meshql_restlette::build_restlette_router;
+use meshql_core::{
+    Auth, ConfigError, CorsConfig, NoAuth, Searcher, ServerConfig, DEFAULT_MAX_BODY_BYTES,
+};
+use meshql_graphlette::{build_schema_at, GraphletteRouter, ResolverRegistry, REQUEST_ID_HEADER};
+use meshql_restlette::{build_validated_restlette_router, openapi_document, openapi_router};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer};
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tracing::{Level, Span};
 
+pub use manifest::{load_server_config, server_config_from_manifest};
 pub use meshql_restlette::{
     build_restlette_router_ext, PostCreateFn, SideEffectContext, ValidatorContext, ValidatorFn,
 };
+#[cfg(feature = "otel")]
+pub use otel::init_otlp_tracing;
+pub use preflight::validate;
 
 /// Build the full Axum application from a ServerConfig.
 ///
 /// For each graphlette, it also registers the searcher in the ResolverRegistry under the
-/// graphlette path so that inter-graphlette resolution works without HTTP.
-pub async fn build_app(config: ServerConfig) -> anyhow::Result<Router> {
+/// graphlette path so that inter-graphlette resolution works without HTTP. A restlette at
+/// `/<entity>/api` lends its repository to the graphlette at `/<entity>/graph` for mutations,
+/// and borrows that graphlette's searcher to list the entity as of a time.
+pub async fn build_app(config: ServerConfig) -> Result<Router, ConfigError> {
     build_app_ext(config, Router::new()).await
 }
 
 /// Build the full Axum application, merging in extra custom routes.
-pub async fn build_app_ext(config: ServerConfig, extra: Router) -> anyhow::Result<Router> {
-    let mut registry = ResolverRegistry::new();
+pub async fn build_app_ext(config: ServerConfig, extra: Router) -> Result<Router, ConfigError> {
+    build_app_with_auth(config, extra, Arc::new(NoAuth)).await
+}
 
-    // First pass: register all graphlette searchers in the registry
-    for g in &config.graphlettes {
-        registry.register(&g.path, Arc::clone(&g.searcher), g.root_config.clone());
-    }
+/// Build the full Axum application, authorizing every restlette and graphlette
+/// request with `auth`.
+///
+/// Restlettes are described by an OpenAPI document at `/openapi.json`. `/health`
+/// answers 200 once the app is built, and `/ready` pings every graphlette's
+/// searcher and restlette's repository, answering 503 when any is unreachable.
+/// `/stats` reports each restlette's document count, and its connection pool
+/// for backends that keep one, keyed by path.
+/// With the `metrics` feature, each graphlette also records request counts,
+/// latency and searcher fan-out, served at `/metrics`. With the `otel` feature,
+/// requests, relation resolvers and searcher calls run in `tracing` spans; see
+/// [`init_otlp_tracing`] to export them.
+///
+/// Every HTTP request is logged through `tower_http::trace` at `info`, and every
+/// GraphQL operation to the `meshql::graphql` target with its duration.
+///
+/// Each request keeps the `X-Request-Id` it was sent with, or is given a fresh
+/// UUID, which is echoed on the response. The id is recorded on the request's
+/// span, so everything logged while serving it, from resolvers to searchers,
+/// can be correlated; relations resolved over HTTP send it on.
+///
+/// Fails with the first problem [`validate`] finds, such as two graphlettes
+/// or restlettes sharing a path or a route served under one, or one claiming
+/// a route the app serves itself (`/health`, `/ready`, `/stats`, `/metrics`
+/// or `/openapi.json`), a resolver targeting a path no graphlette serves or a
+/// query it doesn't define, a query template that doesn't compile, or a schema
+/// that doesn't build or has a relation no resolver fills in.
+pub async fn build_app_with_auth(
+    config: ServerConfig,
+    extra: Router,
+    auth: Arc<dyn Auth>,
+) -> Result<Router, ConfigError> {
+    validate(&config).map_err(|mut problems| problems.remove(0))?;
+    let cors = cors_layer(config.cors.as_ref())?;
+    let body_limit = config.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES);
+
+    #[cfg(feature = "metrics")]
+    let config = {
+        let mut config = config;
+        for g in &mut config.graphlettes {
+            g.searcher = Arc::new(metrics::MeteredSearcher(Arc::clone(&g.searcher)));
+        }
+        config
+    };
+
+    #[cfg(feature = "otel")]
+    let config = {
+        let mut config = config;
+        for g in &mut config.graphlettes {
+            g.searcher = Arc::new(otel::TracedSearcher {
+                inner: Arc::clone(&g.searcher),
+                graphlette: g.path.clone(),
+            });
+        }
+        config
+    };
 
-    let mut app = Router::new();
+    let registry = registry(&config);
+
+    // Liveness, and readiness of every backend
+    let backends = config
+        .graphlettes
+        .iter()
+        .map(|g| {
+            let searcher = health::Backend::Searcher(Arc::clone(&g.searcher));
+            (g.path.clone(), searcher)
+        })
+        .chain(config.restlettes.iter().map(|r| {
+            let repository = health::Backend::Repository(Arc::clone(&r.repository));
+            (r.path.clone(), repository)
+        }))
+        .collect();
+    let mut app = health::health_router(backends);
+
+    // What each restlette's repository holds
+    let repositories = config
+        .restlettes
+        .iter()
+        .map(|r| (r.path.clone(), Arc::clone(&r.repository)))
+        .collect();
+    app = app.merge(stats::stats_router(repositories));
+
+    // A restlette lists `?at=` a time through its graphlette's searcher
+    let searchers: HashMap<String, Arc<dyn Searcher>> = config
+        .graphlettes
+        .iter()
+        .map(|g| (g.path.clone(), Arc::clone(&g.searcher)))
+        .collect();
 
     // Add graphlette routes
     for g in config.graphlettes {
-        let schema = build_schema(&g.schema_text, &g.root_config, g.searcher, &registry)
-            .map_err(|e| anyhow::anyhow!("Schema build error for {}: {:?}", g.path, e))?;
-        let router = GraphletteRouter::build(&g.path, schema);
+        let cache_control = g
+            .root_config
+            .get_cache_control
+            .as_deref()
+            .map(|value| {
+                HeaderValue::from_str(value).map_err(|_| ConfigError::CacheControl {
+                    path: g.path.clone(),
+                    value: value.to_string(),
+                })
+            })
+            .transpose()?;
+        let schema = build_schema_at(
+            &g.path,
+            &g.schema_text,
+            &g.root_config,
+            g.searcher,
+            &registry,
+        )
+        .map_err(|e| ConfigError::SchemaParse {
+            path: g.path.clone(),
+            message: format!("{e:?}"),
+        })?;
+        let router = GraphletteRouter::build_with_cache_control(
+            &g.path,
+            schema,
+            Arc::clone(&auth),
+            cache_control,
+        );
+        #[cfg(feature = "metrics")]
+        let router = metrics::instrument(router, &g.path);
         app = app.merge(router);
     }
 
+    // Describe the restlettes at /openapi.json
+    if !config.restlettes.is_empty() {
+        let restlettes: Vec<(&str, &serde_json::Value)> = config
+            .restlettes
+            .iter()
+            .map(|r| (r.path.as_str(), &r.schema_json))
+            .collect();
+        app = app.merge(openapi_router(openapi_document(&restlettes)));
+    }
+
     // Add restlette routes
-    let auth: Arc<dyn Auth> = Arc::new(NoAuth);
     for r in config.restlettes {
-        let router = build_restlette_router(&r.path, r.repository, Arc::clone(&auth));
+        let searcher = r
+            .path
+            .strip_suffix("/api")
+            .and_then(|base| searchers.get(&format!("{base}/graph")))
+            .cloned();
+        let router = build_validated_restlette_router(
+            &r.path,
+            r.repository,
+            Arc::clone(&auth),
+            &r.schema_json,
+            searcher,
+            r.delete_mode,
+        )
+        .map_err(|e| ConfigError::Restlette {
+            path: r.path.clone(),
+            message: e.to_string(),
+        })?;
         app = app.merge(router);
     }
 
+    #[cfg(feature = "metrics")]
+    {
+        app = app.merge(metrics::metrics_router());
+    }
+
     // Merge extra custom routes (these take priority for overlapping paths)
     app = extra.merge(app);
 
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    // Log every request's method, path, status and latency
+    let trace = TraceLayer::new_for_http()
+        .make_span_with(request_span)
+        .on_response(DefaultOnResponse::new().level(Level::INFO));
+    // Cap bodies at the configured size, rather than at axum's own default
+    let app = app
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(body_limit));
+    // Tag every request with an id before it's traced, and echo it back
+    Ok(app
+        .layer(cors)
+        .layer(trace)
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid)))
+}
 
-    Ok(app.layer(cors))
+/// The `info` span each request is served in, as `tower_http::trace` names it,
+/// along with the request's id.
+fn request_span<B>(request: &axum::http::Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        request_id,
+    )
+}
+
+/// Every graphlette's searcher and root config, keyed by path, so resolvers
+/// can reach them without HTTP. A restlette at `/<entity>/api` lends its
+/// repository to the graphlette at `/<entity>/graph` for mutations.
+fn registry(config: &ServerConfig) -> ResolverRegistry {
+    let mut registry = ResolverRegistry::new();
+    for g in &config.graphlettes {
+        registry.register(&g.path, Arc::clone(&g.searcher), g.root_config.clone());
+    }
+    for r in &config.restlettes {
+        if let Some(base) = r.path.strip_suffix("/api") {
+            registry.register_repository(&format!("{base}/graph"), Arc::clone(&r.repository));
+        }
+    }
+    registry
+}
+
+/// The CORS layer for `config`, or one allowing any origin, method and header without it.
+fn cors_layer(config: Option<&CorsConfig>) -> Result<CorsLayer, ConfigError> {
+    let Some(config) = config else {
+        return Ok(CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any));
+    };
+    config
+        .validate()
+        .map_err(|e| ConfigError::Cors(e.to_string()))?;
+
+    let wildcard = |values: &[String]| values.iter().any(|v| v == "*");
+    let origins = if wildcard(&config.allow_origins) {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            config
+                .allow_origins
+                .iter()
+                .map(|o| o.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ConfigError::Cors(format!("invalid origin: {e}")))?,
+        )
+    };
+    let methods = if config.allow_methods.is_empty() {
+        AllowMethods::mirror_request()
+    } else if wildcard(&config.allow_methods) {
+        AllowMethods::any()
+    } else {
+        AllowMethods::list(
+            config
+                .allow_methods
+                .iter()
+                .map(|m| m.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ConfigError::Cors(format!("invalid method: {e}")))?,
+        )
+    };
+    let headers = if config.allow_headers.is_empty() {
+        AllowHeaders::mirror_request()
+    } else if wildcard(&config.allow_headers) {
+        AllowHeaders::any()
+    } else {
+        AllowHeaders::list(
+            config
+                .allow_headers
+                .iter()
+                .map(|h| h.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ConfigError::Cors(format!("invalid header: {e}")))?,
+        )
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(config.allow_credentials))
 }
 
 /// Start the server on the configured port.
@@ -66,8 +332,17 @@ pub async fn run(config: ServerConfig) -> anyhow::Result<()> {
 
 /// Start the server with extra custom routes.
 pub async fn run_ext(config: ServerConfig, extra: Router) -> anyhow::Result<()> {
+    run_with_auth(config, extra, Arc::new(NoAuth)).await
+}
+
+/// Start the server with extra custom routes, authorizing requests with `auth`.
+pub async fn run_with_auth(
+    config: ServerConfig,
+    extra: Router,
+    auth: Arc<dyn Auth>,
+) -> anyhow::Result<()> {
     let port = config.port;
-    let app = build_app_ext(config, extra).await?;
+    let app = build_app_with_auth(config, extra, auth).await?;
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}")).await?;
     println!("meshql-rs listening on port {port}");
     axum::serve(listener, app).await?;
@@ -0,0 +1,73 @@
+use meshql_core::{
+    load_from_file, BackendFactory, GraphletteConfig, RestletteConfig, ServerConfig,
+    ServerConfigManifest, StorageManifest,
+};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Load a manifest file and open its storage with `backends`.
+pub async fn load_server_config(
+    path: impl AsRef<Path>,
+    backends: &[Arc<dyn BackendFactory>],
+) -> anyhow::Result<ServerConfig> {
+    let path = path.as_ref();
+    let manifest = load_from_file(path)
+        .map_err(|e| anyhow::anyhow!("Manifest error in {}: {e}", path.display()))?;
+    server_config_from_manifest(manifest, backends).await
+}
+
+/// Turn a manifest into a `ServerConfig`, opening each graphlette's searcher and
+/// each restlette's repository with the backend its storage names. Each storage
+/// `collection` is first named by the manifest's [`Namespace`](meshql_core::Namespace).
+pub async fn server_config_from_manifest(
+    manifest: ServerConfigManifest,
+    backends: &[Arc<dyn BackendFactory>],
+) -> anyhow::Result<ServerConfig> {
+    let mut graphlettes = Vec::with_capacity(manifest.graphlettes.len());
+    for mut g in manifest.graphlettes {
+        g.storage.collection = manifest.namespace.name(&g.storage.collection);
+        let searcher = backend_for(backends, &g.storage)?
+            .searcher(&g.storage)
+            .await
+            .map_err(|e| anyhow::anyhow!("Searcher error for {}: {e}", g.path))?;
+        graphlettes.push(GraphletteConfig {
+            root_config: g.root_config(),
+            path: g.path,
+            schema_text: g.schema_text,
+            searcher,
+        });
+    }
+
+    let mut restlettes = Vec::with_capacity(manifest.restlettes.len());
+    for mut r in manifest.restlettes {
+        r.storage.collection = manifest.namespace.name(&r.storage.collection);
+        let repository = backend_for(backends, &r.storage)?
+            .repository(&r.storage)
+            .await
+            .map_err(|e| anyhow::anyhow!("Repository error for {}: {e}", r.path))?;
+        restlettes.push(RestletteConfig {
+            path: r.path,
+            schema_json: r.schema_json,
+            repository,
+            delete_mode: r.delete_mode,
+        });
+    }
+
+    Ok(ServerConfig {
+        port: manifest.port,
+        graphlettes,
+        restlettes,
+        cors: manifest.cors,
+        max_body_bytes: manifest.max_body_bytes,
+    })
+}
+
+fn backend_for<'a>(
+    backends: &'a [Arc<dyn BackendFactory>],
+    storage: &StorageManifest,
+) -> anyhow::Result<&'a Arc<dyn BackendFactory>> {
+    backends
+        .iter()
+        .find(|b| b.name() == storage.backend)
+        .ok_or_else(|| anyhow::anyhow!("No backend registered for '{}'", storage.backend))
+}
@@ -0,0 +1,233 @@
+use async_graphql_parser::types::{DocumentOperations, Selection};
+use axum::body::{to_bytes, Body};
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use meshql_core::{Result, Searcher, Stash, StashStream};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::cell::Cell;
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+const REQUESTS: &str = "meshql_graphql_requests_total";
+const DURATION: &str = "meshql_graphql_request_duration_seconds";
+const SEARCHER_CALLS: &str = "meshql_graphql_searcher_calls_total";
+const FAN_OUT: &str = "meshql_graphql_searcher_calls_per_request";
+
+/// Largest GraphQL body the metrics layer will buffer to read the query name.
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+tokio::task_local! {
+    /// Searcher calls made while serving the current GraphQL request.
+    static SEARCHER_CALL_COUNT: Cell<u64>;
+}
+
+/// The process-wide Prometheus recorder, installed on first use.
+fn handle() -> &'static PrometheusHandle {
+    static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+    HANDLE.get_or_init(|| {
+        let recorder = PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Full(DURATION.to_string()),
+                &[
+                    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+                ],
+            )
+            .and_then(|b| {
+                b.set_buckets_for_metric(
+                    Matcher::Full(FAN_OUT.to_string()),
+                    &[1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 1000.0],
+                )
+            })
+            .expect("buckets are non-empty")
+            .build_recorder();
+        let handle = recorder.handle();
+        // Another recorder may already be installed by the embedding application;
+        // the endpoint then stays empty rather than taking it over.
+        let _ = metrics::set_global_recorder(recorder);
+        handle
+    })
+}
+
+/// `GET /metrics` in Prometheus text format.
+pub(crate) fn metrics_router() -> Router {
+    let handle = handle();
+    Router::new().route(
+        "/metrics",
+        get(move || async move {
+            handle.run_upkeep();
+            (
+                [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+                handle.render(),
+            )
+        }),
+    )
+}
+
+/// Record request count, latency and searcher fan-out for the graphlette at `path`.
+pub(crate) fn instrument(router: Router, path: &str) -> Router {
+    let _ = handle();
+    router.layer(axum::middleware::from_fn_with_state(
+        path.to_string(),
+        track_request,
+    ))
+}
+
+async fn track_request(State(path): State<String>, request: Request, next: Next) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => return (StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response(),
+    };
+    let query = query_name(&bytes);
+    let request = Request::from_parts(parts, Body::from(bytes));
+
+    let started = Instant::now();
+    let (response, calls) = SEARCHER_CALL_COUNT
+        .scope(Cell::new(0), async {
+            let response = next.run(request).await;
+            (response, SEARCHER_CALL_COUNT.with(Cell::get))
+        })
+        .await;
+
+    let labels = [("graphlette", path), ("query", query)];
+    metrics::counter!(REQUESTS, &labels).increment(1);
+    metrics::histogram!(DURATION, &labels).record(started.elapsed().as_secs_f64());
+    metrics::counter!(SEARCHER_CALLS, &labels).increment(calls);
+    metrics::histogram!(FAN_OUT, &labels).record(calls as f64);
+    response
+}
+
+/// The first root field of the request's operation. Client-chosen operation
+/// names aren't used, to keep label cardinality bounded by the schema.
+fn query_name(body: &[u8]) -> String {
+    let Ok(request) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return "unknown".to_string();
+    };
+    let Some(doc) = request
+        .get("query")
+        .and_then(|v| v.as_str())
+        .and_then(|q| async_graphql_parser::parse_query(q).ok())
+    else {
+        return "unknown".to_string();
+    };
+    let operation = match &doc.operations {
+        DocumentOperations::Single(op) => Some(op),
+        DocumentOperations::Multiple(ops) => ops.values().next(),
+    };
+    operation
+        .and_then(|op| {
+            op.node
+                .selection_set
+                .node
+                .items
+                .iter()
+                .find_map(|s| match &s.node {
+                    Selection::Field(f) => Some(f.node.name.node.to_string()),
+                    _ => None,
+                })
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Counts calls against the GraphQL request being served, wherever in the
+/// graphlette mesh they land.
+pub(crate) struct MeteredSearcher(pub(crate) Arc<dyn Searcher>);
+
+impl MeteredSearcher {
+    fn record(&self) {
+        let _ = SEARCHER_CALL_COUNT.try_with(|c| c.set(c.get() + 1));
+    }
+}
+
+#[async_trait::async_trait]
+impl Searcher for MeteredSearcher {
+    async fn find(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        self.record();
+        self.0.find(template, args, creds, at).await
+    }
+
+    async fn find_all(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        self.record();
+        self.0.find_all(template, args, creds, at).await
+    }
+
+    async fn find_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        self.record();
+        self.0
+            .find_projected(template, args, fields, creds, at)
+            .await
+    }
+
+    async fn find_all_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        self.record();
+        self.0
+            .find_all_projected(template, args, fields, creds, at)
+            .await
+    }
+
+    async fn find_stream(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
+        self.record();
+        self.0.find_stream(template, args, creds, at).await
+    }
+
+    async fn count(&self, template: &str, args: &Stash, creds: &[String], at: i64) -> Result<u64> {
+        self.record();
+        self.0.count(template, args, creds, at).await
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        self.record();
+        self.0.exists(template, args, creds, at).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.0.ping().await
+    }
+}
@@ -0,0 +1,140 @@
+use meshql_core::{Result, Searcher, Stash, StashStream};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::sync::Arc;
+use tracing::Instrument;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Export spans over OTLP/HTTP as `service_name`, to the collector named by the
+/// standard `OTEL_EXPORTER_OTLP_*` environment variables (`http://localhost:4318`
+/// by default).
+///
+/// Installs the global `tracing` subscriber. Keep the returned provider and
+/// call `shutdown` on it before exiting to flush the last batch of spans.
+pub fn init_otlp_tracing(service_name: &str) -> anyhow::Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder().with_http().build()?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("meshql")))
+        .try_init()?;
+    Ok(provider)
+}
+
+/// Runs every call in a `searcher.*` span, a child of the resolver or request
+/// span it was made from.
+pub(crate) struct TracedSearcher {
+    pub(crate) inner: Arc<dyn Searcher>,
+    pub(crate) graphlette: String,
+}
+
+#[async_trait::async_trait]
+impl Searcher for TracedSearcher {
+    async fn find(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        let span = tracing::info_span!("searcher.find", graphlette = %self.graphlette);
+        self.inner
+            .find(template, args, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    async fn find_all(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        let span = tracing::info_span!("searcher.find_all", graphlette = %self.graphlette);
+        self.inner
+            .find_all(template, args, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    async fn find_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        let span = tracing::info_span!("searcher.find", graphlette = %self.graphlette);
+        self.inner
+            .find_projected(template, args, fields, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    async fn find_all_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        let span = tracing::info_span!("searcher.find_all", graphlette = %self.graphlette);
+        self.inner
+            .find_all_projected(template, args, fields, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    /// The span covers opening the stream, not draining it.
+    async fn find_stream(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
+        let span = tracing::info_span!("searcher.find_stream", graphlette = %self.graphlette);
+        self.inner
+            .find_stream(template, args, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    async fn count(&self, template: &str, args: &Stash, creds: &[String], at: i64) -> Result<u64> {
+        let span = tracing::info_span!("searcher.count", graphlette = %self.graphlette);
+        self.inner
+            .count(template, args, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        let span = tracing::info_span!("searcher.exists", graphlette = %self.graphlette);
+        self.inner
+            .exists(template, args, creds, at)
+            .instrument(span)
+            .await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+}
@@ -0,0 +1,192 @@
+use meshql_core::{CompiledTemplate, ConfigError, MeshqlError, RootConfig, ServerConfig};
+use meshql_graphlette::{build_schema_at_with_report, ResolverRegistry};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Routes the app serves itself, which no graphlette or restlette may claim.
+const RESERVED_PATHS: [&str; 5] = ["/health", "/ready", "/stats", "/metrics", "/openapi.json"];
+
+/// Check `config` without serving anything, returning every problem found
+/// rather than just the first.
+///
+/// Every graphlette and restlette needs a path of its own, trailing slashes
+/// aside, clear of the routes the others serve under theirs and of the app's
+/// reserved routes. Each resolver running in-process must target a graphlette
+/// that is configured and defines its `query_name`; resolvers over HTTP are
+/// left alone, since they may be served elsewhere. Every query template must
+/// compile, and every schema build, with each object or list field filled in
+/// by some resolver.
+pub fn validate(config: &ServerConfig) -> Result<(), Vec<ConfigError>> {
+    let mut problems = path_problems(config);
+    let registry = crate::registry(config);
+    for g in &config.graphlettes {
+        let resolver_problems = resolver_problems(&g.path, &g.root_config, &registry);
+        // A misconfigured resolver also leaves its field dangling; report it once
+        let misconfigured: HashSet<&str> = resolver_problems
+            .iter()
+            .filter_map(|problem| match problem {
+                ConfigError::UnknownResolverTarget { field, .. }
+                | ConfigError::UnknownResolverQuery { field, .. } => Some(last_segment(field)),
+                _ => None,
+            })
+            .collect();
+        let built = build_schema_at_with_report(
+            &g.path,
+            &g.schema_text,
+            &g.root_config,
+            Arc::clone(&g.searcher),
+            &registry,
+        );
+        let schema_problems = match built {
+            Ok((_, report)) => report
+                .dangling_relations
+                .into_iter()
+                .filter(|field| !misconfigured.contains(last_segment(field)))
+                .map(|field| ConfigError::DanglingRelation {
+                    path: g.path.clone(),
+                    field,
+                })
+                .collect(),
+            Err(e) => vec![ConfigError::SchemaParse {
+                path: g.path.clone(),
+                message: format!("{e:?}"),
+            }],
+        };
+        problems.extend(template_problems(&g.path, &g.root_config));
+        problems.extend(resolver_problems);
+        problems.extend(schema_problems);
+    }
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(problems),
+    }
+}
+
+/// Routes a graphlette serves under its path, besides the path itself.
+const GRAPHLETTE_ROUTES: [&str; 3] = ["sdl", "schema.json", "stream"];
+
+/// Routes a restlette serves under its path, besides the path itself and
+/// `/:id`, which static routes take precedence over.
+const RESTLETTE_ROUTES: [&str; 2] = ["bulk", "bulk-read"];
+
+/// Paths claimed twice, or claiming a route the app serves itself, so
+/// merging the routers would panic or one would shadow another. Trailing
+/// slashes are ignored, and the routes each mount serves under its path are
+/// claimed along with it.
+fn path_problems(config: &ServerConfig) -> Vec<ConfigError> {
+    let graphlettes = config
+        .graphlettes
+        .iter()
+        .map(|g| (&g.path, &GRAPHLETTE_ROUTES[..]));
+    let restlettes = config
+        .restlettes
+        .iter()
+        .map(|r| (&r.path, &RESTLETTE_ROUTES[..]));
+    let mut claimed = HashSet::new();
+    let mut problems = Vec::new();
+    for (path, sub_routes) in graphlettes.chain(restlettes) {
+        let base = normalized(path);
+        if RESERVED_PATHS.contains(&base) {
+            problems.push(ConfigError::ReservedPath { path: path.clone() });
+            continue;
+        }
+        if !claimed.insert(base.to_string()) {
+            problems.push(ConfigError::DuplicatePath { path: path.clone() });
+            continue;
+        }
+        for sub_route in sub_routes {
+            let route = format!("{}/{sub_route}", base.trim_end_matches('/'));
+            if RESERVED_PATHS.contains(&route.as_str()) {
+                problems.push(ConfigError::ReservedPath { path: route });
+            } else if !claimed.insert(route.clone()) {
+                problems.push(ConfigError::DuplicatePath { path: route });
+            }
+        }
+    }
+    problems
+}
+
+/// `path` without its trailing slashes, leaving the root as `/`.
+fn normalized(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    }
+}
+
+/// Queries on the graphlette at `path` whose template doesn't compile.
+fn template_problems(path: &str, root_config: &RootConfig) -> Vec<ConfigError> {
+    root_config
+        .queries
+        .iter()
+        .filter_map(|q| match CompiledTemplate::compile(&q.template) {
+            Ok(_) => None,
+            Err(e) => Some(ConfigError::InvalidTemplate {
+                path: path.to_string(),
+                query: q.name.clone(),
+                message: match e {
+                    MeshqlError::Template(message) => message,
+                    other => other.to_string(),
+                },
+            }),
+        })
+        .collect()
+}
+
+/// In-process resolvers on the graphlette at `path` whose target isn't
+/// registered or lacks their query.
+fn resolver_problems(
+    path: &str,
+    root_config: &RootConfig,
+    registry: &ResolverRegistry,
+) -> Vec<ConfigError> {
+    let internal = root_config
+        .internal_singleton_resolvers
+        .iter()
+        .map(|r| (&r.field_name, &r.query_name, &r.graphlette_path))
+        .chain(
+            root_config
+                .internal_vector_resolvers
+                .iter()
+                .map(|r| (&r.field_name, &r.query_name, &r.graphlette_path)),
+        );
+    let local = root_config
+        .singleton_resolvers
+        .iter()
+        .map(|r| (&r.field_name, &r.query_name, &r.url))
+        .chain(
+            root_config
+                .vector_resolvers
+                .iter()
+                .map(|r| (&r.field_name, &r.query_name, &r.url)),
+        )
+        .filter(|(_, _, url)| !url.starts_with("http://") && !url.starts_with("https://"));
+
+    internal
+        .chain(local)
+        .filter_map(
+            |(field, query, target)| match registry.get_for_url(target) {
+                None => Some(ConfigError::UnknownResolverTarget {
+                    path: path.to_string(),
+                    field: field.clone(),
+                    target: target.clone(),
+                }),
+                Some(entry) if entry.root_config.get_template(query).is_none() => {
+                    Some(ConfigError::UnknownResolverQuery {
+                        path: path.to_string(),
+                        field: field.clone(),
+                        target: target.clone(),
+                        query: query.clone(),
+                    })
+                }
+                Some(_) => None,
+            },
+        )
+        .collect()
+}
+
+/// The field a resolver name or `Type.field` ends with, e.g. `layReports`
+/// for `hens.layReports`.
+fn last_segment(field: &str) -> &str {
+    field.rsplit('.').next().unwrap_or(field)
+}
@@ -0,0 +1,26 @@
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use meshql_core::Repository;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+/// `GET /stats`, each restlette's [`Repository::stats`] keyed by its path.
+/// A repository that can't report is listed with its error instead, so one
+/// unreachable store doesn't hide the others.
+pub(crate) fn stats_router(repositories: Vec<(String, Arc<dyn Repository>)>) -> Router {
+    let repositories = Arc::new(repositories);
+    Router::new().route("/stats", get(move || stats(Arc::clone(&repositories))))
+}
+
+async fn stats(repositories: Arc<Vec<(String, Arc<dyn Repository>)>>) -> Response {
+    let mut body = Map::new();
+    for (path, repository) in repositories.iter() {
+        let stats = match repository.stats().await {
+            Ok(stats) => json!(stats),
+            Err(e) => json!({"error": e.to_string()}),
+        };
+        body.insert(path.clone(), stats);
+    }
+    Json(Value::Object(body)).into_response()
+}
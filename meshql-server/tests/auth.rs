@@ -0,0 +1,175 @@
+use axum::http::HeaderMap;
+use meshql_core::{
+    Auth, DeleteMode, Envelope, GraphletteConfig, Repository, RestletteConfig, RootConfig,
+    Searcher, ServerConfig, Stash,
+};
+use meshql_memory::{MemoryRepository, MemorySearcher};
+use meshql_server::build_app_with_auth;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+
+const FARM_GRAPHQL: &str = r#"
+type Farm {
+    id: ID
+    name: String
+}
+type Query {
+    getFarms(at: Int): [Farm]
+}
+"#;
+
+/// Gives every caller the same token.
+struct FixedToken(&'static str);
+
+#[async_trait::async_trait]
+impl Auth for FixedToken {
+    fn get_auth_token(&self, _context: &Stash) -> Vec<String> {
+        vec![self.0.to_string()]
+    }
+
+    fn is_authorized(&self, credentials: &[String], envelope: &Envelope) -> bool {
+        envelope
+            .authorized_tokens
+            .iter()
+            .any(|t| credentials.contains(t))
+    }
+
+    async fn authorize(&self, _headers: &HeaderMap) -> meshql_core::Result<Vec<String>> {
+        Ok(vec![self.0.to_string()])
+    }
+}
+
+/// A [`MemorySearcher`] that remembers the credentials of each call.
+struct Recording {
+    inner: MemorySearcher,
+    creds: Mutex<Vec<Vec<String>>>,
+}
+
+impl Recording {
+    fn record(&self, creds: &[String]) {
+        self.creds.lock().unwrap().push(creds.to_vec());
+    }
+}
+
+#[async_trait::async_trait]
+impl Searcher for Recording {
+    async fn find(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> meshql_core::Result<Option<Stash>> {
+        self.record(creds);
+        self.inner.find(template, args, creds, at).await
+    }
+
+    async fn find_all(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> meshql_core::Result<Vec<Stash>> {
+        self.record(creds);
+        self.inner.find_all(template, args, creds, at).await
+    }
+
+    async fn count(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> meshql_core::Result<u64> {
+        self.record(creds);
+        self.inner.count(template, args, creds, at).await
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> meshql_core::Result<bool> {
+        self.record(creds);
+        self.inner.exists(template, args, creds, at).await
+    }
+
+    async fn ping(&self) -> meshql_core::Result<()> {
+        self.inner.ping().await
+    }
+}
+
+#[tokio::test]
+async fn restlette_and_graphlette_requests_carry_the_configured_auth() {
+    let farms = Arc::new(MemoryRepository::new());
+    let searcher = Arc::new(Recording {
+        inner: MemorySearcher::new(farms.store()),
+        creds: Mutex::default(),
+    });
+
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![GraphletteConfig {
+            path: "/farm/graph".to_string(),
+            schema_text: FARM_GRAPHQL.to_string(),
+            root_config: RootConfig::builder().vector("getFarms", "{}").build(),
+            searcher: searcher.clone(),
+        }],
+        restlettes: vec![RestletteConfig {
+            path: "/farm/api".to_string(),
+            schema_json: json!({}),
+            repository: farms.clone(),
+            delete_mode: DeleteMode::Soft,
+        }],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app_with_auth(
+        config,
+        axum::Router::new(),
+        Arc::new(FixedToken("farmer-7")),
+    )
+    .await
+    .unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+
+    let created: serde_json::Value = client
+        .post(format!("http://{addr}/farm/api"))
+        .json(&json!({"name": "Emerdale"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+    let stored = farms
+        .read(id, &["*".to_string()], None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.authorized_tokens, vec!["farmer-7".to_string()]);
+
+    let body: serde_json::Value = client
+        .post(format!("http://{addr}/farm/graph"))
+        .json(&json!({"query": "{ getFarms { name } }"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["getFarms"], json!([{"name": "Emerdale"}]));
+    assert_eq!(
+        *searcher.creds.lock().unwrap(),
+        vec![vec!["farmer-7".to_string()]]
+    );
+}
@@ -0,0 +1,68 @@
+use meshql_core::{DeleteMode, RestletteConfig, ServerConfig};
+use meshql_memory::MemoryRepository;
+use meshql_server::build_app;
+use std::sync::Arc;
+
+async fn serve(max_body_bytes: Option<usize>) -> String {
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![],
+        restlettes: vec![RestletteConfig {
+            path: "/farm/api".to_string(),
+            schema_json: serde_json::json!({}),
+            repository: Arc::new(MemoryRepository::new()),
+            delete_mode: DeleteMode::Soft,
+        }],
+        cors: None,
+        max_body_bytes,
+    };
+    let app = build_app(config).await.unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/farm/api")
+}
+
+fn farm(name_len: usize) -> serde_json::Value {
+    serde_json::json!({"name": "x".repeat(name_len)})
+}
+
+#[tokio::test]
+async fn bodies_over_the_configured_limit_get_a_413() {
+    let url = serve(Some(1024)).await;
+    let client = reqwest::Client::new();
+
+    let response = client.post(&url).json(&farm(2048)).send().await.unwrap();
+    assert_eq!(response.status(), 413);
+
+    let response = client.post(&url).json(&farm(16)).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+}
+
+#[tokio::test]
+async fn bodies_are_capped_at_2_mib_by_default() {
+    let url = serve(None).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(&url)
+        .json(&farm(3 * 1024 * 1024))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+}
+
+#[tokio::test]
+async fn the_limit_can_be_raised_for_bulk_ingest() {
+    let url = serve(Some(8 * 1024 * 1024)).await;
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&farm(3 * 1024 * 1024))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+}
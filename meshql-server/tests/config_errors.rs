@@ -0,0 +1,212 @@
+use meshql_core::{
+    ConfigError, DeleteMode, GraphletteConfig, RestletteConfig, RootConfig, ServerConfig,
+};
+use meshql_memory::{MemoryRepository, MemorySearcher};
+use meshql_server::{build_app, validate};
+use std::sync::Arc;
+
+const FARM_SCHEMA: &str = r#"
+    type Farm {
+        id: ID
+        name: String
+        coops: [Coop]
+    }
+    type Coop {
+        id: ID
+        name: String
+    }
+    type Query {
+        getById(id: ID, at: Int): Farm
+    }
+"#;
+
+fn graphlette(path: &str, root_config: RootConfig) -> GraphletteConfig {
+    GraphletteConfig {
+        path: path.to_string(),
+        schema_text: FARM_SCHEMA.to_string(),
+        root_config,
+        searcher: Arc::new(MemorySearcher::new(MemoryRepository::new().store())),
+    }
+}
+
+fn config(graphlettes: Vec<GraphletteConfig>) -> ServerConfig {
+    ServerConfig {
+        port: 0,
+        graphlettes,
+        restlettes: vec![],
+        cors: None,
+        max_body_bytes: None,
+    }
+}
+
+fn farms() -> RootConfig {
+    RootConfig::builder()
+        .singleton("getById", r#"{"id": "{{id}}"}"#)
+        .build()
+}
+
+#[tokio::test]
+async fn duplicate_graphlette_paths_are_rejected() {
+    let graphlettes = vec![
+        graphlette("/farm/graph", farms()),
+        graphlette("/farm/graph", farms()),
+    ];
+    match build_app(config(graphlettes)).await {
+        Err(ConfigError::DuplicatePath { path }) => assert_eq!(path, "/farm/graph"),
+        other => panic!("expected a duplicate path, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn a_restlette_cant_share_a_graphlettes_path() {
+    let mut config = config(vec![graphlette("/farm/graph", farms())]);
+    config.restlettes.push(RestletteConfig {
+        path: "/farm/graph".to_string(),
+        schema_json: serde_json::json!({}),
+        repository: Arc::new(MemoryRepository::new()),
+        delete_mode: DeleteMode::Soft,
+    });
+    match build_app(config).await {
+        Err(ConfigError::DuplicatePath { path }) => assert_eq!(path, "/farm/graph"),
+        other => panic!("expected a duplicate path, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn trailing_slashes_dont_make_a_path_new() {
+    let graphlettes = vec![
+        graphlette("/farm/graph", farms()),
+        graphlette("/farm/graph/", farms()),
+    ];
+    match build_app(config(graphlettes)).await {
+        Err(ConfigError::DuplicatePath { path }) => assert_eq!(path, "/farm/graph/"),
+        other => panic!("expected a duplicate path, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn a_path_cant_claim_a_route_served_under_another() {
+    let over_sdl = config(vec![
+        graphlette("/farm/graph", farms()),
+        graphlette("/farm/graph/sdl", farms()),
+    ]);
+    match build_app(over_sdl).await {
+        Err(ConfigError::DuplicatePath { path }) => assert_eq!(path, "/farm/graph/sdl"),
+        other => panic!("expected a duplicate path, got {:?}", other.err()),
+    }
+
+    let mut over_bulk = config(vec![graphlette("/farm/api/bulk", farms())]);
+    over_bulk.restlettes.push(RestletteConfig {
+        path: "/farm/api".to_string(),
+        schema_json: serde_json::json!({}),
+        repository: Arc::new(MemoryRepository::new()),
+        delete_mode: DeleteMode::Soft,
+    });
+    match build_app(over_bulk).await {
+        Err(ConfigError::DuplicatePath { path }) => assert_eq!(path, "/farm/api/bulk"),
+        other => panic!("expected a duplicate path, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn reserved_paths_are_rejected() {
+    for reserved in ["/health", "/ready", "/stats", "/metrics", "/openapi.json"] {
+        match build_app(config(vec![graphlette(reserved, farms())])).await {
+            Err(ConfigError::ReservedPath { path }) => assert_eq!(path, reserved),
+            other => panic!("expected {reserved} to be reserved, got {:?}", other.err()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn query_templates_must_compile() {
+    let root_config = RootConfig::builder()
+        .singleton("getById", r#"{"id": "{{id"}"#)
+        .build();
+    match build_app(config(vec![graphlette("/farm/graph", root_config)])).await {
+        Err(ConfigError::InvalidTemplate { path, query, .. }) => {
+            assert_eq!(path, "/farm/graph");
+            assert_eq!(query, "getById");
+        }
+        other => panic!("expected an invalid template, got {:?}", other.err()),
+    }
+}
+
+#[tokio::test]
+async fn resolvers_must_target_a_registered_graphlette() {
+    let root_config = RootConfig::builder()
+        .singleton("getById", r#"{"id": "{{id}}"}"#)
+        .internal_vector_resolver("coops", None, "getByFarm", "/coop/graph")
+        .build();
+    let graphlettes = vec![graphlette("/farm/graph", root_config)];
+    match build_app(config(graphlettes)).await {
+        Err(ConfigError::UnknownResolverTarget {
+            path,
+            field,
+            target,
+        }) => {
+            assert_eq!(path, "/farm/graph");
+            assert_eq!(field, "coops");
+            assert_eq!(target, "/coop/graph");
+        }
+        other => panic!("expected an unknown resolver target, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn validate_reports_a_resolver_running_an_undefined_query() {
+    let farm_config = RootConfig::builder()
+        .singleton("getById", r#"{"id": "{{id}}"}"#)
+        .internal_vector_resolver("coops", None, "getByFarm", "/coop/graph")
+        .build();
+    let coops = GraphletteConfig {
+        schema_text: r#"
+            type Coop {
+                id: ID
+                name: String
+            }
+            type Query {
+                getById(id: ID, at: Int): Coop
+            }
+        "#
+        .to_string(),
+        ..graphlette("/coop/graph", farms())
+    };
+    let graphlettes = vec![graphlette("/farm/graph", farm_config), coops];
+    let problems = validate(&config(graphlettes)).unwrap_err();
+    match problems.as_slice() {
+        [ConfigError::UnknownResolverQuery {
+            path,
+            field,
+            target,
+            query,
+        }] => {
+            assert_eq!(path, "/farm/graph");
+            assert_eq!(field, "coops");
+            assert_eq!(target, "/coop/graph");
+            assert_eq!(query, "getByFarm");
+        }
+        other => panic!("expected an unknown resolver query, got {other:?}"),
+    }
+}
+
+#[test]
+fn validate_reports_every_problem() {
+    let graphlettes = vec![
+        graphlette("/farm/graph", farms()),
+        graphlette("/farm/graph", farms()),
+    ];
+    let problems: Vec<String> = validate(&config(graphlettes))
+        .unwrap_err()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        problems,
+        vec![
+            "/farm/graph is configured more than once",
+            "Farm.coops on /farm/graph has no resolver",
+            "Farm.coops on /farm/graph has no resolver",
+        ]
+    );
+}
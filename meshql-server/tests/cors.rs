@@ -0,0 +1,83 @@
+use meshql_core::{CorsConfig, DeleteMode, RestletteConfig, ServerConfig};
+use meshql_memory::MemoryRepository;
+use meshql_server::build_app;
+use std::sync::Arc;
+
+fn config(cors: Option<CorsConfig>) -> ServerConfig {
+    ServerConfig {
+        port: 0,
+        graphlettes: vec![],
+        restlettes: vec![RestletteConfig {
+            path: "/farm/api".to_string(),
+            schema_json: serde_json::json!({}),
+            repository: Arc::new(MemoryRepository::new()),
+            delete_mode: DeleteMode::Soft,
+        }],
+        cors,
+        max_body_bytes: None,
+    }
+}
+
+async fn serve(config: ServerConfig) -> String {
+    let app = build_app(config).await.unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/farm/api")
+}
+
+#[tokio::test]
+async fn configured_origins_are_echoed_with_credentials() {
+    let cors = CorsConfig::new()
+        .allow_origin("https://farm.example.com")
+        .allow_origin("https://admin.example.com")
+        .allow_method("GET")
+        .allow_credentials(true);
+    let url = serve(config(Some(cors))).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(&url)
+        .header("Origin", "https://admin.example.com")
+        .send()
+        .await
+        .unwrap();
+    let headers = response.headers();
+    assert_eq!(
+        headers["access-control-allow-origin"],
+        "https://admin.example.com"
+    );
+    assert_eq!(headers["access-control-allow-credentials"], "true");
+
+    let response = client
+        .get(&url)
+        .header("Origin", "https://evil.example.com")
+        .send()
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn any_origin_is_allowed_without_a_cors_config() {
+    let url = serve(config(None)).await;
+    let response = reqwest::Client::new()
+        .get(&url)
+        .header("Origin", "https://anywhere.example.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+}
+
+#[tokio::test]
+async fn credentials_with_a_wildcard_origin_are_rejected() {
+    let cors = CorsConfig::new().allow_origin("*").allow_credentials(true);
+    assert!(cors.validate().is_err());
+    assert!(build_app(config(Some(cors))).await.is_err());
+}
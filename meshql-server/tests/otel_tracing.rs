@@ -0,0 +1,196 @@
+#![cfg(feature = "otel")]
+
+use meshql_core::{Envelope, GraphletteConfig, Repository, RootConfig, ServerConfig};
+use meshql_memory::{MemoryRepository, MemorySearcher};
+use meshql_server::build_app;
+use opentelemetry::trace::{SpanId, TracerProvider as _};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use serde_json::json;
+use std::sync::Arc;
+use tracing_subscriber::layer::SubscriberExt;
+
+const FARM_GRAPHQL: &str = r#"
+type Farm {
+    id: ID
+    name: String
+    coops: [Coop]
+}
+type Coop {
+    id: ID
+    name: String
+}
+type Query {
+    getFarms(name: String, at: Int): [Farm]
+}
+"#;
+
+const COOP_GRAPHQL: &str = r#"
+type Coop {
+    id: ID
+    name: String
+}
+type Query {
+    getCoopsByFarm(id: ID, at: Int): [Coop]
+}
+"#;
+
+async fn create(repo: &MemoryRepository, id: &str, payload: serde_json::Value) {
+    let payload = payload.as_object().unwrap().clone();
+    let star = vec!["*".to_string()];
+    repo.create(Envelope::new(id, payload, star.clone()), &star)
+        .await
+        .unwrap();
+}
+
+fn attribute(span: &SpanData, key: &str) -> Option<String> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.to_string())
+}
+
+/// Whether `ancestor` is `span`'s parent, or its parent's parent, and so on.
+fn descends_from(spans: &[SpanData], span: &SpanData, ancestor: &SpanData) -> bool {
+    let mut parent = span.parent_span_id;
+    while parent != SpanId::INVALID {
+        if parent == ancestor.span_context.span_id() {
+            return true;
+        }
+        parent = match spans.iter().find(|s| s.span_context.span_id() == parent) {
+            Some(s) => s.parent_span_id,
+            None => return false,
+        };
+    }
+    false
+}
+
+#[tokio::test]
+async fn request_span_parents_resolver_and_searcher_spans() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    // The test runtime is single-threaded, so the server task sees this subscriber too.
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let farms = MemoryRepository::new();
+    let coops = MemoryRepository::new();
+    create(&farms, "farm-1", json!({"name": "Farm"})).await;
+    for c in 0..2 {
+        let coop_id = format!("coop-{c}");
+        create(
+            &coops,
+            &coop_id,
+            json!({"name": coop_id, "farmId": "farm-1"}),
+        )
+        .await;
+    }
+
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![
+            GraphletteConfig {
+                path: "/farm/graph".to_string(),
+                schema_text: FARM_GRAPHQL.to_string(),
+                root_config: RootConfig::builder()
+                    .vector("getFarms", r#"{"payload.name": "{{name}}"}"#)
+                    .internal_vector_resolver("coops", None, "getCoopsByFarm", "/coop/graph")
+                    .build(),
+                searcher: Arc::new(MemorySearcher::new(farms.store())),
+            },
+            GraphletteConfig {
+                path: "/coop/graph".to_string(),
+                schema_text: COOP_GRAPHQL.to_string(),
+                root_config: RootConfig::builder()
+                    .vector("getCoopsByFarm", r#"{"payload.farmId": "{{id}}"}"#)
+                    .build(),
+                searcher: Arc::new(MemorySearcher::new(coops.store())),
+            },
+        ],
+        restlettes: vec![],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app(config).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let body: serde_json::Value = reqwest::Client::new()
+        .post(format!("http://{addr}/farm/graph"))
+        .json(&json!({"query": r#"{ getFarms(name: "Farm") { name coops { name } } }"#}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        body["data"]["getFarms"][0]["coops"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    provider.force_flush().unwrap();
+    let spans = exporter.get_finished_spans().unwrap();
+    let names: Vec<&str> = spans.iter().map(|s| s.name.as_ref()).collect();
+
+    let request = spans
+        .iter()
+        .find(|s| s.name == "graphql.request")
+        .unwrap_or_else(|| panic!("no request span in {names:?}"));
+    // The HTTP span from `tower_http::trace` is the root
+    let http = spans
+        .iter()
+        .find(|s| s.span_context.span_id() == request.parent_span_id)
+        .unwrap_or_else(|| panic!("no parent for the request span in {names:?}"));
+    assert_eq!(http.name, "request");
+    assert_eq!(http.parent_span_id, SpanId::INVALID);
+    assert_eq!(
+        attribute(request, "graphlette").as_deref(),
+        Some("/farm/graph")
+    );
+
+    let resolver = spans
+        .iter()
+        .find(|s| s.name == "graphql.resolver")
+        .unwrap_or_else(|| panic!("no resolver span in {names:?}"));
+    assert_eq!(resolver.parent_span_id, request.span_context.span_id());
+    assert_eq!(attribute(resolver, "field").as_deref(), Some("coops"));
+    assert_eq!(
+        attribute(resolver, "query").as_deref(),
+        Some("getCoopsByFarm")
+    );
+    assert_eq!(
+        attribute(resolver, "target_path").as_deref(),
+        Some("/coop/graph")
+    );
+
+    let searches: Vec<&SpanData> = spans
+        .iter()
+        .filter(|s| s.name == "searcher.find_all")
+        .collect();
+    let graphlettes: Vec<Option<String>> = searches
+        .iter()
+        .map(|s| attribute(s, "graphlette"))
+        .collect();
+    assert!(
+        graphlettes.contains(&Some("/farm/graph".to_string()))
+            && graphlettes.contains(&Some("/coop/graph".to_string())),
+        "{graphlettes:?}"
+    );
+    for search in searches {
+        assert_eq!(
+            search.span_context.trace_id(),
+            request.span_context.trace_id()
+        );
+        assert!(descends_from(&spans, search, request), "{names:?}");
+    }
+}
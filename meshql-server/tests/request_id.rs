@@ -0,0 +1,105 @@
+use meshql_core::{GraphletteConfig, RootConfig, ServerConfig};
+use meshql_memory::{MemoryRepository, MemorySearcher};
+use meshql_server::build_app;
+use serde_json::json;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+const FARM_GRAPHQL: &str = r#"
+    type Farm {
+        id: ID
+        name: String
+    }
+    type Query {
+        getFarms: [Farm]
+    }
+"#;
+
+/// The `request_id` of every operation logged to `meshql::graphql`.
+#[derive(Clone, Default)]
+struct LoggedIds(Arc<Mutex<Vec<String>>>);
+
+impl<S: Subscriber> Layer<S> for LoggedIds {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "meshql::graphql" {
+            event.record(&mut RequestIdField(&mut self.0.lock().unwrap()));
+        }
+    }
+}
+
+struct RequestIdField<'a>(&'a mut Vec<String>);
+
+impl Visit for RequestIdField<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "request_id" {
+            self.0.push(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+}
+
+async fn serve() -> String {
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![GraphletteConfig {
+            path: "/farm/graph".to_string(),
+            schema_text: FARM_GRAPHQL.to_string(),
+            root_config: RootConfig::builder().vector("getFarms", "{}").build(),
+            searcher: Arc::new(MemorySearcher::new(MemoryRepository::new().store())),
+        }],
+        restlettes: vec![],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app(config).await.unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/farm/graph")
+}
+
+#[tokio::test]
+async fn responses_echo_the_request_id_sent() {
+    let logged = LoggedIds::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(logged.clone()));
+    let url = serve().await;
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .header("X-Request-Id", "trace-me-42")
+        .json(&json!({"query": "{ getFarms { id } }"}))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["x-request-id"], "trace-me-42");
+    assert_eq!(*logged.0.lock().unwrap(), vec!["trace-me-42".to_string()]);
+}
+
+#[tokio::test]
+async fn requests_without_an_id_are_given_one() {
+    let url = serve().await;
+    let client = reqwest::Client::new();
+    let query = json!({"query": "{ getFarms { id } }"});
+
+    let first = client.post(&url).json(&query).send().await.unwrap();
+    let second = client.post(&url).json(&query).send().await.unwrap();
+
+    let id = |response: &reqwest::Response| {
+        response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(id(&first).len(), 36, "{}", id(&first));
+    assert_ne!(id(&first), id(&second));
+}
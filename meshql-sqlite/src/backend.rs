@@ -0,0 +1,72 @@
+use crate::{pool_options, SqliteRepository, SqliteSearcher};
+use async_trait::async_trait;
+use meshql_core::{BackendFactory, MeshqlError, Repository, Result, Searcher, StorageManifest};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Opens `backend: sqlite` manifest storage, one table per `collection`.
+///
+/// In-memory databases are kept apart per `collection`, and an entity's
+/// repository and searcher share one pool so they see the same data.
+#[derive(Default)]
+pub struct SqliteBackend {
+    pools: Mutex<HashMap<(String, String), SqlitePool>>,
+}
+
+impl SqliteBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn pool(&self, storage: &StorageManifest) -> Result<SqlitePool> {
+        let mut pools = self.pools.lock().await;
+        let key = (storage.uri.clone(), storage.collection.clone());
+        if let Some(pool) = pools.get(&key) {
+            return Ok(pool.clone());
+        }
+
+        let opts = SqliteConnectOptions::from_str(&storage.uri)
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?
+            .create_if_missing(true);
+        // Each connection to `:memory:` opens a separate database.
+        let max_connections = if storage.uri.contains(":memory:") {
+            1
+        } else {
+            storage.pool.max_connections.unwrap_or(10)
+        };
+        let pool = pool_options(&storage.pool)
+            .max_connections(max_connections)
+            .connect_with(opts)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        pools.insert(key, pool.clone());
+        Ok(pool)
+    }
+}
+
+#[async_trait]
+impl BackendFactory for SqliteBackend {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    async fn repository(&self, storage: &StorageManifest) -> Result<Arc<dyn Repository>> {
+        let pool = self.pool(storage).await?;
+        let repo = SqliteRepository::new_with_pool_and_table(pool, &storage.collection)
+            .await?
+            .with_id_strategy(storage.id_strategy)
+            .with_payload_codec(storage.payload_codec)
+            .with_auth_policy(storage.auth_policy);
+        Ok(Arc::new(repo))
+    }
+
+    async fn searcher(&self, storage: &StorageManifest) -> Result<Arc<dyn Searcher>> {
+        let pool = self.pool(storage).await?;
+        let searcher = SqliteSearcher::new_with_pool_and_table(pool, &storage.collection).await?;
+        Ok(Arc::new(searcher))
+    }
+}
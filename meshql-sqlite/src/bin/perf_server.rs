@@ -3,9 +3,9 @@
 //!
 //! Usage: cargo run -p meshql-sqlite --release --bin perf_server
 
-use meshql_core::{GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
-use meshql_sqlite::{SqliteRepository, SqliteSearcher};
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use meshql_core::{DeleteMode, GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
+use meshql_sqlite::{pool_options, PoolConfig, SqliteRepository, SqliteSearcher};
+use sqlx::sqlite::SqliteConnectOptions;
 use std::str::FromStr;
 use std::sync::Arc;
 
@@ -115,8 +115,7 @@ struct Entity {
 
 async fn make_entity(dir: &str, name: &str) -> Entity {
     let db_path = format!("{dir}/{name}.db");
-    let pool = SqlitePoolOptions::new()
-        .max_connections(4)
+    let pool = pool_options(&PoolConfig::new().max_connections(4))
         .connect_with(
             SqliteConnectOptions::from_str(&format!("sqlite:{db_path}"))
                 .unwrap()
@@ -130,6 +129,27 @@ async fn make_entity(dir: &str, name: &str) -> Entity {
     Entity { repo, searcher }
 }
 
+async fn bench_create_many(dir: &str, rows: usize) {
+    let bench = make_entity(dir, "bench_create_many").await;
+    let tokens = vec!["*".to_string()];
+    let envelopes: Vec<meshql_core::Envelope> = (0..rows)
+        .map(|i| {
+            let mut payload = meshql_core::Stash::new();
+            payload.insert("name".into(), serde_json::json!(format!("bench-{i}")));
+            meshql_core::Envelope::new("", payload, tokens.clone())
+        })
+        .collect();
+
+    let started = std::time::Instant::now();
+    bench.repo.create_many(envelopes, &tokens).await.unwrap();
+    let elapsed = started.elapsed();
+    println!(
+        "create_many: {rows} rows in {:.1} ms ({:.0} rows/s)",
+        elapsed.as_secs_f64() * 1000.0,
+        rows as f64 / elapsed.as_secs_f64()
+    );
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let port: u16 = std::env::var("PORT")
@@ -155,6 +175,12 @@ async fn main() -> anyhow::Result<()> {
     let hen_productivity = make_entity(&data_dir, "hen_productivity").await;
     let farm_output = make_entity(&data_dir, "farm_output").await;
 
+    // Optional bulk-insert benchmark into a scratch store: BENCH_CREATE_MANY=<rows>
+    if let Ok(rows) = std::env::var("BENCH_CREATE_MANY") {
+        let rows: usize = rows.parse().expect("BENCH_CREATE_MANY must be a row count");
+        bench_create_many(&data_dir, rows).await;
+    }
+
     // Root configs (same as egg_economy_cert.rs)
     let farm_config = RootConfig::builder()
         .singleton("getById", r#"{"id": "{{id}}"}"#)
@@ -394,68 +420,83 @@ async fn main() -> anyhow::Result<()> {
                 path: "/farm/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: coop.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumer/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: consumer.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/lay_report/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: lay_report.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_deposit/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: storage_deposit.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_withdrawal/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: storage_withdrawal.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_transfer/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container_transfer.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumption_report/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: consumption_report.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_inventory/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container_inventory.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen_productivity/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen_productivity.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/farm_output/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm_output.repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     meshql_server::run(config).await
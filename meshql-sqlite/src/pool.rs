@@ -0,0 +1,24 @@
+use meshql_core::{MeshqlError, PoolConfig, Result};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+
+/// sqlx pool options with `config` applied over the driver's defaults.
+pub fn pool_options(config: &PoolConfig) -> SqlitePoolOptions {
+    let mut options = SqlitePoolOptions::new();
+    if let Some(max) = config.max_connections {
+        options = options.max_connections(max);
+    }
+    if let Some(timeout) = config.acquire_timeout {
+        options = options.acquire_timeout(timeout);
+    }
+    if let Some(timeout) = config.idle_timeout {
+        options = options.idle_timeout(timeout);
+    }
+    options
+}
+
+pub(crate) async fn connect(database_url: &str, config: &PoolConfig) -> Result<SqlitePool> {
+    pool_options(config)
+        .connect(database_url)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))
+}
@@ -1,39 +1,254 @@
+use meshql_core::{
+    distinct_from_args, sort_from_args, AuthPolicy, CreatedWindow, Result, SortField, SortKey,
+};
+
 pub struct QueryPart {
     pub clause: String,
     pub values: Vec<String>,
 }
 
+/// ANDs a condition for each key of `query_obj`. An `{"$or": [...]}` key
+/// matches rows meeting every condition of any one of its groups.
 pub fn build_where(query_obj: &serde_json::Map<String, serde_json::Value>) -> QueryPart {
-    if query_obj.is_empty() {
-        return QueryPart {
-            clause: String::new(),
-            values: vec![],
-        };
+    let mut values = Vec::new();
+    let clauses = conditions(query_obj, &mut values);
+    QueryPart {
+        clause: clauses.join(" AND "),
+        values,
     }
+}
 
+/// One condition per key of `query_obj`, pushing their bind values to `values`.
+fn conditions(
+    query_obj: &serde_json::Map<String, serde_json::Value>,
+    values: &mut Vec<String>,
+) -> Vec<String> {
     let mut clauses = Vec::new();
-    let mut values = Vec::new();
 
     for (key, val) in query_obj {
-        let str_val = match val {
-            serde_json::Value::String(s) => s.clone(),
-            other => other.to_string(),
-        };
-
-        if key == "id" {
-            clauses.push("id = ?".to_string());
+        if key == "$or" {
+            clauses.push(or_group(val, values));
+            continue;
+        }
+        let column = if key == "id" {
+            "id".to_string()
         } else if let Some(field) = key.strip_prefix("payload.") {
-            clauses.push(format!("json_extract(payload, '$.{}') = ?", field));
+            format!("json_extract(payload, '$.{}')", field)
         } else {
             // Unknown key — skip
             continue;
+        };
+
+        if let Some(ops) = comparisons(val) {
+            for (op, operand) in ops {
+                // Numbers compare numerically; SQLite's `IS NOT` is a NULL-safe `!=`.
+                let op = if op == "!=" { "IS NOT" } else { op };
+                let rhs = if operand.is_number() && key != "id" {
+                    "CAST(? AS REAL)"
+                } else {
+                    "?"
+                };
+                clauses.push(format!("{} {} {}", column, op, rhs));
+                values.push(bind_value(operand));
+            }
+            continue;
         }
 
-        values.push(str_val);
+        match in_list(val) {
+            Some([]) => clauses.push("0 = 1".to_string()),
+            Some(list) => {
+                let placeholders = vec!["?"; list.len()].join(", ");
+                clauses.push(format!("{} IN ({})", column, placeholders));
+                values.extend(list.iter().map(bind_value));
+            }
+            None => {
+                clauses.push(format!("{} = ?", column));
+                values.push(bind_value(val));
+            }
+        }
     }
 
-    QueryPart {
-        clause: clauses.join(" AND "),
-        values,
+    clauses
+}
+
+/// `((a AND b) OR (c))` for the groups of an `{"$or": [...]}`. No groups
+/// match nothing.
+fn or_group(val: &serde_json::Value, values: &mut Vec<String>) -> String {
+    let groups: Vec<String> = val
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(serde_json::Value::as_object)
+        .map(|group| match conditions(group, values).as_slice() {
+            [] => "1 = 1".to_string(),
+            clauses => format!("({})", clauses.join(" AND ")),
+        })
+        .collect();
+    if groups.is_empty() {
+        "0 = 1".to_string()
+    } else {
+        format!("({})", groups.join(" OR "))
+    }
+}
+
+/// The candidates of an `{"$in": [...]}` value, which matches any one of them.
+fn in_list(val: &serde_json::Value) -> Option<&[serde_json::Value]> {
+    val.as_object()?.get("$in")?.as_array().map(Vec::as_slice)
+}
+
+/// The operators of a comparison object like `{"$gt": 20, "$lte": 40}` as SQL
+/// operators paired with their operands. `None` unless every key is one of
+/// `$gt`, `$gte`, `$lt`, `$lte` or `$ne`.
+fn comparisons(val: &serde_json::Value) -> Option<Vec<(&'static str, &serde_json::Value)>> {
+    let obj = val.as_object().filter(|obj| !obj.is_empty())?;
+    obj.iter()
+        .map(|(op, operand)| {
+            let op = match op.as_str() {
+                "$gt" => ">",
+                "$gte" => ">=",
+                "$lt" => "<",
+                "$lte" => "<=",
+                "$ne" => "!=",
+                _ => return None,
+            };
+            Some((op, operand))
+        })
+        .collect()
+}
+
+fn bind_value(val: &serde_json::Value) -> String {
+    match val {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Build a clause restricting rows to those whose stored `authorized_tokens`
+/// intersect the caller's `tokens`, or under [`AuthPolicy::AllOf`] are all
+/// held by the caller. Rows stored with `*` are visible to everyone.
+///
+/// Returns `None` when the caller holds `*` and may see every row.
+pub fn build_token_filter(tokens: &[String], policy: AuthPolicy) -> Option<QueryPart> {
+    if tokens.iter().any(|t| t == "*") {
+        return None;
+    }
+
+    let mut values: Vec<String> = tokens.to_vec();
+    values.push("*".to_string());
+    let placeholders = vec!["?"; values.len()].join(", ");
+
+    let clause = match policy {
+        AuthPolicy::AnyOf => format!(
+            "EXISTS (SELECT 1 FROM json_each(authorized_tokens) WHERE json_each.value IN ({}))",
+            placeholders
+        ),
+        AuthPolicy::AllOf => format!(
+            "json_array_length(authorized_tokens) > 0 AND NOT EXISTS (SELECT 1 FROM json_each(authorized_tokens) WHERE json_each.value NOT IN ({}))",
+            placeholders
+        ),
+    };
+    Some(QueryPart { clause, values })
+}
+
+/// The SQL expression reading `field` from a row.
+pub fn column(field: &SortField) -> String {
+    match field {
+        SortField::Id => "id".to_string(),
+        SortField::Payload(name) => format!("json_extract(payload, '$.{}')", name),
+    }
+}
+
+/// ` AND created_at_ms >= <after> AND created_at_ms < <before>` for whichever
+/// bounds of `window` are set. They're integers, so safe to interpolate.
+pub fn created_filter(window: &CreatedWindow) -> String {
+    let mut sql = String::new();
+    if let Some(after) = window.after {
+        sql.push_str(&format!(" AND created_at_ms >= {after}"));
+    }
+    if let Some(before) = window.before {
+        sql.push_str(&format!(" AND created_at_ms < {before}"));
+    }
+    sql
+}
+
+/// `limit`/`offset` paging, `sort` ordering, `distinct` and the creation
+/// window taken from the search args.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Page {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub sort: Vec<SortKey>,
+    pub distinct: Option<SortField>,
+    pub created: CreatedWindow,
+}
+
+impl Page {
+    /// Just the first match, for singleton searches.
+    pub fn first() -> Self {
+        Page {
+            limit: Some(1),
+            ..Default::default()
+        }
+    }
+
+    /// Split `limit`, `offset`, `sort`, `distinct` and the creation window out
+    /// of `args` so they are never rendered into the template as payload filters.
+    pub fn split(
+        args: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<(serde_json::Map<String, serde_json::Value>, Page)> {
+        let sort = sort_from_args(args)?;
+        let distinct = distinct_from_args(args)?;
+        let created = CreatedWindow::from_args(args)?;
+        let mut rest = args.clone();
+        let limit = rest.remove("limit").and_then(|v| v.as_i64());
+        let offset = rest.remove("offset").and_then(|v| v.as_i64());
+        rest.remove("sort");
+        rest.remove("distinct");
+        CreatedWindow::remove_args(&mut rest);
+        Ok((
+            rest,
+            Page {
+                limit,
+                offset,
+                sort,
+                distinct,
+                created,
+            },
+        ))
+    }
+
+    pub fn is_unpaged(&self) -> bool {
+        self.limit.is_none() && self.offset.is_none()
+    }
+
+    /// `ORDER BY <sort>, id LIMIT ? OFFSET ?`; each part is omitted when not
+    /// requested. Paged results always end with `id` so consecutive pages neither
+    /// overlap nor skip rows.
+    pub fn clause(&self) -> String {
+        let mut order: Vec<String> = self
+            .sort
+            .iter()
+            .map(|key| {
+                let dir = if key.descending { "DESC" } else { "ASC" };
+                format!("{} {dir}", column(&key.field))
+            })
+            .collect();
+        if !order.is_empty() || !self.is_unpaged() {
+            order.push("id".to_string());
+        }
+
+        let mut sql = String::new();
+        if !order.is_empty() {
+            sql.push_str(&format!(" ORDER BY {}", order.join(", ")));
+        }
+        if !self.is_unpaged() {
+            sql.push_str(" LIMIT ? OFFSET ?");
+        }
+        sql
+    }
+
+    /// Bind values for the limit clause: a missing limit means "no limit".
+    pub fn values(&self) -> (i64, i64) {
+        (self.limit.unwrap_or(i64::MAX), self.offset.unwrap_or(0))
     }
 }
@@ -1,49 +1,209 @@
+use crate::query::build_token_filter;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use meshql_core::{Envelope, MeshqlError, Repository, Result};
-use sqlx::{Row, SqlitePool};
-use std::collections::HashMap;
+use meshql_core::{
+    conflict, forbid_hidden, AuthPolicy, Clock, Envelope, IdStrategy, ListOptions, MeshqlError,
+    PayloadCodec, PoolConfig, PoolStats, RepoStats, Repository, Result, SystemClock,
+};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
+
+/// SQLite's default `SQLITE_MAX_VARIABLE_NUMBER` is 999; each row binds 6 values.
+const MAX_ROWS_PER_INSERT: usize = 999 / 6;
+/// Ids bound per bulk read, leaving the rest of the 999 for the token filter.
+const MAX_IDS_PER_SELECT: usize = 500;
 
 pub struct SqliteRepository {
     pub pool: SqlitePool,
+    pub table: String,
+    ids: IdStrategy,
+    clock: Arc<dyn Clock>,
+    codec: PayloadCodec,
+    /// Fail reads of several rows on one that doesn't parse, rather than skip it.
+    strict: bool,
+    policy: AuthPolicy,
 }
 
 impl SqliteRepository {
+    /// Create a new repository using the default table name `envelopes`.
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = SqlitePool::connect(database_url)
-            .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        Self::init_schema(&pool).await?;
-        Ok(Self { pool })
+        Self::new_with_table(database_url, "envelopes").await
+    }
+
+    /// Create a new repository with a custom table name, so several entities
+    /// can share one database file.
+    pub async fn new_with_table(database_url: &str, table: &str) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, table, PoolConfig::default()).await
+    }
+
+    /// Connect with a pool sized by `config`. Every connection to `sqlite::memory:`
+    /// opens a separate database, so in-memory pools need a single connection.
+    pub async fn new_with_config(database_url: &str, config: PoolConfig) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, "envelopes", config).await
+    }
+
+    pub async fn new_with_table_and_config(
+        database_url: &str,
+        table: &str,
+        config: PoolConfig,
+    ) -> Result<Self> {
+        let pool = crate::pool::connect(database_url, &config).await?;
+        Self::new_with_pool_and_table(pool, table).await
     }
 
     pub async fn new_with_pool(pool: SqlitePool) -> Result<Self> {
-        Self::init_schema(&pool).await?;
-        Ok(Self { pool })
+        Self::new_with_pool_and_table(pool, "envelopes").await
+    }
+
+    pub async fn new_with_pool_and_table(pool: SqlitePool, table: &str) -> Result<Self> {
+        Self::init_schema(&pool, table).await?;
+        Ok(Self {
+            pool,
+            table: table.to_string(),
+            ids: IdStrategy::default(),
+            clock: Arc::new(SystemClock),
+            codec: PayloadCodec::default(),
+            strict: false,
+            policy: AuthPolicy::default(),
+        })
+    }
+
+    /// Name envelopes created without an id by `strategy`, rather than
+    /// [`IdStrategy::Uuid4`].
+    pub fn with_id_strategy(mut self, strategy: IdStrategy) -> Self {
+        self.ids = strategy;
+        self
+    }
+
+    /// Take the time from `clock`, rather than [`SystemClock`], both to stamp
+    /// updates and tombstones with and to read the latest versions as of.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Write payloads with `codec`, rather than [`PayloadCodec::Json`].
+    pub fn with_payload_codec(mut self, codec: PayloadCodec) -> Self {
+        self.codec = codec;
+        self
     }
 
-    async fn init_schema(pool: &SqlitePool) -> Result<()> {
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS envelopes (
+    /// Fail `list` and `read_many` on a row whose payload or tokens don't
+    /// parse, rather than skip it with a warning.
+    pub fn with_strict_parsing(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Match callers' tokens by `policy`, rather than [`AuthPolicy::AnyOf`].
+    pub fn with_auth_policy(mut self, policy: AuthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Decode `rows`, skipping any that don't parse unless strict.
+    fn decode_rows(&self, rows: &[sqlx::sqlite::SqliteRow]) -> Result<Vec<Envelope>> {
+        let mut envelopes = Vec::with_capacity(rows.len());
+        for row in rows {
+            match Self::row_to_envelope(row) {
+                Ok(env) => envelopes.push(env),
+                Err(MeshqlError::Parse(e)) if !self.strict => {
+                    warn!(table = %self.table, error = %e, "skipping a row that doesn't parse");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(envelopes)
+    }
+
+    async fn init_schema(pool: &SqlitePool, table: &str) -> Result<()> {
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
                 id TEXT NOT NULL,
                 created_at_ms INTEGER NOT NULL,
                 deleted INTEGER NOT NULL DEFAULT 0,
                 authorized_tokens TEXT NOT NULL,
-                payload TEXT NOT NULL
-            )",
-        )
+                payload TEXT NOT NULL,
+                payload_bin BLOB
+            )"
+        ))
         .execute(pool)
         .await
         .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Self::add_payload_bin_column(pool, table).await?;
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_envelopes_id ON envelopes(id)")
+        for (name, column) in [("id", "id"), ("created", "created_at_ms")] {
+            sqlx::query(&format!(
+                "CREATE INDEX IF NOT EXISTS idx_{table}_{name} ON {table}({column})"
+            ))
             .execute(pool)
             .await
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
 
         Ok(())
     }
 
+    /// Give a table created before payloads could be binary its `payload_bin`
+    /// column. Its rows keep reading from `payload`.
+    async fn add_payload_bin_column(pool: &SqlitePool, table: &str) -> Result<()> {
+        let (columns,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = 'payload_bin'")
+                .bind(table)
+                .fetch_one(pool)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        if columns == 0 {
+            sqlx::query(&format!("ALTER TABLE {table} ADD COLUMN payload_bin BLOB"))
+                .execute(pool)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// The latest version of each of `ids`, skipping ids that are deleted or
+    /// whose latest version `tokens` can't see.
+    async fn read_latest(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
+        let cutoff_ms = self.clock.now().timestamp_millis() + 1;
+        let token_filter = build_token_filter(tokens, self.policy);
+        let table = &self.table;
+        let mut results = Vec::with_capacity(ids.len());
+        for chunk in ids.chunks(MAX_IDS_PER_SELECT) {
+            let sql = format!(
+                "WITH latest AS (
+                    SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin,
+                           ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, rowid DESC) AS rn
+                    FROM {table} WHERE id IN ({}) AND created_at_ms <= ?
+                )
+                SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+                FROM latest WHERE rn = 1 AND deleted = 0{}",
+                vec!["?"; chunk.len()].join(", "),
+                token_filter
+                    .as_ref()
+                    .map(|f| format!(" AND {}", f.clause))
+                    .unwrap_or_default()
+            );
+
+            let mut q = sqlx::query(&sql);
+            for id in chunk {
+                q = q.bind(id);
+            }
+            q = q.bind(cutoff_ms);
+            for val in token_filter.iter().flat_map(|f| &f.values) {
+                q = q.bind(val);
+            }
+            let rows = q
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            results.extend(self.decode_rows(&rows)?);
+        }
+        Ok(results)
+    }
+
     fn row_to_envelope(row: &sqlx::sqlite::SqliteRow) -> Result<Envelope> {
         let id: String = row
             .try_get("id")
@@ -60,12 +220,14 @@ impl SqliteRepository {
         let payload_json: String = row
             .try_get("payload")
             .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let payload_bin: Option<Vec<u8>> = row
+            .try_get("payload_bin")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
         let created_at = DateTime::from_timestamp_millis(created_at_ms).unwrap_or_default();
         let authorized_tokens: Vec<String> =
             serde_json::from_str(&tokens_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-        let payload: meshql_core::Stash =
-            serde_json::from_str(&payload_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let payload = PayloadCodec::from_columns(&payload_json, payload_bin.as_deref())?;
 
         Ok(Envelope {
             id,
@@ -82,7 +244,7 @@ impl Repository for SqliteRepository {
     async fn create(&self, envelope: Envelope, tokens: &[String]) -> Result<Envelope> {
         let mut env = envelope;
         if env.id.is_empty() {
-            env.id = uuid::Uuid::new_v4().to_string();
+            env.id = self.ids.generate();
         }
         env.authorized_tokens = tokens.to_vec();
 
@@ -90,17 +252,18 @@ impl Repository for SqliteRepository {
         let deleted_i: i64 = if env.deleted { 1 } else { 0 };
         let tokens_json = serde_json::to_string(&env.authorized_tokens)
             .map_err(|e| MeshqlError::Parse(e.to_string()))?;
-        let payload_json =
-            serde_json::to_string(&env.payload).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
 
-        sqlx::query(
-            "INSERT INTO envelopes (id, created_at_ms, deleted, authorized_tokens, payload) VALUES (?, ?, ?, ?, ?)",
-        )
+        sqlx::query(&format!(
+            "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) VALUES (?, ?, ?, ?, ?, ?)",
+            self.table
+        ))
         .bind(&env.id)
         .bind(created_at_ms)
         .bind(deleted_i)
         .bind(&tokens_json)
         .bind(&payload_json)
+        .bind(&payload_bin)
         .execute(&self.pool)
         .await
         .map_err(|e| MeshqlError::Storage(e.to_string()))?;
@@ -111,68 +274,186 @@ impl Repository for SqliteRepository {
     async fn read(
         &self,
         id: &str,
-        _tokens: &[String],
+        tokens: &[String],
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Option<Envelope>> {
+        Ok(self
+            .read_raw(id, tokens, at)
+            .await?
+            .filter(|env| !env.deleted))
+    }
+
+    async fn read_raw(
+        &self,
+        id: &str,
+        tokens: &[String],
         at: Option<DateTime<Utc>>,
     ) -> Result<Option<Envelope>> {
         let cutoff_ms = match at {
             Some(t) => t.timestamp_millis(),
-            None => Utc::now().timestamp_millis() + 1,
+            None => self.clock.now().timestamp_millis() + 1,
         };
 
-        let row = sqlx::query(
-            "SELECT id, created_at_ms, deleted, authorized_tokens, payload
-             FROM envelopes WHERE id = ? AND created_at_ms <= ?
-             ORDER BY created_at_ms DESC, rowid DESC LIMIT 1",
-        )
-        .bind(id)
-        .bind(cutoff_ms)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let token_filter = build_token_filter(tokens, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin FROM (
+                SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+                FROM {} WHERE id = ? AND created_at_ms <= ?
+                ORDER BY created_at_ms DESC, rowid DESC LIMIT 1
+            ){}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" WHERE {}", f.clause))
+                .unwrap_or_default()
+        );
 
-        match row {
-            None => Ok(None),
-            Some(r) => {
-                let env = Self::row_to_envelope(&r)?;
-                if env.deleted {
-                    Ok(None)
-                } else {
-                    Ok(Some(env))
-                }
-            }
+        let mut q = sqlx::query(&sql).bind(id).bind(cutoff_ms);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
         }
+        let row = q
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        row.map(|r| Self::row_to_envelope(&r)).transpose()
     }
 
-    async fn list(&self, _tokens: &[String]) -> Result<Vec<Envelope>> {
-        let rows = sqlx::query(
+    async fn list_with(&self, tokens: &[String], options: ListOptions) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let sql = format!(
             "WITH latest AS (
-                SELECT id, created_at_ms, deleted, authorized_tokens, payload,
+                SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin,
                        ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, rowid DESC) AS rn
-                FROM envelopes
+                FROM {}
             )
-            SELECT id, created_at_ms, deleted, authorized_tokens, payload
-            FROM latest WHERE rn = 1 AND deleted = 0",
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+            SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+            FROM latest WHERE rn = 1{}{}{}",
+            self.table,
+            options
+                .deleted()
+                .map(|deleted| format!(" AND deleted = {}", deleted as i32))
+                .unwrap_or_default(),
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default(),
+            page_clause(&options)
+        );
 
-        let mut results = Vec::new();
-        for row in rows {
-            results.push(Self::row_to_envelope(&row)?);
+        let mut q = sqlx::query(&sql);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
         }
-        Ok(results)
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        self.decode_rows(&rows)
+    }
+
+    async fn count(&self, tokens: &[String]) -> Result<u64> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let sql = format!(
+            "WITH latest AS (
+                SELECT deleted, authorized_tokens,
+                       ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, rowid DESC) AS rn
+                FROM {}
+            )
+            SELECT COUNT(*) FROM latest WHERE rn = 1 AND deleted = 0{}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+
+        let mut q = sqlx::query_scalar::<_, i64>(&sql);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let count = q
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(count as u64)
+    }
+
+    async fn history(&self, id: &str, tokens: &[String]) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+            FROM {} WHERE id = ?{}
+            ORDER BY created_at_ms ASC, rowid ASC",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+
+        let mut q = sqlx::query(&sql).bind(id);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        rows.iter().map(Self::row_to_envelope).collect()
+    }
+
+    async fn created_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        tokens: &[String],
+    ) -> Result<Vec<Envelope>> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let sql = format!(
+            "SELECT id, created_at_ms, deleted, authorized_tokens, payload, payload_bin
+            FROM {} WHERE created_at_ms >= ? AND created_at_ms < ?{}
+            ORDER BY created_at_ms ASC, rowid ASC",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+
+        let mut q = sqlx::query(&sql)
+            .bind(from.timestamp_millis())
+            .bind(to.timestamp_millis());
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let rows = q
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
+        rows.iter().map(Self::row_to_envelope).collect()
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
     }
 
     async fn remove(&self, id: &str, tokens: &[String]) -> Result<bool> {
         let current = self.read(id, tokens, None).await?;
         match current {
-            None => Ok(false),
+            None => {
+                forbid_hidden(self, id, tokens).await?;
+                Ok(false)
+            }
             Some(env) => {
                 let deleted_env = Envelope {
                     id: env.id,
                     payload: env.payload,
-                    created_at: Utc::now(),
+                    created_at: self.clock.now(),
                     deleted: true,
                     authorized_tokens: env.authorized_tokens,
                 };
@@ -182,26 +463,135 @@ impl Repository for SqliteRepository {
         }
     }
 
+    async fn purge(&self, id: &str, tokens: &[String]) -> Result<u64> {
+        let token_filter = build_token_filter(tokens, self.policy);
+        let sql = format!(
+            "DELETE FROM {} WHERE id = ?{}",
+            self.table,
+            token_filter
+                .as_ref()
+                .map(|f| format!(" AND {}", f.clause))
+                .unwrap_or_default()
+        );
+
+        let mut q = sqlx::query(&sql).bind(id);
+        for val in token_filter.iter().flat_map(|f| &f.values) {
+            q = q.bind(val);
+        }
+        let result = q
+            .execute(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(result.rows_affected())
+    }
+
+    /// One `INSERT ... SELECT ... WHERE NOT EXISTS`, which SQLite runs under
+    /// the database's write lock, so it holds for every connection to it.
+    async fn create_if_unchanged(
+        &self,
+        envelope: Envelope,
+        expected_created_at: Option<DateTime<Utc>>,
+        tokens: &[String],
+    ) -> Result<Envelope> {
+        let mut env = envelope;
+        if env.id.is_empty() {
+            env.id = self.ids.generate();
+        }
+        env.authorized_tokens = tokens.to_vec();
+
+        let deleted_i: i64 = if env.deleted { 1 } else { 0 };
+        let tokens_json = serde_json::to_string(&env.authorized_tokens)
+            .map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
+
+        let sql = format!(
+            "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin)
+            SELECT ?, ?, ?, ?, ?, ? WHERE NOT EXISTS ({})",
+            self.table,
+            superseding_clause(&self.table, expected_created_at.is_some())
+        );
+        let mut q = sqlx::query(&sql)
+            .bind(&env.id)
+            .bind(env.created_at.timestamp_millis())
+            .bind(deleted_i)
+            .bind(&tokens_json)
+            .bind(&payload_json)
+            .bind(&payload_bin)
+            .bind(&env.id);
+        if let Some(expected) = expected_created_at {
+            q = q.bind(expected.timestamp_millis());
+        }
+        let result = q
+            .execute(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(conflict(&env.id));
+        }
+        Ok(env)
+    }
+
     async fn create_many(
         &self,
         envelopes: Vec<Envelope>,
         tokens: &[String],
     ) -> Result<Vec<Envelope>> {
-        let mut results = Vec::new();
-        for env in envelopes {
-            results.push(self.create(env, tokens).await?);
+        let tokens_json =
+            serde_json::to_string(tokens).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+
+        let mut results = Vec::with_capacity(envelopes.len());
+        let mut rows = Vec::with_capacity(envelopes.len());
+        for mut env in envelopes {
+            if env.id.is_empty() {
+                env.id = self.ids.generate();
+            }
+            env.authorized_tokens = tokens.to_vec();
+            let (payload_json, payload_bin) = self.codec.to_columns(&env.payload)?;
+            rows.push((
+                env.id.clone(),
+                env.created_at.timestamp_millis(),
+                if env.deleted { 1i64 } else { 0i64 },
+                payload_json,
+                payload_bin,
+            ));
+            results.push(env);
         }
+
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        for chunk in rows.chunks(MAX_ROWS_PER_INSERT) {
+            let mut qb = QueryBuilder::<Sqlite>::new(format!(
+                "INSERT INTO {} (id, created_at_ms, deleted, authorized_tokens, payload, payload_bin) ",
+                self.table
+            ));
+            qb.push_values(
+                chunk,
+                |mut b, (id, created_at_ms, deleted_i, payload, payload_bin)| {
+                    b.push_bind(id)
+                        .push_bind(created_at_ms)
+                        .push_bind(deleted_i)
+                        .push_bind(&tokens_json)
+                        .push_bind(payload)
+                        .push_bind(payload_bin);
+                },
+            );
+            qb.build()
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+
         Ok(results)
     }
 
     async fn read_many(&self, ids: &[String], tokens: &[String]) -> Result<Vec<Envelope>> {
-        let mut results = Vec::new();
-        for id in ids {
-            if let Some(env) = self.read(id, tokens, None).await? {
-                results.push(env);
-            }
-        }
-        Ok(results)
+        self.read_latest(ids, tokens).await
     }
 
     async fn remove_many(
@@ -209,11 +599,64 @@ impl Repository for SqliteRepository {
         ids: &[String],
         tokens: &[String],
     ) -> Result<HashMap<String, bool>> {
-        let mut results = HashMap::new();
-        for id in ids {
-            let deleted = self.remove(id, tokens).await?;
-            results.insert(id.clone(), deleted);
-        }
-        Ok(results)
+        let current = self.read_latest(ids, tokens).await?;
+        let removed: HashSet<String> = current.iter().map(|env| env.id.clone()).collect();
+        let now = self.clock.now();
+        let tombstones = current
+            .into_iter()
+            .map(|env| Envelope {
+                created_at: now,
+                deleted: true,
+                ..env
+            })
+            .collect();
+        self.create_many(tombstones, tokens).await?;
+        Ok(ids
+            .iter()
+            .map(|id| (id.clone(), removed.contains(id)))
+            .collect())
+    }
+
+    async fn stats(&self) -> Result<RepoStats> {
+        Ok(RepoStats {
+            count: self.count(&["*".to_string()]).await?,
+            pool: Some(PoolStats::new(
+                self.pool.size(),
+                self.pool.num_idle() as u32,
+            )),
+        })
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
+    }
+}
+
+/// `ORDER BY id` and the `LIMIT`/`OFFSET` of the page `options` asks for, if any.
+fn page_clause(options: &ListOptions) -> String {
+    if !options.is_paged() {
+        return String::new();
+    }
+    // SQLite only takes an OFFSET after a LIMIT; -1 is no limit
+    let limit = options.limit.map_or(-1, |limit| limit as i64);
+    format!(" ORDER BY id LIMIT {limit} OFFSET {}", options.offset)
+}
+
+/// A query finding the version of the id bound first that rules out a
+/// conditional write: one newer than the millis bound next when the write
+/// expects a version, or else a live latest version.
+fn superseding_clause(table: &str, expects_version: bool) -> String {
+    match expects_version {
+        true => format!("SELECT 1 FROM {table} WHERE id = ? AND created_at_ms > ?"),
+        false => format!(
+            "SELECT 1 FROM (
+                SELECT deleted FROM {table} WHERE id = ?
+                ORDER BY created_at_ms DESC, rowid DESC LIMIT 1
+            ) WHERE deleted = 0"
+        ),
     }
 }
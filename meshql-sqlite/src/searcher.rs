@@ -1,105 +1,100 @@
-use crate::query::build_where;
+use crate::query::{build_where, column, created_filter, Page, QueryPart};
+use async_stream::try_stream;
 use async_trait::async_trait;
-use handlebars::Handlebars;
-use meshql_core::{Envelope, MeshqlError, Result, Searcher, Stash};
+use futures::TryStreamExt;
+use meshql_core::{
+    check_or_groups, insert_metadata, is_projectable, render_template, MeshqlError, MissingKey,
+    PoolConfig, Result, Searcher, SortField, Stash, StashStream,
+};
 use serde_json::json;
 use sqlx::{Row, SqlitePool};
 
 pub struct SqliteSearcher {
     pool: SqlitePool,
-    handlebars: Handlebars<'static>,
+    table: String,
 }
 
 impl SqliteSearcher {
+    /// Search the default table `envelopes`.
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = SqlitePool::connect(database_url)
-            .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        Self::init_schema(&pool).await?;
-        let mut handlebars = Handlebars::new();
-        handlebars.set_strict_mode(false);
-        Ok(Self { pool, handlebars })
+        Self::new_with_table(database_url, "envelopes").await
+    }
+
+    pub async fn new_with_table(database_url: &str, table: &str) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, table, PoolConfig::default()).await
+    }
+
+    /// Connect with a pool sized by `config`. Every connection to `sqlite::memory:`
+    /// opens a separate database, so in-memory pools need a single connection.
+    pub async fn new_with_config(database_url: &str, config: PoolConfig) -> Result<Self> {
+        Self::new_with_table_and_config(database_url, "envelopes", config).await
+    }
+
+    pub async fn new_with_table_and_config(
+        database_url: &str,
+        table: &str,
+        config: PoolConfig,
+    ) -> Result<Self> {
+        let pool = crate::pool::connect(database_url, &config).await?;
+        Self::new_with_pool_and_table(pool, table).await
     }
 
     pub async fn new_with_pool(pool: SqlitePool) -> Result<Self> {
-        Self::init_schema(&pool).await?;
-        let mut handlebars = Handlebars::new();
-        handlebars.set_strict_mode(false);
-        Ok(Self { pool, handlebars })
+        Self::new_with_pool_and_table(pool, "envelopes").await
     }
 
-    async fn init_schema(pool: &SqlitePool) -> Result<()> {
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS envelopes (
+    pub async fn new_with_pool_and_table(pool: SqlitePool, table: &str) -> Result<Self> {
+        Self::init_schema(&pool, table).await?;
+        Ok(Self {
+            pool,
+            table: table.to_string(),
+        })
+    }
+
+    async fn init_schema(pool: &SqlitePool, table: &str) -> Result<()> {
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
                 id TEXT NOT NULL,
                 created_at_ms INTEGER NOT NULL,
                 deleted INTEGER NOT NULL DEFAULT 0,
                 authorized_tokens TEXT NOT NULL,
-                payload TEXT NOT NULL
-            )",
-        )
+                payload TEXT NOT NULL,
+                payload_bin BLOB
+            )"
+        ))
         .execute(pool)
         .await
         .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
-        sqlx::query("CREATE INDEX IF NOT EXISTS idx_envelopes_id ON envelopes(id)")
-            .execute(pool)
-            .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS idx_{table}_id ON {table}(id)"
+        ))
+        .execute(pool)
+        .await
+        .map_err(|e| MeshqlError::Storage(e.to_string()))?;
 
         Ok(())
     }
 
     fn render_template(&self, template: &str, args: &Stash) -> Result<String> {
-        self.handlebars
-            .render_template(template, &serde_json::Value::Object(args.clone()))
-            .map_err(|e| MeshqlError::Template(e.to_string()))
+        render_template(template, args, MissingKey::Empty)
     }
 
-    fn row_to_envelope(row: &sqlx::sqlite::SqliteRow) -> Result<Envelope> {
-        let id: String = row
-            .try_get("id")
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        let created_at_ms: i64 = row
-            .try_get("created_at_ms")
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        let deleted_i: i64 = row
-            .try_get("deleted")
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        let tokens_json: String = row
-            .try_get("authorized_tokens")
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-        let payload_json: String = row
-            .try_get("payload")
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
-
-        let created_at = chrono::DateTime::from_timestamp_millis(created_at_ms).unwrap_or_default();
-        let authorized_tokens: Vec<String> =
-            serde_json::from_str(&tokens_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-        let payload: meshql_core::Stash =
-            serde_json::from_str(&payload_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
-
-        Ok(Envelope {
-            id,
-            payload,
-            created_at,
-            deleted: deleted_i != 0,
-            authorized_tokens,
-        })
-    }
-
-    async fn execute_query(
+    /// Render the template into the latest-version query, selecting `projection`.
+    /// A `distinct` payload field keeps the lowest id of each of its values;
+    /// the latest versions already hold one row per id.
+    fn build_query(
         &self,
         template: &str,
         args: &Stash,
-        _creds: &[String],
-        at: i64,
-        limit: Option<i64>,
-    ) -> Result<Vec<Stash>> {
-        let query_json = self.render_template(template, args)?;
+        projection: &str,
+    ) -> Result<(String, QueryPart)> {
+        let (filter_args, page) = Page::split(args)?;
+        let query_json = self.render_template(template, &filter_args)?;
 
         let query_val: serde_json::Value =
             serde_json::from_str(&query_json).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+        check_or_groups(&query_val)?;
 
         let query_obj = query_val.as_object().ok_or_else(|| {
             MeshqlError::Parse("Query template must produce a JSON object".to_string())
@@ -107,52 +102,121 @@ impl SqliteSearcher {
 
         let where_part = build_where(query_obj);
 
-        let cutoff_ms = at + 1;
-
-        let base_sql = "
+        let table = &self.table;
+        let latest = format!(
+            "
 WITH latest AS (
     SELECT id, created_at_ms, deleted, authorized_tokens, payload,
            ROW_NUMBER() OVER (PARTITION BY id ORDER BY created_at_ms DESC, rowid DESC) AS rn
-    FROM envelopes WHERE created_at_ms <= ?
+    FROM {table} WHERE created_at_ms <= ?
+)"
+        );
+        let mut filter = format!("rn = 1 AND deleted = 0{}", created_filter(&page.created));
+        if !where_part.clause.is_empty() {
+            filter = format!("{filter} AND {}", where_part.clause);
+        }
+
+        let sql = match &page.distinct {
+            Some(field @ SortField::Payload(_)) => format!(
+                "{latest},
+matched AS (
+    SELECT latest.*, ROW_NUMBER() OVER (PARTITION BY {} ORDER BY id) AS dn
+    FROM latest WHERE {filter}
 )
-SELECT id, created_at_ms, deleted, authorized_tokens, payload
-FROM latest WHERE rn = 1 AND deleted = 0"
-            .to_string();
-
-        let sql = if where_part.clause.is_empty() {
-            if let Some(lim) = limit {
-                format!("{} LIMIT {}", base_sql, lim)
-            } else {
-                base_sql
-            }
-        } else {
-            let with_where = format!("{} AND {}", base_sql, where_part.clause);
-            if let Some(lim) = limit {
-                format!("{} LIMIT {}", with_where, lim)
-            } else {
-                with_where
-            }
+SELECT {projection}
+FROM matched WHERE dn = 1",
+                column(field)
+            ),
+            _ => format!("{latest}\nSELECT {projection}\nFROM latest WHERE {filter}"),
         };
 
-        let mut q = sqlx::query(&sql).bind(cutoff_ms);
-        for val in &where_part.values {
-            q = q.bind(val);
+        Ok((sql, where_part))
+    }
+
+    /// `id`, the version's metadata and the payload as `body`, cut down to
+    /// `fields` when they are given and safe to interpolate. The alias leaves
+    /// `payload` free for sorting on.
+    fn projection(fields: Option<&[String]>) -> String {
+        match fields {
+            // SQLite functions take at most 127 arguments by default, two per field.
+            Some(fields) if is_projectable(fields) && fields.len() <= 63 => {
+                let pairs: Vec<String> = fields
+                    .iter()
+                    .map(|f| format!("'{f}', payload -> '$.\"{f}\"'"))
+                    .collect();
+                format!(
+                    "id, created_at_ms, deleted, json_object({}) AS body",
+                    pairs.join(", ")
+                )
+            }
+            _ => "id, created_at_ms, deleted, payload AS body".to_string(),
         }
+    }
 
-        let rows = q
-            .fetch_all(&self.pool)
+    async fn execute_query(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: Option<&[String]>,
+        at: i64,
+        page: Page,
+    ) -> Result<Vec<Stash>> {
+        self.stream_query(template, args, fields, at, page)?
+            .try_collect()
             .await
-            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+    }
 
-        let mut results = Vec::new();
-        for row in rows {
-            let env = Self::row_to_envelope(&row)?;
-            let mut stash = env.payload.clone();
-            stash.insert("id".to_string(), json!(env.id));
-            results.push(stash);
-        }
+    /// Matching rows, decoded one at a time as the database returns them.
+    fn stream_query(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: Option<&[String]>,
+        at: i64,
+        page: Page,
+    ) -> Result<StashStream> {
+        let (base_sql, where_part) = self.build_query(template, args, &Self::projection(fields))?;
+
+        let cutoff_ms = at + 1;
 
-        Ok(results)
+        let sql = format!("{}{}", base_sql, page.clause());
+        let pool = self.pool.clone();
+
+        Ok(Box::pin(try_stream! {
+            let mut q = sqlx::query(&sql).bind(cutoff_ms);
+            for val in &where_part.values {
+                q = q.bind(val);
+            }
+            if !page.is_unpaged() {
+                let (limit, offset) = page.values();
+                q = q.bind(limit).bind(offset);
+            }
+
+            let mut rows = q.fetch(&pool);
+            while let Some(row) = rows
+                .try_next()
+                .await
+                .map_err(|e| MeshqlError::Storage(e.to_string()))?
+            {
+                let id: String = row
+                    .try_get("id")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let body: String = row
+                    .try_get("body")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let created_at_ms: i64 = row
+                    .try_get("created_at_ms")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let deleted: bool = row
+                    .try_get("deleted")
+                    .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+                let mut stash: Stash =
+                    serde_json::from_str(&body).map_err(|e| MeshqlError::Parse(e.to_string()))?;
+                stash.insert("id".to_string(), json!(id));
+                insert_metadata(&mut stash, created_at_ms, deleted);
+                yield stash;
+            }
+        }))
     }
 }
 
@@ -162,11 +226,11 @@ impl Searcher for SqliteSearcher {
         &self,
         template: &str,
         args: &Stash,
-        creds: &[String],
+        _creds: &[String],
         at: i64,
     ) -> Result<Option<Stash>> {
         let mut results = self
-            .execute_query(template, args, creds, at, Some(1))
+            .execute_query(template, args, None, at, Page::first())
             .await?;
         Ok(results.pop())
     }
@@ -175,10 +239,96 @@ impl Searcher for SqliteSearcher {
         &self,
         template: &str,
         args: &Stash,
-        creds: &[String],
+        _creds: &[String],
         at: i64,
     ) -> Result<Vec<Stash>> {
-        let limit = args.get("limit").and_then(|v| v.as_i64());
-        self.execute_query(template, args, creds, at, limit).await
+        let (_, page) = Page::split(args)?;
+        self.execute_query(template, args, None, at, page).await
+    }
+
+    async fn find_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        _creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        let mut results = self
+            .execute_query(template, args, Some(fields), at, Page::first())
+            .await?;
+        Ok(results.pop())
+    }
+
+    async fn find_all_projected(
+        &self,
+        template: &str,
+        args: &Stash,
+        fields: &[String],
+        _creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        let (_, page) = Page::split(args)?;
+        self.execute_query(template, args, Some(fields), at, page)
+            .await
+    }
+
+    async fn find_stream(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<StashStream> {
+        let (_, page) = Page::split(args)?;
+        self.stream_query(template, args, None, at, page)
+    }
+
+    async fn count(&self, template: &str, args: &Stash, _creds: &[String], at: i64) -> Result<u64> {
+        let (sql, where_part) = self.build_query(template, args, "COUNT(*) AS n")?;
+
+        let mut q = sqlx::query(&sql).bind(at + 1);
+        for val in &where_part.values {
+            q = q.bind(val);
+        }
+
+        let row = q
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        let n: i64 = row
+            .try_get("n")
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(n as u64)
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        _creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        let (sql, where_part) = self.build_query(template, args, "1")?;
+        let sql = format!("{} LIMIT 1", sql);
+
+        let mut q = sqlx::query(&sql).bind(at + 1);
+        for val in &where_part.values {
+            q = q.bind(val);
+        }
+
+        let row = q
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| MeshqlError::Storage(e.to_string()))?;
+        Ok(row.is_some())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|e| MeshqlError::Storage(e.to_string()))
     }
 }
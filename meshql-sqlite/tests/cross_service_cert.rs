@@ -2,7 +2,9 @@ use cucumber::World as _;
 #[allow(unused_imports)]
 use meshql_cert::steps::farm;
 use meshql_cert::CertWorld;
-use meshql_core::{GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{
+    DeleteMode, GraphletteConfig, NoAuth, RestletteConfig, RootConfig, ServerConfig,
+};
 use meshql_server::build_app;
 use meshql_sqlite::{SqliteRepository, SqliteSearcher};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
@@ -116,7 +118,10 @@ async fn build_cross_service_servers() -> (String, String) {
             path: "/farm/api".into(),
             schema_json: serde_json::json!({}),
             repository: farm_repo,
+            delete_mode: DeleteMode::Soft,
         }],
+        cors: None,
+        max_body_bytes: None,
     };
 
     // Server B config: coop with HTTP resolver pointing at Server A for farm
@@ -144,7 +149,10 @@ async fn build_cross_service_servers() -> (String, String) {
             path: "/coop/api".into(),
             schema_json: serde_json::json!({}),
             repository: coop_repo,
+            delete_mode: DeleteMode::Soft,
         }],
+        cors: None,
+        max_body_bytes: None,
     };
 
     let app_a = build_app(server_a_config).await.unwrap();
@@ -2,7 +2,7 @@ use cucumber::World as _;
 #[allow(unused_imports)]
 use meshql_cert::steps::farm;
 use meshql_cert::CertWorld;
-use meshql_core::{GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
+use meshql_core::{DeleteMode, GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
 use meshql_server::build_app;
 use meshql_sqlite::{SqliteRepository, SqliteSearcher};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
@@ -571,70 +571,85 @@ async fn build_egg_economy_server() -> String {
                 path: "/farm/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/coop/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: coop.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumer/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: consumer.repo,
+                delete_mode: DeleteMode::Soft,
             },
             // Events
             RestletteConfig {
                 path: "/lay_report/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: lay_report.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_deposit/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: storage_deposit.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/storage_withdrawal/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: storage_withdrawal.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/container_transfer/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container_transfer.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/consumption_report/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: consumption_report.repo,
+                delete_mode: DeleteMode::Soft,
             },
             // Projections
             RestletteConfig {
                 path: "/container_inventory/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: container_inventory.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/hen_productivity/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: hen_productivity.repo,
+                delete_mode: DeleteMode::Soft,
             },
             RestletteConfig {
                 path: "/farm_output/api".into(),
                 schema_json: serde_json::json!({}),
                 repository: farm_output.repo,
+                delete_mode: DeleteMode::Soft,
             },
         ],
+        cors: None,
+        max_body_bytes: None,
     };
 
     let app = build_app(server_config).await.unwrap();
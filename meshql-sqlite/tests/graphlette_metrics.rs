@@ -0,0 +1,159 @@
+use meshql_core::{Envelope, GraphletteConfig, Repository, RootConfig, Searcher, ServerConfig};
+use meshql_server::build_app;
+use meshql_sqlite::{SqliteRepository, SqliteSearcher};
+use serde_json::json;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::str::FromStr;
+use std::sync::Arc;
+
+const FARM_GRAPHQL: &str = r#"
+type Farm {
+    id: ID
+    name: String
+    coops: [Coop]
+}
+type Coop {
+    id: ID
+    name: String
+}
+type Query {
+    getFarms(name: String, at: Int): [Farm]
+}
+"#;
+
+const COOP_GRAPHQL: &str = r#"
+type Coop {
+    id: ID
+    name: String
+}
+type Query {
+    getCoopsByFarm(id: ID, at: Int): [Coop]
+}
+"#;
+
+async fn make_store() -> (SqliteRepository, Arc<dyn Searcher>) {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(
+            SqliteConnectOptions::from_str("sqlite::memory:")
+                .unwrap()
+                .create_if_missing(true),
+        )
+        .await
+        .unwrap();
+    let repo = SqliteRepository::new_with_pool(pool.clone()).await.unwrap();
+    let searcher = SqliteSearcher::new_with_pool(pool).await.unwrap();
+    (repo, Arc::new(searcher))
+}
+
+async fn create(repo: &SqliteRepository, id: &str, payload: serde_json::Value) {
+    let payload = payload.as_object().unwrap().clone();
+    let star = vec!["*".to_string()];
+    repo.create(Envelope::new(id, payload, star.clone()), &star)
+        .await
+        .unwrap();
+}
+
+/// The value of the sample named `metric` with exactly `labels`, if scraped.
+fn sample(scrape: &str, metric: &str, labels: &str) -> Option<f64> {
+    let prefix = format!("{metric}{{{labels}}} ");
+    scrape
+        .lines()
+        .find_map(|line| line.strip_prefix(&prefix))
+        .map(|value| value.parse().unwrap())
+}
+
+#[tokio::test]
+async fn metrics_count_requests_and_searcher_fan_out() {
+    let (farm_repo, farm_searcher) = make_store().await;
+    let (coop_repo, coop_searcher) = make_store().await;
+    for f in 0..2 {
+        let farm_id = format!("farm-{f}");
+        create(&farm_repo, &farm_id, json!({"name": "Farm"})).await;
+        for c in 0..2 {
+            let coop_id = format!("{farm_id}-coop-{c}");
+            create(
+                &coop_repo,
+                &coop_id,
+                json!({"name": coop_id, "farmId": farm_id}),
+            )
+            .await;
+        }
+    }
+
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![
+            GraphletteConfig {
+                path: "/farm/graph".to_string(),
+                schema_text: FARM_GRAPHQL.to_string(),
+                root_config: RootConfig::builder()
+                    .vector("getFarms", r#"{"payload.name": "{{name}}"}"#)
+                    .vector_resolver("coops", None, "getCoopsByFarm", "/coop/graph")
+                    .build(),
+                searcher: farm_searcher,
+            },
+            GraphletteConfig {
+                path: "/coop/graph".to_string(),
+                schema_text: COOP_GRAPHQL.to_string(),
+                root_config: RootConfig::builder()
+                    .vector("getCoopsByFarm", r#"{"payload.farmId": "{{id}}"}"#)
+                    .build(),
+                searcher: coop_searcher,
+            },
+        ],
+        restlettes: vec![],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app(config).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let base = format!("http://{addr}");
+    let client = reqwest::Client::new();
+
+    let body: serde_json::Value = client
+        .post(format!("{base}/farm/graph"))
+        .json(&json!({"query": r#"{ getFarms(name: "Farm") { name coops { name } } }"#}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["getFarms"].as_array().unwrap().len(), 2);
+
+    let scrape = client
+        .get(format!("{base}/metrics"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let labels = r#"graphlette="/farm/graph",query="getFarms""#;
+    assert_eq!(
+        sample(&scrape, "meshql_graphql_requests_total", labels),
+        Some(1.0),
+        "{scrape}"
+    );
+    // getFarms, then one batched getCoopsByFarm across both farms.
+    assert_eq!(
+        sample(&scrape, "meshql_graphql_searcher_calls_total", labels),
+        Some(2.0),
+        "{scrape}"
+    );
+    assert!(
+        sample(
+            &scrape,
+            "meshql_graphql_request_duration_seconds_count",
+            labels
+        )
+        .is_some(),
+        "{scrape}"
+    );
+}
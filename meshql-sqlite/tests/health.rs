@@ -0,0 +1,130 @@
+use meshql_core::{DeleteMode, GraphletteConfig, RestletteConfig, RootConfig, ServerConfig};
+use meshql_server::build_app;
+use meshql_sqlite::{SqliteRepository, SqliteSearcher};
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::Arc;
+
+const HEN_GRAPHQL: &str = r#"
+type Hen {
+    id: ID
+    name: String
+}
+type Query {
+    getHen(id: ID, at: Int): Hen
+}
+"#;
+
+#[tokio::test]
+async fn ready_names_the_backends_that_stop_answering() {
+    let repository = SqliteRepository::new("sqlite::memory:").await.unwrap();
+    let pool = repository.pool.clone();
+    let searcher = SqliteSearcher::new_with_pool(pool.clone()).await.unwrap();
+    let coops = SqliteRepository::new("sqlite::memory:").await.unwrap();
+
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![GraphletteConfig {
+            path: "/hen/graph".to_string(),
+            schema_text: HEN_GRAPHQL.to_string(),
+            root_config: RootConfig::builder()
+                .singleton("getHen", r#"{"id": "{{id}}"}"#)
+                .build(),
+            searcher: Arc::new(searcher),
+        }],
+        restlettes: vec![
+            RestletteConfig {
+                path: "/hen/api".to_string(),
+                schema_json: json!({}),
+                repository: Arc::new(repository),
+                delete_mode: DeleteMode::Soft,
+            },
+            RestletteConfig {
+                path: "/coop/api".to_string(),
+                schema_json: json!({}),
+                repository: Arc::new(coops),
+                delete_mode: DeleteMode::Soft,
+            },
+        ],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app(config).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let base = format!("http://{addr}");
+    let client = reqwest::Client::new();
+
+    let ready = client.get(format!("{base}/ready")).send().await.unwrap();
+    assert_eq!(ready.status(), StatusCode::OK);
+
+    // Break the hen store out from under the running app.
+    pool.close().await;
+
+    let health = client.get(format!("{base}/health")).send().await.unwrap();
+    assert_eq!(health.status(), StatusCode::OK);
+
+    let ready = client.get(format!("{base}/ready")).send().await.unwrap();
+    assert_eq!(ready.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = ready.json().await.unwrap();
+    assert_eq!(body["failing"], json!(["/hen/graph", "/hen/api"]));
+}
+
+#[tokio::test]
+async fn stats_count_each_restlettes_documents() {
+    let hens = SqliteRepository::new("sqlite::memory:").await.unwrap();
+    let coops = SqliteRepository::new("sqlite::memory:").await.unwrap();
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![],
+        restlettes: vec![
+            RestletteConfig {
+                path: "/hen/api".to_string(),
+                schema_json: json!({}),
+                repository: Arc::new(hens),
+                delete_mode: DeleteMode::Soft,
+            },
+            RestletteConfig {
+                path: "/coop/api".to_string(),
+                schema_json: json!({}),
+                repository: Arc::new(coops),
+                delete_mode: DeleteMode::Soft,
+            },
+        ],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app(config).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let base = format!("http://{addr}");
+    let client = reqwest::Client::new();
+
+    for name in ["Henny", "Penny"] {
+        let created = client
+            .post(format!("{base}/hen/api"))
+            .json(&json!({"name": name}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(created.status(), StatusCode::CREATED);
+    }
+
+    let stats = client.get(format!("{base}/stats")).send().await.unwrap();
+    assert_eq!(stats.status(), StatusCode::OK);
+    let body: serde_json::Value = stats.json().await.unwrap();
+    assert_eq!(body["/hen/api"]["count"], 2);
+    assert_eq!(body["/coop/api"]["count"], 0);
+    let pool = &body["/hen/api"]["pool"];
+    let size = pool["size"].as_u64().unwrap();
+    assert!(size > 0, "{body}");
+    assert!(pool["active"].as_u64().unwrap() <= size, "{body}");
+}
@@ -0,0 +1,62 @@
+use meshql_core::{Envelope, MeshqlError, Repository, Stash};
+use meshql_sqlite::SqliteRepository;
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+
+async fn repo_with_a_corrupt_row() -> (SqliteRepository, Vec<String>) {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    let repo = SqliteRepository::new_with_pool_and_table(pool.clone(), "hens")
+        .await
+        .unwrap();
+    let tokens = vec!["*".to_string()];
+    for (id, name) in [("hen-1", "Henrietta"), ("hen-2", "Clucky")] {
+        let mut payload = Stash::new();
+        payload.insert("name".into(), json!(name));
+        repo.create(Envelope::new(id, payload, tokens.clone()), &tokens)
+            .await
+            .unwrap();
+    }
+    sqlx::query(
+        "INSERT INTO hens (id, created_at_ms, deleted, authorized_tokens, payload)
+         VALUES ('hen-3', 1, 0, '[\"*\"]', '{not json')",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    (repo, tokens)
+}
+
+#[tokio::test]
+async fn list_should_skip_a_row_whose_payload_does_not_parse() {
+    let (repo, tokens) = repo_with_a_corrupt_row().await;
+
+    let mut names: Vec<_> = repo
+        .list(&tokens)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|e| e.payload["name"].clone())
+        .collect();
+    names.sort_by_key(|n| n.to_string());
+    assert_eq!(names, vec![json!("Clucky"), json!("Henrietta")]);
+
+    let ids = vec!["hen-1".to_string(), "hen-3".to_string()];
+    let found = repo.read_many(&ids, &tokens).await.unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, "hen-1");
+}
+
+#[tokio::test]
+async fn strict_parsing_should_fail_on_a_row_whose_payload_does_not_parse() {
+    let (repo, tokens) = repo_with_a_corrupt_row().await;
+    let repo = repo.with_strict_parsing(true);
+
+    assert!(matches!(
+        repo.list(&tokens).await,
+        Err(MeshqlError::Parse(_))
+    ));
+}
@@ -0,0 +1,143 @@
+use meshql_core::{load_from_file, BackendFactory, RootConfig};
+use meshql_server::{build_app, server_config_from_manifest};
+use meshql_sqlite::SqliteBackend;
+use serde_json::json;
+use std::sync::Arc;
+
+const FARM_MANIFEST: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../examples/farm/config/farm.yaml"
+);
+
+/// The root configs the farm example used to assemble by hand in `main.rs`.
+fn hand_written_root_configs() -> Vec<(&'static str, RootConfig)> {
+    vec![
+        (
+            "/farm/graph",
+            RootConfig::builder()
+                .singleton("getFarm", r#"{"id": "{{id}}"}"#)
+                .vector("getFarms", r#"{"name": "{{name}}"}"#)
+                .vector_resolver("coops", None, "getCoopsByFarm", "/coop/graph")
+                .build(),
+        ),
+        (
+            "/coop/graph",
+            RootConfig::builder()
+                .singleton("getCoop", r#"{"id": "{{id}}"}"#)
+                .vector("getCoops", r#"{"name": "{{name}}"}"#)
+                .vector("getCoopsByFarm", r#"{"farmId": "{{id}}"}"#)
+                .singleton_resolver("farm", Some("farmId"), "getFarm", "/farm/graph")
+                .vector_resolver("hens", None, "getHensByCoop", "/hen/graph")
+                .build(),
+        ),
+        (
+            "/hen/graph",
+            RootConfig::builder()
+                .singleton("getHen", r#"{"id": "{{id}}"}"#)
+                .vector("getHens", r#"{"name": "{{name}}"}"#)
+                .vector("getHensByCoop", r#"{"coopId": "{{id}}"}"#)
+                .singleton_resolver("coop", Some("coopId"), "getCoop", "/coop/graph")
+                .vector_resolver(
+                    "layReports",
+                    None,
+                    "getLayReportsByHen",
+                    "/lay_report/graph",
+                )
+                .build(),
+        ),
+        (
+            "/lay_report/graph",
+            RootConfig::builder()
+                .singleton("getLayReport", r#"{"id": "{{id}}"}"#)
+                .vector("getLayReports", r#"{"date": "{{date}}"}"#)
+                .vector("getLayReportsByHen", r#"{"henId": "{{id}}"}"#)
+                .singleton_resolver("hen", Some("henId"), "getHen", "/hen/graph")
+                .build(),
+        ),
+    ]
+}
+
+#[test]
+fn farm_manifest_matches_the_hand_written_config() {
+    let manifest = load_from_file(FARM_MANIFEST).unwrap();
+    assert_eq!(manifest.port, 3033);
+
+    let expected = hand_written_root_configs();
+    assert_eq!(manifest.graphlettes.len(), expected.len());
+    for (g, (path, root_config)) in manifest.graphlettes.iter().zip(expected) {
+        assert_eq!(g.path, path);
+        assert_eq!(g.root_config(), root_config, "root config for {path}");
+        assert_eq!(g.storage.backend, "mongo");
+        assert_eq!(g.storage.database.as_deref(), Some("farm_db"));
+        assert!(g.schema_text.contains("type Query"));
+    }
+    assert_eq!(
+        manifest.graphlettes[0].schema_text,
+        include_str!("../../examples/farm/config/graph/farm.graphql")
+    );
+
+    let restlettes: Vec<&str> = manifest
+        .restlettes
+        .iter()
+        .map(|r| r.path.as_str())
+        .collect();
+    assert_eq!(
+        restlettes,
+        vec!["/farm/api", "/coop/api", "/hen/api", "/lay_report/api"]
+    );
+    for (r, g) in manifest.restlettes.iter().zip(&manifest.graphlettes) {
+        assert_eq!(r.storage, g.storage);
+        assert_eq!(r.schema_json, json!({}));
+    }
+}
+
+#[tokio::test]
+async fn farm_manifest_builds_a_working_router() {
+    let mut manifest = load_from_file(FARM_MANIFEST).unwrap();
+    for storage in manifest
+        .graphlettes
+        .iter_mut()
+        .map(|g| &mut g.storage)
+        .chain(manifest.restlettes.iter_mut().map(|r| &mut r.storage))
+    {
+        storage.backend = "sqlite".to_string();
+        storage.uri = "sqlite::memory:".to_string();
+    }
+
+    let backends: Vec<Arc<dyn BackendFactory>> = vec![Arc::new(SqliteBackend::new())];
+    let config = server_config_from_manifest(manifest, &backends)
+        .await
+        .unwrap();
+    let app = build_app(config).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    let base = format!("http://{addr}");
+    let client = reqwest::Client::new();
+
+    let created: serde_json::Value = client
+        .post(format!("{base}/farm/api"))
+        .json(&json!({"name": "Emerdale"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = created["id"].as_str().unwrap();
+
+    let query = format!(r#"{{ getFarm(id: "{id}") {{ id name }} }}"#);
+    let body: serde_json::Value = client
+        .post(format!("{base}/farm/graph"))
+        .json(&json!({ "query": query }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["getFarm"]["name"], json!("Emerdale"));
+}
@@ -0,0 +1,44 @@
+use meshql_core::{Envelope, Namespace, Repository, Searcher, Stash};
+use meshql_sqlite::{SqliteRepository, SqliteSearcher};
+use serde_json::json;
+
+#[tokio::test]
+async fn prefixed_tables_keep_entities_apart_in_one_file() {
+    let path = std::env::temp_dir().join(format!("meshql-ns-{}.db", uuid::Uuid::new_v4()));
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+    let north = Namespace::new().prefix("north").name("farms");
+    let south = Namespace::new().prefix("south").name("farms");
+    let north_repo = SqliteRepository::new_with_table(&url, &north)
+        .await
+        .unwrap();
+    let south_repo = SqliteRepository::new_with_table(&url, &south)
+        .await
+        .unwrap();
+    let south_searcher = SqliteSearcher::new_with_table(&url, &south).await.unwrap();
+
+    let tokens = vec!["*".to_string()];
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("Emerdale"));
+    north_repo
+        .create(Envelope::new("farm-1", payload, tokens.clone()), &tokens)
+        .await
+        .unwrap();
+
+    assert_eq!(north_repo.list(&tokens).await.unwrap().len(), 1);
+    assert!(south_repo.list(&tokens).await.unwrap().is_empty());
+    assert!(south_repo
+        .read("farm-1", &tokens, None)
+        .await
+        .unwrap()
+        .is_none());
+    let now = chrono::Utc::now().timestamp_millis();
+    assert!(south_searcher
+        .find_all("{}", &Stash::new(), &tokens, now)
+        .await
+        .unwrap()
+        .is_empty());
+
+    north_repo.pool.close().await;
+    south_repo.pool.close().await;
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,60 @@
+use meshql_core::{Envelope, PayloadCodec, Repository, Stash};
+use meshql_sqlite::SqliteRepository;
+use serde_json::json;
+use sqlx::sqlite::SqlitePoolOptions;
+
+#[tokio::test]
+async fn message_pack_rows_sit_beside_json_rows_written_before_the_column_existed() {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::query(
+        "CREATE TABLE hens (
+            id TEXT NOT NULL,
+            created_at_ms INTEGER NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            authorized_tokens TEXT NOT NULL,
+            payload TEXT NOT NULL
+        )",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO hens VALUES ('hen-1', 1, 0, '[\"*\"]', '{\"name\":\"Henrietta\"}')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let repo = SqliteRepository::new_with_pool_and_table(pool.clone(), "hens")
+        .await
+        .unwrap()
+        .with_payload_codec(PayloadCodec::MessagePack);
+    let tokens = vec!["*".to_string()];
+    let mut payload = Stash::new();
+    for i in 0..500 {
+        payload.insert(
+            format!("lay_{i}"),
+            json!({"eggs": i, "weight": i as f64 / 3.0, "cracked": i % 5 == 0}),
+        );
+    }
+    repo.create(
+        Envelope::new("hen-2", payload.clone(), tokens.clone()),
+        &tokens,
+    )
+    .await
+    .unwrap();
+
+    let old = repo.read("hen-1", &tokens, None).await.unwrap().unwrap();
+    assert_eq!(old.payload["name"], json!("Henrietta"));
+    let wide = repo.read("hen-2", &tokens, None).await.unwrap().unwrap();
+    assert_eq!(wide.payload, payload);
+    assert_eq!(repo.list(&tokens).await.unwrap().len(), 2);
+
+    let (stored,): (Vec<u8>,) = sqlx::query_as("SELECT payload_bin FROM hens WHERE id = 'hen-2'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(stored.len() < serde_json::to_string(&payload).unwrap().len());
+}
@@ -0,0 +1,42 @@
+use meshql_core::{Envelope, Repository, Stash};
+use meshql_sqlite::{PoolConfig, SqliteRepository};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn pooled_repository_serves_concurrent_reads() {
+    // A file database, since every connection to `sqlite::memory:` is a separate database.
+    let path = std::env::temp_dir().join(format!("meshql-pool-{}.db", uuid::Uuid::new_v4()));
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+    let config = PoolConfig::new()
+        .max_connections(8)
+        .acquire_timeout(Duration::from_secs(5));
+    let repo = Arc::new(
+        SqliteRepository::new_with_config(&url, config)
+            .await
+            .unwrap(),
+    );
+    assert_eq!(repo.pool.options().get_max_connections(), 8);
+
+    let tokens = vec!["*".to_string()];
+    let mut payload = Stash::new();
+    payload.insert("name".to_string(), json!("Henrietta"));
+    repo.create(Envelope::new("hen-1", payload, tokens.clone()), &tokens)
+        .await
+        .unwrap();
+
+    let mut reads = tokio::task::JoinSet::new();
+    for _ in 0..32 {
+        let repo = Arc::clone(&repo);
+        let tokens = tokens.clone();
+        reads.spawn(async move { repo.read("hen-1", &tokens, None).await });
+    }
+    while let Some(read) = reads.join_next().await {
+        let env = read.unwrap().unwrap().expect("hen-1 should be readable");
+        assert_eq!(env.payload["name"], json!("Henrietta"));
+    }
+
+    repo.pool.close().await;
+    let _ = std::fs::remove_file(path);
+}
@@ -0,0 +1,214 @@
+use async_graphql::Request;
+use async_trait::async_trait;
+use meshql_core::{Envelope, Repository, Result, RootConfig, Searcher, Stash};
+use meshql_graphlette::{build_schema, BatchLoader, ResolverRegistry};
+use meshql_sqlite::{SqliteRepository, SqliteSearcher};
+use serde_json::json;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const FARM_GRAPHQL: &str = r#"
+type Farm {
+    id: ID
+    name: String
+    coops: [Coop]
+}
+type Coop {
+    id: ID
+    name: String
+    hens: [Hen]
+}
+type Hen {
+    id: ID
+    name: String
+    coop: Coop
+}
+type Query {
+    getFarms(name: String, at: Int): [Farm]
+}
+"#;
+
+const QUERY: &str =
+    r#"{ getFarms(name: "Farm") { name coops { name hens { name coop { name } } } } }"#;
+
+/// Counts every call that reaches the wrapped searcher.
+struct CountingSearcher {
+    inner: SqliteSearcher,
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Searcher for CountingSearcher {
+    async fn find(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Option<Stash>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.find(template, args, creds, at).await
+    }
+
+    async fn find_all(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<Vec<Stash>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.find_all(template, args, creds, at).await
+    }
+
+    async fn count(&self, template: &str, args: &Stash, creds: &[String], at: i64) -> Result<u64> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.count(template, args, creds, at).await
+    }
+
+    async fn exists(
+        &self,
+        template: &str,
+        args: &Stash,
+        creds: &[String],
+        at: i64,
+    ) -> Result<bool> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        self.inner.exists(template, args, creds, at).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.inner.ping().await
+    }
+}
+
+async fn make_store(calls: &Arc<AtomicUsize>) -> (SqliteRepository, Arc<dyn Searcher>) {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(
+            SqliteConnectOptions::from_str("sqlite::memory:")
+                .unwrap()
+                .create_if_missing(true),
+        )
+        .await
+        .unwrap();
+    let repo = SqliteRepository::new_with_pool(pool.clone()).await.unwrap();
+    let searcher = CountingSearcher {
+        inner: SqliteSearcher::new_with_pool(pool).await.unwrap(),
+        calls: Arc::clone(calls),
+    };
+    (repo, Arc::new(searcher))
+}
+
+async fn create(repo: &SqliteRepository, id: String, payload: serde_json::Value) {
+    let payload = payload.as_object().unwrap().clone();
+    let star = vec!["*".to_string()];
+    repo.create(Envelope::new(id, payload, star.clone()), &star)
+        .await
+        .unwrap();
+}
+
+/// 3 farms × 3 coops × 3 hens, each hen resolving back to its coop.
+async fn build_farm_schema(calls: &Arc<AtomicUsize>) -> async_graphql::dynamic::Schema {
+    let (farm_repo, farm_searcher) = make_store(calls).await;
+    let (coop_repo, coop_searcher) = make_store(calls).await;
+    let (hen_repo, hen_searcher) = make_store(calls).await;
+
+    for f in 0..3 {
+        let farm_id = format!("farm-{f}");
+        create(&farm_repo, farm_id.clone(), json!({"name": "Farm"})).await;
+        for c in 0..3 {
+            let coop_id = format!("{farm_id}-coop-{c}");
+            create(
+                &coop_repo,
+                coop_id.clone(),
+                json!({"name": coop_id, "farmId": farm_id}),
+            )
+            .await;
+            for h in 0..3 {
+                let hen_id = format!("{coop_id}-hen-{h}");
+                create(
+                    &hen_repo,
+                    hen_id.clone(),
+                    json!({"name": hen_id, "coopId": coop_id}),
+                )
+                .await;
+            }
+        }
+    }
+
+    let farm_config = RootConfig::builder()
+        .vector("getFarms", r#"{"payload.name": "{{name}}"}"#)
+        .vector_resolver("coops", None, "getCoopsByFarm", "/coop/graph")
+        .build();
+    let coop_config = RootConfig::builder()
+        .singleton("getCoop", r#"{"id": "{{id}}"}"#)
+        .vector("getCoopsByFarm", r#"{"payload.farmId": "{{id}}"}"#)
+        .vector_resolver("hens", None, "getHensByCoop", "/hen/graph")
+        .build();
+    let hen_config = RootConfig::builder()
+        .vector("getHensByCoop", r#"{"payload.coopId": "{{id}}"}"#)
+        .singleton_resolver("coop", Some("coopId"), "getCoop", "/coop/graph")
+        .build();
+
+    let mut registry = ResolverRegistry::new();
+    registry.register(
+        "/farm/graph",
+        Arc::clone(&farm_searcher),
+        farm_config.clone(),
+    );
+    registry.register("/coop/graph", coop_searcher, coop_config);
+    registry.register("/hen/graph", hen_searcher, hen_config);
+
+    build_schema(FARM_GRAPHQL, &farm_config, farm_searcher, &registry).unwrap()
+}
+
+fn assert_farm_graph(data: serde_json::Value) {
+    let farms = data["getFarms"].as_array().unwrap();
+    assert_eq!(farms.len(), 3);
+    for farm in farms {
+        let coops = farm["coops"].as_array().unwrap();
+        assert_eq!(coops.len(), 3);
+        for coop in coops {
+            let hens = coop["hens"].as_array().unwrap();
+            assert_eq!(hens.len(), 3);
+            for hen in hens {
+                assert_eq!(hen["coop"]["name"], coop["name"]);
+                assert!(hen["name"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with(coop["name"].as_str().unwrap()));
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn batched_request_makes_one_searcher_call_per_level() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let schema = build_farm_schema(&calls).await;
+
+    let response = schema
+        .execute(Request::new(QUERY).data(BatchLoader::new()))
+        .await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_farm_graph(response.data.into_json().unwrap());
+
+    // getFarms, coops, hens, coop: one call per level of the query.
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn unbatched_request_makes_one_searcher_call_per_node() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let schema = build_farm_schema(&calls).await;
+
+    let response = schema.execute(QUERY).await;
+    assert!(response.errors.is_empty(), "{:?}", response.errors);
+    assert_farm_graph(response.data.into_json().unwrap());
+
+    // 1 root + 3 farms + 9 coops + 27 hens.
+    assert_eq!(calls.load(Ordering::SeqCst), 40);
+}
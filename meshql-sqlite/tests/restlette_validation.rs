@@ -0,0 +1,115 @@
+use meshql_core::{DeleteMode, RestletteConfig, ServerConfig};
+use meshql_server::build_app;
+use meshql_sqlite::SqliteRepository;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Serve one restlette at `/hen/api` whose bodies must match `schema_json`.
+async fn serve(schema_json: serde_json::Value) -> String {
+    let repository = SqliteRepository::new("sqlite::memory:").await.unwrap();
+    let config = ServerConfig {
+        port: 0,
+        graphlettes: vec![],
+        restlettes: vec![RestletteConfig {
+            path: "/hen/api".to_string(),
+            schema_json,
+            repository: Arc::new(repository),
+            delete_mode: DeleteMode::Soft,
+        }],
+        cors: None,
+        max_body_bytes: None,
+    };
+    let app = build_app(config).await.unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{addr}/hen/api")
+}
+
+fn hen_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "required": ["name", "eggs"],
+        "properties": {
+            "name": {"type": "string"},
+            "eggs": {"type": "integer", "minimum": 0}
+        }
+    })
+}
+
+#[tokio::test]
+async fn rejects_a_post_missing_a_required_field() {
+    let url = serve(hen_schema()).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(&url)
+        .json(&json!({"name": "Henrietta"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let errors = body["errors"].as_array().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0]["message"].as_str().unwrap().contains("eggs"),
+        "{body}"
+    );
+
+    let listed: Vec<serde_json::Value> =
+        client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert!(listed.is_empty());
+}
+
+#[tokio::test]
+async fn rejects_a_put_that_breaks_the_schema() {
+    let url = serve(hen_schema()).await;
+    let client = reqwest::Client::new();
+
+    let created = client
+        .post(&url)
+        .json(&json!({"name": "Henrietta", "eggs": 3}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), StatusCode::CREATED);
+    let created: serde_json::Value = created.json().await.unwrap();
+    let id = created["id"].as_str().unwrap();
+
+    let response = client
+        .put(format!("{url}/{id}"))
+        .json(&json!({"eggs": -1}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["errors"][0]["path"], json!("/eggs"));
+
+    let read: serde_json::Value = client
+        .get(format!("{url}/{id}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(read["eggs"], json!(3));
+}
+
+#[tokio::test]
+async fn empty_schema_accepts_anything() {
+    let url = serve(json!({})).await;
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&json!({"anything": ["goes"]}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
@@ -50,6 +50,24 @@ async fn should_find_all_by_type_and_name() {
     cert::test_searcher_find_all_by_type_and_name(&searcher).await;
 }
 
+#[tokio::test]
+async fn should_find_all_in_list() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_in_list(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_by_comparison() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_comparisons(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_find_all_in_or_groups() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_find_all_or_groups(&searcher).await;
+}
+
 #[tokio::test]
 async fn should_return_empty_for_nonexistent_type() {
     let (_repo, searcher) = create_searcher().await;
@@ -67,3 +85,57 @@ async fn should_handle_empty_query() {
     let (_repo, searcher) = create_searcher().await;
     cert::test_searcher_empty_query(&searcher).await;
 }
+
+#[tokio::test]
+async fn should_count_matches() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_count(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_check_existence() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_exists(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_page_with_limit_and_offset() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_pages_with_limit_and_offset(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_sort_by_count_desc() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_sorts_by_count_desc(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_collapse_distinct_values() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_collapses_distinct_values(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_project_requested_fields() {
+    let (_repo, searcher) = create_searcher().await;
+    cert::test_searcher_projects_requested_fields(&searcher).await;
+}
+
+#[tokio::test]
+async fn should_stream_large_results() {
+    let (repo, searcher) = create_searcher().await;
+    cert::test_searcher_streams_large_results(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_share_a_pool_with_the_repository() {
+    let (repo, searcher) = create_searcher().await;
+    cert::test_searcher_sees_created_envelope(&repo, &searcher).await;
+}
+
+#[tokio::test]
+async fn should_filter_by_creation_window() {
+    let (repo, searcher) = create_searcher().await;
+    cert::test_searcher_filters_by_creation_window(&repo, &searcher).await;
+}